@@ -1,10 +1,35 @@
-use crate::common_types::{DlpRequestLimit, GcpProjectId, GcpRegion};
+use crate::commands::{
+    AggregateOutput, CopyCollisionMode, CopyExitCodePolicy, CopyFileOrder, Generalization,
+    LsOutputFormat, LsSortBy,
+};
+use crate::common_types::{
+    AudioLanguageCode, DlpRequestLimit, DlpVolumeLimit, GcpProjectId, GcpPubSubSubscription,
+    GcpRegion, ImageRedactionColor, RedactLanguage,
+};
 use crate::errors::AppError;
+use crate::file_converters::ocr::{OcrEngineType, OcrOptions};
+use crate::file_systems::{FileSystemOptions, KafkaOffsetReset};
+use crate::file_tools::{FileFilters, FileIgnorePatterns};
+use crate::logging::{LogFormat, LogRedactedContent};
+use crate::metrics::MetricsExportOptions;
+use crate::notifications::{NotifyFormat, NotifyOptions};
 use crate::redacters::{
-    GcpDlpRedacterOptions, GcpVertexAiModelName, GeminiLlmModelName, OpenAiLlmApiKey,
-    OpenAiModelName, RedacterBaseOptions, RedacterOptions, RedacterProviderOptions,
+    AudioRedactionOutput, AudioTranscriberOptions, AudioTranscriptionProvider,
+    AwsComprehendRedacter, DictionaryRedacter, EntityMappings, FaceRedacterOptions,
+    GcpDlpKmsWrappedKey, GcpDlpRedacterOptions, GcpDlpTransformation, GcpVertexAiBatchOptions,
+    GcpVertexAiModelName, GeminiLlmModelName, ImageRedactionOptions, ImageRedactionStyle,
+    IpAnonymization, LogFileFormat, LogRedactionOptions, MacAnonymization, MsPresidioRedacter,
+    NetworkIdHashKey, NetworkIdOptions, OpenAiLlmApiKey, OpenAiModelName, ProviderIo,
+    ProviderIoMode, RedactEntityType, RedactEntityTypeFilter, RedactPolicy, RedactVerifyAction,
+    RedacterBaseOptions, RedacterOptions, RedacterProviderOptions, RedacterVerifyOptions,
+    RedactionCacheOptions, RedactionProfile, SamplingStrategy,
 };
+use crate::secrets::{is_secret_ref, read_secret_file, resolve_secret};
+use crate::AppResult;
+use base64::Engine;
+use chrono::{DateTime, Utc};
 use clap::*;
+use rvstruct::ValueStruct;
 use std::fmt::Display;
 use std::path::PathBuf;
 use url::Url;
@@ -14,17 +39,65 @@ use url::Url;
 pub struct CliArgs {
     #[command(subcommand)]
     pub command: CliCommand,
+
+    #[arg(
+        long,
+        global = true,
+        display_order = 1000,
+        value_enum,
+        default_value_t = LogFormat::Text,
+        help = "Log format. JSON logs are written to stderr with per-file and per-provider call fields, so they aren't mixed with the console output. Text logs are written to stderr if enabled by RUST_LOG"
+    )]
+    pub log_format: LogFormat,
+
+    #[arg(
+        short = 'q',
+        long,
+        global = true,
+        display_order = 1001,
+        help = "Report only errors. Progress bars and colored output are disabled"
+    )]
+    pub quiet: bool,
+
+    #[arg(
+        long,
+        global = true,
+        display_order = 1002,
+        help = "Disable progress bars and colored output. Disabled automatically if the output isn't a terminal"
+    )]
+    pub no_progress: bool,
+
+    #[arg(
+        short = 'v',
+        long,
+        global = true,
+        display_order = 1003,
+        action = ArgAction::Count,
+        help = "Enable debug logs (-v) or trace logs (-vv) unless RUST_LOG is specified. Text logs are written to stderr"
+    )]
+    pub verbose: u8,
+
+    #[arg(
+        long,
+        global = true,
+        display_order = 1004,
+        value_enum,
+        default_value_t = LogRedactedContent::Never,
+        help = "Log contents of provider responses, which are sensitive themselves: never, only responses failed to be processed, or always (with -v)"
+    )]
+    pub log_redacted_content: LogRedactedContent,
 }
 
 #[derive(Subcommand, Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum CliCommand {
-    #[command(about = "Copy and redact files from source to destination")]
+    #[command(about = "Copy and redact files from sources to destination")]
     Cp {
         #[arg(
-            help = "Source directory or file such as /tmp, /tmp/file.txt or gs://bucket/file.txt and others supported providers"
+            required = true,
+            help = "Source directory or file such as /tmp, /tmp/file.txt or gs://bucket/file.txt and others supported providers. Multiple source directories are merged into the destination"
         )]
-        source: String,
+        sources: Vec<String>,
         #[arg(
             help = "Destination directory or file such as /tmp, /tmp/file.txt or gs://bucket/file.txt and others supported providers"
         )]
@@ -40,200 +113,1528 @@ pub enum CliCommand {
         )]
         max_files_limit: Option<usize>,
 
-        #[arg(
-            short = 'f',
-            long,
-            help = "Filter by name using glob patterns such as *.txt"
-        )]
-        filename_filter: Option<globset::Glob>,
+        #[arg(
+            long,
+            help = "Stop copying gracefully before the total size of processed files exceeds the specified number of bytes"
+        )]
+        max_bytes_per_run: Option<u64>,
+
+        #[arg(
+            long,
+            help = "Stop copying gracefully after the specified number of redacter API calls (including verification, OCR and transcription)"
+        )]
+        max_api_calls_per_run: Option<u64>,
+
+        #[arg(
+            long,
+            help = "Retry files failed due to errors (such as transient network or API errors) up to the specified number of times after copying the rest of the files",
+            default_value = "0"
+        )]
+        file_retries: usize,
+
+        #[arg(
+            long,
+            help = "Download up to the specified number of next files while the current file is redacted and uploaded. Set to 0 to download files one by one",
+            default_value = "1"
+        )]
+        prefetch_files: usize,
+
+        #[arg(
+            long,
+            help = "Memory in bytes for content of prefetched files waiting to be redacted. Content beyond it is kept in temporary work files, and files larger than it are not prefetched. Files are still read into memory to be redacted one at a time",
+            default_value = "268435456"
+        )]
+        memory_budget: usize,
+
+        #[command(flatten)]
+        file_filter_args: FileFilterArgs,
+
+        #[command(flatten)]
+        redacter_args: Option<RedacterArgs>,
+
+        #[command(flatten)]
+        file_system_args: FileSystemArgs,
+
+        #[arg(long, help = "Override media type detection using glob patterns such as 'text/plain=*.md'", value_parser = CliCommand::parse_key_val::<mime::Mime, globset::Glob>)]
+        mime_override: Vec<(mime::Mime, globset::Glob)>,
+
+        #[arg(
+            long,
+            help = "Save redacted results in JSON format to the specified file"
+        )]
+        save_json_results: Option<PathBuf>,
+
+        #[arg(
+            long,
+            help = "Save a report of copied files in HTML format to the specified file with redactions per redacter, skip reasons, errors and thumbnails of redacted images"
+        )]
+        report_html: Option<PathBuf>,
+
+        #[arg(
+            long,
+            help = "Copy only files that are newer than the existing files in the destination",
+            default_value = "false"
+        )]
+        if_newer: bool,
+
+        #[arg(
+            long,
+            help = "Copy only files with a different checksum (or size and modification time if checksums are not available) than the sources of the existing files in the destination",
+            default_value = "false"
+        )]
+        if_changed: bool,
+
+        #[arg(
+            long,
+            help = "Redact files again even if they are marked as redacted by previous runs with x-redacter metadata",
+            default_value = "false"
+        )]
+        force: bool,
+
+        #[arg(
+            long,
+            help = "Copy original files skipped due to unsupported media types to the quarantine location (such as /tmp/quarantine/ or gs://bucket/quarantine/) with a reason record"
+        )]
+        quarantine: Option<String>,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = CopyCollisionMode::Fail,
+            help = "What to do with files having the same paths in multiple sources: fail before copying, skip files of later sources, overwrite them with files of later sources or rename them with the source number appended"
+        )]
+        on_collision: CopyCollisionMode,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = CopyCollisionMode::Overwrite,
+            help = "What to do with files already existing in the destination: overwrite them, skip them, fail them or rename copied files with a number appended"
+        )]
+        on_conflict: CopyCollisionMode,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = CopyFileOrder::Listed,
+            help = "Order of copying files: as listed by sources, smallest or largest files first, or files grouped by media types"
+        )]
+        order: CopyFileOrder,
+
+        #[arg(
+            long,
+            help = "Redact identical files (detected by sizes and content hashes) separately instead of redacting their content once for all of them",
+            default_value = "false"
+        )]
+        no_dedup: bool,
+
+        #[arg(
+            long,
+            help = "Preserve metadata of source files such as local permissions and modification time, S3 user metadata and tags, and GCS custom metadata",
+            default_value = "false"
+        )]
+        preserve_metadata: bool,
+
+        #[arg(
+            long,
+            requires = "preserve_metadata",
+            help = "Metadata keys or tags to exclude from preserved metadata such as author. Use 'modified-at' and 'permissions' to exclude local file attributes. Can be specified multiple times"
+        )]
+        scrub_metadata: Vec<String>,
+
+        #[arg(
+            long,
+            conflicts_with_all = ["if_newer", "if_changed"],
+            help = "Redact files in place when the destination is the same as the source directory. Redacted files are uploaded next to the originals first and replace them only after successful redaction",
+            default_value = "false"
+        )]
+        in_place: bool,
+
+        #[arg(
+            long,
+            requires = "in_place",
+            help = "Keep original files replaced in place as backups with the suffix appended to their names such as '.orig'"
+        )]
+        backup_suffix: Option<String>,
+
+        #[arg(
+            long,
+            help = "Review each redacted file before uploading: accept it, skip it or retry the redaction with another redacter. Requires a terminal",
+            default_value = "false"
+        )]
+        interactive: bool,
+
+        #[arg(
+            long,
+            help = "Redact file names of copied files using the redacters supporting text and rename destination files accordingly. Original and redacted names are recorded in logs and JSON results",
+            default_value = "false"
+        )]
+        redact_file_names: bool,
+
+        #[command(flatten)]
+        metrics_args: MetricsArgs,
+
+        #[command(flatten)]
+        notify_args: NotifyArgs,
+
+        #[command(flatten)]
+        exit_code_args: ExitCodeArgs,
+    },
+    #[command(about = "Watch the source and copy/redact new and changed files to destination")]
+    Watch {
+        #[arg(
+            help = "Source directory such as /tmp/ or gs://bucket/dir/ and others supported providers"
+        )]
+        source: String,
+        #[arg(
+            help = "Destination directory such as /tmp/ or gs://bucket/dir/ and others supported providers"
+        )]
+        destination: String,
+
+        #[arg(short = 'm', long, help = "Maximum size of files to copy in bytes")]
+        max_size_limit: Option<usize>,
+
+        #[command(flatten)]
+        file_filter_args: FileFilterArgs,
+
+        #[command(flatten)]
+        redacter_args: Option<RedacterArgs>,
+
+        #[command(flatten)]
+        file_system_args: FileSystemArgs,
+
+        #[arg(long, help = "Override media type detection using glob patterns such as 'text/plain=*.md'", value_parser = CliCommand::parse_key_val::<mime::Mime, globset::Glob>)]
+        mime_override: Vec<(mime::Mime, globset::Glob)>,
+
+        #[arg(
+            long,
+            help = "Interval in seconds to poll non-local sources such as buckets for changes",
+            default_value = "30"
+        )]
+        poll_interval: u64,
+
+        #[arg(
+            long,
+            help = "Time in milliseconds to wait for local files to stop changing before copying them",
+            default_value = "1000"
+        )]
+        debounce: u64,
+
+        #[command(flatten)]
+        metrics_args: MetricsArgs,
+    },
+    #[command(about = "List files in the source")]
+    Ls {
+        #[arg(
+            required = true,
+            help = "Source directories or files such as /tmp, /tmp/file.txt or gs://bucket/file.txt and others supported providers"
+        )]
+        sources: Vec<String>,
+        #[arg(short = 'm', long, help = "Maximum size of files to copy in bytes")]
+        max_size_limit: Option<usize>,
+        #[command(flatten)]
+        file_filter_args: FileFilterArgs,
+        #[arg(
+            short = 's',
+            long = "short",
+            help = "Use a short listing format with file names only instead of the table with sizes, media types and modification time"
+        )]
+        short_format: bool,
+        /// The long listing format is the default, the flag is kept for existing scripts
+        #[arg(
+            short = 'l',
+            long = "long",
+            hide = true,
+            conflicts_with = "short_format"
+        )]
+        long_format: bool,
+        #[arg(
+            long = "sort",
+            value_enum,
+            help = "Sort files by name, size or modification time"
+        )]
+        sort_by: Option<LsSortBy>,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = LsOutputFormat::Text,
+            help = "Output format. JSON output includes the support of every file by redacters if redacters are specified"
+        )]
+        output: LsOutputFormat,
+
+        #[command(flatten)]
+        redacter_args: Option<RedacterArgs>,
+
+        #[command(flatten)]
+        connection_args: StorageConnectionArgs,
+    },
+    #[command(
+        about = "Estimate API calls and costs of redacting files in the source using detection on sampled files"
+    )]
+    Estimate {
+        #[arg(
+            help = "Source directory or file such as /tmp, /tmp/file.txt or gs://bucket/file.txt and others supported providers"
+        )]
+        source: String,
+
+        #[arg(short = 'm', long, help = "Maximum size of files to copy in bytes")]
+        max_size_limit: Option<usize>,
+
+        #[arg(
+            short = 'n',
+            long,
+            help = "Maximum number of files to copy. Sort order is not guaranteed and depends on the provider"
+        )]
+        max_files_limit: Option<usize>,
+
+        #[command(flatten)]
+        file_filter_args: FileFilterArgs,
+
+        #[arg(
+            long,
+            help = "Fraction of text files (from 0.0 to 1.0) to sample and run detection on",
+            default_value = "0.1",
+            value_parser = CliCommand::parse_fraction
+        )]
+        sample_fraction: f64,
+
+        #[arg(
+            long,
+            help = "Number of bytes to read from the beginning of every sampled file",
+            default_value = "4096"
+        )]
+        sample_bytes: usize,
+
+        #[arg(long, help = "Price in USD per GB of content for a redacter such as 'gcp-dlp=3.0' instead of the approximate list price", value_parser = CliCommand::parse_price)]
+        price: Vec<(RedacterType, f64)>,
+
+        #[command(flatten)]
+        redacter_args: Option<RedacterArgs>,
+
+        #[arg(long, help = "Override media type detection using glob patterns such as 'text/plain=*.md'", value_parser = CliCommand::parse_key_val::<mime::Mime, globset::Glob>)]
+        mime_override: Vec<(mime::Mime, globset::Glob)>,
+
+        #[command(flatten)]
+        connection_args: StorageConnectionArgs,
+    },
+    #[command(
+        about = "Compare original and redacted files or directories highlighting redacted text spans and image regions"
+    )]
+    Diff {
+        #[arg(
+            help = "Original directory or file such as /tmp, /tmp/file.txt or gs://bucket/file.txt and others supported providers"
+        )]
+        original: String,
+
+        #[arg(
+            help = "Redacted directory or file such as /tmp/redacted, /tmp/redacted.txt or gs://bucket/redacted/ and others supported providers"
+        )]
+        redacted: String,
+
+        #[arg(short = 'm', long, help = "Maximum size of files to compare in bytes")]
+        max_size_limit: Option<usize>,
+
+        #[command(flatten)]
+        file_filter_args: FileFilterArgs,
+
+        #[arg(long, help = "Override media type detection using glob patterns such as 'text/plain=*.md'", value_parser = CliCommand::parse_key_val::<mime::Mime, globset::Glob>)]
+        mime_override: Vec<(mime::Mime, globset::Glob)>,
+
+        #[arg(
+            long,
+            help = "Save an HTML report with highlighted text changes and side-by-side thumbnails of images and PDF pages with redacted regions"
+        )]
+        html_report: Option<PathBuf>,
+
+        #[command(flatten)]
+        connection_args: StorageConnectionArgs,
+    },
+    #[command(
+        about = "Produce k-anonymized or aggregated CSV files for analytics sharing by generalizing quasi-identifiers and suppressing small groups"
+    )]
+    Aggregate {
+        #[arg(
+            help = "Source directory or CSV file such as /tmp, /tmp/file.csv or gs://bucket/file.csv and others supported providers"
+        )]
+        source: String,
+
+        #[arg(
+            help = "Destination directory or file such as /tmp/aggregated, /tmp/aggregated.csv or gs://bucket/aggregated/ and others supported providers"
+        )]
+        destination: String,
+
+        #[arg(
+            short = 'k',
+            long,
+            help = "Minimum number of rows with the same quasi-identifiers. Smaller groups are suppressed",
+            default_value = "5",
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        k: u64,
+
+        #[arg(
+            long,
+            help = "Column identifying people in combination with other columns, such as age or ZIP code. Columns without roles are removed"
+        )]
+        quasi_identifier: Vec<String>,
+
+        #[arg(
+            long,
+            help = "Column with sensitive attributes such as diagnoses. Summarized in counts outputs"
+        )]
+        sensitive: Vec<String>,
+
+        #[arg(long, help = "Column kept as is in k-anonymized outputs")]
+        insensitive: Vec<String>,
+
+        #[arg(long, help = "Generalization of a quasi-identifier such as 'age=range:10', 'zip=prefix:3', 'visit_date=month' (or year) and 'gender=suppress'", value_parser = CliCommand::parse_generalization)]
+        generalize: Vec<(String, Generalization)>,
+
+        #[arg(
+            long,
+            value_enum,
+            help = "Output of k-anonymized rows or counts of rows by groups",
+            default_value = "k-anonymized"
+        )]
+        output: AggregateOutput,
+
+        #[arg(
+            long,
+            help = "Add Laplace noise with the privacy budget epsilon to counts, such as 1.0. Lower values add more noise"
+        )]
+        epsilon: Option<f64>,
+
+        #[arg(
+            long,
+            help = "CSV delimiter. Default is detected automatically (',', tab, ';' or '|')"
+        )]
+        csv_delimiter: Option<char>,
+
+        #[arg(
+            short = 'm',
+            long,
+            help = "Maximum size of files to aggregate in bytes"
+        )]
+        max_size_limit: Option<usize>,
+
+        #[command(flatten)]
+        file_filter_args: FileFilterArgs,
+
+        #[arg(long, help = "Override media type detection using glob patterns such as 'text/csv=*.txt'", value_parser = CliCommand::parse_key_val::<mime::Mime, globset::Glob>)]
+        mime_override: Vec<(mime::Mime, globset::Glob)>,
+
+        #[command(flatten)]
+        connection_args: StorageConnectionArgs,
+    },
+    #[command(
+        about = "Check credentials, settings, endpoints and models of redacters with tiny requests before running jobs"
+    )]
+    Check {
+        #[command(flatten)]
+        redacter_args: Option<RedacterArgs>,
+    },
+    #[command(about = "Manage GCP DLP resources used by the gcp-dlp redacter")]
+    Dlp {
+        #[command(subcommand)]
+        command: DlpCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DlpCommand {
+    #[command(
+        about = "Create a stored infoType from a dictionary file or a regular expression to use it with --gcp-dlp-stored-info-type"
+    )]
+    CreateStoredInfotype {
+        #[arg(help = "Stored infoType ID such as project-names")]
+        stored_info_type_id: String,
+
+        #[arg(long, help = "GCP project id where the stored infoType is created")]
+        gcp_project_id: GcpProjectId,
+
+        #[arg(
+            long,
+            help = "Dictionary file with a term per line such as terms.txt or gs://bucket/terms.txt. Lines starting with # are ignored in local files",
+            required_unless_present = "regex",
+            conflicts_with = "regex"
+        )]
+        dictionary_file: Option<String>,
+
+        #[arg(long, help = "Regular expression (RE2 syntax) to detect")]
+        regex: Option<String>,
+
+        #[arg(long, help = "Display name of the stored infoType. Default is the ID")]
+        display_name: Option<String>,
+
+        #[arg(long, help = "Description of the stored infoType")]
+        description: Option<String>,
+    },
+}
+
+impl CliCommand {
+    fn parse_key_val<T, U>(
+        s: &str,
+    ) -> Result<(T, U), Box<dyn std::error::Error + Send + Sync + 'static>>
+    where
+        T: std::str::FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+        U: std::str::FromStr,
+        U::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let pos = s
+            .find('=')
+            .ok_or_else(|| format!("invalid KEY=value: no `=` found in `{s}`"))?;
+        Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
+    }
+
+    fn parse_price(s: &str) -> Result<(RedacterType, f64), String> {
+        let (redacter_type, price) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid REDACTER=price: no `=` found in `{s}`"))?;
+        Ok((
+            <RedacterType as ValueEnum>::from_str(redacter_type, true)?,
+            price
+                .parse()
+                .map_err(|err| format!("invalid price `{price}`: {err}"))?,
+        ))
+    }
+
+    fn parse_generalization(s: &str) -> Result<(String, Generalization), String> {
+        let (column, generalization) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid COLUMN=generalization: no `=` found in `{s}`"))?;
+        Ok((column.to_string(), Generalization::parse(generalization)?))
+    }
+
+    fn parse_fraction(s: &str) -> Result<f64, String> {
+        match s.parse::<f64>() {
+            Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+            _ => Err(format!("`{s}` is not a number from 0.0 to 1.0")),
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, PartialEq, Eq)]
+pub enum RedacterType {
+    GcpDlp,
+    AwsComprehend,
+    MsPresidio,
+    GeminiLlm,
+    OpenAiLlm,
+    GcpVertexAi,
+    External,
+    Dictionary,
+    Secrets,
+    /// Deterministic redacter for tests and dry-runs without external dependencies
+    #[value(hide = true)]
+    Mock,
+}
+
+impl RedacterType {
+    /// Only providers reporting detected entities can verify redacted content and estimate findings
+    pub fn supports_detection(&self) -> bool {
+        matches!(
+            self,
+            RedacterType::GcpDlp | RedacterType::AwsComprehend | RedacterType::MsPresidio
+        )
+    }
+}
+
+impl std::str::FromStr for RedacterType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gcp-dlp" => Ok(RedacterType::GcpDlp),
+            "aws-comprehend" => Ok(RedacterType::AwsComprehend),
+            "ms-presidio" => Ok(RedacterType::MsPresidio),
+            "gemini-llm" => Ok(RedacterType::GeminiLlm),
+            "external" => Ok(RedacterType::External),
+            "dictionary" => Ok(RedacterType::Dictionary),
+            "secrets" => Ok(RedacterType::Secrets),
+            "mock" => Ok(RedacterType::Mock),
+            _ => Err(format!("Unknown redacter type: {}", s)),
+        }
+    }
+}
+
+impl Display for RedacterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RedacterType::GcpDlp => write!(f, "gcp-dlp"),
+            RedacterType::AwsComprehend => write!(f, "aws-comprehend"),
+            RedacterType::MsPresidio => write!(f, "ms-presidio"),
+            RedacterType::GeminiLlm => write!(f, "gemini-llm"),
+            RedacterType::OpenAiLlm => write!(f, "openai-llm"),
+            RedacterType::GcpVertexAi => write!(f, "gcp-vertex-ai"),
+            RedacterType::External => write!(f, "external"),
+            RedacterType::Dictionary => write!(f, "dictionary"),
+            RedacterType::Secrets => write!(f, "secrets"),
+            RedacterType::Mock => write!(f, "mock"),
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct FileSystemArgs {
+    #[arg(
+        long,
+        help = "Pub/Sub subscription with GCS notifications (projects/<project>/subscriptions/<subscription>) to receive only new files from GCS sources instead of listing buckets"
+    )]
+    pub gcs_pubsub_subscription: Option<GcpPubSubSubscription>,
+
+    #[arg(
+        long,
+        help = "Part size in MiB for multipart uploads to S3 and resumable uploads to GCS. Default is 8 MiB",
+        value_parser = clap::value_parser!(u64).range(5..)
+    )]
+    pub upload_part_size: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Maximum number of messages received from Kafka topics in one batch. Default is 100"
+    )]
+    pub kafka_batch_size: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Kafka consumer group to track consumed offsets. Default is 'redacter'"
+    )]
+    pub kafka_group_id: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Where to start consuming Kafka topics without committed offsets for the consumer group. Default is earliest"
+    )]
+    pub kafka_offset_reset: Option<KafkaOffsetReset>,
+
+    #[arg(
+        long,
+        help = "Number of rows in every CSV file exported from PostgreSQL sources. Default is 1000"
+    )]
+    pub postgres_batch_size: Option<usize>,
+
+    #[arg(
+        long,
+        env = "REDACTER_IMAP_PASSWORD",
+        hide_env_values = true,
+        help = "Password for IMAP sources without passwords in their URLs. Secret manager references are supported"
+    )]
+    pub imap_password: Option<String>,
+
+    #[arg(
+        long,
+        env = "REDACTER_IMAP_OAUTH_TOKEN",
+        hide_env_values = true,
+        help = "OAuth 2.0 access token to authenticate IMAP sources with XOAUTH2 instead of passwords, such as for Gmail"
+    )]
+    pub imap_oauth_token: Option<String>,
+
+    #[arg(
+        long,
+        value_parser = ["AES256", "aws:kms", "aws:kms:dsse"],
+        help = "Server-side encryption for uploads to S3"
+    )]
+    pub s3_sse: Option<String>,
+
+    #[arg(
+        long,
+        requires = "s3_sse",
+        help = "KMS key ID or ARN for uploads to S3 encrypted with aws:kms"
+    )]
+    pub s3_kms_key_id: Option<String>,
+
+    #[arg(
+        long,
+        help = "Storage class for uploads to S3 such as STANDARD_IA, INTELLIGENT_TIERING or GLACIER_IR"
+    )]
+    pub s3_storage_class: Option<String>,
+
+    #[arg(
+        long,
+        help = "Customer-managed Cloud KMS key for uploads to GCS (projects/<project>/locations/<location>/keyRings/<ring>/cryptoKeys/<key>)"
+    )]
+    pub gcs_kms_key: Option<String>,
+
+    #[arg(
+        long,
+        help = "Storage class for uploads to GCS such as NEARLINE, COLDLINE or ARCHIVE"
+    )]
+    pub gcs_storage_class: Option<String>,
+
+    #[arg(
+        long,
+        help = "Add files to an existing zip destination replacing files with the same names instead of failing",
+        default_value = "false"
+    )]
+    pub zip_append: bool,
+
+    #[command(flatten)]
+    pub connection_args: StorageConnectionArgs,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct StorageConnectionArgs {
+    #[arg(
+        long,
+        help = "Custom endpoint URL for S3-compatible storages such as MinIO, Ceph, or Cloudflare R2"
+    )]
+    pub s3_endpoint_url: Option<Url>,
+
+    #[arg(
+        long,
+        help = "Use path-style addressing for S3 (required by most self-hosted S3-compatible storages)",
+        default_value = "false"
+    )]
+    pub s3_force_path_style: bool,
+
+    #[arg(long, help = "AWS profile name to use for S3 credentials and region")]
+    pub s3_profile: Option<String>,
+
+    #[arg(
+        long,
+        help = "Custom endpoint URL for GCS such as fake-gcs-server or private Google access endpoints"
+    )]
+    pub gcs_endpoint_url: Option<Url>,
+
+    #[arg(
+        long,
+        help = "Service account key file (JSON) for GCS instead of Application Default Credentials"
+    )]
+    pub gcs_service_account_key_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Follow symbolic links in local directories. By default symbolic links are skipped",
+        default_value = "false"
+    )]
+    pub follow_symlinks: bool,
+
+    #[arg(
+        long,
+        help = "Directory for temporary files such as prefetched files beyond the memory budget instead of the system temp directory. Use a tmpfs mount to keep them off disks",
+        conflicts_with = "work_in_memory"
+    )]
+    pub work_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Keep temporary files such as prefetched files beyond the memory budget in memory instead of writing them to disk",
+        default_value = "false"
+    )]
+    pub work_in_memory: bool,
+
+    #[arg(
+        long,
+        help = "Encrypt temporary files with a random key which exists only in memory of the process",
+        default_value = "false"
+    )]
+    pub encrypt_work_files: bool,
+
+    #[arg(
+        long,
+        help = "Maximum total uncompressed size in bytes of zip archives used as sources. Default is 16 GiB"
+    )]
+    pub zip_max_uncompressed_size: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Maximum compression ratio of entries larger than 1 MiB in zip archives used as sources, to reject zip bombs. Default is 250"
+    )]
+    pub zip_max_compression_ratio: Option<u64>,
+}
+
+impl From<StorageConnectionArgs> for FileSystemOptions {
+    fn from(args: StorageConnectionArgs) -> Self {
+        FileSystemOptions {
+            s3_endpoint_url: args.s3_endpoint_url,
+            s3_force_path_style: args.s3_force_path_style,
+            s3_profile: args.s3_profile,
+            gcs_endpoint_url: args.gcs_endpoint_url,
+            gcs_service_account_key_file: args.gcs_service_account_key_file,
+            follow_symlinks: args.follow_symlinks,
+            work_dir: args.work_dir,
+            work_in_memory: args.work_in_memory,
+            encrypt_work_files: args.encrypt_work_files,
+            zip_max_uncompressed_size: args.zip_max_uncompressed_size,
+            zip_max_compression_ratio: args.zip_max_compression_ratio,
+            ..FileSystemOptions::default()
+        }
+    }
+}
+
+impl From<FileSystemArgs> for FileSystemOptions {
+    fn from(args: FileSystemArgs) -> Self {
+        FileSystemOptions {
+            gcs_pubsub_subscription: args.gcs_pubsub_subscription,
+            upload_part_size: args
+                .upload_part_size
+                .map(|size_mib| size_mib as usize * 1024 * 1024),
+            kafka_batch_size: args.kafka_batch_size,
+            kafka_group_id: args.kafka_group_id,
+            kafka_offset_reset: args.kafka_offset_reset,
+            postgres_batch_size: args.postgres_batch_size,
+            imap_password: args.imap_password,
+            imap_oauth_token: args.imap_oauth_token,
+            s3_server_side_encryption: args.s3_sse,
+            s3_kms_key_id: args.s3_kms_key_id,
+            s3_storage_class: args.s3_storage_class,
+            gcs_kms_key: args.gcs_kms_key,
+            gcs_storage_class: args.gcs_storage_class,
+            zip_append: args.zip_append,
+            ..args.connection_args.into()
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct FileFilterArgs {
+    #[arg(
+        short = 'f',
+        long,
+        help = "Filter by name using glob patterns such as *.txt. Can be specified multiple times to match any of the patterns"
+    )]
+    pub filename_filter: Vec<globset::Glob>,
+
+    #[arg(
+        long,
+        help = "Exclude files by name using glob patterns such as tmp/*. Can be specified multiple times"
+    )]
+    pub exclude: Vec<globset::Glob>,
+
+    #[arg(
+        long,
+        help = "Exclude files using patterns in the gitignore format from the file such as .redacterignore"
+    )]
+    pub ignore_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Filter by media types such as 'image/*,application/pdf'"
+    )]
+    pub mime_filter: Vec<mime::Mime>,
+
+    #[arg(
+        long,
+        value_parser = FileFilterArgs::parse_date_time,
+        help = "Filter files modified after the date (such as 2024-05-01) or time in RFC 3339 format (such as 2024-05-01T12:00:00Z)"
+    )]
+    pub modified_after: Option<DateTime<Utc>>,
+
+    #[arg(
+        long,
+        value_parser = FileFilterArgs::parse_date_time,
+        help = "Filter files modified before the date (such as 2024-05-08) or time in RFC 3339 format"
+    )]
+    pub modified_before: Option<DateTime<Utc>>,
+}
+
+impl FileFilterArgs {
+    /// Dates without time are the beginning of the day in UTC
+    fn parse_date_time(s: &str) -> Result<DateTime<Utc>, String> {
+        DateTime::parse_from_rfc3339(s)
+            .map(|date_time| date_time.with_timezone(&Utc))
+            .or_else(|_| {
+                chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                    .map(|date| date.and_time(chrono::NaiveTime::MIN).and_utc())
+            })
+            .map_err(|_| format!("invalid date or time `{s}`: expected YYYY-MM-DD or RFC 3339"))
+    }
+}
+
+impl TryInto<FileFilters> for FileFilterArgs {
+    type Error = AppError;
+
+    fn try_into(self) -> Result<FileFilters, Self::Error> {
+        Ok(FileFilters {
+            include: self.filename_filter,
+            exclude: self.exclude,
+            ignore_patterns: self
+                .ignore_file
+                .as_deref()
+                .map(FileIgnorePatterns::from_file)
+                .transpose()?,
+            media_types: self.mime_filter,
+            modified_after: self.modified_after,
+            modified_before: self.modified_before,
+        })
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct MetricsArgs {
+    #[arg(
+        long,
+        help = "Write Prometheus metrics (files, bytes, redactions, provider API calls, retries, failures and durations) to the file, such as node_exporter textfile collector directory"
+    )]
+    pub metrics_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Push Prometheus metrics to the Pushgateway URL such as http://localhost:9091"
+    )]
+    pub metrics_push_gateway: Option<Url>,
+}
+
+impl From<MetricsArgs> for MetricsExportOptions {
+    fn from(args: MetricsArgs) -> Self {
+        MetricsExportOptions {
+            metrics_file: args.metrics_file,
+            metrics_push_gateway: args.metrics_push_gateway,
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct NotifyArgs {
+    #[arg(
+        long,
+        env = "REDACTER_NOTIFY_WEBHOOK",
+        help = "Post a summary of the job with counts of files and the path to JSON results to the webhook URL when the job completes or fails"
+    )]
+    pub notify_webhook: Option<Url>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = NotifyFormat::Json,
+        help = "Payload of notifications: JSON job summaries or Slack-compatible messages for incoming webhooks"
+    )]
+    pub notify_format: NotifyFormat,
+}
+
+impl From<NotifyArgs> for NotifyOptions {
+    fn from(args: NotifyArgs) -> Self {
+        NotifyOptions {
+            notify_webhook: args.notify_webhook,
+            notify_format: args.notify_format,
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ExitCodeArgs {
+    #[arg(
+        long,
+        help = "Exit with code 2 if any files failed to be redacted",
+        default_value = "false"
+    )]
+    pub fail_on_error: bool,
+
+    #[arg(
+        long,
+        help = "Exit with code 2 if any files were skipped (including failed, quarantined and up to date files)",
+        default_value = "false"
+    )]
+    pub fail_on_skipped: bool,
+
+    #[arg(
+        long,
+        help = "Exit with code 3 if no files were copied",
+        default_value = "false"
+    )]
+    pub fail_on_empty: bool,
+}
+
+impl From<ExitCodeArgs> for CopyExitCodePolicy {
+    fn from(args: ExitCodeArgs) -> Self {
+        CopyExitCodePolicy {
+            fail_on_error: args.fail_on_error,
+            fail_on_skipped: args.fail_on_skipped,
+            fail_on_empty: args.fail_on_empty,
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+#[group(required = false)]
+pub struct RedacterArgs {
+    #[arg(short = 'd', long, value_enum, help = "List of redacters to use")]
+    redact: Option<Vec<RedacterType>>,
+
+    #[arg(
+        long,
+        help = "Named redaction profile with redacters, entity types, thresholds and replacement strategies, such as hipaa, pci or gdpr-basic. Options specified explicitly take precedence over the profile"
+    )]
+    pub profile: Option<String>,
+
+    #[arg(
+        long,
+        help = "JSON file with additional redaction profiles by names, which take precedence over the built-in profiles with the same names"
+    )]
+    pub profiles_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "How multiple redacters are applied: all of them one after another, only the first one supporting the file, or the next one only if the previous one fails. Default is 'all'"
+    )]
+    pub redact_policy: Option<RedactPolicy>,
+
+    #[arg(
+        long,
+        help = "Allow unsupported types to be copied without redaction",
+        default_value = "false"
+    )]
+    pub allow_unsupported_copies: bool,
+
+    #[arg(
+        long,
+        help = "GCP project id that will be used to redact and bill API calls"
+    )]
+    pub gcp_project_id: Option<GcpProjectId>,
+
+    #[arg(long, help = "Additional GCP DLP built in info types for redaction")]
+    pub gcp_dlp_built_in_info_type: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        help = "Additional GCP DLP user defined stored info types for redaction"
+    )]
+    pub gcp_dlp_stored_info_type: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Transformation for values found by GCP DLP. 'fpe' and 'deterministic' encrypt values with a KMS wrapped key, so the same input produces the same token across files and runs. Default is 'replace'"
+    )]
+    pub gcp_dlp_transformation: Option<GcpDlpTransformation>,
+
+    #[arg(
+        long,
+        help = "Cloud KMS key name (projects/<project>/locations/<location>/keyRings/<ring>/cryptoKeys/<key>) that wraps the GCP DLP encryption key"
+    )]
+    pub gcp_dlp_kms_key_name: Option<String>,
+
+    #[arg(
+        long,
+        help = "Base64 encoded GCP DLP encryption key wrapped by the Cloud KMS key"
+    )]
+    pub gcp_dlp_wrapped_key: Option<String>,
+
+    #[arg(
+        long,
+        help = "Custom info type to annotate GCP DLP encrypted values with (such as TOKEN(10):...) to allow re-identification in free text"
+    )]
+    pub gcp_dlp_surrogate_info_type: Option<String>,
+
+    #[arg(
+        long,
+        help = "GCP region that will be used to redact and bill API calls for Vertex AI"
+    )]
+    pub gcp_region: Option<GcpRegion>,
+
+    #[arg(
+        long,
+        help = "Vertex AI model supports image editing natively. Default is false."
+    )]
+    pub gcp_vertex_ai_native_image_support: bool,
+
+    #[arg(
+        long,
+        help = "Model name for text redaction in Vertex AI. Default is 'publishers/google/models/gemini-1.5-flash-001'"
+    )]
+    pub gcp_vertex_ai_text_model: Option<GcpVertexAiModelName>,
+
+    #[arg(
+        long,
+        help = "Model name for image redaction in Vertex AI. Default is 'publishers/google/models/gemini-1.5-pro-001'"
+    )]
+    pub gcp_vertex_ai_image_model: Option<GcpVertexAiModelName>,
+
+    #[arg(
+        long,
+        help = "Block none harmful content threshold for Vertex AI redacter. Default is BlockOnlyHigh since BlockNone is required a special billing settings.",
+        default_value = "false"
+    )]
+    pub gcp_vertex_ai_block_none_harmful: bool,
+
+    #[arg(
+        long,
+        help = "Redact text files using Vertex AI batch prediction jobs instead of requests per file. Requires --gcp-vertex-ai-batch-staging",
+        default_value = "false"
+    )]
+    pub gcp_vertex_ai_batch: bool,
+
+    #[arg(
+        long,
+        help = "GCS prefix for inputs and outputs of Vertex AI batch prediction jobs such as 'gs://my-bucket/staging/'"
+    )]
+    pub gcp_vertex_ai_batch_staging: Option<String>,
+
+    #[arg(
+        long,
+        help = "Disable CSV headers (if they are not present)",
+        default_value = "false"
+    )]
+    pub csv_headers_disable: bool,
+
+    #[arg(
+        long,
+        help = "CSV delimiter. Default is detected automatically (',', tab, ';' or '|')"
+    )]
+    pub csv_delimiter: Option<char>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Redact only the specified CSV columns (header names or zero-based indexes without headers). Other columns are not sent to redacters. Default is all columns"
+    )]
+    pub csv_redact_columns: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "CSV columns (header names or zero-based indexes without headers) that should never be sent to redacters such as id,created_at"
+    )]
+    pub csv_skip_columns: Option<Vec<String>>,
+
+    #[arg(long, help = "AWS region for AWS Comprehend DLP redacter")]
+    pub aws_region: Option<String>,
+
+    #[arg(
+        long,
+        env = "MS_PRESIDIO_TEXT_ANALYZE_URL",
+        hide_env_values = true,
+        help = "URL for text analyze endpoint for MsPresidio redacter. Can be a reference to a secret in GCP Secret Manager or AWS Secrets Manager"
+    )]
+    pub ms_presidio_text_analyze_url: Option<Url>,
+
+    #[arg(
+        long,
+        env = "MS_PRESIDIO_IMAGE_REDACT_URL",
+        hide_env_values = true,
+        help = "URL for image redact endpoint for MsPresidio redacter. Can be a reference to a secret in GCP Secret Manager or AWS Secrets Manager"
+    )]
+    pub ms_presidio_image_redact_url: Option<Url>,
+
+    #[arg(
+        long,
+        help = "Minimum confidence score (0.0-1.0) of entities found by MsPresidio redacter"
+    )]
+    pub ms_presidio_score_threshold: Option<f64>,
+
+    #[arg(
+        long,
+        help = "MsPresidio entity types to find such as PERSON or EMAIL_ADDRESS. Can be specified multiple times. All types except US_DRIVER_LICENSE are found by default"
+    )]
+    pub ms_presidio_entity: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Analyze small text files combined into MsPresidio requests up to the specified size in bytes instead of requests per file"
+    )]
+    pub ms_presidio_batch_size: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Command line of the external redacter program such as 'python3 my_redacter.py --strict'. The program receives items as JSON in stdin and responds with redacted content or entity offsets as JSON in stdout"
+    )]
+    pub external_redacter_cmd: Option<String>,
+
+    #[arg(
+        long,
+        help = "External redacter supports images, otherwise images are redacted with OCR. Default is false",
+        default_value = "false"
+    )]
+    pub external_redacter_image_support: bool,
+
+    #[arg(
+        long,
+        help = "File with terms to redact by the dictionary redacter such as employee names or customer IDs, one per line. Lines starting with '#' are ignored"
+    )]
+    pub dictionary_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Match dictionary terms case-insensitively. Default is false",
+        default_value = "false"
+    )]
+    pub dictionary_ignore_case: bool,
+
+    #[arg(
+        long,
+        help = "Match dictionary terms only as whole words, so 'John' doesn't match 'Johnson'. Default is false",
+        default_value = "false"
+    )]
+    pub dictionary_whole_words: bool,
+
+    #[arg(
+        long,
+        help = "Minimum Shannon entropy in bits per character of tokens with letters and digits (20+ characters) to redact them as generic secrets by the secrets redacter. Default is 4.0"
+    )]
+    pub secrets_min_entropy: Option<f64>,
+
+    #[arg(
+        long,
+        hide = true,
+        help = "Regular expressions of texts to replace by the mock redacter for tests and dry-runs"
+    )]
+    pub mock_pattern: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        hide = true,
+        help = "Replace every Nth word by the mock redacter. Default is 5 if no patterns are specified"
+    )]
+    pub mock_every_nth_word: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Gemini model name for Gemini LLM redacter. Default is 'models/gemini-1.5-flash'"
+    )]
+    pub gemini_model: Option<GeminiLlmModelName>,
+
+    #[arg(
+        long,
+        help = "Sampling size in bytes before redacting files. Disabled by default"
+    )]
+    pub sampling_size: Option<usize>,
+
+    #[arg(
+        long,
+        value_enum,
+        requires = "sampling_size",
+        help = "How text samples are taken: characters from the start, chunks at random positions spread across the text, or whole lines evenly spread across the text. Default is 'head'"
+    )]
+    pub sampling_strategy: Option<SamplingStrategy>,
+
+    #[arg(
+        long,
+        requires = "sampling_size",
+        help = "Redact samples of text files first and redact whole files only if sensitive data is found in their samples. Other files are copied as is",
+        default_value = "false"
+    )]
+    pub sample_then_full: bool,
+
+    #[arg(
+        long,
+        help = "Maximum size in bytes of files to redact, since files are redacted in memory. Larger files are skipped (or quarantined) instead of failing the run. Disabled by default"
+    )]
+    pub max_item_size: Option<usize>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Redact log files (text files with syslog, Apache/Nginx access or JSON lines) by fields: IP addresses, emails, users and user agents are redacted locally and only free text such as messages is sent to redacters. Disabled by default"
+    )]
+    pub redact_log_format: Option<LogFileFormat>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Anonymize IP addresses locally before redacting: mask whole addresses, truncate them to networks such as 192.168.1.0/24, or replace them with consistent keyed hashes. Disabled by default, while IP addresses in log files are masked"
+    )]
+    pub network_ip_anonymization: Option<IpAnonymization>,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(0..=32),
+        help = "Prefix length in bits of IPv4 networks kept by the truncate anonymization. Default is 24"
+    )]
+    pub network_ipv4_prefix: Option<u8>,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(0..=128),
+        help = "Prefix length in bits of IPv6 networks kept by the truncate anonymization. Default is 48"
+    )]
+    pub network_ipv6_prefix: Option<u8>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Anonymize MAC addresses locally before redacting: mask whole addresses, keep only vendor prefixes (OUI), or replace them with consistent keyed hashes. Disabled by default, while MAC addresses in log files are masked"
+    )]
+    pub network_mac_anonymization: Option<MacAnonymization>,
+
+    #[arg(
+        long,
+        env = "REDACTER_NETWORK_HASH_KEY",
+        hide_env_values = true,
+        help = "Key for hashes of IP and MAC addresses, so pseudonyms are consistent across runs. Random key for every run by default"
+    )]
+    pub network_hash_key: Option<String>,
+
+    #[arg(
+        long,
+        help = "Replace entities found by aws-comprehend and ms-presidio in texts with surrogates such as PERSON_NAME_1 instead of X characters. The same entity has the same surrogate in all files of a run",
+        default_value = "false"
+    )]
+    pub pseudonymize: bool,
+
+    #[arg(
+        long,
+        requires = "pseudonymize",
+        help = "JSON file to load and save surrogates of entities, so they are consistent across runs. The file contains only hashes of entity values"
+    )]
+    pub pseudonym_mappings_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with = "replay_provider_io",
+        help = "Debug mode to record requests and responses of openai-llm and gemini-llm redacters to the directory. Recordings contain the original content and are readable only by the owner"
+    )]
+    pub record_provider_io: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Debug mode to replay responses recorded with --record-provider-io instead of calling openai-llm and gemini-llm providers, so prompts can be tuned offline"
+    )]
+    pub replay_provider_io: Option<PathBuf>,
 
-        #[command(flatten)]
-        redacter_args: Option<RedacterArgs>,
+    #[arg(
+        long,
+        env = "OPENAI_API_KEY",
+        hide_env_values = true,
+        help = "API key for OpenAI LLM redacter. Can be a reference to a secret such as gcp-secret-manager://projects/<project>/secrets/<secret>/versions/latest or aws-secrets-manager://<secret>"
+    )]
+    pub open_ai_api_key: Option<OpenAiLlmApiKey>,
 
-        #[arg(long, help = "Override media type detection using glob patterns such as 'text/plain=*.md'", value_parser = CliCommand::parse_key_val::<mime::Mime, globset::Glob>)]
-        mime_override: Vec<(mime::Mime, globset::Glob)>,
+    #[arg(
+        long,
+        help = "File with API key for OpenAI LLM redacter such as mounted secrets. Overrides --open-ai-api-key"
+    )]
+    pub open_ai_api_key_file: Option<PathBuf>,
 
-        #[arg(
-            long,
-            help = "Save redacted results in JSON format to the specified file"
-        )]
-        save_json_results: Option<PathBuf>,
-    },
-    #[command(about = "List files in the source")]
-    Ls {
-        #[arg(
-            help = "Source directory or file such as /tmp, /tmp/file.txt or gs://bucket/file.txt and others supported providers"
-        )]
-        source: String,
-        #[arg(short = 'm', long, help = "Maximum size of files to copy in bytes")]
-        max_size_limit: Option<usize>,
-        #[arg(
-            short = 'f',
-            long,
-            help = "Filter by name using glob patterns such as *.txt"
-        )]
-        filename_filter: Option<globset::Glob>,
-    },
-}
+    #[arg(
+        long,
+        help = "Open AI model name for OpenAI LLM redacter. Default is 'gpt-4o-mini'"
+    )]
+    pub open_ai_model: Option<OpenAiModelName>,
 
-impl CliCommand {
-    fn parse_key_val<T, U>(
-        s: &str,
-    ) -> Result<(T, U), Box<dyn std::error::Error + Send + Sync + 'static>>
-    where
-        T: std::str::FromStr,
-        T::Err: std::error::Error + Send + Sync + 'static,
-        U: std::str::FromStr,
-        U::Err: std::error::Error + Send + Sync + 'static,
-    {
-        let pos = s
-            .find('=')
-            .ok_or_else(|| format!("invalid KEY=value: no `=` found in `{s}`"))?;
-        Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
-    }
-}
+    #[arg(
+        long,
+        help = "Base URL of OpenAI compatible APIs for OpenAI LLM redacter. Default is 'https://api.openai.com/v1'"
+    )]
+    pub open_ai_api_url: Option<Url>,
 
-#[derive(ValueEnum, Debug, Clone)]
-pub enum RedacterType {
-    GcpDlp,
-    AwsComprehend,
-    MsPresidio,
-    GeminiLlm,
-    OpenAiLlm,
-    GcpVertexAi,
-}
+    #[arg(
+        long,
+        help = "Limit the number of DLP requests. Some DLPs has strict quotas and to avoid errors, limit the number of requests delaying them. Default is disabled"
+    )]
+    pub limit_dlp_requests: Option<DlpRequestLimit>,
 
-impl std::str::FromStr for RedacterType {
-    type Err = String;
+    #[arg(
+        long,
+        help = "Limit the volume of content sent to redacters in bytes (such as 1000000bpm) or LLM tokens estimated from bytes (such as 100000tpm) per second or minute, since DLP and LLM quotas are often byte or token based. Files are delayed by their sizes, so files with unknown sizes aren't limited. Default is disabled"
+    )]
+    pub limit_dlp_volume: Option<DlpVolumeLimit>,
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "gcp-dlp" => Ok(RedacterType::GcpDlp),
-            "aws-comprehend" => Ok(RedacterType::AwsComprehend),
-            "ms-presidio" => Ok(RedacterType::MsPresidio),
-            "gemini-llm" => Ok(RedacterType::GeminiLlm),
-            _ => Err(format!("Unknown redacter type: {}", s)),
-        }
-    }
-}
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u16).range(1..=64),
+        help = "Number of pages of PDF and multi-page TIFF files redacted concurrently. Pages are throttled as separate requests with --limit-dlp-requests. Default is 4"
+    )]
+    pub page_concurrency: Option<u16>,
 
-impl Display for RedacterType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            RedacterType::GcpDlp => write!(f, "gcp-dlp"),
-            RedacterType::AwsComprehend => write!(f, "aws-comprehend"),
-            RedacterType::MsPresidio => write!(f, "ms-presidio"),
-            RedacterType::GeminiLlm => write!(f, "gemini-llm"),
-            RedacterType::OpenAiLlm => write!(f, "openai-llm"),
-            RedacterType::GcpVertexAi => write!(f, "gcp-vertex-ai"),
-        }
-    }
-}
+    #[arg(
+        long,
+        value_enum,
+        help = "How sensitive regions in images are obfuscated: solid fills them with --image-redaction-color, blur and pixelate keep their shapes. Default is 'solid'"
+    )]
+    pub image_redaction_style: Option<ImageRedactionStyle>,
 
-#[derive(Args, Debug, Clone)]
-#[group(required = false)]
-pub struct RedacterArgs {
-    #[arg(short = 'd', long, value_enum, help = "List of redacters to use")]
-    redact: Option<Vec<RedacterType>>,
+    #[arg(
+        long,
+        help = "Color of solid redacted regions in images as hex RGB such as '#000000'. Default is black"
+    )]
+    pub image_redaction_color: Option<ImageRedactionColor>,
 
     #[arg(
         long,
-        help = "Allow unsupported types to be copied without redaction",
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        help = "Padding added to every side of redacted text regions in images as a percentage of their heights, so small text isn't over-masked and large headlines aren't under-masked. Applied to regions found by LLMs, OCR and DLP inspection, but not to faces. Default is 20"
+    )]
+    pub image_redaction_padding: Option<u8>,
+
+    #[arg(
+        long,
+        help = "Include SHA-256 hashes of the original text of image regions redacted through OCR into redacted regions of JSON results",
         default_value = "false"
     )]
-    pub allow_unsupported_copies: bool,
+    pub hash_redacted_text: bool,
 
     #[arg(
         long,
-        help = "GCP project id that will be used to redact and bill API calls"
+        help = "Produce PDFs rebuilt from redacted page images in PDF/A-2b conformance with XMP metadata and an sRGB output intent for archives. All pages are converted to images, since original pages may not conform",
+        default_value = "false"
     )]
-    pub gcp_project_id: Option<GcpProjectId>,
+    pub pdf_a: bool,
 
-    #[arg(long, help = "Additional GCP DLP built in info types for redaction")]
-    pub gcp_dlp_built_in_info_type: Option<Vec<String>>,
+    #[arg(
+        long,
+        help = "Redact raster images embedded in OpenDocument files and Word documents (such as scans and screenshots) natively or using OCR, and write the redacted images back to the documents. Images of PowerPoint presentations are always redacted, and PDFs are redacted as page images with embedded images",
+        default_value = "false"
+    )]
+    pub redact_embedded_images: bool,
 
     #[arg(
         long,
-        help = "Additional GCP DLP user defined stored info types for redaction"
+        help = "Detect faces in images and PDFs using GCP Vision API and redact them with --image-redaction-style. Requires --gcp-project-id",
+        default_value = "false"
     )]
-    pub gcp_dlp_stored_info_type: Option<Vec<String>>,
+    pub redact_faces: bool,
 
     #[arg(
         long,
-        help = "GCP region that will be used to redact and bill API calls for Vertex AI"
+        value_enum,
+        help = "Transcribe speech in WAV and MP3 files using the specified provider to redact it. Requires --gcp-project-id"
     )]
-    pub gcp_region: Option<GcpRegion>,
+    pub audio_transcription: Option<AudioTranscriptionProvider>,
 
     #[arg(
         long,
-        help = "Vertex AI model supports image editing natively. Default is false."
+        value_enum,
+        help = "How redacted audio files are written: silence and beep replace redacted words in WAV files, transcript writes the redacted transcript to a text file with '.txt' appended. Default is 'silence'"
     )]
-    pub gcp_vertex_ai_native_image_support: bool,
+    pub audio_redaction: Option<AudioRedactionOutput>,
 
     #[arg(
         long,
-        help = "Model name for text redaction in Vertex AI. Default is 'publishers/google/models/gemini-1.5-flash-001'"
+        help = "Language of speech in audio files as BCP-47 code for audio transcription. Default is 'en-US'"
     )]
-    pub gcp_vertex_ai_text_model: Option<GcpVertexAiModelName>,
+    pub audio_language: Option<AudioLanguageCode>,
 
     #[arg(
         long,
-        help = "Model name for image redaction in Vertex AI. Default is 'publishers/google/models/gemini-1.5-pro-001'"
+        value_enum,
+        help = "OCR engine to extract text from images and PDFs for redacters supporting only text: ocrs runs locally, gcp-vision uses GCP Vision API and requires --gcp-project-id. Default is 'ocrs'"
     )]
-    pub gcp_vertex_ai_image_model: Option<GcpVertexAiModelName>,
+    pub ocr_engine: Option<OcrEngineType>,
 
     #[arg(
         long,
-        help = "Block none harmful content threshold for Vertex AI redacter. Default is BlockOnlyHigh since BlockNone is required a special billing settings.",
-        default_value = "false"
+        value_delimiter = ',',
+        help = "Languages of text in images as ISO 639-1 codes (such as en,de) for OCR. ocrs engine supports a single language pack besides English"
     )]
-    pub gcp_vertex_ai_block_none_harmful: bool,
+    pub ocr_language: Option<Vec<RedactLanguage>>,
 
     #[arg(
         long,
-        help = "Disable CSV headers (if they are not present)",
-        default_value = "false"
+        help = "Directory to cache redaction results, so identical content isn't sent to redacters again. Disabled by default"
     )]
-    pub csv_headers_disable: bool,
+    pub cache_dir: Option<PathBuf>,
 
-    #[arg(long, help = "CSV delimiter (default is ',')")]
-    pub csv_delimiter: Option<char>,
+    #[arg(
+        long,
+        help = "Time in seconds to keep cached redaction results. Default is 604800 (7 days)"
+    )]
+    pub cache_ttl: Option<u64>,
 
-    #[arg(long, help = "AWS region for AWS Comprehend DLP redacter")]
-    pub aws_region: Option<String>,
+    #[arg(
+        long,
+        help = "Maximum size of cached redaction results in bytes, the oldest results are removed first. Unlimited by default"
+    )]
+    pub cache_max_size: Option<u64>,
 
-    #[arg(long, help = "URL for text analyze endpoint for MsPresidio redacter")]
-    pub ms_presidio_text_analyze_url: Option<Url>,
+    #[arg(
+        long,
+        help = "Also keep OCR results of images in --cache-dir between runs, so repeated pages aren't recognized again. Cached OCR results contain the original text of images",
+        default_value = "false"
+    )]
+    pub cache_ocr: bool,
 
-    #[arg(long, help = "URL for image redact endpoint for MsPresidio redacter")]
-    pub ms_presidio_image_redact_url: Option<Url>,
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        help = "Redact only the specified entity types (such as person-name,email-address). Default is all entity types supported by the redacters"
+    )]
+    pub redact_entity_types: Option<Vec<RedactEntityType>>,
 
     #[arg(
         long,
-        help = "Gemini model name for Gemini LLM redacter. Default is 'models/gemini-1.5-flash'"
+        value_enum,
+        value_delimiter = ',',
+        help = "Entity types that should never be redacted (such as date-time,location)"
     )]
-    pub gemini_model: Option<GeminiLlmModelName>,
+    pub ignore_entity_types: Option<Vec<RedactEntityType>>,
 
     #[arg(
         long,
-        help = "Sampling size in bytes before redacting files. Disabled by default"
+        help = "File with terms that should never be redacted even if redacters detect them, such as product names, public officials or your own company name, one per line. Lines starting with '#' are ignored"
     )]
-    pub sampling_size: Option<usize>,
+    pub keep_terms_file: Option<PathBuf>,
 
-    #[arg(long, help = "API key for OpenAI LLM redacter")]
-    pub open_ai_api_key: Option<OpenAiLlmApiKey>,
+    #[arg(
+        long,
+        help = "Language of the content as ISO 639-1 code for MsPresidio and AWS Comprehend redacters. Default is 'en'"
+    )]
+    pub redact_language: Option<RedactLanguage>,
 
     #[arg(
         long,
-        help = "Open AI model name for OpenAI LLM redacter. Default is 'gpt-4o-mini'"
+        value_enum,
+        help = "Verify redacted text and tables using the specified redacter (gcp-dlp, aws-comprehend or ms-presidio) to detect entities missed by redacters"
     )]
-    pub open_ai_model: Option<OpenAiModelName>,
+    pub verify: Option<RedacterType>,
 
     #[arg(
         long,
-        help = "Limit the number of DLP requests. Some DLPs has strict quotas and to avoid errors, limit the number of requests delaying them. Default is disabled"
+        value_enum,
+        help = "What to do with files still containing detected entities after redaction: fail skips the file, report copies it with a warning. Default is 'fail'"
     )]
-    pub limit_dlp_requests: Option<DlpRequestLimit>,
+    pub verify_action: Option<RedactVerifyAction>,
+}
+
+impl RedacterArgs {
+    /// Fills options that aren't specified explicitly from the selected redaction profile
+    pub async fn apply_profile(mut self) -> AppResult<Self> {
+        let profile = match self.profile {
+            Some(ref name) => RedactionProfile::find(name, self.profiles_file.as_deref()).await?,
+            None => return Ok(self),
+        };
+        self.redact = self.redact.or(profile.redact);
+        self.redact_entity_types = self.redact_entity_types.or(profile.redact_entity_types);
+        self.ignore_entity_types = self.ignore_entity_types.or(profile.ignore_entity_types);
+        self.ms_presidio_score_threshold = self
+            .ms_presidio_score_threshold
+            .or(profile.ms_presidio_score_threshold);
+        self.secrets_min_entropy = self.secrets_min_entropy.or(profile.secrets_min_entropy);
+        self.gcp_dlp_transformation = self
+            .gcp_dlp_transformation
+            .or(profile.gcp_dlp_transformation);
+        self.image_redaction_style = self.image_redaction_style.or(profile.image_redaction_style);
+        self.network_ip_anonymization = self
+            .network_ip_anonymization
+            .or(profile.network_ip_anonymization);
+        self.network_mac_anonymization = self
+            .network_mac_anonymization
+            .or(profile.network_mac_anonymization);
+        self.redact_faces = self.redact_faces || profile.redact_faces.unwrap_or(false);
+        Ok(self)
+    }
+
+    /// Reads credentials from files and secret managers, so they don't need to be specified as plain values
+    pub async fn resolve_secrets(mut self) -> AppResult<Self> {
+        if let Some(api_key_file) = self.open_ai_api_key_file.take() {
+            self.open_ai_api_key = Some(read_secret_file(&api_key_file).await?.into());
+        }
+        if let Some(api_key) = self.open_ai_api_key.as_ref() {
+            if is_secret_ref(api_key.value()) {
+                self.open_ai_api_key = Some(resolve_secret(api_key.value()).await?.into());
+            }
+        }
+        for url in [
+            &mut self.ms_presidio_text_analyze_url,
+            &mut self.ms_presidio_image_redact_url,
+        ] {
+            if let Some(secret_ref) = url.as_ref().filter(|url| is_secret_ref(url.as_str())) {
+                let secret_url = resolve_secret(secret_ref.as_str()).await?;
+                *url = Some(
+                    Url::parse(&secret_url).map_err(|err| AppError::ConfigError {
+                        message: format!("Invalid URL in secret {}: {}", secret_ref, err),
+                    })?,
+                );
+            }
+        }
+        Ok(self)
+    }
 }
 
 impl TryInto<RedacterOptions> for RedacterArgs {
@@ -242,104 +1643,442 @@ impl TryInto<RedacterOptions> for RedacterArgs {
     fn try_into(self) -> Result<RedacterOptions, Self::Error> {
         let mut provider_options =
             Vec::with_capacity(self.redact.as_ref().map(Vec::len).unwrap_or(0));
-        for options in self.redact.unwrap_or_default() {
-            let redacter_options = match options {
-                RedacterType::GcpDlp => match self.gcp_project_id {
-                    Some(ref project_id) => {
-                        Ok(RedacterProviderOptions::GcpDlp(GcpDlpRedacterOptions {
-                            project_id: project_id.clone(),
-                            user_defined_built_in_info_types: self
-                                .gcp_dlp_built_in_info_type
-                                .clone()
-                                .unwrap_or_default(),
-                            user_defined_stored_info_types: self
-                                .gcp_dlp_stored_info_type
-                                .clone()
-                                .unwrap_or_default(),
-                        }))
-                    }
-                    None => Err(AppError::RedacterConfigError {
-                        message: "GCP project id is required for GCP DLP redacter".to_string(),
-                    }),
-                },
-                RedacterType::AwsComprehend => Ok(RedacterProviderOptions::AwsComprehend(
-                    crate::redacters::AwsComprehendRedacterOptions {
-                        region: self.aws_region.clone().map(aws_config::Region::new),
-                    },
-                )),
-                RedacterType::MsPresidio => {
-                    if self.ms_presidio_text_analyze_url.is_none()
-                        && self.ms_presidio_image_redact_url.is_none()
-                    {
-                        return Err(AppError::RedacterConfigError {
-                            message:
-                            "MsPresidio requires text analyze/image URL specified (at least one)"
-                                .to_string(),
-                        });
-                    }
-                    Ok(RedacterProviderOptions::MsPresidio(
-                        crate::redacters::MsPresidioRedacterOptions {
-                            text_analyze_url: self.ms_presidio_text_analyze_url.clone(),
-                            image_redact_url: self.ms_presidio_image_redact_url.clone(),
-                        },
-                    ))
-                }
-                RedacterType::GeminiLlm => Ok(RedacterProviderOptions::GeminiLlm(
-                    crate::redacters::GeminiLlmRedacterOptions {
-                        project_id: self.gcp_project_id.clone().ok_or_else(|| {
-                            AppError::RedacterConfigError {
-                                message: "GCP project id is required for Gemini LLM redacter"
-                                    .to_string(),
-                            }
-                        })?,
-                        gemini_model: self.gemini_model.clone(),
-                    },
-                )),
-                RedacterType::OpenAiLlm => Ok(RedacterProviderOptions::OpenAiLlm(
-                    crate::redacters::OpenAiLlmRedacterOptions {
-                        api_key: self.open_ai_api_key.clone().ok_or_else(|| {
-                            AppError::RedacterConfigError {
-                                message: "OpenAI API key is required for OpenAI LLM redacter"
-                                    .to_string(),
-                            }
-                        })?,
-                        model: self.open_ai_model.clone(),
-                    },
-                )),
-                RedacterType::GcpVertexAi => Ok(RedacterProviderOptions::GcpVertexAi(
-                    crate::redacters::GcpVertexAiRedacterOptions {
-                        project_id: self.gcp_project_id.clone().ok_or_else(|| {
-                            AppError::RedacterConfigError {
-                                message: "GCP project id is required for GCP Vertex AI redacter"
-                                    .to_string(),
-                            }
-                        })?,
-                        gcp_region: self.gcp_region.clone().ok_or_else(|| {
-                            AppError::RedacterConfigError {
-                                message: "GCP region is required for GCP Vertex AI redacter"
-                                    .to_string(),
-                            }
-                        })?,
-                        native_image_support: self.gcp_vertex_ai_native_image_support,
-                        text_model: self.gcp_vertex_ai_text_model.clone(),
-                        image_model: self.gcp_vertex_ai_image_model.clone(),
-                        block_none_harmful: self.gcp_vertex_ai_block_none_harmful,
-                    },
-                )),
-            }?;
-            provider_options.push(redacter_options);
+        let entity_type_filter = RedactEntityTypeFilter {
+            redact_entity_types: self.redact_entity_types.clone(),
+            ignore_entity_types: self.ignore_entity_types.clone().unwrap_or_default(),
+            keep_terms: self.keep_terms()?,
+        };
+        let entity_mappings = if self.pseudonymize {
+            Some(EntityMappings::new(self.pseudonym_mappings_file.clone())?)
+        } else {
+            None
+        };
+        let provider_io = match (&self.record_provider_io, &self.replay_provider_io) {
+            (Some(dir), _) => Some(ProviderIo::new(ProviderIoMode::Record, dir.clone())?),
+            (None, Some(dir)) => Some(ProviderIo::new(ProviderIoMode::Replay, dir.clone())?),
+            (None, None) => None,
+        };
+        for redacter_type in self.redact.clone().unwrap_or_default() {
+            provider_options.push(self.provider_options(
+                &redacter_type,
+                &entity_type_filter,
+                entity_mappings.as_ref(),
+                provider_io.as_ref(),
+            )?);
         }
+        let verify_options = match self.verify {
+            Some(ref redacter_type) if redacter_type.supports_detection() => {
+                Some(RedacterVerifyOptions {
+                    provider_options: self.provider_options(
+                        redacter_type,
+                        &entity_type_filter,
+                        None,
+                        None,
+                    )?,
+                    action: self.verify_action.unwrap_or_default(),
+                })
+            }
+            Some(ref redacter_type) => {
+                return Err(AppError::RedacterConfigError {
+                    message: format!(
+                        "Redacter {} doesn't support verification. Supported redacters: gcp-dlp, aws-comprehend, ms-presidio",
+                        redacter_type
+                    ),
+                })
+            }
+            None => None,
+        };
+        let face_redacter_options = match (self.redact_faces, &self.gcp_project_id) {
+            (true, Some(project_id)) => Some(FaceRedacterOptions {
+                project_id: project_id.clone(),
+            }),
+            (true, None) => {
+                return Err(AppError::RedacterConfigError {
+                    message: "GCP project id is required for face redaction".to_string(),
+                })
+            }
+            (false, _) => None,
+        };
+        let audio_transcriber_options = match (self.audio_transcription, &self.gcp_project_id) {
+            (Some(provider), Some(project_id)) => Some(AudioTranscriberOptions {
+                provider,
+                project_id: project_id.clone(),
+                language_code: self.audio_language.clone().unwrap_or_else(|| {
+                    AudioLanguageCode::new(
+                        AudioTranscriberOptions::DEFAULT_LANGUAGE_CODE.to_string(),
+                    )
+                }),
+                output: self.audio_redaction.unwrap_or_default(),
+            }),
+            (Some(_), None) => {
+                return Err(AppError::RedacterConfigError {
+                    message: "GCP project id is required for audio transcription".to_string(),
+                })
+            }
+            (None, _) => None,
+        };
+        let cache_options = self
+            .cache_dir
+            .clone()
+            .map(|cache_dir| RedactionCacheOptions {
+                cache_dir,
+                ttl: self
+                    .cache_ttl
+                    .map(std::time::Duration::from_secs)
+                    .unwrap_or(RedactionCacheOptions::DEFAULT_TTL),
+                max_size: self.cache_max_size,
+                ocr: self.cache_ocr,
+            });
+        let ocr_options = OcrOptions {
+            engine: self.ocr_engine.unwrap_or_default(),
+            languages: self.ocr_language.clone().unwrap_or_default(),
+            gcp_project_id: self.gcp_project_id.clone(),
+        };
 
+        let image_redaction = self.image_redaction_options();
+        let network_id_options = self.network_id_options()?;
         let base_options = RedacterBaseOptions {
             allow_unsupported_copies: self.allow_unsupported_copies,
             csv_headers_disable: self.csv_headers_disable,
             csv_delimiter: self.csv_delimiter.map(|c| c as u8),
+            csv_redact_columns: self.csv_redact_columns,
+            csv_skip_columns: self.csv_skip_columns.unwrap_or_default(),
             sampling_size: self.sampling_size,
+            sampling_strategy: self.sampling_strategy.unwrap_or_default(),
+            sample_then_full: self.sample_then_full,
+            max_item_size: self.max_item_size,
             limit_dlp_requests: self.limit_dlp_requests,
+            limit_dlp_volume: self.limit_dlp_volume,
+            page_concurrency: self
+                .page_concurrency
+                .map(usize::from)
+                .unwrap_or(RedacterBaseOptions::DEFAULT_PAGE_CONCURRENCY),
+            image_redaction,
+            redact_policy: self.redact_policy.unwrap_or_default(),
+            hash_redacted_text: self.hash_redacted_text,
+            pdf_a: self.pdf_a,
+            redact_embedded_images: self.redact_embedded_images,
+            log_redaction: self.redact_log_format.map(|format| LogRedactionOptions {
+                format,
+                // Addresses are always anonymized in logs, masked if no mode is specified
+                network: NetworkIdOptions {
+                    ip: Some(self.network_ip_anonymization.unwrap_or_default()),
+                    mac: Some(self.network_mac_anonymization.unwrap_or_default()),
+                    ..network_id_options.clone()
+                },
+            }),
+            network_anonymization: Some(network_id_options)
+                .filter(|options| options.ip.is_some() || options.mac.is_some()),
+            entity_mappings,
         };
         Ok(RedacterOptions {
             provider_options,
             base_options,
+            verify_options,
+            face_redacter_options,
+            audio_transcriber_options,
+            ocr_options,
+            cache_options,
+        })
+    }
+}
+
+impl RedacterArgs {
+    fn gcp_vertex_ai_batch_options(&self) -> AppResult<Option<GcpVertexAiBatchOptions>> {
+        match (self.gcp_vertex_ai_batch, &self.gcp_vertex_ai_batch_staging) {
+            (true, Some(staging_prefix)) if staging_prefix.starts_with("gs://") => {
+                Ok(Some(GcpVertexAiBatchOptions {
+                    staging_prefix: staging_prefix.clone(),
+                }))
+            }
+            (true, Some(staging_prefix)) => Err(AppError::RedacterConfigError {
+                message: format!(
+                    "Vertex AI batch staging must be a GCS prefix such as 'gs://my-bucket/staging/', got '{}'",
+                    staging_prefix
+                ),
+            }),
+            (true, None) => Err(AppError::RedacterConfigError {
+                message: "GCS staging prefix is required for Vertex AI batch prediction".to_string(),
+            }),
+            (false, _) => Ok(None),
+        }
+    }
+
+    fn image_redaction_options(&self) -> ImageRedactionOptions {
+        ImageRedactionOptions {
+            style: self.image_redaction_style.unwrap_or_default(),
+            color: self.image_redaction_color.unwrap_or_default(),
+            padding_percent: self
+                .image_redaction_padding
+                .unwrap_or(ImageRedactionOptions::DEFAULT_PADDING_PERCENT),
+        }
+    }
+
+    fn keep_terms(&self) -> AppResult<Vec<String>> {
+        match &self.keep_terms_file {
+            Some(keep_terms_file) => {
+                let keep_terms = std::fs::read_to_string(keep_terms_file).map_err(|err| {
+                    AppError::RedacterConfigError {
+                        message: format!(
+                            "Failed to read keep terms file {}: {}",
+                            keep_terms_file.display(),
+                            err
+                        ),
+                    }
+                })?;
+                Ok(DictionaryRedacter::dictionary_terms(&keep_terms)
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect())
+            }
+            None => Ok(vec![]),
+        }
+    }
+
+    fn network_id_options(&self) -> AppResult<NetworkIdOptions> {
+        let hash_key = match &self.network_hash_key {
+            Some(key) => NetworkIdHashKey::new(key.as_bytes().to_vec()),
+            None => NetworkIdHashKey::random()?,
+        };
+        Ok(NetworkIdOptions {
+            ip: self.network_ip_anonymization,
+            ipv4_prefix: self
+                .network_ipv4_prefix
+                .unwrap_or(NetworkIdOptions::DEFAULT_IPV4_PREFIX),
+            ipv6_prefix: self
+                .network_ipv6_prefix
+                .unwrap_or(NetworkIdOptions::DEFAULT_IPV6_PREFIX),
+            mac: self.network_mac_anonymization,
+            hash_key,
         })
     }
+
+    fn provider_options(
+        &self,
+        redacter_type: &RedacterType,
+        entity_type_filter: &RedactEntityTypeFilter,
+        entity_mappings: Option<&EntityMappings>,
+        provider_io: Option<&ProviderIo>,
+    ) -> Result<RedacterProviderOptions, AppError> {
+        match redacter_type {
+            RedacterType::GcpDlp => match self.gcp_project_id {
+                Some(ref project_id) => {
+                    let transformation = self.gcp_dlp_transformation.unwrap_or_default();
+                    let crypto_key = match (
+                    &self.gcp_dlp_kms_key_name,
+                    &self.gcp_dlp_wrapped_key,
+                ) {
+                    (Some(crypto_key_name), Some(wrapped_key)) => {
+                        Some(GcpDlpKmsWrappedKey {
+                            crypto_key_name: crypto_key_name.clone(),
+                            wrapped_key: base64::engine::general_purpose::STANDARD
+                                .decode(wrapped_key)
+                                .map_err(|err| AppError::RedacterConfigError {
+                                    message: format!(
+                                        "GCP DLP wrapped key must be base64 encoded: {}",
+                                        err
+                                    ),
+                                })?,
+                        })
+                    }
+                    (None, None) => None,
+                    _ => {
+                        return Err(AppError::RedacterConfigError {
+                            message: "Both GCP DLP KMS key name and wrapped key are required for encryption".to_string(),
+                        })
+                    }
+                };
+                    if transformation != GcpDlpTransformation::Replace && crypto_key.is_none() {
+                        return Err(AppError::RedacterConfigError {
+                        message: "GCP DLP KMS key name and wrapped key are required for 'fpe' and 'deterministic' transformations".to_string(),
+                    });
+                    }
+                    Ok(RedacterProviderOptions::GcpDlp(GcpDlpRedacterOptions {
+                        project_id: project_id.clone(),
+                        user_defined_built_in_info_types: self
+                            .gcp_dlp_built_in_info_type
+                            .clone()
+                            .unwrap_or_default(),
+                        user_defined_stored_info_types: self
+                            .gcp_dlp_stored_info_type
+                            .clone()
+                            .unwrap_or_default(),
+                        entity_type_filter: entity_type_filter.clone(),
+                        transformation,
+                        crypto_key,
+                        surrogate_info_type: self.gcp_dlp_surrogate_info_type.clone(),
+                        image_redaction: self.image_redaction_options(),
+                    }))
+                }
+                None => Err(AppError::RedacterConfigError {
+                    message: "GCP project id is required for GCP DLP redacter".to_string(),
+                }),
+            },
+            RedacterType::AwsComprehend => {
+                if let Some(language) = self.redact_language.as_ref().filter(|language| {
+                    !AwsComprehendRedacter::SUPPORTED_LANGUAGES.contains(&language.value().as_str())
+                }) {
+                    return Err(AppError::RedacterConfigError {
+                        message: format!(
+                            "AWS Comprehend doesn't support language '{}'. Supported languages: {}",
+                            language.value(),
+                            AwsComprehendRedacter::SUPPORTED_LANGUAGES.join(", ")
+                        ),
+                    });
+                }
+                Ok(RedacterProviderOptions::AwsComprehend(
+                    crate::redacters::AwsComprehendRedacterOptions {
+                        region: self.aws_region.clone().map(aws_config::Region::new),
+                        entity_type_filter: entity_type_filter.clone(),
+                        language: self.redact_language.clone(),
+                        entity_mappings: entity_mappings.cloned(),
+                    },
+                ))
+            }
+            RedacterType::MsPresidio => {
+                if self.ms_presidio_text_analyze_url.is_none()
+                    && self.ms_presidio_image_redact_url.is_none()
+                {
+                    return Err(AppError::RedacterConfigError {
+                        message:
+                            "MsPresidio requires text analyze/image URL specified (at least one)"
+                                .to_string(),
+                    });
+                }
+                if let Some(score_threshold) = self
+                    .ms_presidio_score_threshold
+                    .filter(|score_threshold| !(0.0..=1.0).contains(score_threshold))
+                {
+                    return Err(AppError::RedacterConfigError {
+                        message: format!(
+                            "MsPresidio score threshold must be between 0.0 and 1.0, got {}",
+                            score_threshold
+                        ),
+                    });
+                }
+                if let Some(language) = self
+                    .redact_language
+                    .as_ref()
+                    .filter(|language| !MsPresidioRedacter::is_supported_language(language))
+                {
+                    return Err(AppError::RedacterConfigError {
+                        message: format!(
+                            "MsPresidio requires language as ISO 639-1 code such as 'en', got '{}'",
+                            language.value()
+                        ),
+                    });
+                }
+                Ok(RedacterProviderOptions::MsPresidio(
+                    crate::redacters::MsPresidioRedacterOptions {
+                        text_analyze_url: self.ms_presidio_text_analyze_url.clone(),
+                        image_redact_url: self.ms_presidio_image_redact_url.clone(),
+                        entity_type_filter: entity_type_filter.clone(),
+                        language: self.redact_language.clone(),
+                        image_redaction: self.image_redaction_options(),
+                        score_threshold: self.ms_presidio_score_threshold,
+                        entities: self.ms_presidio_entity.clone(),
+                        batch_size: self.ms_presidio_batch_size,
+                        entity_mappings: entity_mappings.cloned(),
+                    },
+                ))
+            }
+            RedacterType::GeminiLlm => Ok(RedacterProviderOptions::GeminiLlm(
+                crate::redacters::GeminiLlmRedacterOptions {
+                    project_id: self.gcp_project_id.clone().ok_or_else(|| {
+                        AppError::RedacterConfigError {
+                            message: "GCP project id is required for Gemini LLM redacter"
+                                .to_string(),
+                        }
+                    })?,
+                    gemini_model: self.gemini_model.clone(),
+                    entity_type_filter: entity_type_filter.clone(),
+                    image_redaction: self.image_redaction_options(),
+                    provider_io: provider_io.cloned(),
+                },
+            )),
+            RedacterType::OpenAiLlm => Ok(RedacterProviderOptions::OpenAiLlm(
+                crate::redacters::OpenAiLlmRedacterOptions {
+                    api_key: self.open_ai_api_key.clone().ok_or_else(|| {
+                        AppError::RedacterConfigError {
+                            message: "OpenAI API key is required for OpenAI LLM redacter"
+                                .to_string(),
+                        }
+                    })?,
+                    model: self.open_ai_model.clone(),
+                    api_url: self.open_ai_api_url.clone(),
+                    entity_type_filter: entity_type_filter.clone(),
+                    image_redaction: self.image_redaction_options(),
+                    provider_io: provider_io.cloned(),
+                },
+            )),
+            RedacterType::GcpVertexAi => Ok(RedacterProviderOptions::GcpVertexAi(
+                crate::redacters::GcpVertexAiRedacterOptions {
+                    project_id: self.gcp_project_id.clone().ok_or_else(|| {
+                        AppError::RedacterConfigError {
+                            message: "GCP project id is required for GCP Vertex AI redacter"
+                                .to_string(),
+                        }
+                    })?,
+                    gcp_region: self.gcp_region.clone().ok_or_else(|| {
+                        AppError::RedacterConfigError {
+                            message: "GCP region is required for GCP Vertex AI redacter"
+                                .to_string(),
+                        }
+                    })?,
+                    native_image_support: self.gcp_vertex_ai_native_image_support,
+                    text_model: self.gcp_vertex_ai_text_model.clone(),
+                    image_model: self.gcp_vertex_ai_image_model.clone(),
+                    block_none_harmful: self.gcp_vertex_ai_block_none_harmful,
+                    entity_type_filter: entity_type_filter.clone(),
+                    image_redaction: self.image_redaction_options(),
+                    batch: self.gcp_vertex_ai_batch_options()?,
+                },
+            )),
+            RedacterType::External => {
+                let command = self.external_redacter_cmd.as_ref().ok_or_else(|| {
+                    AppError::RedacterConfigError {
+                        message: "External redacter command is required for external redacter"
+                            .to_string(),
+                    }
+                })?;
+                let command = shlex::split(command)
+                    .filter(|command| !command.is_empty())
+                    .ok_or_else(|| AppError::RedacterConfigError {
+                        message: format!("Invalid external redacter command: {}", command),
+                    })?;
+                Ok(RedacterProviderOptions::External(
+                    crate::redacters::ExternalRedacterOptions {
+                        command,
+                        image_support: self.external_redacter_image_support,
+                        entity_type_filter: entity_type_filter.clone(),
+                    },
+                ))
+            }
+            RedacterType::Dictionary => Ok(RedacterProviderOptions::Dictionary(
+                crate::redacters::DictionaryRedacterOptions {
+                    dictionary_file: self.dictionary_file.clone().ok_or_else(|| {
+                        AppError::RedacterConfigError {
+                            message: "Dictionary file is required for dictionary redacter"
+                                .to_string(),
+                        }
+                    })?,
+                    ignore_case: self.dictionary_ignore_case,
+                    whole_words: self.dictionary_whole_words,
+                },
+            )),
+            RedacterType::Secrets => Ok(RedacterProviderOptions::Secrets(
+                crate::redacters::SecretsRedacterOptions {
+                    min_entropy: self
+                        .secrets_min_entropy
+                        .unwrap_or(crate::redacters::SecretsRedacterOptions::DEFAULT_MIN_ENTROPY),
+                },
+            )),
+            RedacterType::Mock => Ok(RedacterProviderOptions::Mock(
+                crate::redacters::MockRedacterOptions {
+                    patterns: self.mock_pattern.clone().unwrap_or_default(),
+                    every_nth_word: self.mock_every_nth_word,
+                },
+            )),
+        }
+    }
 }
@@ -0,0 +1,580 @@
+use crate::errors::AppError;
+use crate::file_systems::{
+    DetectFileSystem, FileSystemConnection, FileSystemOptions, FileSystemRef,
+};
+use crate::file_tools::{CsvDialect, FileFilters, FileMatcher, FileMimeOverride};
+use crate::redacters::Redacters;
+use crate::reporter::{AppReporter, AppReporterOptions};
+use crate::AppResult;
+use console::{Style, Term};
+use futures::TryStreamExt;
+use rand::Rng;
+use rvstruct::ValueStruct;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum AggregateOutput {
+    /// Rows with generalized quasi-identifiers, without rows of groups smaller than k
+    KAnonymized,
+    /// A row with the number of rows and summaries of sensitive columns for every group
+    Counts,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateGranularity {
+    Year,
+    Month,
+}
+
+/// Generalization of quasi-identifier values, so more rows share the same values
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generalization {
+    /// Numbers as ranges of the width such as `30-39`
+    Range(f64),
+    /// First characters such as `941*` for ZIP codes
+    Prefix(usize),
+    /// Dates truncated to years or months
+    Date(DateGranularity),
+    /// All values are replaced with `*`
+    Suppress,
+}
+
+impl Generalization {
+    /// Parses `range:<width>`, `prefix:<length>`, `year`, `month` or `suppress`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.split_once(':') {
+            Some(("range", width)) => match width.parse::<f64>() {
+                Ok(width) if width > 0.0 => Ok(Generalization::Range(width)),
+                _ => Err(format!("`{width}` is not a positive range width")),
+            },
+            Some(("prefix", length)) => length
+                .parse()
+                .map(Generalization::Prefix)
+                .map_err(|err| format!("invalid prefix length `{length}`: {err}")),
+            None if s == "year" => Ok(Generalization::Date(DateGranularity::Year)),
+            None if s == "month" => Ok(Generalization::Date(DateGranularity::Month)),
+            None if s == "suppress" => Ok(Generalization::Suppress),
+            _ => Err(format!(
+                "unknown generalization `{s}`, expected range:<width>, prefix:<length>, year, month or suppress"
+            )),
+        }
+    }
+
+    fn apply(&self, value: &str) -> String {
+        let value = value.trim();
+        if value.is_empty() {
+            return String::new();
+        }
+        match self {
+            Generalization::Range(width) => match value.parse::<f64>() {
+                Ok(number) if number.is_finite() => {
+                    let lower = (number / width).floor() * width;
+                    if width.fract() == 0.0 && value.parse::<i64>().is_ok() {
+                        format!("{}-{}", lower, lower + width - 1.0)
+                    } else {
+                        format!("{}-{}", lower, lower + width)
+                    }
+                }
+                _ => "*".to_string(),
+            },
+            Generalization::Prefix(length) => {
+                if value.chars().count() <= *length {
+                    value.to_string()
+                } else {
+                    format!("{}*", value.chars().take(*length).collect::<String>())
+                }
+            }
+            Generalization::Date(granularity) => {
+                // Dates and timestamps starting with ISO 8601 dates such as 2024-07-17T09:44:25Z
+                let date = value
+                    .get(..10)
+                    .and_then(|date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+                match (date, granularity) {
+                    (Some(date), DateGranularity::Year) => date.format("%Y").to_string(),
+                    (Some(date), DateGranularity::Month) => date.format("%Y-%m").to_string(),
+                    (None, _) => "*".to_string(),
+                }
+            }
+            Generalization::Suppress => "*".to_string(),
+        }
+    }
+}
+
+/// Roles of CSV columns. Columns without roles are considered direct identifiers and removed.
+#[derive(Debug, Clone, Default)]
+pub struct AggregationOptions {
+    pub k: usize,
+    pub quasi_identifiers: Vec<String>,
+    pub sensitive: Vec<String>,
+    pub insensitive: Vec<String>,
+    pub generalizations: Vec<(String, Generalization)>,
+    pub output: Option<AggregateOutput>,
+    /// Privacy budget of Laplace noise added to counts
+    pub epsilon: Option<f64>,
+    pub csv_delimiter: Option<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AggregateCommandOptions {
+    pub file_matcher: FileMatcher,
+    pub file_mime_override: FileMimeOverride,
+    pub aggregation: AggregationOptions,
+    pub file_system_options: FileSystemOptions,
+    pub reporter_options: AppReporterOptions,
+}
+
+impl AggregateCommandOptions {
+    pub fn new(
+        file_filters: FileFilters,
+        max_size_limit: Option<usize>,
+        mime_override: Vec<(mime::Mime, globset::Glob)>,
+        aggregation: AggregationOptions,
+        file_system_options: FileSystemOptions,
+        reporter_options: AppReporterOptions,
+    ) -> Self {
+        AggregateCommandOptions {
+            file_matcher: FileMatcher::new(file_filters, max_size_limit),
+            file_mime_override: FileMimeOverride::new(mime_override),
+            aggregation,
+            file_system_options,
+            reporter_options,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregatedTable {
+    pub content: Vec<u8>,
+    pub rows: usize,
+    pub groups: usize,
+    /// Rows of groups smaller than k which aren't in the output
+    pub suppressed_rows: usize,
+    pub removed_columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AggregateCommandResult {
+    pub files_aggregated: usize,
+    pub files_skipped: usize,
+    pub rows: usize,
+    pub suppressed_rows: usize,
+}
+
+#[tracing::instrument(name = "command_aggregate", skip_all, fields(source = source, destination = destination))]
+pub async fn command_aggregate(
+    term: &Term,
+    source: &str,
+    destination: &str,
+    options: AggregateCommandOptions,
+) -> AppResult<AggregateCommandResult> {
+    let bold_style = Style::new().bold();
+    let app_reporter = AppReporter::from(term).with_options(&options.reporter_options);
+    app_reporter.report(format!(
+        "Aggregating {} to {} with k={}.",
+        bold_style.clone().white().apply_to(source),
+        bold_style.clone().green().apply_to(destination),
+        bold_style.apply_to(options.aggregation.k)
+    ))?;
+    let mut source_fs =
+        DetectFileSystem::open(source, &app_reporter, &options.file_system_options).await?;
+    let mut destination_fs =
+        DetectFileSystem::open(destination, &app_reporter, &options.file_system_options).await?;
+
+    let file_refs = if source_fs.has_multiple_files().await? {
+        if !destination_fs.accepts_multiple_files().await? {
+            return Err(AppError::DestinationDoesNotSupportMultipleFiles {
+                destination: destination.to_string(),
+            });
+        }
+        source_fs
+            .list_files(Some(&options.file_matcher), None)
+            .await?
+            .files
+            .into_iter()
+            .map(Some)
+            .collect()
+    } else {
+        vec![None]
+    };
+
+    let mut result = AggregateCommandResult::default();
+    for file_ref in file_refs {
+        let (file_ref, stream) = source_fs.download(file_ref.as_ref()).await?;
+        let file_ref = options.file_mime_override.override_for_file_ref(file_ref);
+        app_reporter.report(format!(
+            "Aggregating {}",
+            bold_style
+                .clone()
+                .white()
+                .apply_to(file_ref.relative_path.value())
+        ))?;
+        let is_tsv = match file_ref.media_type.as_ref() {
+            Some(media_type) if Redacters::is_mime_table(media_type) => {
+                Redacters::is_mime_tsv(media_type)
+            }
+            _ => {
+                app_reporter.report(format!(
+                    "↳ Skipping {} because only CSV files can be aggregated",
+                    bold_style.clone().yellow().apply_to(
+                        file_ref
+                            .media_type
+                            .as_ref()
+                            .map(|media_type| media_type.to_string())
+                            .unwrap_or_else(|| "unknown media type".to_string())
+                    )
+                ))?;
+                result.files_skipped += 1;
+                continue;
+            }
+        };
+        let chunks: Vec<bytes::Bytes> = stream.try_collect().await?;
+        let aggregation = AggregationOptions {
+            csv_delimiter: options
+                .aggregation
+                .csv_delimiter
+                .or(is_tsv.then_some(b'\t')),
+            ..options.aggregation.clone()
+        };
+        let table = aggregate_csv(&chunks.concat(), &aggregation).await?;
+        if !table.removed_columns.is_empty() {
+            app_reporter.report(format!(
+                "↳ Removed columns without roles: {}",
+                table.removed_columns.join(", ")
+            ))?;
+        }
+        app_reporter.report(format!(
+            "↳ {} rows in {} groups, {} rows suppressed in groups smaller than k",
+            bold_style.apply_to(table.rows),
+            bold_style.apply_to(table.groups),
+            bold_style.clone().yellow().apply_to(table.suppressed_rows)
+        ))?;
+        destination_fs
+            .upload(
+                futures::stream::iter(vec![Ok(bytes::Bytes::from(table.content))]),
+                Some(&FileSystemRef {
+                    file_size: None,
+                    modified_at: None,
+                    checksum: None,
+                    ..file_ref
+                }),
+            )
+            .await?;
+        result.files_aggregated += 1;
+        result.rows += table.rows;
+        result.suppressed_rows += table.suppressed_rows;
+    }
+    source_fs.close().await?;
+    destination_fs.close().await?;
+
+    app_reporter.report(format!(
+        "{} files aggregated ({} rows, {} suppressed) and {} skipped.",
+        bold_style.clone().green().apply_to(result.files_aggregated),
+        bold_style.apply_to(result.rows),
+        bold_style.clone().yellow().apply_to(result.suppressed_rows),
+        bold_style.clone().yellow().apply_to(result.files_skipped)
+    ))?;
+    Ok(result)
+}
+
+/// Generalizes quasi-identifiers of CSV files with headers and suppresses groups of rows
+/// with the same quasi-identifiers smaller than k, so every row is indistinguishable from at least k-1 others.
+pub async fn aggregate_csv(
+    content: &[u8],
+    options: &AggregationOptions,
+) -> AppResult<AggregatedTable> {
+    if options.k == 0 {
+        return Err(AppError::ConfigError {
+            message: "k must be greater than 0".to_string(),
+        });
+    }
+    if options.epsilon.is_some_and(|epsilon| epsilon <= 0.0) {
+        return Err(AppError::ConfigError {
+            message: "epsilon must be greater than 0".to_string(),
+        });
+    }
+    let dialect = CsvDialect::sniff(content, options.csv_delimiter);
+    let mut reader = dialect.reader_builder(true).create_reader(content);
+    let headers: Vec<String> = reader
+        .headers()
+        .await?
+        .iter()
+        .map(|h| h.to_string())
+        .collect();
+    let records: Vec<csv_async::StringRecord> = reader.records().try_collect().await?;
+
+    let column_index = |name: &String| {
+        headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| AppError::ConfigError {
+                message: format!(
+                    "Column '{}' is not found. Available columns: {}",
+                    name,
+                    headers.join(", ")
+                ),
+            })
+    };
+    let quasi_identifiers = options
+        .quasi_identifiers
+        .iter()
+        .map(column_index)
+        .collect::<AppResult<Vec<usize>>>()?;
+    let sensitive = options
+        .sensitive
+        .iter()
+        .map(column_index)
+        .collect::<AppResult<Vec<usize>>>()?;
+    let insensitive = options
+        .insensitive
+        .iter()
+        .map(column_index)
+        .collect::<AppResult<Vec<usize>>>()?;
+    let mut generalizations: HashMap<usize, &Generalization> = HashMap::new();
+    for (name, generalization) in &options.generalizations {
+        let idx = column_index(name)?;
+        if !quasi_identifiers.contains(&idx) {
+            return Err(AppError::ConfigError {
+                message: format!(
+                    "Only quasi-identifiers are generalized, but '{}' isn't",
+                    name
+                ),
+            });
+        }
+        generalizations.insert(idx, generalization);
+    }
+    let kept_columns: HashSet<usize> = quasi_identifiers
+        .iter()
+        .chain(sensitive.iter())
+        .chain(insensitive.iter())
+        .copied()
+        .collect();
+    let removed_columns: Vec<String> = headers
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| !kept_columns.contains(idx))
+        .map(|(_, header)| header.clone())
+        .collect();
+
+    let generalized_rows: Vec<Vec<String>> = records
+        .iter()
+        .map(|record| {
+            (0..headers.len())
+                .map(|idx| {
+                    let value = record.get(idx).unwrap_or_default();
+                    match generalizations.get(&idx) {
+                        Some(generalization) => generalization.apply(value),
+                        None => value.to_string(),
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    let group_key = |row: &Vec<String>| -> Vec<String> {
+        quasi_identifiers
+            .iter()
+            .map(|idx| row[*idx].clone())
+            .collect()
+    };
+    let mut groups: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+    let mut groups_order: Vec<Vec<String>> = Vec::new();
+    for (row_idx, row) in generalized_rows.iter().enumerate() {
+        let key = group_key(row);
+        if !groups.contains_key(&key) {
+            groups_order.push(key.clone());
+        }
+        groups.entry(key).or_default().push(row_idx);
+    }
+    groups_order.retain(|key| groups[key].len() >= options.k);
+    let suppressed_rows = records.len()
+        - groups_order
+            .iter()
+            .map(|key| groups[key].len())
+            .sum::<usize>();
+
+    let mut writer = dialect.writer_builder().create_writer(vec![]);
+    match options.output.unwrap_or(AggregateOutput::KAnonymized) {
+        AggregateOutput::KAnonymized => {
+            let output_columns: Vec<usize> = (0..headers.len())
+                .filter(|idx| kept_columns.contains(idx))
+                .collect();
+            writer
+                .write_record(output_columns.iter().map(|idx| headers[*idx].as_str()))
+                .await?;
+            for row in &generalized_rows {
+                if groups[&group_key(row)].len() < options.k {
+                    continue;
+                }
+                writer
+                    .write_record(output_columns.iter().map(|idx| row[*idx].as_str()))
+                    .await?;
+            }
+        }
+        AggregateOutput::Counts => {
+            let sensitive_numeric: Vec<bool> = sensitive
+                .iter()
+                .map(|idx| {
+                    generalized_rows.iter().all(|row| {
+                        row[*idx].trim().is_empty() || row[*idx].trim().parse::<f64>().is_ok()
+                    })
+                })
+                .collect();
+            let mut header: Vec<String> = quasi_identifiers
+                .iter()
+                .map(|idx| headers[*idx].clone())
+                .collect();
+            header.push("count".to_string());
+            for (idx, numeric) in sensitive.iter().zip(sensitive_numeric.iter()) {
+                header.push(if *numeric {
+                    format!("{}_mean", headers[*idx])
+                } else {
+                    format!("{}_distinct", headers[*idx])
+                });
+            }
+            writer.write_record(&header).await?;
+            for key in &groups_order {
+                let rows = &groups[key];
+                let count = match options.epsilon {
+                    Some(epsilon) => noisy_count(rows.len(), epsilon),
+                    None => rows.len(),
+                };
+                let mut output_row = key.clone();
+                output_row.push(count.to_string());
+                for (idx, numeric) in sensitive.iter().zip(sensitive_numeric.iter()) {
+                    let values = rows
+                        .iter()
+                        .map(|row_idx| generalized_rows[*row_idx][*idx].trim())
+                        .filter(|value| !value.is_empty());
+                    output_row.push(if *numeric {
+                        let numbers: Vec<f64> = values.filter_map(|v| v.parse().ok()).collect();
+                        if numbers.is_empty() {
+                            String::new()
+                        } else {
+                            format!("{:.2}", numbers.iter().sum::<f64>() / numbers.len() as f64)
+                        }
+                    } else {
+                        values.collect::<HashSet<&str>>().len().to_string()
+                    });
+                }
+                writer.write_record(&output_row).await?;
+            }
+        }
+    }
+    writer.flush().await?;
+    Ok(AggregatedTable {
+        content: writer.into_inner().await?,
+        rows: records.len(),
+        groups: groups_order.len(),
+        suppressed_rows,
+        removed_columns,
+    })
+}
+
+/// Counts with Laplace noise of the scale 1/epsilon, since adding or removing a row changes a count by 1
+fn noisy_count(count: usize, epsilon: f64) -> usize {
+    let u: f64 = rand::thread_rng().gen_range(-0.5..0.5);
+    let noise = -(1.0 / epsilon) * u.signum() * (1.0 - 2.0 * u.abs()).ln();
+    (count as f64 + noise).round().max(0.0) as usize
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[allow(dead_code)]
+    const PATIENTS_CSV: &str = "name,age,zip,visit_date,diagnosis,cost\n\
+        John Doe,34,94103,2024-03-05,flu,120\n\
+        Jane Roe,37,94107,2024-03-19,asthma,300\n\
+        Max Mustermann,31,94110,2024-03-28,flu,150\n\
+        Erika Mustermann,52,10001,2024-04-02,diabetes,800\n\
+        Richard Roe,45,94112,2024-05-11,flu,90\n";
+
+    #[allow(dead_code)]
+    fn patients_options(output: AggregateOutput) -> AggregationOptions {
+        AggregationOptions {
+            k: 3,
+            quasi_identifiers: vec![
+                "age".to_string(),
+                "zip".to_string(),
+                "visit_date".to_string(),
+            ],
+            sensitive: vec!["diagnosis".to_string(), "cost".to_string()],
+            generalizations: vec![
+                ("age".to_string(), Generalization::Range(10.0)),
+                ("zip".to_string(), Generalization::Prefix(3)),
+                (
+                    "visit_date".to_string(),
+                    Generalization::Date(DateGranularity::Month),
+                ),
+            ],
+            output: Some(output),
+            ..AggregationOptions::default()
+        }
+    }
+
+    #[test]
+    fn generalization_test() {
+        assert_eq!(Generalization::Range(10.0).apply("34"), "30-39");
+        assert_eq!(Generalization::Range(0.5).apply("1.7"), "1.5-2");
+        assert_eq!(Generalization::Range(10.0).apply("unknown"), "*");
+        assert_eq!(Generalization::Prefix(3).apply("94103"), "941*");
+        assert_eq!(Generalization::Prefix(3).apply("NY"), "NY");
+        assert_eq!(
+            Generalization::Date(DateGranularity::Year).apply("2024-07-17T09:44:25Z"),
+            "2024"
+        );
+        assert_eq!(Generalization::Suppress.apply("male"), "*");
+        assert_eq!(Generalization::Range(10.0).apply(""), "");
+        assert_eq!(
+            Generalization::parse("prefix:3"),
+            Ok(Generalization::Prefix(3))
+        );
+        assert!(Generalization::parse("range:0").is_err());
+        assert!(Generalization::parse("week").is_err());
+    }
+
+    #[tokio::test]
+    async fn aggregate_k_anonymized_test() -> AppResult<()> {
+        let table = aggregate_csv(
+            PATIENTS_CSV.as_bytes(),
+            &patients_options(AggregateOutput::KAnonymized),
+        )
+        .await?;
+        assert_eq!(
+            String::from_utf8_lossy(&table.content),
+            "age,zip,visit_date,diagnosis,cost\n\
+            30-39,941*,2024-03,flu,120\n\
+            30-39,941*,2024-03,asthma,300\n\
+            30-39,941*,2024-03,flu,150\n"
+        );
+        assert_eq!(table.rows, 5);
+        assert_eq!(table.groups, 1);
+        assert_eq!(table.suppressed_rows, 2);
+        assert_eq!(table.removed_columns, vec!["name".to_string()]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn aggregate_counts_test() -> AppResult<()> {
+        let mut options = patients_options(AggregateOutput::Counts);
+        options.k = 1;
+        options.quasi_identifiers = vec!["zip".to_string()];
+        options.generalizations = vec![("zip".to_string(), Generalization::Prefix(3))];
+        let table = aggregate_csv(PATIENTS_CSV.as_bytes(), &options).await?;
+        assert_eq!(
+            String::from_utf8_lossy(&table.content),
+            "zip,count,diagnosis_distinct,cost_mean\n\
+            941*,4,2,165.00\n\
+            100*,1,1,800.00\n"
+        );
+
+        options.epsilon = Some(1000.0);
+        let noisy_table = aggregate_csv(PATIENTS_CSV.as_bytes(), &options).await?;
+        assert_eq!(noisy_table.content, table.content);
+
+        options.quasi_identifiers = vec!["ssn".to_string()];
+        assert!(aggregate_csv(PATIENTS_CSV.as_bytes(), &options)
+            .await
+            .is_err());
+        Ok(())
+    }
+}
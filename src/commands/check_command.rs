@@ -0,0 +1,179 @@
+use crate::args::RedacterType;
+use crate::errors::AppError;
+use crate::file_systems::FileSystemRef;
+use crate::redacters::{
+    RedactSupport, Redacter, RedacterDataItem, RedacterDataItemContent, RedacterOptions,
+    RedacterProviderOptions, Redacters,
+};
+use crate::reporter::{AppReporter, AppReporterOptions};
+use crate::AppResult;
+use console::{Style, Term};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct CheckCommandOptions {
+    pub reporter_options: AppReporterOptions,
+}
+
+impl CheckCommandOptions {
+    pub fn new(reporter_options: AppReporterOptions) -> Self {
+        CheckCommandOptions { reporter_options }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckProviderResult {
+    pub redacter_type: RedacterType,
+    pub duration: Duration,
+    /// Error of the failed check, None if the provider works
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckCommandResult {
+    pub providers: Vec<CheckProviderResult>,
+}
+
+impl CheckCommandResult {
+    pub fn failed(&self) -> usize {
+        self.providers
+            .iter()
+            .filter(|provider| provider.error.is_some())
+            .count()
+    }
+}
+
+/// Checks configured providers with tiny requests, so misconfigurations such as credentials,
+/// projects, regions, endpoints and models are found before copying any files
+#[tracing::instrument(name = "command_check", skip_all)]
+pub async fn command_check(
+    term: &Term,
+    options: CheckCommandOptions,
+    redacter_options: Option<RedacterOptions>,
+) -> AppResult<CheckCommandResult> {
+    let bold_style = Style::new().bold();
+    let app_reporter = AppReporter::from(term).with_options(&options.reporter_options);
+    let redacter_options = redacter_options
+        .filter(|redacter_options| !redacter_options.provider_options.is_empty())
+        .ok_or_else(|| AppError::RedacterConfigError {
+            message: "At least one redacter is required to check".to_string(),
+        })?;
+    app_reporter.report(format!(
+        "Checking redacters: {}.",
+        bold_style.clone().green().apply_to(&redacter_options)
+    ))?;
+
+    let mut providers = Vec::new();
+    for provider_options in redacter_options.provider_options.into_iter().chain(
+        redacter_options
+            .verify_options
+            .map(|verify_options| verify_options.provider_options),
+    ) {
+        let redacter_type = provider_options.redacter_type();
+        let started = Instant::now();
+        let check_result = check_provider(provider_options, &app_reporter).await;
+        let provider_result = CheckProviderResult {
+            redacter_type,
+            duration: started.elapsed(),
+            error: check_result.err().map(|err| err.to_string()),
+        };
+        match provider_result.error {
+            None => app_reporter.report(format!(
+                "{} {} ({} ms)",
+                bold_style.clone().green().apply_to("✓"),
+                bold_style.apply_to(&provider_result.redacter_type),
+                provider_result.duration.as_millis()
+            ))?,
+            Some(ref error) => app_reporter.report_error(format!(
+                "{} {}: {}\n  ↳ {}",
+                bold_style.clone().red().apply_to("✗"),
+                bold_style.apply_to(&provider_result.redacter_type),
+                error,
+                check_hint(&provider_result.redacter_type)
+            ))?,
+        }
+        providers.push(provider_result);
+    }
+    Ok(CheckCommandResult { providers })
+}
+
+async fn check_provider(
+    provider_options: RedacterProviderOptions,
+    reporter: &AppReporter<'_>,
+) -> AppResult<()> {
+    let redacter = Redacters::new_redacter(provider_options, reporter).await?;
+    let text_file_ref = FileSystemRef {
+        relative_path: "check.txt".into(),
+        media_type: Some(mime::TEXT_PLAIN),
+        file_size: None,
+        modified_at: None,
+        checksum: None,
+    };
+    let image_file_ref = FileSystemRef {
+        relative_path: "check.png".into(),
+        media_type: Some(mime::IMAGE_PNG),
+        file_size: None,
+        modified_at: None,
+        checksum: None,
+    };
+    // Providers configured only for images, such as MsPresidio with an image endpoint, are checked with an image
+    let check_item = if redacter.redact_support(&text_file_ref).await? == RedactSupport::Supported {
+        RedacterDataItem {
+            file_ref: text_file_ref,
+            content: RedacterDataItemContent::Value("Hello, my name is John Doe.".to_string()),
+        }
+    } else if redacter.redact_support(&image_file_ref).await? == RedactSupport::Supported {
+        let image = image::RgbImage::from_pixel(16, 16, image::Rgb([255, 255, 255]));
+        let mut image_bytes = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut image_bytes, image::ImageFormat::Png)?;
+        RedacterDataItem {
+            file_ref: image_file_ref,
+            content: RedacterDataItemContent::Image {
+                mime_type: mime::IMAGE_PNG,
+                data: image_bytes.into_inner().into(),
+            },
+        }
+    } else {
+        return Err(AppError::RedacterConfigError {
+            message: "Redacter doesn't support text or images with the specified options"
+                .to_string(),
+        });
+    };
+    redacter.redact(check_item).await?;
+    Ok(())
+}
+
+/// Most common causes of failed checks
+fn check_hint(redacter_type: &RedacterType) -> &'static str {
+    match redacter_type {
+        RedacterType::GcpDlp | RedacterType::GeminiLlm => "Check GCP credentials (gcloud auth application-default login or GOOGLE_APPLICATION_CREDENTIALS), --gcp-project-id and that the API is enabled in the project",
+        RedacterType::GcpVertexAi => "Check GCP credentials, --gcp-project-id, --gcp-region and that the models are available in the region",
+        RedacterType::AwsComprehend => "Check AWS credentials (AWS_PROFILE or AWS_ACCESS_KEY_ID) and --aws-region",
+        RedacterType::MsPresidio => "Check that Presidio endpoints specified by --ms-presidio-text-analyze-url and --ms-presidio-image-redact-url are reachable",
+        RedacterType::OpenAiLlm => "Check --open-ai-api-key and that the model specified by --open-ai-model is available for the key",
+        RedacterType::External => "Check that the program specified by --external-redacter-cmd runs and responds with JSON",
+        RedacterType::Dictionary => "Check the file specified by --dictionary-file",
+        RedacterType::Secrets => "Check the secrets redacter options",
+        RedacterType::Mock => "Check --mock-pattern regular expressions",
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::redacters::SecretsRedacterOptions;
+
+    #[tokio::test]
+    async fn check_local_provider_test() -> AppResult<()> {
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        check_provider(
+            RedacterProviderOptions::Secrets(SecretsRedacterOptions {
+                min_entropy: SecretsRedacterOptions::DEFAULT_MIN_ENTROPY,
+            }),
+            &reporter,
+        )
+        .await?;
+        Ok(())
+    }
+}
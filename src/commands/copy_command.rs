@@ -1,25 +1,517 @@
+use crate::commands::html_report::png_thumbnail;
+use crate::commands::{InteractiveReview, ReviewDecision};
 use crate::errors::AppError;
 use crate::file_converters::FileConverters;
-use crate::file_systems::{DetectFileSystem, FileSystemConnection, FileSystemRef};
-use crate::file_tools::{FileMatcher, FileMatcherResult, FileMimeOverride};
+use crate::file_systems::{
+    local_path_from_uri, DetectFileSystem, FileMetadata, FileSystemConnection, FileSystemOptions,
+    FileSystemRef, FileSystemStream, RelativeFilePath,
+};
+use crate::file_tools::{
+    FileFilters, FileMatcher, FileMatcherResult, FileMimeOverride, MemoryBudget, SpillBuffer,
+};
+use crate::metrics::{AppMetric, AppMetrics};
 use crate::redacters::{
-    RedacterBaseOptions, RedacterOptions, RedacterThrottler, Redacters, StreamRedacter,
+    AudioTranscriber, FaceRedacter, RedactFinding, RedactSupport, RedactVerifyAction,
+    RedactedImageRegion, Redacter, RedacterBaseOptions, RedacterDataItem, RedacterDataItemContent,
+    RedacterOptions, RedacterProviderUsage, RedacterThrottler, RedacterVerifier,
+    RedacterVolumeThrottler, Redacters, RedactionCache, RedactionCacheFingerprint,
+    RedactionDeduplicator, StreamRedacter,
 };
-use crate::reporter::AppReporter;
+use crate::reporter::{AppReporter, AppReporterOptions};
 use crate::AppResult;
+use clap::ValueEnum;
 use console::{pad_str, Alignment, Style, Term};
-use futures::Stream;
+use futures::future::RemoteHandle;
+use futures::{FutureExt, Stream, StreamExt, TryStreamExt};
 use gcloud_sdk::prost::bytes;
 use indicatif::*;
+use rvstruct::ValueStruct;
 use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::error::Error;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Once};
 use std::time::{Duration, Instant};
+use tracing::Instrument;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct CopyCommandResult {
+    /// Version of the JSON results schema, increased on incompatible changes of saved results
+    pub schema_version: u32,
     pub files_copied: usize,
     pub files_redacted: usize,
     pub files_skipped: usize,
+    pub files_quarantined: usize,
+    pub files_failed: usize,
+    /// Files left to copy if the run was stopped due to run limits
+    pub files_remaining: usize,
+    pub stopped_reason: Option<String>,
+    /// The run was stopped by Ctrl-C after finishing the current file
+    pub interrupted: bool,
+    /// Results of processed files except the ones failed and retried later
+    pub files: Vec<CopyFileResult>,
+    /// Calls and billable usage of redacter providers during the run by redacter types
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub provider_usage: BTreeMap<String, RedacterProviderUsage>,
+}
+
+impl CopyCommandResult {
+    pub const SCHEMA_VERSION: u32 = 1;
+}
+
+/// Results of processed files for JSON and HTML reports
+#[derive(Debug, Clone, Serialize)]
+pub struct CopyFileResult {
+    pub source: String,
+    /// Destination of copied files, which might differ from the source name for converted files
+    pub destination: Option<String>,
+    pub media_type: Option<String>,
+    pub file_size: Option<usize>,
+    /// Bytes uploaded to the destination, which differ from the source size for redacted files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination_size: Option<usize>,
+    pub result: TransferFileResult,
+    pub redactions: usize,
+    /// Types of redacters applied to the file such as `gcp-dlp`
+    pub redacters: Vec<String>,
+    /// Bounding boxes of redacted regions of images, and of pages of PDF and TIFF files
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub redacted_regions: Vec<RedactedImageRegion>,
+    /// Entities still detected in redacted files by verification, with their context for triage
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub findings: Vec<RedactFinding>,
+    /// Reasons of skipped, quarantined and failed files, and warnings for copied files
+    pub reason: Option<String>,
+    /// Stable codes of errors of failed files such as `provider_error`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    /// Time spent on downloading, redacting and uploading the file
+    pub duration_ms: u64,
+    /// Failed files are retried with --file-retries unless they failed with errors which aren't retryable
+    #[serde(skip)]
+    pub retryable: bool,
+    /// PNG thumbnails of redacted images for HTML reports
+    #[serde(skip)]
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+impl CopyFileResult {
+    pub const THUMBNAIL_SIZE: u32 = 160;
+
+    pub fn new(source: String, file_ref: &FileSystemRef, result: TransferFileResult) -> Self {
+        Self {
+            source,
+            destination: None,
+            media_type: file_ref
+                .media_type
+                .as_ref()
+                .map(|media_type| media_type.to_string()),
+            file_size: file_ref.file_size,
+            destination_size: None,
+            result,
+            redactions: 0,
+            redacters: Vec::new(),
+            redacted_regions: Vec::new(),
+            findings: Vec::new(),
+            reason: None,
+            error_code: None,
+            duration_ms: 0,
+            retryable: true,
+            thumbnail: None,
+        }
+    }
+
+    pub fn with_reason<S: Into<String>>(self, reason: S) -> Self {
+        Self {
+            reason: Some(reason.into()),
+            ..self
+        }
+    }
+
+    pub fn with_error(self, error: &AppError) -> Self {
+        Self {
+            error_code: Some(error.code().to_string()),
+            retryable: error.is_retryable(),
+            ..self
+        }
+    }
+}
+
+/// Exit codes for partial failures, so orchestration systems can react to them
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CopyExitCodePolicy {
+    pub fail_on_error: bool,
+    pub fail_on_skipped: bool,
+    pub fail_on_empty: bool,
+}
+
+impl CopyExitCodePolicy {
+    pub const EXIT_CODE_FILES_FAILED: i32 = 2;
+    pub const EXIT_CODE_NOTHING_COPIED: i32 = 3;
+    pub const EXIT_CODE_LIMIT_REACHED: i32 = 4;
+    /// Conventional exit code of processes stopped by SIGINT
+    pub const EXIT_CODE_INTERRUPTED: i32 = 130;
+
+    /// Returns the exit code and the reason if the result doesn't satisfy the policy.
+    /// Runs stopped due to run limits or Ctrl-C always fail, since some files weren't copied.
+    pub fn check(&self, result: &CopyCommandResult) -> Option<(i32, String)> {
+        if result.interrupted {
+            Some((
+                Self::EXIT_CODE_INTERRUPTED,
+                format!("Interrupted. {} files remaining", result.files_remaining),
+            ))
+        } else if let Some(ref stopped_reason) = result.stopped_reason {
+            Some((
+                Self::EXIT_CODE_LIMIT_REACHED,
+                format!(
+                    "{}. {} files remaining",
+                    stopped_reason, result.files_remaining
+                ),
+            ))
+        } else if self.fail_on_error && result.files_failed > 0 {
+            Some((
+                Self::EXIT_CODE_FILES_FAILED,
+                format!("{} files failed to be redacted", result.files_failed),
+            ))
+        } else if self.fail_on_skipped && result.files_skipped > 0 {
+            Some((
+                Self::EXIT_CODE_FILES_FAILED,
+                format!("{} files were skipped", result.files_skipped),
+            ))
+        } else if self.fail_on_empty && result.files_copied == 0 {
+            Some((
+                Self::EXIT_CODE_NOTHING_COPIED,
+                "No files were copied".to_string(),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// Safety limits of a single run, so an accidentally broad source or filter
+/// can't exhaust the bandwidth or API budget
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CopyRunLimits {
+    pub max_bytes: Option<u64>,
+    pub max_api_calls: Option<u64>,
+}
+
+impl CopyRunLimits {
+    /// Returns the reason to stop the run before copying the next file
+    fn check(&self, usage: &CopyRunUsage, next_file_ref: &FileSystemRef) -> Option<String> {
+        let next_file_size = next_file_ref.file_size.unwrap_or(0) as u64;
+        match (self.max_bytes, self.max_api_calls) {
+            (Some(max_bytes), _) if usage.bytes + next_file_size > max_bytes => Some(format!(
+                "Limit of {} bytes per run reached with {} bytes processed",
+                max_bytes, usage.bytes
+            )),
+            (_, Some(max_api_calls)) if usage.api_calls >= max_api_calls => Some(format!(
+                "Limit of {} API calls per run reached",
+                max_api_calls
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Bytes and API calls of the current run, counted using the global metrics
+#[derive(Debug, Clone, Copy, Default)]
+struct CopyRunUsage {
+    bytes: u64,
+    api_calls: u64,
+}
+
+impl CopyRunUsage {
+    fn current() -> Self {
+        let metrics = AppMetrics::global();
+        Self {
+            bytes: metrics.total(AppMetric::BYTES_PROCESSED) as u64,
+            api_calls: metrics.total(AppMetric::PROVIDER_CALLS) as u64,
+        }
+    }
+
+    fn since(&self, started: &CopyRunUsage) -> Self {
+        Self {
+            bytes: self.bytes.saturating_sub(started.bytes),
+            api_calls: self.api_calls.saturating_sub(started.api_calls),
+        }
+    }
+}
+
+/// Ctrl-C stops copying after the current file instead of aborting uploads, so destinations such as zip files
+/// are finalized and the run can be continued with --if-newer. Ctrl-C outside of copying or pressed twice
+/// exits immediately as without the handler.
+struct CopyInterruption {
+    copying: AtomicBool,
+    requested: AtomicBool,
+    listening: Once,
+}
+
+static COPY_INTERRUPTION: CopyInterruption = CopyInterruption {
+    copying: AtomicBool::new(false),
+    requested: AtomicBool::new(false),
+    listening: Once::new(),
+};
+
+impl CopyInterruption {
+    /// Signal handlers can't be uninstalled, so a single listener serves all runs of the process
+    fn start(&'static self) -> CopyInterruptionGuard {
+        self.requested.store(false, Ordering::SeqCst);
+        self.copying.store(true, Ordering::SeqCst);
+        self.listening.call_once(|| {
+            tokio::spawn(async move {
+                while tokio::signal::ctrl_c().await.is_ok() {
+                    if !self.copying.load(Ordering::SeqCst)
+                        || self.requested.swap(true, Ordering::SeqCst)
+                    {
+                        std::process::exit(CopyExitCodePolicy::EXIT_CODE_INTERRUPTED);
+                    }
+                    tracing::warn!("Interrupted, stopping after the current file");
+                    let term = Term::stderr();
+                    let _ = AppReporter::from(&term).report_error(format!(
+                        "{}. Stopping after the current file, press Ctrl-C again to abort",
+                        Style::new().bold().yellow().apply_to("Interrupted")
+                    ));
+                }
+            });
+        });
+        CopyInterruptionGuard
+    }
+
+    fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+}
+
+/// Restores the immediate exit on Ctrl-C when the run finishes, including runs failed with errors
+struct CopyInterruptionGuard;
+
+impl Drop for CopyInterruptionGuard {
+    fn drop(&mut self) {
+        COPY_INTERRUPTION.copying.store(false, Ordering::SeqCst);
+    }
+}
+
+/// How files colliding in the destination are copied: files with the same relative paths
+/// in multiple sources (`--on-collision`) or files already existing in the destination (`--on-conflict`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum CopyCollisionMode {
+    #[default]
+    Fail,
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+impl Display for CopyCollisionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CopyCollisionMode::Fail => write!(f, "fail"),
+            CopyCollisionMode::Skip => write!(f, "skip"),
+            CopyCollisionMode::Overwrite => write!(f, "overwrite"),
+            CopyCollisionMode::Rename => write!(f, "rename"),
+        }
+    }
+}
+
+impl CopyCollisionMode {
+    /// Merges files listed in sources (in the order of sources) resolving their destination paths.
+    /// Returns the files to copy and the number of files skipped due to collisions.
+    fn merge_sources(
+        &self,
+        sources: &[String],
+        source_files: Vec<Vec<FileSystemRef>>,
+    ) -> AppResult<(Vec<CopySourceFile>, usize)> {
+        // Renamed files shouldn't take paths of any other listed files
+        let listed_paths: HashSet<String> = source_files
+            .iter()
+            .flatten()
+            .map(|file_ref| file_ref.relative_path.value().clone())
+            .collect();
+        let mut destination_sources: HashMap<String, usize> = HashMap::new();
+        let mut merged_files = Vec::new();
+        let mut skipped = 0;
+        for (source_idx, files) in source_files.into_iter().enumerate() {
+            for file_ref in files {
+                let relative_path = file_ref.relative_path.value().clone();
+                let destination_path = match destination_sources.get(&relative_path) {
+                    None => None,
+                    Some(_) if *self == CopyCollisionMode::Overwrite => None,
+                    Some(_) if *self == CopyCollisionMode::Skip => {
+                        skipped += 1;
+                        continue;
+                    }
+                    Some(_) if *self == CopyCollisionMode::Rename => {
+                        let mut suffix = source_idx + 1;
+                        let mut renamed_path = Self::renamed_path(&relative_path, suffix);
+                        while destination_sources.contains_key(&renamed_path)
+                            || listed_paths.contains(&renamed_path)
+                        {
+                            suffix += 1;
+                            renamed_path = Self::renamed_path(&relative_path, suffix);
+                        }
+                        Some(renamed_path)
+                    }
+                    Some(other_source_idx) => {
+                        return Err(AppError::DestinationCollision {
+                            relative_path,
+                            sources: format!(
+                                "{}, {}",
+                                sources[*other_source_idx], sources[source_idx]
+                            ),
+                        });
+                    }
+                };
+                destination_sources.insert(
+                    destination_path.clone().unwrap_or(relative_path),
+                    source_idx,
+                );
+                merged_files.push(CopySourceFile {
+                    source_idx,
+                    file_ref,
+                    destination_path: destination_path.map(RelativeFilePath),
+                });
+            }
+        }
+        Ok((merged_files, skipped))
+    }
+
+    /// Appends the suffix to the file name before its extension, such as `dir/report-2.pdf`
+    fn renamed_path(relative_path: &str, suffix: usize) -> String {
+        let file_name_start = relative_path.rfind('/').map(|idx| idx + 1).unwrap_or(0);
+        match relative_path[file_name_start..].rfind('.') {
+            Some(extension_idx) if extension_idx > 0 => {
+                let (stem, extension) = relative_path.split_at(file_name_start + extension_idx);
+                format!("{}-{}{}", stem, suffix, extension)
+            }
+            _ => format!("{}-{}", relative_path, suffix),
+        }
+    }
+}
+
+/// Order of copying listed files, such as small text files first to get results early
+/// or large files first when they're reviewed while the rest is copied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum CopyFileOrder {
+    /// Files are copied in the order they're listed by sources
+    #[default]
+    Listed,
+    SmallestFirst,
+    LargestFirst,
+    /// Files of the same media types are copied together, ordered by media types
+    ByMime,
+}
+
+impl Display for CopyFileOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CopyFileOrder::Listed => write!(f, "listed"),
+            CopyFileOrder::SmallestFirst => write!(f, "smallest-first"),
+            CopyFileOrder::LargestFirst => write!(f, "largest-first"),
+            CopyFileOrder::ByMime => write!(f, "by-mime"),
+        }
+    }
+}
+
+impl CopyFileOrder {
+    /// Sorts files keeping the listed order of files with the same sizes or media types.
+    /// Files without known sizes or media types are copied last.
+    fn sort_files(&self, files: &mut [CopySourceFile]) {
+        match self {
+            CopyFileOrder::Listed => {}
+            CopyFileOrder::SmallestFirst => {
+                files.sort_by_key(|file| file.file_ref.file_size.unwrap_or(usize::MAX))
+            }
+            CopyFileOrder::LargestFirst => files.sort_by_key(|file| {
+                std::cmp::Reverse(file.file_ref.file_size.map(|size| size as u128 + 1))
+            }),
+            CopyFileOrder::ByMime => {
+                files.sort_by(
+                    |a, b| match (&a.file_ref.media_type, &b.file_ref.media_type) {
+                        (Some(a), Some(b)) => a.essence_str().cmp(b.essence_str()),
+                        (Some(_), None) => std::cmp::Ordering::Less,
+                        (None, Some(_)) => std::cmp::Ordering::Greater,
+                        (None, None) => std::cmp::Ordering::Equal,
+                    },
+                )
+            }
+        }
+    }
+}
+
+/// A listed file of one of the sources and its path in the destination if it differs due to collisions
+#[derive(Debug, Clone)]
+struct CopySourceFile {
+    source_idx: usize,
+    file_ref: FileSystemRef,
+    destination_path: Option<RelativeFilePath>,
+}
+
+/// A file downloaded in a background task while previous files are redacted and uploaded.
+/// Downloads are cancelled when prefetched files are dropped, such as when runs are stopped
+struct CopyPrefetchedFile {
+    file_ref: FileSystemRef,
+    content: RemoteHandle<AppResult<SpillBuffer>>,
+}
+
+impl CopyPrefetchedFile {
+    /// Starts downloading the file if the source supports it. Files failed to start downloading
+    /// are downloaded again when they are copied, so their errors are reported and retried as usual.
+    /// Files larger than the memory budget are downloaded when they are copied, and content of
+    /// prefetched files beyond the budget is kept in work files
+    async fn start<'a, SFS: FileSystemConnection<'a>>(
+        source_fs: &mut SFS,
+        source_file: &CopySourceFile,
+        options: &CopyCommandOptions,
+        memory_budget: &MemoryBudget,
+    ) -> Option<Self> {
+        if options.if_newer
+            || options.if_changed
+            || !source_fs.supports_prefetch()
+            || !source_file
+                .file_ref
+                .file_size
+                .is_some_and(|file_size| file_size <= memory_budget.limit())
+        {
+            return None;
+        }
+        match source_fs.download(Some(&source_file.file_ref)).await {
+            Ok((file_ref, stream)) => {
+                let memory_budget = memory_budget.clone();
+                let file_system_options = options.file_system_options.clone();
+                let (download, content) = async move {
+                    SpillBuffer::collect(stream, &memory_budget, &file_system_options).await
+                }
+                .remote_handle();
+                tokio::spawn(download);
+                Some(Self { file_ref, content })
+            }
+            Err(error) => {
+                tracing::debug!(error = %error, "Prefetching failed");
+                None
+            }
+        }
+    }
+
+    fn into_download(self) -> (FileSystemRef, FileSystemStream) {
+        (
+            self.file_ref,
+            Box::new(futures::stream::once(
+                self.content
+                    .map(|content| content.and_then(|content| content.into_bytes())),
+            )),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct QuarantineRecord {
+    relative_path: String,
+    media_type: Option<String>,
+    file_size: Option<usize>,
+    reason: String,
 }
 
 #[derive(Debug, Clone)]
@@ -27,173 +519,718 @@ pub struct CopyCommandOptions {
     pub file_matcher: FileMatcher,
     pub file_mime_override: FileMimeOverride,
     pub max_files_limit: Option<usize>,
+    pub run_limits: CopyRunLimits,
+    pub file_retries: usize,
+    /// Number of next files downloaded while the current file is redacted and uploaded
+    pub prefetch_files: usize,
+    /// Memory for content of prefetched files waiting to be redacted, content beyond it
+    /// is kept in work files until the file is redacted
+    pub memory_budget: usize,
+    pub if_newer: bool,
+    pub if_changed: bool,
+    /// Files marked as redacted by previous runs are redacted again
+    pub force: bool,
+    pub quarantine: Option<String>,
+    pub on_collision: CopyCollisionMode,
+    /// Files already existing in the destination are checked unless they're overwritten
+    pub on_conflict: CopyCollisionMode,
+    pub order: CopyFileOrder,
+    pub dedup: bool,
+    pub preserve_metadata: bool,
+    pub scrub_metadata: Vec<String>,
+    pub in_place: bool,
+    pub backup_suffix: Option<String>,
+    pub interactive: bool,
+    /// Relative paths of copied files are redacted, so file names don't leak PII
+    pub redact_file_names: bool,
+    /// Thumbnails of redacted images are kept for HTML reports
+    pub report_thumbnails: bool,
+    pub file_system_options: FileSystemOptions,
+    pub reporter_options: AppReporterOptions,
 }
 
-impl CopyCommandOptions {
-    pub fn new(
-        filename_filter: Option<globset::Glob>,
-        max_size_limit: Option<usize>,
-        max_files_limit: Option<usize>,
-        mime_override: Vec<(mime::Mime, globset::Glob)>,
-    ) -> Self {
-        let filename_matcher = filename_filter
-            .as_ref()
-            .map(|filter| filter.compile_matcher());
+impl Default for CopyCommandOptions {
+    fn default() -> Self {
         CopyCommandOptions {
-            file_matcher: FileMatcher::new(filename_matcher, max_size_limit),
-            file_mime_override: FileMimeOverride::new(mime_override),
-            max_files_limit,
+            file_matcher: FileMatcher::new(FileFilters::default(), None),
+            file_mime_override: FileMimeOverride::new(Vec::new()),
+            max_files_limit: None,
+            run_limits: CopyRunLimits::default(),
+            file_retries: 0,
+            prefetch_files: 1,
+            memory_budget: Self::DEFAULT_MEMORY_BUDGET,
+            if_newer: false,
+            if_changed: false,
+            force: false,
+            quarantine: None,
+            on_collision: CopyCollisionMode::default(),
+            on_conflict: CopyCollisionMode::Overwrite,
+            order: CopyFileOrder::default(),
+            dedup: true,
+            preserve_metadata: false,
+            scrub_metadata: Vec::new(),
+            in_place: false,
+            backup_suffix: None,
+            interactive: false,
+            redact_file_names: false,
+            report_thumbnails: false,
+            file_system_options: FileSystemOptions::default(),
+            reporter_options: AppReporterOptions::default(),
+        }
+    }
+}
+
+impl CopyCommandOptions {
+    pub const IN_PLACE_TEMP_SUFFIX: &'static str = ".redacter-tmp";
+    pub const DEFAULT_MEMORY_BUDGET: usize = 256 * 1024 * 1024;
+
+    /// Files redacted in place are uploaded next to the originals first,
+    /// so the originals are replaced only after successful uploads
+    pub fn upload_file_ref(&self, file_ref: &FileSystemRef) -> FileSystemRef {
+        if self.in_place {
+            FileSystemRef {
+                relative_path: format!(
+                    "{}{}",
+                    file_ref.relative_path.value(),
+                    Self::IN_PLACE_TEMP_SUFFIX
+                )
+                .into(),
+                ..file_ref.clone()
+            }
+        } else {
+            file_ref.clone()
         }
     }
+
+    /// Temporary files of interrupted in-place runs and backups aren't redacted in place again
+    pub fn is_in_place_file(&self, file_ref: &FileSystemRef) -> bool {
+        let relative_path = file_ref.relative_path.value();
+        relative_path.ends_with(Self::IN_PLACE_TEMP_SUFFIX)
+            || self
+                .backup_suffix
+                .as_ref()
+                .is_some_and(|backup_suffix| relative_path.ends_with(backup_suffix.as_str()))
+    }
+
+    /// Checks if the existing destination file doesn't need to be copied again.
+    /// Copies are compared with the sources recorded in their metadata by previous runs,
+    /// since redacted copies differ from their sources. Without recorded sources, checksums
+    /// are compared only if both sides report the same kind of checksum,
+    /// otherwise it falls back to the size and modification time.
+    pub fn is_destination_up_to_date(
+        &self,
+        source_file_ref: &FileSystemRef,
+        destination_file_ref: &FileSystemRef,
+        destination_metadata: Option<&FileMetadata>,
+    ) -> bool {
+        let destination_is_newer = matches!(
+            (source_file_ref.modified_at, destination_file_ref.modified_at),
+            (Some(source_modified_at), Some(destination_modified_at)) if destination_modified_at >= source_modified_at
+        );
+        let destination_is_same = match destination_metadata
+            .and_then(|metadata| metadata.is_copied_from(source_file_ref))
+        {
+            Some(copied_from) => copied_from,
+            None => match (
+                source_file_ref.checksum.as_ref(),
+                destination_file_ref.checksum.as_ref(),
+            ) {
+                (Some(source_checksum), Some(destination_checksum))
+                    if source_checksum.is_comparable_with(destination_checksum) =>
+                {
+                    source_checksum == destination_checksum
+                }
+                _ => {
+                    source_file_ref.file_size.is_some()
+                        && source_file_ref.file_size == destination_file_ref.file_size
+                        && destination_is_newer
+                }
+            },
+        };
+        (self.if_newer && destination_is_newer) || (self.if_changed && destination_is_same)
+    }
 }
 
+#[tracing::instrument(name = "command_copy", skip_all, fields(source = sources.join(",").as_str(), destination = destination))]
 pub async fn command_copy(
     term: &Term,
-    source: &str,
+    sources: &[String],
     destination: &str,
     options: CopyCommandOptions,
     redacter_options: Option<RedacterOptions>,
 ) -> AppResult<CopyCommandResult> {
-    let term_reporter = AppReporter::from(term);
-    let file_converters = FileConverters::new().init(&term_reporter).await?;
+    if options.in_place {
+        if sources.len() != 1 || !is_same_location(&sources[0], destination) {
+            return Err(AppError::InPlaceNotSupported {
+                source_path: sources.join(", "),
+                reason: "the destination must be the same as the single source".to_string(),
+            });
+        }
+    } else if let Some(source) = sources
+        .iter()
+        .find(|source| is_same_location(source, destination))
+    {
+        return Err(AppError::SourceIsDestination {
+            source_path: source.clone(),
+        });
+    }
+    if options.redact_file_names && redacter_options.is_none() {
+        return Err(AppError::RedacterConfigError {
+            message: "Redacters are required to redact file names".to_string(),
+        });
+    }
+    if options.interactive && !term.is_term() {
+        return Err(AppError::SystemError {
+            message: "Interactive review requires a terminal".to_string(),
+        });
+    }
+    let started = Instant::now();
+    let _interruption_guard = COPY_INTERRUPTION.start();
+    let provider_usage_at_start = RedacterProviderUsage::collect(AppMetrics::global());
+    let usage_started = CopyRunUsage::current();
+    // Failure counters are always reported, so alerts can rely on them
+    AppMetrics::global().add(AppMetric::FAILURES, &[], 0.0);
+    AppMetrics::global().add(AppMetric::RETRIES, &[("operation", "download")], 0.0);
+    AppMetrics::global().add(AppMetric::RETRIES, &[("operation", "file")], 0.0);
+    let term_reporter = AppReporter::from(term).with_options(&options.reporter_options);
+    let ocr_options = redacter_options
+        .as_ref()
+        .map(|options| options.ocr_options.clone())
+        .unwrap_or_default();
+    let file_converters = FileConverters::new()
+        .init(&term_reporter, &ocr_options)
+        .await?
+        .with_ocr_cache(
+            &ocr_options,
+            redacter_options
+                .as_ref()
+                .and_then(|options| options.cache_options.clone()),
+        )
+        .await?;
 
     report_copy_info(
-        term,
-        source,
+        &term_reporter,
+        &sources.join(", "),
         destination,
         &redacter_options,
         &file_converters,
     )
     .await?;
 
-    let bar = ProgressBar::new(1);
-    bar.set_style(
-        ProgressStyle::with_template(
-            "{spinner:.green} [{elapsed_precise}] [{wide_bar:.green/237}] {pos:>3}/{len:3}",
-        )?
-        .progress_chars("━>─"),
-    );
-    bar.enable_steady_tick(Duration::from_millis(100));
-    let app_reporter = AppReporter::from(&bar);
+    let bar = if options.reporter_options.progress {
+        let bar = ProgressBar::new(1);
+        bar.set_style(
+            ProgressStyle::with_template(
+                "{spinner:.green} [{elapsed_precise}] [{wide_bar:.green/237}] {pos:>3}/{len:3} {msg}",
+            )?
+            .progress_chars("━>─"),
+        );
+        bar.enable_steady_tick(Duration::from_millis(100));
+        bar
+    } else {
+        ProgressBar::hidden()
+    };
+    let app_reporter = AppReporter::from(&bar).with_options(&options.reporter_options);
 
-    let mut source_fs = DetectFileSystem::open(source, &app_reporter).await?;
-    let mut destination_fs = DetectFileSystem::open(destination, &app_reporter).await?;
-    let mut redacter_throttler = redacter_options
+    let mut source_fss = Vec::with_capacity(sources.len());
+    for source in sources {
+        source_fss.push(
+            DetectFileSystem::open(source, &app_reporter, &options.file_system_options).await?,
+        );
+    }
+    // Multiple sources are always copied as directories merged into the destination
+    let has_multiple_files = match source_fss.as_slice() {
+        [source_fs] => source_fs.has_multiple_files().await?,
+        _ => {
+            for (source, source_fs) in sources.iter().zip(source_fss.iter()) {
+                if !source_fs.has_multiple_files().await? {
+                    return Err(AppError::SourceDoesNotSupportMultipleSources {
+                        source_path: source.clone(),
+                    });
+                }
+            }
+            true
+        }
+    };
+    let mut destination_fs =
+        DetectFileSystem::open(destination, &app_reporter, &options.file_system_options).await?;
+    if options.in_place {
+        if !has_multiple_files {
+            return Err(AppError::InPlaceNotSupported {
+                source_path: destination.to_string(),
+                reason: "only directories can be redacted in place. Trailing slash needed?"
+                    .to_string(),
+            });
+        }
+        if !destination_fs.supports_file_operations() {
+            return Err(AppError::InPlaceNotSupported {
+                source_path: destination.to_string(),
+                reason: "only local, GCS and S3 directories can be redacted in place".to_string(),
+            });
+        }
+    }
+    let mut quarantine_fs = match options.quarantine {
+        Some(ref quarantine) => Some(
+            DetectFileSystem::open(quarantine, &app_reporter, &options.file_system_options).await?,
+        ),
+        None => None,
+    };
+    // Shared with stream redacters, since pages of PDF and TIFF files are throttled as separate requests
+    let redacter_throttler = Mutex::new(
+        redacter_options
+            .as_ref()
+            .and_then(|o| o.base_options.limit_dlp_requests.clone())
+            .map(|limit| limit.to_throttling_counter()),
+    );
+    let entity_mappings = redacter_options
+        .as_ref()
+        .and_then(|o| o.base_options.entity_mappings.clone());
+    let mut redacter_volume_throttler = redacter_options
         .as_ref()
-        .and_then(|o| o.base_options.limit_dlp_requests.clone())
+        .and_then(|o| o.base_options.limit_dlp_volume.clone())
         .map(|limit| limit.to_throttling_counter());
 
-    let maybe_redacters = match redacter_options {
-        Some(options) => {
+    let (
+        mut maybe_redacters,
+        maybe_verifier,
+        maybe_face_redacter,
+        maybe_audio_transcriber,
+        maybe_redaction_cache,
+    ) = match redacter_options {
+        Some(mut options) => {
+            let redaction_cache = match options.cache_options.take() {
+                // Surrogates of cached results wouldn't be registered in the mappings of the run,
+                // so new entities could get the same surrogates as entities of cached results
+                Some(_) if options.base_options.entity_mappings.is_some() => {
+                    app_reporter.report(format!(
+                        "{}: redaction cache is disabled with pseudonymization",
+                        Style::new().yellow().apply_to("Warning")
+                    ))?;
+                    None
+                }
+                Some(cache_options) => Some(
+                    RedactionCache::new(
+                        cache_options,
+                        &RedactionCacheFingerprint::new(&options)?.to_json()?,
+                        &app_reporter,
+                    )
+                    .await?,
+                ),
+                None => None,
+            };
             let mut redacters = Vec::with_capacity(options.provider_options.len());
             for provider_options in options.provider_options {
                 let redacter = Redacters::new_redacter(provider_options, &app_reporter).await?;
                 redacters.push(redacter);
             }
-            Some((options.base_options, redacters))
+            let verifier = match options.verify_options {
+                Some(verify_options) => {
+                    Some(RedacterVerifier::new(verify_options, &app_reporter).await?)
+                }
+                None => None,
+            };
+            let face_redacter = match options.face_redacter_options {
+                Some(face_redacter_options) => {
+                    Some(FaceRedacter::new(face_redacter_options, &app_reporter).await?)
+                }
+                None => None,
+            };
+            let audio_transcriber = match options.audio_transcriber_options {
+                Some(audio_transcriber_options) => {
+                    Some(AudioTranscriber::new(audio_transcriber_options, &app_reporter).await?)
+                }
+                None => None,
+            };
+            (
+                Some((options.base_options, redacters)),
+                verifier,
+                face_redacter,
+                audio_transcriber,
+                redaction_cache,
+            )
         }
-        None => None,
+        None => (None, None, None, None, None),
     };
 
-    let copy_result: AppResult<CopyCommandResult> = if source_fs.has_multiple_files().await? {
+    let copy_result: AppResult<CopyCommandResult> = if has_multiple_files {
         if !destination_fs.accepts_multiple_files().await? {
             return Err(AppError::DestinationDoesNotSupportMultipleFiles {
                 destination: destination.to_string(),
             });
         }
-        bar.println("Copying directory and listing source files...");
-        let source_files_result = source_fs
-            .list_files(Some(&options.file_matcher), options.max_files_limit)
-            .await?;
-        let source_files: Vec<FileSystemRef> = source_files_result.files;
+        if let Some(ref quarantine_fs) = quarantine_fs {
+            if !quarantine_fs.accepts_multiple_files().await? {
+                return Err(AppError::DestinationDoesNotSupportMultipleFiles {
+                    destination: options.quarantine.clone().unwrap_or_default(),
+                });
+            }
+        }
+        app_reporter.report("Copying directory and listing source files...")?;
+        let mut listed_files = Vec::with_capacity(source_fss.len());
+        let mut files_skipped_by_listing = 0;
+        for source_fs in source_fss.iter_mut() {
+            let mut source_files_result = source_fs
+                .list_files(Some(&options.file_matcher), options.max_files_limit)
+                .await?;
+            if options.in_place {
+                source_files_result
+                    .files
+                    .retain(|file_ref| !options.is_in_place_file(file_ref));
+            }
+            files_skipped_by_listing += source_files_result.skipped;
+            listed_files.push(source_files_result.files);
+        }
+        let (mut source_files, files_collided) =
+            options.on_collision.merge_sources(sources, listed_files)?;
+        if let Some(max_files_limit) = options.max_files_limit {
+            source_files.truncate(max_files_limit);
+        }
+        options.order.sort_files(&mut source_files);
+        if files_collided > 0 {
+            app_reporter.report(format!(
+                "Skipping {} files already found in other sources",
+                Style::new().bold().yellow().apply_to(files_collided)
+            ))?;
+        }
         let files_found = source_files.len();
         let files_total_size: usize = source_files
             .iter()
-            .map(|file| file.file_size.unwrap_or(0))
+            .map(|file| file.file_ref.file_size.unwrap_or(0))
             .sum();
         let bold_style = Style::new().bold();
-        bar.println(
-            format!(
-                "Found {} files. Total size: {}",
-                bold_style.apply_to(files_found),
-                bold_style.apply_to(HumanBytes(files_total_size as u64))
-            )
-            .as_str(),
-        );
+        app_reporter.report(format!(
+            "Found {} files. Total size: {}",
+            bold_style.apply_to(files_found),
+            bold_style.apply_to(HumanBytes(files_total_size as u64))
+        ))?;
 
         bar.set_length(files_found as u64);
 
+        let mut maybe_redaction_deduplicator = if options.dedup && maybe_redacters.is_some() {
+            let redaction_deduplicator = RedactionDeduplicator::new(
+                &source_files
+                    .iter()
+                    .map(|file| file.file_ref.clone())
+                    .collect::<Vec<_>>(),
+                &app_reporter,
+            );
+            let duplicate_candidates = redaction_deduplicator.number_of_candidates();
+            if duplicate_candidates > 0 {
+                app_reporter.report(format!(
+                    "Found {} files with the same sizes. Identical content is redacted once",
+                    bold_style.apply_to(duplicate_candidates)
+                ))?;
+            }
+            Some(redaction_deduplicator)
+        } else {
+            None
+        };
+
+        if let Some((ref base_options, ref mut redacters)) = maybe_redacters {
+            prepare_batch_redaction(
+                &app_reporter,
+                &mut source_fss,
+                &source_files,
+                base_options,
+                redacters,
+            )
+            .await?;
+        }
+
         let mut total_files_copied = 0;
         let mut total_files_redacted = 0;
-        let mut total_files_skipped = source_files_result.skipped;
-        for source_file in source_files {
-            match transfer_and_redact_file(
-                term,
-                &bar,
-                Some(&source_file),
-                &mut source_fs,
-                &mut destination_fs,
-                &options,
-                &maybe_redacters,
-                &file_converters,
-                &mut redacter_throttler,
-            )
-            .await?
-            {
-                TransferFileResult::Copied => total_files_copied += 1,
-                TransferFileResult::RedactedAndCopied => {
-                    total_files_redacted += 1;
-                    total_files_copied += 1;
+        let mut total_files_skipped = files_skipped_by_listing + files_collided;
+        let mut total_files_quarantined = 0;
+        let mut total_files_failed = 0;
+        let mut files_remaining = 0;
+        let mut stopped_reason = None;
+        let mut interrupted = false;
+        let mut files = Vec::new();
+        let mut pending_files = source_files;
+        let mut retry_attempt = 0;
+        // Failed files are retried after the main pass, so transient errors
+        // don't stop copying the rest of the files
+        while !pending_files.is_empty() {
+            let is_last_attempt = retry_attempt >= options.file_retries;
+            let mut failed_files = Vec::new();
+            let mut pending_queue: VecDeque<CopySourceFile> = pending_files.into();
+            // Prefetched files are the first of the pending files in the same order,
+            // None for files which aren't prefetched
+            let mut prefetched_files: VecDeque<Option<CopyPrefetchedFile>> = VecDeque::new();
+            let memory_budget = MemoryBudget::new(options.memory_budget);
+            while let Some(source_file) = pending_queue.pop_front() {
+                let prefetched_file = prefetched_files.pop_front().flatten();
+                interrupted = COPY_INTERRUPTION.is_requested();
+                let maybe_stop_reason = if interrupted {
+                    Some("Interrupted".to_string())
+                } else {
+                    options.run_limits.check(
+                        &CopyRunUsage::current().since(&usage_started),
+                        &source_file.file_ref,
+                    )
+                };
+                if let Some(reason) = maybe_stop_reason {
+                    files_remaining = 1 + pending_queue.len() + failed_files.len();
+                    app_reporter.report_error(format!(
+                        "{}. Stopping with {} files remaining. Run the command again with --if-newer to continue",
+                        Style::new().bold().yellow().apply_to(&reason),
+                        Style::new().bold().apply_to(files_remaining)
+                    ))?;
+                    tracing::warn!(files_remaining, reason = reason.as_str(), "Run stopped");
+                    stopped_reason = Some(reason);
+                    failed_files.clear();
+                    break;
+                }
+                while prefetched_files.len() < options.prefetch_files.min(pending_queue.len()) {
+                    let next_file = &pending_queue[prefetched_files.len()];
+                    prefetched_files.push_back(
+                        CopyPrefetchedFile::start(
+                            &mut source_fss[next_file.source_idx],
+                            next_file,
+                            &options,
+                            &memory_budget,
+                        )
+                        .await,
+                    );
+                }
+                let file_started = Instant::now();
+                let file_span = tracing::info_span!(
+                    "transfer_file",
+                    file = source_file.file_ref.relative_path.value().as_str()
+                );
+                let file_result = match transfer_and_redact_file(
+                    term,
+                    &app_reporter,
+                    &bar,
+                    Some(&source_file.file_ref),
+                    source_file.destination_path.as_ref(),
+                    prefetched_file,
+                    &mut source_fss[source_file.source_idx],
+                    &mut destination_fs,
+                    &mut quarantine_fs,
+                    &options,
+                    &maybe_redacters,
+                    &maybe_verifier,
+                    &maybe_face_redacter,
+                    &maybe_audio_transcriber,
+                    &maybe_redaction_cache,
+                    &mut maybe_redaction_deduplicator,
+                    &file_converters,
+                    &redacter_throttler,
+                    &mut redacter_volume_throttler,
+                )
+                .instrument(file_span.clone())
+                .await
+                {
+                    Ok(ref file_result)
+                        if file_result.result == TransferFileResult::Failed
+                            && file_result.retryable
+                            && !is_last_attempt =>
+                    {
+                        failed_files.push(source_file);
+                        continue;
+                    }
+                    Err(ref error) => {
+                        file_span.in_scope(|| tracing::warn!(error = %error, "Error copying"));
+                        app_reporter.report_error(format!(
+                            "↲ {}. Skipping due to: {}\n{:?}\n",
+                            Style::new().bold().red().apply_to("Error copying"),
+                            Style::new().bold().apply_to(error),
+                            error.source()
+                        ))?;
+                        bar.inc(1);
+                        if !is_last_attempt && error.is_retryable() {
+                            failed_files.push(source_file);
+                            continue;
+                        }
+                        AppMetrics::global().inc(AppMetric::FAILURES, &[]);
+                        CopyFileResult::new(
+                            source_fss[source_file.source_idx]
+                                .resolve(Some(&source_file.file_ref))
+                                .file_path,
+                            &source_file.file_ref,
+                            TransferFileResult::Failed,
+                        )
+                        .with_reason(error.to_string())
+                        .with_error(error)
+                    }
+                    Ok(file_result) => file_result,
+                };
+                let file_duration = file_started.elapsed();
+                file_span.in_scope(|| record_transfer_result(&file_result.result, file_duration));
+                let file_result = CopyFileResult {
+                    duration_ms: file_duration.as_millis() as u64,
+                    ..file_result
+                };
+                if let Some(ref mut redaction_deduplicator) = maybe_redaction_deduplicator {
+                    redaction_deduplicator.file_processed(&source_file.file_ref);
+                }
+                source_fss[source_file.source_idx]
+                    .complete_file(
+                        Some(&source_file.file_ref),
+                        file_result.result != TransferFileResult::Failed,
+                    )
+                    .await?;
+                match file_result.result {
+                    TransferFileResult::Copied => total_files_copied += 1,
+                    TransferFileResult::RedactedAndCopied => {
+                        total_files_redacted += 1;
+                        total_files_copied += 1;
+                    }
+                    TransferFileResult::Skipped => total_files_skipped += 1,
+                    TransferFileResult::Quarantined => {
+                        total_files_skipped += 1;
+                        total_files_quarantined += 1;
+                    }
+                    TransferFileResult::Failed => {
+                        total_files_skipped += 1;
+                        total_files_failed += 1;
+                    }
                 }
-                TransferFileResult::Skipped => total_files_skipped += 1,
+                files.push(file_result);
             }
+            if !failed_files.is_empty() {
+                retry_attempt += 1;
+                app_reporter.report(format!(
+                    "↻ Retrying {} failed files (attempt {} of {})",
+                    Style::new().bold().yellow().apply_to(failed_files.len()),
+                    retry_attempt,
+                    options.file_retries
+                ))?;
+                AppMetrics::global().add(
+                    AppMetric::RETRIES,
+                    &[("operation", "file")],
+                    failed_files.len() as f64,
+                );
+                bar.inc_length(failed_files.len() as u64);
+            }
+            pending_files = failed_files;
         }
         Ok(CopyCommandResult {
+            schema_version: CopyCommandResult::SCHEMA_VERSION,
             files_copied: total_files_copied,
             files_redacted: total_files_redacted,
             files_skipped: total_files_skipped,
+            files_quarantined: total_files_quarantined,
+            files_failed: total_files_failed,
+            files_remaining,
+            stopped_reason,
+            interrupted,
+            files,
+            provider_usage: BTreeMap::new(),
         })
     } else {
-        Ok(
-            match transfer_and_redact_file(
-                term,
-                &bar,
-                None,
-                &mut source_fs,
-                &mut destination_fs,
-                &options,
-                &maybe_redacters,
-                &file_converters,
-                &mut redacter_throttler,
-            )
-            .await?
-            {
-                TransferFileResult::Copied => CopyCommandResult {
-                    files_copied: 1,
-                    files_redacted: 0,
-                    files_skipped: 0,
-                },
-                TransferFileResult::RedactedAndCopied => CopyCommandResult {
-                    files_copied: 1,
-                    files_redacted: 1,
-                    files_skipped: 0,
-                },
-                TransferFileResult::Skipped => CopyCommandResult {
-                    files_copied: 0,
-                    files_redacted: 0,
-                    files_skipped: 1,
-                },
-            },
+        let file_started = Instant::now();
+        let file_span = tracing::info_span!("transfer_file", file = tracing::field::Empty);
+        let file_result = transfer_and_redact_file(
+            term,
+            &app_reporter,
+            &bar,
+            None,
+            None,
+            None,
+            &mut source_fss[0],
+            &mut destination_fs,
+            &mut quarantine_fs,
+            &options,
+            &maybe_redacters,
+            &maybe_verifier,
+            &maybe_face_redacter,
+            &maybe_audio_transcriber,
+            &maybe_redaction_cache,
+            &mut None,
+            &file_converters,
+            &redacter_throttler,
+            &mut redacter_volume_throttler,
         )
+        .instrument(file_span.clone())
+        .await?;
+        let file_duration = file_started.elapsed();
+        file_span.in_scope(|| record_transfer_result(&file_result.result, file_duration));
+        let file_result = CopyFileResult {
+            duration_ms: file_duration.as_millis() as u64,
+            ..file_result
+        };
+        let result = file_result.result;
+        source_fss[0]
+            .complete_file(None, result != TransferFileResult::Failed)
+            .await?;
+        Ok(CopyCommandResult {
+            schema_version: CopyCommandResult::SCHEMA_VERSION,
+            files_copied: matches!(
+                result,
+                TransferFileResult::Copied | TransferFileResult::RedactedAndCopied
+            ) as usize,
+            files_redacted: (result == TransferFileResult::RedactedAndCopied) as usize,
+            files_skipped: matches!(
+                result,
+                TransferFileResult::Skipped
+                    | TransferFileResult::Quarantined
+                    | TransferFileResult::Failed
+            ) as usize,
+            files_quarantined: (result == TransferFileResult::Quarantined) as usize,
+            files_failed: (result == TransferFileResult::Failed) as usize,
+            files_remaining: 0,
+            stopped_reason: None,
+            interrupted: false,
+            files: vec![file_result],
+            provider_usage: BTreeMap::new(),
+        })
     };
 
     destination_fs.close().await?;
-    source_fs.close().await?;
+    if let Some(quarantine_fs) = quarantine_fs {
+        quarantine_fs.close().await?;
+    }
+    for source_fs in source_fss {
+        source_fs.close().await?;
+    }
+    // Mappings are saved even for failed runs, so redacted files keep the same surrogates in the next runs
+    if let Some(entity_mappings) = entity_mappings {
+        entity_mappings.save().await?;
+    }
+
+    let copy_result = copy_result.map(|result| CopyCommandResult {
+        provider_usage: RedacterProviderUsage::collect_since(
+            AppMetrics::global(),
+            &provider_usage_at_start,
+        ),
+        ..result
+    });
+    if let Ok(ref result) = copy_result {
+        tracing::info!(
+            files_copied = result.files_copied,
+            files_redacted = result.files_redacted,
+            files_skipped = result.files_skipped,
+            files_quarantined = result.files_quarantined,
+            duration_ms = started.elapsed().as_millis() as u64,
+            "Copy finished"
+        );
+    }
+    let metrics = AppMetrics::global();
+    metrics.set(
+        AppMetric::RUN_DURATION,
+        &[],
+        started.elapsed().as_secs_f64(),
+    );
     copy_result
 }
 
+/// Reports the result of a file transfer to logs and metrics
+fn record_transfer_result(transfer_result: &TransferFileResult, duration: Duration) {
+    let result = transfer_result.to_string();
+    let metrics = AppMetrics::global();
+    metrics.inc(AppMetric::FILES_PROCESSED, &[("result", result.as_str())]);
+    metrics.add_duration(AppMetric::FILES_DURATION, &[], duration);
+    tracing::info!(
+        result,
+        duration_ms = duration.as_millis() as u64,
+        "File processed"
+    );
+}
+
 async fn report_copy_info(
-    term: &Term,
+    reporter: &AppReporter<'_>,
     source: &str,
     destination: &str,
     redacter_options: &Option<RedacterOptions>,
@@ -208,11 +1245,22 @@ async fn report_copy_info(
     } else {
         bold_style.clone().red().apply_to("✗ No".to_string())
     };
-    let sampling_output = if let Some(ref sampling_size) = redacter_options
-        .as_ref()
-        .and_then(|o| o.base_options.sampling_size)
-    {
-        Style::new().apply_to(format!("{} bytes.", sampling_size))
+    let sampling_output = if let Some((sampling_size, base_options)) =
+        redacter_options.as_ref().and_then(|o| {
+            o.base_options
+                .sampling_size
+                .map(|sampling_size| (sampling_size, &o.base_options))
+        }) {
+        Style::new().apply_to(format!(
+            "{} bytes ({}){}.",
+            sampling_size,
+            base_options.sampling_strategy,
+            if base_options.sample_then_full {
+                ", then whole files with sensitive data"
+            } else {
+                ""
+            }
+        ))
     } else {
         Style::new().dim().apply_to("-".to_string())
     };
@@ -228,33 +1276,51 @@ async fn report_copy_info(
     };
 
     let ocr_support_output = if file_converters.ocr.is_some() {
+        let ocr_engine = redacter_options
+            .as_ref()
+            .map(|options| options.ocr_options.engine)
+            .unwrap_or_default();
         converter_style
             .clone()
             .green()
-            .apply_to("✓ Yes".to_string())
+            .apply_to(format!("✓ Yes ({})", ocr_engine))
     } else {
         converter_style.clone().dim().apply_to("✗ No".to_string())
     };
 
-    term.write_line(
-        format!(
-            "Copying from {} to {}.\nRedacting: {}. | Sampling: {} | PDF to image support: {} | OCR support: {}\n",
-            bold_style.clone().white().apply_to(source),
-            bold_style.clone().yellow().apply_to(destination),
-            redacted_output,
-            sampling_output,
-            pdf_support_output,
-            ocr_support_output,
-        )
-        .as_str(),
-    )?;
+    reporter.report(format!(
+        "Copying from {} to {}.\nRedacting: {}. | Sampling: {} | PDF to image support: {} | OCR support: {}\n",
+        bold_style.clone().white().apply_to(source),
+        bold_style.clone().yellow().apply_to(destination),
+        redacted_output,
+        sampling_output,
+        pdf_support_output,
+        ocr_support_output,
+    ))?;
     Ok(())
 }
 
-enum TransferFileResult {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferFileResult {
     Copied,
+    #[serde(rename = "redacted")]
     RedactedAndCopied,
     Skipped,
+    Quarantined,
+    Failed,
+}
+
+impl Display for TransferFileResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransferFileResult::Copied => write!(f, "copied"),
+            TransferFileResult::RedactedAndCopied => write!(f, "redacted"),
+            TransferFileResult::Skipped => write!(f, "skipped"),
+            TransferFileResult::Quarantined => write!(f, "quarantined"),
+            TransferFileResult::Failed => write!(f, "failed"),
+        }
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -264,36 +1330,206 @@ async fn transfer_and_redact_file<
     DFS: FileSystemConnection<'a>,
 >(
     term: &Term,
+    reporter: &AppReporter<'_>,
     bar: &ProgressBar,
     source_file_ref: Option<&FileSystemRef>,
+    destination_path: Option<&RelativeFilePath>,
+    prefetched_file: Option<CopyPrefetchedFile>,
     source_fs: &mut SFS,
     destination_fs: &mut DFS,
+    quarantine_fs: &mut Option<DFS>,
     options: &CopyCommandOptions,
     redacter: &Option<(RedacterBaseOptions, Vec<Redacters<'a>>)>,
+    redacter_verifier: &Option<RedacterVerifier<'a>>,
+    face_redacter: &Option<FaceRedacter<'a>>,
+    audio_transcriber: &Option<AudioTranscriber<'a>>,
+    redaction_cache: &Option<RedactionCache<'a>>,
+    redaction_deduplicator: &mut Option<RedactionDeduplicator<'a>>,
     file_converters: &FileConverters<'a>,
-    redacter_throttler: &mut Option<RedacterThrottler>,
-) -> AppResult<TransferFileResult> {
+    redacter_throttler: &Mutex<Option<RedacterThrottler>>,
+    redacter_volume_throttler: &mut Option<RedacterVolumeThrottler>,
+) -> AppResult<CopyFileResult> {
+    const UP_TO_DATE_REASON: &str = "Destination is up to date";
+    const ALREADY_REDACTED_REASON: &str = "Already redacted";
+    const EMPTY_FILE_REASON: &str = "Empty file has nothing to redact";
     let bold_style = Style::new().bold().white();
-    let (base_file_ref, source_reader) = source_fs.download(source_file_ref).await?;
+    if let Some(source_file_ref) = source_file_ref {
+        if is_destination_up_to_date(
+            reporter,
+            bar,
+            destination_fs,
+            options,
+            &with_destination_path(source_file_ref, destination_path),
+        )
+        .await?
+        {
+            return Ok(CopyFileResult::new(
+                source_fs.resolve(Some(source_file_ref)).file_path,
+                source_file_ref,
+                TransferFileResult::Skipped,
+            )
+            .with_reason(UP_TO_DATE_REASON));
+        }
+    }
+    // Files are checked before downloading them, so files redacted in place aren't downloaded again
+    let listed_source_redacted_by = match source_file_ref {
+        Some(source_file_ref) => {
+            source_redacted_by(source_fs, options, redacter, source_file_ref).await
+        }
+        None => None,
+    };
+    if let (Some(source_file_ref), Some(_)) = (source_file_ref, &listed_source_redacted_by) {
+        if options.in_place {
+            reporter.report(format!(
+                "↲ Skipping {} because it's already redacted",
+                bold_style.apply_to(source_file_ref.relative_path.value())
+            ))?;
+            bar.inc(1);
+            return Ok(CopyFileResult::new(
+                source_fs.resolve(Some(source_file_ref)).file_path,
+                source_file_ref,
+                TransferFileResult::Skipped,
+            )
+            .with_reason(ALREADY_REDACTED_REASON));
+        }
+    }
+    let (base_file_ref, source_reader) = match prefetched_file {
+        Some(prefetched_file) => prefetched_file.into_download(),
+        None => source_fs.download(source_file_ref).await?,
+    };
+    if source_file_ref.is_none() {
+        tracing::Span::current().record("file", base_file_ref.relative_path.value().as_str());
+    }
+    let source_redacted_by = match source_file_ref {
+        Some(_) => listed_source_redacted_by,
+        None => source_redacted_by(source_fs, options, redacter, &base_file_ref).await,
+    };
 
     let base_resolved_file_ref = source_fs.resolve(Some(&base_file_ref));
+    let mut file_result = CopyFileResult::new(
+        base_resolved_file_ref.file_path.clone(),
+        &base_file_ref,
+        TransferFileResult::Skipped,
+    );
     match options.file_matcher.matches(&base_file_ref) {
-        FileMatcherResult::SkippedDueToSize | FileMatcherResult::SkippedDueToName => {
+        FileMatcherResult::SkippedDueToSize
+        | FileMatcherResult::SkippedDueToName
+        | FileMatcherResult::SkippedDueToMediaType
+        | FileMatcherResult::SkippedDueToModificationTime => {
             bar.inc(1);
-            return Ok(TransferFileResult::Skipped);
+            return Ok(file_result.with_reason("Filtered out by file filters"));
         }
         FileMatcherResult::Matched => {}
     }
 
-    let file_ref = source_file_ref.unwrap_or(&base_file_ref);
-
+    if source_file_ref.is_none()
+        && is_destination_up_to_date(reporter, bar, destination_fs, options, &base_file_ref).await?
+    {
+        return Ok(file_result.with_reason(UP_TO_DATE_REASON));
+    }
+
+    let file_ref =
+        &with_destination_path(source_file_ref.unwrap_or(&base_file_ref), destination_path);
+    // Names of single files are specified by destinations
+    let file_ref = &match redacter {
+        Some((_, redacters)) if options.redact_file_names && source_file_ref.is_some() => {
+            match redact_file_name(file_ref, redacters).await {
+                Ok(redacted_file_ref) => {
+                    if redacted_file_ref.relative_path.value() != file_ref.relative_path.value() {
+                        reporter.report(format!(
+                            "↳ Redacted file name to {}",
+                            bold_style.apply_to(redacted_file_ref.relative_path.value())
+                        ))?;
+                        tracing::info!(
+                            original_path = file_ref.relative_path.value().as_str(),
+                            redacted_path = redacted_file_ref.relative_path.value().as_str(),
+                            "File name redacted"
+                        );
+                    }
+                    redacted_file_ref
+                }
+                Err(error) => {
+                    AppMetrics::global().inc(AppMetric::FAILURES, &[]);
+                    reporter.report_error(format!(
+                        "↲ {}. Skipping due to: {}",
+                        Style::new()
+                            .bold()
+                            .red()
+                            .apply_to("Error redacting file name"),
+                        bold_style.apply_to(&error)
+                    ))?;
+                    bar.inc(1);
+                    return Ok(CopyFileResult {
+                        result: TransferFileResult::Failed,
+                        ..file_result
+                    }
+                    .with_reason(format!("Error redacting file name: {}", error))
+                    .with_error(&error));
+                }
+            }
+        }
+        _ => file_ref.clone(),
+    };
+    let file_ref = &match resolve_destination_conflict(destination_fs, options, file_ref).await? {
+        DestinationConflict::None => file_ref.clone(),
+        DestinationConflict::Renamed(renamed_file_ref) => {
+            reporter.report(format!(
+                "↳ Destination exists, renamed to {}",
+                bold_style.apply_to(renamed_file_ref.relative_path.value())
+            ))?;
+            renamed_file_ref
+        }
+        DestinationConflict::Skipped => {
+            reporter.report(format!(
+                "↲ Skipping {} because destination already exists",
+                bold_style.apply_to(file_ref.relative_path.value())
+            ))?;
+            bar.inc(1);
+            return Ok(file_result.with_reason("Destination already exists"));
+        }
+        DestinationConflict::Failed(error) => {
+            AppMetrics::global().inc(AppMetric::FAILURES, &[]);
+            reporter.report_error(format!(
+                "↲ {}. Skipping due to: {}",
+                Style::new()
+                    .bold()
+                    .red()
+                    .apply_to("Destination already exists"),
+                bold_style.apply_to(&error)
+            ))?;
+            bar.inc(1);
+            return Ok(CopyFileResult {
+                result: TransferFileResult::Failed,
+                ..file_result
+            }
+            .with_reason("Destination already exists")
+            .with_error(&error));
+        }
+    };
+
+    // Redacters aren't called for files without anything to redact, they're copied as they are
+    let skip_redaction_reason = match source_redacted_by {
+        _ if redacter.is_none() => None,
+        Some(_) => Some(ALREADY_REDACTED_REASON),
+        None if file_ref.file_size == Some(0) => Some(EMPTY_FILE_REASON),
+        None => None,
+    };
+    if let Some(skip_redaction_reason) = skip_redaction_reason {
+        if options.in_place {
+            bar.inc(1);
+            return Ok(file_result.with_reason(skip_redaction_reason));
+        }
+    }
+
     let dest_file_ref = FileSystemRef {
         relative_path: file_ref.relative_path.clone(),
         media_type: file_ref.media_type.clone(),
         file_size: file_ref.file_size,
+        modified_at: file_ref.modified_at,
+        checksum: None,
     };
     let max_filename_width = (term.width() as f64 * 0.25) as usize;
-    bar.println(
+    reporter.report(
         format!(
             "Processing {} to {} {} Size: {}",
             bold_style.apply_to(pad_str(
@@ -332,105 +1568,882 @@ async fn transfer_and_redact_file<
             ))
         )
         .as_str(),
-    );
-    let transfer_result = if let Some(ref redacter_with_options) = redacter {
-        redact_upload_file::<SFS, DFS, _>(
-            bar,
-            destination_fs,
-            bold_style,
-            source_reader,
-            file_ref,
-            options,
-            redacter_with_options,
-            file_converters,
-            redacter_throttler,
-        )
-        .await?
-    } else {
+    )?;
+    let source_reader = track_transfer_progress(bar, source_reader, file_ref.file_size);
+    let (transfer_result, uploaded_file_ref) =
+        if let (Some(ref redacter_with_options), None) = (redacter, skip_redaction_reason) {
+            if options.interactive {
+                // Original files are kept in memory, so they can be redacted again with another redacter
+                let original_content: bytes::Bytes = source_reader
+                    .try_collect::<Vec<bytes::Bytes>>()
+                    .await?
+                    .concat()
+                    .into();
+                let (redacter_base_options, redacters) = redacter_with_options;
+                let review = InteractiveReview::new(term, bar, redacters);
+                let mut selected_redacters = redacter_with_options.clone();
+                let mut retried_redaction_deduplicator = None;
+                let mut retried = false;
+                loop {
+                    // Retries must not reuse cached redactions of the rejected ones
+                    let (redaction_cache, redaction_deduplicator) = if retried {
+                        (&None, &mut retried_redaction_deduplicator)
+                    } else {
+                        (redaction_cache, &mut *redaction_deduplicator)
+                    };
+                    match redact_upload_file::<DFS, _>(
+                        reporter,
+                        destination_fs,
+                        quarantine_fs,
+                        bold_style.clone(),
+                        futures::stream::iter(vec![Ok(original_content.clone())]),
+                        file_ref,
+                        options,
+                        &selected_redacters,
+                        redacter_verifier,
+                        face_redacter,
+                        audio_transcriber,
+                        redaction_cache,
+                        redaction_deduplicator,
+                        file_converters,
+                        redacter_throttler,
+                        redacter_volume_throttler,
+                        Some((&review, &original_content)),
+                        &mut file_result,
+                    )
+                    .await?
+                    {
+                        RedactUploadResult::Completed(transfer_result, uploaded_file_ref) => {
+                            break (transfer_result, uploaded_file_ref);
+                        }
+                        RedactUploadResult::Retry { redacter_index } => {
+                            reporter.report(format!(
+                                "↳ Retrying redaction with {}",
+                                bold_style.apply_to(redacters[redacter_index].redacter_type())
+                            ))?;
+                            selected_redacters = (
+                                redacter_base_options.clone(),
+                                vec![redacters[redacter_index].clone()],
+                            );
+                            retried = true;
+                        }
+                    }
+                }
+            } else {
+                match redact_upload_file::<DFS, _>(
+                    reporter,
+                    destination_fs,
+                    quarantine_fs,
+                    bold_style,
+                    source_reader,
+                    file_ref,
+                    options,
+                    redacter_with_options,
+                    redacter_verifier,
+                    face_redacter,
+                    audio_transcriber,
+                    redaction_cache,
+                    redaction_deduplicator,
+                    file_converters,
+                    redacter_throttler,
+                    redacter_volume_throttler,
+                    None,
+                    &mut file_result,
+                )
+                .await?
+                {
+                    RedactUploadResult::Completed(transfer_result, uploaded_file_ref) => {
+                        (transfer_result, uploaded_file_ref)
+                    }
+                    // Redactions are retried only in the interactive review
+                    RedactUploadResult::Retry { .. } => (TransferFileResult::Skipped, None),
+                }
+            }
+        } else {
+            if let Some(skip_redaction_reason) = skip_redaction_reason {
+                reporter.report(format!(
+                    "↳ Copying without redaction: {}",
+                    skip_redaction_reason
+                ))?;
+                file_result.reason = Some(skip_redaction_reason.to_string());
+            }
+            let uploaded_bytes = Arc::new(AtomicUsize::new(0));
+            destination_fs
+                .upload(
+                    count_uploaded_bytes(source_reader, &uploaded_bytes),
+                    Some(&options.upload_file_ref(&dest_file_ref)),
+                )
+                .await?;
+            file_result.destination_size = Some(uploaded_bytes.load(Ordering::Relaxed));
+            (TransferFileResult::Copied, Some(dest_file_ref.clone()))
+        };
+    if let Some(ref uploaded_file_ref) = uploaded_file_ref {
+        file_result.destination = Some(destination_fs.resolve(Some(uploaded_file_ref)).file_path);
+        let original_file_ref = source_file_ref.unwrap_or(&base_file_ref);
+        // Copies of files redacted by previous runs keep their markers
+        let redacted_by = match transfer_result {
+            TransferFileResult::RedactedAndCopied => Some(file_result.redacters.clone()),
+            _ => source_redacted_by,
+        };
+        if options.preserve_metadata || options.if_changed || redacted_by.is_some() {
+            // Copies are not failed because of metadata, since content is already in the destination
+            if let Err(err) = write_metadata(
+                source_fs,
+                destination_fs,
+                original_file_ref,
+                &options.upload_file_ref(uploaded_file_ref),
+                options,
+                redacted_by.as_deref(),
+            )
+            .await
+            {
+                reporter.report(format!("↳ Failed to write metadata: {}", err))?;
+            }
+        }
+        if options.in_place {
+            replace_in_place(
+                reporter,
+                destination_fs,
+                original_file_ref,
+                uploaded_file_ref,
+                options,
+            )
+            .await?;
+        }
+    }
+    bar.set_message("");
+    bar.inc(1);
+    Ok(CopyFileResult {
+        result: transfer_result,
+        ..file_result
+    })
+}
+
+/// Replaces the original file with the redacted file uploaded next to it, keeping the original as a backup if requested.
+/// Uploaded files are deleted if they can't replace the originals, so the originals stay intact.
+async fn replace_in_place<'a, DFS: FileSystemConnection<'a>>(
+    reporter: &AppReporter<'_>,
+    destination_fs: &mut DFS,
+    original_file_ref: &FileSystemRef,
+    redacted_file_ref: &FileSystemRef,
+    options: &CopyCommandOptions,
+) -> AppResult<()> {
+    let uploaded_file_ref = options.upload_file_ref(redacted_file_ref);
+    let replace_result: AppResult<()> = async {
+        if destination_fs
+            .get_file_ref(Some(&uploaded_file_ref))
+            .await?
+            .is_none()
+        {
+            return Err(AppError::SystemError {
+                message: format!(
+                    "Redacted file {} is not found after upload",
+                    uploaded_file_ref.relative_path.value()
+                ),
+            });
+        }
+        if let Some(ref backup_suffix) = options.backup_suffix {
+            let backup_file_ref = FileSystemRef {
+                relative_path: format!(
+                    "{}{}",
+                    original_file_ref.relative_path.value(),
+                    backup_suffix
+                )
+                .into(),
+                ..original_file_ref.clone()
+            };
+            destination_fs
+                .copy_file(original_file_ref, &backup_file_ref)
+                .await?;
+        }
         destination_fs
-            .upload(source_reader, Some(&dest_file_ref))
+            .move_file(&uploaded_file_ref, redacted_file_ref)
+            .await
+    }
+    .await;
+    if let Err(ref err) = replace_result {
+        if let Err(delete_err) = destination_fs.delete_file(&uploaded_file_ref).await {
+            reporter.report_error(format!(
+                "↳ Failed to delete {} after failing to replace the original file due to {}: {}",
+                uploaded_file_ref.relative_path.value(),
+                err,
+                delete_err
+            ))?;
+        }
+        return replace_result;
+    }
+    // Converted files such as HEIF images have different names, so the originals are deleted separately
+    if redacted_file_ref.relative_path.value() != original_file_ref.relative_path.value() {
+        destination_fs.delete_file(original_file_ref).await?;
+    }
+    reporter.report(format!(
+        "↳ Replaced {} in place",
+        Style::new()
+            .bold()
+            .apply_to(original_file_ref.relative_path.value())
+    ))?;
+    Ok(())
+}
+
+/// Redacts relative paths of files using redacters supporting text.
+/// Redacters must keep the directory structure, so the number of path segments is checked.
+async fn redact_file_name(
+    file_ref: &FileSystemRef,
+    redacters: &[Redacters<'_>],
+) -> AppResult<FileSystemRef> {
+    let text_file_ref = FileSystemRef {
+        media_type: Some(mime::TEXT_PLAIN),
+        ..file_ref.clone()
+    };
+    let original_path = file_ref.relative_path.value();
+    let mut redacted_path = original_path.clone();
+    for redacter in redacters {
+        if redacter.redact_support(&text_file_ref).await? != RedactSupport::Supported {
+            continue;
+        }
+        let redacted = redacter
+            .redact(RedacterDataItem {
+                content: RedacterDataItemContent::Value(redacted_path),
+                file_ref: text_file_ref.clone(),
+            })
             .await?;
-        TransferFileResult::Copied
+        redacted_path = match redacted.content {
+            RedacterDataItemContent::Value(value) => value.trim().to_string(),
+            _ => {
+                return Err(AppError::SystemError {
+                    message: "Redacter returned unexpected content for a file name".to_string(),
+                })
+            }
+        };
+    }
+    if redacted_path.is_empty()
+        || redacted_path.split('/').count() != original_path.split('/').count()
+    {
+        return Err(AppError::SystemError {
+            message: format!(
+                "Redacted file name '{}' doesn't keep the directory structure of the original one",
+                redacted_path
+            ),
+        });
+    }
+    Ok(FileSystemRef {
+        relative_path: redacted_path.into(),
+        ..file_ref.clone()
+    })
+}
+
+/// Redacts text files to copy in advance using redacters with batch redaction enabled,
+/// so they are redacted in a Vertex AI batch prediction job or in MsPresidio batch requests instead of requests per file
+async fn prepare_batch_redaction<'a, SFS: FileSystemConnection<'a>>(
+    reporter: &AppReporter<'_>,
+    source_fss: &mut [SFS],
+    source_files: &[CopySourceFile],
+    base_options: &RedacterBaseOptions,
+    redacters: &mut [Redacters<'_>],
+) -> AppResult<()> {
+    let mut batch_contents: Option<Vec<String>> = None;
+    for redacter in redacters.iter_mut() {
+        if !redacter.is_batch_enabled() {
+            continue;
+        }
+        let contents = match batch_contents {
+            Some(ref contents) => contents.clone(),
+            None => {
+                reporter.report(format!(
+                    "Reading text files for {} batch redaction...",
+                    redacter.redacter_type()
+                ))?;
+                let contents = read_batch_contents(source_fss, source_files, base_options).await?;
+                batch_contents = Some(contents.clone());
+                contents
+            }
+        };
+        redacter.redact_batch(contents).await?;
+    }
+    Ok(())
+}
+
+async fn read_batch_contents<'a, SFS: FileSystemConnection<'a>>(
+    source_fss: &mut [SFS],
+    source_files: &[CopySourceFile],
+    base_options: &RedacterBaseOptions,
+) -> AppResult<Vec<String>> {
+    let mut contents = Vec::new();
+    for source_file in source_files {
+        if !source_file
+            .file_ref
+            .media_type
+            .as_ref()
+            .is_some_and(Redacters::is_mime_text)
+        {
+            continue;
+        }
+        let (_, stream) = source_fss[source_file.source_idx]
+            .download(Some(&source_file.file_ref))
+            .await?;
+        let chunks: Vec<bytes::Bytes> = stream.try_collect().await?;
+        // Files that aren't valid UTF-8 fail later when they are copied
+        if let Ok(content) = String::from_utf8(chunks.concat()) {
+            contents.push(match base_options.sampling_size {
+                Some(sampling_size) if !base_options.sample_then_full => base_options
+                    .sampling_strategy
+                    .sample(&content, sampling_size),
+                _ => content,
+            });
+        }
+    }
+    contents.sort();
+    contents.dedup();
+    Ok(contents)
+}
+
+/// Sources and destinations are the same if they differ only by trailing slashes or `file://` prefixes.
+/// Clipboard content is read before it is replaced, so it can be redacted back to the clipboard
+fn is_same_location(source: &str, destination: &str) -> bool {
+    source != "clipboard://"
+        && local_path_from_uri(source).trim_end_matches(std::path::is_separator)
+            == local_path_from_uri(destination).trim_end_matches(std::path::is_separator)
+}
+
+/// Writes preserved metadata of source files, markers of redacted files and sources of copies
+/// compared by `--if-changed` in one update, since S3 replaces all user metadata of objects on updates
+async fn write_metadata<'a, SFS: FileSystemConnection<'a>, DFS: FileSystemConnection<'a>>(
+    source_fs: &mut SFS,
+    destination_fs: &mut DFS,
+    source_file_ref: &FileSystemRef,
+    dest_file_ref: &FileSystemRef,
+    options: &CopyCommandOptions,
+    redacted_by: Option<&[String]>,
+) -> AppResult<()> {
+    let mut metadata = FileMetadata::default();
+    if options.preserve_metadata {
+        if let Some(source_metadata) = source_fs.get_metadata(Some(source_file_ref)).await? {
+            metadata = source_metadata.scrub(&options.scrub_metadata);
+        }
+    }
+    if let Some(redacted_by) = redacted_by {
+        metadata = metadata.merge(FileMetadata::redacted_marker(redacted_by));
+    }
+    if options.if_changed {
+        metadata = metadata.merge(FileMetadata::copied_from(source_file_ref));
+    }
+    if !metadata.is_empty() {
+        destination_fs
+            .set_metadata(Some(dest_file_ref), &metadata)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Providers of source files marked as redacted by previous runs, so they aren't redacted again.
+/// Markers are checked only for files to redact, and files without readable metadata aren't marked.
+async fn source_redacted_by<'a, SFS: FileSystemConnection<'a>>(
+    source_fs: &mut SFS,
+    options: &CopyCommandOptions,
+    redacter: &Option<(RedacterBaseOptions, Vec<Redacters<'a>>)>,
+    source_file_ref: &FileSystemRef,
+) -> Option<Vec<String>> {
+    if options.force || redacter.is_none() {
+        return None;
+    }
+    match source_fs.get_metadata(Some(source_file_ref)).await {
+        Ok(metadata) => metadata.and_then(|metadata| metadata.redacted_by()),
+        Err(error) => {
+            tracing::debug!(error = %error, "Reading metadata of source file failed");
+            None
+        }
+    }
+}
+
+/// Files from multiple sources may be copied to different paths in the destination to avoid collisions
+fn with_destination_path(
+    file_ref: &FileSystemRef,
+    destination_path: Option<&RelativeFilePath>,
+) -> FileSystemRef {
+    match destination_path {
+        Some(destination_path) => FileSystemRef {
+            relative_path: destination_path.clone(),
+            ..file_ref.clone()
+        },
+        None => file_ref.clone(),
+    }
+}
+
+/// Shows the number of bytes transferred within the current file in the progress bar message
+fn track_transfer_progress<
+    S: Stream<Item = AppResult<bytes::Bytes>> + Send + Sync + Unpin + 'static,
+>(
+    bar: &ProgressBar,
+    input: S,
+    file_size: Option<usize>,
+) -> FileSystemStream {
+    let bar = bar.clone();
+    let total_size = file_size.map(|size| HumanBytes(size as u64).to_string());
+    let mut transferred: u64 = 0;
+    Box::new(input.inspect(move |item| {
+        if let Ok(bytes) = item {
+            transferred += bytes.len() as u64;
+            AppMetrics::global().add(AppMetric::BYTES_PROCESSED, &[], bytes.len() as f64);
+            match total_size {
+                Some(ref total_size) => {
+                    bar.set_message(format!("{} / {}", HumanBytes(transferred), total_size))
+                }
+                None => bar.set_message(HumanBytes(transferred).to_string()),
+            }
+        }
+    }))
+}
+
+/// Counts bytes of uploaded streams, so sizes of redacted files are known for results
+fn count_uploaded_bytes<
+    S: Stream<Item = AppResult<bytes::Bytes>> + Send + Sync + Unpin + 'static,
+>(
+    input: S,
+    uploaded_bytes: &Arc<AtomicUsize>,
+) -> FileSystemStream {
+    let uploaded_bytes = uploaded_bytes.clone();
+    Box::new(input.inspect(move |item| {
+        if let Ok(bytes) = item {
+            uploaded_bytes.fetch_add(bytes.len(), Ordering::Relaxed);
+        }
+    }))
+}
+
+async fn is_destination_up_to_date<'a, DFS: FileSystemConnection<'a>>(
+    reporter: &AppReporter<'_>,
+    bar: &ProgressBar,
+    destination_fs: &mut DFS,
+    options: &CopyCommandOptions,
+    source_file_ref: &FileSystemRef,
+) -> AppResult<bool> {
+    if !options.if_newer && !options.if_changed {
+        return Ok(false);
+    }
+    let Some(destination_file_ref) = destination_fs.get_file_ref(Some(source_file_ref)).await?
+    else {
+        return Ok(false);
     };
-    bar.inc(1);
-    Ok(transfer_result)
+    let destination_metadata = if options.if_changed {
+        match destination_fs
+            .get_metadata(Some(&destination_file_ref))
+            .await
+        {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                tracing::debug!(error = %error, "Reading metadata of destination file failed");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    if options.is_destination_up_to_date(
+        source_file_ref,
+        &destination_file_ref,
+        destination_metadata.as_ref(),
+    ) {
+        reporter.report(format!(
+            "↲ Skipping {} because destination is up to date",
+            Style::new()
+                .bold()
+                .apply_to(source_file_ref.relative_path.value())
+        ))?;
+        bar.inc(1);
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Resolutions of files already existing in the destination according to `--on-conflict`
+enum DestinationConflict {
+    None,
+    Renamed(FileSystemRef),
+    Skipped,
+    Failed(AppError),
+}
+
+/// Checks whether files exist in the destination unless they're overwritten anyway.
+/// Files redacted in place always replace their originals.
+async fn resolve_destination_conflict<'a, DFS: FileSystemConnection<'a>>(
+    destination_fs: &mut DFS,
+    options: &CopyCommandOptions,
+    file_ref: &FileSystemRef,
+) -> AppResult<DestinationConflict> {
+    if options.in_place || options.on_conflict == CopyCollisionMode::Overwrite {
+        return Ok(DestinationConflict::None);
+    }
+    if destination_fs.get_file_ref(Some(file_ref)).await?.is_none() {
+        return Ok(DestinationConflict::None);
+    }
+    match options.on_conflict {
+        CopyCollisionMode::Overwrite => Ok(DestinationConflict::None),
+        CopyCollisionMode::Skip => Ok(DestinationConflict::Skipped),
+        CopyCollisionMode::Fail => Ok(DestinationConflict::Failed(AppError::DestinationExists {
+            destination_path: destination_fs.resolve(Some(file_ref)).file_path,
+        })),
+        CopyCollisionMode::Rename => {
+            let mut suffix = 1;
+            loop {
+                let renamed_file_ref = FileSystemRef {
+                    relative_path: CopyCollisionMode::renamed_path(
+                        file_ref.relative_path.value(),
+                        suffix,
+                    )
+                    .into(),
+                    ..file_ref.clone()
+                };
+                if destination_fs
+                    .get_file_ref(Some(&renamed_file_ref))
+                    .await?
+                    .is_none()
+                {
+                    return Ok(DestinationConflict::Renamed(renamed_file_ref));
+                }
+                suffix += 1;
+            }
+        }
+    }
+}
+
+/// Results of redacting and uploading files, redactions rejected in the interactive review are retried
+enum RedactUploadResult {
+    Completed(TransferFileResult, Option<FileSystemRef>),
+    Retry { redacter_index: usize },
 }
 
 #[allow(clippy::too_many_arguments)]
 async fn redact_upload_file<
     'a,
-    SFS: FileSystemConnection<'a>,
     DFS: FileSystemConnection<'a>,
     S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static,
 >(
-    bar: &ProgressBar,
+    reporter: &AppReporter<'_>,
     destination_fs: &mut DFS,
+    quarantine_fs: &mut Option<DFS>,
     bold_style: Style,
     source_reader: S,
     dest_file_ref: &FileSystemRef,
     options: &CopyCommandOptions,
     redacter_with_options: &(RedacterBaseOptions, Vec<Redacters<'a>>),
+    redacter_verifier: &Option<RedacterVerifier<'a>>,
+    face_redacter: &Option<FaceRedacter<'a>>,
+    audio_transcriber: &Option<AudioTranscriber<'a>>,
+    redaction_cache: &Option<RedactionCache<'a>>,
+    redaction_deduplicator: &mut Option<RedactionDeduplicator<'a>>,
     file_converters: &FileConverters<'a>,
-    redacter_throttler: &mut Option<RedacterThrottler>,
-) -> AppResult<TransferFileResult> {
+    redacter_throttler: &Mutex<Option<RedacterThrottler>>,
+    redacter_volume_throttler: &mut Option<RedacterVolumeThrottler>,
+    review: Option<(&InteractiveReview<'_>, &bytes::Bytes)>,
+    file_result: &mut CopyFileResult,
+) -> AppResult<RedactUploadResult> {
     let (redacter_base_options, redacters) = redacter_with_options;
-    let stream_redacter = StreamRedacter::new(redacter_base_options, file_converters, bar);
+    let stream_redacter = StreamRedacter::new(
+        redacter_base_options,
+        file_converters,
+        reporter,
+        redacter_verifier.as_ref(),
+        face_redacter.as_ref(),
+        audio_transcriber.as_ref(),
+    )
+    .with_request_throttler(redacter_throttler);
 
     let dest_file_ref_overridden = options
         .file_mime_override
         .override_for_file_ref(dest_file_ref.clone());
 
+    let heif_converted_file_ref =
+        stream_redacter.heif_converted_file_ref(&dest_file_ref_overridden);
+
     let redact_plan = stream_redacter
-        .create_redact_plan(redacters, &dest_file_ref_overridden)
+        .create_redact_plan(
+            redacters,
+            heif_converted_file_ref
+                .as_ref()
+                .unwrap_or(&dest_file_ref_overridden),
+        )
         .await?;
 
-    if !redact_plan.supported_redacters.is_empty() {
-        if let Some(ref mut throttler) = redacter_throttler {
-            *throttler = throttler.update(Instant::now());
+    let exceeded_max_item_size = redacter_base_options.max_item_size.filter(|max_item_size| {
+        redact_plan.has_redactions()
+            && dest_file_ref
+                .file_size
+                .is_some_and(|file_size| file_size > *max_item_size)
+    });
+
+    if let Some(max_item_size) = exceeded_max_item_size {
+        reporter.report(format!(
+            "↲ Skipping redaction because the file is {} than {}",
+            bold_style.clone().yellow().apply_to("larger"),
+            bold_style.apply_to(HumanBytes(max_item_size as u64))
+        ))?;
+        let reason = AppError::ItemSizeLimitExceeded { max_item_size }.to_string();
+        file_result.reason = Some(reason.clone());
+        quarantine_or_skip(
+            reporter,
+            quarantine_fs,
+            bold_style,
+            source_reader,
+            dest_file_ref,
+            reason,
+        )
+        .await
+    } else if redact_plan.has_redactions() {
+        // Files without known sizes are buffered only up to the maximum item size
+        let source_reader = limit_item_size(source_reader, redacter_base_options.max_item_size);
+        // Duplicates are detected by listed file sizes, so the file is checked before any conversions
+        let source_file_ref = dest_file_ref;
+        let redaction_deduplicator = redaction_deduplicator
+            .as_mut()
+            .filter(|deduplicator| deduplicator.is_duplicate_candidate(source_file_ref));
+        let delay = redacter_throttler.lock().ok().and_then(|mut throttler| {
+            throttler.as_mut().map(|throttler| {
+                *throttler = throttler.update(Instant::now());
+                *throttler.delay()
+            })
+        });
+        if let Some(delay) = delay.filter(|delay| delay.as_millis() > 0) {
+            reporter.report(
+                format!(
+                    "⧗ Delaying redaction for {} seconds",
+                    bold_style
+                        .clone()
+                        .yellow()
+                        .apply_to(delay.as_secs().to_string())
+                )
+                .as_str(),
+            )?;
+            tokio::time::sleep(delay).await;
+        }
+        if let Some((throttler, file_size)) = redacter_volume_throttler
+            .as_mut()
+            .zip(source_file_ref.file_size)
+        {
+            *throttler = throttler.update(Instant::now(), file_size);
             let delay = throttler.delay();
             if delay.as_millis() > 0 {
-                bar.println(
+                reporter.report(
                     format!(
-                        "⧗ Delaying redaction for {} seconds",
+                        "⧗ Delaying redaction for {} seconds to limit the volume",
                         bold_style
                             .clone()
                             .yellow()
-                            .apply_to(throttler.delay().as_secs().to_string())
+                            .apply_to(delay.as_secs().to_string())
                     )
                     .as_str(),
-                );
+                )?;
                 tokio::time::sleep(*delay).await;
             }
         }
-        match stream_redacter
-            .redact_stream(source_reader, redact_plan, &dest_file_ref_overridden)
-            .await
-        {
+        let (source_reader, dest_file_ref, dest_file_ref_overridden): (
+            Box<dyn Stream<Item = AppResult<bytes::Bytes>> + Send + Sync + Unpin + 'static>,
+            FileSystemRef,
+            FileSystemRef,
+        ) = match heif_converted_file_ref {
+            Some(converted_file_ref) => {
+                reporter.report(format!(
+                    "↳ Converting HEIF image to JPEG as {}",
+                    bold_style.apply_to(converted_file_ref.relative_path.value())
+                ))?;
+                let converted = stream_redacter.convert_heif_to_jpeg(source_reader).await?;
+                (
+                    Box::new(futures::stream::iter(vec![Ok(converted)])),
+                    FileSystemRef {
+                        relative_path: converted_file_ref.relative_path.clone(),
+                        media_type: converted_file_ref.media_type.clone(),
+                        ..dest_file_ref.clone()
+                    },
+                    converted_file_ref,
+                )
+            }
+            None => (
+                Box::new(source_reader),
+                stream_redacter
+                    .audio_transcript_file_ref(&redact_plan, dest_file_ref)
+                    .unwrap_or_else(|| dest_file_ref.clone()),
+                dest_file_ref_overridden,
+            ),
+        };
+        let redacted_result = match (redaction_deduplicator, redaction_cache) {
+            (Some(redaction_deduplicator), _) => {
+                redaction_deduplicator
+                    .redact_stream(
+                        source_file_ref,
+                        &stream_redacter,
+                        redaction_cache.as_ref(),
+                        source_reader,
+                        redact_plan,
+                        &dest_file_ref_overridden,
+                    )
+                    .await
+            }
+            (None, Some(redaction_cache)) => {
+                redaction_cache
+                    .redact_stream(
+                        &stream_redacter,
+                        source_reader,
+                        redact_plan,
+                        &dest_file_ref_overridden,
+                    )
+                    .await
+            }
+            (None, None) => {
+                stream_redacter
+                    .redact_stream(source_reader, redact_plan, &dest_file_ref_overridden)
+                    .await
+            }
+        };
+        match redacted_result {
             Ok(redacted_result)
                 if redacted_result.number_of_redactions > 0
                     || redacter_base_options.allow_unsupported_copies =>
             {
+                AppMetrics::global().add(
+                    AppMetric::REDACTIONS_APPLIED,
+                    &[],
+                    redacted_result.number_of_redactions as f64,
+                );
+                file_result.redactions = redacted_result.number_of_redactions;
+                file_result.redacters = redacted_result.applied_redacters.clone();
+                file_result.redacted_regions = redacted_result.redacted_regions.clone();
+                file_result.findings = redacted_result.residual_findings.clone();
+                if !redacted_result.residual_findings.is_empty() {
+                    let findings =
+                        RedacterVerifier::summarize_findings(&redacted_result.residual_findings);
+                    AppMetrics::global().inc(AppMetric::VERIFICATION_FAILURES, &[]);
+                    tracing::warn!(findings = findings.as_str(), "Verification failed");
+                    match redacter_verifier.as_ref().map(|verifier| verifier.action) {
+                        Some(RedactVerifyAction::Report) => {
+                            reporter.report_error(format!(
+                                "↳ {}. Copying although entities are still detected: {}",
+                                bold_style.clone().yellow().apply_to("Verification failed"),
+                                bold_style.apply_to(&findings)
+                            ))?;
+                            file_result.reason = Some(format!(
+                                "Verification failed. Copied although entities are still detected: {}",
+                                findings
+                            ));
+                        }
+                        _ => {
+                            reporter.report_error(format!(
+                                "↲ {}. Skipping because entities are still detected: {}",
+                                bold_style.clone().red().apply_to("Verification failed"),
+                                bold_style.apply_to(&findings)
+                            ))?;
+                            file_result.reason = Some(format!(
+                                "Verification failed. Entities are still detected: {}",
+                                findings
+                            ));
+                            return Ok(RedactUploadResult::Completed(
+                                TransferFileResult::Failed,
+                                None,
+                            ));
+                        }
+                    }
+                }
+                let redacted_stream = match review {
+                    Some((review, original_content)) => {
+                        let redacted_content: bytes::Bytes = redacted_result
+                            .stream
+                            .try_collect::<Vec<bytes::Bytes>>()
+                            .await?
+                            .concat()
+                            .into();
+                        match review.review(
+                            &dest_file_ref,
+                            original_content,
+                            &redacted_content,
+                            redacted_result.number_of_redactions,
+                        )? {
+                            ReviewDecision::Accept => {
+                                Box::new(futures::stream::iter(vec![Ok(redacted_content)]))
+                            }
+                            ReviewDecision::Skip => {
+                                reporter.report(format!(
+                                    "↲ Skipping because the redacted file is {}",
+                                    bold_style.clone().yellow().apply_to("rejected in review")
+                                ))?;
+                                file_result.reason = Some("Rejected in review".to_string());
+                                return Ok(RedactUploadResult::Completed(
+                                    TransferFileResult::Skipped,
+                                    None,
+                                ));
+                            }
+                            ReviewDecision::Retry { redacter_index } => {
+                                return Ok(RedactUploadResult::Retry { redacter_index });
+                            }
+                        }
+                    }
+                    None => redacted_result.stream,
+                };
+                let redacted_stream = if options.report_thumbnails
+                    && dest_file_ref
+                        .media_type
+                        .as_ref()
+                        .is_some_and(Redacters::is_mime_image)
+                {
+                    let redacted_content: bytes::Bytes = redacted_stream
+                        .try_collect::<Vec<bytes::Bytes>>()
+                        .await?
+                        .concat()
+                        .into();
+                    file_result.thumbnail =
+                        png_thumbnail(&redacted_content, CopyFileResult::THUMBNAIL_SIZE);
+                    Box::new(futures::stream::iter(vec![Ok(redacted_content)]))
+                } else {
+                    redacted_stream
+                };
+                let uploaded_bytes = Arc::new(AtomicUsize::new(0));
                 destination_fs
-                    .upload(redacted_result.stream, Some(dest_file_ref))
+                    .upload(
+                        count_uploaded_bytes(redacted_stream, &uploaded_bytes),
+                        Some(&options.upload_file_ref(&dest_file_ref)),
+                    )
                     .await?;
+                file_result.destination_size = Some(uploaded_bytes.load(Ordering::Relaxed));
                 if redacted_result.number_of_redactions > 0 {
-                    Ok(TransferFileResult::RedactedAndCopied)
+                    Ok(RedactUploadResult::Completed(
+                        TransferFileResult::RedactedAndCopied,
+                        Some(dest_file_ref),
+                    ))
                 } else {
-                    Ok(TransferFileResult::Copied)
+                    Ok(RedactUploadResult::Completed(
+                        TransferFileResult::Copied,
+                        Some(dest_file_ref),
+                    ))
                 }
             }
             Ok(_) => {
-                bar.println(
+                reporter.report(
                     format!(
                         "↲ Skipping redaction because {} redactions were applied",
                         bold_style.yellow().apply_to("no suitable".to_string())
                     )
                     .as_str(),
-                );
-                Ok(TransferFileResult::Skipped)
+                )?;
+                file_result.reason = Some("No suitable redactions were applied".to_string());
+                Ok(RedactUploadResult::Completed(
+                    TransferFileResult::Skipped,
+                    None,
+                ))
+            }
+            Err(AppError::ItemSizeLimitExceeded { max_item_size }) => {
+                reporter.report(format!(
+                    "↲ Skipping redaction because the file is {} than {}",
+                    bold_style.clone().yellow().apply_to("larger"),
+                    bold_style.apply_to(HumanBytes(max_item_size as u64))
+                ))?;
+                file_result.reason =
+                    Some(AppError::ItemSizeLimitExceeded { max_item_size }.to_string());
+                Ok(RedactUploadResult::Completed(
+                    TransferFileResult::Skipped,
+                    None,
+                ))
             }
             Err(ref error) => {
-                bar.println(
+                AppMetrics::global().inc(AppMetric::FAILURES, &[]);
+                tracing::warn!(error = %error, "Error redacting");
+                reporter.report_error(
                     format!(
                         "↲ {}. Skipping due to: {}\n{:?}\n",
                         bold_style.clone().red().apply_to("Error redacting"),
@@ -438,12 +2451,18 @@ async fn redact_upload_file<
                         error.source()
                     )
                     .as_str(),
-                );
-                Ok(TransferFileResult::Skipped)
+                )?;
+                file_result.reason = Some(format!("Error redacting: {}", error));
+                file_result.error_code = Some(error.code().to_string());
+                file_result.retryable = error.is_retryable();
+                Ok(RedactUploadResult::Completed(
+                    TransferFileResult::Failed,
+                    None,
+                ))
             }
         }
     } else if redacter_base_options.allow_unsupported_copies {
-        bar.println(
+        reporter.report(
             format!(
                 "↳ Copying {} because it is explicitly allowed by arguments",
                 bold_style
@@ -452,25 +2471,518 @@ async fn redact_upload_file<
                     .apply_to("unredacted".to_string())
             )
             .as_str(),
-        );
+        )?;
+        file_result.reason =
+            Some("Copied unredacted as explicitly allowed by arguments".to_string());
+        let uploaded_bytes = Arc::new(AtomicUsize::new(0));
         destination_fs
-            .upload(source_reader, Some(dest_file_ref))
+            .upload(
+                count_uploaded_bytes(source_reader, &uploaded_bytes),
+                Some(&options.upload_file_ref(dest_file_ref)),
+            )
             .await?;
-        Ok(TransferFileResult::Copied)
+        file_result.destination_size = Some(uploaded_bytes.load(Ordering::Relaxed));
+        Ok(RedactUploadResult::Completed(
+            TransferFileResult::Copied,
+            Some(dest_file_ref.clone()),
+        ))
     } else {
-        bar.println(
+        let media_type_str = dest_file_ref
+            .media_type
+            .as_ref()
+            .map(|mt| mt.to_string())
+            .unwrap_or("".to_string());
+        reporter.report(
             format!(
                 "↲ Skipping redaction because {} media type is not supported",
-                bold_style.apply_to(
-                    dest_file_ref
-                        .media_type
-                        .as_ref()
-                        .map(|mt| mt.to_string())
-                        .unwrap_or("".to_string())
-                )
+                bold_style.apply_to(&media_type_str)
             )
             .as_str(),
+        )?;
+        let reason = format!("No redacter supports media type '{}'", media_type_str);
+        file_result.reason = Some(reason.clone());
+        quarantine_or_skip(
+            reporter,
+            quarantine_fs,
+            bold_style,
+            source_reader,
+            dest_file_ref,
+            reason,
+        )
+        .await
+    }
+}
+
+/// Files which can't be redacted are kept in the quarantine if it is specified
+async fn quarantine_or_skip<
+    'a,
+    DFS: FileSystemConnection<'a>,
+    S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static,
+>(
+    reporter: &AppReporter<'_>,
+    quarantine_fs: &mut Option<DFS>,
+    bold_style: Style,
+    source_reader: S,
+    dest_file_ref: &FileSystemRef,
+    reason: String,
+) -> AppResult<RedactUploadResult> {
+    match quarantine_fs {
+        Some(quarantine_fs) => {
+            quarantine_file(quarantine_fs, source_reader, dest_file_ref, reason).await?;
+            reporter.report(
+                format!(
+                    "↳ Quarantined to {}",
+                    bold_style.apply_to(quarantine_fs.resolve(Some(dest_file_ref)).file_path)
+                )
+                .as_str(),
+            )?;
+            Ok(RedactUploadResult::Completed(
+                TransferFileResult::Quarantined,
+                None,
+            ))
+        }
+        None => Ok(RedactUploadResult::Completed(
+            TransferFileResult::Skipped,
+            None,
+        )),
+    }
+}
+
+/// Fails streams as soon as they exceed the maximum item size, so files without known sizes
+/// aren't buffered entirely before they are skipped
+fn limit_item_size<S: Stream<Item = AppResult<bytes::Bytes>> + Send + Sync + Unpin + 'static>(
+    input: S,
+    max_item_size: Option<usize>,
+) -> FileSystemStream {
+    match max_item_size {
+        Some(max_item_size) => {
+            let mut item_size = 0;
+            Box::new(input.map(move |chunk| {
+                let chunk = chunk?;
+                item_size += chunk.len();
+                if item_size > max_item_size {
+                    Err(AppError::ItemSizeLimitExceeded { max_item_size })
+                } else {
+                    Ok(chunk)
+                }
+            }))
+        }
+        None => Box::new(input),
+    }
+}
+
+/// Copies the original file to the quarantine destination with a reason record next to it
+async fn quarantine_file<
+    'a,
+    DFS: FileSystemConnection<'a>,
+    S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static,
+>(
+    quarantine_fs: &mut DFS,
+    source_reader: S,
+    file_ref: &FileSystemRef,
+    reason: String,
+) -> AppResult<()> {
+    quarantine_fs.upload(source_reader, Some(file_ref)).await?;
+    let record = QuarantineRecord {
+        relative_path: file_ref.relative_path.value().clone(),
+        media_type: file_ref.media_type.as_ref().map(|mt| mt.to_string()),
+        file_size: file_ref.file_size,
+        reason,
+    };
+    let record_json = serde_json::to_string_pretty(&record)?;
+    let record_file_ref = FileSystemRef {
+        relative_path: format!("{}.quarantine.json", file_ref.relative_path.value()).into(),
+        media_type: Some(mime::APPLICATION_JSON),
+        file_size: Some(record_json.len()),
+        modified_at: None,
+        checksum: None,
+    };
+    quarantine_fs
+        .upload(
+            futures::stream::iter(vec![Ok(bytes::Bytes::from(record_json))]),
+            Some(&record_file_ref),
+        )
+        .await
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::file_systems::FileChecksum;
+
+    #[test]
+    fn is_destination_up_to_date_test() {
+        let test_file_ref =
+            |file_size: usize, modified_at_secs: i64, checksum: Option<FileChecksum>| {
+                FileSystemRef {
+                    relative_path: "test.txt".into(),
+                    media_type: Some(mime::TEXT_PLAIN),
+                    file_size: Some(file_size),
+                    modified_at: chrono::DateTime::from_timestamp(modified_at_secs, 0),
+                    checksum,
+                }
+            };
+        let if_newer = CopyCommandOptions {
+            if_newer: true,
+            ..CopyCommandOptions::default()
+        };
+        let if_changed = CopyCommandOptions {
+            if_changed: true,
+            ..CopyCommandOptions::default()
+        };
+        let source = test_file_ref(10, 100, Some(FileChecksum::Md5("aa".to_string())));
+
+        assert!(if_newer.is_destination_up_to_date(&source, &test_file_ref(20, 200, None), None));
+        assert!(!if_newer.is_destination_up_to_date(&source, &test_file_ref(10, 50, None), None));
+
+        assert!(if_changed.is_destination_up_to_date(
+            &source,
+            &test_file_ref(10, 50, Some(FileChecksum::Md5("aa".to_string()))),
+            None
+        ));
+        assert!(!if_changed.is_destination_up_to_date(
+            &source,
+            &test_file_ref(10, 200, Some(FileChecksum::Md5("bb".to_string()))),
+            None
+        ));
+        assert!(if_changed.is_destination_up_to_date(
+            &source,
+            &test_file_ref(10, 200, Some(FileChecksum::Crc32c("cc".to_string()))),
+            None
+        ));
+        assert!(!if_changed.is_destination_up_to_date(
+            &source,
+            &test_file_ref(20, 200, None),
+            None
+        ));
+
+        // Redacted copies have different checksums than their sources recorded in their metadata
+        let redacted = test_file_ref(8, 50, Some(FileChecksum::Md5("bb".to_string())));
+        assert!(if_changed.is_destination_up_to_date(
+            &source,
+            &redacted,
+            Some(&FileMetadata::copied_from(&source))
+        ));
+        let changed_source = test_file_ref(10, 300, Some(FileChecksum::Md5("cc".to_string())));
+        assert!(!if_changed.is_destination_up_to_date(
+            &changed_source,
+            &redacted,
+            Some(&FileMetadata::copied_from(&source))
+        ));
+    }
+
+    #[test]
+    fn exit_code_policy_test() {
+        let partially_failed = CopyCommandResult {
+            schema_version: CopyCommandResult::SCHEMA_VERSION,
+            files_copied: 2,
+            files_redacted: 2,
+            files_skipped: 2,
+            files_quarantined: 0,
+            files_failed: 1,
+            files_remaining: 0,
+            stopped_reason: None,
+            interrupted: false,
+            files: Vec::new(),
+            provider_usage: BTreeMap::new(),
+        };
+        let nothing_copied = CopyCommandResult {
+            schema_version: CopyCommandResult::SCHEMA_VERSION,
+            files_copied: 0,
+            files_redacted: 0,
+            files_skipped: 0,
+            files_quarantined: 0,
+            files_failed: 0,
+            files_remaining: 0,
+            stopped_reason: None,
+            interrupted: false,
+            files: Vec::new(),
+            provider_usage: BTreeMap::new(),
+        };
+
+        assert!(CopyExitCodePolicy::default()
+            .check(&partially_failed)
+            .is_none());
+        assert!(CopyExitCodePolicy::default()
+            .check(&nothing_copied)
+            .is_none());
+
+        let fail_on_error = CopyExitCodePolicy {
+            fail_on_error: true,
+            ..CopyExitCodePolicy::default()
+        };
+        assert_eq!(
+            fail_on_error.check(&partially_failed).map(|(code, _)| code),
+            Some(CopyExitCodePolicy::EXIT_CODE_FILES_FAILED)
+        );
+        assert!(fail_on_error
+            .check(&CopyCommandResult {
+                files_failed: 0,
+                ..partially_failed.clone()
+            })
+            .is_none());
+
+        let fail_on_skipped = CopyExitCodePolicy {
+            fail_on_skipped: true,
+            ..CopyExitCodePolicy::default()
+        };
+        assert_eq!(
+            fail_on_skipped
+                .check(&CopyCommandResult {
+                    files_failed: 0,
+                    ..partially_failed.clone()
+                })
+                .map(|(code, _)| code),
+            Some(CopyExitCodePolicy::EXIT_CODE_FILES_FAILED)
+        );
+
+        let fail_on_empty = CopyExitCodePolicy {
+            fail_on_empty: true,
+            ..CopyExitCodePolicy::default()
+        };
+        assert_eq!(
+            fail_on_empty.check(&nothing_copied).map(|(code, _)| code),
+            Some(CopyExitCodePolicy::EXIT_CODE_NOTHING_COPIED)
         );
-        Ok(TransferFileResult::Skipped)
+        assert!(fail_on_empty.check(&partially_failed).is_none());
+
+        assert_eq!(
+            CopyExitCodePolicy::default()
+                .check(&CopyCommandResult {
+                    files_remaining: 10,
+                    stopped_reason: Some("Limit of 100 API calls per run reached".to_string()),
+                    ..partially_failed.clone()
+                })
+                .map(|(code, _)| code),
+            Some(CopyExitCodePolicy::EXIT_CODE_LIMIT_REACHED)
+        );
+        assert_eq!(
+            CopyExitCodePolicy::default()
+                .check(&CopyCommandResult {
+                    files_remaining: 10,
+                    stopped_reason: Some("Interrupted".to_string()),
+                    interrupted: true,
+                    ..partially_failed.clone()
+                })
+                .map(|(code, _)| code),
+            Some(CopyExitCodePolicy::EXIT_CODE_INTERRUPTED)
+        );
+    }
+
+    #[test]
+    fn merge_sources_test() -> AppResult<()> {
+        let test_file_ref = |relative_path: &str| FileSystemRef {
+            relative_path: relative_path.into(),
+            media_type: Some(mime::TEXT_PLAIN),
+            file_size: Some(10),
+            modified_at: None,
+            checksum: None,
+        };
+        let sources = vec!["gs://first/".to_string(), "s3://second/".to_string()];
+        let source_files = || {
+            vec![
+                vec![test_file_ref("a.txt"), test_file_ref("dir/b.txt")],
+                vec![
+                    test_file_ref("dir/b.txt"),
+                    test_file_ref("dir/b-2.txt"),
+                    test_file_ref("c"),
+                ],
+            ]
+        };
+        let destination_paths = |files: &[CopySourceFile]| {
+            files
+                .iter()
+                .map(|file| {
+                    (
+                        file.source_idx,
+                        file.destination_path
+                            .as_ref()
+                            .unwrap_or(&file.file_ref.relative_path)
+                            .value()
+                            .clone(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert!(matches!(
+            CopyCollisionMode::Fail.merge_sources(&sources, source_files()),
+            Err(AppError::DestinationCollision { .. })
+        ));
+
+        let (files, skipped) = CopyCollisionMode::Skip.merge_sources(&sources, source_files())?;
+        assert_eq!(skipped, 1);
+        assert_eq!(
+            destination_paths(&files),
+            vec![
+                (0, "a.txt".to_string()),
+                (0, "dir/b.txt".to_string()),
+                (1, "dir/b-2.txt".to_string()),
+                (1, "c".to_string())
+            ]
+        );
+
+        let (files, skipped) =
+            CopyCollisionMode::Overwrite.merge_sources(&sources, source_files())?;
+        assert_eq!(skipped, 0);
+        assert_eq!(files.len(), 5);
+
+        let (files, _) = CopyCollisionMode::Rename.merge_sources(&sources, source_files())?;
+        assert_eq!(
+            destination_paths(&files)[2..4],
+            [
+                (1, "dir/b-3.txt".to_string()),
+                (1, "dir/b-2.txt".to_string())
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sort_files_test() {
+        let test_file =
+            |relative_path: &str, media_type: Option<mime::Mime>, size: Option<usize>| {
+                CopySourceFile {
+                    source_idx: 0,
+                    file_ref: FileSystemRef {
+                        relative_path: relative_path.into(),
+                        media_type,
+                        file_size: size,
+                        modified_at: None,
+                        checksum: None,
+                    },
+                    destination_path: None,
+                }
+            };
+        let files = vec![
+            test_file("notes.txt", Some(mime::TEXT_PLAIN), Some(100)),
+            test_file("report.pdf", Some(mime::APPLICATION_PDF), Some(5000)),
+            test_file("unknown", None, None),
+            test_file("photo.png", Some(mime::IMAGE_PNG), Some(100)),
+        ];
+        let sorted_paths = |order: CopyFileOrder| {
+            let mut files = files.clone();
+            order.sort_files(&mut files);
+            files
+                .iter()
+                .map(|file| file.file_ref.relative_path.value().clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            sorted_paths(CopyFileOrder::Listed),
+            vec!["notes.txt", "report.pdf", "unknown", "photo.png"]
+        );
+        assert_eq!(
+            sorted_paths(CopyFileOrder::SmallestFirst),
+            vec!["notes.txt", "photo.png", "report.pdf", "unknown"]
+        );
+        assert_eq!(
+            sorted_paths(CopyFileOrder::LargestFirst),
+            vec!["report.pdf", "notes.txt", "photo.png", "unknown"]
+        );
+        assert_eq!(
+            sorted_paths(CopyFileOrder::ByMime),
+            vec!["report.pdf", "photo.png", "notes.txt", "unknown"]
+        );
+    }
+
+    #[tokio::test]
+    async fn limit_item_size_test() -> AppResult<()> {
+        let chunks = || {
+            futures::stream::iter(vec![
+                Ok(bytes::Bytes::from_static(b"Hello, ")),
+                Ok(bytes::Bytes::from_static(b"John")),
+            ])
+        };
+        let content: Vec<bytes::Bytes> = limit_item_size(chunks(), Some(11)).try_collect().await?;
+        assert_eq!(content.concat(), b"Hello, John");
+        assert!(matches!(
+            limit_item_size(chunks(), Some(10))
+                .try_collect::<Vec<bytes::Bytes>>()
+                .await,
+            Err(AppError::ItemSizeLimitExceeded { max_item_size: 10 })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn run_limits_test() {
+        let next_file_ref = FileSystemRef {
+            relative_path: "test.txt".into(),
+            media_type: Some(mime::TEXT_PLAIN),
+            file_size: Some(100),
+            modified_at: None,
+            checksum: None,
+        };
+        let usage = CopyRunUsage {
+            bytes: 1000,
+            api_calls: 10,
+        };
+        assert!(CopyRunLimits::default()
+            .check(&usage, &next_file_ref)
+            .is_none());
+        assert!(CopyRunLimits {
+            max_bytes: Some(1100),
+            max_api_calls: Some(11),
+        }
+        .check(&usage, &next_file_ref)
+        .is_none());
+        assert!(CopyRunLimits {
+            max_bytes: Some(1050),
+            max_api_calls: None,
+        }
+        .check(&usage, &next_file_ref)
+        .is_some());
+        assert!(CopyRunLimits {
+            max_bytes: None,
+            max_api_calls: Some(10),
+        }
+        .check(&usage, &next_file_ref)
+        .is_some());
+    }
+
+    #[tokio::test]
+    async fn in_place_copy_test() -> AppResult<()> {
+        let term = Term::stdout();
+        let temp_dir = tempfile::tempdir()?;
+        tokio::fs::create_dir_all(temp_dir.path().join("dir")).await?;
+        tokio::fs::write(temp_dir.path().join("dir/test.txt"), "test content").await?;
+        let dir_path = format!("{}/", temp_dir.path().to_string_lossy());
+        let sources = vec![dir_path.clone()];
+        let options = |in_place: bool| CopyCommandOptions {
+            in_place,
+            backup_suffix: Some(".orig".to_string()),
+            reporter_options: AppReporterOptions::new(&term, true, true),
+            ..CopyCommandOptions::default()
+        };
+
+        assert!(matches!(
+            command_copy(&term, &sources, &dir_path, options(false), None).await,
+            Err(AppError::SourceIsDestination { .. })
+        ));
+
+        let result = command_copy(&term, &sources, &dir_path, options(true), None).await?;
+        assert_eq!(result.files_copied, 1);
+        assert_eq!(
+            tokio::fs::read_to_string(temp_dir.path().join("dir/test.txt")).await?,
+            "test content"
+        );
+        assert_eq!(
+            tokio::fs::read_to_string(temp_dir.path().join("dir/test.txt.orig")).await?,
+            "test content"
+        );
+        assert!(!temp_dir
+            .path()
+            .join(format!(
+                "dir/test.txt{}",
+                CopyCommandOptions::IN_PLACE_TEMP_SUFFIX
+            ))
+            .exists());
+
+        // Backups of previous runs aren't redacted in place again
+        let result = command_copy(&term, &sources, &dir_path, options(true), None).await?;
+        assert_eq!(result.files_copied, 1);
+        Ok(())
     }
 }
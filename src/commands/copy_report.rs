@@ -0,0 +1,274 @@
+use crate::commands::html_report::{html_escape, png_data_uri};
+use crate::commands::{CopyCommandResult, TransferFileResult};
+use indicatif::HumanBytes;
+use std::collections::BTreeMap;
+
+/// HTML reports of copy jobs built from the same results as JSON results,
+/// with thumbnails of redacted images embedded, so reports can be shared as single files
+pub fn copy_html_report(
+    sources: &[String],
+    destination: &str,
+    result: &CopyCommandResult,
+) -> String {
+    let mut html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Redacter copy report</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; margin-bottom: 1.5em; }}
+th, td {{ border-bottom: 1px solid #eee; padding: 4px 8px; text-align: left; vertical-align: top; }}
+th {{ background: #f5f5f5; }}
+td.number {{ text-align: right; }}
+.copied {{ color: #060; }}
+.redacted {{ color: #060; font-weight: bold; }}
+.skipped {{ color: #555; }}
+.quarantined {{ color: #a60; }}
+.failed {{ color: #a00; font-weight: bold; }}
+.stopped {{ background: #fdd; color: #a00; padding: 0.5em; }}
+img.thumbnail {{ max-width: 160px; max-height: 160px; }}
+</style>
+</head>
+<body>
+<h1>Redacter copy report</h1>
+<p>Sources: <code>{}</code><br>Destination: <code>{}</code></p>
+<table>
+<tr><th>Copied</th><th>Redacted</th><th>Skipped</th><th>Quarantined</th><th>Failed</th><th>Remaining</th></tr>
+<tr><td class="number">{}</td><td class="number">{}</td><td class="number">{}</td><td class="number">{}</td><td class="number">{}</td><td class="number">{}</td></tr>
+</table>
+"#,
+        html_escape(&sources.join(", ")),
+        html_escape(destination),
+        result.files_copied,
+        result.files_redacted,
+        result.files_skipped,
+        result.files_quarantined,
+        result.files_failed,
+        result.files_remaining
+    );
+    if let Some(ref stopped_reason) = result.stopped_reason {
+        html.push_str(&format!(
+            "<p class=\"stopped\">Stopped: {}</p>\n",
+            html_escape(stopped_reason)
+        ));
+    }
+
+    let mut redacter_counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for file_result in &result.files {
+        for redacter in &file_result.redacters {
+            let (files, redactions) = redacter_counts.entry(redacter.as_str()).or_default();
+            *files += 1;
+            // Redactions aren't counted per redacter, so they are attributed to all applied redacters
+            *redactions += file_result.redactions;
+        }
+    }
+    if !redacter_counts.is_empty() {
+        html.push_str(
+            "<h2>Redacters</h2>\n<table>\n<tr><th>Redacter</th><th>Files</th><th>Redactions</th></tr>\n",
+        );
+        for (redacter, (files, redactions)) in redacter_counts {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td class=\"number\">{}</td><td class=\"number\">{}</td></tr>\n",
+                html_escape(redacter),
+                files,
+                redactions
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    let mut skip_reasons: BTreeMap<&str, usize> = BTreeMap::new();
+    for file_result in result.files.iter().filter(|file_result| {
+        matches!(
+            file_result.result,
+            TransferFileResult::Skipped | TransferFileResult::Quarantined
+        )
+    }) {
+        *skip_reasons
+            .entry(file_result.reason.as_deref().unwrap_or("Unknown"))
+            .or_default() += 1;
+    }
+    if !skip_reasons.is_empty() {
+        html.push_str("<h2>Skipped files</h2>\n<table>\n<tr><th>Reason</th><th>Files</th></tr>\n");
+        for (reason, files) in skip_reasons {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td class=\"number\">{}</td></tr>\n",
+                html_escape(reason),
+                files
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    let failed_files: Vec<_> = result
+        .files
+        .iter()
+        .filter(|file_result| file_result.result == TransferFileResult::Failed)
+        .collect();
+    if !failed_files.is_empty() {
+        html.push_str("<h2>Errors</h2>\n<table>\n<tr><th>File</th><th>Error</th></tr>\n");
+        for file_result in failed_files {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td class=\"failed\">{}</td></tr>\n",
+                html_escape(&file_result.source),
+                html_escape(file_result.reason.as_deref().unwrap_or_default())
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    let findings: Vec<_> = result
+        .files
+        .iter()
+        .flat_map(|file_result| {
+            file_result
+                .findings
+                .iter()
+                .map(move |finding| (file_result, finding))
+        })
+        .collect();
+    if !findings.is_empty() {
+        html.push_str(
+            "<h2>Verification findings</h2>\n<table>\n<tr><th>File</th><th>Provider</th><th>Type</th><th>Offsets</th><th>Context</th></tr>\n",
+        );
+        for (file_result, finding) in findings {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td><code>{}</code></td></tr>\n",
+                html_escape(&file_result.source),
+                html_escape(&finding.provider),
+                html_escape(&finding.info_type),
+                finding
+                    .start
+                    .zip(finding.end)
+                    .map(|(start, end)| format!("{}..{}", start, end))
+                    .unwrap_or_default(),
+                html_escape(finding.context.as_deref().unwrap_or_default())
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str(
+        "<h2>Files</h2>\n<table>\n<tr><th>Source</th><th>Destination</th><th>Media type</th><th>Size</th><th>Result</th><th>Redactions</th><th>Redacters</th><th>Reason</th><th>Preview</th></tr>\n",
+    );
+    for file_result in &result.files {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"number\">{}</td><td class=\"{}\">{}</td><td class=\"number\">{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            html_escape(&file_result.source),
+            html_escape(file_result.destination.as_deref().unwrap_or_default()),
+            html_escape(file_result.media_type.as_deref().unwrap_or_default()),
+            file_result
+                .file_size
+                .map(|file_size| HumanBytes(file_size as u64).to_string())
+                .unwrap_or_default(),
+            file_result.result,
+            file_result.result,
+            file_result.redactions,
+            html_escape(&file_result.redacters.join(", ")),
+            html_escape(file_result.reason.as_deref().unwrap_or_default()),
+            file_result
+                .thumbnail
+                .as_ref()
+                .map(|thumbnail| format!(
+                    "<img class=\"thumbnail\" src=\"{}\">",
+                    png_data_uri(thumbnail)
+                ))
+                .unwrap_or_default()
+        ));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::args::RedacterType;
+    use crate::commands::CopyFileResult;
+    use crate::redacters::RedactFinding;
+
+    #[test]
+    fn copy_html_report_test() {
+        let file_result =
+            |source: &str, result: TransferFileResult, reason: Option<&str>| CopyFileResult {
+                source: source.to_string(),
+                destination: None,
+                media_type: Some("text/plain".to_string()),
+                file_size: Some(100),
+                destination_size: None,
+                result,
+                redactions: 0,
+                redacters: Vec::new(),
+                redacted_regions: Vec::new(),
+                findings: Vec::new(),
+                reason: reason.map(|reason| reason.to_string()),
+                error_code: None,
+                duration_ms: 0,
+                retryable: true,
+                thumbnail: None,
+            };
+        let result = CopyCommandResult {
+            schema_version: CopyCommandResult::SCHEMA_VERSION,
+            files_copied: 1,
+            files_redacted: 1,
+            files_skipped: 3,
+            files_quarantined: 0,
+            files_failed: 1,
+            files_remaining: 0,
+            stopped_reason: None,
+            interrupted: false,
+            provider_usage: std::collections::BTreeMap::new(),
+            files: vec![
+                CopyFileResult {
+                    destination: Some("s3://bucket/a.txt".to_string()),
+                    redactions: 2,
+                    redacters: vec!["gcp-dlp".to_string()],
+                    findings: vec![RedactFinding {
+                        context: Some("mail <at> [EMAIL_ADDRESS]".to_string()),
+                        ..RedactFinding::new(
+                            RedacterType::AwsComprehend,
+                            "EMAIL".to_string(),
+                            Some(10),
+                            Some(26),
+                        )
+                    }],
+                    thumbnail: Some(vec![1, 2, 3]),
+                    ..file_result("a.txt", TransferFileResult::RedactedAndCopied, None)
+                },
+                file_result(
+                    "b.txt",
+                    TransferFileResult::Skipped,
+                    Some("Destination is up to date"),
+                ),
+                file_result(
+                    "c.txt",
+                    TransferFileResult::Skipped,
+                    Some("Destination is up to date"),
+                ),
+                file_result(
+                    "<d>.txt",
+                    TransferFileResult::Failed,
+                    Some("Error redacting: quota exceeded"),
+                ),
+            ],
+        };
+
+        let html = copy_html_report(&["./docs/".to_string()], "s3://bucket/", &result);
+        assert!(html.contains(
+            "<tr><td>gcp-dlp</td><td class=\"number\">1</td><td class=\"number\">2</td></tr>"
+        ));
+        assert!(
+            html.contains("<tr><td>Destination is up to date</td><td class=\"number\">2</td></tr>")
+        );
+        assert!(html.contains(
+            "<tr><td>&lt;d&gt;.txt</td><td class=\"failed\">Error redacting: quota exceeded</td></tr>"
+        ));
+        assert!(html.contains(
+            "<tr><td>a.txt</td><td>aws-comprehend</td><td>EMAIL</td><td>10..26</td><td><code>mail &lt;at&gt; [EMAIL_ADDRESS]</code></td></tr>"
+        ));
+        assert!(html.contains("<td class=\"redacted\">redacted</td>"));
+        assert!(html.contains("src=\"data:image/png;base64,AQID\""));
+    }
+}
@@ -0,0 +1,984 @@
+use crate::commands::html_report::{encode_png, html_escape, png_data_uri};
+use crate::errors::AppError;
+use crate::file_converters::ocr::OcrOptions;
+use crate::file_converters::tiff_pages::tiff_to_images;
+use crate::file_converters::FileConverters;
+use crate::file_systems::{
+    DetectFileSystem, FileSystemConnection, FileSystemOptions, FileSystemRef, RelativeFilePath,
+};
+use crate::file_tools::{FileFilters, FileMatcher, FileMimeOverride};
+use crate::redacters::Redacters;
+use crate::reporter::{AppReporter, AppReporterOptions};
+use crate::AppResult;
+use console::{Style, Term};
+use futures::TryStreamExt;
+use gcloud_sdk::prost::bytes;
+use image::{DynamicImage, GenericImageView};
+use indicatif::HumanBytes;
+use rvstruct::ValueStruct;
+use std::collections::{HashSet, VecDeque};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct DiffCommandOptions {
+    pub file_matcher: FileMatcher,
+    pub file_mime_override: FileMimeOverride,
+    pub html_report: Option<PathBuf>,
+    pub file_system_options: FileSystemOptions,
+    pub reporter_options: AppReporterOptions,
+}
+
+impl DiffCommandOptions {
+    pub fn new(
+        file_filters: FileFilters,
+        max_size_limit: Option<usize>,
+        mime_override: Vec<(mime::Mime, globset::Glob)>,
+        html_report: Option<PathBuf>,
+        file_system_options: FileSystemOptions,
+        reporter_options: AppReporterOptions,
+    ) -> Self {
+        DiffCommandOptions {
+            file_matcher: FileMatcher::new(file_filters, max_size_limit),
+            file_mime_override: FileMimeOverride::new(mime_override),
+            html_report,
+            file_system_options,
+            reporter_options,
+        }
+    }
+}
+
+/// Spans of changed lines: removed spans are only in original lines, inserted spans are only in redacted lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffSpan {
+    Unchanged(String),
+    Removed(String),
+    Inserted(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextLineDiff {
+    /// Line numbers starting from 1, `None` for lines inserted or removed entirely
+    pub original_line_number: Option<usize>,
+    pub redacted_line_number: Option<usize>,
+    pub spans: Vec<DiffSpan>,
+}
+
+impl TextLineDiff {
+    /// Replaced, removed or inserted spans separated by unchanged text
+    pub fn changes(&self) -> usize {
+        self.spans
+            .iter()
+            .enumerate()
+            .filter(|(idx, span)| {
+                !matches!(span, DiffSpan::Unchanged(_))
+                    && (*idx == 0 || matches!(self.spans[idx - 1], DiffSpan::Unchanged(_)))
+            })
+            .count()
+    }
+}
+
+/// Rectangular regions of images in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageDiff {
+    pub width: u32,
+    pub height: u32,
+    /// Bounding boxes of changed areas such as redacted text or faces
+    pub regions: Vec<ImageRegion>,
+    /// Fraction of changed pixels from 0.0 to 1.0
+    pub changed_ratio: f64,
+    /// Redacted images of other sizes are compared scaled to the original size
+    pub resized: bool,
+    pub original_thumbnail: DynamicImage,
+    pub redacted_thumbnail: DynamicImage,
+}
+
+#[derive(Debug, Clone)]
+pub enum FileContentDiff {
+    Text(Vec<TextLineDiff>),
+    /// Images, or pages of PDF and TIFF files
+    Images(Vec<ImageDiff>),
+    Binary {
+        original_size: usize,
+        redacted_size: usize,
+        identical: bool,
+    },
+    MissingRedacted,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileDiff {
+    pub relative_path: RelativeFilePath,
+    pub media_type: Option<mime::Mime>,
+    pub content: FileContentDiff,
+}
+
+impl FileDiff {
+    pub fn is_changed(&self) -> bool {
+        match &self.content {
+            FileContentDiff::Text(lines) => !lines.is_empty(),
+            FileContentDiff::Images(pages) => pages.iter().any(|page| !page.regions.is_empty()),
+            FileContentDiff::Binary { identical, .. } => !identical,
+            FileContentDiff::MissingRedacted => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffCommandResult {
+    pub files: Vec<FileDiff>,
+    pub files_changed: usize,
+    pub files_missing: usize,
+}
+
+#[tracing::instrument(name = "command_diff", skip_all, fields(original = original, redacted = redacted))]
+pub async fn command_diff(
+    term: &Term,
+    original: &str,
+    redacted: &str,
+    options: DiffCommandOptions,
+) -> AppResult<DiffCommandResult> {
+    let bold_style = Style::new().bold();
+    let app_reporter = AppReporter::from(term).with_options(&options.reporter_options);
+    app_reporter.report(format!(
+        "Comparing {} to redacted {}.",
+        bold_style.clone().white().apply_to(original),
+        bold_style.clone().green().apply_to(redacted)
+    ))?;
+    let file_converters = FileConverters::new()
+        .init(&app_reporter, &OcrOptions::default())
+        .await?;
+    let mut original_fs =
+        DetectFileSystem::open(original, &app_reporter, &options.file_system_options).await?;
+    let mut redacted_fs =
+        DetectFileSystem::open(redacted, &app_reporter, &options.file_system_options).await?;
+
+    let mut files = Vec::new();
+    if original_fs.has_multiple_files().await? {
+        if !redacted_fs.has_multiple_files().await? {
+            return Err(AppError::DestinationDoesNotSupportMultipleFiles {
+                destination: redacted.to_string(),
+            });
+        }
+        let redacted_paths: HashSet<String> = redacted_fs
+            .list_files(None, None)
+            .await?
+            .files
+            .into_iter()
+            .map(|file_ref| file_ref.relative_path.value().clone())
+            .collect();
+        let original_files = original_fs
+            .list_files(Some(&options.file_matcher), None)
+            .await?
+            .files;
+        for file_ref in original_files {
+            let file_ref = options.file_mime_override.override_for_file_ref(file_ref);
+            if !redacted_paths.contains(file_ref.relative_path.value()) {
+                files.push(FileDiff {
+                    relative_path: file_ref.relative_path,
+                    media_type: file_ref.media_type,
+                    content: FileContentDiff::MissingRedacted,
+                });
+                continue;
+            }
+            let original_content = download_content(&mut original_fs, Some(&file_ref)).await?.1;
+            let redacted_content = download_content(&mut redacted_fs, Some(&file_ref)).await?.1;
+            files.push(diff_file(
+                file_ref,
+                &original_content,
+                &redacted_content,
+                &file_converters,
+            )?);
+        }
+    } else {
+        let (file_ref, original_content) = download_content(&mut original_fs, None).await?;
+        let file_ref = options.file_mime_override.override_for_file_ref(file_ref);
+        let redacted_content = download_content(&mut redacted_fs, None).await?.1;
+        files.push(diff_file(
+            file_ref,
+            &original_content,
+            &redacted_content,
+            &file_converters,
+        )?);
+    }
+    original_fs.close().await?;
+    redacted_fs.close().await?;
+
+    for file_diff in &files {
+        report_file_diff(&app_reporter, file_diff)?;
+    }
+    let files_changed = files.iter().filter(|file| file.is_changed()).count();
+    let files_missing = files
+        .iter()
+        .filter(|file| matches!(file.content, FileContentDiff::MissingRedacted))
+        .count();
+    app_reporter.report(format!(
+        "{} files compared: {} changed, {} unchanged and {} missing in the redacted destination.",
+        bold_style.apply_to(files.len()),
+        bold_style.clone().green().apply_to(files_changed),
+        bold_style
+            .clone()
+            .yellow()
+            .apply_to(files.len() - files_changed - files_missing),
+        bold_style.clone().red().apply_to(files_missing)
+    ))?;
+    if let Some(ref html_report) = options.html_report {
+        tokio::fs::write(
+            html_report,
+            html_report_content(original, redacted, &files)?,
+        )
+        .await?;
+        app_reporter.report(format!(
+            "HTML report saved to {}",
+            bold_style.apply_to(html_report.to_string_lossy())
+        ))?;
+    }
+
+    Ok(DiffCommandResult {
+        files,
+        files_changed,
+        files_missing,
+    })
+}
+
+async fn download_content<'a, FS: FileSystemConnection<'a>>(
+    fs: &mut FS,
+    file_ref: Option<&FileSystemRef>,
+) -> AppResult<(FileSystemRef, bytes::Bytes)> {
+    let (file_ref, stream) = fs.download(file_ref).await?;
+    let chunks: Vec<bytes::Bytes> = stream.try_collect().await?;
+    Ok((file_ref, chunks.concat().into()))
+}
+
+fn diff_file(
+    file_ref: FileSystemRef,
+    original: &[u8],
+    redacted: &[u8],
+    file_converters: &FileConverters,
+) -> AppResult<FileDiff> {
+    let binary_diff = FileContentDiff::Binary {
+        original_size: original.len(),
+        redacted_size: redacted.len(),
+        identical: original == redacted,
+    };
+    let content = match file_ref.media_type.as_ref() {
+        Some(media_type)
+            if Redacters::is_mime_text(media_type) || Redacters::is_mime_table(media_type) =>
+        {
+            match (std::str::from_utf8(original), std::str::from_utf8(redacted)) {
+                (Ok(original_text), Ok(redacted_text)) => {
+                    FileContentDiff::Text(diff_text(original_text, redacted_text))
+                }
+                _ => binary_diff,
+            }
+        }
+        Some(media_type) if Redacters::is_mime_tiff(media_type) => {
+            let original_pages = tiff_to_images(original)?;
+            let redacted_pages = tiff_to_images(redacted)?;
+            FileContentDiff::Images(
+                original_pages
+                    .iter()
+                    .zip(redacted_pages.iter())
+                    .map(|(original_page, redacted_page)| diff_images(original_page, redacted_page))
+                    .collect(),
+            )
+        }
+        Some(media_type) if Redacters::is_mime_image(media_type) => {
+            // Images the image crate can't decode such as HEIF are compared as binary files
+            match (
+                image::load_from_memory(original),
+                image::load_from_memory(redacted),
+            ) {
+                (Ok(original_image), Ok(redacted_image)) => {
+                    FileContentDiff::Images(vec![diff_images(&original_image, &redacted_image)])
+                }
+                _ => binary_diff,
+            }
+        }
+        Some(media_type) if Redacters::is_mime_pdf(media_type) => {
+            match file_converters.pdf_image_converter.as_ref() {
+                Some(pdf_image_converter) => {
+                    let original_pdf = pdf_image_converter
+                        .convert_to_images(bytes::Bytes::copy_from_slice(original))?;
+                    let redacted_pdf = pdf_image_converter
+                        .convert_to_images(bytes::Bytes::copy_from_slice(redacted))?;
+                    FileContentDiff::Images(
+                        original_pdf
+                            .pages
+                            .iter()
+                            .zip(redacted_pdf.pages.iter())
+                            .map(|(original_page, redacted_page)| {
+                                diff_images(
+                                    &original_page.page_as_images,
+                                    &redacted_page.page_as_images,
+                                )
+                            })
+                            .collect(),
+                    )
+                }
+                None => binary_diff,
+            }
+        }
+        _ => binary_diff,
+    };
+    Ok(FileDiff {
+        relative_path: file_ref.relative_path,
+        media_type: file_ref.media_type,
+        content,
+    })
+}
+
+/// Operations with indexes of items in original or redacted sequences
+enum DiffOp {
+    Equal(usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Longest common subsequence of items after trimming common prefixes and suffixes,
+/// falling back to comparing items by position if the remaining sequences are too large
+fn diff_ops<T: PartialEq>(original: &[T], redacted: &[T]) -> Vec<DiffOp> {
+    const MAX_LCS_CELLS: usize = 4_000_000;
+    let prefix_len = original
+        .iter()
+        .zip(redacted.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix_len = original[prefix_len..]
+        .iter()
+        .rev()
+        .zip(redacted[prefix_len..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let original_middle = &original[prefix_len..original.len() - suffix_len];
+    let redacted_middle = &redacted[prefix_len..redacted.len() - suffix_len];
+
+    let mut ops: Vec<DiffOp> = (0..prefix_len).map(DiffOp::Equal).collect();
+    let (n, m) = (original_middle.len(), redacted_middle.len());
+    if n * m <= MAX_LCS_CELLS {
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if original_middle[i] == redacted_middle[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && original_middle[i] == redacted_middle[j] {
+                ops.push(DiffOp::Equal(prefix_len + i));
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                // Deletions first, so replaced words are grouped before their replacements
+                ops.push(DiffOp::Delete(prefix_len + i));
+                i += 1;
+            } else {
+                ops.push(DiffOp::Insert(prefix_len + j));
+                j += 1;
+            }
+        }
+    } else {
+        for idx in 0..n.max(m) {
+            match (original_middle.get(idx), redacted_middle.get(idx)) {
+                (Some(a), Some(b)) if a == b => ops.push(DiffOp::Equal(prefix_len + idx)),
+                (a, b) => {
+                    if a.is_some() {
+                        ops.push(DiffOp::Delete(prefix_len + idx));
+                    }
+                    if b.is_some() {
+                        ops.push(DiffOp::Insert(prefix_len + idx));
+                    }
+                }
+            }
+        }
+    }
+    ops.extend((original.len() - suffix_len..original.len()).map(DiffOp::Equal));
+    ops
+}
+
+/// Changed lines of text files, removed and inserted lines between unchanged lines are paired,
+/// so replaced spans are highlighted within lines
+pub fn diff_text(original: &str, redacted: &str) -> Vec<TextLineDiff> {
+    let original_lines: Vec<&str> = original.lines().collect();
+    let redacted_lines: Vec<&str> = redacted.lines().collect();
+    let mut line_diffs = Vec::new();
+    let mut removed = Vec::new();
+    let mut inserted = Vec::new();
+    let mut flush_changes = |removed: &mut Vec<usize>, inserted: &mut Vec<usize>| {
+        for idx in 0..removed.len().max(inserted.len()) {
+            let original_idx = removed.get(idx).copied();
+            let redacted_idx = inserted.get(idx).copied();
+            line_diffs.push(TextLineDiff {
+                original_line_number: original_idx.map(|idx| idx + 1),
+                redacted_line_number: redacted_idx.map(|idx| idx + 1),
+                spans: diff_line_spans(
+                    original_idx.map(|idx| original_lines[idx]).unwrap_or(""),
+                    redacted_idx.map(|idx| redacted_lines[idx]).unwrap_or(""),
+                ),
+            });
+        }
+        removed.clear();
+        inserted.clear();
+    };
+    for op in diff_ops(&original_lines, &redacted_lines) {
+        match op {
+            DiffOp::Equal(_) => flush_changes(&mut removed, &mut inserted),
+            DiffOp::Delete(idx) => removed.push(idx),
+            DiffOp::Insert(idx) => inserted.push(idx),
+        }
+    }
+    flush_changes(&mut removed, &mut inserted);
+    line_diffs
+}
+
+/// Spans of words and separators, adjacent spans of the same kind are merged
+pub fn diff_line_spans(original: &str, redacted: &str) -> Vec<DiffSpan> {
+    let original_tokens = line_tokens(original);
+    let redacted_tokens = line_tokens(redacted);
+    let mut spans: Vec<DiffSpan> = Vec::new();
+    let mut removed = String::new();
+    let mut inserted = String::new();
+    let flush_changes = |spans: &mut Vec<DiffSpan>, removed: &mut String, inserted: &mut String| {
+        if !removed.is_empty() {
+            spans.push(DiffSpan::Removed(std::mem::take(removed)));
+        }
+        if !inserted.is_empty() {
+            spans.push(DiffSpan::Inserted(std::mem::take(inserted)));
+        }
+    };
+    for op in diff_ops(&original_tokens, &redacted_tokens) {
+        match op {
+            DiffOp::Equal(idx) => {
+                flush_changes(&mut spans, &mut removed, &mut inserted);
+                match spans.last_mut() {
+                    Some(DiffSpan::Unchanged(text)) => text.push_str(original_tokens[idx]),
+                    _ => spans.push(DiffSpan::Unchanged(original_tokens[idx].to_string())),
+                }
+            }
+            DiffOp::Delete(idx) => removed.push_str(original_tokens[idx]),
+            DiffOp::Insert(idx) => inserted.push_str(redacted_tokens[idx]),
+        }
+    }
+    flush_changes(&mut spans, &mut removed, &mut inserted);
+    spans
+}
+
+/// Words as runs of alphanumeric characters, and every other character as a separate token
+fn line_tokens(line: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut word_start = None;
+    for (idx, c) in line.char_indices() {
+        if c.is_alphanumeric() {
+            word_start.get_or_insert(idx);
+        } else {
+            if let Some(start) = word_start.take() {
+                tokens.push(&line[start..idx]);
+            }
+            tokens.push(&line[idx..idx + c.len_utf8()]);
+        }
+    }
+    if let Some(start) = word_start {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+pub fn diff_images(original: &DynamicImage, redacted: &DynamicImage) -> ImageDiff {
+    const THUMBNAIL_SIZE: u32 = 320;
+
+    let (width, height) = original.dimensions();
+    let (regions, changed_ratio) = diff_image_regions(original, redacted);
+    ImageDiff {
+        width,
+        height,
+        regions,
+        changed_ratio,
+        resized: redacted.dimensions() != (width, height),
+        original_thumbnail: original.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE),
+        redacted_thumbnail: redacted.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE),
+    }
+}
+
+/// Changed pixels are grouped into cells, and adjacent changed cells into bounding boxes of regions.
+/// Returns the regions and the fraction of changed pixels
+pub fn diff_image_regions(
+    original: &DynamicImage,
+    redacted: &DynamicImage,
+) -> (Vec<ImageRegion>, f64) {
+    const CELL_SIZE: u32 = 8;
+    const PIXEL_THRESHOLD: u8 = 32;
+
+    let (width, height) = original.dimensions();
+    let resized = redacted.dimensions() != (width, height);
+    let original_rgb = original.to_rgb8();
+    let redacted_rgb = if resized {
+        redacted
+            .resize_exact(width, height, image::imageops::FilterType::Triangle)
+            .to_rgb8()
+    } else {
+        redacted.to_rgb8()
+    };
+
+    let (grid_width, grid_height) = (width.div_ceil(CELL_SIZE), height.div_ceil(CELL_SIZE));
+    let mut changed_cells = vec![false; (grid_width * grid_height) as usize];
+    let mut changed_pixels = 0_u64;
+    for (x, y, original_pixel) in original_rgb.enumerate_pixels() {
+        let redacted_pixel = redacted_rgb.get_pixel(x, y);
+        let is_changed = original_pixel
+            .0
+            .iter()
+            .zip(redacted_pixel.0.iter())
+            .any(|(a, b)| a.abs_diff(*b) > PIXEL_THRESHOLD);
+        if is_changed {
+            changed_pixels += 1;
+            changed_cells[((y / CELL_SIZE) * grid_width + x / CELL_SIZE) as usize] = true;
+        }
+    }
+
+    let mut regions = Vec::new();
+    let mut visited = vec![false; changed_cells.len()];
+    for start_idx in 0..changed_cells.len() {
+        if !changed_cells[start_idx] || visited[start_idx] {
+            continue;
+        }
+        visited[start_idx] = true;
+        let mut queue = VecDeque::from([start_idx]);
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+        while let Some(idx) = queue.pop_front() {
+            let (cell_x, cell_y) = (idx as u32 % grid_width, idx as u32 / grid_width);
+            min_x = min_x.min(cell_x);
+            min_y = min_y.min(cell_y);
+            max_x = max_x.max(cell_x);
+            max_y = max_y.max(cell_y);
+            for (dx, dy) in [
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ] {
+                let (neighbour_x, neighbour_y) = (cell_x as i64 + dx, cell_y as i64 + dy);
+                if neighbour_x < 0
+                    || neighbour_y < 0
+                    || neighbour_x >= grid_width as i64
+                    || neighbour_y >= grid_height as i64
+                {
+                    continue;
+                }
+                let neighbour_idx = (neighbour_y as u32 * grid_width + neighbour_x as u32) as usize;
+                if changed_cells[neighbour_idx] && !visited[neighbour_idx] {
+                    visited[neighbour_idx] = true;
+                    queue.push_back(neighbour_idx);
+                }
+            }
+        }
+        let (x, y) = (min_x * CELL_SIZE, min_y * CELL_SIZE);
+        regions.push(ImageRegion {
+            x,
+            y,
+            width: ((max_x + 1) * CELL_SIZE).min(width) - x,
+            height: ((max_y + 1) * CELL_SIZE).min(height) - y,
+        });
+    }
+
+    (
+        regions,
+        changed_pixels as f64 / (width as u64 * height as u64).max(1) as f64,
+    )
+}
+
+fn report_file_diff(app_reporter: &AppReporter, file_diff: &FileDiff) -> AppResult<()> {
+    const MAX_REPORTED_LINES: usize = 50;
+    let bold_style = Style::new().bold();
+    let dimmed_style = Style::new().dim();
+    let path = bold_style
+        .clone()
+        .white()
+        .apply_to(file_diff.relative_path.value());
+    match &file_diff.content {
+        FileContentDiff::Text(lines) => {
+            app_reporter.report(format!(
+                "{}: {} changed lines, {} changes",
+                path,
+                bold_style.apply_to(lines.len()),
+                bold_style.apply_to(lines.iter().map(|line| line.changes()).sum::<usize>())
+            ))?;
+            for line in lines.iter().take(MAX_REPORTED_LINES) {
+                if let Some(line_number) = line.original_line_number {
+                    app_reporter.report(format!(
+                        "  {} - {}",
+                        dimmed_style.apply_to(format!("{:>5}", line_number)),
+                        line.spans
+                            .iter()
+                            .filter_map(|span| match span {
+                                DiffSpan::Unchanged(text) => Some(text.to_string()),
+                                DiffSpan::Removed(text) =>
+                                    Some(Style::new().red().bold().apply_to(text).to_string()),
+                                DiffSpan::Inserted(_) => None,
+                            })
+                            .collect::<String>()
+                    ))?;
+                }
+                if let Some(line_number) = line.redacted_line_number {
+                    app_reporter.report(format!(
+                        "  {} + {}",
+                        dimmed_style.apply_to(format!("{:>5}", line_number)),
+                        line.spans
+                            .iter()
+                            .filter_map(|span| match span {
+                                DiffSpan::Unchanged(text) => Some(text.to_string()),
+                                DiffSpan::Inserted(text) =>
+                                    Some(Style::new().green().bold().apply_to(text).to_string()),
+                                DiffSpan::Removed(_) => None,
+                            })
+                            .collect::<String>()
+                    ))?;
+                }
+            }
+            if lines.len() > MAX_REPORTED_LINES {
+                app_reporter.report(format!(
+                    "  {}",
+                    dimmed_style.apply_to(format!(
+                        "… {} more changed lines",
+                        lines.len() - MAX_REPORTED_LINES
+                    ))
+                ))?;
+            }
+        }
+        FileContentDiff::Images(pages) => {
+            for (page_idx, page) in pages.iter().enumerate() {
+                app_reporter.report(format!(
+                    "{}{}: {}x{}, {} redacted regions ({:.1}% of the image){}",
+                    path,
+                    if pages.len() > 1 {
+                        format!(" page {}", page_idx + 1)
+                    } else {
+                        "".to_string()
+                    },
+                    page.width,
+                    page.height,
+                    bold_style.apply_to(page.regions.len()),
+                    page.changed_ratio * 100.0,
+                    if page.resized {
+                        ", redacted image is resized"
+                    } else {
+                        ""
+                    }
+                ))?;
+                for region in &page.regions {
+                    app_reporter.report(format!(
+                        "  {}",
+                        dimmed_style.apply_to(format!(
+                            "at {},{} size {}x{}",
+                            region.x, region.y, region.width, region.height
+                        ))
+                    ))?;
+                }
+            }
+        }
+        FileContentDiff::Binary {
+            original_size,
+            redacted_size,
+            identical,
+        } => {
+            app_reporter.report(format!(
+                "{}: {} ({} → {})",
+                path,
+                if *identical {
+                    "identical content"
+                } else {
+                    "binary content differs"
+                },
+                HumanBytes(*original_size as u64),
+                HumanBytes(*redacted_size as u64)
+            ))?;
+        }
+        FileContentDiff::MissingRedacted => {
+            app_reporter.report(format!(
+                "{}: {}",
+                path,
+                Style::new()
+                    .yellow()
+                    .apply_to("missing in the redacted destination")
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+fn html_image(image: &DynamicImage, diff: &ImageDiff) -> AppResult<String> {
+    let regions: String = diff
+        .regions
+        .iter()
+        .map(|region| {
+            format!(
+                r#"<div class="region" style="left:{:.2}%;top:{:.2}%;width:{:.2}%;height:{:.2}%"></div>"#,
+                region.x as f64 * 100.0 / diff.width as f64,
+                region.y as f64 * 100.0 / diff.height as f64,
+                region.width as f64 * 100.0 / diff.width as f64,
+                region.height as f64 * 100.0 / diff.height as f64
+            )
+        })
+        .collect();
+    Ok(format!(
+        r#"<div class="thumbnail"><img src="{}">{}</div>"#,
+        png_data_uri(&encode_png(image)?),
+        regions
+    ))
+}
+
+/// Self-contained HTML report with embedded thumbnails, so it can be shared as a single file
+fn html_report_content(original: &str, redacted: &str, files: &[FileDiff]) -> AppResult<String> {
+    let mut html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Redaction diff</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table.diff {{ border-collapse: collapse; font-family: monospace; width: 100%; }}
+table.diff td {{ border-bottom: 1px solid #eee; padding: 2px 6px; vertical-align: top; white-space: pre-wrap; }}
+td.line {{ color: #999; text-align: right; width: 4em; }}
+del {{ background: #fdd; color: #a00; }}
+ins {{ background: #dfd; color: #060; text-decoration: none; }}
+.images {{ display: flex; gap: 1em; }}
+.thumbnail {{ position: relative; display: inline-block; }}
+.thumbnail img {{ display: block; }}
+.region {{ position: absolute; border: 2px solid #e00; box-sizing: border-box; }}
+.summary {{ color: #555; }}
+</style>
+</head>
+<body>
+<h1>Redaction diff</h1>
+<p>Original: <code>{}</code><br>Redacted: <code>{}</code></p>
+"#,
+        html_escape(original),
+        html_escape(redacted)
+    );
+    for file_diff in files {
+        html.push_str(&format!(
+            "<section>\n<h2>{}</h2>\n",
+            html_escape(file_diff.relative_path.value())
+        ));
+        match &file_diff.content {
+            FileContentDiff::Text(lines) => {
+                html.push_str(&format!(
+                    "<p class=\"summary\">{} changed lines, {} changes</p>\n<table class=\"diff\">\n",
+                    lines.len(),
+                    lines.iter().map(|line| line.changes()).sum::<usize>()
+                ));
+                for line in lines {
+                    let mut original_html = String::new();
+                    let mut redacted_html = String::new();
+                    for span in &line.spans {
+                        match span {
+                            DiffSpan::Unchanged(text) => {
+                                original_html.push_str(&html_escape(text));
+                                redacted_html.push_str(&html_escape(text));
+                            }
+                            DiffSpan::Removed(text) => {
+                                original_html.push_str(&format!("<del>{}</del>", html_escape(text)))
+                            }
+                            DiffSpan::Inserted(text) => {
+                                redacted_html.push_str(&format!("<ins>{}</ins>", html_escape(text)))
+                            }
+                        }
+                    }
+                    html.push_str(&format!(
+                        "<tr><td class=\"line\">{}</td><td>{}</td><td class=\"line\">{}</td><td>{}</td></tr>\n",
+                        line.original_line_number
+                            .map(|line_number| line_number.to_string())
+                            .unwrap_or_default(),
+                        original_html,
+                        line.redacted_line_number
+                            .map(|line_number| line_number.to_string())
+                            .unwrap_or_default(),
+                        redacted_html
+                    ));
+                }
+                html.push_str("</table>\n");
+            }
+            FileContentDiff::Images(pages) => {
+                for (page_idx, page) in pages.iter().enumerate() {
+                    html.push_str(&format!(
+                        "<p class=\"summary\">{}{}x{}, {} redacted regions ({:.1}% of the image)</p>\n<div class=\"images\">\n<figure>{}<figcaption>Original</figcaption></figure>\n<figure>{}<figcaption>Redacted</figcaption></figure>\n</div>\n",
+                        if pages.len() > 1 {
+                            format!("Page {}: ", page_idx + 1)
+                        } else {
+                            "".to_string()
+                        },
+                        page.width,
+                        page.height,
+                        page.regions.len(),
+                        page.changed_ratio * 100.0,
+                        html_image(&page.original_thumbnail, page)?,
+                        html_image(&page.redacted_thumbnail, page)?
+                    ));
+                }
+            }
+            FileContentDiff::Binary {
+                original_size,
+                redacted_size,
+                identical,
+            } => {
+                html.push_str(&format!(
+                    "<p class=\"summary\">{} ({} → {})</p>\n",
+                    if *identical {
+                        "Identical content"
+                    } else {
+                        "Binary content differs"
+                    },
+                    HumanBytes(*original_size as u64),
+                    HumanBytes(*redacted_size as u64)
+                ));
+            }
+            FileContentDiff::MissingRedacted => {
+                html.push_str("<p class=\"summary\">Missing in the redacted destination</p>\n");
+            }
+        }
+        html.push_str("</section>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    Ok(html)
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_text_test() {
+        let original = "Name,Email\nJohn Doe,john@example.com\nJane,jane@example.com\nTotal: 2\n";
+        let redacted = "Name,Email\n[PERSON],[EMAIL]\nJane,[EMAIL]\nTotal: 2\nRedacted\n";
+        let lines = diff_text(original, redacted);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].original_line_number, Some(2));
+        assert_eq!(lines[0].redacted_line_number, Some(2));
+        assert_eq!(
+            lines[1].spans,
+            vec![
+                DiffSpan::Unchanged("Jane,".to_string()),
+                DiffSpan::Removed("jane@example.com".to_string()),
+                DiffSpan::Inserted("[EMAIL]".to_string()),
+            ]
+        );
+        assert_eq!(lines[1].changes(), 1);
+        assert_eq!(lines[2].original_line_number, None);
+        assert_eq!(lines[2].redacted_line_number, Some(5));
+        assert!(diff_text(original, original).is_empty());
+    }
+
+    #[test]
+    fn diff_line_spans_test() {
+        let spans = diff_line_spans(
+            "Call John Doe at 555-1234 today",
+            "Call [REDACTED] at [REDACTED] today",
+        );
+        assert_eq!(
+            spans,
+            vec![
+                DiffSpan::Unchanged("Call ".to_string()),
+                DiffSpan::Removed("John Doe".to_string()),
+                DiffSpan::Inserted("[REDACTED]".to_string()),
+                DiffSpan::Unchanged(" at ".to_string()),
+                DiffSpan::Removed("555-1234".to_string()),
+                DiffSpan::Inserted("[REDACTED]".to_string()),
+                DiffSpan::Unchanged(" today".to_string()),
+            ]
+        );
+        let line_diff = TextLineDiff {
+            original_line_number: Some(1),
+            redacted_line_number: Some(1),
+            spans,
+        };
+        assert_eq!(line_diff.changes(), 2);
+    }
+
+    #[test]
+    fn diff_images_test() {
+        let original = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            100,
+            60,
+            image::Rgb([255, 255, 255]),
+        ));
+        let mut redacted = original.to_rgb8();
+        for (x, y) in [(10..30, 10..20), (70..90, 40..50)]
+            .into_iter()
+            .flat_map(|(xs, ys)| xs.flat_map(move |x| ys.clone().map(move |y| (x, y))))
+        {
+            redacted.put_pixel(x, y, image::Rgb([0, 0, 0]));
+        }
+        let image_diff = diff_images(&original, &DynamicImage::ImageRgb8(redacted));
+        assert_eq!(
+            image_diff.regions,
+            vec![
+                ImageRegion {
+                    x: 8,
+                    y: 8,
+                    width: 24,
+                    height: 16
+                },
+                ImageRegion {
+                    x: 64,
+                    y: 40,
+                    width: 32,
+                    height: 16
+                },
+            ]
+        );
+        assert!((image_diff.changed_ratio - 400.0 / 6000.0).abs() < 1e-9);
+        assert!(!image_diff.resized);
+        assert!(diff_images(&original, &original).regions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn diff_directories_test() -> AppResult<()> {
+        let term = Term::stdout();
+        let temp_dir = tempfile::tempdir()?;
+        let original_dir = temp_dir.path().join("original");
+        let redacted_dir = temp_dir.path().join("redacted");
+        tokio::fs::create_dir_all(&original_dir).await?;
+        tokio::fs::create_dir_all(&redacted_dir).await?;
+        tokio::fs::write(original_dir.join("test.txt"), "Hello John Doe\n").await?;
+        tokio::fs::write(redacted_dir.join("test.txt"), "Hello [REDACTED]\n").await?;
+        tokio::fs::write(original_dir.join("other.txt"), "Not redacted\n").await?;
+        let html_report = temp_dir.path().join("report.html");
+
+        let result = command_diff(
+            &term,
+            &format!("{}/", original_dir.to_string_lossy()),
+            &format!("{}/", redacted_dir.to_string_lossy()),
+            DiffCommandOptions::new(
+                FileFilters::default(),
+                None,
+                vec![],
+                Some(html_report.clone()),
+                FileSystemOptions::default(),
+                AppReporterOptions::default(),
+            ),
+        )
+        .await?;
+        assert_eq!(result.files.len(), 2);
+        assert_eq!(result.files_changed, 1);
+        assert_eq!(result.files_missing, 1);
+        let html = tokio::fs::read_to_string(&html_report).await?;
+        assert!(html.contains("<del>John Doe</del>"));
+        assert!(html.contains("<ins>[REDACTED]</ins>"));
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use crate::common_types::GcpProjectId;
+use crate::errors::AppError;
+use crate::redacters::DictionaryRedacter;
+use crate::reporter::{AppReporter, AppReporterOptions};
+use crate::AppResult;
+use console::{Style, Term};
+use gcloud_sdk::google::privacy::dlp::v2::custom_info_type::{dictionary, Dictionary, Regex};
+use gcloud_sdk::google::privacy::dlp::v2::dlp_service_client::DlpServiceClient;
+use gcloud_sdk::google::privacy::dlp::v2::{
+    stored_info_type_config, CloudStoragePath, CreateStoredInfoTypeRequest, StoredInfoTypeConfig,
+    StoredInfoTypeState,
+};
+use gcloud_sdk::tonic::metadata::MetadataValue;
+use gcloud_sdk::{tonic, GoogleApi};
+use rvstruct::ValueStruct;
+use std::path::PathBuf;
+
+/// Detection rules of stored infoTypes
+#[derive(Debug, Clone, PartialEq)]
+pub enum DlpStoredInfoTypeSource {
+    /// Local dictionary file with a term per line, uploaded as a word list
+    DictionaryFile(PathBuf),
+    /// Dictionary file in GCS with a term per line, read by DLP
+    DictionaryGcsPath(String),
+    Regex(String),
+}
+
+impl DlpStoredInfoTypeSource {
+    pub fn dictionary(path: &str) -> Self {
+        if path.starts_with("gs://") {
+            DlpStoredInfoTypeSource::DictionaryGcsPath(path.to_string())
+        } else {
+            DlpStoredInfoTypeSource::DictionaryFile(PathBuf::from(path))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateStoredInfoTypeOptions {
+    pub project_id: GcpProjectId,
+    pub stored_info_type_id: String,
+    pub source: DlpStoredInfoTypeSource,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub reporter_options: AppReporterOptions,
+}
+
+/// Creates a stored infoType in the GCP project, so it can be used with --gcp-dlp-stored-info-type
+/// without managing DLP resources with other tools
+pub async fn command_dlp_create_stored_info_type(
+    term: &Term,
+    options: CreateStoredInfoTypeOptions,
+) -> AppResult<String> {
+    let bold_style = Style::new().bold();
+    let app_reporter = AppReporter::from(term).with_options(&options.reporter_options);
+    let config = stored_info_type_config(&options).await?;
+    app_reporter.report(format!(
+        "Creating stored infoType {} in project {}...",
+        bold_style.apply_to(&options.stored_info_type_id),
+        bold_style.apply_to(options.project_id.value())
+    ))?;
+    let client: GoogleApi<DlpServiceClient<_>> =
+        GoogleApi::from_function(DlpServiceClient::new, "https://dlp.googleapis.com", None).await?;
+    let mut request = tonic::Request::new(CreateStoredInfoTypeRequest {
+        parent: format!("projects/{}/locations/global", options.project_id.value()),
+        config: Some(config),
+        stored_info_type_id: options.stored_info_type_id.clone(),
+        ..CreateStoredInfoTypeRequest::default()
+    });
+    request.metadata_mut().insert(
+        "x-goog-user-project",
+        MetadataValue::<tonic::metadata::Ascii>::try_from(options.project_id.value())?,
+    );
+    let stored_info_type = client
+        .get()
+        .create_stored_info_type(request)
+        .await?
+        .into_inner();
+    let state = stored_info_type
+        .current_version
+        .or_else(|| stored_info_type.pending_versions.into_iter().next())
+        .and_then(|version| StoredInfoTypeState::try_from(version.state).ok())
+        .unwrap_or(StoredInfoTypeState::Unspecified);
+    app_reporter.report(format!(
+        "Created stored infoType {} ({}). Use it with: --gcp-dlp-stored-info-type {}",
+        bold_style.clone().green().apply_to(&stored_info_type.name),
+        state.as_str_name().to_lowercase(),
+        options.stored_info_type_id
+    ))?;
+    if state == StoredInfoTypeState::Pending {
+        app_reporter.report("↳ The infoType is ready to use when DLP finishes building it")?;
+    }
+    Ok(stored_info_type.name)
+}
+
+async fn stored_info_type_config(
+    options: &CreateStoredInfoTypeOptions,
+) -> AppResult<StoredInfoTypeConfig> {
+    let stored_info_type = match options.source {
+        DlpStoredInfoTypeSource::DictionaryFile(ref dictionary_file) => {
+            let dictionary = tokio::fs::read_to_string(dictionary_file)
+                .await
+                .map_err(|err| AppError::RedacterConfigError {
+                    message: format!(
+                        "Failed to read dictionary file {}: {}",
+                        dictionary_file.display(),
+                        err
+                    ),
+                })?;
+            let words: Vec<String> = DictionaryRedacter::dictionary_terms(&dictionary)
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+            if words.is_empty() {
+                return Err(AppError::RedacterConfigError {
+                    message: format!(
+                        "Dictionary file {} doesn't have any terms",
+                        dictionary_file.display()
+                    ),
+                });
+            }
+            stored_info_type_config::Type::Dictionary(Dictionary {
+                source: Some(dictionary::Source::WordList(dictionary::WordList { words })),
+            })
+        }
+        DlpStoredInfoTypeSource::DictionaryGcsPath(ref path) => {
+            stored_info_type_config::Type::Dictionary(Dictionary {
+                source: Some(dictionary::Source::CloudStoragePath(CloudStoragePath {
+                    path: path.clone(),
+                })),
+            })
+        }
+        DlpStoredInfoTypeSource::Regex(ref pattern) => {
+            stored_info_type_config::Type::Regex(Regex {
+                pattern: pattern.clone(),
+                group_indexes: vec![],
+            })
+        }
+    };
+    Ok(StoredInfoTypeConfig {
+        display_name: options
+            .display_name
+            .clone()
+            .unwrap_or_else(|| options.stored_info_type_id.clone()),
+        description: options.description.clone().unwrap_or_default(),
+        r#type: Some(stored_info_type),
+    })
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stored_info_type_config_test() -> AppResult<()> {
+        let temp_dir = tempfile::TempDir::with_prefix("dlp_command_tests")?;
+        let dictionary_file = temp_dir.path().join("terms.txt");
+        tokio::fs::write(&dictionary_file, "# Project names\nApollo\n\n  Gemini  \n").await?;
+        let options = CreateStoredInfoTypeOptions {
+            project_id: GcpProjectId::new("test-project".to_string()),
+            stored_info_type_id: "project-names".to_string(),
+            source: DlpStoredInfoTypeSource::dictionary(&dictionary_file.to_string_lossy()),
+            display_name: None,
+            description: None,
+            reporter_options: AppReporterOptions::default(),
+        };
+        let config = stored_info_type_config(&options).await?;
+        assert_eq!(config.display_name, "project-names");
+        assert_eq!(
+            config.r#type,
+            Some(stored_info_type_config::Type::Dictionary(Dictionary {
+                source: Some(dictionary::Source::WordList(dictionary::WordList {
+                    words: vec!["Apollo".to_string(), "Gemini".to_string()],
+                })),
+            }))
+        );
+
+        assert_eq!(
+            DlpStoredInfoTypeSource::dictionary("gs://my-bucket/terms.txt"),
+            DlpStoredInfoTypeSource::DictionaryGcsPath("gs://my-bucket/terms.txt".to_string())
+        );
+        Ok(())
+    }
+}
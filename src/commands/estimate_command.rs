@@ -0,0 +1,584 @@
+use crate::args::RedacterType;
+use crate::errors::AppError;
+use crate::file_converters::pdf::pdf_page_count;
+use crate::file_converters::tiff_pages::tiff_page_count;
+use crate::file_converters::FileConverters;
+use crate::file_systems::{
+    DetectFileSystem, FileSystemConnection, FileSystemOptions, FileSystemRef,
+};
+use crate::file_tools::{FileFilters, FileMatcher, FileMimeOverride};
+use crate::redacters::{
+    LlmTextChunker, Redacter, RedacterOptions, RedacterVerifier, Redacters, StreamRedactPlan,
+    StreamRedacter,
+};
+use crate::reporter::{AppReporter, AppReporterOptions};
+use crate::AppResult;
+use console::{pad_str, Alignment, Style, Term};
+use futures::StreamExt;
+use indicatif::HumanBytes;
+use rvstruct::ValueStruct;
+
+/// Approximate list prices in USD used to estimate costs of redaction runs.
+/// LLM prices assume ~4 bytes per token for both prompts and responses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RedacterPricing {
+    pub usd_per_gb: f64,
+    /// Providers such as AWS Comprehend bill a minimum amount of content per request
+    pub min_billed_bytes_per_call: usize,
+    /// LLMs bill images by image tokens and coordinates in responses regardless of the file size
+    pub usd_per_image: Option<f64>,
+}
+
+impl RedacterPricing {
+    pub fn for_redacter(redacter_type: &RedacterType) -> Self {
+        match redacter_type {
+            // Inspection and de-identification of content
+            RedacterType::GcpDlp => Self {
+                usd_per_gb: 3.0,
+                usd_per_image: None,
+                min_billed_bytes_per_call: 0,
+            },
+            // $0.0001 per unit of 100 characters, at least 3 units per request
+            RedacterType::AwsComprehend => Self {
+                usd_per_gb: 1000.0,
+                usd_per_image: None,
+                min_billed_bytes_per_call: 300,
+            },
+            // Self-hosted or local
+            RedacterType::MsPresidio
+            | RedacterType::External
+            | RedacterType::Dictionary
+            | RedacterType::Secrets
+            | RedacterType::Mock => Self {
+                usd_per_gb: 0.0,
+                usd_per_image: None,
+                min_billed_bytes_per_call: 0,
+            },
+            RedacterType::GeminiLlm | RedacterType::GcpVertexAi => Self {
+                usd_per_gb: 93.75,
+                min_billed_bytes_per_call: 0,
+                usd_per_image: Some(0.0001),
+            },
+            RedacterType::OpenAiLlm => Self {
+                usd_per_gb: 187.5,
+                min_billed_bytes_per_call: 0,
+                usd_per_image: Some(0.004),
+            },
+        }
+    }
+
+    pub fn billed_bytes(&self, content_size: usize, api_calls: usize) -> usize {
+        content_size.max(self.min_billed_bytes_per_call * api_calls)
+    }
+
+    pub fn cost(&self, bytes_billed: usize) -> f64 {
+        bytes_billed as f64 / 1_000_000_000.0 * self.usd_per_gb
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EstimateCommandOptions {
+    pub file_matcher: FileMatcher,
+    pub file_mime_override: FileMimeOverride,
+    pub max_files_limit: Option<usize>,
+    pub sample_fraction: f64,
+    pub sample_bytes: usize,
+    pub prices: Vec<(RedacterType, f64)>,
+    pub file_system_options: FileSystemOptions,
+    pub reporter_options: AppReporterOptions,
+}
+
+impl EstimateCommandOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        file_filters: FileFilters,
+        max_size_limit: Option<usize>,
+        max_files_limit: Option<usize>,
+        mime_override: Vec<(mime::Mime, globset::Glob)>,
+        sample_fraction: f64,
+        sample_bytes: usize,
+        prices: Vec<(RedacterType, f64)>,
+        file_system_options: FileSystemOptions,
+        reporter_options: AppReporterOptions,
+    ) -> Self {
+        EstimateCommandOptions {
+            file_matcher: FileMatcher::new(file_filters, max_size_limit),
+            file_mime_override: FileMimeOverride::new(mime_override),
+            max_files_limit,
+            sample_fraction,
+            sample_bytes,
+            prices,
+            file_system_options,
+            reporter_options,
+        }
+    }
+
+    /// Prices specified explicitly override the default pricing table
+    pub fn pricing(&self, redacter_type: &RedacterType) -> RedacterPricing {
+        let default_pricing = RedacterPricing::for_redacter(redacter_type);
+        match self
+            .prices
+            .iter()
+            .rev()
+            .find(|(price_redacter_type, _)| price_redacter_type == redacter_type)
+        {
+            Some((_, usd_per_gb)) => RedacterPricing {
+                usd_per_gb: *usd_per_gb,
+                ..default_pricing
+            },
+            None => default_pricing,
+        }
+    }
+
+    /// Indexes of files to sample, spread evenly across the listed files
+    pub fn sample_indexes(&self, files_count: usize) -> Vec<usize> {
+        if files_count == 0 || self.sample_fraction <= 0.0 {
+            return vec![];
+        }
+        let samples_count =
+            ((files_count as f64 * self.sample_fraction).ceil() as usize).clamp(1, files_count);
+        (0..samples_count)
+            .map(|idx| idx * files_count / samples_count)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EstimateRedacterResult {
+    pub redacter_type: RedacterType,
+    pub verification: bool,
+    pub files: usize,
+    pub api_calls: usize,
+    pub bytes_billed: usize,
+    /// Images and pages priced per image instead of bytes billed
+    pub images: usize,
+    pub estimated_cost: f64,
+    /// Entities projected from the detection results on sampled files,
+    /// if the redacter supports detection
+    pub projected_findings: Option<usize>,
+    text_bytes: usize,
+    sampled_bytes: usize,
+    sampled_findings: usize,
+}
+
+impl EstimateRedacterResult {
+    fn new(redacter_type: RedacterType, verification: bool) -> Self {
+        Self {
+            projected_findings: (redacter_type.supports_detection() && !verification).then_some(0),
+            redacter_type,
+            verification,
+            files: 0,
+            api_calls: 0,
+            bytes_billed: 0,
+            images: 0,
+            estimated_cost: 0.0,
+            text_bytes: 0,
+            sampled_bytes: 0,
+            sampled_findings: 0,
+        }
+    }
+
+    fn add_file(
+        &mut self,
+        pricing: &RedacterPricing,
+        content_size: usize,
+        api_calls: usize,
+        images: usize,
+        is_text: bool,
+    ) {
+        self.files += 1;
+        self.api_calls += api_calls;
+        match pricing.usd_per_image.filter(|_| images > 0) {
+            Some(usd_per_image) => {
+                self.images += images;
+                self.estimated_cost += images as f64 * usd_per_image;
+            }
+            None => {
+                let bytes_billed = pricing.billed_bytes(content_size, api_calls);
+                self.bytes_billed += bytes_billed;
+                self.estimated_cost += pricing.cost(bytes_billed);
+            }
+        }
+        if is_text {
+            self.text_bytes += content_size;
+        }
+    }
+
+    fn add_sample(&mut self, sampled_bytes: usize, findings: usize) {
+        self.sampled_bytes += sampled_bytes;
+        self.sampled_findings += findings;
+    }
+
+    /// Extrapolates findings in samples to all text content sent to the redacter
+    fn project_findings(&mut self) {
+        if self.projected_findings.is_some() && self.sampled_bytes > 0 {
+            self.projected_findings = Some(
+                (self.sampled_findings as f64 * self.text_bytes as f64 / self.sampled_bytes as f64)
+                    .round() as usize,
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EstimateCommandResult {
+    pub files_found: usize,
+    pub files_sampled: usize,
+    pub redacters: Vec<EstimateRedacterResult>,
+    pub estimated_cost: f64,
+}
+
+#[tracing::instrument(name = "command_estimate", skip_all, fields(source = source))]
+pub async fn command_estimate(
+    term: &Term,
+    source: &str,
+    options: EstimateCommandOptions,
+    redacter_options: Option<RedacterOptions>,
+) -> AppResult<EstimateCommandResult> {
+    let bold_style = Style::new().bold();
+    let dimmed_style = Style::new().dim();
+    let app_reporter = AppReporter::from(term).with_options(&options.reporter_options);
+    let redacter_options = redacter_options
+        .filter(|redacter_options| !redacter_options.provider_options.is_empty())
+        .ok_or_else(|| AppError::RedacterConfigError {
+            message: "At least one redacter is required to estimate costs".to_string(),
+        })?;
+    app_reporter.report(format!(
+        "Estimating redaction of {} using {}.",
+        bold_style.clone().white().apply_to(source),
+        bold_style.clone().green().apply_to(&redacter_options)
+    ))?;
+
+    let file_converters = FileConverters::new()
+        .init(&app_reporter, &redacter_options.ocr_options)
+        .await?;
+    let mut source_fs =
+        DetectFileSystem::open(source, &app_reporter, &options.file_system_options).await?;
+    let mut redacters = Vec::with_capacity(redacter_options.provider_options.len());
+    for provider_options in redacter_options.provider_options {
+        redacters.push(Redacters::new_redacter(provider_options, &app_reporter).await?);
+    }
+    // Verification calls are billed as any other provider calls
+    let verifier = match redacter_options.verify_options {
+        Some(verify_options) => Some(RedacterVerifier::new(verify_options, &app_reporter).await?),
+        None => None,
+    };
+    let stream_redacter = StreamRedacter::new(
+        &redacter_options.base_options,
+        &file_converters,
+        &app_reporter,
+        None,
+        None,
+        None,
+    );
+
+    let list_files_result = source_fs
+        .list_files(Some(&options.file_matcher), options.max_files_limit)
+        .await?;
+    let files: Vec<FileSystemRef> = list_files_result
+        .files
+        .into_iter()
+        .map(|file_ref| options.file_mime_override.override_for_file_ref(file_ref))
+        .collect();
+    let total_size: usize = files.iter().map(|f| f.file_size.unwrap_or(0)).sum();
+    app_reporter.report(format!(
+        "Found {} files. Total size: {}",
+        bold_style.apply_to(files.len()),
+        bold_style.apply_to(HumanBytes(total_size as u64))
+    ))?;
+
+    let mut results: Vec<EstimateRedacterResult> = redacters
+        .iter()
+        .map(|redacter| EstimateRedacterResult::new(redacter.redacter_type(), false))
+        .chain(
+            verifier
+                .iter()
+                .map(|verifier| EstimateRedacterResult::new(verifier.redacter_type(), true)),
+        )
+        .collect();
+    let text_chunkers: Vec<Option<LlmTextChunker>> = redacters
+        .iter()
+        .map(|redacter| redacter.llm_text_chunker())
+        .chain(verifier.iter().map(|verifier| verifier.llm_text_chunker()))
+        .collect();
+    let verifier_result_idx = verifier.as_ref().map(|_| redacters.len());
+    let sample_indexes = options.sample_indexes(files.len());
+    let mut files_sampled = 0;
+
+    for (file_idx, file_ref) in files.iter().enumerate() {
+        let redact_plan = stream_redacter
+            .create_redact_plan(&redacters, file_ref)
+            .await?;
+        if redact_plan.supported_redacters.is_empty() {
+            continue;
+        }
+        // Whole files are estimated with --sample-then-full, since any of them may have sensitive data
+        let content_size = match redacter_options.base_options.sampling_size {
+            Some(sampling_size) if !redacter_options.base_options.sample_then_full => {
+                file_ref.file_size.unwrap_or(0).min(sampling_size)
+            }
+            _ => file_ref.file_size.unwrap_or(0),
+        };
+        let is_text = file_ref.media_type.as_ref().is_some_and(|media_type| {
+            Redacters::is_mime_text(media_type) || Redacters::is_mime_table(media_type)
+        });
+        // PDF and TIFF pages are redacted as separate images with a call per page
+        let pages = count_pages(&mut source_fs, file_ref, &redact_plan).await?;
+        let is_image = !redact_plan.apply_ocr
+            && (redact_plan.apply_pdf_image_converter
+                || redact_plan.apply_tiff_pages_converter
+                || file_ref
+                    .media_type
+                    .as_ref()
+                    .is_some_and(Redacters::is_mime_image));
+        let supported_idxs: Vec<usize> = redacters
+            .iter()
+            .enumerate()
+            .filter(|(_, redacter)| {
+                redact_plan
+                    .supported_redacters
+                    .iter()
+                    .any(|supported| std::ptr::eq(*supported, *redacter))
+            })
+            .map(|(idx, _)| idx)
+            .chain(verifier_result_idx.filter(|_| is_text))
+            .collect();
+        for idx in supported_idxs.iter() {
+            let pricing = options.pricing(&results[*idx].redacter_type);
+            // LLMs redact large texts in chunks with a call per chunk
+            let api_calls = match &text_chunkers[*idx] {
+                Some(text_chunker) if is_text => text_chunker.estimate_chunks(content_size),
+                _ => pages,
+            };
+            let images = if is_image { pages } else { 0 };
+            results[*idx].add_file(&pricing, content_size, api_calls, images, is_text);
+        }
+
+        let detecting_idxs: Vec<usize> = supported_idxs
+            .into_iter()
+            .filter(|idx| results[*idx].projected_findings.is_some())
+            .collect();
+        if !is_text || detecting_idxs.is_empty() || !sample_indexes.contains(&file_idx) {
+            continue;
+        }
+        let sample = download_sample(&mut source_fs, file_ref, options.sample_bytes).await?;
+        files_sampled += 1;
+        let mut sample_findings = Vec::with_capacity(detecting_idxs.len());
+        for idx in detecting_idxs {
+            let redacter = &redacters[idx];
+            let findings = redacter.detect_text(sample.clone()).await?;
+            sample_findings.push(format!("{}: {}", redacter.redacter_type(), findings.len()));
+            results[idx].add_sample(sample.len(), findings.len());
+        }
+        app_reporter.report(format!(
+            "↳ Sampled {} ({}). Found entities: {}",
+            bold_style
+                .clone()
+                .white()
+                .apply_to(file_ref.relative_path.value()),
+            HumanBytes(sample.len() as u64),
+            sample_findings.join(", ")
+        ))?;
+    }
+    source_fs.close().await?;
+    for result in results.iter_mut() {
+        result.project_findings();
+    }
+
+    let estimated_cost: f64 = results.iter().map(|result| result.estimated_cost).sum();
+
+    app_reporter.report(format!(
+        "\n  {} {} {} {} {} {} {}",
+        dimmed_style.apply_to(pad_str("Redacter", 24, Alignment::Left, None)),
+        dimmed_style.apply_to(pad_str("Files", 10, Alignment::Left, None)),
+        dimmed_style.apply_to(pad_str("API calls", 10, Alignment::Left, None)),
+        dimmed_style.apply_to(pad_str("Bytes billed", 14, Alignment::Left, None)),
+        dimmed_style.apply_to(pad_str("Images", 10, Alignment::Left, None)),
+        dimmed_style.apply_to(pad_str("Est. cost", 12, Alignment::Left, None)),
+        dimmed_style.apply_to("Projected findings")
+    ))?;
+    for result in &results {
+        app_reporter.report(format!(
+            "- {} {} {} {} {} {} {}",
+            bold_style.clone().white().apply_to(pad_str(
+                if result.verification {
+                    format!("{} (verify)", result.redacter_type)
+                } else {
+                    result.redacter_type.to_string()
+                }
+                .as_str(),
+                24,
+                Alignment::Left,
+                None
+            )),
+            pad_str(result.files.to_string().as_str(), 10, Alignment::Left, None),
+            pad_str(
+                result.api_calls.to_string().as_str(),
+                10,
+                Alignment::Left,
+                None
+            ),
+            pad_str(
+                HumanBytes(result.bytes_billed as u64).to_string().as_str(),
+                14,
+                Alignment::Left,
+                None
+            ),
+            pad_str(
+                result.images.to_string().as_str(),
+                10,
+                Alignment::Left,
+                None
+            ),
+            bold_style.apply_to(pad_str(
+                format!("${:.2}", result.estimated_cost).as_str(),
+                12,
+                Alignment::Left,
+                None
+            )),
+            result
+                .projected_findings
+                .map(|findings| findings.to_string())
+                .unwrap_or_else(|| "-".to_string())
+        ))?;
+    }
+    app_reporter.report("")?;
+    app_reporter.report(format!(
+        "Estimated total cost: {} ({} of {} files sampled). Costs are based on approximate list prices, use --price to specify your own prices.",
+        bold_style.clone().yellow().apply_to(format!("${:.2}", estimated_cost)),
+        bold_style.apply_to(files_sampled),
+        bold_style.apply_to(files.len())
+    ))?;
+
+    Ok(EstimateCommandResult {
+        files_found: files.len(),
+        files_sampled,
+        redacters: results,
+        estimated_cost,
+    })
+}
+
+/// Pages of PDF and TIFF files converted to images, or a single page for other files
+async fn count_pages<'a, SFS: FileSystemConnection<'a>>(
+    source_fs: &mut SFS,
+    file_ref: &FileSystemRef,
+    redact_plan: &StreamRedactPlan<'_>,
+) -> AppResult<usize> {
+    if !redact_plan.apply_pdf_image_converter && !redact_plan.apply_tiff_pages_converter {
+        return Ok(1);
+    }
+    let (_, mut stream) = source_fs.download(Some(file_ref)).await?;
+    let mut content = Vec::with_capacity(file_ref.file_size.unwrap_or(0));
+    while let Some(chunk) = stream.next().await {
+        content.extend_from_slice(&chunk?);
+    }
+    let pages = if redact_plan.apply_pdf_image_converter {
+        pdf_page_count(&content)
+    } else {
+        tiff_page_count(&content)
+    };
+    Ok(pages.unwrap_or_else(|err| {
+        tracing::warn!(
+            file = file_ref.relative_path.value().as_str(),
+            "Failed to count pages, estimating a single page: {err}"
+        );
+        1
+    }))
+}
+
+/// Reads only the beginning of the file, so sampling large files is cheap
+async fn download_sample<'a, SFS: FileSystemConnection<'a>>(
+    source_fs: &mut SFS,
+    file_ref: &FileSystemRef,
+    sample_bytes: usize,
+) -> AppResult<String> {
+    let (_, mut stream) = source_fs.download(Some(file_ref)).await?;
+    let mut sample = Vec::with_capacity(sample_bytes);
+    while sample.len() < sample_bytes {
+        match stream.next().await {
+            Some(chunk) => {
+                let chunk = chunk?;
+                let remaining = sample_bytes - sample.len();
+                sample.extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+            }
+            None => break,
+        }
+    }
+    Ok(String::from_utf8_lossy(&sample).to_string())
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_indexes_test() {
+        let options = |sample_fraction: f64| {
+            EstimateCommandOptions::new(
+                FileFilters::default(),
+                None,
+                None,
+                vec![],
+                sample_fraction,
+                1024,
+                vec![],
+                FileSystemOptions::default(),
+                AppReporterOptions::default(),
+            )
+        };
+        assert_eq!(options(0.1).sample_indexes(20), vec![0, 10]);
+        assert_eq!(options(0.1).sample_indexes(3), vec![0]);
+        assert_eq!(options(1.0).sample_indexes(3), vec![0, 1, 2]);
+        assert!(options(0.0).sample_indexes(3).is_empty());
+        assert!(options(0.5).sample_indexes(0).is_empty());
+    }
+
+    #[test]
+    fn pricing_test() {
+        let options = EstimateCommandOptions::new(
+            FileFilters::default(),
+            None,
+            None,
+            vec![],
+            0.1,
+            1024,
+            vec![(RedacterType::GcpDlp, 1.5)],
+            FileSystemOptions::default(),
+            AppReporterOptions::default(),
+        );
+        let gcp_dlp = options.pricing(&RedacterType::GcpDlp);
+        assert_eq!(gcp_dlp.usd_per_gb, 1.5);
+        assert_eq!(gcp_dlp.cost(2_000_000_000), 3.0);
+
+        let aws_comprehend = options.pricing(&RedacterType::AwsComprehend);
+        assert_eq!(aws_comprehend.billed_bytes(100, 1), 300);
+        assert_eq!(aws_comprehend.billed_bytes(100, 2), 600);
+        assert_eq!(aws_comprehend.billed_bytes(1000, 1), 1000);
+
+        let mut result = EstimateRedacterResult::new(RedacterType::AwsComprehend, false);
+        result.add_file(&aws_comprehend, 100, 1, 0, true);
+        result.add_file(&aws_comprehend, 1900, 1, 0, true);
+        assert_eq!(result.api_calls, 2);
+        assert_eq!(result.bytes_billed, 2200);
+        result.add_sample(500, 3);
+        result.project_findings();
+        assert_eq!(result.projected_findings, Some(12));
+        assert_eq!(
+            EstimateRedacterResult::new(RedacterType::OpenAiLlm, false).projected_findings,
+            None
+        );
+
+        let open_ai = options.pricing(&RedacterType::OpenAiLlm);
+        let mut result = EstimateRedacterResult::new(RedacterType::OpenAiLlm, false);
+        result.add_file(&open_ai, 1_000_000, 3, 3, false);
+        result.add_file(&open_ai, 4000, 2, 0, true);
+        assert_eq!(result.api_calls, 5);
+        assert_eq!(result.images, 3);
+        assert_eq!(result.bytes_billed, 4000);
+        assert!((result.estimated_cost - (3.0 * 0.004 + open_ai.cost(4000))).abs() < 1e-9);
+        assert_eq!(
+            EstimateRedacterResult::new(RedacterType::MsPresidio, true).projected_findings,
+            None
+        );
+    }
+}
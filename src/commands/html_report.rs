@@ -0,0 +1,31 @@
+use crate::AppResult;
+use base64::Engine;
+use image::{DynamicImage, ImageFormat};
+
+pub fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn encode_png(image: &DynamicImage) -> AppResult<Vec<u8>> {
+    let mut png_bytes = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut png_bytes, ImageFormat::Png)?;
+    Ok(png_bytes.into_inner())
+}
+
+/// Small PNG previews of images, or `None` if images can't be decoded
+pub fn png_thumbnail(image_bytes: &[u8], max_size: u32) -> Option<Vec<u8>> {
+    image::load_from_memory(image_bytes)
+        .ok()
+        .and_then(|image| encode_png(&image.thumbnail(max_size, max_size)).ok())
+}
+
+/// Images are embedded into reports, so reports can be shared as single files
+pub fn png_data_uri(png_bytes: &[u8]) -> String {
+    format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(png_bytes)
+    )
+}
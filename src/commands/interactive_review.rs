@@ -0,0 +1,212 @@
+use crate::file_systems::FileSystemRef;
+use crate::redacters::{Redacter, Redacters};
+use crate::AppResult;
+use console::{Style, Term};
+use indicatif::{HumanBytes, ProgressBar};
+use rvstruct::ValueStruct;
+
+/// Decisions on redacted files reviewed before uploading them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewDecision {
+    Accept,
+    Skip,
+    /// Redact the original file again with the configured redacter at the index
+    Retry {
+        redacter_index: usize,
+    },
+}
+
+/// Interactive review of redacted files: a summary and changed lines of text files are displayed,
+/// and users are prompted to accept the redacted file, skip it or retry with another redacter
+pub struct InteractiveReview<'a> {
+    term: &'a Term,
+    bar: &'a ProgressBar,
+    redacter_names: Vec<String>,
+}
+
+impl<'a> InteractiveReview<'a> {
+    const MAX_PREVIEW_LINES: usize = 20;
+
+    pub fn new(term: &'a Term, bar: &'a ProgressBar, redacters: &[Redacters<'_>]) -> Self {
+        Self {
+            term,
+            bar,
+            redacter_names: redacters
+                .iter()
+                .map(|redacter| redacter.redacter_type().to_string())
+                .collect(),
+        }
+    }
+
+    /// Progress bars are suspended while users review files
+    pub fn review(
+        &self,
+        file_ref: &FileSystemRef,
+        original: &[u8],
+        redacted: &[u8],
+        number_of_redactions: usize,
+    ) -> AppResult<ReviewDecision> {
+        self.bar.suspend(|| {
+            self.display_summary(file_ref, original, redacted, number_of_redactions)?;
+            self.prompt()
+        })
+    }
+
+    fn display_summary(
+        &self,
+        file_ref: &FileSystemRef,
+        original: &[u8],
+        redacted: &[u8],
+        number_of_redactions: usize,
+    ) -> AppResult<()> {
+        let bold_style = Style::new().bold();
+        self.term.write_line(
+            format!(
+                "Review of {}: {} redactions applied, size {} → {}",
+                bold_style.apply_to(file_ref.relative_path.value()),
+                bold_style.apply_to(number_of_redactions),
+                HumanBytes(original.len() as u64),
+                HumanBytes(redacted.len() as u64)
+            )
+            .as_str(),
+        )?;
+        let text_preview = file_ref
+            .media_type
+            .as_ref()
+            .filter(|media_type| {
+                Redacters::is_mime_text(media_type) || Redacters::is_mime_table(media_type)
+            })
+            .and_then(|_| {
+                std::str::from_utf8(original)
+                    .ok()
+                    .zip(std::str::from_utf8(redacted).ok())
+            });
+        match text_preview {
+            Some((original_text, redacted_text)) => {
+                let changed_lines =
+                    text_diff_preview(original_text, redacted_text, Self::MAX_PREVIEW_LINES);
+                if changed_lines.is_empty() {
+                    self.term.write_line("  No changed lines")?;
+                }
+                for line in changed_lines {
+                    let line_style = if line.starts_with('-') {
+                        Style::new().red()
+                    } else if line.starts_with('+') {
+                        Style::new().green()
+                    } else {
+                        Style::new().dim()
+                    };
+                    self.term
+                        .write_line(format!("  {}", line_style.apply_to(line)).as_str())?;
+                }
+            }
+            None => {
+                self.term.write_line(
+                    format!(
+                        "  Preview is not available for {} files",
+                        file_ref
+                            .media_type
+                            .as_ref()
+                            .map(|media_type| media_type.to_string())
+                            .unwrap_or_else(|| "unknown".to_string())
+                    )
+                    .as_str(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn prompt(&self) -> AppResult<ReviewDecision> {
+        loop {
+            self.term
+                .write_str("Upload the redacted file? [a]ccept, [s]kip, [r]etry: ")?;
+            let answer = self.term.read_char()?;
+            self.term.write_line(answer.to_string().as_str())?;
+            match answer.to_ascii_lowercase() {
+                'a' => return Ok(ReviewDecision::Accept),
+                's' => return Ok(ReviewDecision::Skip),
+                'r' => {
+                    if let Some(redacter_index) = self.prompt_redacter()? {
+                        return Ok(ReviewDecision::Retry { redacter_index });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns `None` if the answer isn't a number of the configured redacters
+    fn prompt_redacter(&self) -> AppResult<Option<usize>> {
+        if let [_] = self.redacter_names.as_slice() {
+            return Ok(Some(0));
+        }
+        for (index, redacter_name) in self.redacter_names.iter().enumerate() {
+            self.term
+                .write_line(format!("  {}: {}", index + 1, redacter_name).as_str())?;
+        }
+        self.term.write_str("Retry with redacter number: ")?;
+        Ok(self
+            .term
+            .read_line()?
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|number| (1..=self.redacter_names.len()).contains(number))
+            .map(|number| number - 1))
+    }
+}
+
+/// Changed lines of text files as `-` original and `+` redacted lines,
+/// limited to the max number of lines with a note about the rest
+pub fn text_diff_preview(original: &str, redacted: &str, max_lines: usize) -> Vec<String> {
+    let mut original_lines = original.lines();
+    let mut redacted_lines = redacted.lines();
+    let mut changed_lines = Vec::new();
+    loop {
+        match (original_lines.next(), redacted_lines.next()) {
+            (None, None) => break,
+            (Some(original_line), Some(redacted_line)) if original_line == redacted_line => {}
+            (original_line, redacted_line) => {
+                changed_lines.extend(original_line.map(|line| format!("- {}", line)));
+                changed_lines.extend(redacted_line.map(|line| format!("+ {}", line)));
+            }
+        }
+    }
+    if changed_lines.len() > max_lines {
+        let more_lines = changed_lines.len() - max_lines;
+        changed_lines.truncate(max_lines);
+        changed_lines.push(format!("… {} more changed lines", more_lines));
+    }
+    changed_lines
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_diff_preview_test() {
+        let original = "Hello\nMy name is John Doe\nBye\nCall me at 555-1234\n";
+        let redacted = "Hello\nMy name is [REDACTED]\nBye\nCall me at [REDACTED]\n";
+        assert_eq!(
+            text_diff_preview(original, redacted, 10),
+            vec![
+                "- My name is John Doe",
+                "+ My name is [REDACTED]",
+                "- Call me at 555-1234",
+                "+ Call me at [REDACTED]"
+            ]
+        );
+        assert_eq!(
+            text_diff_preview(original, redacted, 2),
+            vec![
+                "- My name is John Doe",
+                "+ My name is [REDACTED]",
+                "… 2 more changed lines"
+            ]
+        );
+        assert_eq!(text_diff_preview("a\nb", "a", 10), vec!["- b".to_string()]);
+        assert!(text_diff_preview(original, original, 10).is_empty());
+    }
+}
@@ -1,57 +1,298 @@
+use crate::file_converters::FileConverters;
 use crate::file_systems::DetectFileSystem;
-use crate::file_systems::FileSystemConnection;
-use crate::file_tools::FileMatcher;
+use crate::file_systems::{FileSystemConnection, FileSystemOptions, FileSystemRef};
+use crate::file_tools::{FileFilters, FileMatcher};
+use crate::redacters::{Redacter, RedacterOptions, Redacters, StreamRedacter};
+use crate::reporter::{AppReporter, AppReporterOptions};
 use crate::AppResult;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
 use console::{pad_str, Alignment, Style, Term};
 use indicatif::{HumanBytes, TermLike};
 use rvstruct::ValueStruct;
+use serde::Serialize;
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LsSortBy {
+    Name,
+    Size,
+    Mtime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LsOutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl Display for LsOutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LsOutputFormat::Text => write!(f, "text"),
+            LsOutputFormat::Json => write!(f, "json"),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct LsCommandOptions {
     pub file_matcher: FileMatcher,
+    pub short_format: bool,
+    pub sort_by: Option<LsSortBy>,
+    pub output: LsOutputFormat,
+    pub file_system_options: FileSystemOptions,
+    pub reporter_options: AppReporterOptions,
 }
 
 impl LsCommandOptions {
-    pub fn new(filename_filter: Option<globset::Glob>, max_size_limit: Option<usize>) -> Self {
-        let filename_matcher = filename_filter
-            .as_ref()
-            .map(|filter| filter.compile_matcher());
+    pub fn new(
+        file_filters: FileFilters,
+        max_size_limit: Option<usize>,
+        short_format: bool,
+        sort_by: Option<LsSortBy>,
+        output: LsOutputFormat,
+        file_system_options: FileSystemOptions,
+        reporter_options: AppReporterOptions,
+    ) -> Self {
         LsCommandOptions {
-            file_matcher: FileMatcher::new(filename_matcher, max_size_limit),
+            file_matcher: FileMatcher::new(file_filters, max_size_limit),
+            short_format,
+            sort_by,
+            output,
+            file_system_options,
+            reporter_options,
         }
     }
 }
 
-pub async fn command_ls(term: &Term, source: &str, options: LsCommandOptions) -> AppResult<()> {
+/// Sorts by name ascending, and by size/modification time descending (the largest/newest first)
+fn sort_files(files: &mut [FileSystemRef], sort_by: LsSortBy) {
+    match sort_by {
+        LsSortBy::Name => {
+            files.sort_by(|a, b| a.relative_path.value().cmp(b.relative_path.value()))
+        }
+        LsSortBy::Size => files.sort_by_key(|f| std::cmp::Reverse(f.file_size)),
+        LsSortBy::Mtime => files.sort_by_key(|f| std::cmp::Reverse(f.modified_at)),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LsFileResult {
+    pub relative_path: String,
+    pub file_path: String,
+    pub media_type: Option<String>,
+    pub file_size: Option<usize>,
+    pub modified_at: Option<DateTime<Utc>>,
+    /// Redacters supporting the file (natively or with conversions), if any redacters are configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supported_redacters: Option<Vec<String>>,
+    /// Support of the file by every configured redacter on its own, if any redacters are configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redact_support: Option<Vec<LsRedacterSupport>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LsRedactSupport {
+    Native,
+    Conversion,
+    Unsupported,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LsRedacterSupport {
+    pub redacter: String,
+    pub support: LsRedactSupport,
+    /// Conversions applied before redaction such as `pdf-image` and `ocr`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conversions: Vec<String>,
+}
+
+impl Display for LsRedacterSupport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.support {
+            LsRedactSupport::Native => write!(f, "{}: native", self.redacter),
+            LsRedactSupport::Conversion => {
+                write!(f, "{}: {}", self.redacter, self.conversions.join("+"))
+            }
+            LsRedactSupport::Unsupported => write!(f, "{}: -", self.redacter),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LsSourceResult {
+    pub source: String,
+    pub files: Vec<LsFileResult>,
+    pub files_skipped: usize,
+}
+
+pub async fn command_ls(
+    term: &Term,
+    sources: &[String],
+    options: LsCommandOptions,
+    redacter_options: Option<RedacterOptions>,
+) -> AppResult<Vec<LsSourceResult>> {
     let bold_style = Style::new().bold();
-    let highlighted = bold_style.clone().white();
-    let dimmed_style = Style::new().dim();
-    term.write_line(format!("Listing files in {}.", bold_style.apply_to(source)).as_str())?;
-    let app_reporter = crate::reporter::AppReporter::from(term);
-    let mut source_fs = DetectFileSystem::open(source, &app_reporter).await?;
-    let list_files_result = source_fs
-        .list_files(Some(&options.file_matcher), None)
-        .await?;
-    let total_size: usize = list_files_result
-        .files
+    let app_reporter = AppReporter::from(term).with_options(&options.reporter_options);
+    let redacter_options =
+        redacter_options.filter(|redacter_options| !redacter_options.provider_options.is_empty());
+    let file_converters = match redacter_options {
+        Some(ref redacter_options) => {
+            FileConverters::new()
+                .init(&app_reporter, &redacter_options.ocr_options)
+                .await?
+        }
+        None => FileConverters::new(),
+    };
+    let mut redacters = Vec::new();
+    let mut redacter_base_options = None;
+    if let Some(redacter_options) = redacter_options {
+        for provider_options in redacter_options.provider_options {
+            redacters.push(Redacters::new_redacter(provider_options, &app_reporter).await?);
+        }
+        redacter_base_options = Some(redacter_options.base_options);
+    }
+    // Every redacter is planned on its own as well, so the support of files by redacters
+    // isn't hidden by other redacters supporting them natively
+    let provider_redacters: Vec<Vec<Redacters>> = redacters
         .iter()
-        .map(|f| f.file_size.unwrap_or(0))
-        .sum();
+        .map(|redacter| vec![redacter.clone()])
+        .collect();
+    let stream_redacter = redacter_base_options.as_ref().map(|base_options| {
+        StreamRedacter::new(
+            base_options,
+            &file_converters,
+            &app_reporter,
+            None,
+            None,
+            None,
+        )
+    });
 
-    if !list_files_result.files.is_empty() {
+    let mut results = Vec::with_capacity(sources.len());
+    for source in sources {
+        app_reporter.report(format!("Listing files in {}.", bold_style.apply_to(source)))?;
+        let mut source_fs =
+            DetectFileSystem::open(source, &app_reporter, &options.file_system_options).await?;
+        let mut list_files_result = source_fs
+            .list_files(Some(&options.file_matcher), None)
+            .await?;
+        if let Some(sort_by) = options.sort_by {
+            sort_files(&mut list_files_result.files, sort_by);
+        }
+        let mut files = Vec::with_capacity(list_files_result.files.len());
+        for file_ref in &list_files_result.files {
+            let supported_redacters = match stream_redacter {
+                Some(ref stream_redacter) => Some(
+                    stream_redacter
+                        .create_redact_plan(&redacters, file_ref)
+                        .await?
+                        .supported_redacters
+                        .iter()
+                        .map(|redacter| redacter.redacter_type().to_string())
+                        .collect(),
+                ),
+                None => None,
+            };
+            let redact_support = match stream_redacter {
+                Some(ref stream_redacter) => {
+                    let mut redact_support = Vec::with_capacity(provider_redacters.len());
+                    for (redacter, redacter_only) in redacters.iter().zip(&provider_redacters) {
+                        let redact_plan = stream_redacter
+                            .create_redact_plan(redacter_only, file_ref)
+                            .await?;
+                        let conversions = redact_plan.conversions();
+                        redact_support.push(LsRedacterSupport {
+                            redacter: redacter.redacter_type().to_string(),
+                            support: if redact_plan.supported_redacters.is_empty() {
+                                LsRedactSupport::Unsupported
+                            } else if conversions.is_empty() {
+                                LsRedactSupport::Native
+                            } else {
+                                LsRedactSupport::Conversion
+                            },
+                            conversions: conversions.into_iter().map(str::to_string).collect(),
+                        });
+                    }
+                    Some(redact_support)
+                }
+                None => None,
+            };
+            files.push(LsFileResult {
+                relative_path: file_ref.relative_path.value().clone(),
+                file_path: source_fs.resolve(Some(file_ref)).file_path,
+                media_type: file_ref.media_type.as_ref().map(|mime| mime.to_string()),
+                file_size: file_ref.file_size,
+                modified_at: file_ref.modified_at,
+                supported_redacters,
+                redact_support,
+            });
+        }
+        source_fs.close().await?;
+        let source_result = LsSourceResult {
+            source: source.clone(),
+            files,
+            files_skipped: list_files_result.skipped,
+        };
+        if options.output == LsOutputFormat::Text {
+            report_source_result(term, &app_reporter, &options, &source_result)?;
+        }
+        results.push(source_result);
+    }
+
+    if options.output == LsOutputFormat::Json {
+        term.write_line(serde_json::to_string_pretty(&results)?.as_str())?;
+    }
+    Ok(results)
+}
+
+fn report_source_result(
+    term: &Term,
+    app_reporter: &AppReporter<'_>,
+    options: &LsCommandOptions,
+    source_result: &LsSourceResult,
+) -> AppResult<()> {
+    let highlighted = Style::new().bold().white();
+    let dimmed_style = Style::new().dim();
+    let files = &source_result.files;
+    let total_size: usize = files.iter().map(|f| f.file_size.unwrap_or(0)).sum();
+
+    if !files.is_empty() && options.short_format {
+        term.write_line("")?;
+        for file in files {
+            term.write_line(
+                format!(
+                    "- {}{}",
+                    highlighted.apply_to(&file.relative_path),
+                    match file.redact_support {
+                        Some(ref redact_support) => format!(
+                            " {}",
+                            dimmed_style.apply_to(format_redact_support(redact_support))
+                        ),
+                        None => "".to_string(),
+                    }
+                )
+                .as_str(),
+            )?;
+        }
+        term.write_line("")?;
+    } else if !files.is_empty() {
         let max_filename_width = std::cmp::min(
-            list_files_result
-                .files
+            files
                 .iter()
-                .map(|f| f.relative_path.value().len())
+                .map(|f| f.relative_path.len())
                 .max()
                 .unwrap_or(25)
                 + 5,
             (term.width() * 2 / 3) as usize,
         );
+        let with_redacters = files.iter().any(|f| f.redact_support.is_some());
         term.write_line(
             format!(
-                "\n  {} {} {}",
+                "\n  {} {} {} {}{}",
                 dimmed_style.apply_to(pad_str(
                     "Filename",
                     max_filename_width,
@@ -59,27 +300,29 @@ pub async fn command_ls(term: &Term, source: &str, options: LsCommandOptions) ->
                     None
                 )),
                 dimmed_style.apply_to(pad_str("Media Type", 40, Alignment::Left, None)),
-                dimmed_style.apply_to(pad_str("Size", 16, Alignment::Left, None))
+                dimmed_style.apply_to(pad_str("Size", 16, Alignment::Left, None)),
+                dimmed_style.apply_to(pad_str("Modified", 20, Alignment::Left, None)),
+                if with_redacters {
+                    format!(" {}", dimmed_style.apply_to("Redacters"))
+                } else {
+                    "".to_string()
+                }
             )
             .as_str(),
         )?;
 
-        for file in &list_files_result.files {
+        for file in files {
             term.write_line(
                 format!(
-                    "- {} {} {}",
+                    "- {} {} {} {}{}",
                     highlighted.apply_to(pad_str(
-                        file.relative_path.value(),
+                        &file.relative_path,
                         max_filename_width,
                         Alignment::Left,
                         Some("...")
                     )),
                     pad_str(
-                        file.media_type
-                            .as_ref()
-                            .map(|mime| mime.to_string())
-                            .unwrap_or("".to_string())
-                            .as_str(),
+                        file.media_type.as_deref().unwrap_or(""),
                         40,
                         Alignment::Left,
                         None
@@ -93,28 +336,82 @@ pub async fn command_ls(term: &Term, source: &str, options: LsCommandOptions) ->
                         16,
                         Alignment::Left,
                         None
-                    ))
+                    )),
+                    pad_str(
+                        file.modified_at
+                            .map(|modified_at| modified_at.format("%Y-%m-%d %H:%M:%S").to_string())
+                            .unwrap_or_default()
+                            .as_str(),
+                        20,
+                        Alignment::Left,
+                        None
+                    ),
+                    match file.redact_support {
+                        Some(ref redact_support) => {
+                            format!(" {}", format_redact_support(redact_support))
+                        }
+                        None => "".to_string(),
+                    }
                 )
                 .as_str(),
             )?;
         }
         term.write_line("")?;
     }
-    term.write_line(
-        format!(
-            "{} files found. Total size: {}",
-            highlighted.apply_to(list_files_result.files.len()),
-            highlighted.apply_to(HumanBytes(total_size as u64))
-        )
-        .as_str(),
-    )?;
-    term.write_line(
-        format!(
-            "{} files skipped/filtered out.",
-            dimmed_style.apply_to(list_files_result.skipped.to_string())
-        )
-        .as_str(),
-    )?;
-    source_fs.close().await?;
+    app_reporter.report(format!(
+        "{} files found. Total size: {}",
+        highlighted.apply_to(files.len()),
+        highlighted.apply_to(HumanBytes(total_size as u64))
+    ))?;
+    app_reporter.report(format!(
+        "{} files skipped/filtered out.",
+        dimmed_style.apply_to(source_result.files_skipped.to_string())
+    ))?;
+    for (redacter, supported_natively, supported_with_conversions, unsupported) in
+        redact_support_summary(files)
+    {
+        app_reporter.report(format!(
+            "{}: {} files supported natively, {} with conversions, {} not supported.",
+            highlighted.apply_to(redacter),
+            supported_natively,
+            supported_with_conversions,
+            unsupported
+        ))?;
+    }
     Ok(())
 }
+
+fn format_redact_support(redact_support: &[LsRedacterSupport]) -> String {
+    redact_support
+        .iter()
+        .map(|redacter_support| redacter_support.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Numbers of files supported natively, with conversions and not supported by every redacter
+fn redact_support_summary(files: &[LsFileResult]) -> Vec<(String, usize, usize, usize)> {
+    let mut summary: Vec<(String, usize, usize, usize)> = Vec::new();
+    for redacter_support in files
+        .iter()
+        .flat_map(|file| file.redact_support.iter().flatten())
+    {
+        let idx = match summary
+            .iter()
+            .position(|(redacter, ..)| *redacter == redacter_support.redacter)
+        {
+            Some(idx) => idx,
+            None => {
+                summary.push((redacter_support.redacter.clone(), 0, 0, 0));
+                summary.len() - 1
+            }
+        };
+        let (_, native, conversion, unsupported) = &mut summary[idx];
+        match redacter_support.support {
+            LsRedactSupport::Native => *native += 1,
+            LsRedactSupport::Conversion => *conversion += 1,
+            LsRedactSupport::Unsupported => *unsupported += 1,
+        }
+    }
+    summary
+}
@@ -3,3 +3,29 @@ pub use copy_command::*;
 
 mod ls_command;
 pub use ls_command::*;
+
+mod watch_command;
+pub use watch_command::*;
+
+mod estimate_command;
+pub use estimate_command::*;
+
+mod html_report;
+
+mod copy_report;
+pub use copy_report::*;
+
+mod diff_command;
+pub use diff_command::*;
+
+mod aggregate_command;
+pub use aggregate_command::*;
+
+mod check_command;
+pub use check_command::*;
+
+mod dlp_command;
+pub use dlp_command::*;
+
+mod interactive_review;
+pub use interactive_review::*;
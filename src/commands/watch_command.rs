@@ -0,0 +1,163 @@
+use crate::commands::{command_copy, CopyCommandOptions};
+use crate::file_systems::{is_local_path, local_path_from_uri};
+use crate::metrics::{AppMetrics, MetricsExportOptions};
+use crate::redacters::RedacterOptions;
+use crate::reporter::AppReporter;
+use crate::AppResult;
+use console::{Style, Term};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct WatchCommandOptions {
+    pub copy_options: CopyCommandOptions,
+    pub poll_interval: Duration,
+    pub debounce: Duration,
+    pub metrics_export_options: MetricsExportOptions,
+}
+
+impl WatchCommandOptions {
+    pub fn new(
+        copy_options: CopyCommandOptions,
+        poll_interval: Duration,
+        debounce: Duration,
+        metrics_export_options: MetricsExportOptions,
+    ) -> Self {
+        WatchCommandOptions {
+            // Only new and changed files are copied on every run
+            copy_options: CopyCommandOptions {
+                if_newer: true,
+                ..copy_options
+            },
+            poll_interval,
+            debounce,
+            metrics_export_options,
+        }
+    }
+}
+
+/// Watches local directories using file system notifications and polls other sources on an interval,
+/// copying and redacting new and changed files.
+pub async fn command_watch(
+    term: &Term,
+    source: &str,
+    destination: &str,
+    options: WatchCommandOptions,
+    redacter_options: Option<RedacterOptions>,
+) -> AppResult<()> {
+    let bold_style = Style::new().bold();
+    let reporter = AppReporter::from(term).with_options(&options.copy_options.reporter_options);
+    let local_source_path = if is_local_path(source) {
+        Some(PathBuf::from(local_path_from_uri(source)))
+    } else {
+        None
+    };
+
+    run_copy(term, source, destination, &options, &redacter_options).await;
+
+    match local_source_path {
+        Some(source_path) => {
+            let (events_sender, mut events_receiver) = tokio::sync::mpsc::unbounded_channel();
+            let mut watcher =
+                notify::recommended_watcher(move |event_result: notify::Result<notify::Event>| {
+                    let _ = events_sender.send(event_result);
+                })?;
+            watcher.watch(&source_path, RecursiveMode::Recursive)?;
+            reporter.report(format!(
+                "Watching {} for changes. Press Ctrl-C to stop.",
+                bold_style.apply_to(source)
+            ))?;
+
+            while let Some(event_result) = events_receiver.recv().await {
+                match event_result {
+                    Ok(event) if is_relevant_event(&event) => {}
+                    Ok(_) => continue,
+                    Err(err) => {
+                        reporter.report_error(format!("File watcher error: {}", err))?;
+                        continue;
+                    }
+                }
+                // Waiting until files are not changing anymore
+                while let Ok(Some(_)) =
+                    tokio::time::timeout(options.debounce, events_receiver.recv()).await
+                {
+                }
+                run_copy(term, source, destination, &options, &redacter_options).await;
+            }
+            Ok(())
+        }
+        None => {
+            reporter.report(format!(
+                "Polling {} for changes every {} seconds. Press Ctrl-C to stop.",
+                bold_style.apply_to(source),
+                bold_style.apply_to(options.poll_interval.as_secs())
+            ))?;
+            loop {
+                tokio::time::sleep(options.poll_interval).await;
+                run_copy(term, source, destination, &options, &redacter_options).await;
+            }
+        }
+    }
+}
+
+fn is_relevant_event(event: &notify::Event) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Any
+    )
+}
+
+/// Errors are reported and don't stop watching, since they might be temporary.
+/// Metrics are exported after every run.
+async fn run_copy(
+    term: &Term,
+    source: &str,
+    destination: &str,
+    options: &WatchCommandOptions,
+    redacter_options: &Option<RedacterOptions>,
+) {
+    let reporter = AppReporter::from(term).with_options(&options.copy_options.reporter_options);
+    match command_copy(
+        term,
+        &[source.to_string()],
+        destination,
+        options.copy_options.clone(),
+        redacter_options.clone(),
+    )
+    .await
+    {
+        Ok(copy_result) => {
+            let _ = AppMetrics::global().set_last_success();
+            let _ = reporter.report(
+                format!(
+                    "Copied: {}. Redacted: {}. Skipped: {}.",
+                    Style::new()
+                        .bold()
+                        .green()
+                        .apply_to(copy_result.files_copied),
+                    Style::new()
+                        .bold()
+                        .green()
+                        .apply_to(copy_result.files_redacted),
+                    Style::new().yellow().apply_to(copy_result.files_skipped),
+                )
+                .as_str(),
+            );
+        }
+        Err(err) => {
+            let _ = reporter.report_error(
+                format!(
+                    "{}: {}. Waiting for next changes.",
+                    Style::new().bold().red().apply_to("Error"),
+                    err
+                )
+                .as_str(),
+            );
+        }
+    }
+    options
+        .metrics_export_options
+        .export_or_warn(AppMetrics::global(), &reporter)
+        .await;
+}
@@ -1,4 +1,4 @@
-use crate::redacters::RedacterThrottler;
+use crate::redacters::{RedacterThrottler, RedacterVolumeThrottler};
 use rvstruct::ValueStruct;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
@@ -9,8 +9,17 @@ pub struct GcpProjectId(String);
 #[derive(Debug, Clone, ValueStruct)]
 pub struct GcpRegion(String);
 
+/// Pub/Sub subscription in the format of 'projects/<project>/subscriptions/<subscription>'
 #[derive(Debug, Clone, ValueStruct)]
-pub struct AwsAccountId(String);
+pub struct GcpPubSubSubscription(String);
+
+/// ISO 639-1 language code of the content to redact such as 'en' or 'es'
+#[derive(Debug, Clone, PartialEq, Eq, ValueStruct)]
+pub struct RedactLanguage(String);
+
+/// BCP-47 language code of speech in audio files such as 'en-US'
+#[derive(Debug, Clone, PartialEq, Eq, ValueStruct)]
+pub struct AudioLanguageCode(String);
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct TextImageCoords {
@@ -21,6 +30,48 @@ pub struct TextImageCoords {
     pub text: Option<String>,
 }
 
+/// Transcribed word with its time range in audio
+#[derive(Debug, Clone)]
+pub struct TextAudioTimestamps {
+    pub start_secs: f64,
+    pub end_secs: f64,
+    pub text: String,
+}
+
+/// Color of redacted image regions specified as hex RGB such as '#000000'
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImageRedactionColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl ImageRedactionColor {
+    pub fn to_rgb(self) -> image::Rgb<u8> {
+        image::Rgb([self.red, self.green, self.blue])
+    }
+}
+
+impl FromStr for ImageRedactionColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!(
+                "Color must be specified as hex RGB such as '#000000', got '{}'",
+                s
+            ));
+        }
+        let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).unwrap_or(0);
+        Ok(Self {
+            red: channel(0),
+            green: channel(2),
+            blue: channel(4),
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DlpRequestLimit {
     pub value: usize,
@@ -73,3 +124,68 @@ impl FromStr for DlpRequestLimit {
         }
     }
 }
+
+/// What volume limits count: bytes of files or tokens of LLMs estimated from bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DlpVolumeUnit {
+    Bytes,
+    Tokens,
+}
+
+/// Limit of content sent to redacters per interval, since DLP and LLM quotas are often byte or token based
+#[derive(Debug, Clone)]
+pub struct DlpVolumeLimit {
+    pub value: usize,
+    pub per: std::time::Duration,
+    pub unit: DlpVolumeUnit,
+}
+
+impl DlpVolumeLimit {
+    /// LLM tokens are about 4 bytes of English text on average
+    const BYTES_PER_TOKEN: usize = 4;
+
+    pub fn new(value: usize, per: std::time::Duration, unit: DlpVolumeUnit) -> Self {
+        assert!(value > 0, "Limit value should be more than zero");
+        assert!(
+            per.as_millis() > 0,
+            "Limit duration should be more than zero"
+        );
+
+        Self { value, per, unit }
+    }
+
+    /// Volume of content with the size in bytes in units of the limit
+    pub fn volume_of(&self, size: usize) -> usize {
+        match self.unit {
+            DlpVolumeUnit::Bytes => size,
+            DlpVolumeUnit::Tokens => size.div_ceil(Self::BYTES_PER_TOKEN),
+        }
+    }
+
+    pub fn to_throttling_counter(&self) -> RedacterVolumeThrottler {
+        RedacterVolumeThrottler::new(self.clone())
+    }
+}
+
+impl FromStr for DlpVolumeLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let index = s.find(|c: char| !c.is_numeric()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(index);
+        let max_volume_in_units = number
+            .parse::<usize>()
+            .map_err(|e| format!("Failed to parse number in DlpVolumeLimit: {}", e))?;
+        let (unit, per) = match unit {
+            "bps" => (DlpVolumeUnit::Bytes, std::time::Duration::from_secs(1)),
+            "bpm" => (DlpVolumeUnit::Bytes, std::time::Duration::from_secs(60)),
+            "tps" => (DlpVolumeUnit::Tokens, std::time::Duration::from_secs(1)),
+            "tpm" => (DlpVolumeUnit::Tokens, std::time::Duration::from_secs(60)),
+            unknown => return Err(format!("Unknown unit specified: {}", unknown)),
+        };
+        if max_volume_in_units == 0 {
+            return Err("Limit value should be more than zero".to_string());
+        }
+        Ok(DlpVolumeLimit::new(max_volume_in_units, per, unit))
+    }
+}
@@ -13,12 +13,26 @@ pub enum AppError {
     InputOutputError(#[from] std::io::Error),
     #[error("Destination '{destination}' doesn't support multiple files. Trailing slash needed?")]
     DestinationDoesNotSupportMultipleFiles { destination: String },
+    #[error("Source '{source_path}' must be a directory to copy from multiple sources. Trailing slash needed?")]
+    SourceDoesNotSupportMultipleSources { source_path: String },
+    #[error("Source '{source_path}' is the same as the destination. Use --in-place to redact files in place")]
+    SourceIsDestination { source_path: String },
+    #[error("In-place redaction of '{source_path}' is not supported: {reason}")]
+    InPlaceNotSupported { source_path: String, reason: String },
+    #[error("File '{relative_path}' exists in multiple sources: {sources}. Use --on-collision to skip, overwrite or rename such files")]
+    DestinationCollision {
+        relative_path: String,
+        sources: String,
+    },
+    #[error("Destination '{destination_path}' already exists. Use --on-conflict to skip, overwrite or rename such files")]
+    DestinationExists { destination_path: String },
     #[error("Google Cloud REST SDK error:\n{0}")]
     GoogleCloudRestSdkError(#[from] gcloud_sdk::error::Error),
     #[error("Google Cloud REST SDK API error:\n{0:?}")]
     GoogleCloudRestSdkApiError(Box<dyn std::fmt::Debug + Send + Sync + 'static>),
+    /// Boxed, since gRPC statuses are much larger than other errors
     #[error("Google Cloud SDK error:\n{0}")]
-    GoogleCloudGrpcError(#[from] gcloud_sdk::tonic::Status),
+    GoogleCloudGrpcError(Box<gcloud_sdk::tonic::Status>),
     #[error("Google Cloud invalid metadata value:\n{0}")]
     GoogleCloudInvalidMetadataValue(#[from] InvalidMetadataValue),
     #[error("AWS SDK error occurred")]
@@ -29,6 +43,10 @@ pub enum AppError {
     HttpClientError(#[from] reqwest::Error),
     #[error("Zip error:\n{0}")]
     ZipError(#[from] zip::result::ZipError),
+    #[error("Zip archive '{zip_path}' exceeds limits: {reason}")]
+    ZipArchiveLimitExceeded { zip_path: String, reason: String },
+    #[error("File exceeds the maximum size of {max_item_size} bytes to redact")]
+    ItemSizeLimitExceeded { max_item_size: usize },
     #[error("CSV parser error:\n{0}")]
     CsvParserError(#[from] csv_async::Error),
     #[error("Redacter config error: {message}")]
@@ -37,8 +55,12 @@ pub enum AppError {
     TemplateError(#[from] TemplateError),
     #[error("PDF conversion error: {0}")]
     PdfiumError(#[from] pdfium_render::prelude::PdfiumError),
+    #[error("PDF document error: {0}")]
+    PdfDocumentError(#[from] lopdf::Error),
     #[error("Image conversion error: {0}")]
     ImageError(#[from] image::ImageError),
+    #[error("TIFF conversion error: {0}")]
+    TiffError(#[from] tiff::TiffError),
     #[cfg(feature = "clipboard")]
     #[error("Clipboard error: {0}")]
     ClipboardError(#[from] arboard::Error),
@@ -52,6 +74,36 @@ pub enum AppError {
     #[cfg(feature = "ocr")]
     #[error("OCR image error: {0}")]
     OcrImageError(#[from] ocrs::ImageSourceError),
+    #[cfg(feature = "heif")]
+    #[error("HEIF conversion error: {0}")]
+    HeifError(#[from] libheif_rs::HeifError),
+    #[cfg(feature = "kafka")]
+    #[error("Kafka error: {0}")]
+    KafkaError(#[from] rdkafka::error::KafkaError),
+    #[cfg(feature = "postgres")]
+    #[error("PostgreSQL error: {0}")]
+    PostgresError(#[from] tokio_postgres::Error),
+    #[error("File watcher error: {0}")]
+    FileWatcherError(#[from] notify::Error),
+    #[error("Configuration error: {message}")]
+    ConfigError { message: String },
+    #[error("Error of {provider}: {message}")]
+    ProviderError {
+        provider: String,
+        /// Transient errors such as rate limits and unavailable services, which may succeed if retried
+        retryable: bool,
+        /// HTTP status of failed API calls
+        status: Option<u16>,
+        message: String,
+    },
+    #[error("File system error for {scheme} path '{path}': {message}")]
+    FileSystemError {
+        scheme: String,
+        path: String,
+        message: String,
+    },
+    #[error("Conversion error: {message}")]
+    ConversionError { message: String },
     #[error("System error: {message}")]
     SystemError { message: String },
     #[error("System error: {message}")]
@@ -61,6 +113,137 @@ pub enum AppError {
     },
 }
 
+impl AppError {
+    pub const EXIT_CODE_FAILED: i32 = 1;
+    pub const EXIT_CODE_CONFIG_ERROR: i32 = 5;
+    pub const EXIT_CODE_PROVIDER_ERROR: i32 = 6;
+    pub const EXIT_CODE_FILE_SYSTEM_ERROR: i32 = 7;
+
+    /// Errors of provider API calls with HTTP statuses. Rate limits, timeouts and server errors are retryable
+    pub fn provider_http_error(
+        provider: impl ToString,
+        status: reqwest::StatusCode,
+        message: String,
+    ) -> Self {
+        Self::ProviderError {
+            provider: provider.to_string(),
+            retryable: status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || status == reqwest::StatusCode::REQUEST_TIMEOUT
+                || status.is_server_error(),
+            status: Some(status.as_u16()),
+            message,
+        }
+    }
+
+    /// Errors of providers such as invalid responses, which aren't retryable
+    pub fn provider_error(provider: impl ToString, message: String) -> Self {
+        Self::ProviderError {
+            provider: provider.to_string(),
+            retryable: false,
+            status: None,
+            message,
+        }
+    }
+
+    /// Stable codes of error categories for JSON results and scripts, which don't change with messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::UnknownFileSystem { .. }
+            | AppError::UnknownRedacter { .. }
+            | AppError::DestinationDoesNotSupportMultipleFiles { .. }
+            | AppError::SourceDoesNotSupportMultipleSources { .. }
+            | AppError::SourceIsDestination { .. }
+            | AppError::InPlaceNotSupported { .. }
+            | AppError::DestinationCollision { .. }
+            | AppError::GoogleCloudInvalidMetadataValue(_)
+            | AppError::RedacterConfigError { .. }
+            | AppError::ConfigError { .. } => "config_error",
+            AppError::GoogleCloudRestSdkError(_)
+            | AppError::GoogleCloudRestSdkApiError(_)
+            | AppError::GoogleCloudGrpcError(_)
+            | AppError::AwsSdkError(_)
+            | AppError::HttpClientError(_) => "api_error",
+            AppError::ProviderError { .. } => "provider_error",
+            AppError::InputOutputError(_) => "io_error",
+            AppError::ZipError(_)
+            | AppError::DestinationExists { .. }
+            | AppError::FileWatcherError(_)
+            | AppError::FileSystemError { .. } => "file_system_error",
+            #[cfg(feature = "clipboard")]
+            AppError::ClipboardError(_) => "file_system_error",
+            #[cfg(feature = "kafka")]
+            AppError::KafkaError(_) => "file_system_error",
+            #[cfg(feature = "postgres")]
+            AppError::PostgresError(_) => "file_system_error",
+            AppError::ZipArchiveLimitExceeded { .. } | AppError::ItemSizeLimitExceeded { .. } => {
+                "limit_exceeded"
+            }
+            AppError::MimeError(_)
+            | AppError::CsvParserError(_)
+            | AppError::PdfiumError(_)
+            | AppError::PdfDocumentError(_)
+            | AppError::ImageError(_)
+            | AppError::TiffError(_)
+            | AppError::ConversionError { .. } => "conversion_error",
+            #[cfg(feature = "ocr")]
+            AppError::OcrModelLoadError(_) | AppError::OcrImageError(_) => "conversion_error",
+            #[cfg(feature = "heif")]
+            AppError::HeifError(_) => "conversion_error",
+            AppError::TemplateError(_)
+            | AppError::SystemTimeError(_)
+            | AppError::JsonSerializeError(_)
+            | AppError::SystemError { .. }
+            | AppError::SystemErrorWithCause { .. } => "system_error",
+        }
+    }
+
+    /// Whether the operation may succeed if retried, such as after network errors and rate limits.
+    /// Errors of configurations, limits and conversions of files fail the same way again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AppError::ProviderError { retryable, .. } => *retryable,
+            AppError::GoogleCloudGrpcError(status) => matches!(
+                status.code(),
+                gcloud_sdk::tonic::Code::Unavailable
+                    | gcloud_sdk::tonic::Code::ResourceExhausted
+                    | gcloud_sdk::tonic::Code::DeadlineExceeded
+                    | gcloud_sdk::tonic::Code::Aborted
+                    | gcloud_sdk::tonic::Code::Internal
+                    | gcloud_sdk::tonic::Code::Unknown
+            ),
+            AppError::HttpClientError(err) => {
+                err.is_timeout()
+                    || err.is_connect()
+                    || err.is_body()
+                    || err.status().is_some_and(|status| {
+                        status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+                    })
+            }
+            AppError::InputOutputError(_)
+            | AppError::GoogleCloudRestSdkError(_)
+            | AppError::GoogleCloudRestSdkApiError(_)
+            | AppError::AwsSdkError(_)
+            | AppError::ZipError(_)
+            | AppError::SystemErrorWithCause { .. } => true,
+            #[cfg(feature = "kafka")]
+            AppError::KafkaError(_) => true,
+            #[cfg(feature = "postgres")]
+            AppError::PostgresError(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Exit codes of failed commands by the category of errors
+    pub fn exit_code(&self) -> i32 {
+        match self.code() {
+            "config_error" => Self::EXIT_CODE_CONFIG_ERROR,
+            "api_error" | "provider_error" => Self::EXIT_CODE_PROVIDER_ERROR,
+            "file_system_error" | "io_error" => Self::EXIT_CODE_FILE_SYSTEM_ERROR,
+            _ => Self::EXIT_CODE_FAILED,
+        }
+    }
+}
+
 impl<
         O: std::error::Error + std::fmt::Debug + Send + Sync + 'static,
         H: std::fmt::Debug + Send + Sync + 'static,
@@ -79,6 +262,12 @@ impl<T: std::fmt::Debug + Send + Sync + 'static>
     }
 }
 
+impl From<gcloud_sdk::tonic::Status> for AppError {
+    fn from(status: gcloud_sdk::tonic::Status) -> Self {
+        Self::GoogleCloudGrpcError(Box::new(status))
+    }
+}
+
 impl From<anyhow::Error> for AppError {
     fn from(err: anyhow::Error) -> Self {
         Self::SystemErrorWithCause {
@@ -87,3 +276,42 @@ impl From<anyhow::Error> for AppError {
         }
     }
 }
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_codes_test() {
+        let rate_limited = AppError::provider_http_error(
+            "ms-presidio",
+            reqwest::StatusCode::TOO_MANY_REQUESTS,
+            "Too many requests".to_string(),
+        );
+        assert_eq!(rate_limited.code(), "provider_error");
+        assert!(rate_limited.is_retryable());
+        assert_eq!(rate_limited.exit_code(), AppError::EXIT_CODE_PROVIDER_ERROR);
+
+        let bad_request = AppError::provider_http_error(
+            "ms-presidio",
+            reqwest::StatusCode::BAD_REQUEST,
+            "Invalid entities".to_string(),
+        );
+        assert!(!bad_request.is_retryable());
+
+        let config_error = AppError::ConfigError {
+            message: "Invalid pattern".to_string(),
+        };
+        assert_eq!(config_error.code(), "config_error");
+        assert!(!config_error.is_retryable());
+        assert_eq!(config_error.exit_code(), AppError::EXIT_CODE_CONFIG_ERROR);
+
+        let io_error = AppError::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset));
+        assert!(io_error.is_retryable());
+        assert_eq!(io_error.exit_code(), AppError::EXIT_CODE_FILE_SYSTEM_ERROR);
+
+        let size_error = AppError::ItemSizeLimitExceeded { max_item_size: 10 };
+        assert_eq!(size_error.code(), "limit_exceeded");
+        assert_eq!(size_error.exit_code(), AppError::EXIT_CODE_FAILED);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::errors::AppError;
+use crate::AppResult;
+use gcloud_sdk::prost::bytes;
+
+/// PCM WAV audio, so time ranges of audio can be silenced or replaced with a beep
+/// without decoding and re-encoding the whole file.
+#[derive(Debug, Clone)]
+pub struct WavAudio {
+    data: Vec<u8>,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    samples_offset: usize,
+    samples_len: usize,
+}
+
+/// How the time ranges are obfuscated in audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavAudioMask {
+    Silence,
+    Beep,
+}
+
+impl WavAudio {
+    const FORMAT_PCM: u16 = 1;
+    const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+    const BEEP_FREQUENCY: f64 = 1000.0;
+    const BEEP_AMPLITUDE: f64 = 0.25;
+
+    pub fn parse(data: &[u8]) -> AppResult<Self> {
+        let invalid = |message: &str| AppError::ConversionError {
+            message: format!("Invalid WAV audio: {}", message),
+        };
+        if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+            return Err(invalid("no RIFF/WAVE header"));
+        }
+        let read_u16 = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes([
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ])
+        };
+        let mut format = None;
+        let mut samples = None;
+        let mut chunk_offset = 12;
+        while chunk_offset + 8 <= data.len() {
+            let chunk_id = &data[chunk_offset..chunk_offset + 4];
+            let chunk_len = read_u32(chunk_offset + 4) as usize;
+            let chunk_data_offset = chunk_offset + 8;
+            // Streamed files may have the length of the last chunk unspecified
+            let chunk_data_len = chunk_len.min(data.len() - chunk_data_offset);
+            match chunk_id {
+                b"fmt " if chunk_data_len >= 16 => {
+                    format = Some((
+                        read_u16(chunk_data_offset),
+                        read_u16(chunk_data_offset + 2),
+                        read_u32(chunk_data_offset + 4),
+                        read_u16(chunk_data_offset + 14),
+                    ));
+                }
+                b"data" => {
+                    samples = Some((chunk_data_offset, chunk_data_len));
+                }
+                _ => {}
+            }
+            // Chunks are aligned to words
+            chunk_offset = chunk_data_offset + chunk_len + chunk_len % 2;
+        }
+        let (format_tag, channels, sample_rate, bits_per_sample) =
+            format.ok_or_else(|| invalid("no format chunk"))?;
+        let (samples_offset, samples_len) = samples.ok_or_else(|| invalid("no data chunk"))?;
+        if format_tag != Self::FORMAT_PCM && format_tag != Self::FORMAT_EXTENSIBLE {
+            return Err(invalid("only PCM audio is supported"));
+        }
+        if !matches!(bits_per_sample, 8 | 16 | 24 | 32) || channels == 0 || sample_rate == 0 {
+            return Err(invalid("unsupported audio format"));
+        }
+        Ok(Self {
+            data: data.to_vec(),
+            channels,
+            sample_rate,
+            bits_per_sample,
+            samples_offset,
+            samples_len,
+        })
+    }
+
+    pub fn mask_time_ranges(&mut self, time_ranges: &[(f64, f64)], mask: WavAudioMask) {
+        let bytes_per_sample = (self.bits_per_sample / 8) as usize;
+        let block_align = bytes_per_sample * self.channels as usize;
+        let frames_len = self.samples_len / block_align;
+        for (start_secs, end_secs) in time_ranges {
+            let start_frame =
+                ((start_secs.max(0.0) * self.sample_rate as f64) as usize).min(frames_len);
+            let end_frame =
+                ((end_secs.max(0.0) * self.sample_rate as f64).ceil() as usize).min(frames_len);
+            for frame in start_frame..end_frame {
+                let value = match mask {
+                    WavAudioMask::Silence => 0.0,
+                    WavAudioMask::Beep => {
+                        Self::BEEP_AMPLITUDE
+                            * (2.0 * std::f64::consts::PI * Self::BEEP_FREQUENCY * frame as f64
+                                / self.sample_rate as f64)
+                                .sin()
+                    }
+                };
+                for channel in 0..self.channels as usize {
+                    let offset =
+                        self.samples_offset + frame * block_align + channel * bytes_per_sample;
+                    Self::write_sample(&mut self.data[offset..offset + bytes_per_sample], value);
+                }
+            }
+        }
+    }
+
+    /// Writes a sample from -1.0 to 1.0. 8-bit samples are unsigned, others are signed.
+    fn write_sample(sample: &mut [u8], value: f64) {
+        match sample.len() {
+            1 => sample[0] = (128.0 + value * 127.0) as u8,
+            bytes_len => {
+                let max = ((1i64 << (bytes_len * 8 - 1)) - 1) as f64;
+                let encoded = ((value * max) as i64).to_le_bytes();
+                sample.copy_from_slice(&encoded[..bytes_len]);
+            }
+        }
+    }
+
+    pub fn into_bytes(self) -> bytes::Bytes {
+        self.data.into()
+    }
+}
+
+/// Sample rate from the first MPEG audio frame header, skipping ID3v2 tags
+pub fn mp3_sample_rate(data: &[u8]) -> Option<u32> {
+    let mut offset = 0;
+    if data.len() >= 10 && &data[0..3] == b"ID3" {
+        // ID3v2 tag size is encoded with 7 bits per byte
+        let tag_size = data[6..10]
+            .iter()
+            .fold(0usize, |size, byte| (size << 7) | (*byte & 0x7F) as usize);
+        offset = 10 + tag_size;
+    }
+    while offset + 4 <= data.len() {
+        if data[offset] == 0xFF && data[offset + 1] & 0xE0 == 0xE0 {
+            let version = (data[offset + 1] >> 3) & 0x03;
+            let sample_rate_index = ((data[offset + 2] >> 2) & 0x03) as usize;
+            let base_sample_rate = [44100, 48000, 32000].get(sample_rate_index)?;
+            return match version {
+                0b11 => Some(*base_sample_rate),
+                0b10 => Some(base_sample_rate / 2),
+                0b00 => Some(base_sample_rate / 4),
+                _ => None,
+            };
+        }
+        offset += 1;
+    }
+    None
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wav_mask_time_ranges_test() -> Result<(), Box<dyn std::error::Error>> {
+        let sample_rate: u32 = 100;
+        let samples: Vec<u8> = (0..200)
+            .flat_map(|_| 1000i16.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let mut wav_bytes = Vec::new();
+        wav_bytes.extend_from_slice(b"RIFF");
+        wav_bytes.extend_from_slice(&(36 + samples.len() as u32).to_le_bytes());
+        wav_bytes.extend_from_slice(b"WAVEfmt ");
+        wav_bytes.extend_from_slice(&16u32.to_le_bytes());
+        wav_bytes.extend_from_slice(&1u16.to_le_bytes());
+        wav_bytes.extend_from_slice(&1u16.to_le_bytes());
+        wav_bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        wav_bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        wav_bytes.extend_from_slice(&2u16.to_le_bytes());
+        wav_bytes.extend_from_slice(&16u16.to_le_bytes());
+        wav_bytes.extend_from_slice(b"data");
+        wav_bytes.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+        wav_bytes.extend_from_slice(&samples);
+
+        let mut wav_audio = WavAudio::parse(&wav_bytes)?;
+        assert_eq!(wav_audio.sample_rate, sample_rate);
+        assert_eq!(wav_audio.channels, 1);
+        wav_audio.mask_time_ranges(&[(0.5, 1.0)], WavAudioMask::Silence);
+        let masked = wav_audio.into_bytes();
+        let sample_at =
+            |frame: usize| i16::from_le_bytes([masked[44 + frame * 2], masked[45 + frame * 2]]);
+        assert_eq!(sample_at(49), 1000);
+        assert_eq!(sample_at(50), 0);
+        assert_eq!(sample_at(99), 0);
+        assert_eq!(sample_at(100), 1000);
+
+        assert!(WavAudio::parse(b"RIFF\0\0\0\0WAVE").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn mp3_sample_rate_test() {
+        // MPEG-1 Layer III, 128 kbps, 44100 Hz
+        assert_eq!(mp3_sample_rate(&[0xFF, 0xFB, 0x90, 0x64]), Some(44100));
+        // MPEG-2 Layer III, 22050 Hz after ID3v2 tag
+        assert_eq!(
+            mp3_sample_rate(&[b'I', b'D', b'3', 4, 0, 0, 0, 0, 0, 1, 0, 0xFF, 0xF3, 0x90, 0x64]),
+            Some(22050)
+        );
+        assert_eq!(mp3_sample_rate(b"not an mp3"), None);
+    }
+}
@@ -0,0 +1,357 @@
+use crate::errors::AppError;
+use crate::file_converters::xml_package::{PackageImage, XmlPackage};
+use crate::AppResult;
+use gcloud_sdk::prost::bytes;
+use std::collections::HashMap;
+use std::ops::Range;
+use xmlparser::{ElementEnd, Token, Tokenizer};
+
+/// Text of paragraphs (body, headers, footers, notes and comments) and images of a Word document.
+/// Redacted text is written back to the XML of parts in place, so only paragraphs with changed
+/// text lose their inline formatting.
+/// Embedded images are kept as is, unless redacted images are written back with the text.
+pub struct DocxDocument {
+    package: XmlPackage,
+    parts: Vec<DocxPart>,
+    blocks: Vec<DocxBlock>,
+    images: Vec<PackageImage>,
+}
+
+struct DocxPart {
+    name: String,
+    xml: String,
+}
+
+/// Text of a paragraph (or of a line of a paragraph with line breaks)
+struct DocxBlock {
+    part: usize,
+    pieces: Vec<DocxPiece>,
+}
+
+/// Text of `<w:t>` elements or a `<w:tab/>` element of runs
+struct DocxPiece {
+    span: Range<usize>,
+    text: String,
+    is_tab: bool,
+}
+
+enum DocxElement {
+    Paragraph,
+    Run,
+    Text,
+    Tab,
+    LineBreak,
+    Other,
+}
+
+impl DocxDocument {
+    const THUMBNAIL: &'static str = "docProps/thumbnail.jpeg";
+    const TEXT_PARTS: [&'static str; 6] = [
+        "word/document",
+        "word/header",
+        "word/footer",
+        "word/footnotes",
+        "word/endnotes",
+        "word/comments",
+    ];
+    const MEDIA: &'static str = "word/media/";
+
+    pub fn is_mime_docx(mime: &mime::Mime) -> bool {
+        mime.essence_str()
+            == "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+    }
+
+    pub fn open(package: bytes::Bytes) -> AppResult<Self> {
+        let package = XmlPackage::new(package);
+        let mut parts = package.read_xml_entries(|name| Self::part_order(name).is_some())?;
+        parts.sort_by_key(|(name, _)| Self::part_order(name));
+        let images = package.read_images(Self::MEDIA)?;
+        let mut document = Self {
+            package,
+            parts: Vec::new(),
+            blocks: Vec::new(),
+            images,
+        };
+        for (name, xml) in parts {
+            document.parse_part(document.parts.len(), &xml)?;
+            document.parts.push(DocxPart { name, xml });
+        }
+        Ok(document)
+    }
+
+    pub fn number_of_paragraphs(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Text of all paragraphs of the body followed by headers, footers, notes and comments,
+    /// separated by new lines
+    pub fn text(&self) -> String {
+        self.blocks
+            .iter()
+            .map(DocxBlock::text)
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn images(&self) -> &[PackageImage] {
+        &self.images
+    }
+
+    /// Writes the redacted text of paragraphs separated by new lines and the redacted images
+    /// back to the package
+    pub fn repack(
+        &self,
+        redacted_text: &str,
+        redacted_images: Vec<PackageImage>,
+    ) -> AppResult<bytes::Bytes> {
+        let texts: Vec<&str> = if self.blocks.is_empty() {
+            Vec::new()
+        } else {
+            redacted_text.split('\n').collect()
+        };
+        if texts.len() != self.blocks.len() {
+            return Err(AppError::ConversionError {
+                message: format!(
+                    "Redacted text has {} lines instead of {} paragraphs of the document",
+                    texts.len(),
+                    self.blocks.len()
+                ),
+            });
+        }
+
+        let mut edits: Vec<Vec<(Range<usize>, String)>> = vec![Vec::new(); self.parts.len()];
+        for (block, text) in self.blocks.iter().zip(texts) {
+            if block.text() == text {
+                continue;
+            }
+            // Text can be written only to `<w:t>` elements, so tabs are removed and kept as text
+            let first_text = block
+                .pieces
+                .iter()
+                .position(|piece| !piece.is_tab)
+                .ok_or_else(|| AppError::ConversionError {
+                    message: "Redacted paragraph of the document has no text elements".to_string(),
+                })?;
+            for (idx, piece) in block.pieces.iter().enumerate() {
+                let replacement = if idx == first_text {
+                    let mut escaped = String::with_capacity(text.len());
+                    text.chars()
+                        .for_each(|ch| XmlPackage::escape_char(ch, &mut escaped));
+                    escaped
+                } else {
+                    String::new()
+                };
+                edits[block.part].push((piece.span.clone(), replacement));
+            }
+        }
+
+        let mut replaced: HashMap<String, Vec<u8>> = HashMap::new();
+        for (part, part_edits) in self.parts.iter().zip(edits.iter_mut()) {
+            if !part_edits.is_empty() {
+                replaced.insert(
+                    part.name.clone(),
+                    XmlPackage::apply_edits(&part.xml, part_edits).into_bytes(),
+                );
+            }
+        }
+        XmlPackage::replace_images(&self.images, redacted_images, &mut replaced)?;
+        replaced.insert(
+            Self::THUMBNAIL.to_string(),
+            XmlPackage::blank_thumbnail(image::ImageFormat::Jpeg)?,
+        );
+        self.package.repack(&replaced)
+    }
+
+    fn parse_part(&mut self, part: usize, xml: &str) -> AppResult<()> {
+        let mut pending: Option<(DocxElement, usize)> = None;
+        // Paragraphs of text boxes are nested in paragraphs of the body
+        let mut paragraphs: Vec<usize> = Vec::new();
+        // Tabs are also tab stops of paragraph properties, so only tabs of runs are text.
+        // Runs with text boxes have nested runs
+        let mut runs = 0;
+        let mut in_text = false;
+        for token in Tokenizer::from(xml) {
+            let token = token.map_err(|err| AppError::ConversionError {
+                message: format!("Invalid Word XML: {}", err),
+            })?;
+            match token {
+                Token::ElementStart {
+                    prefix,
+                    local,
+                    span,
+                } => {
+                    let element = match (prefix.as_str(), local.as_str()) {
+                        ("w", "p") => DocxElement::Paragraph,
+                        ("w", "r") => DocxElement::Run,
+                        ("w", "t") => DocxElement::Text,
+                        ("w", "tab") => DocxElement::Tab,
+                        ("w", "br") | ("w", "cr") => DocxElement::LineBreak,
+                        _ => DocxElement::Other,
+                    };
+                    pending = Some((element, span.start()));
+                }
+                Token::ElementEnd { end, span } => match end {
+                    ElementEnd::Open => match pending.take() {
+                        Some((DocxElement::Paragraph, _)) => {
+                            paragraphs.push(self.push_block(part));
+                        }
+                        Some((DocxElement::Run, _)) => {
+                            runs += 1;
+                        }
+                        Some((DocxElement::Text, _)) => {
+                            in_text = true;
+                        }
+                        _ => {}
+                    },
+                    ElementEnd::Empty => match pending.take() {
+                        Some((DocxElement::LineBreak, _)) => {
+                            if let Some(paragraph) = paragraphs.last_mut() {
+                                *paragraph = self.push_block(part);
+                            }
+                        }
+                        Some((DocxElement::Tab, start)) if runs > 0 => {
+                            if let Some(paragraph) = paragraphs.last() {
+                                self.blocks[*paragraph].pieces.push(DocxPiece {
+                                    span: start..span.end(),
+                                    text: "\t".to_string(),
+                                    is_tab: true,
+                                });
+                            }
+                        }
+                        _ => {}
+                    },
+                    ElementEnd::Close(prefix, local) => match (prefix.as_str(), local.as_str()) {
+                        ("w", "p") => {
+                            paragraphs.pop();
+                        }
+                        ("w", "r") => runs -= 1,
+                        ("w", "t") => in_text = false,
+                        _ => {}
+                    },
+                },
+                Token::Text { text } if in_text => {
+                    if let Some(paragraph) = paragraphs.last() {
+                        self.blocks[*paragraph].pieces.push(DocxPiece {
+                            span: text.range(),
+                            text: XmlPackage::unescape_text(text.as_str()),
+                            is_tab: false,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn push_block(&mut self, part: usize) -> usize {
+        self.blocks.push(DocxBlock {
+            part,
+            pieces: Vec::new(),
+        });
+        self.blocks.len() - 1
+    }
+
+    /// The body followed by headers and footers ordered by their numbers, notes and comments
+    fn part_order(name: &str) -> Option<(usize, usize)> {
+        Self::TEXT_PARTS
+            .iter()
+            .enumerate()
+            .find_map(|(kind, prefix)| {
+                let number = name.strip_prefix(prefix)?.strip_suffix(".xml")?;
+                if number.is_empty() {
+                    Some((kind, 0))
+                } else {
+                    number.parse::<usize>().ok().map(|number| (kind, number))
+                }
+            })
+    }
+}
+
+impl DocxBlock {
+    fn text(&self) -> String {
+        self.pieces
+            .iter()
+            .map(|piece| piece.text.as_str())
+            .collect()
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read, Write};
+
+    #[test]
+    fn docx_test() -> AppResult<()> {
+        let body = concat!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><w:document><w:body>"#,
+            r#"<w:p><w:pPr><w:tabs><w:tab w:val="left" w:pos="720"/></w:tabs></w:pPr><w:r><w:rPr><w:b/></w:rPr><w:t>Customer:</w:t></w:r><w:r><w:tab/><w:t xml:space="preserve">John Doe</w:t></w:r><w:r><w:br/><w:t>Q3 &amp; Q4</w:t></w:r></w:p>"#,
+            r#"<w:p/><w:p><w:r><w:t>Signed</w:t></w:r></w:p>"#,
+            r#"</w:body></w:document>"#
+        );
+        let footer = r#"<w:ftr><w:p><w:r><w:t>Call John</w:t></w:r></w:p></w:ftr>"#;
+        let mut screenshot = Cursor::new(Vec::new());
+        image::RgbImage::from_pixel(2, 2, image::Rgb([0, 0, 0]))
+            .write_to(&mut screenshot, image::ImageFormat::Png)?;
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in [
+            ("[Content_Types].xml", b"<Types/>".to_vec()),
+            ("word/footer1.xml", footer.as_bytes().to_vec()),
+            ("word/document.xml", body.as_bytes().to_vec()),
+            ("word/styles.xml", b"<w:styles/>".to_vec()),
+            ("word/media/image1.png", screenshot.into_inner()),
+            ("word/media/image2.emf", b"emf".to_vec()),
+        ] {
+            writer.start_file(name, zip::write::SimpleFileOptions::default())?;
+            writer.write_all(&content)?;
+        }
+        let document = DocxDocument::open(writer.finish()?.into_inner().into())?;
+        assert_eq!(document.number_of_paragraphs(), 4);
+        assert_eq!(
+            document.text(),
+            "Customer:\tJohn Doe\nQ3 & Q4\nSigned\nCall John"
+        );
+        assert_eq!(
+            document
+                .images()
+                .iter()
+                .map(|image| image.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["word/media/image1.png"]
+        );
+
+        let mut redacted_image = Cursor::new(Vec::new());
+        image::RgbImage::from_pixel(2, 2, image::Rgb([255, 255, 255]))
+            .write_to(&mut redacted_image, image::ImageFormat::Jpeg)?;
+        let redacted = document.repack(
+            "Customer:\t[PERSON]\nQ3 & Q4\nSigned\nCall [PERSON]",
+            vec![PackageImage {
+                name: "word/media/image1.png".to_string(),
+                mime_type: mime::IMAGE_JPEG,
+                data: redacted_image.into_inner().into(),
+            }],
+        )?;
+        let mut archive = zip::ZipArchive::new(Cursor::new(redacted.as_ref()))?;
+        let mut redacted_body = String::new();
+        archive
+            .by_name("word/document.xml")?
+            .read_to_string(&mut redacted_body)?;
+        assert!(redacted_body.contains(concat!(
+            r#"<w:tabs><w:tab w:val="left" w:pos="720"/></w:tabs></w:pPr><w:r><w:rPr><w:b/></w:rPr><w:t>Customer:"#,
+            "\t",
+            r#"[PERSON]</w:t></w:r><w:r><w:t xml:space="preserve"></w:t></w:r><w:r><w:br/><w:t>Q3 &amp; Q4</w:t></w:r>"#
+        )));
+        let mut redacted_png = Vec::new();
+        archive
+            .by_name("word/media/image1.png")?
+            .read_to_end(&mut redacted_png)?;
+        assert_eq!(image::guess_format(&redacted_png)?, image::ImageFormat::Png);
+        assert_eq!(
+            DocxDocument::open(redacted)?.text(),
+            "Customer:\t[PERSON]\nQ3 & Q4\nSigned\nCall [PERSON]"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,5 @@
+use crate::AppResult;
+
+pub trait HeifToImage {
+    fn convert_to_image(&self, heif_bytes: &[u8]) -> AppResult<image::DynamicImage>;
+}
@@ -0,0 +1,51 @@
+use crate::errors::AppError;
+use crate::file_converters::heif::HeifToImage;
+use crate::AppResult;
+use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+/// Decodes HEIF/HEIC images using the system libheif library
+pub struct HeifImageConverter {
+    lib_heif: LibHeif,
+}
+
+impl HeifImageConverter {
+    pub fn new() -> AppResult<Self> {
+        Ok(Self {
+            lib_heif: LibHeif::new_checked()?,
+        })
+    }
+}
+
+impl HeifToImage for HeifImageConverter {
+    fn convert_to_image(&self, heif_bytes: &[u8]) -> AppResult<image::DynamicImage> {
+        let context = HeifContext::read_from_bytes(heif_bytes)?;
+        let handle = context.primary_image_handle()?;
+        let heif_image = self
+            .lib_heif
+            .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)?;
+        let plane = heif_image
+            .planes()
+            .interleaved
+            .ok_or_else(|| AppError::ConversionError {
+                message: "HEIF image doesn't contain RGB data".to_string(),
+            })?;
+        let (width, height) = (plane.width as usize, plane.height as usize);
+        if plane.stride < width * 3 || plane.data.len() < plane.stride * height {
+            return Err(AppError::ConversionError {
+                message: "HEIF image doesn't contain RGB data".to_string(),
+            });
+        }
+        let data: Vec<u8> = plane
+            .data
+            .chunks(plane.stride)
+            .take(height)
+            .flat_map(|row| &row[..width * 3])
+            .copied()
+            .collect();
+        image::RgbImage::from_raw(plane.width, plane.height, data)
+            .map(image::DynamicImage::ImageRgb8)
+            .ok_or_else(|| AppError::ConversionError {
+                message: "Invalid HEIF image dimensions".to_string(),
+            })
+    }
+}
@@ -1,10 +1,23 @@
-use crate::file_converters::ocr::Ocr;
+use crate::errors::AppError;
+use crate::file_converters::heif::HeifToImage;
+use crate::file_converters::ocr::{Ocr, OcrEngineType, OcrOptions};
+use crate::file_converters::ocr_cache::CachedOcr;
 use crate::file_converters::pdf::PdfToImage;
+use crate::redacters::RedactionCacheOptions;
 use crate::reporter::AppReporter;
 use crate::AppResult;
 
+pub mod audio;
+pub mod docx;
+pub mod heif;
 pub mod ocr;
+pub mod ocr_cache;
+pub mod odf;
 pub mod pdf;
+pub mod pdf_a;
+pub mod pptx;
+pub mod tiff_pages;
+pub mod xml_package;
 
 #[cfg(feature = "pdf-render")]
 mod pdf_image_converter;
@@ -12,9 +25,21 @@ mod pdf_image_converter;
 #[cfg(feature = "ocr")]
 mod ocr_ocrs;
 
+mod ocr_gcp_vision;
+
+#[cfg(feature = "heif")]
+mod heif_image_converter;
+
 pub struct FileConverters<'a> {
     pub pdf_image_converter: Option<Box<dyn PdfToImage + 'a>>,
     pub ocr: Option<Box<dyn Ocr + 'a>>,
+    pub heif_image_converter: Option<Box<dyn HeifToImage + 'a>>,
+}
+
+impl Default for FileConverters<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<'a> FileConverters<'a> {
@@ -22,23 +47,72 @@ impl<'a> FileConverters<'a> {
         Self {
             pdf_image_converter: None,
             ocr: None,
+            heif_image_converter: None,
         }
     }
 
-    pub async fn init(mut self, app_reporter: &'a AppReporter<'a>) -> AppResult<Self> {
+    pub async fn init(
+        mut self,
+        app_reporter: &'a AppReporter<'a>,
+        ocr_options: &OcrOptions,
+    ) -> AppResult<Self> {
         #[cfg(feature = "pdf-render")]
         {
             if let Ok(pdf_image_converter) = pdf_image_converter::PdfImageConverter::new() {
                 self.pdf_image_converter = Some(Box::new(pdf_image_converter));
             }
         }
-        #[cfg(feature = "ocr")]
+        match ocr_options.engine {
+            OcrEngineType::Ocrs => {
+                #[cfg(feature = "ocr")]
+                {
+                    match ocr_ocrs::Ocrs::new(app_reporter, &ocr_options.languages) {
+                        Ok(ocr) => {
+                            self.ocr = Some(Box::new(ocr));
+                        }
+                        // OCR is optional unless languages are requested explicitly
+                        Err(err) if !ocr_options.languages.is_empty() => return Err(err),
+                        Err(_) => {}
+                    }
+                }
+            }
+            OcrEngineType::GcpVision => {
+                let project_id = ocr_options.gcp_project_id.clone().ok_or_else(|| {
+                    AppError::RedacterConfigError {
+                        message: "GCP project id is required for gcp-vision OCR engine".to_string(),
+                    }
+                })?;
+                self.ocr = Some(Box::new(
+                    ocr_gcp_vision::GcpVisionOcr::new(
+                        app_reporter,
+                        project_id,
+                        ocr_options.languages.clone(),
+                    )
+                    .await?,
+                ));
+            }
+        }
+        #[cfg(feature = "heif")]
         {
-            if let Ok(ocr) = ocr_ocrs::Ocrs::new(app_reporter) {
-                self.ocr = Some(Box::new(ocr));
+            if let Ok(heif_image_converter) = heif_image_converter::HeifImageConverter::new() {
+                self.heif_image_converter = Some(Box::new(heif_image_converter));
             }
         }
 
         Ok(self)
     }
+
+    /// Caches OCR results by image hashes during the run, and between runs in the cache directory if enabled
+    pub async fn with_ocr_cache(
+        mut self,
+        ocr_options: &OcrOptions,
+        cache_options: Option<RedactionCacheOptions>,
+    ) -> AppResult<Self> {
+        if let Some(ocr) = self.ocr.take() {
+            self.ocr = Some(Box::new(
+                CachedOcr::new(ocr, &format!("{:?}", ocr_options), cache_options).await?,
+            ));
+        }
+        Ok(self)
+    }
 }
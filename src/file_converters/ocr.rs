@@ -1,6 +1,34 @@
-use crate::common_types::TextImageCoords;
+use crate::common_types::{GcpProjectId, RedactLanguage, TextImageCoords};
 use crate::AppResult;
+use async_trait::async_trait;
+use clap::ValueEnum;
+use std::fmt::Display;
 
-pub trait Ocr {
-    fn image_to_text(&self, image: image::DynamicImage) -> AppResult<Vec<TextImageCoords>>;
+#[async_trait]
+pub trait Ocr: Sync {
+    async fn image_to_text(&self, image: image::DynamicImage) -> AppResult<Vec<TextImageCoords>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OcrEngineType {
+    #[default]
+    Ocrs,
+    GcpVision,
+}
+
+impl Display for OcrEngineType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OcrEngineType::Ocrs => write!(f, "ocrs"),
+            OcrEngineType::GcpVision => write!(f, "gcp-vision"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OcrOptions {
+    pub engine: OcrEngineType,
+    /// Languages of text in images, empty means the default language of the engine
+    pub languages: Vec<RedactLanguage>,
+    pub gcp_project_id: Option<GcpProjectId>,
 }
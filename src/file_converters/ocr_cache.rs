@@ -0,0 +1,262 @@
+use crate::common_types::TextImageCoords;
+use crate::file_converters::ocr::Ocr;
+use crate::metrics::{AppMetric, AppMetrics};
+use crate::redacters::RedactionCacheOptions;
+use crate::AppResult;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OcrCacheEntry {
+    coords: Vec<TextImageCoords>,
+    created_at: DateTime<Utc>,
+}
+
+/// Results of OCR engines keyed by hashes of image pixels, since OCR is the slowest stage and
+/// the same scanned letterheads and pages repeat across documents. Results are kept in memory
+/// during a run, and in the `ocr` directory of the redaction cache between runs if OCR caching is enabled.
+pub struct CachedOcr<'a> {
+    ocr: Box<dyn Ocr + 'a>,
+    engine_fingerprint: String,
+    cache_options: Option<RedactionCacheOptions>,
+    memory: Mutex<HashMap<String, Vec<TextImageCoords>>>,
+}
+
+impl<'a> CachedOcr<'a> {
+    pub const CACHE_DIR: &'static str = "ocr";
+
+    /// The fingerprint should describe all options affecting OCR results, such as engines and languages
+    pub async fn new(
+        ocr: Box<dyn Ocr + 'a>,
+        engine_fingerprint: &str,
+        cache_options: Option<RedactionCacheOptions>,
+    ) -> AppResult<Self> {
+        let cache_options =
+            cache_options
+                .filter(|options| options.ocr)
+                .map(|options| RedactionCacheOptions {
+                    cache_dir: options.cache_dir.join(Self::CACHE_DIR),
+                    ..options
+                });
+        let cached_ocr = Self {
+            ocr,
+            engine_fingerprint: hex::encode(Sha256::digest(engine_fingerprint.as_bytes())),
+            cache_options,
+            memory: Mutex::new(HashMap::new()),
+        };
+        if let Some(ref options) = cached_ocr.cache_options {
+            tokio::fs::create_dir_all(&options.cache_dir).await?;
+            cached_ocr.cleanup().await?;
+        }
+        Ok(cached_ocr)
+    }
+
+    fn key(&self, image: &image::DynamicImage) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.engine_fingerprint.as_bytes());
+        hasher.update(image.width().to_be_bytes());
+        hasher.update(image.height().to_be_bytes());
+        hasher.update(format!("{:?}", image.color()).as_bytes());
+        hasher.update(image.as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    async fn get(&self, key: &str) -> AppResult<Option<Vec<TextImageCoords>>> {
+        if let Some(coords) = self.memory.lock().unwrap().get(key) {
+            return Ok(Some(coords.clone()));
+        }
+        let options = match self.cache_options {
+            Some(ref options) => options,
+            None => return Ok(None),
+        };
+        let entry_path = options.cache_dir.join(format!("{}.json", key));
+        if !tokio::fs::try_exists(&entry_path).await? {
+            return Ok(None);
+        }
+        let entry: OcrCacheEntry =
+            match serde_json::from_slice(&tokio::fs::read(&entry_path).await?) {
+                Ok(entry) => entry,
+                Err(_) => return Ok(None),
+            };
+        if self.is_expired(&entry) {
+            tokio::fs::remove_file(entry_path).await?;
+            return Ok(None);
+        }
+        self.memory
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), entry.coords.clone());
+        Ok(Some(entry.coords))
+    }
+
+    async fn put(&self, key: &str, coords: &[TextImageCoords]) -> AppResult<()> {
+        self.memory
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), coords.to_vec());
+        if let Some(ref options) = self.cache_options {
+            let entry = OcrCacheEntry {
+                coords: coords.to_vec(),
+                created_at: Utc::now(),
+            };
+            tokio::fs::write(
+                options.cache_dir.join(format!("{}.json", key)),
+                serde_json::to_vec(&entry)?,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    fn is_expired(&self, entry: &OcrCacheEntry) -> bool {
+        self.cache_options.as_ref().is_some_and(|options| {
+            Utc::now()
+                .signed_duration_since(entry.created_at)
+                .to_std()
+                .is_ok_and(|age| age > options.ttl)
+        })
+    }
+
+    /// Removes expired and unreadable entries. OCR entries are small compared to cached redacted files,
+    /// so they aren't limited by the maximum size of the cache.
+    async fn cleanup(&self) -> AppResult<()> {
+        let options = match self.cache_options {
+            Some(ref options) => options,
+            None => return Ok(()),
+        };
+        let mut dir = tokio::fs::read_dir(&options.cache_dir).await?;
+        while let Some(dir_entry) = dir.next_entry().await? {
+            let path = dir_entry.path();
+            if !path.extension().is_some_and(|ext| ext == "json") {
+                continue;
+            }
+            let entry: Option<OcrCacheEntry> = tokio::fs::read(&path)
+                .await
+                .ok()
+                .and_then(|content| serde_json::from_slice(&content).ok());
+            if entry.map_or(true, |entry| self.is_expired(&entry)) {
+                tokio::fs::remove_file(path).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Ocr for CachedOcr<'_> {
+    async fn image_to_text(&self, image: image::DynamicImage) -> AppResult<Vec<TextImageCoords>> {
+        let key = self.key(&image);
+        match self.get(&key).await {
+            Ok(Some(coords)) => {
+                AppMetrics::global().inc(AppMetric::OCR_CACHE_HITS, &[]);
+                return Ok(coords);
+            }
+            Ok(None) => {}
+            Err(error) => {
+                tracing::warn!(error = %error, "Failed to read cached OCR result");
+            }
+        }
+        let coords = self.ocr.image_to_text(image).await?;
+        if let Err(error) = self.put(&key, &coords).await {
+            tracing::warn!(error = %error, "Failed to cache OCR result");
+        }
+        Ok(coords)
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, Rgb, RgbImage};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[allow(dead_code)]
+    struct CountingOcr<'c> {
+        calls: &'c AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Ocr for CountingOcr<'_> {
+        async fn image_to_text(&self, image: DynamicImage) -> AppResult<Vec<TextImageCoords>> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            Ok(vec![TextImageCoords {
+                x1: 0.0,
+                y1: 0.0,
+                x2: image.width() as f32,
+                y2: image.height() as f32,
+                text: Some("John Doe".to_string()),
+            }])
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_ocr_test() -> AppResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache_options = RedactionCacheOptions {
+            cache_dir: temp_dir.path().to_path_buf(),
+            ttl: Duration::from_secs(60),
+            max_size: None,
+            ocr: true,
+        };
+        let letterhead = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 4, Rgb([255, 0, 0])));
+        let page = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 4, Rgb([0, 0, 255])));
+        let calls = AtomicUsize::new(0);
+
+        let cached_ocr = CachedOcr::new(
+            Box::new(CountingOcr { calls: &calls }),
+            "ocrs",
+            Some(cache_options.clone()),
+        )
+        .await?;
+        cached_ocr.image_to_text(letterhead.clone()).await?;
+        let coords = cached_ocr.image_to_text(letterhead.clone()).await?;
+        assert_eq!(coords[0].text.as_deref(), Some("John Doe"));
+        cached_ocr.image_to_text(page).await?;
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+        // Results are reused between runs, but not for other engines
+        let next_run_ocr = CachedOcr::new(
+            Box::new(CountingOcr { calls: &calls }),
+            "ocrs",
+            Some(cache_options.clone()),
+        )
+        .await?;
+        next_run_ocr.image_to_text(letterhead.clone()).await?;
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        let other_engine_ocr = CachedOcr::new(
+            Box::new(CountingOcr { calls: &calls }),
+            "gcp-vision",
+            Some(cache_options.clone()),
+        )
+        .await?;
+        other_engine_ocr.image_to_text(letterhead.clone()).await?;
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        // Original text of images isn't stored between runs unless OCR caching is enabled
+        let memory_only_ocr = CachedOcr::new(
+            Box::new(CountingOcr { calls: &calls }),
+            "tesseract",
+            Some(RedactionCacheOptions {
+                ocr: false,
+                ..cache_options.clone()
+            }),
+        )
+        .await?;
+        memory_only_ocr.image_to_text(letterhead.clone()).await?;
+        memory_only_ocr.image_to_text(letterhead).await?;
+        assert_eq!(calls.load(Ordering::Relaxed), 4);
+        let mut cached_entries =
+            tokio::fs::read_dir(temp_dir.path().join(CachedOcr::CACHE_DIR)).await?;
+        let mut cached_entries_count = 0;
+        while cached_entries.next_entry().await?.is_some() {
+            cached_entries_count += 1;
+        }
+        assert_eq!(cached_entries_count, 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+use crate::common_types::{GcpProjectId, RedactLanguage, TextImageCoords};
+use crate::errors::AppError;
+use crate::file_converters::ocr::Ocr;
+use crate::metrics::{AppMetric, AppMetrics};
+use crate::reporter::AppReporter;
+use crate::AppResult;
+use async_trait::async_trait;
+use gcloud_sdk::google::cloud::vision::v1::image_annotator_client::ImageAnnotatorClient;
+use gcloud_sdk::google::cloud::vision::v1::{
+    feature, AnnotateImageRequest, BatchAnnotateImagesRequest, BoundingPoly, EntityAnnotation,
+    Feature, Image, ImageContext,
+};
+use gcloud_sdk::tonic::metadata::MetadataValue;
+use gcloud_sdk::{tonic, GoogleApi, GoogleAuthMiddleware};
+use image::ImageFormat;
+use rvstruct::ValueStruct;
+use std::time::Instant;
+
+/// OCR using GCP Vision API text detection, that supports many languages and scripts
+/// without installing any models.
+pub struct GcpVisionOcr<'a> {
+    client: GoogleApi<ImageAnnotatorClient<GoogleAuthMiddleware>>,
+    project_id: GcpProjectId,
+    languages: Vec<RedactLanguage>,
+    #[allow(dead_code)]
+    app_reporter: &'a AppReporter<'a>,
+}
+
+impl<'a> GcpVisionOcr<'a> {
+    pub async fn new(
+        app_reporter: &'a AppReporter<'a>,
+        project_id: GcpProjectId,
+        languages: Vec<RedactLanguage>,
+    ) -> AppResult<Self> {
+        let client = GoogleApi::from_function(
+            ImageAnnotatorClient::new,
+            "https://vision.googleapis.com",
+            None,
+        )
+        .await?;
+        Ok(Self {
+            client,
+            project_id,
+            languages,
+            app_reporter,
+        })
+    }
+
+    /// The first annotation is the whole text, the rest are separate words
+    fn word_annotations_to_coords(text_annotations: &[EntityAnnotation]) -> Vec<TextImageCoords> {
+        text_annotations
+            .iter()
+            .skip(1)
+            .filter_map(|annotation| {
+                annotation
+                    .bounding_poly
+                    .as_ref()
+                    .and_then(Self::bounding_poly_to_coords)
+                    .map(|coords| TextImageCoords {
+                        text: Some(annotation.description.clone()),
+                        ..coords
+                    })
+            })
+            .collect()
+    }
+
+    fn bounding_poly_to_coords(bounding_poly: &BoundingPoly) -> Option<TextImageCoords> {
+        let xs = bounding_poly.vertices.iter().map(|vertex| vertex.x.max(0));
+        let ys = bounding_poly.vertices.iter().map(|vertex| vertex.y.max(0));
+        Some(TextImageCoords {
+            x1: xs.clone().min()? as f32,
+            y1: ys.clone().min()? as f32,
+            x2: xs.max()? as f32,
+            y2: ys.max()? as f32,
+            text: None,
+        })
+    }
+}
+
+#[async_trait]
+impl Ocr for GcpVisionOcr<'_> {
+    async fn image_to_text(&self, image: image::DynamicImage) -> AppResult<Vec<TextImageCoords>> {
+        let mut png_image_bytes = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut png_image_bytes, ImageFormat::Png)?;
+        let mut request = tonic::Request::new(BatchAnnotateImagesRequest {
+            requests: vec![AnnotateImageRequest {
+                image: Some(Image {
+                    content: png_image_bytes.into_inner(),
+                    source: None,
+                }),
+                features: vec![Feature {
+                    r#type: feature::Type::DocumentTextDetection.into(),
+                    ..Feature::default()
+                }],
+                image_context: Some(ImageContext {
+                    language_hints: self
+                        .languages
+                        .iter()
+                        .map(|language| language.value().clone())
+                        .collect(),
+                    ..ImageContext::default()
+                }),
+            }],
+            parent: format!("projects/{}/locations/global", self.project_id.value()),
+            ..BatchAnnotateImagesRequest::default()
+        });
+        request.metadata_mut().insert(
+            "x-goog-user-project",
+            MetadataValue::<tonic::metadata::Ascii>::try_from(self.project_id.value())?,
+        );
+        let started = Instant::now();
+        let response = self.client.get().batch_annotate_images(request).await;
+        let labels = [("redacter", "gcp-vision")];
+        let metrics = AppMetrics::global();
+        metrics.inc(AppMetric::PROVIDER_CALLS, &labels);
+        metrics.add_duration(AppMetric::PROVIDER_DURATION, &labels, started.elapsed());
+        if response.is_err() {
+            metrics.inc(AppMetric::PROVIDER_FAILURES, &labels);
+        }
+        let mut text_image_coords = vec![];
+        for image_response in response?.into_inner().responses {
+            if let Some(error) = image_response.error {
+                return Err(AppError::ProviderError {
+                    provider: "gcp-vision".to_string(),
+                    retryable: false,
+                    status: None,
+                    message: format!("GCP Vision text detection error: {}", error.message),
+                });
+            }
+            text_image_coords.extend(Self::word_annotations_to_coords(
+                &image_response.text_annotations,
+            ));
+        }
+        Ok(text_image_coords)
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use console::Term;
+    use gcloud_sdk::google::cloud::vision::v1::Vertex;
+
+    #[test]
+    fn word_annotations_to_coords_test() {
+        let annotation = |description: &str, x: i32| EntityAnnotation {
+            description: description.to_string(),
+            bounding_poly: Some(BoundingPoly {
+                vertices: vec![Vertex { x, y: 10 }, Vertex { x: x + 40, y: 30 }],
+                normalized_vertices: vec![],
+            }),
+            ..EntityAnnotation::default()
+        };
+        let coords = GcpVisionOcr::word_annotations_to_coords(&[
+            annotation("Hallo Welt", 0),
+            annotation("Hallo", 0),
+            annotation("Welt", 50),
+        ]);
+        assert_eq!(coords.len(), 2);
+        assert_eq!(coords[1].text.as_deref(), Some("Welt"));
+        assert_eq!(
+            (coords[1].x1, coords[1].y1, coords[1].x2, coords[1].y2),
+            (50.0, 10.0, 90.0, 30.0)
+        );
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ci-gcp"), ignore)]
+    async fn test_recognise_png_file() -> AppResult<()> {
+        let term = Term::stdout();
+        let app_reporter = AppReporter::from(&term);
+        let test_gcp_project_id =
+            std::env::var("TEST_GCP_PROJECT").expect("TEST_GCP_PROJECT required");
+        let ocr = GcpVisionOcr::new(
+            &app_reporter,
+            GcpProjectId::new(test_gcp_project_id),
+            vec![RedactLanguage::new("en".to_string())],
+        )
+        .await?;
+        let image = image::open("test-fixtures/media/form-example.png")?;
+        let text_image_coords = ocr.image_to_text(image).await?;
+        assert!(text_image_coords.len() > 10);
+        Ok(())
+    }
+}
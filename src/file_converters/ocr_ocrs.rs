@@ -1,9 +1,11 @@
-use crate::common_types::TextImageCoords;
+use crate::common_types::{RedactLanguage, TextImageCoords};
 use crate::errors::AppError;
 use crate::file_converters::ocr::Ocr;
 use crate::reporter::AppReporter;
 use crate::AppResult;
+use async_trait::async_trait;
 use ocrs::{ImageSource, OcrEngine, OcrEngineParams, OcrInput, TextItem};
+use rvstruct::ValueStruct;
 use std::path::PathBuf;
 
 pub struct Ocrs<'a> {
@@ -13,19 +15,23 @@ pub struct Ocrs<'a> {
 }
 
 impl<'a> Ocrs<'a> {
-    pub fn new(app_reporter: &'a AppReporter<'a>) -> AppResult<Self> {
+    const DEFAULT_LANGUAGE: &'static str = "en";
+
+    pub fn new(app_reporter: &'a AppReporter<'a>, languages: &[RedactLanguage]) -> AppResult<Self> {
         let find_models_dir = Self::find_models_dir()?;
         app_reporter.report(format!(
             "Loading OCR models from {}",
             find_models_dir.to_string_lossy()
         ))?;
         let detection_model_path = find_models_dir.join("text-detection.rten");
-        let rec_model_path = find_models_dir.join("text-recognition.rten");
+        let (rec_model_path, alphabet) =
+            Self::find_recognition_model(app_reporter, &find_models_dir, languages)?;
         let detection_model = rten::Model::load_file(detection_model_path)?;
         let recognition_model = rten::Model::load_file(rec_model_path)?;
         let ocr_engine = OcrEngine::new(OcrEngineParams {
             detection_model: Some(detection_model),
             recognition_model: Some(recognition_model),
+            alphabet,
             ..Default::default()
         })?;
         Ok(Self {
@@ -34,6 +40,46 @@ impl<'a> Ocrs<'a> {
         })
     }
 
+    /// The default recognition model supports only English text. Other languages require
+    /// a language pack in the models directory: `text-recognition-<language>.rten` model
+    /// and optional `alphabet-<language>.txt` if the model alphabet differs from the default one.
+    fn find_recognition_model(
+        app_reporter: &'a AppReporter<'a>,
+        models_dir: &std::path::Path,
+        languages: &[RedactLanguage],
+    ) -> AppResult<(PathBuf, Option<String>)> {
+        let language_packs: Vec<&RedactLanguage> = languages
+            .iter()
+            .filter(|language| language.value() != Self::DEFAULT_LANGUAGE)
+            .collect();
+        match language_packs.as_slice() {
+            [] => Ok((models_dir.join("text-recognition.rten"), None)),
+            [language] => {
+                app_reporter.report(format!(
+                    "Loading OCR language pack for '{}'",
+                    language.value()
+                ))?;
+                let alphabet_path = models_dir.join(format!("alphabet-{}.txt", language.value()));
+                let alphabet = if alphabet_path.exists() {
+                    Some(
+                        std::fs::read_to_string(alphabet_path)?
+                            .trim_end_matches(['\r', '\n'])
+                            .to_string(),
+                    )
+                } else {
+                    None
+                };
+                Ok((
+                    models_dir.join(format!("text-recognition-{}.rten", language.value())),
+                    alphabet,
+                ))
+            }
+            _ => Err(AppError::RedacterConfigError {
+                message: "OCR engine ocrs supports only a single language pack. Use gcp-vision OCR engine for multiple languages".to_string(),
+            }),
+        }
+    }
+
     fn find_models_dir() -> AppResult<std::path::PathBuf> {
         let executable = std::env::current_exe()?;
         let current_dir = executable.parent().map(|p| p.to_path_buf());
@@ -51,14 +97,15 @@ impl<'a> Ocrs<'a> {
         .flatten()
         .find(|p| p.exists())
         .cloned()
-        .ok_or_else(|| AppError::SystemError {
+        .ok_or_else(|| AppError::ConfigError {
             message: "Could not find models directory".to_string(),
         })
     }
 }
 
+#[async_trait]
 impl Ocr for Ocrs<'_> {
-    fn image_to_text(&self, image: image::DynamicImage) -> AppResult<Vec<TextImageCoords>> {
+    async fn image_to_text(&self, image: image::DynamicImage) -> AppResult<Vec<TextImageCoords>> {
         let rgb_image = image.to_rgb8();
         let image_source = ImageSource::from_bytes(rgb_image.as_raw(), rgb_image.dimensions())?;
         let input: OcrInput = self.ocr_engine.prepare_input(image_source)?;
@@ -106,14 +153,14 @@ mod tests {
     use super::*;
     use console::Term;
 
-    #[test]
+    #[tokio::test]
     #[cfg_attr(not(feature = "ci-ocr"), ignore)]
-    fn test_recognise_png_file() -> AppResult<()> {
+    async fn test_recognise_png_file() -> AppResult<()> {
         let term = Term::stdout();
         let app_reporter = AppReporter::from(&term);
-        let ocrs = Ocrs::new(&app_reporter)?;
+        let ocrs = Ocrs::new(&app_reporter, &[])?;
         let image = image::open("test-fixtures/media/form-example.png")?;
-        let text_image_coords = ocrs.image_to_text(image)?;
+        let text_image_coords = ocrs.image_to_text(image).await?;
         assert!(text_image_coords.len() > 10);
         Ok(())
     }
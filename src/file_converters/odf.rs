@@ -0,0 +1,602 @@
+use crate::errors::AppError;
+use crate::file_converters::xml_package::{PackageImage, XmlPackage};
+use crate::AppResult;
+use gcloud_sdk::prost::bytes;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use xmlparser::{ElementEnd, Token, Tokenizer};
+
+/// OpenDocument files such as exports of Google Docs and Sheets, and documents of LibreOffice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OdfDocumentType {
+    Text,
+    Spreadsheet,
+}
+
+impl OdfDocumentType {
+    pub fn from_mime(mime: &mime::Mime) -> Option<Self> {
+        match mime.essence_str() {
+            "application/vnd.oasis.opendocument.text" => Some(OdfDocumentType::Text),
+            "application/vnd.oasis.opendocument.spreadsheet" => Some(OdfDocumentType::Spreadsheet),
+            _ => None,
+        }
+    }
+}
+
+/// Text of paragraphs and spreadsheet cells of an OpenDocument package.
+/// Redacted text is written back to the XML of the package in place, so styles and other content
+/// of documents are kept as is, and only paragraphs with changed text lose their inline formatting.
+/// Embedded images are kept as is, unless redacted images are written back with the text.
+pub struct OdfDocument {
+    package: XmlPackage,
+    parts: Vec<OdfPart>,
+    images: Vec<PackageImage>,
+    blocks: Vec<OdfBlock>,
+    cells: Vec<OdfCell>,
+    rows: Vec<Vec<usize>>,
+}
+
+struct OdfPart {
+    name: String,
+    xml: String,
+}
+
+/// Text of a paragraph (or of a line of a paragraph with line breaks)
+struct OdfBlock {
+    part: usize,
+    cell: Option<usize>,
+    pieces: Vec<OdfPiece>,
+}
+
+/// Text or a space element such as `<text:s/>` and `<text:tab/>` in the XML of a part
+struct OdfPiece {
+    span: Range<usize>,
+    text: String,
+}
+
+struct OdfCell {
+    part: usize,
+    blocks: Vec<usize>,
+    /// Attributes with cell values such as numbers and formulas, which would reveal redacted values
+    value_attributes: Vec<(String, Range<usize>)>,
+}
+
+enum OdfElement {
+    Paragraph,
+    Cell(usize),
+    Row,
+    Space(String),
+    LineBreak,
+    Other,
+}
+
+impl OdfDocument {
+    /// Headers, footers and other text of page styles are in `styles.xml`
+    const TEXT_PARTS: [&'static str; 2] = ["content.xml", "styles.xml"];
+    const THUMBNAIL: &'static str = "Thumbnails/thumbnail.png";
+    const PICTURES: &'static str = "Pictures/";
+
+    pub fn open(package: bytes::Bytes) -> AppResult<Self> {
+        let package = XmlPackage::new(package);
+        let mut parts = package.read_xml_entries(|name| Self::TEXT_PARTS.contains(&name))?;
+        if !parts.iter().any(|(name, _)| name == "content.xml") {
+            return Err(AppError::ConversionError {
+                message: "OpenDocument file has no content.xml".to_string(),
+            });
+        }
+        parts.sort_by_key(|(name, _)| Self::TEXT_PARTS.iter().position(|part| part == name));
+        let images = package.read_images(Self::PICTURES)?;
+        let mut document = Self {
+            package,
+            parts: Vec::new(),
+            images,
+            blocks: Vec::new(),
+            cells: Vec::new(),
+            rows: Vec::new(),
+        };
+        for (name, xml) in parts {
+            document.parse_part(document.parts.len(), &xml)?;
+            document.parts.push(OdfPart { name, xml });
+        }
+        Ok(document)
+    }
+
+    pub fn number_of_paragraphs(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn images(&self) -> &[PackageImage] {
+        &self.images
+    }
+
+    /// Text of all paragraphs separated by new lines
+    pub fn text(&self) -> String {
+        self.blocks
+            .iter()
+            .map(OdfBlock::text)
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Cells of all sheets as rows with column names such as `A` and `B`.
+    /// Paragraphs outside cells such as headers of pages are rows with a single cell
+    pub fn table(&self) -> (Vec<String>, Vec<Vec<String>>) {
+        let layout = self.table_layout();
+        let columns = layout.iter().map(Vec::len).max().unwrap_or(0);
+        let rows = layout
+            .iter()
+            .map(|row| {
+                let mut cells: Vec<String> = row
+                    .iter()
+                    .map(|blocks| {
+                        blocks
+                            .iter()
+                            .map(|idx| self.blocks[*idx].text())
+                            .collect::<Vec<String>>()
+                            .join("\n")
+                    })
+                    .collect();
+                cells.resize(columns, String::new());
+                cells
+            })
+            .collect();
+        (Self::column_names(columns), rows)
+    }
+
+    /// Writes the redacted text of paragraphs separated by new lines and the redacted images
+    /// back to the package
+    pub fn repack_text(
+        &self,
+        redacted_text: &str,
+        redacted_images: Vec<PackageImage>,
+    ) -> AppResult<bytes::Bytes> {
+        let texts: Vec<String> = if self.blocks.is_empty() {
+            Vec::new()
+        } else {
+            redacted_text.split('\n').map(str::to_string).collect()
+        };
+        if texts.len() != self.blocks.len() {
+            return Err(AppError::ConversionError {
+                message: format!(
+                    "Redacted text has {} lines instead of {} paragraphs of the document",
+                    texts.len(),
+                    self.blocks.len()
+                ),
+            });
+        }
+        self.repack(texts, redacted_images)
+    }
+
+    /// Writes the redacted rows of the table and the redacted images back to the package
+    pub fn repack_table(
+        &self,
+        redacted_rows: &[Vec<String>],
+        redacted_images: Vec<PackageImage>,
+    ) -> AppResult<bytes::Bytes> {
+        let layout = self.table_layout();
+        if redacted_rows.len() != layout.len() {
+            return Err(AppError::ConversionError {
+                message: format!(
+                    "Redacted table has {} rows instead of {} rows of the document",
+                    redacted_rows.len(),
+                    layout.len()
+                ),
+            });
+        }
+        let mut texts: Vec<String> = self.blocks.iter().map(OdfBlock::text).collect();
+        for (row, redacted_row) in layout.iter().zip(redacted_rows) {
+            for (blocks, redacted_cell) in row.iter().zip(redacted_row) {
+                let lines: Vec<&str> = redacted_cell.split('\n').collect();
+                if lines.len() == blocks.len() {
+                    for (idx, line) in blocks.iter().zip(lines) {
+                        texts[*idx] = line.to_string();
+                    }
+                } else if let Some((first, rest)) = blocks.split_first() {
+                    // Lines of cells changed by redacters are written as a single paragraph
+                    texts[*first] = redacted_cell.replace('\n', " ");
+                    for idx in rest {
+                        texts[*idx] = String::new();
+                    }
+                }
+            }
+        }
+        self.repack(texts, redacted_images)
+    }
+
+    fn repack(
+        &self,
+        texts: Vec<String>,
+        redacted_images: Vec<PackageImage>,
+    ) -> AppResult<bytes::Bytes> {
+        let mut edits: Vec<Vec<(Range<usize>, String)>> = vec![Vec::new(); self.parts.len()];
+        let mut changed_cells = HashSet::new();
+        for (block, text) in self.blocks.iter().zip(texts) {
+            if block.text() == text {
+                continue;
+            }
+            if let Some(cell) = block.cell {
+                changed_cells.insert(cell);
+            }
+            for (idx, piece) in block.pieces.iter().enumerate() {
+                let replacement = if idx == 0 {
+                    Self::escape_text(&text)
+                } else {
+                    String::new()
+                };
+                edits[block.part].push((piece.span.clone(), replacement));
+            }
+        }
+        for cell in changed_cells.into_iter().map(|idx| &self.cells[idx]) {
+            for (name, span) in cell.value_attributes.iter() {
+                let replacement = if name.ends_with(":value-type") {
+                    format!("{}=\"string\"", name)
+                } else {
+                    String::new()
+                };
+                edits[cell.part].push((span.clone(), replacement));
+            }
+        }
+
+        let mut replaced: HashMap<String, Vec<u8>> = self
+            .parts
+            .iter()
+            .zip(edits.iter_mut())
+            .map(|(part, edits)| {
+                (
+                    part.name.clone(),
+                    XmlPackage::apply_edits(&part.xml, edits).into_bytes(),
+                )
+            })
+            .collect();
+        XmlPackage::replace_images(&self.images, redacted_images, &mut replaced)?;
+        replaced.insert(
+            Self::THUMBNAIL.to_string(),
+            XmlPackage::blank_thumbnail(image::ImageFormat::Png)?,
+        );
+        self.package.repack(&replaced)
+    }
+
+    fn parse_part(&mut self, part: usize, xml: &str) -> AppResult<()> {
+        let mut elements: Vec<OdfElement> = Vec::new();
+        let mut pending: Option<(OdfElement, usize)> = None;
+        // Current blocks of open paragraphs, which can be nested such as in notes and frames
+        let mut paragraphs: Vec<usize> = Vec::new();
+        let mut open_cells: Vec<usize> = Vec::new();
+        for token in Tokenizer::from(xml) {
+            let token = token.map_err(|err| AppError::ConversionError {
+                message: format!("Invalid OpenDocument XML: {}", err),
+            })?;
+            match token {
+                Token::ElementStart {
+                    prefix,
+                    local,
+                    span,
+                } => {
+                    let element = match (prefix.as_str(), local.as_str()) {
+                        ("text", "p") | ("text", "h") => OdfElement::Paragraph,
+                        ("table", "table-cell") | ("table", "covered-table-cell") => {
+                            self.cells.push(OdfCell {
+                                part,
+                                blocks: Vec::new(),
+                                value_attributes: Vec::new(),
+                            });
+                            if let Some(row) = self.rows.last_mut() {
+                                row.push(self.cells.len() - 1);
+                            }
+                            OdfElement::Cell(self.cells.len() - 1)
+                        }
+                        ("table", "table-row") => {
+                            self.rows.push(Vec::new());
+                            OdfElement::Row
+                        }
+                        ("text", "s") => OdfElement::Space(" ".to_string()),
+                        ("text", "tab") => OdfElement::Space("\t".to_string()),
+                        ("text", "line-break") => OdfElement::LineBreak,
+                        _ => OdfElement::Other,
+                    };
+                    pending = Some((element, span.start()));
+                }
+                Token::Attribute {
+                    prefix,
+                    local,
+                    value,
+                    span,
+                } => match pending {
+                    Some((OdfElement::Cell(idx), _))
+                        if matches!(
+                            (prefix.as_str(), local.as_str()),
+                            ("office", _) | ("calcext", "value-type") | ("table", "formula")
+                        ) && local.as_str() != "annotation" =>
+                    {
+                        self.cells[idx]
+                            .value_attributes
+                            .push((format!("{}:{}", prefix, local), span.range()));
+                    }
+                    Some((OdfElement::Space(ref mut text), _))
+                        if prefix.as_str() == "text" && local.as_str() == "c" =>
+                    {
+                        *text = text.repeat(value.as_str().parse::<usize>().unwrap_or(1));
+                    }
+                    _ => {}
+                },
+                Token::ElementEnd { end, span } => match end {
+                    ElementEnd::Open => {
+                        let element = match pending.take() {
+                            Some((OdfElement::Paragraph, _)) => {
+                                paragraphs.push(self.push_block(part, open_cells.last().copied()));
+                                OdfElement::Paragraph
+                            }
+                            Some((OdfElement::Cell(idx), _)) => {
+                                open_cells.push(idx);
+                                OdfElement::Cell(idx)
+                            }
+                            Some((OdfElement::Row, _)) => OdfElement::Row,
+                            _ => OdfElement::Other,
+                        };
+                        elements.push(element);
+                    }
+                    ElementEnd::Empty => match pending.take() {
+                        Some((OdfElement::Space(text), start)) => {
+                            if let Some(block) = paragraphs.last() {
+                                self.blocks[*block].pieces.push(OdfPiece {
+                                    span: start..span.end(),
+                                    text,
+                                });
+                            }
+                        }
+                        Some((OdfElement::LineBreak, _)) => {
+                            if let Some(block) = paragraphs.last_mut() {
+                                *block = self.push_block(part, self.blocks[*block].cell);
+                            }
+                        }
+                        _ => {}
+                    },
+                    ElementEnd::Close(_, _) => match elements.pop() {
+                        Some(OdfElement::Paragraph) => {
+                            paragraphs.pop();
+                        }
+                        Some(OdfElement::Cell(_)) => {
+                            open_cells.pop();
+                        }
+                        _ => {}
+                    },
+                },
+                Token::Text { text } => {
+                    if let Some(block) = paragraphs.last() {
+                        self.blocks[*block].pieces.push(OdfPiece {
+                            span: text.range(),
+                            text: XmlPackage::unescape_text(text.as_str()),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn push_block(&mut self, part: usize, cell: Option<usize>) -> usize {
+        let idx = self.blocks.len();
+        self.blocks.push(OdfBlock {
+            part,
+            cell,
+            pieces: Vec::new(),
+        });
+        if let Some(cell) = cell {
+            self.cells[cell].blocks.push(idx);
+        }
+        idx
+    }
+
+    /// Blocks of cells of every row, followed by paragraphs outside cells as rows with a single cell
+    fn table_layout(&self) -> Vec<Vec<Vec<usize>>> {
+        let mut layout: Vec<Vec<Vec<usize>>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|idx| self.cells[*idx].blocks.clone())
+                    .collect()
+            })
+            .collect();
+        layout.extend(
+            self.blocks
+                .iter()
+                .enumerate()
+                .filter(|(_, block)| block.cell.is_none())
+                .map(|(idx, _)| vec![vec![idx]]),
+        );
+        layout
+    }
+
+    fn column_names(columns: usize) -> Vec<String> {
+        (0..columns)
+            .map(|mut column| {
+                let mut name = String::new();
+                loop {
+                    name.insert(0, (b'A' + (column % 26) as u8) as char);
+                    if column < 26 {
+                        break name;
+                    }
+                    column = column / 26 - 1;
+                }
+            })
+            .collect()
+    }
+
+    /// Escapes text for paragraphs, where tabs, line breaks and repeated spaces are elements
+    fn escape_text(text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut previous = None;
+        for ch in text.chars() {
+            match ch {
+                '\t' => result.push_str("<text:tab/>"),
+                '\n' => result.push_str("<text:line-break/>"),
+                ' ' if previous.is_none() || previous == Some(' ') => result.push_str("<text:s/>"),
+                _ => XmlPackage::escape_char(ch, &mut result),
+            }
+            previous = Some(ch);
+        }
+        result
+    }
+}
+
+impl OdfBlock {
+    fn text(&self) -> String {
+        self.pieces
+            .iter()
+            .map(|piece| piece.text.as_str())
+            .collect()
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read, Write};
+
+    #[allow(dead_code)]
+    fn odf_package(mime_type: &str, content: &str) -> AppResult<bytes::Bytes> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "mimetype",
+            zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Stored),
+        )?;
+        writer.write_all(mime_type.as_bytes())?;
+        writer.start_file("content.xml", zip::write::SimpleFileOptions::default())?;
+        writer.write_all(content.as_bytes())?;
+        writer.start_file(
+            OdfDocument::THUMBNAIL,
+            zip::write::SimpleFileOptions::default(),
+        )?;
+        writer.write_all(b"original thumbnail")?;
+        let mut photo = Cursor::new(Vec::new());
+        image::RgbImage::from_pixel(2, 2, image::Rgb([0, 0, 0]))
+            .write_to(&mut photo, image::ImageFormat::Png)?;
+        for (name, content) in [
+            ("Pictures/photo.png", photo.into_inner()),
+            ("Pictures/diagram.svg", b"<svg/>".to_vec()),
+        ] {
+            writer.start_file(name, zip::write::SimpleFileOptions::default())?;
+            writer.write_all(&content)?;
+        }
+        Ok(writer.finish()?.into_inner().into())
+    }
+
+    #[allow(dead_code)]
+    fn read_entry(package: &bytes::Bytes, name: &str) -> AppResult<Vec<u8>> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(package.as_ref()))?;
+        let mut content = Vec::new();
+        archive.by_name(name)?.read_to_end(&mut content)?;
+        Ok(content)
+    }
+
+    #[test]
+    fn odf_text_test() -> AppResult<()> {
+        let content = concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><office:document-content><office:body><office:text>"#,
+            r#"<text:h>Report &amp; notes</text:h>"#,
+            r#"<text:p>Call <text:span text:style-name="T1">John Doe</text:span> at<text:s text:c="2"/>555-1234<text:line-break/>Thanks</text:p>"#,
+            r#"<text:p/><text:p>Nothing sensitive</text:p>"#,
+            r#"</office:text></office:body></office:document-content>"#
+        );
+        let package = odf_package("application/vnd.oasis.opendocument.text", content)?;
+        let document = OdfDocument::open(package)?;
+        assert_eq!(
+            document.text(),
+            "Report & notes\nCall John Doe at  555-1234\nThanks\nNothing sensitive"
+        );
+        assert_eq!(
+            document
+                .images()
+                .iter()
+                .map(|image| image.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["Pictures/photo.png"]
+        );
+
+        let mut redacted_photo = Cursor::new(Vec::new());
+        image::RgbImage::from_pixel(2, 2, image::Rgb([255, 255, 255]))
+            .write_to(&mut redacted_photo, image::ImageFormat::Jpeg)?;
+        let redacted = document.repack_text(
+            "Report & notes\nCall [PERSON] at  [PHONE]\nThanks\nNothing sensitive",
+            vec![PackageImage {
+                name: "Pictures/photo.png".to_string(),
+                mime_type: mime::IMAGE_JPEG,
+                data: redacted_photo.into_inner().into(),
+            }],
+        )?;
+        let redacted_content =
+            String::from_utf8_lossy(&read_entry(&redacted, "content.xml")?).to_string();
+        assert!(redacted_content.contains(
+            r#"<text:h>Report &amp; notes</text:h><text:p>Call [PERSON] at <text:s/>[PHONE]<text:span text:style-name="T1"></text:span><text:line-break/>Thanks</text:p>"#
+        ));
+        assert!(redacted_content.contains("<text:p>Nothing sensitive</text:p>"));
+        assert_eq!(
+            OdfDocument::open(redacted.clone())?.text(),
+            "Report & notes\nCall [PERSON] at  [PHONE]\nThanks\nNothing sensitive"
+        );
+        assert_eq!(
+            read_entry(&redacted, "mimetype")?,
+            b"application/vnd.oasis.opendocument.text"
+        );
+        assert_ne!(
+            read_entry(&redacted, OdfDocument::THUMBNAIL)?,
+            b"original thumbnail"
+        );
+        let redacted_png = read_entry(&redacted, "Pictures/photo.png")?;
+        assert_eq!(image::guess_format(&redacted_png)?, image::ImageFormat::Png);
+        assert_eq!(
+            image::load_from_memory(&redacted_png)?
+                .to_rgb8()
+                .get_pixel(0, 0),
+            &image::Rgb([255, 255, 255])
+        );
+        assert_eq!(read_entry(&redacted, "Pictures/diagram.svg")?, b"<svg/>");
+
+        assert!(document.repack_text("Report & notes", vec![]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn odf_spreadsheet_test() -> AppResult<()> {
+        let content = concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><office:document-content><office:body><office:spreadsheet><table:table>"#,
+            r#"<table:table-row><table:table-cell office:value-type="string" calcext:value-type="string"><text:p>Name</text:p></table:table-cell>"#,
+            r#"<table:table-cell office:value-type="string" calcext:value-type="string"><text:p>Phone</text:p></table:table-cell></table:table-row>"#,
+            r#"<table:table-row><table:table-cell office:value-type="string" calcext:value-type="string"><text:p>John Doe</text:p></table:table-cell>"#,
+            r#"<table:table-cell office:value-type="float" office:value="5551234" calcext:value-type="float"><text:p>5551234</text:p></table:table-cell>"#,
+            r#"<table:table-cell table:number-columns-repeated="1020"/></table:table-row>"#,
+            r#"</table:table></office:spreadsheet></office:body></office:document-content>"#
+        );
+        let package = odf_package("application/vnd.oasis.opendocument.spreadsheet", content)?;
+        let document = OdfDocument::open(package)?;
+        let (headers, rows) = document.table();
+        assert_eq!(headers, vec!["A", "B", "C"]);
+        assert_eq!(
+            rows,
+            vec![vec!["Name", "Phone", ""], vec!["John Doe", "5551234", ""]]
+        );
+
+        let redacted = document.repack_table(
+            &[
+                vec!["Name".to_string(), "Phone".to_string(), "".to_string()],
+                vec![
+                    "[PERSON]".to_string(),
+                    "[PHONE]".to_string(),
+                    "".to_string(),
+                ],
+            ],
+            vec![],
+        )?;
+        let redacted_content =
+            String::from_utf8_lossy(&read_entry(&redacted, "content.xml")?).to_string();
+        assert!(!redacted_content.contains("5551234"));
+        assert!(redacted_content.contains(
+            r#"<table:table-cell office:value-type="string"  calcext:value-type="string"><text:p>[PHONE]</text:p></table:table-cell>"#
+        ));
+        assert_eq!(OdfDocument::column_names(28)[26..], ["AA", "AB"]);
+        Ok(())
+    }
+}
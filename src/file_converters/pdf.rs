@@ -4,6 +4,8 @@ use gcloud_sdk::prost::bytes;
 #[derive(Debug, Clone)]
 pub struct PdfInfo {
     pub pages: Vec<PdfPageInfo>,
+    /// The original document, pages of which are reused if they are kept as is
+    pub original_pdf: bytes::Bytes,
 }
 
 #[derive(Debug, Clone)]
@@ -11,6 +13,8 @@ pub struct PdfPageInfo {
     pub height: PdfPoints,
     pub width: PdfPoints,
     pub page_as_images: image::DynamicImage,
+    /// Pages without redactions keep the original page instead of the image, preserving text and size
+    pub keep_original: bool,
 }
 
 #[cfg(feature = "pdf-render")]
@@ -24,3 +28,8 @@ pub trait PdfToImage {
 
     fn images_to_pdf(&self, pdf_info: PdfInfo) -> AppResult<bytes::Bytes>;
 }
+
+/// Counts pages without rendering them, since every page is redacted as a separate image
+pub fn pdf_page_count(pdf_bytes: &[u8]) -> AppResult<usize> {
+    Ok(lopdf::Document::load_mem(pdf_bytes)?.get_pages().len())
+}
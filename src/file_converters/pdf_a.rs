@@ -0,0 +1,424 @@
+use crate::errors::AppError;
+use crate::AppResult;
+use gcloud_sdk::prost::bytes;
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use sha2::{Digest, Sha256};
+
+const SRGB_PROFILE_NAME: &str = "sRGB IEC61966-2.1";
+
+/// ICC v2 sRGB IEC61966-2.1 profile with the sRGB tone curves (Little CMS `cmsCreate_sRGBProfile`),
+/// ICC v2 profiles are accepted by all PDF/A parts
+const SRGB_ICC_PROFILE: &[u8] = include_bytes!("srgb.icc");
+
+/// Binary comment of the header marking documents as binary files, as required by PDF/A
+const BINARY_MARK: [u8; 4] = [0xE2, 0xE3, 0xCF, 0xD3];
+
+/// Converts PDFs rebuilt from page images to PDF/A-2b for archives, adding XMP metadata,
+/// an sRGB output intent and a document ID. The document information dictionary is
+/// dropped instead of keeping it in sync with XMP metadata.
+pub fn pdf_to_pdf_a(pdf: &[u8]) -> AppResult<bytes::Bytes> {
+    let mut document = Document::load_mem(pdf)?;
+    if document.is_encrypted() {
+        return Err(AppError::ConversionError {
+            message: "Encrypted PDFs can't be converted to PDF/A".to_string(),
+        });
+    }
+    if !document.version.starts_with("1.") {
+        document.version = "1.7".to_string();
+    }
+    document.binary_mark = BINARY_MARK.to_vec();
+
+    let metadata_id = document.add_object(
+        Stream::new(
+            dictionary! { "Type" => "Metadata", "Subtype" => "XML" },
+            xmp_metadata().into_bytes(),
+        )
+        .with_compression(false),
+    );
+    let profile_id = document.add_object(Stream::new(
+        dictionary! { "N" => 3 },
+        SRGB_ICC_PROFILE.to_vec(),
+    ));
+    let output_intent_id = document.add_object(dictionary! {
+        "Type" => "OutputIntent",
+        "S" => "GTS_PDFA1",
+        "OutputConditionIdentifier" => Object::string_literal(SRGB_PROFILE_NAME),
+        "Info" => Object::string_literal(SRGB_PROFILE_NAME),
+        "DestOutputProfile" => profile_id,
+    });
+    let catalog = document.catalog_mut()?;
+    catalog.set("Metadata", metadata_id);
+    catalog.set("OutputIntents", vec![Object::Reference(output_intent_id)]);
+
+    let document_hash = Sha256::digest(pdf)[..16].to_vec();
+    let original_id = document
+        .trailer
+        .get(b"ID")
+        .and_then(Object::as_array)
+        .ok()
+        .and_then(|id| id.first().cloned())
+        .unwrap_or_else(|| Object::String(document_hash.clone(), StringFormat::Hexadecimal));
+    document.trailer.remove(b"Info");
+    document.trailer.set(
+        "ID",
+        vec![
+            original_id,
+            Object::String(document_hash, StringFormat::Hexadecimal),
+        ],
+    );
+    // Previous metadata, output intents and the information dictionary aren't referred anymore
+    document.prune_objects();
+
+    let mut output = Vec::new();
+    document.save_to(&mut output)?;
+    Ok(output.into())
+}
+
+/// Post-check of PDF/A-2b requirements of rebuilt documents, so non-conforming documents fail
+/// instead of being rejected by archives later. Documents are checked by their parsed structure,
+/// the full conformance of converted documents is verified with veraPDF in tests.
+pub fn validate_pdf_a(pdf: &[u8]) -> AppResult<()> {
+    let fail = |message: &str| {
+        Err(AppError::ConversionError {
+            message: format!("PDF/A validation failed: {}", message),
+        })
+    };
+    let mut lines = pdf
+        .split(|byte| *byte == b'\n' || *byte == b'\r')
+        .filter(|line| !line.is_empty());
+    let header = lines.next().unwrap_or_default();
+    let header_comment = lines.next().unwrap_or_default();
+    if !header.starts_with(b"%PDF-1.")
+        || !header_comment.starts_with(b"%")
+        || header_comment.iter().filter(|byte| **byte > 127).count() < 4
+    {
+        return fail("PDF 1.x header with a binary comment is required");
+    }
+    if !pdf.trim_ascii_end().ends_with(b"%%EOF") {
+        return fail("PDF must end with %%EOF");
+    }
+
+    let document = Document::load_mem(pdf)?;
+    if document.is_encrypted() {
+        return fail("Encryption is not allowed");
+    }
+    if !document
+        .trailer
+        .get(b"ID")
+        .and_then(Object::as_array)
+        .is_ok_and(|id| id.len() == 2)
+    {
+        return fail("Document ID is required in the trailer");
+    }
+
+    let catalog = document.catalog()?;
+    let metadata = catalog
+        .get(b"Metadata")
+        .and_then(Object::as_reference)
+        .and_then(|metadata_id| document.get_object(metadata_id))
+        .and_then(Object::as_stream);
+    match metadata {
+        Ok(metadata) if metadata.dict.get(b"Filter").is_err() => {
+            let xmp = String::from_utf8_lossy(&metadata.content);
+            if !xmp.contains("<pdfaid:part>2</pdfaid:part>")
+                || !xmp.contains("<pdfaid:conformance>B</pdfaid:conformance>")
+            {
+                return fail("XMP metadata must identify PDF/A-2b conformance");
+            }
+        }
+        Ok(_) => return fail("XMP metadata must not be compressed"),
+        Err(_) => return fail("Catalog must refer to XMP metadata"),
+    }
+
+    let output_intents = catalog
+        .get(b"OutputIntents")
+        .and_then(|output_intents| document.dereference(output_intents))
+        .and_then(|(_, output_intents)| output_intents.as_array())
+        .map(|output_intents| {
+            output_intents
+                .iter()
+                .filter_map(|output_intent| {
+                    document
+                        .dereference(output_intent)
+                        .and_then(|(_, output_intent)| output_intent.as_dict())
+                        .ok()
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let pdf_a_output_intent = output_intents.iter().find(|output_intent| {
+        output_intent
+            .get(b"S")
+            .and_then(Object::as_name)
+            .is_ok_and(|name| name == b"GTS_PDFA1")
+    });
+    match pdf_a_output_intent.map(|output_intent| output_profile(&document, output_intent)) {
+        Some(Ok(())) => {}
+        Some(Err(message)) => return fail(&message),
+        None => return fail("PDF/A output intent is required"),
+    }
+
+    for (object_id, object) in &document.objects {
+        let dictionary = match object {
+            Object::Dictionary(dictionary) => dictionary,
+            Object::Stream(stream) => &stream.dict,
+            _ => continue,
+        };
+        if let Err(message) = validate_dictionary(dictionary) {
+            return fail(&format!(
+                "{} (object {} {})",
+                message, object_id.0, object_id.1
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The profile of the output intent must be an ICC profile of RGB devices not newer than ICC v4
+fn output_profile(document: &Document, output_intent: &Dictionary) -> Result<(), String> {
+    let profile = output_intent
+        .get(b"DestOutputProfile")
+        .and_then(Object::as_reference)
+        .and_then(|profile_id: ObjectId| document.get_object(profile_id))
+        .and_then(Object::as_stream)
+        .map_err(|_| "Output intent must refer to an ICC profile".to_string())?;
+    let content = profile
+        .decompressed_content()
+        .unwrap_or_else(|_| profile.content.clone());
+    if content.len() < 128 || &content[36..40] != b"acsp" || content[8] > 4 {
+        return Err("Output intent profile must be an ICC v2 or v4 profile".to_string());
+    }
+    if &content[16..20] != b"RGB "
+        || !profile
+            .dict
+            .get(b"N")
+            .and_then(Object::as_i64)
+            .is_ok_and(|components| components == 3)
+    {
+        return Err("Output intent profile must be an RGB profile".to_string());
+    }
+    Ok(())
+}
+
+/// Objects forbidden by PDF/A-2: JavaScript, LZW compression and non-embedded fonts
+fn validate_dictionary(dictionary: &Dictionary) -> Result<(), String> {
+    let is_name = |key: &[u8], value: &[u8]| {
+        dictionary
+            .get(key)
+            .and_then(Object::as_name)
+            .is_ok_and(|name| name == value)
+    };
+    if is_name(b"S", b"JavaScript") || dictionary.has(b"JS") || dictionary.has(b"JavaScript") {
+        return Err("JavaScript is not allowed".to_string());
+    }
+    let filters = match dictionary.get(b"Filter") {
+        Ok(Object::Name(name)) => vec![name.as_slice()],
+        Ok(Object::Array(names)) => names
+            .iter()
+            .filter_map(|name| name.as_name().ok())
+            .collect(),
+        _ => Vec::new(),
+    };
+    if filters.contains(&b"LZWDecode".as_slice()) {
+        return Err("LZW compression is not allowed".to_string());
+    }
+    if is_name(b"Type", b"FontDescriptor")
+        && !dictionary.has(b"FontFile")
+        && !dictionary.has(b"FontFile2")
+        && !dictionary.has(b"FontFile3")
+    {
+        return Err("Fonts must be embedded".to_string());
+    }
+    Ok(())
+}
+
+fn xmp_metadata() -> String {
+    let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    format!(
+        r#"<?xpacket begin="{bom}" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:pdfaid="http://www.aiim.org/pdfa/ns/id/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmlns:pdf="http://ns.adobe.com/pdf/1.3/">
+<pdfaid:part>2</pdfaid:part>
+<pdfaid:conformance>B</pdfaid:conformance>
+<dc:format>application/pdf</dc:format>
+<xmp:CreateDate>{now}</xmp:CreateDate>
+<xmp:ModifyDate>{now}</xmp:ModifyDate>
+<xmp:CreatorTool>redacter</xmp:CreatorTool>
+<pdf:Producer>redacter {version}</pdf:Producer>
+</rdf:Description>
+</rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#,
+        bom = '\u{feff}',
+        now = now,
+        version = env!("CARGO_PKG_VERSION")
+    )
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[allow(dead_code)]
+    fn minimal_pdf() -> Vec<u8> {
+        let mut pdf = b"%PDF-1.7\r\n%\xA1\xB3\xC5\xD7\r\n".to_vec();
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] >>",
+            "<< /Producer (PDFium) >>",
+        ];
+        let mut offsets = Vec::new();
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(
+                format!("{} 0 obj\r\n{}\r\nendobj\r\n", index + 1, object).as_bytes(),
+            );
+        }
+        let xref_offset = pdf.len();
+        pdf.extend_from_slice(b"xref\r\n0 5\r\n0000000000 65535 f\r\n");
+        for offset in offsets {
+            pdf.extend_from_slice(format!("{:010} 00000 n\r\n", offset).as_bytes());
+        }
+        pdf.extend_from_slice(
+            format!(
+                "trailer\r\n<</Info 4 0 R/Root 1 0 R/Size 5>>\r\nstartxref\r\n{}\r\n%%EOF\r\n",
+                xref_offset
+            )
+            .as_bytes(),
+        );
+        pdf
+    }
+
+    #[test]
+    fn pdf_to_pdf_a_test() -> AppResult<()> {
+        let pdf = minimal_pdf();
+        assert!(validate_pdf_a(&pdf).is_err());
+
+        let pdf_a = pdf_to_pdf_a(&pdf)?;
+        validate_pdf_a(&pdf_a)?;
+
+        let document = Document::load_mem(&pdf_a)?;
+        assert_eq!(document.get_pages().len(), 1);
+        assert!(document.trailer.get(b"Info").is_err());
+        let catalog = document.catalog()?;
+        let output_intent_id = catalog.get(b"OutputIntents")?.as_array()?[0].as_reference()?;
+        let profile_id = document
+            .get_dictionary(output_intent_id)?
+            .get(b"DestOutputProfile")?
+            .as_reference()?;
+        assert_eq!(
+            document.get_object(profile_id)?.as_stream()?.content,
+            SRGB_ICC_PROFILE
+        );
+
+        // Converting converted documents again replaces their metadata and output intents
+        let pdf_a_again = pdf_to_pdf_a(&pdf_a)?;
+        validate_pdf_a(&pdf_a_again)?;
+        let document_again = Document::load_mem(&pdf_a_again)?;
+        assert_eq!(document_again.objects.len(), document.objects.len());
+        assert_eq!(
+            document_again.trailer.get(b"ID")?.as_array()?[0],
+            document.trailer.get(b"ID")?.as_array()?[0]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn validate_pdf_a_test() -> AppResult<()> {
+        let pdf_a = pdf_to_pdf_a(&minimal_pdf())?;
+        let with_object = |object: Object| -> AppResult<Vec<u8>> {
+            let mut document = Document::load_mem(&pdf_a)?;
+            document.add_object(object);
+            let mut output = Vec::new();
+            document.save_to(&mut output)?;
+            Ok(output)
+        };
+        assert!(validate_pdf_a(&with_object(Object::Dictionary(dictionary! {
+            "S" => "JavaScript",
+            "JS" => Object::string_literal("app.alert(1)"),
+        }))?)
+        .is_err());
+        assert!(validate_pdf_a(&with_object(Object::Dictionary(dictionary! {
+            "Type" => "FontDescriptor",
+            "FontName" => "Helvetica",
+        }))?)
+        .is_err());
+
+        let mut document = Document::load_mem(&pdf_a)?;
+        document.catalog_mut()?.remove(b"OutputIntents");
+        let mut output = Vec::new();
+        document.save_to(&mut output)?;
+        assert!(validate_pdf_a(&output).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn srgb_icc_profile_test() {
+        let profile = SRGB_ICC_PROFILE;
+        assert_eq!(
+            u32::from_be_bytes(profile[..4].try_into().unwrap()) as usize,
+            profile.len()
+        );
+        assert_eq!(profile[8], 2);
+        assert_eq!(&profile[12..24], b"mntrRGB XYZ ");
+        assert_eq!(&profile[36..40], b"acsp");
+
+        // Tone curves follow the sRGB transfer function instead of a plain gamma
+        let tag_count = u32::from_be_bytes(profile[128..132].try_into().unwrap()) as usize;
+        let (offset, _) = (0..tag_count)
+            .map(|index| &profile[132 + 12 * index..144 + 12 * index])
+            .find(|tag| &tag[..4] == b"rTRC")
+            .map(|tag| {
+                (
+                    u32::from_be_bytes(tag[4..8].try_into().unwrap()) as usize,
+                    u32::from_be_bytes(tag[8..12].try_into().unwrap()) as usize,
+                )
+            })
+            .expect("rTRC tag");
+        assert_eq!(&profile[offset..offset + 4], b"curv");
+        let entries = u32::from_be_bytes(profile[offset + 8..offset + 12].try_into().unwrap());
+        assert!(entries > 1);
+        let entry = |input: f64| -> f64 {
+            let index = (input * (entries - 1) as f64).round() as usize;
+            let value_offset = offset + 12 + index * 2;
+            u16::from_be_bytes(profile[value_offset..value_offset + 2].try_into().unwrap()) as f64
+                / 65535.0
+        };
+        let srgb = |input: f64| -> f64 {
+            if input <= 0.04045 {
+                input / 12.92
+            } else {
+                ((input + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        for input in [0.01, 0.03, 0.2, 0.5, 0.8, 1.0] {
+            assert!((entry(input) - srgb(input)).abs() < 0.002, "{}", input);
+        }
+    }
+
+    /// Converted documents are validated with veraPDF (https://verapdf.org) available in `PATH`
+    /// or `VERAPDF_PATH`
+    #[test]
+    #[cfg_attr(not(feature = "ci-verapdf"), ignore)]
+    fn verapdf_validation_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let temp_dir = tempfile::TempDir::with_prefix("pdf_a_tests")?;
+        let pdf_a_path = temp_dir.path().join("test.pdf");
+        std::fs::write(&pdf_a_path, pdf_to_pdf_a(&minimal_pdf())?)?;
+
+        let verapdf = std::env::var("VERAPDF_PATH").unwrap_or_else(|_| "verapdf".to_string());
+        let output = std::process::Command::new(verapdf)
+            .arg("--flavour")
+            .arg("2b")
+            .arg(&pdf_a_path)
+            .output()?;
+        let report = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            report.contains("isCompliant=\"true\""),
+            "veraPDF report:\n{}",
+            report
+        );
+        Ok(())
+    }
+}
@@ -44,14 +44,20 @@ impl PdfImageConverter {
 impl PdfToImage for PdfImageConverter {
     fn convert_to_images(&self, pdf_bytes: Bytes) -> AppResult<PdfInfo> {
         let render_config = PdfRenderConfig::default();
-        let document = self.pdfium.load_pdf_from_byte_vec(pdf_bytes.into(), None)?;
-        let mut pdf_info = PdfInfo { pages: Vec::new() };
+        let document = self
+            .pdfium
+            .load_pdf_from_byte_vec(pdf_bytes.to_vec(), None)?;
+        let mut pdf_info = PdfInfo {
+            pages: Vec::new(),
+            original_pdf: pdf_bytes.clone(),
+        };
         for page in document.pages().iter() {
             let image = page.render_with_config(&render_config)?.as_image();
             let page_info = PdfPageInfo {
                 height: page.height(),
                 width: page.width(),
                 page_as_images: image,
+                keep_original: false,
             };
             pdf_info.pages.push(page_info);
         }
@@ -60,11 +66,34 @@ impl PdfToImage for PdfImageConverter {
 
     fn images_to_pdf(&self, pdf_info: PdfInfo) -> AppResult<Bytes> {
         let mut document = self.pdfium.create_new_pdf()?;
-        for src_page in pdf_info.pages.iter().rev() {
+        let original_document = if pdf_info.pages.iter().any(|page| page.keep_original) {
+            Some(
+                self.pdfium
+                    .load_pdf_from_byte_vec(pdf_info.original_pdf.to_vec(), None)?,
+            )
+        } else {
+            None
+        };
+        for (page_index, src_page) in pdf_info.pages.iter().enumerate() {
+            let page_index =
+                PdfPageIndex::try_from(page_index).map_err(|_| AppError::SystemError {
+                    message: format!("Too many PDF pages: {}", pdf_info.pages.len()),
+                })?;
+            if let Some(original_document) = original_document
+                .as_ref()
+                .filter(|_| src_page.keep_original)
+            {
+                document.pages_mut().copy_page_from_document(
+                    original_document,
+                    page_index,
+                    page_index,
+                )?;
+                continue;
+            }
             let mut page =
                 document
                     .pages_mut()
-                    .create_page_at_start(PdfPagePaperSize::from_points(
+                    .create_page_at_end(PdfPagePaperSize::from_points(
                         src_page.width,
                         src_page.height,
                     ))?;
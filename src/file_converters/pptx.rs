@@ -0,0 +1,312 @@
+use crate::errors::AppError;
+use crate::file_converters::xml_package::{PackageImage, XmlPackage};
+use crate::AppResult;
+use gcloud_sdk::prost::bytes;
+use std::collections::HashMap;
+use std::ops::Range;
+use xmlparser::{ElementEnd, Token, Tokenizer};
+
+/// Text of slides (text frames, tables and notes) and images of a PowerPoint presentation.
+/// Redacted text is written back to the XML of slides in place, so only paragraphs with changed
+/// text lose their inline formatting.
+pub struct PptxDocument {
+    package: XmlPackage,
+    parts: Vec<PptxPart>,
+    blocks: Vec<PptxBlock>,
+    images: Vec<PackageImage>,
+}
+
+struct PptxPart {
+    name: String,
+    xml: String,
+}
+
+/// Text of a paragraph (or of a line of a paragraph with line breaks)
+struct PptxBlock {
+    part: usize,
+    pieces: Vec<PptxPiece>,
+}
+
+/// Text of `<a:t>` elements of runs and fields
+struct PptxPiece {
+    span: Range<usize>,
+    text: String,
+}
+
+enum PptxElement {
+    Paragraph,
+    Text,
+    LineBreak,
+    Other,
+}
+
+impl PptxDocument {
+    const THUMBNAIL: &'static str = "docProps/thumbnail.jpeg";
+    const SLIDE_PARTS: [&'static str; 2] = ["ppt/slides/slide", "ppt/notesSlides/notesSlide"];
+    const MEDIA: &'static str = "ppt/media/";
+
+    pub fn is_mime_pptx(mime: &mime::Mime) -> bool {
+        mime.essence_str()
+            == "application/vnd.openxmlformats-officedocument.presentationml.presentation"
+    }
+
+    pub fn open(package: bytes::Bytes) -> AppResult<Self> {
+        let package = XmlPackage::new(package);
+        let mut parts = package.read_xml_entries(|name| Self::slide_order(name).is_some())?;
+        parts.sort_by_key(|(name, _)| Self::slide_order(name));
+        let images = package.read_images(Self::MEDIA)?;
+        let mut document = Self {
+            package,
+            parts: Vec::new(),
+            blocks: Vec::new(),
+            images,
+        };
+        for (name, xml) in parts {
+            document.parse_part(document.parts.len(), &xml)?;
+            document.parts.push(PptxPart { name, xml });
+        }
+        Ok(document)
+    }
+
+    pub fn number_of_paragraphs(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn number_of_slides(&self) -> usize {
+        self.parts
+            .iter()
+            .filter(|part| part.name.starts_with(Self::SLIDE_PARTS[0]))
+            .count()
+    }
+
+    /// Text of all paragraphs of slides followed by notes, separated by new lines
+    pub fn text(&self) -> String {
+        self.blocks
+            .iter()
+            .map(PptxBlock::text)
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn images(&self) -> &[PackageImage] {
+        &self.images
+    }
+
+    /// Writes the redacted text of paragraphs separated by new lines and the redacted images
+    /// back to the package
+    pub fn repack(
+        &self,
+        redacted_text: &str,
+        redacted_images: Vec<PackageImage>,
+    ) -> AppResult<bytes::Bytes> {
+        let texts: Vec<&str> = if self.blocks.is_empty() {
+            Vec::new()
+        } else {
+            redacted_text.split('\n').collect()
+        };
+        if texts.len() != self.blocks.len() {
+            return Err(AppError::ConversionError {
+                message: format!(
+                    "Redacted text has {} lines instead of {} paragraphs of the presentation",
+                    texts.len(),
+                    self.blocks.len()
+                ),
+            });
+        }
+
+        let mut edits: Vec<Vec<(Range<usize>, String)>> = vec![Vec::new(); self.parts.len()];
+        for (block, text) in self.blocks.iter().zip(texts) {
+            if block.text() == text {
+                continue;
+            }
+            for (idx, piece) in block.pieces.iter().enumerate() {
+                let replacement = if idx == 0 {
+                    let mut escaped = String::with_capacity(text.len());
+                    text.chars()
+                        .for_each(|ch| XmlPackage::escape_char(ch, &mut escaped));
+                    escaped
+                } else {
+                    String::new()
+                };
+                edits[block.part].push((piece.span.clone(), replacement));
+            }
+        }
+
+        let mut replaced: HashMap<String, Vec<u8>> = HashMap::new();
+        for (part, part_edits) in self.parts.iter().zip(edits.iter_mut()) {
+            if !part_edits.is_empty() {
+                replaced.insert(
+                    part.name.clone(),
+                    XmlPackage::apply_edits(&part.xml, part_edits).into_bytes(),
+                );
+            }
+        }
+        XmlPackage::replace_images(&self.images, redacted_images, &mut replaced)?;
+        replaced.insert(
+            Self::THUMBNAIL.to_string(),
+            XmlPackage::blank_thumbnail(image::ImageFormat::Jpeg)?,
+        );
+        self.package.repack(&replaced)
+    }
+
+    fn parse_part(&mut self, part: usize, xml: &str) -> AppResult<()> {
+        let mut pending: Option<PptxElement> = None;
+        // Paragraphs of DrawingML aren't nested
+        let mut paragraph: Option<usize> = None;
+        let mut in_text = false;
+        for token in Tokenizer::from(xml) {
+            let token = token.map_err(|err| AppError::ConversionError {
+                message: format!("Invalid PowerPoint XML: {}", err),
+            })?;
+            match token {
+                Token::ElementStart { prefix, local, .. } => {
+                    pending = Some(match (prefix.as_str(), local.as_str()) {
+                        ("a", "p") => PptxElement::Paragraph,
+                        ("a", "t") => PptxElement::Text,
+                        ("a", "br") => PptxElement::LineBreak,
+                        _ => PptxElement::Other,
+                    });
+                }
+                Token::ElementEnd { end, .. } => match end {
+                    ElementEnd::Open => match pending.take() {
+                        Some(PptxElement::Paragraph) => {
+                            paragraph = Some(self.push_block(part));
+                        }
+                        Some(PptxElement::Text) => {
+                            in_text = true;
+                        }
+                        _ => {}
+                    },
+                    ElementEnd::Empty => {
+                        if let Some(PptxElement::LineBreak) = pending.take() {
+                            if paragraph.is_some() {
+                                paragraph = Some(self.push_block(part));
+                            }
+                        }
+                    }
+                    ElementEnd::Close(prefix, local) => match (prefix.as_str(), local.as_str()) {
+                        ("a", "p") => paragraph = None,
+                        ("a", "t") => in_text = false,
+                        _ => {}
+                    },
+                },
+                Token::Text { text } if in_text => {
+                    if let Some(block) = paragraph {
+                        self.blocks[block].pieces.push(PptxPiece {
+                            span: text.range(),
+                            text: XmlPackage::unescape_text(text.as_str()),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn push_block(&mut self, part: usize) -> usize {
+        self.blocks.push(PptxBlock {
+            part,
+            pieces: Vec::new(),
+        });
+        self.blocks.len() - 1
+    }
+
+    /// Slides ordered by their numbers, followed by notes of slides
+    fn slide_order(name: &str) -> Option<(usize, usize)> {
+        Self::SLIDE_PARTS
+            .iter()
+            .enumerate()
+            .find_map(|(kind, prefix)| {
+                name.strip_prefix(prefix)?
+                    .strip_suffix(".xml")?
+                    .parse::<usize>()
+                    .ok()
+                    .map(|number| (kind, number))
+            })
+    }
+}
+
+impl PptxBlock {
+    fn text(&self) -> String {
+        self.pieces
+            .iter()
+            .map(|piece| piece.text.as_str())
+            .collect()
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read, Write};
+
+    #[test]
+    fn pptx_test() -> AppResult<()> {
+        let slide = concat!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><p:sld><p:cSld><p:spTree><p:sp><p:txBody>"#,
+            r#"<a:p><a:r><a:rPr b="1"/><a:t>Customer: </a:t></a:r><a:r><a:t>John Doe</a:t></a:r><a:br/><a:r><a:t>Q3 &amp; Q4</a:t></a:r></a:p>"#,
+            r#"<a:p><a:endParaRPr/></a:p>"#,
+            r#"</p:txBody></p:sp></p:spTree></p:cSld></p:sld>"#
+        );
+        let notes =
+            r#"<p:notes><p:txBody><a:p><a:r><a:t>Call John</a:t></a:r></a:p></p:txBody></p:notes>"#;
+        let mut screenshot = Cursor::new(Vec::new());
+        image::RgbImage::from_pixel(2, 2, image::Rgb([0, 0, 0]))
+            .write_to(&mut screenshot, image::ImageFormat::Png)?;
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in [
+            ("[Content_Types].xml", b"<Types/>".to_vec()),
+            ("ppt/notesSlides/notesSlide1.xml", notes.as_bytes().to_vec()),
+            ("ppt/slides/slide10.xml", b"<p:sld/>".to_vec()),
+            ("ppt/slides/slide2.xml", slide.as_bytes().to_vec()),
+            ("ppt/media/image1.png", screenshot.into_inner()),
+            ("ppt/media/image2.emf", b"emf".to_vec()),
+        ] {
+            writer.start_file(name, zip::write::SimpleFileOptions::default())?;
+            writer.write_all(&content)?;
+        }
+        let document = PptxDocument::open(writer.finish()?.into_inner().into())?;
+        assert_eq!(document.number_of_slides(), 2);
+        assert_eq!(document.text(), "Customer: John Doe\nQ3 & Q4\n\nCall John");
+        assert_eq!(
+            document
+                .images()
+                .iter()
+                .map(|image| image.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["ppt/media/image1.png"]
+        );
+
+        let mut redacted_image = Cursor::new(Vec::new());
+        image::RgbImage::from_pixel(2, 2, image::Rgb([255, 255, 255]))
+            .write_to(&mut redacted_image, image::ImageFormat::Jpeg)?;
+        let redacted = document.repack(
+            "Customer: [PERSON]\nQ3 & Q4\n\nCall [PERSON]",
+            vec![PackageImage {
+                name: "ppt/media/image1.png".to_string(),
+                mime_type: mime::IMAGE_JPEG,
+                data: redacted_image.into_inner().into(),
+            }],
+        )?;
+        let mut archive = zip::ZipArchive::new(Cursor::new(redacted.as_ref()))?;
+        let mut redacted_slide = String::new();
+        archive
+            .by_name("ppt/slides/slide2.xml")?
+            .read_to_string(&mut redacted_slide)?;
+        assert!(redacted_slide.contains(
+            r#"<a:r><a:rPr b="1"/><a:t>Customer: [PERSON]</a:t></a:r><a:r><a:t></a:t></a:r><a:br/><a:r><a:t>Q3 &amp; Q4</a:t></a:r>"#
+        ));
+        let mut redacted_png = Vec::new();
+        archive
+            .by_name("ppt/media/image1.png")?
+            .read_to_end(&mut redacted_png)?;
+        assert_eq!(image::guess_format(&redacted_png)?, image::ImageFormat::Png);
+        assert_eq!(
+            PptxDocument::open(redacted)?.text(),
+            "Customer: [PERSON]\nQ3 & Q4\n\nCall [PERSON]"
+        );
+        Ok(())
+    }
+}
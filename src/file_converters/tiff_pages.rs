@@ -0,0 +1,98 @@
+use crate::errors::AppError;
+use crate::AppResult;
+use gcloud_sdk::prost::bytes;
+use image::{DynamicImage, ImageBuffer};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::encoder::{colortype, compression, TiffEncoder};
+use tiff::ColorType;
+
+/// Decodes all pages of a TIFF, since scanned documents frequently arrive as
+/// multi-page TIFFs and image decoders only read the first page.
+pub fn tiff_to_images(tiff_bytes: &[u8]) -> AppResult<Vec<DynamicImage>> {
+    let mut decoder = Decoder::new(std::io::Cursor::new(tiff_bytes))?;
+    let mut pages = Vec::new();
+    loop {
+        let (width, height) = decoder.dimensions()?;
+        let color_type = decoder.colortype()?;
+        let page = match (color_type, decoder.read_image()?) {
+            (ColorType::Gray(8), DecodingResult::U8(data)) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+            }
+            (ColorType::GrayA(8), DecodingResult::U8(data)) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8)
+            }
+            (ColorType::RGB(8), DecodingResult::U8(data)) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+            }
+            (ColorType::RGBA(8), DecodingResult::U8(data)) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+            }
+            (ColorType::Gray(16), DecodingResult::U16(data)) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16)
+            }
+            (ColorType::RGB(16), DecodingResult::U16(data)) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16)
+            }
+            (ColorType::RGBA(16), DecodingResult::U16(data)) => {
+                ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16)
+            }
+            _ => None,
+        }
+        .ok_or_else(|| AppError::ConversionError {
+            message: format!("Unsupported TIFF page color type: {:?}", color_type),
+        })?;
+        pages.push(page);
+        if !decoder.more_images() {
+            break;
+        }
+        decoder.next_image()?;
+    }
+    Ok(pages)
+}
+
+/// Counts pages without decoding their images
+pub fn tiff_page_count(tiff_bytes: &[u8]) -> AppResult<usize> {
+    let mut decoder = Decoder::new(std::io::Cursor::new(tiff_bytes))?;
+    let mut pages = 1;
+    while decoder.more_images() {
+        decoder.next_image()?;
+        pages += 1;
+    }
+    Ok(pages)
+}
+
+/// Encodes pages as a multi-page TIFF with LZW compression
+pub fn images_to_tiff(pages: &[DynamicImage]) -> AppResult<bytes::Bytes> {
+    let mut output = std::io::Cursor::new(Vec::new());
+    let mut encoder = TiffEncoder::new(&mut output)?;
+    for page in pages {
+        let rgb_page = page.to_rgb8();
+        encoder.write_image_with_compression::<colortype::RGB8, _>(
+            rgb_page.width(),
+            rgb_page.height(),
+            compression::Lzw,
+            rgb_page.as_raw(),
+        )?;
+    }
+    Ok(output.into_inner().into())
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiff_pages_round_trip_test() -> Result<(), Box<dyn std::error::Error>> {
+        let pages = vec![
+            DynamicImage::ImageRgb8(image::RgbImage::from_pixel(16, 8, image::Rgb([255, 0, 0]))),
+            DynamicImage::ImageLuma8(image::GrayImage::from_pixel(8, 16, image::Luma([128]))),
+        ];
+        let tiff_bytes = images_to_tiff(&pages)?;
+        let decoded_pages = tiff_to_images(&tiff_bytes)?;
+        assert_eq!(decoded_pages.len(), 2);
+        assert_eq!(tiff_page_count(&tiff_bytes)?, 2);
+        assert_eq!(decoded_pages[0].to_rgb8(), pages[0].to_rgb8());
+        assert_eq!(decoded_pages[1].to_rgb8(), pages[1].to_rgb8());
+        Ok(())
+    }
+}
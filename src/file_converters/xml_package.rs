@@ -0,0 +1,257 @@
+use crate::errors::AppError;
+use crate::AppResult;
+use gcloud_sdk::prost::bytes;
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::ops::Range;
+
+/// Images embedded in documents and presentations such as screenshots, scans and photos
+#[derive(Debug, Clone)]
+pub struct PackageImage {
+    pub name: String,
+    pub mime_type: mime::Mime,
+    pub data: bytes::Bytes,
+}
+
+/// Zip packages of XML parts and media such as OpenDocument, PowerPoint (PPTX) and Word (DOCX) documents
+pub struct XmlPackage {
+    package: bytes::Bytes,
+}
+
+impl XmlPackage {
+    const MAX_ENTRY_SIZE: u64 = 256 * 1024 * 1024;
+
+    pub fn new(package: bytes::Bytes) -> Self {
+        Self { package }
+    }
+
+    /// Reads the entries with matching names in the order of the package
+    pub fn read_entries<F: Fn(&str) -> bool>(
+        &self,
+        filter: F,
+    ) -> AppResult<Vec<(String, Vec<u8>)>> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(self.package.as_ref()))?;
+        let mut entries = Vec::new();
+        for idx in 0..archive.len() {
+            let entry = archive.by_index(idx)?;
+            if entry.is_dir() || !filter(entry.name()) {
+                continue;
+            }
+            let name = entry.name().to_string();
+            // Sizes in headers can be forged, so reading stops right after the limit
+            let mut content = Vec::new();
+            entry
+                .take(Self::MAX_ENTRY_SIZE + 1)
+                .read_to_end(&mut content)?;
+            if content.len() as u64 > Self::MAX_ENTRY_SIZE {
+                return Err(AppError::ConversionError {
+                    message: format!(
+                        "Document entry {} exceeds {} bytes",
+                        name,
+                        Self::MAX_ENTRY_SIZE
+                    ),
+                });
+            }
+            entries.push((name, content));
+        }
+        Ok(entries)
+    }
+
+    pub fn read_xml_entries<F: Fn(&str) -> bool>(
+        &self,
+        filter: F,
+    ) -> AppResult<Vec<(String, String)>> {
+        self.read_entries(filter)?
+            .into_iter()
+            .map(|(name, content)| {
+                String::from_utf8(content)
+                    .map(|xml| (name.clone(), xml))
+                    .map_err(|_| AppError::ConversionError {
+                        message: format!("Document entry {} is not UTF-8 XML", name),
+                    })
+            })
+            .collect()
+    }
+
+    /// Reads raster images under the directory of media of the package.
+    /// Vector images such as EMF/WMF and SVG aren't supported by redacters and are kept as is
+    pub fn read_images(&self, media_dir: &str) -> AppResult<Vec<PackageImage>> {
+        Ok(self
+            .read_entries(|name| Self::image_mime(media_dir, name).is_some())?
+            .into_iter()
+            .filter_map(|(name, data)| {
+                Self::image_mime(media_dir, &name).map(|mime_type| PackageImage {
+                    name,
+                    mime_type,
+                    data: data.into(),
+                })
+            })
+            .collect())
+    }
+
+    /// Adds redacted images to replaced entries. Images are converted back to their original
+    /// formats if needed, since formats of media are declared by their file extensions
+    pub fn replace_images(
+        images: &[PackageImage],
+        redacted_images: Vec<PackageImage>,
+        replaced: &mut HashMap<String, Vec<u8>>,
+    ) -> AppResult<()> {
+        for redacted_image in redacted_images {
+            let original_mime = images
+                .iter()
+                .find(|image| image.name == redacted_image.name)
+                .map(|image| image.mime_type.clone())
+                .ok_or_else(|| AppError::SystemError {
+                    message: format!("Image {} is not found in the package", redacted_image.name),
+                })?;
+            let data = if redacted_image.mime_type == original_mime {
+                redacted_image.data.to_vec()
+            } else {
+                Self::convert_image(&redacted_image, &original_mime)?
+            };
+            replaced.insert(redacted_image.name, data);
+        }
+        Ok(())
+    }
+
+    fn image_mime(media_dir: &str, name: &str) -> Option<mime::Mime> {
+        let extension = name
+            .strip_prefix(media_dir)?
+            .rsplit_once('.')?
+            .1
+            .to_lowercase();
+        match extension.as_str() {
+            "png" => Some(mime::IMAGE_PNG),
+            "jpg" | "jpeg" => Some(mime::IMAGE_JPEG),
+            "gif" => Some(mime::IMAGE_GIF),
+            "bmp" => Some(mime::IMAGE_BMP),
+            _ => None,
+        }
+    }
+
+    fn convert_image(image: &PackageImage, mime_type: &mime::Mime) -> AppResult<Vec<u8>> {
+        let format = image::ImageFormat::from_mime_type(mime_type).ok_or_else(|| {
+            AppError::ConversionError {
+                message: format!("Unsupported image format {}", mime_type),
+            }
+        })?;
+        let mut output = Cursor::new(Vec::new());
+        image::load_from_memory(&image.data)?
+            .to_rgb8()
+            .write_to(&mut output, format)?;
+        Ok(output.into_inner())
+    }
+
+    /// Writes the package with replaced entries. Other entries are copied without recompression,
+    /// so entries such as the uncompressed OpenDocument mimetype stay as required by formats
+    pub fn repack(&self, replaced: &HashMap<String, Vec<u8>>) -> AppResult<bytes::Bytes> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(self.package.as_ref()))?;
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let file_options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        for idx in 0..archive.len() {
+            let entry = archive.by_index_raw(idx)?;
+            match replaced.get(entry.name()) {
+                Some(content) => {
+                    let name = entry.name().to_string();
+                    drop(entry);
+                    writer.start_file(name, file_options)?;
+                    writer.write_all(content)?;
+                }
+                None => writer.raw_copy_file(entry)?,
+            }
+        }
+        Ok(writer.finish()?.into_inner().into())
+    }
+
+    /// Thumbnails of documents are renderings with the original text, so they are replaced with blank images
+    pub fn blank_thumbnail(format: image::ImageFormat) -> AppResult<Vec<u8>> {
+        let mut thumbnail = Cursor::new(Vec::new());
+        image::RgbImage::from_pixel(1, 1, image::Rgb([255, 255, 255]))
+            .write_to(&mut thumbnail, format)?;
+        Ok(thumbnail.into_inner())
+    }
+
+    /// Replaces spans of the XML, which must not overlap
+    pub fn apply_edits(xml: &str, edits: &mut [(Range<usize>, String)]) -> String {
+        edits.sort_by_key(|(span, _)| span.start);
+        let mut result = String::with_capacity(xml.len());
+        let mut position = 0;
+        for (span, replacement) in edits.iter() {
+            result.push_str(&xml[position..span.start]);
+            result.push_str(replacement);
+            position = span.end;
+        }
+        result.push_str(&xml[position..]);
+        result
+    }
+
+    pub fn unescape_text(text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(idx) = rest.find('&') {
+            result.push_str(&rest[..idx]);
+            rest = &rest[idx..];
+            let entity = rest.find(';').map(|end| (&rest[1..end], end));
+            let unescaped = entity.and_then(|(entity, end)| {
+                let ch = match entity {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    _ => match entity.strip_prefix('#') {
+                        Some(code) => match code.strip_prefix('x') {
+                            Some(hex_code) => u32::from_str_radix(hex_code, 16).ok(),
+                            None => code.parse::<u32>().ok(),
+                        }
+                        .and_then(char::from_u32),
+                        None => None,
+                    },
+                };
+                ch.map(|ch| (ch, end))
+            });
+            match unescaped {
+                Some((ch, end)) => {
+                    result.push(ch);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    result.push('&');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+
+    pub fn escape_char(ch: char, result: &mut String) {
+        match ch {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            _ => result.push(ch),
+        }
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xml_text_test() {
+        assert_eq!(
+            XmlPackage::unescape_text("a &amp; b &lt;c&gt; &#233;&#x41; &unknown; &"),
+            "a & b <c> éA &unknown; &"
+        );
+        assert_eq!(
+            XmlPackage::apply_edits(
+                "<p>John</p><p>Doe</p>",
+                &mut [(14..17, "X".to_string()), (3..7, "Y".to_string())]
+            ),
+            "<p>Y</p><p>X</p>"
+        );
+    }
+}
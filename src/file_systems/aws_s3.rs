@@ -1,6 +1,10 @@
 use crate::errors::AppError;
+use crate::file_systems::object_path::ObjectStoragePath;
+use crate::file_systems::resumable_download::{range_from_offset, resumable_download_stream};
+use crate::file_systems::upload_parts::UploadPartsReader;
 use crate::file_systems::{
-    AbsoluteFilePath, FileSystemConnection, FileSystemRef, ListFilesResult, RelativeFilePath,
+    AbsoluteFilePath, FileChecksum, FileMetadata, FileSystemConnection, FileSystemOptions,
+    FileSystemRef, FileSystemStream, ListFilesResult,
 };
 use crate::file_tools::{FileMatcher, FileMatcherResult};
 use crate::reporter::AppReporter;
@@ -11,41 +15,167 @@ use gcloud_sdk::prost::bytes::Bytes;
 use rvstruct::ValueStruct;
 
 pub struct AwsS3FileSystem<'a> {
-    bucket_name: String,
-    object_name: String,
+    path: ObjectStoragePath,
     client: aws_sdk_s3::Client,
-    is_dir: bool,
+    server_side_encryption: Option<aws_sdk_s3::types::ServerSideEncryption>,
+    kms_key_id: Option<String>,
+    storage_class: Option<aws_sdk_s3::types::StorageClass>,
+    upload_part_size: usize,
     reporter: &'a AppReporter<'a>,
 }
 
 impl<'a> AwsS3FileSystem<'a> {
-    pub async fn new(path: &str, reporter: &'a AppReporter<'a>) -> AppResult<Self> {
-        let shared_config = aws_config::load_from_env().await;
-        let (bucket_name, object_name) = Self::parse_s3_path(path)?;
-        let is_dir = object_name.ends_with('/');
-        let client = aws_sdk_s3::Client::new(&shared_config);
+    /// Object names in copy sources are URL-encoded except path separators
+    const COPY_SOURCE_ENCODE_SET: &'static percent_encoding::AsciiSet =
+        &percent_encoding::NON_ALPHANUMERIC
+            .remove(b'/')
+            .remove(b'-')
+            .remove(b'_')
+            .remove(b'.')
+            .remove(b'~');
+
+    fn copy_source(&self, object_name: &str) -> String {
+        format!(
+            "{}/{}",
+            self.path.bucket_name,
+            percent_encoding::utf8_percent_encode(object_name, Self::COPY_SOURCE_ENCODE_SET)
+        )
+    }
+
+    pub async fn new(
+        path: &str,
+        reporter: &'a AppReporter<'a>,
+        options: &FileSystemOptions,
+    ) -> AppResult<Self> {
+        let config_loader = match options.s3_profile {
+            Some(ref profile) => aws_config::from_env().profile_name(profile),
+            None => aws_config::from_env(),
+        };
+        let shared_config = config_loader.load().await;
+        let mut s3_config_builder = aws_sdk_s3::config::Builder::from(&shared_config)
+            .force_path_style(options.s3_force_path_style);
+        if let Some(ref endpoint_url) = options.s3_endpoint_url {
+            s3_config_builder =
+                s3_config_builder.endpoint_url(endpoint_url.as_str().trim_end_matches('/'));
+        }
+        let client = aws_sdk_s3::Client::from_conf(s3_config_builder.build());
 
         Ok(AwsS3FileSystem {
-            bucket_name,
-            object_name,
+            path: ObjectStoragePath::parse("s3", path)?,
             client,
-            is_dir,
+            server_side_encryption: options
+                .s3_server_side_encryption
+                .as_deref()
+                .map(aws_sdk_s3::types::ServerSideEncryption::from),
+            kms_key_id: options.s3_kms_key_id.clone(),
+            storage_class: options
+                .s3_storage_class
+                .as_deref()
+                .map(aws_sdk_s3::types::StorageClass::from),
+            upload_part_size: options.upload_part_size(),
             reporter,
         })
     }
 
-    fn parse_s3_path(path: &str) -> AppResult<(String, String)> {
-        let path_parts: Vec<&str> = path.trim_start_matches("s3://").split('/').collect();
-        if path_parts.len() < 2 {
-            return Err(AppError::SystemError {
-                message: format!("Invalid S3 path: {}", path),
-            });
+    /// Objects of multipart uploads appear only when uploads are completed,
+    /// so readers never see partially uploaded objects
+    async fn upload_multipart<S: Stream<Item = AppResult<Bytes>> + Unpin>(
+        &self,
+        object_name: &str,
+        content_type: Option<String>,
+        first_parts: Vec<Bytes>,
+        reader: &mut UploadPartsReader<S>,
+    ) -> AppResult<()> {
+        let multipart_upload = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.path.bucket_name)
+            .key(object_name)
+            .set_content_type(content_type)
+            .set_server_side_encryption(self.server_side_encryption.clone())
+            .set_ssekms_key_id(self.kms_key_id.clone())
+            .set_storage_class(self.storage_class.clone())
+            .send()
+            .await?;
+        let upload_id = multipart_upload
+            .upload_id
+            .ok_or_else(|| AppError::SystemError {
+                message: format!(
+                    "No upload id returned for S3 multipart upload: {}",
+                    object_name
+                ),
+            })?;
+
+        match self
+            .upload_parts(object_name, &upload_id, first_parts, reader)
+            .await
+        {
+            Ok(completed_parts) => {
+                self.client
+                    .complete_multipart_upload()
+                    .bucket(&self.path.bucket_name)
+                    .key(object_name)
+                    .upload_id(&upload_id)
+                    .multipart_upload(
+                        aws_sdk_s3::types::CompletedMultipartUpload::builder()
+                            .set_parts(Some(completed_parts))
+                            .build(),
+                    )
+                    .send()
+                    .await?;
+                Ok(())
+            }
+            Err(err) => {
+                // Aborting uploads is best effort, the original error is more relevant
+                let _ = self
+                    .client
+                    .abort_multipart_upload()
+                    .bucket(&self.path.bucket_name)
+                    .key(object_name)
+                    .upload_id(&upload_id)
+                    .send()
+                    .await;
+                Err(err)
+            }
         }
-        if path_parts[1].is_empty() {
-            Ok((path_parts[0].to_string(), "/".to_string()))
-        } else {
-            Ok((path_parts[0].to_string(), path_parts[1..].join("/")))
+    }
+
+    async fn upload_parts<S: Stream<Item = AppResult<Bytes>> + Unpin>(
+        &self,
+        object_name: &str,
+        upload_id: &str,
+        first_parts: Vec<Bytes>,
+        reader: &mut UploadPartsReader<S>,
+    ) -> AppResult<Vec<aws_sdk_s3::types::CompletedPart>> {
+        let mut completed_parts = Vec::new();
+        let mut pending_parts = first_parts.into_iter();
+        loop {
+            let part = match pending_parts.next() {
+                Some(part) => part,
+                None => match reader.next_part().await? {
+                    Some(part) => part,
+                    None => break,
+                },
+            };
+            let part_number = completed_parts.len() as i32 + 1;
+            let uploaded_part = self
+                .client
+                .upload_part()
+                .bucket(&self.path.bucket_name)
+                .key(object_name)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(aws_sdk_s3::primitives::ByteStream::from(part))
+                .send()
+                .await?;
+            completed_parts.push(
+                aws_sdk_s3::types::CompletedPart::builder()
+                    .part_number(part_number)
+                    .set_e_tag(uploaded_part.e_tag)
+                    .build(),
+            );
         }
+        Ok(completed_parts)
     }
 
     #[async_recursion::async_recursion]
@@ -63,7 +193,7 @@ impl<'a> AwsS3FileSystem<'a> {
         let list_req = self
             .client
             .list_objects_v2()
-            .bucket(&self.bucket_name)
+            .bucket(&self.path.bucket_name)
             .set_prefix(prefix)
             .set_continuation_token(continuation_token.clone());
         let list_resp = list_req.send().await?;
@@ -75,13 +205,17 @@ impl<'a> AwsS3FileSystem<'a> {
                     .filter(|item| item.key.iter().all(|key| !key.ends_with('/')))
                     .filter_map(|item| {
                         item.key.map(|name| {
-                            let relative_path: RelativeFilePath =
-                                name.trim_start_matches(&self.object_name).into();
+                            let relative_path = self.path.relative_path_of(&name);
                             let media_type = mime_guess::from_path(&name).first();
                             FileSystemRef {
                                 relative_path,
                                 media_type,
                                 file_size: item.size.map(|v| v as usize),
+                                modified_at: item
+                                    .last_modified
+                                    .as_ref()
+                                    .and_then(from_aws_date_time),
+                                checksum: item.e_tag.as_deref().map(FileChecksum::from_aws_etag),
                             }
                         })
                     })
@@ -129,6 +263,12 @@ impl<'a> AwsS3FileSystem<'a> {
     }
 }
 
+fn from_aws_date_time(
+    value: &aws_sdk_s3::primitives::DateTime,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::from_timestamp(value.secs(), value.subsec_nanos())
+}
+
 impl<'a> FileSystemConnection<'a> for AwsS3FileSystem<'a> {
     async fn download(
         &mut self,
@@ -138,24 +278,12 @@ impl<'a> FileSystemConnection<'a> for AwsS3FileSystem<'a> {
         Box<dyn Stream<Item = AppResult<Bytes>> + Send + Sync + Unpin + 'static>,
     )> {
         let object_name = self.resolve(file_ref).file_path;
-        let relative_path: RelativeFilePath = if self.is_dir {
-            object_name
-                .clone()
-                .trim_start_matches(&self.object_name)
-                .into()
-        } else {
-            object_name
-                .split('/')
-                .last()
-                .map(|file_name| file_name.to_string())
-                .unwrap_or_else(|| object_name.clone())
-                .into()
-        };
+        let relative_path = self.path.relative_path_of(&object_name);
 
         let object = self
             .client
             .get_object()
-            .bucket(&self.bucket_name)
+            .bucket(&self.path.bucket_name)
             .key(&object_name)
             .send()
             .await?;
@@ -168,12 +296,35 @@ impl<'a> FileSystemConnection<'a> for AwsS3FileSystem<'a> {
                 .transpose()?
                 .or_else(|| mime_guess::from_path(relative_path.value()).first()),
             file_size: object.content_length.map(|v| v as usize),
+            modified_at: object.last_modified.as_ref().and_then(from_aws_date_time),
+            checksum: object.e_tag.as_deref().map(FileChecksum::from_aws_etag),
         };
 
         let reader = object.body.into_async_read();
         let stream = tokio_util::io::ReaderStream::new(reader).map_err(AppError::from);
 
-        Ok((found_file_ref, Box::new(stream)))
+        let client = self.client.clone();
+        let bucket_name = self.path.bucket_name.clone();
+        // Resumed ranges must belong to the same version of the object
+        let e_tag = object.e_tag;
+        let resumable_stream = resumable_download_stream(Box::new(stream), move |offset| {
+            let request = client
+                .get_object()
+                .bucket(&bucket_name)
+                .key(&object_name)
+                .range(range_from_offset(offset))
+                .set_if_match(e_tag.clone());
+            async move {
+                let object = request.send().await?;
+                let reader = object.body.into_async_read();
+                Ok(
+                    Box::new(tokio_util::io::ReaderStream::new(reader).map_err(AppError::from))
+                        as FileSystemStream,
+                )
+            }
+        });
+
+        Ok((found_file_ref, resumable_stream))
     }
 
     async fn upload<S: Stream<Item = AppResult<Bytes>> + Send + Unpin + Sync + 'static>(
@@ -185,20 +336,35 @@ impl<'a> FileSystemConnection<'a> for AwsS3FileSystem<'a> {
         let content_type = file_ref
             .and_then(|fr| fr.media_type.as_ref())
             .map(|v| v.to_string());
-        let body_bytes: Vec<Bytes> = input.try_collect().await?;
-        let all_bytes = body_bytes.concat();
-        let body = aws_sdk_s3::primitives::ByteStream::from(all_bytes);
-
-        self.client
-            .put_object()
-            .bucket(&self.bucket_name)
-            .key(&object_name)
-            .set_content_type(content_type)
-            .body(body)
-            .send()
-            .await?;
-
-        Ok(())
+        let mut reader = UploadPartsReader::new(input, self.upload_part_size);
+        let first_part = reader.next_part().await?;
+        match reader.next_part().await? {
+            Some(second_part) => {
+                self.upload_multipart(
+                    &object_name,
+                    content_type,
+                    first_part.into_iter().chain(Some(second_part)).collect(),
+                    &mut reader,
+                )
+                .await
+            }
+            None => {
+                self.client
+                    .put_object()
+                    .bucket(&self.path.bucket_name)
+                    .key(&object_name)
+                    .set_content_type(content_type)
+                    .set_server_side_encryption(self.server_side_encryption.clone())
+                    .set_ssekms_key_id(self.kms_key_id.clone())
+                    .set_storage_class(self.storage_class.clone())
+                    .body(aws_sdk_s3::primitives::ByteStream::from(
+                        first_part.unwrap_or_default(),
+                    ))
+                    .send()
+                    .await?;
+                Ok(())
+            }
+        }
     }
 
     async fn list_files(
@@ -208,15 +374,11 @@ impl<'a> FileSystemConnection<'a> for AwsS3FileSystem<'a> {
     ) -> AppResult<ListFilesResult> {
         self.reporter.report(format!(
             "Listing files in bucket: {} with prefix: {}",
-            self.bucket_name, self.object_name
+            self.path.bucket_name, self.path.object_name
         ))?;
-        if self.object_name.ends_with('/') {
+        if self.path.is_dir() {
             self.list_files_recursively(
-                if self.object_name == "/" {
-                    None
-                } else {
-                    Some(self.object_name.clone())
-                },
+                self.path.prefix().map(|prefix| prefix.to_string()),
                 None,
                 &file_matcher,
                 max_files_limit,
@@ -227,36 +389,174 @@ impl<'a> FileSystemConnection<'a> for AwsS3FileSystem<'a> {
         }
     }
 
+    async fn get_file_ref(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<Option<FileSystemRef>> {
+        let object_name = self.resolve(file_ref).file_path;
+        match self
+            .client
+            .head_object()
+            .bucket(&self.path.bucket_name)
+            .key(&object_name)
+            .send()
+            .await
+        {
+            Ok(object) => Ok(Some(FileSystemRef {
+                relative_path: file_ref
+                    .map(|fr| fr.relative_path.clone())
+                    .unwrap_or_else(|| object_name.clone().into()),
+                media_type: object.content_type.and_then(|v| v.parse().ok()),
+                file_size: object.content_length.map(|v| v as usize),
+                modified_at: object.last_modified.as_ref().and_then(from_aws_date_time),
+                checksum: object.e_tag.as_deref().map(FileChecksum::from_aws_etag),
+            })),
+            Err(err)
+                if err
+                    .as_service_error()
+                    .map(|service_error| service_error.is_not_found())
+                    .unwrap_or(false) =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn get_metadata(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<Option<FileMetadata>> {
+        let object_name = self.resolve(file_ref).file_path;
+        let object = self
+            .client
+            .head_object()
+            .bucket(&self.path.bucket_name)
+            .key(&object_name)
+            .send()
+            .await?;
+        let tagging = self
+            .client
+            .get_object_tagging()
+            .bucket(&self.path.bucket_name)
+            .key(&object_name)
+            .send()
+            .await?;
+        Ok(Some(FileMetadata {
+            custom: object.metadata.unwrap_or_default().into_iter().collect(),
+            tags: tagging
+                .tag_set
+                .into_iter()
+                .map(|tag| (tag.key, tag.value))
+                .collect(),
+            ..FileMetadata::default()
+        }))
+    }
+
+    /// User metadata can't be updated in place, so objects are copied onto themselves with the metadata
+    async fn set_metadata(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+        metadata: &FileMetadata,
+    ) -> AppResult<()> {
+        let object_name = self.resolve(file_ref).file_path;
+        if !metadata.custom.is_empty() {
+            self.client
+                .copy_object()
+                .bucket(&self.path.bucket_name)
+                .key(&object_name)
+                .copy_source(self.copy_source(&object_name))
+                .metadata_directive(aws_sdk_s3::types::MetadataDirective::Replace)
+                .set_server_side_encryption(self.server_side_encryption.clone())
+                .set_ssekms_key_id(self.kms_key_id.clone())
+                .set_storage_class(self.storage_class.clone())
+                .set_content_type(
+                    file_ref
+                        .and_then(|fr| fr.media_type.as_ref())
+                        .map(|media_type| media_type.to_string()),
+                )
+                .set_metadata(Some(metadata.custom.clone().into_iter().collect()))
+                .send()
+                .await?;
+        }
+        if !metadata.tags.is_empty() {
+            let tag_set = metadata
+                .tags
+                .iter()
+                .map(|(key, value)| {
+                    aws_sdk_s3::types::Tag::builder()
+                        .key(key)
+                        .value(value)
+                        .build()
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| AppError::AwsSdkError(Box::new(err)))?;
+            self.client
+                .put_object_tagging()
+                .bucket(&self.path.bucket_name)
+                .key(&object_name)
+                .tagging(
+                    aws_sdk_s3::types::Tagging::builder()
+                        .set_tag_set(Some(tag_set))
+                        .build()
+                        .map_err(|err| AppError::AwsSdkError(Box::new(err)))?,
+                )
+                .send()
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn supports_file_operations(&self) -> bool {
+        true
+    }
+
+    /// Copies keep metadata and tags of the source objects
+    async fn copy_file(&mut self, from: &FileSystemRef, to: &FileSystemRef) -> AppResult<()> {
+        let from_object_name = self.resolve(Some(from)).file_path;
+        self.client
+            .copy_object()
+            .bucket(&self.path.bucket_name)
+            .key(self.resolve(Some(to)).file_path)
+            .copy_source(self.copy_source(&from_object_name))
+            .set_server_side_encryption(self.server_side_encryption.clone())
+            .set_ssekms_key_id(self.kms_key_id.clone())
+            .set_storage_class(self.storage_class.clone())
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn move_file(&mut self, from: &FileSystemRef, to: &FileSystemRef) -> AppResult<()> {
+        self.copy_file(from, to).await?;
+        self.delete_file(from).await
+    }
+
+    async fn delete_file(&mut self, file_ref: &FileSystemRef) -> AppResult<()> {
+        self.client
+            .delete_object()
+            .bucket(&self.path.bucket_name)
+            .key(self.resolve(Some(file_ref)).file_path)
+            .send()
+            .await?;
+        Ok(())
+    }
+
     async fn close(self) -> AppResult<()> {
         Ok(())
     }
 
     async fn has_multiple_files(&self) -> AppResult<bool> {
-        Ok(self.is_dir)
+        Ok(self.path.is_dir())
     }
 
     async fn accepts_multiple_files(&self) -> AppResult<bool> {
-        Ok(self.is_dir)
+        Ok(self.path.is_dir())
     }
 
     fn resolve(&self, file_ref: Option<&FileSystemRef>) -> AbsoluteFilePath {
         AbsoluteFilePath {
-            file_path: if self.is_dir {
-                let object_name_prefix = if self.object_name == "/" {
-                    ""
-                } else {
-                    self.object_name.as_str()
-                };
-                format!(
-                    "{}{}",
-                    object_name_prefix,
-                    file_ref
-                        .map(|fr| fr.relative_path.value().clone())
-                        .unwrap_or_default()
-                )
-            } else {
-                self.object_name.clone()
-            },
+            file_path: self.path.resolve(file_ref.map(|fr| &fr.relative_path)),
         }
     }
 }
@@ -279,6 +579,7 @@ mod tests {
         let mut fs = AwsS3FileSystem::new(
             &format!("s3://{}/redacter/test-upload/", test_gcp_bucket_name),
             &reporter,
+            &FileSystemOptions::default(),
         )
         .await?;
 
@@ -290,6 +591,8 @@ mod tests {
                 relative_path: "test-upload.txt".into(),
                 media_type: Some(mime::TEXT_PLAIN),
                 file_size: Some(test_data.len()),
+                modified_at: None,
+                checksum: None,
             }),
         )
         .await?;
@@ -299,6 +602,8 @@ mod tests {
                 relative_path: "test-upload.txt".into(),
                 media_type: Some(mime::TEXT_PLAIN),
                 file_size: Some(test_data.len()),
+                modified_at: None,
+                checksum: None,
             }))
             .await?;
 
@@ -316,6 +621,54 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ci-aws"), ignore)]
+    async fn multipart_upload_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let term = console::Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let test_gcp_bucket_name =
+            std::env::var("TEST_AWS_BUCKET_NAME").expect("TEST_AWS_BUCKET_NAME required");
+
+        let part_size = 5 * 1024 * 1024;
+        let mut fs = AwsS3FileSystem::new(
+            &format!(
+                "s3://{}/redacter/test-multipart-upload/",
+                test_gcp_bucket_name
+            ),
+            &reporter,
+            &FileSystemOptions {
+                upload_part_size: Some(part_size),
+                ..FileSystemOptions::default()
+            },
+        )
+        .await?;
+
+        let test_data = vec![b'a'; part_size * 2 + 100];
+        let test_data_stream = futures::stream::iter(
+            test_data
+                .chunks(1024 * 1024)
+                .map(|chunk| Ok(bytes::Bytes::copy_from_slice(chunk)))
+                .collect::<Vec<_>>(),
+        );
+        let file_ref = FileSystemRef {
+            relative_path: "test-upload.txt".into(),
+            media_type: Some(mime::TEXT_PLAIN),
+            file_size: Some(test_data.len()),
+            modified_at: None,
+            checksum: None,
+        };
+        fs.upload(test_data_stream, Some(&file_ref)).await?;
+
+        let (downloaded_file_ref, down_stream) = fs.download(Some(&file_ref)).await?;
+        let downloaded_bytes: Vec<bytes::Bytes> = down_stream.try_collect().await?;
+        assert_eq!(downloaded_bytes.concat(), test_data);
+        assert_eq!(downloaded_file_ref.file_size, Some(test_data.len()));
+
+        fs.close().await?;
+
+        Ok(())
+    }
+
     #[tokio::test]
     #[cfg_attr(not(feature = "ci-aws"), ignore)]
     async fn list_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
@@ -327,6 +680,7 @@ mod tests {
         let mut fs = AwsS3FileSystem::new(
             &format!("s3://{}/redacter/test-list/", test_gcp_bucket_name),
             &reporter,
+            &FileSystemOptions::default(),
         )
         .await?;
 
@@ -338,6 +692,8 @@ mod tests {
                 relative_path: "test-upload.txt".into(),
                 media_type: Some(mime::TEXT_PLAIN),
                 file_size: Some(test_data.len()),
+                modified_at: None,
+                checksum: None,
             }),
         )
         .await?;
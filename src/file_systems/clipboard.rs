@@ -1,5 +1,7 @@
 use crate::errors::AppError;
-use crate::file_systems::{AbsoluteFilePath, FileSystemConnection, FileSystemRef, ListFilesResult};
+use crate::file_systems::{
+    AbsoluteFilePath, FileSystemConnection, FileSystemRef, FileSystemStream, ListFilesResult,
+};
 use crate::file_tools::FileMatcher;
 use crate::redacters::Redacters;
 use crate::reporter::AppReporter;
@@ -9,6 +11,7 @@ use bytes::Bytes;
 use futures::{Stream, TryStreamExt};
 use image::{ImageBuffer, ImageFormat};
 use rvstruct::ValueStruct;
+use std::path::{Path, PathBuf};
 
 pub struct ClipboardFileSystem<'a> {
     clipboard: Clipboard,
@@ -18,7 +21,9 @@ pub struct ClipboardFileSystem<'a> {
 impl<'a> ClipboardFileSystem<'a> {
     pub async fn new(root_path: &str, reporter: &'a AppReporter<'a>) -> AppResult<Self> {
         if root_path != "clipboard://" {
-            return Err(AppError::SystemError {
+            return Err(AppError::FileSystemError {
+                scheme: "clipboard".to_string(),
+                path: root_path.to_string(),
                 message: "Clipboard should be specified as clipboard://".into(),
             });
         }
@@ -27,6 +32,92 @@ impl<'a> ClipboardFileSystem<'a> {
             reporter,
         })
     }
+
+    /// Files copied in file managers are redacted instead of their names
+    async fn download_copied_file(
+        &self,
+        file_path: &Path,
+    ) -> AppResult<(FileSystemRef, FileSystemStream)> {
+        self.reporter.report(format!(
+            "Redacting the file copied to clipboard: {}",
+            file_path.display()
+        ))?;
+        let content = tokio::fs::read(file_path).await?;
+        Ok((
+            FileSystemRef {
+                relative_path: file_path
+                    .file_name()
+                    .map(|file_name| file_name.to_string_lossy().to_string())
+                    .unwrap_or_default()
+                    .into(),
+                media_type: mime_guess::from_path(file_path).first(),
+                file_size: Some(content.len()),
+                modified_at: None,
+                checksum: None,
+            },
+            Box::new(futures::stream::iter(vec![Ok(Bytes::from(content))])),
+        ))
+    }
+}
+
+/// Paths of files copied in Windows Explorer
+#[cfg(windows)]
+fn get_copied_files() -> Vec<PathBuf> {
+    clipboard_win::get_clipboard::<Vec<PathBuf>, _>(clipboard_win::formats::FileList)
+        .unwrap_or_default()
+}
+
+#[cfg(not(windows))]
+fn get_copied_files() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// HTML fragments copied from browsers and office applications on Windows
+#[cfg(windows)]
+fn get_html() -> Option<String> {
+    let html_format = clipboard_win::formats::Html::new()?;
+    clipboard_win::get_clipboard::<String, _>(html_format)
+        .ok()
+        .filter(|html| !html.trim().is_empty())
+}
+
+#[cfg(not(windows))]
+fn get_html() -> Option<String> {
+    None
+}
+
+/// Plain text alternative of HTML for applications pasting only text
+fn html_to_text(html: &str) -> String {
+    const LINE_BREAK_TAGS: [&str; 7] = ["br", "/p", "/div", "/li", "/tr", "/h1", "/h2"];
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(tag_start) = rest.find('<') {
+        text.push_str(&rest[..tag_start]);
+        let tag_end = rest[tag_start..]
+            .find('>')
+            .map(|idx| tag_start + idx + 1)
+            .unwrap_or(rest.len());
+        let tag_name = rest[tag_start + 1..tag_end]
+            .trim_end_matches('>')
+            .split(|c: char| c.is_whitespace() || c == '>')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches('/')
+            .to_lowercase();
+        if LINE_BREAK_TAGS.contains(&tag_name.as_str()) {
+            text.push('\n');
+        }
+        rest = &rest[tag_end..];
+    }
+    text.push_str(rest);
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
 }
 
 impl<'a> FileSystemConnection<'a> for ClipboardFileSystem<'a> {
@@ -37,12 +128,34 @@ impl<'a> FileSystemConnection<'a> for ClipboardFileSystem<'a> {
         FileSystemRef,
         Box<dyn Stream<Item = AppResult<Bytes>> + Send + Sync + Unpin + 'static>,
     )> {
+        let copied_files = get_copied_files();
+        if let Some(copied_file) = copied_files.first() {
+            if copied_files.len() > 1 {
+                self.reporter.report(format!(
+                    "Only the first of {} files copied to clipboard is redacted",
+                    copied_files.len()
+                ))?;
+            }
+            return self.download_copied_file(copied_file).await;
+        }
         let filename = format!(
             "{}",
             std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)?
                 .as_secs()
         );
+        if let Some(html) = get_html() {
+            return Ok((
+                FileSystemRef {
+                    relative_path: format!("{}.html", filename).into(),
+                    media_type: Some(mime::TEXT_HTML),
+                    file_size: Some(html.len()),
+                    modified_at: None,
+                    checksum: None,
+                },
+                Box::new(futures::stream::iter(vec![Ok(bytes::Bytes::from(html))])),
+            ));
+        }
         match self.clipboard.get().image() {
             Ok(image_data) => {
                 let maybe_image: Option<image::RgbaImage> = image::ImageBuffer::from_raw(
@@ -59,6 +172,8 @@ impl<'a> FileSystemConnection<'a> for ClipboardFileSystem<'a> {
                             relative_path: format!("{}.png", filename).into(),
                             media_type: Some(mime::IMAGE_PNG),
                             file_size: Some(png_image_bytes.len()),
+                            modified_at: None,
+                            checksum: None,
                         },
                         Box::new(futures::stream::iter(vec![Ok(bytes::Bytes::from(
                             png_image_bytes,
@@ -78,6 +193,8 @@ impl<'a> FileSystemConnection<'a> for ClipboardFileSystem<'a> {
                         relative_path: format!("{}.txt", filename).into(),
                         media_type: Some(mime::TEXT_PLAIN),
                         file_size: Some(text.len()),
+                        modified_at: None,
+                        checksum: None,
                     },
                     Box::new(futures::stream::iter(vec![Ok(bytes::Bytes::from(text))])),
                 ))
@@ -117,6 +234,12 @@ impl<'a> FileSystemConnection<'a> for ClipboardFileSystem<'a> {
                                     .into(),
                             })
                         }
+                    } else if mime.essence_str() == mime::TEXT_HTML.essence_str() {
+                        let html = String::from_utf8_lossy(&all_bytes);
+                        let alt_text = html_to_text(&html);
+                        self.clipboard
+                            .set_html(html.as_ref(), Some(alt_text.as_str()))?;
+                        Ok(())
                     } else {
                         self.clipboard
                             .set_text(String::from_utf8_lossy(&all_bytes))?;
@@ -144,6 +267,13 @@ impl<'a> FileSystemConnection<'a> for ClipboardFileSystem<'a> {
         Ok(ListFilesResult::EMPTY)
     }
 
+    async fn get_file_ref(
+        &mut self,
+        _file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<Option<FileSystemRef>> {
+        Ok(None)
+    }
+
     async fn close(self) -> AppResult<()> {
         Ok(())
     }
@@ -171,7 +301,7 @@ impl<'a> FileSystemConnection<'a> for ClipboardFileSystem<'a> {
 #[allow(unused_imports)]
 mod tests {
     use super::*;
-    use crate::file_systems::DetectFileSystem;
+    use crate::file_systems::{DetectFileSystem, FileSystemOptions};
     use console::Term;
     use image::RgbaImage;
 
@@ -181,7 +311,9 @@ mod tests {
         let term = Term::stdout();
         let reporter: AppReporter = AppReporter::from(&term);
 
-        let mut fs = DetectFileSystem::open("clipboard://", &reporter).await?;
+        let mut fs =
+            DetectFileSystem::open("clipboard://", &reporter, &FileSystemOptions::default())
+                .await?;
 
         let test_content = "Test content";
 
@@ -191,6 +323,8 @@ mod tests {
                 relative_path: "temp_file.txt".into(),
                 media_type: Some(mime::TEXT_PLAIN),
                 file_size: Some(13),
+                modified_at: None,
+                checksum: None,
             }),
         )
         .await?;
@@ -215,7 +349,9 @@ mod tests {
         let term = Term::stdout();
         let reporter: AppReporter = AppReporter::from(&term);
 
-        let mut fs = DetectFileSystem::open("clipboard://", &reporter).await?;
+        let mut fs =
+            DetectFileSystem::open("clipboard://", &reporter, &FileSystemOptions::default())
+                .await?;
 
         let test_content: image::RgbaImage = RgbaImage::new(100, 100);
         let mut writer = std::io::Cursor::new(Vec::new());
@@ -229,6 +365,8 @@ mod tests {
                 relative_path: "temp_file.png".into(),
                 media_type: Some(mime::IMAGE_PNG),
                 file_size: Some(png_images_bytes_len),
+                modified_at: None,
+                checksum: None,
             }),
         )
         .await?;
@@ -248,4 +386,14 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn html_to_text_test() {
+        assert_eq!(
+            html_to_text(
+                "<p>John <b>Doe</b>&nbsp;&amp; Co</p><p>Email: <a href=\"mailto:jd@example.com\">jd@example.com</a><br/>Tel: 1&lt;2</p>"
+            ),
+            "John Doe & Co\nEmail: jd@example.com\nTel: 1<2"
+        );
+    }
 }
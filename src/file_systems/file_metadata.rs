@@ -0,0 +1,234 @@
+use crate::file_systems::{FileChecksum, FileSystemRef};
+use std::collections::BTreeMap;
+
+/// Metadata of files preserved on copies in addition to the content,
+/// such as local permissions and modification time, S3 user metadata and tags, or GCS custom metadata.
+/// File systems set only the metadata they support.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// S3 user metadata and GCS custom metadata
+    pub custom: BTreeMap<String, String>,
+    /// S3 object tags
+    pub tags: BTreeMap<String, String>,
+    pub modified_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Unix permissions of local files
+    pub permissions: Option<u32>,
+}
+
+impl FileMetadata {
+    pub const MODIFIED_AT_KEY: &'static str = "modified-at";
+    pub const PERMISSIONS_KEY: &'static str = "permissions";
+    /// Custom metadata marking redacted files, so they aren't redacted again by next runs
+    pub const REDACTED_MARKER_KEY: &'static str = "x-redacter";
+    const REDACTED_MARKER_VERSION: &'static str = "v1";
+    /// Custom metadata of copies with checksums (or sizes and modification times) of their sources,
+    /// since redacted copies never have the same checksums as their sources
+    pub const SOURCE_KEY: &'static str = "x-redacter-source";
+    const SOURCE_VERSION: &'static str = "v1";
+
+    /// Metadata with the source of copied files such as `v1;md5=...` or `v1;size=10;modified-at=...`.
+    /// Sources without checksums, sizes or modification times have no metadata.
+    pub fn copied_from(source_file_ref: &FileSystemRef) -> Self {
+        FileMetadata {
+            custom: Self::source_fingerprint(source_file_ref)
+                .map(|fingerprint| BTreeMap::from([(Self::SOURCE_KEY.to_string(), fingerprint)]))
+                .unwrap_or_default(),
+            ..FileMetadata::default()
+        }
+    }
+
+    /// Checks if the file is a copy of the same source content,
+    /// `None` if it isn't known, such as for files copied by previous versions
+    pub fn is_copied_from(&self, source_file_ref: &FileSystemRef) -> Option<bool> {
+        let copied_from = self.custom.get(Self::SOURCE_KEY)?;
+        Self::source_fingerprint(source_file_ref).map(|fingerprint| &fingerprint == copied_from)
+    }
+
+    fn source_fingerprint(file_ref: &FileSystemRef) -> Option<String> {
+        match (&file_ref.checksum, file_ref.file_size, file_ref.modified_at) {
+            (Some(FileChecksum::Md5(md5)), _, _) => {
+                Some(format!("{};md5={}", Self::SOURCE_VERSION, md5))
+            }
+            (Some(FileChecksum::Crc32c(crc32c)), _, _) => {
+                Some(format!("{};crc32c={}", Self::SOURCE_VERSION, crc32c))
+            }
+            (Some(FileChecksum::ETag(etag)), _, _) => {
+                Some(format!("{};etag={}", Self::SOURCE_VERSION, etag))
+            }
+            (None, Some(file_size), Some(modified_at)) => Some(format!(
+                "{};size={};modified-at={}",
+                Self::SOURCE_VERSION,
+                file_size,
+                modified_at.timestamp_millis()
+            )),
+            _ => None,
+        }
+    }
+
+    /// Metadata with the marker of files redacted by the providers such as `v1;providers=gcp-dlp`
+    pub fn redacted_marker(providers: &[String]) -> Self {
+        FileMetadata {
+            custom: BTreeMap::from([(
+                Self::REDACTED_MARKER_KEY.to_string(),
+                format!("{};providers={}", Self::SOURCE_VERSION, providers.join(",")),
+            )]),
+            ..FileMetadata::default()
+        }
+    }
+
+    /// Providers of files marked as redacted, markers of unknown versions are ignored
+    pub fn redacted_by(&self) -> Option<Vec<String>> {
+        let marker = self.custom.get(Self::REDACTED_MARKER_KEY)?;
+        let mut fields = marker.split(';');
+        if fields.next()? != Self::REDACTED_MARKER_VERSION {
+            return None;
+        }
+        Some(
+            fields
+                .find_map(|field| field.strip_prefix("providers="))
+                .map(|providers| {
+                    providers
+                        .split(',')
+                        .filter(|provider| !provider.is_empty())
+                        .map(|provider| provider.to_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Adds custom metadata and tags of the other metadata replacing existing keys
+    pub fn merge(mut self, other: FileMetadata) -> Self {
+        self.custom.extend(other.custom);
+        self.tags.extend(other.tags);
+        self.modified_at = other.modified_at.or(self.modified_at);
+        self.permissions = other.permissions.or(self.permissions);
+        self
+    }
+
+    /// Removes custom metadata and tags with the keys (case-insensitive),
+    /// `modified-at` and `permissions` keys remove file attributes
+    pub fn scrub(mut self, keys: &[String]) -> Self {
+        let is_scrubbed = |key: &str| keys.iter().any(|k| k.eq_ignore_ascii_case(key));
+        self.custom.retain(|key, _| !is_scrubbed(key));
+        self.tags.retain(|key, _| !is_scrubbed(key));
+        if is_scrubbed(Self::MODIFIED_AT_KEY) {
+            self.modified_at = None;
+        }
+        if is_scrubbed(Self::PERMISSIONS_KEY) {
+            self.permissions = None;
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &FileMetadata::default()
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrub_metadata_test() {
+        let metadata = FileMetadata {
+            custom: BTreeMap::from([
+                ("Author".to_string(), "John Doe".to_string()),
+                ("department".to_string(), "sales".to_string()),
+            ]),
+            tags: BTreeMap::from([("author".to_string(), "jdoe".to_string())]),
+            modified_at: chrono::DateTime::from_timestamp(1000, 0),
+            permissions: Some(0o644),
+        };
+
+        let scrubbed = metadata
+            .clone()
+            .scrub(&["author".to_string(), "permissions".to_string()]);
+        assert_eq!(
+            scrubbed.custom,
+            BTreeMap::from([("department".to_string(), "sales".to_string())])
+        );
+        assert!(scrubbed.tags.is_empty());
+        assert_eq!(scrubbed.modified_at, metadata.modified_at);
+        assert_eq!(scrubbed.permissions, None);
+
+        assert!(metadata
+            .scrub(&[
+                "author".to_string(),
+                "department".to_string(),
+                "modified-at".to_string(),
+                "permissions".to_string()
+            ])
+            .is_empty());
+    }
+
+    #[test]
+    fn redacted_marker_test() {
+        let providers = vec!["gcp-dlp".to_string(), "ms-presidio".to_string()];
+        let marker = FileMetadata::redacted_marker(&providers);
+        assert_eq!(
+            marker.custom.get(FileMetadata::REDACTED_MARKER_KEY),
+            Some(&"v1;providers=gcp-dlp,ms-presidio".to_string())
+        );
+        assert_eq!(marker.redacted_by(), Some(providers));
+
+        let metadata = FileMetadata {
+            custom: BTreeMap::from([("department".to_string(), "sales".to_string())]),
+            permissions: Some(0o644),
+            ..FileMetadata::default()
+        };
+        assert_eq!(metadata.redacted_by(), None);
+        let merged = metadata.merge(FileMetadata::redacted_marker(&[]));
+        assert_eq!(merged.custom.len(), 2);
+        assert_eq!(merged.permissions, Some(0o644));
+        assert_eq!(merged.redacted_by(), Some(vec![]));
+
+        let unknown_version = FileMetadata {
+            custom: BTreeMap::from([(
+                FileMetadata::REDACTED_MARKER_KEY.to_string(),
+                "v2;providers=gcp-dlp".to_string(),
+            )]),
+            ..FileMetadata::default()
+        };
+        assert_eq!(unknown_version.redacted_by(), None);
+    }
+
+    #[test]
+    fn copied_from_test() {
+        let source = FileSystemRef {
+            relative_path: "test.txt".into(),
+            media_type: Some(mime::TEXT_PLAIN),
+            file_size: Some(10),
+            modified_at: chrono::DateTime::from_timestamp(1000, 0),
+            checksum: None,
+        };
+        let copy = FileMetadata::copied_from(&source);
+        assert_eq!(
+            copy.custom.get(FileMetadata::SOURCE_KEY),
+            Some(&"v1;size=10;modified-at=1000000".to_string())
+        );
+        assert_eq!(copy.is_copied_from(&source), Some(true));
+        let modified = FileSystemRef {
+            modified_at: chrono::DateTime::from_timestamp(2000, 0),
+            ..source.clone()
+        };
+        assert_eq!(copy.is_copied_from(&modified), Some(false));
+
+        let with_checksum = FileSystemRef {
+            checksum: Some(FileChecksum::Md5("aa".to_string())),
+            ..source.clone()
+        };
+        let copy = FileMetadata::copied_from(&with_checksum);
+        assert_eq!(copy.is_copied_from(&with_checksum), Some(true));
+        assert_eq!(copy.is_copied_from(&source), Some(false));
+        assert_eq!(FileMetadata::default().is_copied_from(&source), None);
+
+        let unknown = FileSystemRef {
+            file_size: None,
+            ..source
+        };
+        assert!(FileMetadata::copied_from(&unknown).is_empty());
+        assert_eq!(copy.is_copied_from(&unknown), None);
+    }
+}
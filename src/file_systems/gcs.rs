@@ -1,46 +1,303 @@
+use crate::common_types::GcpPubSubSubscription;
+use crate::errors::AppError;
+use crate::file_systems::object_path::{url_with_path_segments, ObjectStoragePath};
+use crate::file_systems::resumable_download::{range_from_offset, resumable_download_stream};
+use crate::file_systems::upload_parts::UploadPartsReader;
 use crate::file_systems::{
-    AbsoluteFilePath, FileSystemConnection, FileSystemRef, ListFilesResult, RelativeFilePath,
+    AbsoluteFilePath, FileChecksum, FileMetadata, FileSystemConnection, FileSystemOptions,
+    FileSystemRef, FileSystemStream, ListFilesResult,
 };
 use crate::file_tools::{FileMatcher, FileMatcherResult};
 use crate::reporter::AppReporter;
 use crate::AppResult;
 use futures::{Stream, TryStreamExt};
+use gcloud_sdk::google::pubsub::v1::subscriber_client::SubscriberClient;
 use gcloud_sdk::prost::bytes;
+use gcloud_sdk::{GoogleApi, GoogleAuthMiddleware, TokenSourceType};
 use rvstruct::ValueStruct;
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
+use std::path::PathBuf;
+use std::time::Duration;
 
 pub struct GoogleCloudStorageFileSystem<'a> {
     google_rest_client: gcloud_sdk::GoogleRestApi,
-    bucket_name: String,
-    object_name: String,
-    is_dir: bool,
+    path: ObjectStoragePath,
+    pubsub_subscription: Option<GcpPubSubSubscription>,
+    pubsub_messages: PubSubMessages,
+    upload_part_size: usize,
+    kms_key: Option<String>,
+    storage_class: Option<String>,
+    endpoint_url: String,
+    service_account_key_file: Option<PathBuf>,
     reporter: &'a AppReporter<'a>,
 }
 
 impl<'a> GoogleCloudStorageFileSystem<'a> {
-    pub async fn new(path: &str, reporter: &'a AppReporter<'a>) -> AppResult<Self> {
-        let google_rest_client = gcloud_sdk::GoogleRestApi::new().await?;
-        let (bucket_name, object_name) = GoogleCloudStorageFileSystem::parse_gcs_path(path);
-        let is_dir = object_name.ends_with('/');
+    const PUBSUB_MAX_MESSAGES: i32 = 1000;
+    const PUBSUB_PULL_TIMEOUT: Duration = Duration::from_secs(10);
+    /// Maximum ack deadline of Pub/Sub, extended for pending messages while files are copied
+    const PUBSUB_ACK_DEADLINE_SECS: i32 = 600;
+    const PUBSUB_ACK_EXTENSION_INTERVAL: Duration = Duration::from_secs(60);
+    const DEFAULT_ENDPOINT_URL: &'static str = "https://storage.googleapis.com";
+    /// Emulators such as fake-gcs-server are used without credentials, the same way as Google SDKs do
+    const EMULATOR_HOST_ENV: &'static str = "STORAGE_EMULATOR_HOST";
+
+    pub async fn new(
+        path: &str,
+        reporter: &'a AppReporter<'a>,
+        options: &FileSystemOptions,
+    ) -> AppResult<Self> {
+        let emulator_host = std::env::var(Self::EMULATOR_HOST_ENV)
+            .ok()
+            .filter(|host| !host.is_empty());
+        let google_rest_client = gcloud_sdk::GoogleRestApi::with_token_source(
+            match emulator_host {
+                Some(_) => Self::emulator_token_source_type(),
+                None => Self::token_source_type(&options.gcs_service_account_key_file),
+            },
+            gcloud_sdk::GCP_DEFAULT_SCOPES.clone(),
+        )
+        .await?;
         Ok(GoogleCloudStorageFileSystem {
             google_rest_client,
-            bucket_name,
-            object_name,
-            is_dir,
+            path: ObjectStoragePath::parse("gs", path)?,
+            pubsub_subscription: options.gcs_pubsub_subscription.clone(),
+            pubsub_messages: PubSubMessages::default(),
+            upload_part_size: options.upload_part_size(),
+            kms_key: options.gcs_kms_key.clone(),
+            storage_class: options.gcs_storage_class.clone(),
+            endpoint_url: options
+                .gcs_endpoint_url
+                .as_ref()
+                .map(|url| url.as_str().trim_end_matches('/').to_string())
+                .or(emulator_host.map(|host| Self::emulator_endpoint_url(&host)))
+                .unwrap_or_else(|| Self::DEFAULT_ENDPOINT_URL.to_string()),
+            service_account_key_file: options.gcs_service_account_key_file.clone(),
             reporter,
         })
     }
 
-    fn parse_gcs_path(path: &str) -> (String, String) {
-        let path = path.trim_start_matches("gs://");
-        let parts: Vec<&str> = path.split('/').collect();
-        let bucket = parts[0];
-        if parts.len() == 1 || (parts.len() == 2 && parts[1].is_empty()) {
-            (bucket.to_string(), "/".to_string())
+    fn token_source_type(service_account_key_file: &Option<PathBuf>) -> TokenSourceType {
+        match service_account_key_file {
+            Some(key_file) => TokenSourceType::File(key_file.clone()),
+            None => TokenSourceType::Default,
+        }
+    }
+
+    fn emulator_token_source_type() -> TokenSourceType {
+        TokenSourceType::ExternalSource(Box::new(gcloud_sdk::ExternalJwtFunctionSource::new(
+            || async {
+                Ok(gcloud_sdk::Token::new(
+                    "Bearer".to_string(),
+                    "emulator".into(),
+                    chrono::Utc::now() + chrono::Duration::days(1),
+                ))
+            },
+        )))
+    }
+
+    /// Emulator hosts are specified with or without schemes such as `localhost:4443`
+    fn emulator_endpoint_url(host: &str) -> String {
+        let host = host.trim_end_matches('/');
+        if host.starts_with("http://") || host.starts_with("https://") {
+            host.to_string()
         } else {
-            let object = parts[1..].join("/");
-            (bucket.to_string(), object.to_string())
+            format!("http://{}", host)
+        }
+    }
+
+    async fn storage_config(
+        &self,
+    ) -> AppResult<gcloud_sdk::google_rest_apis::storage_v1::configuration::Configuration> {
+        let mut config = self
+            .google_rest_client
+            .create_google_storage_v1_config()
+            .await?;
+        config.base_path = format!("{}/storage/v1", self.endpoint_url);
+        Ok(config)
+    }
+
+    async fn create_pubsub_client(
+        &self,
+    ) -> AppResult<GoogleApi<SubscriberClient<GoogleAuthMiddleware>>> {
+        Ok(GoogleApi::from_function_with_token_source(
+            SubscriberClient::new,
+            "https://pubsub.googleapis.com",
+            None,
+            gcloud_sdk::GCP_DEFAULT_SCOPES.clone(),
+            Self::token_source_type(&self.service_account_key_file),
+        )
+        .await?)
+    }
+
+    async fn upload_simple(
+        &self,
+        object_name: &str,
+        content_type: Option<String>,
+        body: bytes::Bytes,
+    ) -> AppResult<()> {
+        let config = self.storage_config().await?;
+        let mut upload_req = config
+            .client
+            .post(format!(
+                "{}/upload/storage/v1/b/{}/o",
+                self.endpoint_url, self.path.bucket_name
+            ))
+            .query(&[("name", object_name)]);
+        if let Some(kms_key) = self.kms_key.as_ref() {
+            upload_req = upload_req.query(&[("kmsKeyName", kms_key)]);
+        }
+        if let Some(token) = config.oauth_access_token.as_ref() {
+            upload_req = upload_req.bearer_auth(token);
+        }
+        // Object metadata such as storage classes can be specified only in multipart uploads
+        if let Some(storage_class) = self.storage_class.as_ref() {
+            let boundary = format!("redacter-{:016x}", rand::random::<u64>());
+            upload_req = upload_req
+                .query(&[("uploadType", "multipart")])
+                .header(
+                    reqwest::header::CONTENT_TYPE,
+                    format!("multipart/related; boundary={}", boundary),
+                )
+                .body(multipart_related_body(
+                    &boundary,
+                    &serde_json::json!({ "storageClass": storage_class }),
+                    content_type.as_deref(),
+                    &body,
+                ));
+        } else {
+            upload_req = upload_req.query(&[("uploadType", "media")]);
+            if let Some(content_type) = content_type {
+                upload_req = upload_req.header(reqwest::header::CONTENT_TYPE, content_type);
+            }
+            upload_req = upload_req.body(body);
+        }
+        upload_req.send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// Objects of resumable uploads are created only when the last part is sent,
+    /// so readers never see partially uploaded objects and no temporary objects are needed
+    async fn upload_resumable<S: Stream<Item = AppResult<bytes::Bytes>> + Unpin>(
+        &self,
+        object_name: &str,
+        content_type: Option<String>,
+        first_parts: Vec<bytes::Bytes>,
+        reader: &mut UploadPartsReader<S>,
+    ) -> AppResult<()> {
+        let config = self.storage_config().await?;
+        let mut session_req = config
+            .client
+            .post(format!(
+                "{}/upload/storage/v1/b/{}/o",
+                self.endpoint_url, self.path.bucket_name
+            ))
+            .query(&[("uploadType", "resumable"), ("name", object_name)]);
+        if let Some(kms_key) = self.kms_key.as_ref() {
+            session_req = session_req.query(&[("kmsKeyName", kms_key)]);
+        }
+        session_req = match self.storage_class.as_ref() {
+            Some(storage_class) => session_req
+                .header(
+                    reqwest::header::CONTENT_TYPE,
+                    "application/json; charset=UTF-8",
+                )
+                .body(serde_json::to_vec(
+                    &serde_json::json!({ "storageClass": storage_class }),
+                )?),
+            None => session_req.header(reqwest::header::CONTENT_LENGTH, 0),
+        };
+        if let Some(token) = config.oauth_access_token.as_ref() {
+            session_req = session_req.bearer_auth(token);
+        }
+        if let Some(content_type) = content_type {
+            session_req = session_req.header("X-Upload-Content-Type", content_type);
+        }
+        let session_resp = session_req.send().await?.error_for_status()?;
+        let session_uri = session_resp
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| AppError::SystemError {
+                message: format!(
+                    "No session URI returned for GCS resumable upload: {}",
+                    object_name
+                ),
+            })?
+            .to_string();
+
+        match Self::upload_session_parts(&config.client, &session_uri, first_parts, reader).await {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                // Cancelling the session is best effort, the original error is more relevant
+                let _ = config.client.delete(&session_uri).send().await;
+                Err(err)
+            }
+        }
+    }
+
+    async fn upload_session_parts<S: Stream<Item = AppResult<bytes::Bytes>> + Unpin>(
+        client: &reqwest::Client,
+        session_uri: &str,
+        first_parts: Vec<bytes::Bytes>,
+        reader: &mut UploadPartsReader<S>,
+    ) -> AppResult<()> {
+        let mut pending_parts = first_parts.into_iter();
+        let mut current_part = pending_parts.next();
+        let mut offset = 0;
+        while let Some(part) = current_part {
+            let next_part = match pending_parts.next() {
+                Some(next_part) => Some(next_part),
+                None => reader.next_part().await?,
+            };
+            let part_end = offset + part.len();
+            // Total size is known only when the last part is sent
+            let total_size = if next_part.is_none() {
+                part_end.to_string()
+            } else {
+                "*".to_string()
+            };
+            client
+                .put(session_uri)
+                .header(
+                    reqwest::header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", offset, part_end - 1, total_size),
+                )
+                .body(part)
+                .send()
+                .await?
+                .error_for_status()?;
+            offset = part_end;
+            current_part = next_part;
+        }
+        Ok(())
+    }
+
+    /// Object names are percent-encoded as single path segments,
+    /// since the REST client encodes them as form values (such as spaces as `+`) in paths
+    fn object_url(&self, object_name: &str) -> AppResult<url::Url> {
+        url_with_path_segments(
+            &format!("{}/storage/v1/b/", self.endpoint_url),
+            &[self.path.bucket_name.as_str(), "o", object_name],
+        )
+    }
+
+    async fn get_object(
+        &self,
+        config: &gcloud_sdk::google_rest_apis::storage_v1::configuration::Configuration,
+        object_name: &str,
+    ) -> AppResult<Option<gcloud_sdk::google_rest_apis::storage_v1::Object>> {
+        let mut request = config.client.get(self.object_url(object_name)?);
+        if let Some(token) = config.oauth_access_token.as_ref() {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
         }
+        let content = response.error_for_status()?.bytes().await?;
+        Ok(Some(serde_json::from_slice(&content)?))
     }
 
     #[async_recursion::async_recursion]
@@ -55,12 +312,9 @@ impl<'a> GoogleCloudStorageFileSystem<'a> {
             return Ok(ListFilesResult::EMPTY);
         }
 
-        let config = self
-            .google_rest_client
-            .create_google_storage_v1_config()
-            .await?;
+        let config = self.storage_config().await?;
         let list_params = gcloud_sdk::google_rest_apis::storage_v1::objects_api::StoragePeriodObjectsPeriodListParams {
-            bucket: self.bucket_name.clone(),
+            bucket: self.path.bucket_name.clone(),
             prefix,
             page_token,
             ..gcloud_sdk::google_rest_apis::storage_v1::objects_api::StoragePeriodObjectsPeriodListParams::default()
@@ -78,9 +332,14 @@ impl<'a> GoogleCloudStorageFileSystem<'a> {
                     .filter(|item| item.name.iter().all(|key| !key.ends_with('/')))
                     .filter_map(|item| {
                         item.name.map(|name| FileSystemRef {
-                            relative_path: name.trim_start_matches(&self.object_name).into(),
+                            relative_path: self.path.relative_path_of(&name),
                             media_type: item.content_type.and_then(|v| v.parse().ok()),
                             file_size: item.size.and_then(|v| v.parse::<usize>().ok()),
+                            modified_at: item.updated.as_deref().and_then(parse_gcs_timestamp),
+                            checksum: FileChecksum::from_gcs_hashes(
+                                item.md5_hash.as_deref(),
+                                item.crc32c.as_deref(),
+                            ),
                         })
                     })
                     .collect();
@@ -123,6 +382,263 @@ impl<'a> GoogleCloudStorageFileSystem<'a> {
     }
 }
 
+/// Body of multipart uploads with object metadata followed by the content
+fn multipart_related_body(
+    boundary: &str,
+    metadata: &serde_json::Value,
+    content_type: Option<&str>,
+    content: &[u8],
+) -> bytes::Bytes {
+    let mut body = bytes::BytesMut::new();
+    body.extend_from_slice(
+        format!(
+            "--{boundary}\r\nContent-Type: application/json; charset=UTF-8\r\n\r\n{}\r\n--{boundary}\r\nContent-Type: {}\r\n\r\n",
+            metadata,
+            content_type.unwrap_or("application/octet-stream")
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(content);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    body.freeze()
+}
+
+fn parse_gcs_timestamp(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|v| v.with_timezone(&chrono::Utc))
+}
+
+/// Ack IDs of received Pub/Sub messages by their state
+#[derive(Default)]
+struct PubSubMessages {
+    /// Messages of listed files, acknowledged only if the files are copied successfully
+    pending: HashMap<String, Vec<String>>,
+    /// Messages of copied files and messages which aren't notifications of new objects
+    acknowledged: Vec<String>,
+    /// Messages of failed files and files excluded by filters, delivered again
+    not_acknowledged: Vec<String>,
+    deadline_extended_at: Option<std::time::Instant>,
+}
+
+impl PubSubMessages {
+    fn complete(&mut self, relative_path: &str, succeeded: bool) {
+        if let Some(ack_ids) = self.pending.remove(relative_path) {
+            if succeeded {
+                self.acknowledged.extend(ack_ids);
+            } else {
+                self.not_acknowledged.extend(ack_ids);
+            }
+        }
+    }
+
+    /// Messages which aren't acknowledged, including messages of files which weren't completed
+    fn into_not_acknowledged(self) -> Vec<String> {
+        self.not_acknowledged
+            .into_iter()
+            .chain(self.pending.into_values().flatten())
+            .collect()
+    }
+}
+
+/// Cloud Storage notification attributes and payload
+/// (https://cloud.google.com/storage/docs/pubsub-notifications)
+struct GcsNotification {
+    event_type: String,
+    bucket_id: String,
+    object_id: String,
+    object: Option<gcloud_sdk::google_rest_apis::storage_v1::Object>,
+}
+
+impl GcsNotification {
+    fn from_message(message: gcloud_sdk::google::pubsub::v1::PubsubMessage) -> Option<Self> {
+        Some(GcsNotification {
+            event_type: message.attributes.get("eventType")?.clone(),
+            bucket_id: message.attributes.get("bucketId")?.clone(),
+            object_id: message.attributes.get("objectId")?.clone(),
+            object: serde_json::from_slice(&message.data).ok(),
+        })
+    }
+}
+
+impl<'a> GoogleCloudStorageFileSystem<'a> {
+    /// Only new objects from notifications are listed. Messages of listed files are acknowledged
+    /// on close only if the files were copied successfully, so failed, filtered and interrupted
+    /// files are delivered again.
+    async fn list_files_from_pubsub(
+        &mut self,
+        subscription: &GcpPubSubSubscription,
+        file_matcher: &Option<&FileMatcher>,
+        max_files_limit: Option<usize>,
+    ) -> AppResult<ListFilesResult> {
+        let client = self.create_pubsub_client().await?;
+        let prefix = self.path.prefix().unwrap_or_default().to_string();
+        let mut files: Vec<FileSystemRef> = Vec::new();
+        let mut found_object_ids: HashSet<String> = HashSet::new();
+        let mut skipped: usize = 0;
+
+        while max_files_limit.iter().all(|limit| files.len() < *limit) {
+            let pull_request = gcloud_sdk::google::pubsub::v1::PullRequest {
+                subscription: subscription.value().clone(),
+                max_messages: Self::PUBSUB_MAX_MESSAGES,
+                ..Default::default()
+            };
+            let received_messages = match tokio::time::timeout(
+                Self::PUBSUB_PULL_TIMEOUT,
+                client.get().pull(pull_request),
+            )
+            .await
+            {
+                Ok(response) => response?.into_inner().received_messages,
+                Err(_) => Vec::new(),
+            };
+            if received_messages.is_empty() {
+                break;
+            }
+
+            for received_message in received_messages {
+                let ack_id = received_message.ack_id;
+                let notification = match received_message
+                    .message
+                    .and_then(GcsNotification::from_message)
+                {
+                    Some(notification)
+                        if notification.event_type == "OBJECT_FINALIZE"
+                            && notification.bucket_id == self.path.bucket_name
+                            && notification.object_id.starts_with(&prefix)
+                            && !notification.object_id.ends_with('/') =>
+                    {
+                        notification
+                    }
+                    // Other events and objects are never copied from the source
+                    _ => {
+                        self.pubsub_messages.acknowledged.push(ack_id);
+                        continue;
+                    }
+                };
+                let relative_path = self.path.relative_path_of(&notification.object_id);
+                if !found_object_ids.insert(notification.object_id.clone()) {
+                    // Repeated notifications of the same object share the state of the first one
+                    match self.pubsub_messages.pending.get_mut(relative_path.value()) {
+                        Some(ack_ids) => ack_ids.push(ack_id),
+                        None => self.pubsub_messages.not_acknowledged.push(ack_id),
+                    }
+                    continue;
+                }
+                let object = notification.object.unwrap_or_default();
+                let file_ref = FileSystemRef {
+                    media_type: object
+                        .content_type
+                        .and_then(|v| v.parse().ok())
+                        .or_else(|| mime_guess::from_path(relative_path.value()).first()),
+                    relative_path,
+                    file_size: object.size.and_then(|v| v.parse::<usize>().ok()),
+                    modified_at: object.updated.as_deref().and_then(parse_gcs_timestamp),
+                    checksum: FileChecksum::from_gcs_hashes(
+                        object.md5_hash.as_deref(),
+                        object.crc32c.as_deref(),
+                    ),
+                };
+                if file_matcher
+                    .iter()
+                    .all(|matcher| matches!(matcher.matches(&file_ref), FileMatcherResult::Matched))
+                    && max_files_limit.iter().all(|limit| files.len() < *limit)
+                {
+                    self.pubsub_messages
+                        .pending
+                        .insert(file_ref.relative_path.value().clone(), vec![ack_id]);
+                    files.push(file_ref);
+                } else {
+                    self.pubsub_messages.not_acknowledged.push(ack_id);
+                    skipped += 1;
+                }
+            }
+            self.extend_pubsub_ack_deadlines(subscription, true).await?;
+        }
+        Ok(ListFilesResult { files, skipped })
+    }
+
+    /// Pending messages would be delivered again after the ack deadline of the subscription
+    /// (10 seconds by default), so their deadlines are extended while files are copied
+    async fn extend_pubsub_ack_deadlines(
+        &mut self,
+        subscription: &GcpPubSubSubscription,
+        force: bool,
+    ) -> AppResult<()> {
+        if !force
+            && self
+                .pubsub_messages
+                .deadline_extended_at
+                .is_some_and(|extended_at| {
+                    extended_at.elapsed() < Self::PUBSUB_ACK_EXTENSION_INTERVAL
+                })
+        {
+            return Ok(());
+        }
+        let ack_ids: Vec<String> = self
+            .pubsub_messages
+            .pending
+            .values()
+            .flatten()
+            .cloned()
+            .collect();
+        self.modify_pubsub_ack_deadlines(subscription, &ack_ids, Self::PUBSUB_ACK_DEADLINE_SECS)
+            .await?;
+        self.pubsub_messages.deadline_extended_at = Some(std::time::Instant::now());
+        Ok(())
+    }
+
+    async fn modify_pubsub_ack_deadlines(
+        &self,
+        subscription: &GcpPubSubSubscription,
+        ack_ids: &[String],
+        ack_deadline_seconds: i32,
+    ) -> AppResult<()> {
+        if ack_ids.is_empty() {
+            return Ok(());
+        }
+        let client = self.create_pubsub_client().await?;
+        for ack_ids in ack_ids.chunks(Self::PUBSUB_MAX_MESSAGES as usize) {
+            client
+                .get()
+                .modify_ack_deadline(gcloud_sdk::google::pubsub::v1::ModifyAckDeadlineRequest {
+                    subscription: subscription.value().clone(),
+                    ack_ids: ack_ids.to_vec(),
+                    ack_deadline_seconds,
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Acknowledges messages of copied files and sends negative acknowledgements for
+    /// messages of failed files and files which weren't copied, so they are delivered again
+    async fn acknowledge_pubsub_messages(
+        &mut self,
+        subscription: &GcpPubSubSubscription,
+    ) -> AppResult<()> {
+        let messages = std::mem::take(&mut self.pubsub_messages);
+        if !messages.acknowledged.is_empty() {
+            let client = self.create_pubsub_client().await?;
+            for ack_ids in messages
+                .acknowledged
+                .chunks(Self::PUBSUB_MAX_MESSAGES as usize)
+            {
+                client
+                    .get()
+                    .acknowledge(gcloud_sdk::google::pubsub::v1::AcknowledgeRequest {
+                        subscription: subscription.value().clone(),
+                        ack_ids: ack_ids.to_vec(),
+                    })
+                    .await?;
+            }
+        }
+        let not_acknowledged = messages.into_not_acknowledged();
+        self.modify_pubsub_ack_deadlines(subscription, &not_acknowledged, 0)
+            .await
+    }
+}
+
 impl<'a> FileSystemConnection<'a> for GoogleCloudStorageFileSystem<'a> {
     async fn download(
         &mut self,
@@ -131,35 +647,24 @@ impl<'a> FileSystemConnection<'a> for GoogleCloudStorageFileSystem<'a> {
         FileSystemRef,
         Box<dyn Stream<Item = AppResult<bytes::Bytes>> + Send + Sync + Unpin + 'static>,
     )> {
-        let config = self
-            .google_rest_client
-            .create_google_storage_v1_config()
-            .await?;
+        if let Some(subscription) = self.pubsub_subscription.clone() {
+            self.extend_pubsub_ack_deadlines(&subscription, false)
+                .await?;
+        }
+        let config = self.storage_config().await?;
 
         let object_name = self.resolve(file_ref).file_path;
 
-        let object = gcloud_sdk::google_rest_apis::storage_v1::objects_api::storage_objects_get(
-            &config,
-            gcloud_sdk::google_rest_apis::storage_v1::objects_api::StoragePeriodObjectsPeriodGetParams {
-                bucket: self.bucket_name.clone(),
-                object: object_name.clone(),
-                ..gcloud_sdk::google_rest_apis::storage_v1::objects_api::StoragePeriodObjectsPeriodGetParams::default()
-            },
-        ).await?;
+        let object = self
+            .get_object(&config, &object_name)
+            .await?
+            .ok_or_else(|| AppError::FileSystemError {
+                scheme: "gs".to_string(),
+                path: format!("gs://{}/{}", self.path.bucket_name, object_name),
+                message: "Object not found".to_string(),
+            })?;
 
-        let relative_path: RelativeFilePath = if self.is_dir {
-            object_name
-                .clone()
-                .trim_start_matches(&self.object_name)
-                .into()
-        } else {
-            object_name
-                .split('/')
-                .last()
-                .map(|file_name| file_name.to_string())
-                .unwrap_or_else(|| object_name.clone())
-                .into()
-        };
+        let relative_path = self.path.relative_path_of(&object_name);
 
         let found_file_ref = FileSystemRef {
             relative_path: relative_path.clone(),
@@ -169,20 +674,42 @@ impl<'a> FileSystemConnection<'a> for GoogleCloudStorageFileSystem<'a> {
                 .transpose()?
                 .or_else(|| mime_guess::from_path(relative_path.value()).first()),
             file_size: object.size.and_then(|v| v.parse::<usize>().ok()),
+            modified_at: object.updated.as_deref().and_then(parse_gcs_timestamp),
+            checksum: FileChecksum::from_gcs_hashes(
+                object.md5_hash.as_deref(),
+                object.crc32c.as_deref(),
+            ),
         };
 
-        let stream = gcloud_sdk::google_rest_apis::storage_v1::objects_api::storage_objects_get_stream(
-            &config,
-            gcloud_sdk::google_rest_apis::storage_v1::objects_api::StoragePeriodObjectsPeriodGetParams {
-                bucket: self.bucket_name.clone(),
-                object: object_name.clone(),
-                ..gcloud_sdk::google_rest_apis::storage_v1::objects_api::StoragePeriodObjectsPeriodGetParams::default()
+        let client = config.client.clone();
+        let access_token = config.oauth_access_token.clone();
+        let object_url = self.object_url(&object_name)?;
+        // Content and resumed ranges must belong to the same generation of the object
+        let generation = object.generation;
+        let media_request = move || {
+            let mut request = client.get(object_url.clone()).query(&[("alt", "media")]);
+            if let Some(generation) = generation.as_ref() {
+                request = request.query(&[("generation", generation)]);
+            }
+            if let Some(token) = access_token.as_ref() {
+                request = request.bearer_auth(token);
             }
-        ).await?;
-        Ok((
-            found_file_ref,
-            Box::new(stream.map_err(|err| gcloud_sdk::error::Error::from(err).into())),
-        ))
+            request
+        };
+        let response = media_request().send().await?.error_for_status()?;
+        let resumable_stream = resumable_download_stream(
+            Box::new(response.bytes_stream().map_err(AppError::from)),
+            move |offset| {
+                let request =
+                    media_request().header(reqwest::header::RANGE, range_from_offset(offset));
+                async move {
+                    let response = request.send().await?.error_for_status()?;
+                    Ok(Box::new(response.bytes_stream().map_err(AppError::from))
+                        as FileSystemStream)
+                }
+            },
+        );
+        Ok((found_file_ref, resumable_stream))
     }
 
     async fn upload<S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static>(
@@ -191,27 +718,23 @@ impl<'a> FileSystemConnection<'a> for GoogleCloudStorageFileSystem<'a> {
         file_ref: Option<&FileSystemRef>,
     ) -> AppResult<()> {
         let object_name = self.resolve(file_ref).file_path;
-
-        let config = self
-            .google_rest_client
-            .create_google_storage_v1_config()
-            .await?;
         let content_type = file_ref
             .and_then(|fr| fr.media_type.as_ref())
             .map(|v| v.to_string());
-        let reader = sync_wrapper::SyncStream::new(input);
-        let params =gcloud_sdk::google_rest_apis::storage_v1::objects_api::StoragePeriodObjectsPeriodInsertParams {
-            bucket: self.bucket_name.clone(),
-            name: Some(object_name),
-            ..gcloud_sdk::google_rest_apis::storage_v1::objects_api::StoragePeriodObjectsPeriodInsertParams::default()
-        };
-        let _ = gcloud_sdk::google_rest_apis::storage_v1::objects_api::storage_objects_insert_ext_stream(
-            &config,
-            params,
-            content_type,
-            reader
-        ).await?;
-        Ok(())
+        let mut reader = UploadPartsReader::new(input, self.upload_part_size);
+        let first_part = reader.next_part().await?;
+        if let Some(second_part) = reader.next_part().await? {
+            return self
+                .upload_resumable(
+                    &object_name,
+                    content_type,
+                    first_part.into_iter().chain(Some(second_part)).collect(),
+                    &mut reader,
+                )
+                .await;
+        }
+        self.upload_simple(&object_name, content_type, first_part.unwrap_or_default())
+            .await
     }
 
     async fn list_files(
@@ -219,16 +742,26 @@ impl<'a> FileSystemConnection<'a> for GoogleCloudStorageFileSystem<'a> {
         file_matcher: Option<&FileMatcher>,
         max_files_limit: Option<usize>,
     ) -> AppResult<ListFilesResult> {
+        if let Some(subscription) = self.pubsub_subscription.clone() {
+            self.reporter.report(format!(
+                "Receiving new files in bucket: {} with prefix: {} from Pub/Sub subscription: {}",
+                self.path.bucket_name,
+                self.path.object_name,
+                subscription.value()
+            ))?;
+            return if self.path.is_dir() {
+                self.list_files_from_pubsub(&subscription, &file_matcher, max_files_limit)
+                    .await
+            } else {
+                Ok(ListFilesResult::EMPTY)
+            };
+        }
         self.reporter.report(format!(
             "Listing files in bucket: {} with prefix: {}",
-            self.bucket_name, self.object_name
+            self.path.bucket_name, self.path.object_name
         ))?;
-        if self.object_name.ends_with('/') {
-            let prefix = if self.object_name != "/" {
-                Some(self.object_name.clone())
-            } else {
-                None
-            };
+        if self.path.is_dir() {
+            let prefix = self.path.prefix().map(|prefix| prefix.to_string());
             self.list_files_with_token(prefix, None, &file_matcher, max_files_limit)
                 .await
         } else {
@@ -236,36 +769,183 @@ impl<'a> FileSystemConnection<'a> for GoogleCloudStorageFileSystem<'a> {
         }
     }
 
-    async fn close(self) -> AppResult<()> {
+    async fn get_file_ref(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<Option<FileSystemRef>> {
+        let config = self.storage_config().await?;
+
+        let object_name = self.resolve(file_ref).file_path;
+
+        Ok(self
+            .get_object(&config, &object_name)
+            .await?
+            .map(|object| FileSystemRef {
+                relative_path: file_ref
+                    .map(|fr| fr.relative_path.clone())
+                    .unwrap_or_else(|| object_name.clone().into()),
+                media_type: object.content_type.and_then(|v| v.parse().ok()),
+                file_size: object.size.and_then(|v| v.parse::<usize>().ok()),
+                modified_at: object.updated.as_deref().and_then(parse_gcs_timestamp),
+                checksum: FileChecksum::from_gcs_hashes(
+                    object.md5_hash.as_deref(),
+                    object.crc32c.as_deref(),
+                ),
+            }))
+    }
+
+    async fn get_metadata(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<Option<FileMetadata>> {
+        let config = self.storage_config().await?;
+        let object_name = self.resolve(file_ref).file_path;
+        Ok(self
+            .get_object(&config, &object_name)
+            .await?
+            .map(|object| FileMetadata {
+                custom: object.metadata.unwrap_or_default().into_iter().collect(),
+                ..FileMetadata::default()
+            }))
+    }
+
+    async fn set_metadata(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+        metadata: &FileMetadata,
+    ) -> AppResult<()> {
+        if metadata.custom.is_empty() {
+            return Ok(());
+        }
+        let config = self.storage_config().await?;
+        let object_name = self.resolve(file_ref).file_path;
+        let mut patch_req = config
+            .client
+            .patch(self.object_url(&object_name)?)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&serde_json::json!({
+                "metadata": metadata.custom
+            }))?);
+        if let Some(token) = config.oauth_access_token.as_ref() {
+            patch_req = patch_req.bearer_auth(token);
+        }
+        patch_req.send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    fn supports_file_operations(&self) -> bool {
+        true
+    }
+
+    /// Objects are rewritten instead of copied, since copies fail for objects encrypted with different keys.
+    /// Large objects are rewritten in multiple calls using rewrite tokens
+    async fn copy_file(&mut self, from: &FileSystemRef, to: &FileSystemRef) -> AppResult<()> {
+        let config = self.storage_config().await?;
+        let rewrite_url = url_with_path_segments(
+            &format!("{}/storage/v1/b/", self.endpoint_url),
+            &[
+                self.path.bucket_name.as_str(),
+                "o",
+                self.resolve(Some(from)).file_path.as_str(),
+                "rewriteTo",
+                "b",
+                self.path.bucket_name.as_str(),
+                "o",
+                self.resolve(Some(to)).file_path.as_str(),
+            ],
+        )?;
+        let destination_metadata = match self.storage_class.as_ref() {
+            Some(storage_class) => serde_json::json!({ "storageClass": storage_class }),
+            None => serde_json::json!({}),
+        };
+        let mut rewrite_token: Option<String> = None;
+        loop {
+            let mut rewrite_req = config
+                .client
+                .post(rewrite_url.clone())
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(&destination_metadata)?);
+            if let Some(kms_key) = self.kms_key.as_ref() {
+                rewrite_req = rewrite_req.query(&[("destinationKmsKeyName", kms_key)]);
+            }
+            if let Some(token) = rewrite_token.as_ref() {
+                rewrite_req = rewrite_req.query(&[("rewriteToken", token)]);
+            }
+            if let Some(token) = config.oauth_access_token.as_ref() {
+                rewrite_req = rewrite_req.bearer_auth(token);
+            }
+            let response: serde_json::Value = serde_json::from_slice(
+                &rewrite_req
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .bytes()
+                    .await?,
+            )?;
+            if response["done"].as_bool().unwrap_or(false) {
+                return Ok(());
+            }
+            rewrite_token = Some(
+                response["rewriteToken"]
+                    .as_str()
+                    .ok_or_else(|| AppError::SystemError {
+                        message: "GCS rewrite isn't done and has no rewrite token".to_string(),
+                    })?
+                    .to_string(),
+            );
+        }
+    }
+
+    async fn move_file(&mut self, from: &FileSystemRef, to: &FileSystemRef) -> AppResult<()> {
+        self.copy_file(from, to).await?;
+        self.delete_file(from).await
+    }
+
+    async fn delete_file(&mut self, file_ref: &FileSystemRef) -> AppResult<()> {
+        let config = self.storage_config().await?;
+        let object_name = self.resolve(Some(file_ref)).file_path;
+        let mut delete_req = config.client.delete(self.object_url(&object_name)?);
+        if let Some(token) = config.oauth_access_token.as_ref() {
+            delete_req = delete_req.bearer_auth(token);
+        }
+        delete_req.send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    async fn complete_file(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+        succeeded: bool,
+    ) -> AppResult<()> {
+        if let Some(file_ref) = file_ref {
+            self.pubsub_messages
+                .complete(file_ref.relative_path.value(), succeeded);
+        }
+        if let Some(subscription) = self.pubsub_subscription.clone() {
+            self.extend_pubsub_ack_deadlines(&subscription, false)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn close(mut self) -> AppResult<()> {
+        if let Some(subscription) = self.pubsub_subscription.clone() {
+            self.acknowledge_pubsub_messages(&subscription).await?;
+        }
         Ok(())
     }
 
     async fn has_multiple_files(&self) -> AppResult<bool> {
-        Ok(self.is_dir)
+        Ok(self.path.is_dir())
     }
 
     async fn accepts_multiple_files(&self) -> AppResult<bool> {
-        Ok(self.is_dir)
+        Ok(self.path.is_dir())
     }
 
     fn resolve(&self, file_ref: Option<&FileSystemRef>) -> AbsoluteFilePath {
         AbsoluteFilePath {
-            file_path: if self.is_dir {
-                let object_name_prefix = if self.object_name == "/" {
-                    ""
-                } else {
-                    self.object_name.as_str()
-                };
-                format!(
-                    "{}{}",
-                    object_name_prefix,
-                    file_ref
-                        .map(|fr| fr.relative_path.value().clone())
-                        .unwrap_or_default()
-                )
-            } else {
-                self.object_name.clone()
-            },
+            file_path: self.path.resolve(file_ref.map(|fr| &fr.relative_path)),
         }
     }
 }
@@ -275,6 +955,36 @@ mod tests {
     use super::*;
     use crate::reporter::AppReporter;
 
+    #[test]
+    fn pubsub_messages_complete_test() {
+        let mut messages = PubSubMessages::default();
+        messages.acknowledged.push("other-event".to_string());
+        messages.not_acknowledged.push("filtered".to_string());
+        messages.pending.insert(
+            "copied.txt".to_string(),
+            vec!["copied-1".to_string(), "copied-2".to_string()],
+        );
+        messages
+            .pending
+            .insert("failed.txt".to_string(), vec!["failed".to_string()]);
+        messages.pending.insert(
+            "interrupted.txt".to_string(),
+            vec!["interrupted".to_string()],
+        );
+
+        messages.complete("copied.txt", true);
+        messages.complete("failed.txt", false);
+        messages.complete("unknown.txt", true);
+
+        assert_eq!(
+            messages.acknowledged,
+            vec!["other-event", "copied-1", "copied-2"]
+        );
+        let mut not_acknowledged = messages.into_not_acknowledged();
+        not_acknowledged.sort();
+        assert_eq!(not_acknowledged, vec!["failed", "filtered", "interrupted"]);
+    }
+
     #[tokio::test]
     #[cfg_attr(not(feature = "ci-gcp"), ignore)]
     async fn upload_download_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
@@ -286,6 +996,7 @@ mod tests {
         let mut fs = GoogleCloudStorageFileSystem::new(
             &format!("gs://{}/redacter/test-upload/", test_gcp_bucket_name),
             &reporter,
+            &FileSystemOptions::default(),
         )
         .await?;
 
@@ -297,6 +1008,8 @@ mod tests {
                 relative_path: "test-upload.txt".into(),
                 media_type: Some(mime::TEXT_PLAIN),
                 file_size: Some(test_data.len()),
+                modified_at: None,
+                checksum: None,
             }),
         )
         .await?;
@@ -306,6 +1019,8 @@ mod tests {
                 relative_path: "test-upload.txt".into(),
                 media_type: Some(mime::TEXT_PLAIN),
                 file_size: Some(test_data.len()),
+                modified_at: None,
+                checksum: None,
             }))
             .await?;
 
@@ -334,6 +1049,7 @@ mod tests {
         let mut fs = GoogleCloudStorageFileSystem::new(
             &format!("gs://{}/redacter/test-list/", test_gcp_bucket_name),
             &reporter,
+            &FileSystemOptions::default(),
         )
         .await?;
 
@@ -345,6 +1061,8 @@ mod tests {
                 relative_path: "test-upload.txt".into(),
                 media_type: Some(mime::TEXT_PLAIN),
                 file_size: Some(test_data.len()),
+                modified_at: None,
+                checksum: None,
             }),
         )
         .await?;
@@ -360,4 +1078,20 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn multipart_related_body_test() {
+        let body = multipart_related_body(
+            "test-boundary",
+            &serde_json::json!({ "storageClass": "NEARLINE" }),
+            Some("text/plain"),
+            b"test content",
+        );
+        assert_eq!(
+            body,
+            bytes::Bytes::from(
+                "--test-boundary\r\nContent-Type: application/json; charset=UTF-8\r\n\r\n{\"storageClass\":\"NEARLINE\"}\r\n--test-boundary\r\nContent-Type: text/plain\r\n\r\ntest content\r\n--test-boundary--\r\n"
+            )
+        );
+    }
 }
@@ -0,0 +1,453 @@
+use crate::errors::AppError;
+use crate::file_systems::{
+    AbsoluteFilePath, FileSystemConnection, FileSystemOptions, FileSystemRef, KafkaOffsetReset,
+    ListFilesResult,
+};
+use crate::file_tools::{FileMatcher, FileMatcherResult};
+use crate::reporter::AppReporter;
+use crate::AppResult;
+use bytes::Bytes;
+use futures::{Stream, TryStreamExt};
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::message::{Message, OwnedMessage};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::{ClientConfig, Offset, TopicPartitionList};
+use rvstruct::ValueStruct;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+/// Kafka topic as a file system where every message is a file.
+/// Consumed offsets are committed on close only up to the first message which wasn't copied
+/// successfully in every partition, so failed messages and messages which weren't copied
+/// are received again in the next runs, even if later messages were copied.
+pub struct KafkaFileSystem<'a> {
+    brokers: String,
+    topic: String,
+    group_id: String,
+    offset_reset: KafkaOffsetReset,
+    batch_size: usize,
+    consumer: Option<StreamConsumer>,
+    producer: Option<FutureProducer>,
+    received_messages: HashMap<String, OwnedMessage>,
+    /// Offsets of received messages per partition, completed if messages are copied successfully
+    /// or filtered out, since offsets are committed as a contiguous prefix
+    received_offsets: HashMap<i32, BTreeMap<i64, bool>>,
+    /// Message received without listing to copy a single file
+    single_message: Option<(i32, i64)>,
+    reporter: &'a AppReporter<'a>,
+}
+
+impl<'a> KafkaFileSystem<'a> {
+    const DEFAULT_GROUP_ID: &'static str = "redacter";
+    const DEFAULT_BATCH_SIZE: usize = 100;
+    const POLL_TIMEOUT: Duration = Duration::from_secs(5);
+    const PRODUCE_TIMEOUT: Duration = Duration::from_secs(30);
+
+    pub async fn new(
+        path: &str,
+        reporter: &'a AppReporter<'a>,
+        options: &FileSystemOptions,
+    ) -> AppResult<Self> {
+        let (brokers, topic) = Self::parse_kafka_path(path)?;
+        Ok(KafkaFileSystem {
+            brokers,
+            topic,
+            group_id: options
+                .kafka_group_id
+                .clone()
+                .unwrap_or_else(|| Self::DEFAULT_GROUP_ID.to_string()),
+            offset_reset: options
+                .kafka_offset_reset
+                .unwrap_or(KafkaOffsetReset::Earliest),
+            batch_size: options.kafka_batch_size.unwrap_or(Self::DEFAULT_BATCH_SIZE),
+            consumer: None,
+            producer: None,
+            received_messages: HashMap::new(),
+            received_offsets: HashMap::new(),
+            single_message: None,
+            reporter,
+        })
+    }
+
+    fn parse_kafka_path(path: &str) -> AppResult<(String, String)> {
+        match path
+            .trim_start_matches("kafka://")
+            .trim_end_matches('/')
+            .split_once('/')
+        {
+            Some((brokers, topic))
+                if !brokers.is_empty() && !topic.is_empty() && !topic.contains('/') =>
+            {
+                Ok((brokers.to_string(), topic.to_string()))
+            }
+            _ => Err(AppError::FileSystemError {
+                scheme: "kafka".to_string(),
+                path: path.to_string(),
+                message: "Invalid path. Expected kafka://broker1:9092,broker2:9092/topic"
+                    .to_string(),
+            }),
+        }
+    }
+
+    fn consumer(&mut self) -> AppResult<&StreamConsumer> {
+        let consumer = match self.consumer.take() {
+            Some(consumer) => consumer,
+            None => {
+                let consumer: StreamConsumer = ClientConfig::new()
+                    .set("bootstrap.servers", &self.brokers)
+                    .set("group.id", &self.group_id)
+                    .set("enable.auto.commit", "false")
+                    .set("auto.offset.reset", self.offset_reset.as_config_value())
+                    .create()?;
+                consumer.subscribe(&[self.topic.as_str()])?;
+                consumer
+            }
+        };
+        Ok(self.consumer.insert(consumer))
+    }
+
+    fn producer(&mut self) -> AppResult<&FutureProducer> {
+        let producer = match self.producer.take() {
+            Some(producer) => producer,
+            None => ClientConfig::new()
+                .set("bootstrap.servers", &self.brokers)
+                .create()?,
+        };
+        Ok(self.producer.insert(producer))
+    }
+
+    /// Partition and offset of messages from their file names such as `0-42.json`
+    fn message_offset(relative_path: &str) -> Option<(i32, i64)> {
+        let (partition, offset) = relative_path.rsplit_once('.')?.0.split_once('-')?;
+        Some((partition.parse().ok()?, offset.parse().ok()?))
+    }
+
+    fn receive_offset(&mut self, message: &OwnedMessage, completed: bool) {
+        self.received_offsets
+            .entry(message.partition())
+            .or_default()
+            .insert(message.offset(), completed);
+    }
+
+    /// Offsets to commit per partition: next offsets after the contiguous prefix of completed messages
+    fn committable_offsets(
+        received_offsets: &HashMap<i32, BTreeMap<i64, bool>>,
+    ) -> Vec<(i32, i64)> {
+        let mut committable_offsets: Vec<(i32, i64)> = received_offsets
+            .iter()
+            .filter_map(|(partition, offsets)| {
+                offsets
+                    .iter()
+                    .take_while(|(_, completed)| **completed)
+                    .last()
+                    .map(|(offset, _)| (*partition, *offset + 1))
+            })
+            .collect();
+        committable_offsets.sort();
+        committable_offsets
+    }
+
+    fn message_file_ref(message: &OwnedMessage) -> FileSystemRef {
+        let payload = message.payload().unwrap_or_default();
+        let is_json = serde_json::from_slice::<serde_json::Value>(payload).is_ok();
+        FileSystemRef {
+            relative_path: format!(
+                "{}-{}.{}",
+                message.partition(),
+                message.offset(),
+                if is_json { "json" } else { "txt" }
+            )
+            .into(),
+            media_type: Some(if is_json {
+                mime::APPLICATION_JSON
+            } else {
+                mime::TEXT_PLAIN
+            }),
+            file_size: Some(payload.len()),
+            modified_at: message
+                .timestamp()
+                .to_millis()
+                .and_then(chrono::DateTime::from_timestamp_millis),
+            checksum: None,
+        }
+    }
+
+    async fn receive_message(&mut self) -> AppResult<Option<OwnedMessage>> {
+        let consumer = self.consumer()?;
+        match tokio::time::timeout(Self::POLL_TIMEOUT, consumer.recv()).await {
+            Ok(received) => Ok(Some(received?.detach())),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl<'a> FileSystemConnection<'a> for KafkaFileSystem<'a> {
+    async fn download(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<(
+        FileSystemRef,
+        Box<dyn Stream<Item = AppResult<Bytes>> + Send + Sync + Unpin + 'static>,
+    )> {
+        let message = match file_ref {
+            // Messages are kept until they are completed, so failed files can be retried
+            Some(file_ref) => self
+                .received_messages
+                .get(file_ref.relative_path.value())
+                .cloned()
+                .ok_or_else(|| AppError::SystemError {
+                    message: format!(
+                        "Kafka message {} wasn't received from topic {}",
+                        file_ref.relative_path.value(),
+                        self.topic
+                    ),
+                })?,
+            None => {
+                let message =
+                    self.receive_message()
+                        .await?
+                        .ok_or_else(|| AppError::SystemError {
+                            message: format!(
+                                "No messages received from Kafka topic {}",
+                                self.topic
+                            ),
+                        })?;
+                self.receive_offset(&message, false);
+                self.single_message = Some((message.partition(), message.offset()));
+                message
+            }
+        };
+
+        let found_file_ref = Self::message_file_ref(&message);
+        let payload = Bytes::from(message.payload().unwrap_or_default().to_vec());
+        Ok((
+            found_file_ref,
+            Box::new(futures::stream::iter(vec![Ok(payload)])),
+        ))
+    }
+
+    async fn upload<S: Stream<Item = AppResult<Bytes>> + Send + Unpin + Sync + 'static>(
+        &mut self,
+        input: S,
+        file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<()> {
+        let all_chunks: Vec<Bytes> = input.try_collect().await?;
+        let payload = all_chunks.concat();
+        let key = file_ref
+            .map(|fr| fr.relative_path.value().clone())
+            .unwrap_or_default();
+        let topic = self.topic.clone();
+        let producer = self.producer()?;
+        producer
+            .send(
+                FutureRecord::to(&topic).payload(&payload).key(&key),
+                Self::PRODUCE_TIMEOUT,
+            )
+            .await
+            .map_err(|(err, _)| AppError::from(err))?;
+        Ok(())
+    }
+
+    async fn list_files(
+        &mut self,
+        file_matcher: Option<&FileMatcher>,
+        max_files_limit: Option<usize>,
+    ) -> AppResult<ListFilesResult> {
+        self.reporter.report(format!(
+            "Receiving messages from Kafka topic: {} with consumer group: {}",
+            self.topic, self.group_id
+        ))?;
+        let batch_size = max_files_limit
+            .map(|limit| limit.min(self.batch_size))
+            .unwrap_or(self.batch_size);
+        let mut files: Vec<FileSystemRef> = Vec::new();
+        let mut skipped: usize = 0;
+        while files.len() < batch_size {
+            match self.receive_message().await? {
+                Some(message) => {
+                    let file_ref = Self::message_file_ref(&message);
+                    match file_matcher.map(|matcher| matcher.matches(&file_ref)) {
+                        None | Some(FileMatcherResult::Matched) => {
+                            self.receive_offset(&message, false);
+                            self.received_messages
+                                .insert(file_ref.relative_path.value().clone(), message);
+                            files.push(file_ref);
+                        }
+                        Some(_) => {
+                            // Offsets of filtered messages are committed, otherwise they would block partitions
+                            self.receive_offset(&message, true);
+                            skipped += 1
+                        }
+                    }
+                }
+                None => break,
+            }
+        }
+        Ok(ListFilesResult { files, skipped })
+    }
+
+    async fn get_file_ref(
+        &mut self,
+        _file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<Option<FileSystemRef>> {
+        Ok(None)
+    }
+
+    /// Downloaded messages are committed on close, so messages are downloaded only when they are copied
+    fn supports_prefetch(&self) -> bool {
+        false
+    }
+
+    async fn complete_file(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+        succeeded: bool,
+    ) -> AppResult<()> {
+        let message_offset = match file_ref {
+            Some(file_ref) => {
+                self.received_messages
+                    .remove(file_ref.relative_path.value());
+                Self::message_offset(file_ref.relative_path.value())
+            }
+            None => self.single_message.take(),
+        };
+        if let Some((partition, offset)) = message_offset {
+            if let Some(completed) = self
+                .received_offsets
+                .get_mut(&partition)
+                .and_then(|offsets| offsets.get_mut(&offset))
+            {
+                *completed = succeeded;
+            }
+        }
+        Ok(())
+    }
+
+    async fn close(self) -> AppResult<()> {
+        if let Some(consumer) = self.consumer.as_ref() {
+            let committable_offsets = Self::committable_offsets(&self.received_offsets);
+            if !committable_offsets.is_empty() {
+                let mut offsets = TopicPartitionList::new();
+                for (partition, offset) in committable_offsets {
+                    offsets.add_partition_offset(&self.topic, partition, Offset::Offset(offset))?;
+                }
+                consumer.commit(&offsets, CommitMode::Sync)?;
+            }
+        }
+        if let Some(producer) = self.producer.as_ref() {
+            rdkafka::producer::Producer::flush(producer, Self::PRODUCE_TIMEOUT)?;
+        }
+        Ok(())
+    }
+
+    async fn has_multiple_files(&self) -> AppResult<bool> {
+        Ok(true)
+    }
+
+    async fn accepts_multiple_files(&self) -> AppResult<bool> {
+        Ok(true)
+    }
+
+    fn resolve(&self, file_ref: Option<&FileSystemRef>) -> AbsoluteFilePath {
+        AbsoluteFilePath {
+            file_path: format!(
+                "kafka://{}/{}/{}",
+                self.brokers,
+                self.topic,
+                file_ref
+                    .map(|fr| fr.relative_path.value().to_string())
+                    .unwrap_or("".to_string())
+            ),
+        }
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::file_systems::DetectFileSystem;
+
+    #[test]
+    fn parse_kafka_path_test() {
+        assert_eq!(
+            KafkaFileSystem::parse_kafka_path("kafka://localhost:9092/events").unwrap(),
+            ("localhost:9092".to_string(), "events".to_string())
+        );
+        assert_eq!(
+            KafkaFileSystem::parse_kafka_path("kafka://broker1:9092,broker2:9092/events/").unwrap(),
+            (
+                "broker1:9092,broker2:9092".to_string(),
+                "events".to_string()
+            )
+        );
+        assert!(KafkaFileSystem::parse_kafka_path("kafka://localhost:9092").is_err());
+        assert!(KafkaFileSystem::parse_kafka_path("kafka://localhost:9092/a/b").is_err());
+    }
+
+    #[test]
+    fn committable_offsets_test() {
+        assert_eq!(KafkaFileSystem::message_offset("3-42.json"), Some((3, 42)));
+        assert_eq!(KafkaFileSystem::message_offset("test.json"), None);
+
+        let received_offsets: HashMap<i32, BTreeMap<i64, bool>> = HashMap::from([
+            // The failed message at offset 11 is received again with the later messages
+            (0, BTreeMap::from([(10, true), (11, false), (12, true)])),
+            // Offsets aren't contiguous in compacted topics
+            (1, BTreeMap::from([(3, true), (7, true)])),
+            (2, BTreeMap::from([(5, false), (6, true)])),
+        ]);
+        assert_eq!(
+            KafkaFileSystem::committable_offsets(&received_offsets),
+            vec![(0, 11), (1, 8)]
+        );
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ci-kafka"), ignore)]
+    async fn upload_list_download_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let term = console::Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let test_kafka_brokers =
+            std::env::var("TEST_KAFKA_BROKERS").expect("TEST_KAFKA_BROKERS required");
+        let test_topic = format!(
+            "kafka://{}/redacter-test-{}",
+            test_kafka_brokers,
+            rand::random::<u32>()
+        );
+        let options = FileSystemOptions {
+            kafka_group_id: Some(format!("redacter-test-{}", rand::random::<u32>())),
+            ..FileSystemOptions::default()
+        };
+
+        let test_data = "{\"name\": \"John Doe\"}";
+        let mut destination_fs = KafkaFileSystem::new(&test_topic, &reporter, &options).await?;
+        destination_fs
+            .upload(
+                futures::stream::iter(vec![Ok(Bytes::from(test_data))]),
+                Some(&FileSystemRef {
+                    relative_path: "test.json".into(),
+                    media_type: Some(mime::APPLICATION_JSON),
+                    file_size: Some(test_data.len()),
+                    modified_at: None,
+                    checksum: None,
+                }),
+            )
+            .await?;
+        destination_fs.close().await?;
+
+        let mut source_fs = KafkaFileSystem::new(&test_topic, &reporter, &options).await?;
+        let list_result = source_fs.list_files(None, None).await?;
+        assert_eq!(list_result.files.len(), 1);
+        assert_eq!(
+            list_result.files[0].media_type,
+            Some(mime::APPLICATION_JSON)
+        );
+
+        let (_, down_stream) = source_fs.download(list_result.files.first()).await?;
+        let downloaded_bytes: Vec<Bytes> = down_stream.try_collect().await?;
+        assert_eq!(downloaded_bytes.concat(), test_data.as_bytes());
+        source_fs.close().await?;
+
+        Ok(())
+    }
+}
@@ -1,63 +1,148 @@
 use crate::errors::AppError;
-use crate::file_systems::{AbsoluteFilePath, FileSystemConnection, FileSystemRef, ListFilesResult};
+use crate::file_systems::{
+    local_path_from_uri, AbsoluteFilePath, FileMetadata, FileSystemConnection, FileSystemOptions,
+    FileSystemRef, ListFilesResult, RelativeFilePath,
+};
 use crate::file_tools::{FileMatcher, FileMatcherResult};
 use crate::reporter::AppReporter;
 use crate::AppResult;
 use futures::{Stream, TryStreamExt};
 use gcloud_sdk::prost::bytes;
 use rvstruct::ValueStruct;
-use std::path::PathBuf;
-use tokio::fs::File;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
 
 pub struct LocalFileSystem<'a> {
     root_path: String,
     is_dir: bool,
+    follow_symlinks: bool,
     reporter: &'a AppReporter<'a>,
 }
 
+/// State of recursive listings to detect symlink cycles and count entries which aren't regular files
+#[derive(Debug, Default)]
+struct LocalListingState {
+    visited_dirs: HashSet<PathBuf>,
+    skipped_symlinks: usize,
+    skipped_special_files: usize,
+}
+
 impl<'a> LocalFileSystem<'a> {
-    pub async fn new(root_path: &str, reporter: &'a AppReporter<'a>) -> AppResult<Self> {
-        let root_path_base_str = root_path.trim_start_matches("file://").to_string();
+    /// Suffix of files being uploaded, which are renamed to their final names when completed
+    pub const UPLOAD_TEMP_SUFFIX: &'static str = ".redacter-upload";
+
+    /// Hidden file in the same directory, so renames don't cross file systems
+    fn upload_temp_path(file_path: &Path) -> PathBuf {
+        let file_name = file_path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        file_path.with_file_name(format!(
+            ".{}.{:016x}{}",
+            file_name,
+            rand::random::<u64>(),
+            Self::UPLOAD_TEMP_SUFFIX
+        ))
+    }
+
+    pub async fn new(
+        root_path: &str,
+        reporter: &'a AppReporter<'a>,
+        options: &FileSystemOptions,
+    ) -> AppResult<Self> {
+        let root_path_base_str = local_path_from_uri(root_path);
         let root_path_path = PathBuf::from(&root_path_base_str);
-        let is_dir = root_path.ends_with('/') || root_path_path.is_dir();
-        let root_path_str = if is_dir && !root_path_base_str.ends_with('/') {
-            format!("{}/", root_path_base_str)
+        let is_dir = root_path.ends_with(std::path::is_separator) || root_path_path.is_dir();
+        let root_path_str = if is_dir && !root_path_base_str.ends_with(std::path::is_separator) {
+            format!("{}{}", root_path_base_str, std::path::MAIN_SEPARATOR)
         } else {
             root_path_base_str
         };
         Ok(LocalFileSystem {
             root_path: root_path_str,
             is_dir,
+            follow_symlinks: options.follow_symlinks,
             reporter,
         })
     }
 
+    /// Relative paths are separated with `/` on all platforms including Windows,
+    /// so they resolve the same way in object storages and zip archives
+    fn relative_path_of(&self, entry_path: &Path) -> RelativeFilePath {
+        match entry_path.strip_prefix(&self.root_path) {
+            Ok(relative_path) => relative_path
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+                .into(),
+            Err(_) => entry_path.to_string_lossy().to_string().into(),
+        }
+    }
+
     #[async_recursion::async_recursion]
-    pub async fn list_files_recursive(
+    async fn list_files_recursive(
         &self,
         dir_path: String,
         file_matcher: &Option<&FileMatcher>,
         max_files_limit: Option<usize>,
+        state: &mut LocalListingState,
     ) -> AppResult<ListFilesResult> {
         if max_files_limit.iter().any(|v| *v == 0) {
             return Ok(ListFilesResult::EMPTY);
         }
+        // Directories linked more than once are listed only once, so symlink cycles don't loop forever
+        if !state
+            .visited_dirs
+            .insert(tokio::fs::canonicalize(&dir_path).await?)
+        {
+            self.reporter.report(format!(
+                "Skipping already listed directory (symlink cycle): {}",
+                dir_path
+            ))?;
+            return Ok(ListFilesResult::EMPTY);
+        }
 
         let mut entries = tokio::fs::read_dir(dir_path).await?;
         let mut files = Vec::new();
         let mut skipped: usize = 0;
         while let Some(entry) = entries.next_entry().await? {
-            let file_type = entry.file_type().await?;
+            let mut file_type = entry.file_type().await?;
+            if file_type.is_symlink() {
+                if !self.follow_symlinks {
+                    state.skipped_symlinks += 1;
+                    skipped += 1;
+                    continue;
+                }
+                match tokio::fs::metadata(entry.path()).await {
+                    Ok(target_metadata) => file_type = target_metadata.file_type(),
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                        // Broken links
+                        state.skipped_symlinks += 1;
+                        skipped += 1;
+                        continue;
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            if file_type.is_file()
+                && entry
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(Self::UPLOAD_TEMP_SUFFIX)
+            {
+                // Leftovers of interrupted uploads
+                skipped += 1;
+                continue;
+            }
             if file_type.is_file() {
+                let entry_metadata = tokio::fs::metadata(entry.path()).await?;
                 let file_ref = FileSystemRef {
-                    relative_path: entry
-                        .path()
-                        .to_string_lossy()
-                        .to_string()
-                        .replace(self.root_path.as_str(), "")
-                        .into(),
+                    relative_path: self.relative_path_of(&entry.path()),
                     media_type: mime_guess::from_path(entry.path()).first(),
-                    file_size: Some(entry.metadata().await?.len() as usize),
+                    file_size: Some(entry_metadata.len() as usize),
+                    modified_at: entry_metadata.modified().ok().map(|v| v.into()),
+                    checksum: None,
                 };
                 if file_matcher
                     .iter()
@@ -74,10 +159,15 @@ impl<'a> LocalFileSystem<'a> {
                         entry.path().to_string_lossy().to_string(),
                         file_matcher,
                         new_max_files_limit,
+                        state,
                     )
                     .await?;
                 skipped += dir_files.skipped;
                 files.extend(dir_files.files);
+            } else {
+                // Sockets, FIFOs and devices
+                state.skipped_special_files += 1;
+                skipped += 1;
             }
 
             if let Some(limit) = max_files_limit {
@@ -115,6 +205,8 @@ impl<'a> FileSystemConnection<'a> for LocalFileSystem<'a> {
             relative_path: relative_file_path.into(),
             media_type: mime_guess::from_path(&file_path).first(),
             file_size: Some(file_metadata.len() as usize),
+            modified_at: file_metadata.modified().ok().map(|v| v.into()),
+            checksum: None,
         };
         Ok((file_ref, Box::new(stream)))
     }
@@ -132,11 +224,31 @@ impl<'a> FileSystemConnection<'a> for LocalFileSystem<'a> {
             }
         }
 
-        let mut file = File::create(file_path).await?;
-        let mut reader = tokio_util::io::StreamReader::new(
-            input.map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err)),
-        );
-        tokio::io::copy(&mut reader, &mut file).await?;
+        // Files are written next to the destination first and renamed only when completed,
+        // so interrupted uploads never leave truncated files looking complete
+        let temp_path = Self::upload_temp_path(&file_path);
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+            .await?;
+        let mut reader = tokio_util::io::StreamReader::new(input.map_err(std::io::Error::other));
+        let result = async {
+            tokio::io::copy(&mut reader, &mut file).await?;
+            file.sync_all().await?;
+            drop(file);
+            // Replaced files keep their permissions as they did when they were overwritten
+            if let Ok(existing_metadata) = tokio::fs::metadata(&file_path).await {
+                tokio::fs::set_permissions(&temp_path, existing_metadata.permissions()).await?;
+            }
+            tokio::fs::rename(&temp_path, &file_path).await
+        }
+        .await;
+        if let Err(err) = result {
+            // Removing the temporary file is best effort, the original error is more relevant
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(err.into());
+        }
         Ok(())
     }
 
@@ -149,8 +261,139 @@ impl<'a> FileSystemConnection<'a> for LocalFileSystem<'a> {
             .report(format!("Listing files in dir: {}", self.root_path.as_str()))?;
         let source = PathBuf::from(self.root_path.as_str());
         let source_str = source.to_string_lossy().to_string();
-        self.list_files_recursive(source_str.clone(), &file_matcher, max_files_limit)
-            .await
+        let mut state = LocalListingState::default();
+        let list_files_result = self
+            .list_files_recursive(
+                source_str.clone(),
+                &file_matcher,
+                max_files_limit,
+                &mut state,
+            )
+            .await?;
+        if state.skipped_symlinks > 0 {
+            self.reporter.report(format!(
+                "Skipped symbolic links: {}{}",
+                state.skipped_symlinks,
+                if self.follow_symlinks {
+                    " (broken links)"
+                } else {
+                    " (use --follow-symlinks to follow them)"
+                }
+            ))?;
+        }
+        if state.skipped_special_files > 0 {
+            self.reporter.report(format!(
+                "Skipped special files such as sockets, FIFOs and devices: {}",
+                state.skipped_special_files
+            ))?;
+        }
+        Ok(list_files_result)
+    }
+
+    async fn get_file_ref(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<Option<FileSystemRef>> {
+        let file_path = PathBuf::from(self.resolve(file_ref).file_path);
+        match tokio::fs::metadata(&file_path).await {
+            Ok(file_metadata) if file_metadata.is_file() => Ok(Some(FileSystemRef {
+                relative_path: file_ref
+                    .map(|fr| fr.relative_path.clone())
+                    .unwrap_or_else(|| {
+                        file_path
+                            .file_name()
+                            .map(|name| name.to_string_lossy().to_string())
+                            .unwrap_or_default()
+                            .into()
+                    }),
+                media_type: mime_guess::from_path(&file_path).first(),
+                file_size: Some(file_metadata.len() as usize),
+                modified_at: file_metadata.modified().ok().map(|v| v.into()),
+                checksum: None,
+            })),
+            Ok(_) => Ok(None),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn get_metadata(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<Option<FileMetadata>> {
+        let file_metadata = tokio::fs::metadata(self.resolve(file_ref).file_path).await?;
+        #[cfg(unix)]
+        let permissions = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(file_metadata.permissions().mode() & 0o7777)
+        };
+        #[cfg(not(unix))]
+        let permissions = None;
+        let file_path = PathBuf::from(self.resolve(file_ref).file_path);
+        let mut custom = BTreeMap::new();
+        for key in XATTR_METADATA_KEYS {
+            if let Some(value) = read_xattr_metadata(&file_path, key)? {
+                custom.insert(key.to_string(), value);
+            }
+        }
+        Ok(Some(FileMetadata {
+            custom,
+            modified_at: file_metadata.modified().ok().map(|v| v.into()),
+            permissions,
+            ..FileMetadata::default()
+        }))
+    }
+
+    async fn set_metadata(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+        metadata: &FileMetadata,
+    ) -> AppResult<()> {
+        let file_path = PathBuf::from(self.resolve(file_ref).file_path);
+        if let Some(modified_at) = metadata.modified_at {
+            let file = std::fs::File::options().write(true).open(&file_path)?;
+            file.set_modified(modified_at.into())?;
+        }
+        #[cfg(unix)]
+        if let Some(permissions) = metadata.permissions {
+            use std::os::unix::fs::PermissionsExt;
+            tokio::fs::set_permissions(&file_path, std::fs::Permissions::from_mode(permissions))
+                .await?;
+        }
+        for key in XATTR_METADATA_KEYS {
+            if let Some(value) = metadata.custom.get(key) {
+                write_xattr_metadata(&file_path, key, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn supports_file_operations(&self) -> bool {
+        true
+    }
+
+    async fn copy_file(&mut self, from: &FileSystemRef, to: &FileSystemRef) -> AppResult<()> {
+        let to_path = PathBuf::from(self.resolve(Some(to)).file_path);
+        if let Some(parent) = to_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::copy(self.resolve(Some(from)).file_path, to_path).await?;
+        Ok(())
+    }
+
+    /// Renames are atomic within the same file system, so readers never see partially written files
+    async fn move_file(&mut self, from: &FileSystemRef, to: &FileSystemRef) -> AppResult<()> {
+        tokio::fs::rename(
+            self.resolve(Some(from)).file_path,
+            self.resolve(Some(to)).file_path,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_file(&mut self, file_ref: &FileSystemRef) -> AppResult<()> {
+        tokio::fs::remove_file(self.resolve(Some(file_ref)).file_path).await?;
+        Ok(())
     }
 
     async fn close(self) -> AppResult<()> {
@@ -172,7 +415,10 @@ impl<'a> FileSystemConnection<'a> for LocalFileSystem<'a> {
                     "{}{}",
                     self.root_path,
                     file_ref
-                        .map(|fr| fr.relative_path.value().clone())
+                        .map(|fr| fr
+                            .relative_path
+                            .value()
+                            .replace('/', std::path::MAIN_SEPARATOR_STR))
                         .unwrap_or("".to_string())
                 )
             } else {
@@ -182,10 +428,118 @@ impl<'a> FileSystemConnection<'a> for LocalFileSystem<'a> {
     }
 }
 
+/// Custom metadata kept in extended attributes of local files (such as `user.x-redacter`),
+/// other custom metadata is ignored
+const XATTR_METADATA_KEYS: [&str; 2] =
+    [FileMetadata::REDACTED_MARKER_KEY, FileMetadata::SOURCE_KEY];
+
+/// Reads custom metadata from extended attributes.
+/// File systems without extended attributes have no custom metadata.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn read_xattr_metadata(file_path: &Path, key: &str) -> AppResult<Option<String>> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(file_path.as_os_str().as_bytes()).map_err(|err| {
+        AppError::SystemError {
+            message: format!("Invalid file path: {}", err),
+        }
+    })?;
+    let name =
+        std::ffi::CString::new(format!("user.{}", key)).map_err(|err| AppError::SystemError {
+            message: format!("Invalid attribute name: {}", err),
+        })?;
+    let mut value = vec![0u8; 1024];
+    #[cfg(target_os = "linux")]
+    let size = unsafe {
+        libc::getxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_mut_ptr() as *mut libc::c_void,
+            value.len(),
+        )
+    };
+    #[cfg(target_os = "macos")]
+    let size = unsafe {
+        libc::getxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_mut_ptr() as *mut libc::c_void,
+            value.len(),
+            0,
+            0,
+        )
+    };
+    if size < 0 {
+        let err = std::io::Error::last_os_error();
+        return match err.raw_os_error() {
+            #[cfg(target_os = "linux")]
+            Some(libc::ENODATA) => Ok(None),
+            #[cfg(target_os = "macos")]
+            Some(libc::ENOATTR) => Ok(None),
+            Some(libc::ENOTSUP) | Some(libc::ERANGE) => Ok(None),
+            _ => Err(err.into()),
+        };
+    }
+    value.truncate(size as usize);
+    Ok(String::from_utf8(value).ok())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn write_xattr_metadata(file_path: &Path, key: &str, value: &str) -> AppResult<()> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(file_path.as_os_str().as_bytes()).map_err(|err| {
+        AppError::SystemError {
+            message: format!("Invalid file path: {}", err),
+        }
+    })?;
+    let name =
+        std::ffi::CString::new(format!("user.{}", key)).map_err(|err| AppError::SystemError {
+            message: format!("Invalid attribute name: {}", err),
+        })?;
+    #[cfg(target_os = "linux")]
+    let result = unsafe {
+        libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr() as *const libc::c_void,
+            value.len(),
+            0,
+        )
+    };
+    #[cfg(target_os = "macos")]
+    let result = unsafe {
+        libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr() as *const libc::c_void,
+            value.len(),
+            0,
+            0,
+        )
+    };
+    if result < 0 {
+        let err = std::io::Error::last_os_error();
+        // Files are still copied to file systems without extended attributes, just without metadata
+        if err.raw_os_error() != Some(libc::ENOTSUP) {
+            return Err(err.into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn read_xattr_metadata(_file_path: &Path, _key: &str) -> AppResult<Option<String>> {
+    Ok(None)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn write_xattr_metadata(_file_path: &Path, _key: &str, _value: &str) -> AppResult<()> {
+    Ok(())
+}
+
 #[allow(unused_imports)]
 mod tests {
     use super::*;
-    use crate::file_systems::DetectFileSystem;
+    use crate::file_systems::{DetectFileSystem, FileSystemOptions};
     use console::Term;
 
     #[tokio::test]
@@ -198,6 +552,7 @@ mod tests {
         let fs = DetectFileSystem::open(
             &format!("file://{}", temp_dir_path.to_string_lossy()),
             &reporter,
+            &FileSystemOptions::default(),
         )
         .await?;
         // Create a temp file in the temp dir
@@ -211,6 +566,8 @@ mod tests {
                 relative_path: "temp_file.txt".into(),
                 media_type: None,
                 file_size: None,
+                modified_at: None,
+                checksum: None,
             }))
             .await?;
 
@@ -237,6 +594,7 @@ mod tests {
         let fs = DetectFileSystem::open(
             &format!("file://{}", temp_dir_path.to_string_lossy()),
             &reporter,
+            &FileSystemOptions::default(),
         )
         .await?;
 
@@ -249,6 +607,8 @@ mod tests {
                 relative_path: "temp_file.txt".into(),
                 media_type: None,
                 file_size: None,
+                modified_at: None,
+                checksum: None,
             }),
         )
         .await?;
@@ -257,6 +617,29 @@ mod tests {
         let file_content = tokio::fs::read_to_string(&temp_file).await?;
         assert_eq!(file_content, content);
 
+        // Failed uploads leave existing files intact without temporary files
+        let failed_stream = futures::stream::iter(vec![
+            Ok(bytes::Bytes::from("partial")),
+            Err(AppError::SystemError {
+                message: "Interrupted".to_string(),
+            }),
+        ]);
+        assert!(fs
+            .upload(
+                failed_stream,
+                Some(&FileSystemRef {
+                    relative_path: "temp_file.txt".into(),
+                    media_type: None,
+                    file_size: None,
+                    modified_at: None,
+                    checksum: None,
+                }),
+            )
+            .await
+            .is_err());
+        assert_eq!(tokio::fs::read_to_string(&temp_file).await?, content);
+        assert_eq!(std::fs::read_dir(temp_dir_path)?.count(), 1);
+
         fs.close().await?;
 
         Ok(())
@@ -272,6 +655,7 @@ mod tests {
         let fs = DetectFileSystem::open(
             &format!("file://{}", temp_dir_path.to_string_lossy()),
             &reporter,
+            &FileSystemOptions::default(),
         )
         .await?;
 
@@ -284,6 +668,8 @@ mod tests {
                 relative_path: "temp_file.txt".into(),
                 media_type: None,
                 file_size: None,
+                modified_at: None,
+                checksum: None,
             }),
         )
         .await?;
@@ -294,9 +680,54 @@ mod tests {
             list_files_result.files[0].relative_path.value(),
             "temp_file.txt"
         );
+        assert!(list_files_result.files[0].modified_at.is_some());
 
         fs.close().await?;
 
         Ok(())
     }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn list_symlinks_and_special_files_test(
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let temp_dir = tempfile::TempDir::with_prefix("local_file_system_tests_symlinks")?;
+        let root_path = temp_dir.path().join("root");
+        tokio::fs::create_dir_all(root_path.join("dir")).await?;
+        tokio::fs::write(root_path.join("dir/file.txt"), "test content").await?;
+        std::os::unix::fs::symlink(root_path.join("dir/file.txt"), root_path.join("link.txt"))?;
+        std::os::unix::fs::symlink(&root_path, root_path.join("dir/cycle"))?;
+        std::os::unix::fs::symlink(root_path.join("missing"), root_path.join("broken"))?;
+        let _socket = std::os::unix::net::UnixListener::bind(root_path.join("socket"))?;
+
+        let root_path_str = format!("{}/", root_path.to_string_lossy());
+        let mut fs =
+            LocalFileSystem::new(&root_path_str, &reporter, &FileSystemOptions::default()).await?;
+        let list_files_result = fs.list_files(None, None).await?;
+        assert_eq!(list_files_result.files.len(), 1);
+        assert_eq!(list_files_result.skipped, 4);
+
+        let mut fs = LocalFileSystem::new(
+            &root_path_str,
+            &reporter,
+            &FileSystemOptions {
+                follow_symlinks: true,
+                ..FileSystemOptions::default()
+            },
+        )
+        .await?;
+        let list_files_result = fs.list_files(None, None).await?;
+        let mut relative_paths: Vec<String> = list_files_result
+            .files
+            .iter()
+            .map(|file_ref| file_ref.relative_path.value().clone())
+            .collect();
+        relative_paths.sort();
+        assert_eq!(relative_paths, vec!["dir/file.txt", "link.txt"]);
+        assert_eq!(list_files_result.skipped, 2);
+
+        Ok(())
+    }
 }
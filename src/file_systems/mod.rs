@@ -1,3 +1,4 @@
+use crate::common_types::GcpPubSubSubscription;
 use crate::errors::AppError;
 use crate::file_systems::gcs::GoogleCloudStorageFileSystem;
 use crate::file_systems::local::LocalFileSystem;
@@ -8,18 +9,36 @@ use gcloud_sdk::prost::bytes;
 use gcloud_sdk::prost::bytes::Bytes;
 use mime::Mime;
 use rvstruct::ValueStruct;
+use std::future::Future;
 
 mod aws_s3;
+mod file_metadata;
 mod gcs;
 mod local;
+mod object_path;
+mod resumable_download;
+mod upload_parts;
+mod work_files;
 mod zip;
 
 #[cfg(feature = "clipboard")]
 mod clipboard;
 
+#[cfg(feature = "kafka")]
+mod kafka;
+
+#[cfg(feature = "postgres")]
+mod postgres;
+
+#[cfg(feature = "imap")]
+mod imap;
+
 mod noop;
 
 use crate::file_systems::aws_s3::AwsS3FileSystem;
+pub use crate::file_systems::file_metadata::FileMetadata;
+pub use crate::file_systems::object_path::{is_local_path, local_path_from_uri};
+pub use crate::file_systems::work_files::WorkFiles;
 use crate::file_tools::FileMatcher;
 use crate::reporter::AppReporter;
 
@@ -30,7 +49,7 @@ impl RelativeFilePath {
     pub fn filename(&self) -> String {
         self.value()
             .split('/')
-            .last()
+            .next_back()
             .map(|s| s.to_string())
             .unwrap_or_default()
     }
@@ -46,6 +65,120 @@ pub struct FileSystemRef {
     pub relative_path: RelativeFilePath,
     pub media_type: Option<Mime>,
     pub file_size: Option<usize>,
+    pub modified_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub checksum: Option<FileChecksum>,
+}
+
+/// Content checksums reported by file systems. Only checksums of the same kind are comparable.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FileChecksum {
+    Md5(String),
+    Crc32c(String),
+    ETag(String),
+}
+
+impl FileChecksum {
+    /// S3 ETags are MD5 hex digests unless objects were uploaded in multiple parts
+    pub fn from_aws_etag(etag: &str) -> Self {
+        let etag = etag.trim_matches('"');
+        if etag.contains('-') {
+            FileChecksum::ETag(etag.to_string())
+        } else {
+            FileChecksum::Md5(etag.to_lowercase())
+        }
+    }
+
+    /// GCS reports base64 encoded hashes, MD5 is missing for composite objects
+    pub fn from_gcs_hashes(md5_hash: Option<&str>, crc32c: Option<&str>) -> Option<Self> {
+        use base64::Engine;
+        md5_hash
+            .and_then(|md5| base64::engine::general_purpose::STANDARD.decode(md5).ok())
+            .map(|md5| FileChecksum::Md5(hex::encode(md5)))
+            .or_else(|| crc32c.map(|crc32c| FileChecksum::Crc32c(crc32c.to_string())))
+    }
+
+    pub fn is_comparable_with(&self, other: &FileChecksum) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+/// Options for file systems specified by users for sources and destinations
+#[derive(Debug, Clone, Default)]
+pub struct FileSystemOptions {
+    /// Consume GCS notifications from Pub/Sub subscription instead of listing buckets
+    pub gcs_pubsub_subscription: Option<GcpPubSubSubscription>,
+    /// Part size in bytes for multipart (S3) and resumable (GCS) uploads
+    pub upload_part_size: Option<usize>,
+    /// Custom endpoint for S3-compatible storages such as MinIO, Ceph, or R2
+    pub s3_endpoint_url: Option<url::Url>,
+    /// Use path-style addressing (endpoint/bucket/key) instead of virtual-hosted style for S3
+    pub s3_force_path_style: bool,
+    /// AWS profile name for S3 credentials and region
+    pub s3_profile: Option<String>,
+    /// Custom endpoint for GCS such as fake-gcs-server or private Google access
+    pub gcs_endpoint_url: Option<url::Url>,
+    /// Service account key file for GCS instead of Application Default Credentials
+    pub gcs_service_account_key_file: Option<std::path::PathBuf>,
+    /// Maximum number of Kafka messages received in one batch
+    pub kafka_batch_size: Option<usize>,
+    /// Kafka consumer group to track consumed offsets
+    pub kafka_group_id: Option<String>,
+    /// Where to start consuming Kafka topics without committed offsets
+    pub kafka_offset_reset: Option<KafkaOffsetReset>,
+    /// Number of rows in every CSV file exported from PostgreSQL
+    pub postgres_batch_size: Option<usize>,
+    /// Password or secret reference for IMAP mailboxes without passwords in their URLs
+    pub imap_password: Option<String>,
+    /// OAuth 2.0 access token for IMAP mailboxes authenticated with XOAUTH2 such as Gmail
+    pub imap_oauth_token: Option<String>,
+    /// Follow symbolic links in local directories instead of skipping them
+    pub follow_symlinks: bool,
+    /// Server-side encryption of S3 uploads such as `aws:kms`
+    pub s3_server_side_encryption: Option<String>,
+    /// KMS key for S3 uploads encrypted with `aws:kms`
+    pub s3_kms_key_id: Option<String>,
+    /// Storage class of S3 uploads such as `STANDARD_IA`
+    pub s3_storage_class: Option<String>,
+    /// Customer-managed Cloud KMS key for GCS uploads
+    pub gcs_kms_key: Option<String>,
+    /// Storage class of GCS uploads such as `NEARLINE`
+    pub gcs_storage_class: Option<String>,
+    /// Directory for temporary files such as prefetched files beyond the memory budget instead of the system temp directory
+    pub work_dir: Option<std::path::PathBuf>,
+    /// Keep temporary files in memory instead of writing them to disk
+    pub work_in_memory: bool,
+    /// Encrypt temporary files with a random key which exists only in memory
+    pub encrypt_work_files: bool,
+    /// Add and replace files in existing zip archives used as destinations
+    pub zip_append: bool,
+    /// Maximum total uncompressed size of zip archives used as sources
+    pub zip_max_uncompressed_size: Option<u64>,
+    /// Maximum compression ratio of zip archive entries, to detect zip bombs
+    pub zip_max_compression_ratio: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum KafkaOffsetReset {
+    Earliest,
+    Latest,
+}
+
+impl KafkaOffsetReset {
+    pub fn as_config_value(&self) -> &'static str {
+        match self {
+            KafkaOffsetReset::Earliest => "earliest",
+            KafkaOffsetReset::Latest => "latest",
+        }
+    }
+}
+
+impl FileSystemOptions {
+    pub const DEFAULT_UPLOAD_PART_SIZE: usize = 8 * 1024 * 1024;
+
+    pub fn upload_part_size(&self) -> usize {
+        self.upload_part_size
+            .unwrap_or(Self::DEFAULT_UPLOAD_PART_SIZE)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -61,36 +194,117 @@ impl ListFilesResult {
     };
 }
 
+pub type FileSystemStream =
+    Box<dyn Stream<Item = AppResult<bytes::Bytes>> + Send + Sync + Unpin + 'static>;
+
+/// File system abstraction used to copy files from sources to destinations.
+/// Futures are `Send`, so connections can be used from multithreaded runtimes.
 pub trait FileSystemConnection<'a> {
-    async fn download(
+    fn download(
         &mut self,
         file_ref: Option<&FileSystemRef>,
-    ) -> AppResult<(
-        FileSystemRef,
-        Box<dyn Stream<Item = AppResult<bytes::Bytes>> + Send + Sync + Unpin + 'static>,
-    )>;
+    ) -> impl Future<Output = AppResult<(FileSystemRef, FileSystemStream)>> + Send;
 
-    async fn upload<S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static>(
+    fn upload<S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static>(
         &mut self,
         input: S,
         file_ref: Option<&FileSystemRef>,
-    ) -> AppResult<()>;
+    ) -> impl Future<Output = AppResult<()>> + Send;
 
-    async fn list_files(
+    fn list_files(
         &mut self,
         file_matcher: Option<&FileMatcher>,
         max_files_limit: Option<usize>,
-    ) -> AppResult<ListFilesResult>;
+    ) -> impl Future<Output = AppResult<ListFilesResult>> + Send;
 
-    async fn close(self) -> AppResult<()>;
+    fn get_file_ref(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+    ) -> impl Future<Output = AppResult<Option<FileSystemRef>>> + Send;
 
-    async fn has_multiple_files(&self) -> AppResult<bool>;
+    /// Metadata preserved on copies. File systems without metadata support return `None`
+    fn get_metadata(
+        &mut self,
+        _file_ref: Option<&FileSystemRef>,
+    ) -> impl Future<Output = AppResult<Option<FileMetadata>>> + Send {
+        async { Ok(None) }
+    }
+
+    /// Sets metadata of uploaded files. File systems without metadata support ignore it
+    fn set_metadata(
+        &mut self,
+        _file_ref: Option<&FileSystemRef>,
+        _metadata: &FileMetadata,
+    ) -> impl Future<Output = AppResult<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// File systems supporting copying, moving and deleting files, required to redact files in place
+    fn supports_file_operations(&self) -> bool {
+        false
+    }
+
+    /// File systems where downloads don't change their state, so next files can be downloaded
+    /// while the current file is redacted, and dropped if runs are stopped
+    fn supports_prefetch(&self) -> bool {
+        true
+    }
 
-    async fn accepts_multiple_files(&self) -> AppResult<bool>;
+    /// Called when processing of a source file is complete: copied, redacted, skipped or
+    /// quarantined files succeed, and failed files don't. Message-based sources such as
+    /// Kafka and Pub/Sub notifications acknowledge only succeeded files on close,
+    /// so failed files are delivered again
+    fn complete_file(
+        &mut self,
+        _file_ref: Option<&FileSystemRef>,
+        _succeeded: bool,
+    ) -> impl Future<Output = AppResult<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Copies files within the file system, such as backups of original files
+    fn copy_file(
+        &mut self,
+        _from: &FileSystemRef,
+        _to: &FileSystemRef,
+    ) -> impl Future<Output = AppResult<()>> + Send {
+        async { Err(file_operations_not_supported()) }
+    }
+
+    /// Moves files within the file system replacing existing files.
+    /// Local files are renamed atomically, objects are copied and deleted
+    fn move_file(
+        &mut self,
+        _from: &FileSystemRef,
+        _to: &FileSystemRef,
+    ) -> impl Future<Output = AppResult<()>> + Send {
+        async { Err(file_operations_not_supported()) }
+    }
+
+    fn delete_file(
+        &mut self,
+        _file_ref: &FileSystemRef,
+    ) -> impl Future<Output = AppResult<()>> + Send {
+        async { Err(file_operations_not_supported()) }
+    }
+
+    fn close(self) -> impl Future<Output = AppResult<()>> + Send;
+
+    fn has_multiple_files(&self) -> impl Future<Output = AppResult<bool>> + Send;
+
+    fn accepts_multiple_files(&self) -> impl Future<Output = AppResult<bool>> + Send;
 
     fn resolve(&self, file_ref: Option<&FileSystemRef>) -> AbsoluteFilePath;
 }
 
+fn file_operations_not_supported() -> AppError {
+    AppError::SystemError {
+        message: "Copying, moving and deleting files is not supported by the file system"
+            .to_string(),
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
 pub enum DetectFileSystem<'a> {
     Local(LocalFileSystem<'a>),
     GoogleCloudStorage(GoogleCloudStorageFileSystem<'a>),
@@ -98,28 +312,35 @@ pub enum DetectFileSystem<'a> {
     ZipFile(ZipFileSystem<'a>),
     #[cfg(feature = "clipboard")]
     Clipboard(clipboard::ClipboardFileSystem<'a>),
+    #[cfg(feature = "kafka")]
+    Kafka(kafka::KafkaFileSystem<'a>),
+    #[cfg(feature = "postgres")]
+    Postgres(postgres::PostgresFileSystem<'a>),
+    #[cfg(feature = "imap")]
+    Imap(imap::ImapFileSystem<'a>),
 }
 
 impl<'a> DetectFileSystem<'a> {
     pub async fn open(
         file_path: &str,
         reporter: &'a AppReporter<'a>,
+        options: &FileSystemOptions,
     ) -> AppResult<impl FileSystemConnection<'a>> {
-        if file_path.starts_with("file://") || !file_path.contains("://") {
+        if is_local_path(file_path) {
             Ok(DetectFileSystem::Local(
-                LocalFileSystem::new(file_path, reporter).await?,
+                LocalFileSystem::new(file_path, reporter, options).await?,
             ))
         } else if file_path.starts_with("gs://") {
             Ok(DetectFileSystem::GoogleCloudStorage(
-                GoogleCloudStorageFileSystem::new(file_path, reporter).await?,
+                GoogleCloudStorageFileSystem::new(file_path, reporter, options).await?,
             ))
         } else if file_path.starts_with("s3://") {
             Ok(DetectFileSystem::AwsS3(
-                AwsS3FileSystem::new(file_path, reporter).await?,
+                AwsS3FileSystem::new(file_path, reporter, options).await?,
             ))
         } else if file_path.starts_with("zip://") {
             Ok(DetectFileSystem::ZipFile(
-                ZipFileSystem::new(file_path, reporter).await?,
+                ZipFileSystem::new(file_path, reporter, options).await?,
             ))
         } else if file_path.starts_with("clipboard://") {
             #[cfg(feature = "clipboard")]
@@ -134,6 +355,45 @@ impl<'a> DetectFileSystem<'a> {
                     file_path: file_path.to_string(),
                 });
             }
+        } else if file_path.starts_with("kafka://") {
+            #[cfg(feature = "kafka")]
+            {
+                Ok(DetectFileSystem::Kafka(
+                    kafka::KafkaFileSystem::new(file_path, reporter, options).await?,
+                ))
+            }
+            #[cfg(not(feature = "kafka"))]
+            {
+                Err(AppError::UnknownFileSystem {
+                    file_path: file_path.to_string(),
+                })
+            }
+        } else if file_path.starts_with("postgres://") || file_path.starts_with("postgresql://") {
+            #[cfg(feature = "postgres")]
+            {
+                Ok(DetectFileSystem::Postgres(
+                    postgres::PostgresFileSystem::new(file_path, reporter, options).await?,
+                ))
+            }
+            #[cfg(not(feature = "postgres"))]
+            {
+                Err(AppError::UnknownFileSystem {
+                    file_path: file_path.to_string(),
+                })
+            }
+        } else if file_path.starts_with("imap://") {
+            #[cfg(feature = "imap")]
+            {
+                Ok(DetectFileSystem::Imap(
+                    imap::ImapFileSystem::new(file_path, reporter, options).await?,
+                ))
+            }
+            #[cfg(not(feature = "imap"))]
+            {
+                Err(AppError::UnknownFileSystem {
+                    file_path: file_path.to_string(),
+                })
+            }
         } else {
             Err(AppError::UnknownFileSystem {
                 file_path: file_path.to_string(),
@@ -157,6 +417,12 @@ impl<'a> FileSystemConnection<'a> for DetectFileSystem<'a> {
             DetectFileSystem::ZipFile(fs) => fs.download(file_ref).await,
             #[cfg(feature = "clipboard")]
             DetectFileSystem::Clipboard(fs) => fs.download(file_ref).await,
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.download(file_ref).await,
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.download(file_ref).await,
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.download(file_ref).await,
         }
     }
 
@@ -172,6 +438,12 @@ impl<'a> FileSystemConnection<'a> for DetectFileSystem<'a> {
             DetectFileSystem::ZipFile(fs) => fs.upload(input, file_ref).await,
             #[cfg(feature = "clipboard")]
             DetectFileSystem::Clipboard(fs) => fs.upload(input, file_ref).await,
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.upload(input, file_ref).await,
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.upload(input, file_ref).await,
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.upload(input, file_ref).await,
         }
     }
 
@@ -189,6 +461,179 @@ impl<'a> FileSystemConnection<'a> for DetectFileSystem<'a> {
             DetectFileSystem::ZipFile(fs) => fs.list_files(file_matcher, max_files_limit).await,
             #[cfg(feature = "clipboard")]
             DetectFileSystem::Clipboard(fs) => fs.list_files(file_matcher, max_files_limit).await,
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.list_files(file_matcher, max_files_limit).await,
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.list_files(file_matcher, max_files_limit).await,
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.list_files(file_matcher, max_files_limit).await,
+        }
+    }
+
+    async fn get_file_ref(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<Option<FileSystemRef>> {
+        match self {
+            DetectFileSystem::Local(fs) => fs.get_file_ref(file_ref).await,
+            DetectFileSystem::GoogleCloudStorage(fs) => fs.get_file_ref(file_ref).await,
+            DetectFileSystem::AwsS3(fs) => fs.get_file_ref(file_ref).await,
+            DetectFileSystem::ZipFile(fs) => fs.get_file_ref(file_ref).await,
+            #[cfg(feature = "clipboard")]
+            DetectFileSystem::Clipboard(fs) => fs.get_file_ref(file_ref).await,
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.get_file_ref(file_ref).await,
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.get_file_ref(file_ref).await,
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.get_file_ref(file_ref).await,
+        }
+    }
+
+    async fn get_metadata(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<Option<FileMetadata>> {
+        match self {
+            DetectFileSystem::Local(fs) => fs.get_metadata(file_ref).await,
+            DetectFileSystem::GoogleCloudStorage(fs) => fs.get_metadata(file_ref).await,
+            DetectFileSystem::AwsS3(fs) => fs.get_metadata(file_ref).await,
+            DetectFileSystem::ZipFile(fs) => fs.get_metadata(file_ref).await,
+            #[cfg(feature = "clipboard")]
+            DetectFileSystem::Clipboard(fs) => fs.get_metadata(file_ref).await,
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.get_metadata(file_ref).await,
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.get_metadata(file_ref).await,
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.get_metadata(file_ref).await,
+        }
+    }
+
+    async fn set_metadata(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+        metadata: &FileMetadata,
+    ) -> AppResult<()> {
+        match self {
+            DetectFileSystem::Local(fs) => fs.set_metadata(file_ref, metadata).await,
+            DetectFileSystem::GoogleCloudStorage(fs) => fs.set_metadata(file_ref, metadata).await,
+            DetectFileSystem::AwsS3(fs) => fs.set_metadata(file_ref, metadata).await,
+            DetectFileSystem::ZipFile(fs) => fs.set_metadata(file_ref, metadata).await,
+            #[cfg(feature = "clipboard")]
+            DetectFileSystem::Clipboard(fs) => fs.set_metadata(file_ref, metadata).await,
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.set_metadata(file_ref, metadata).await,
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.set_metadata(file_ref, metadata).await,
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.set_metadata(file_ref, metadata).await,
+        }
+    }
+
+    fn supports_file_operations(&self) -> bool {
+        match self {
+            DetectFileSystem::Local(fs) => fs.supports_file_operations(),
+            DetectFileSystem::GoogleCloudStorage(fs) => fs.supports_file_operations(),
+            DetectFileSystem::AwsS3(fs) => fs.supports_file_operations(),
+            DetectFileSystem::ZipFile(fs) => fs.supports_file_operations(),
+            #[cfg(feature = "clipboard")]
+            DetectFileSystem::Clipboard(fs) => fs.supports_file_operations(),
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.supports_file_operations(),
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.supports_file_operations(),
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.supports_file_operations(),
+        }
+    }
+
+    fn supports_prefetch(&self) -> bool {
+        match self {
+            DetectFileSystem::Local(fs) => fs.supports_prefetch(),
+            DetectFileSystem::GoogleCloudStorage(fs) => fs.supports_prefetch(),
+            DetectFileSystem::AwsS3(fs) => fs.supports_prefetch(),
+            DetectFileSystem::ZipFile(fs) => fs.supports_prefetch(),
+            #[cfg(feature = "clipboard")]
+            DetectFileSystem::Clipboard(fs) => fs.supports_prefetch(),
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.supports_prefetch(),
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.supports_prefetch(),
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.supports_prefetch(),
+        }
+    }
+
+    async fn complete_file(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+        succeeded: bool,
+    ) -> AppResult<()> {
+        match self {
+            DetectFileSystem::Local(fs) => fs.complete_file(file_ref, succeeded).await,
+            DetectFileSystem::GoogleCloudStorage(fs) => fs.complete_file(file_ref, succeeded).await,
+            DetectFileSystem::AwsS3(fs) => fs.complete_file(file_ref, succeeded).await,
+            DetectFileSystem::ZipFile(fs) => fs.complete_file(file_ref, succeeded).await,
+            #[cfg(feature = "clipboard")]
+            DetectFileSystem::Clipboard(fs) => fs.complete_file(file_ref, succeeded).await,
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.complete_file(file_ref, succeeded).await,
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.complete_file(file_ref, succeeded).await,
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.complete_file(file_ref, succeeded).await,
+        }
+    }
+
+    async fn copy_file(&mut self, from: &FileSystemRef, to: &FileSystemRef) -> AppResult<()> {
+        match self {
+            DetectFileSystem::Local(fs) => fs.copy_file(from, to).await,
+            DetectFileSystem::GoogleCloudStorage(fs) => fs.copy_file(from, to).await,
+            DetectFileSystem::AwsS3(fs) => fs.copy_file(from, to).await,
+            DetectFileSystem::ZipFile(fs) => fs.copy_file(from, to).await,
+            #[cfg(feature = "clipboard")]
+            DetectFileSystem::Clipboard(fs) => fs.copy_file(from, to).await,
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.copy_file(from, to).await,
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.copy_file(from, to).await,
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.copy_file(from, to).await,
+        }
+    }
+
+    async fn move_file(&mut self, from: &FileSystemRef, to: &FileSystemRef) -> AppResult<()> {
+        match self {
+            DetectFileSystem::Local(fs) => fs.move_file(from, to).await,
+            DetectFileSystem::GoogleCloudStorage(fs) => fs.move_file(from, to).await,
+            DetectFileSystem::AwsS3(fs) => fs.move_file(from, to).await,
+            DetectFileSystem::ZipFile(fs) => fs.move_file(from, to).await,
+            #[cfg(feature = "clipboard")]
+            DetectFileSystem::Clipboard(fs) => fs.move_file(from, to).await,
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.move_file(from, to).await,
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.move_file(from, to).await,
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.move_file(from, to).await,
+        }
+    }
+
+    async fn delete_file(&mut self, file_ref: &FileSystemRef) -> AppResult<()> {
+        match self {
+            DetectFileSystem::Local(fs) => fs.delete_file(file_ref).await,
+            DetectFileSystem::GoogleCloudStorage(fs) => fs.delete_file(file_ref).await,
+            DetectFileSystem::AwsS3(fs) => fs.delete_file(file_ref).await,
+            DetectFileSystem::ZipFile(fs) => fs.delete_file(file_ref).await,
+            #[cfg(feature = "clipboard")]
+            DetectFileSystem::Clipboard(fs) => fs.delete_file(file_ref).await,
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.delete_file(file_ref).await,
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.delete_file(file_ref).await,
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.delete_file(file_ref).await,
         }
     }
 
@@ -200,6 +645,12 @@ impl<'a> FileSystemConnection<'a> for DetectFileSystem<'a> {
             DetectFileSystem::ZipFile(fs) => fs.close().await,
             #[cfg(feature = "clipboard")]
             DetectFileSystem::Clipboard(fs) => fs.close().await,
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.close().await,
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.close().await,
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.close().await,
         }
     }
 
@@ -211,6 +662,12 @@ impl<'a> FileSystemConnection<'a> for DetectFileSystem<'a> {
             DetectFileSystem::ZipFile(fs) => fs.has_multiple_files().await,
             #[cfg(feature = "clipboard")]
             DetectFileSystem::Clipboard(fs) => fs.has_multiple_files().await,
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.has_multiple_files().await,
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.has_multiple_files().await,
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.has_multiple_files().await,
         }
     }
 
@@ -222,6 +679,12 @@ impl<'a> FileSystemConnection<'a> for DetectFileSystem<'a> {
             DetectFileSystem::ZipFile(fs) => fs.accepts_multiple_files().await,
             #[cfg(feature = "clipboard")]
             DetectFileSystem::Clipboard(fs) => fs.accepts_multiple_files().await,
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.accepts_multiple_files().await,
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.accepts_multiple_files().await,
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.accepts_multiple_files().await,
         }
     }
 
@@ -233,6 +696,12 @@ impl<'a> FileSystemConnection<'a> for DetectFileSystem<'a> {
             DetectFileSystem::ZipFile(fs) => fs.resolve(file_ref),
             #[cfg(feature = "clipboard")]
             DetectFileSystem::Clipboard(fs) => fs.resolve(file_ref),
+            #[cfg(feature = "kafka")]
+            DetectFileSystem::Kafka(fs) => fs.resolve(file_ref),
+            #[cfg(feature = "postgres")]
+            DetectFileSystem::Postgres(fs) => fs.resolve(file_ref),
+            #[cfg(feature = "imap")]
+            DetectFileSystem::Imap(fs) => fs.resolve(file_ref),
         }
     }
 }
@@ -51,6 +51,13 @@ impl<'a> FileSystemConnection<'a> for NoopFileSystem<'a> {
         Ok(ListFilesResult::EMPTY)
     }
 
+    async fn get_file_ref(
+        &mut self,
+        _file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<Option<FileSystemRef>> {
+        Ok(None)
+    }
+
     async fn close(self) -> AppResult<()> {
         Ok(())
     }
@@ -0,0 +1,244 @@
+use crate::errors::AppError;
+use crate::file_systems::RelativeFilePath;
+use crate::AppResult;
+use rvstruct::ValueStruct;
+
+/// Bucket and object names of object storage paths such as `gs://bucket/dir/file.txt`.
+/// Object names are used as specified: they may contain spaces, `#`, `?`, `%` and non-ASCII characters,
+/// so paths are not parsed as URLs, and object names are percent-encoded only when they are sent in URLs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectStoragePath {
+    pub bucket_name: String,
+    /// Object name, or a prefix ending with `/` for directories. `/` is the root of the bucket
+    pub object_name: String,
+}
+
+impl ObjectStoragePath {
+    pub fn parse(scheme: &str, path: &str) -> AppResult<Self> {
+        let invalid_path = || AppError::FileSystemError {
+            scheme: scheme.to_string(),
+            path: path.to_string(),
+            message: "Invalid path. Expected a bucket name followed by an optional object name"
+                .to_string(),
+        };
+        let bucket_and_object = path
+            .strip_prefix(scheme)
+            .and_then(|path| path.strip_prefix("://"))
+            .ok_or_else(invalid_path)?;
+        let (bucket_name, object_name) = bucket_and_object
+            .split_once('/')
+            .unwrap_or((bucket_and_object, ""));
+        if bucket_name.is_empty() || bucket_name.contains(char::is_whitespace) {
+            return Err(invalid_path());
+        }
+        Ok(Self {
+            bucket_name: bucket_name.to_string(),
+            object_name: if object_name.is_empty() {
+                "/".to_string()
+            } else {
+                object_name.to_string()
+            },
+        })
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.object_name.ends_with('/')
+    }
+
+    /// Prefix of objects in directories, `None` for the root of the bucket
+    pub fn prefix(&self) -> Option<&str> {
+        if self.object_name == "/" {
+            None
+        } else {
+            Some(self.object_name.as_str())
+        }
+    }
+
+    /// Object name of the file relative to directories, or the object name itself for file paths
+    pub fn resolve(&self, relative_path: Option<&RelativeFilePath>) -> String {
+        if self.is_dir() {
+            format!(
+                "{}{}",
+                self.prefix().unwrap_or_default(),
+                relative_path
+                    .map(|relative_path| relative_path.value().as_str())
+                    .unwrap_or_default()
+            )
+        } else {
+            self.object_name.clone()
+        }
+    }
+
+    /// Path of the object relative to directories, or the file name of the object for file paths
+    pub fn relative_path_of(&self, object_name: &str) -> RelativeFilePath {
+        if self.is_dir() {
+            object_name
+                .strip_prefix(self.prefix().unwrap_or_default())
+                .unwrap_or(object_name)
+                .into()
+        } else {
+            RelativeFilePath(object_name.to_string()).filename().into()
+        }
+    }
+}
+
+/// Appends path segments to the base URL percent-encoding them,
+/// so segments such as object names with `/`, `?` or `#` stay single segments
+pub fn url_with_path_segments(base_url: &str, segments: &[&str]) -> AppResult<url::Url> {
+    let mut url = url::Url::parse(base_url).map_err(|err| AppError::SystemError {
+        message: format!("Invalid URL {}: {}", base_url, err),
+    })?;
+    url.path_segments_mut()
+        .map_err(|_| AppError::SystemError {
+            message: format!("Invalid base URL: {}", base_url),
+        })?
+        .pop_if_empty()
+        .extend(segments);
+    Ok(url)
+}
+
+/// Local paths from `file://` URIs are percent-decoded, other paths are used as specified.
+/// On Windows, `file:///C:/dir` URIs are paths with drive letters and `file:////server/share` URIs are UNC paths
+pub fn local_path_from_uri(path: &str) -> String {
+    match path.strip_prefix("file://") {
+        Some(uri_path) => {
+            let local_path = percent_encoding::percent_decode_str(uri_path)
+                .decode_utf8_lossy()
+                .to_string();
+            match local_path.strip_prefix('/') {
+                Some(drive_path) if cfg!(windows) && has_drive_letter(drive_path) => {
+                    drive_path.to_string()
+                }
+                _ => local_path,
+            }
+        }
+        None => path.to_string(),
+    }
+}
+
+/// Local paths are `file://` URIs and paths without schemes including Windows paths,
+/// so drive letters such as `C://dir` aren't detected as schemes
+pub fn is_local_path(path: &str) -> bool {
+    match path.split_once("://") {
+        Some((scheme, _)) => scheme == "file" || has_drive_letter(path) || path.starts_with(r"\\"),
+        None => true,
+    }
+}
+
+fn has_drive_letter(path: &str) -> bool {
+    let mut chars = path.chars();
+    matches!(
+        (chars.next(), chars.next(), chars.next()),
+        (Some(drive), Some(':'), None | Some('/') | Some('\\')) if drive.is_ascii_alphabetic()
+    )
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_object_storage_path_test() -> AppResult<()> {
+        let path = ObjectStoragePath::parse("gs", "gs://my-bucket/dir with spaces/file #1?.txt")?;
+        assert_eq!(path.bucket_name, "my-bucket");
+        assert_eq!(path.object_name, "dir with spaces/file #1?.txt");
+        assert!(!path.is_dir());
+
+        let path = ObjectStoragePath::parse("s3", "s3://my-bucket/данные/%20отчёт/")?;
+        assert_eq!(path.object_name, "данные/%20отчёт/");
+        assert!(path.is_dir());
+        assert_eq!(path.prefix(), Some("данные/%20отчёт/"));
+
+        for root_path in ["gs://my-bucket", "gs://my-bucket/"] {
+            let path = ObjectStoragePath::parse("gs", root_path)?;
+            assert_eq!(path.object_name, "/");
+            assert!(path.is_dir());
+            assert_eq!(path.prefix(), None);
+        }
+
+        assert!(ObjectStoragePath::parse("gs", "gs:///file.txt").is_err());
+        assert!(ObjectStoragePath::parse("gs", "s3://my-bucket/file.txt").is_err());
+        assert!(ObjectStoragePath::parse("s3", "s3://my bucket/file.txt").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn relative_paths_test() -> AppResult<()> {
+        let dir_path = ObjectStoragePath::parse("gs", "gs://my-bucket/a/")?;
+        let relative_path = dir_path.relative_path_of("a/a/résumé #2.pdf");
+        assert_eq!(relative_path.value(), "a/résumé #2.pdf");
+        assert_eq!(dir_path.resolve(Some(&relative_path)), "a/a/résumé #2.pdf");
+
+        let root_path = ObjectStoragePath::parse("gs", "gs://my-bucket/")?;
+        assert_eq!(
+            root_path.resolve(Some(&"dir/file?.txt".into())),
+            "dir/file?.txt"
+        );
+        assert_eq!(
+            root_path.relative_path_of("dir/file?.txt").value(),
+            "dir/file?.txt"
+        );
+
+        let file_path = ObjectStoragePath::parse("s3", "s3://my-bucket/dir/file 1.txt")?;
+        assert_eq!(
+            file_path.relative_path_of("dir/file 1.txt").value(),
+            "file 1.txt"
+        );
+        assert_eq!(
+            file_path.resolve(Some(&"other.txt".into())),
+            "dir/file 1.txt"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn url_with_path_segments_test() -> AppResult<()> {
+        let url = url_with_path_segments(
+            "http://localhost:4443/storage/v1/b/",
+            &["my-bucket", "o", "dir/file #1?+ü.txt"],
+        )?;
+        assert_eq!(
+            url.as_str(),
+            "http://localhost:4443/storage/v1/b/my-bucket/o/dir%2Ffile%20%231%3F+%C3%BC.txt"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn local_path_from_uri_test() {
+        assert_eq!(
+            local_path_from_uri("file:///tmp/my%20dir/r%C3%A9sum%C3%A9.txt"),
+            "/tmp/my dir/résumé.txt"
+        );
+        assert_eq!(local_path_from_uri("/tmp/my%20dir/"), "/tmp/my%20dir/");
+        #[cfg(windows)]
+        assert_eq!(
+            local_path_from_uri("file:///C:/my%20dir/file.txt"),
+            "C:/my dir/file.txt"
+        );
+    }
+
+    #[test]
+    fn is_local_path_test() {
+        for path in [
+            "/tmp/dir/",
+            "dir/file.txt",
+            "file:///tmp/dir/",
+            r"C:\Users\John\file.txt",
+            "C://Users/John/",
+            "d:",
+            r"\\server\share\dir\",
+            "//server/share/dir/",
+        ] {
+            assert!(is_local_path(path), "{}", path);
+        }
+        for path in [
+            "gs://my-bucket/dir/",
+            "s3://my-bucket/",
+            "zip://archive.zip",
+            "clipboard://",
+        ] {
+            assert!(!is_local_path(path), "{}", path);
+        }
+    }
+}
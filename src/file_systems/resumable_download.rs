@@ -0,0 +1,114 @@
+use crate::file_systems::FileSystemStream;
+use crate::metrics::{AppMetric, AppMetrics};
+use crate::AppResult;
+use futures::StreamExt;
+use std::future::Future;
+
+/// Number of attempts to resume an interrupted download from the last received byte
+pub const DOWNLOAD_RESUME_ATTEMPTS: usize = 3;
+
+struct ResumableDownloadState<F> {
+    current: FileSystemStream,
+    offset: u64,
+    resume_attempts_left: usize,
+    open_from_offset: F,
+    failed: bool,
+}
+
+/// Wraps a download stream so that on errors the download is requested again
+/// using a ranged request starting from the last received byte.
+pub fn resumable_download_stream<F, Fut>(
+    initial: FileSystemStream,
+    open_from_offset: F,
+) -> FileSystemStream
+where
+    F: Fn(u64) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = AppResult<FileSystemStream>> + Send + 'static,
+{
+    let state = ResumableDownloadState {
+        current: initial,
+        offset: 0,
+        resume_attempts_left: DOWNLOAD_RESUME_ATTEMPTS,
+        open_from_offset,
+        failed: false,
+    };
+    let stream = futures::stream::unfold(state, |mut state| async move {
+        while !state.failed {
+            match state.current.next().await {
+                Some(Ok(bytes)) => {
+                    state.offset += bytes.len() as u64;
+                    return Some((Ok(bytes), state));
+                }
+                Some(Err(_)) if state.resume_attempts_left > 0 => {
+                    state.resume_attempts_left -= 1;
+                    AppMetrics::global().inc(AppMetric::RETRIES, &[("operation", "download")]);
+                    match (state.open_from_offset)(state.offset).await {
+                        Ok(resumed) => state.current = resumed,
+                        Err(err) => {
+                            state.failed = true;
+                            return Some((Err(err), state));
+                        }
+                    }
+                }
+                Some(Err(err)) => {
+                    state.failed = true;
+                    return Some((Err(err), state));
+                }
+                None => return None,
+            }
+        }
+        None
+    });
+    Box::new(sync_wrapper::SyncStream::new(Box::pin(stream)))
+}
+
+/// Formats HTTP Range header value to request the content starting from the offset
+pub fn range_from_offset(offset: u64) -> String {
+    format!("bytes={}-", offset)
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::errors::AppError;
+    use futures::TryStreamExt;
+    use gcloud_sdk::prost::bytes::Bytes;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn resume_from_offset_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let requested_offsets = Arc::new(Mutex::new(Vec::new()));
+        let initial: FileSystemStream = Box::new(futures::stream::iter(vec![
+            Ok(Bytes::from("abc")),
+            Err(AppError::SystemError {
+                message: "connection reset".to_string(),
+            }),
+        ]));
+        let offsets = requested_offsets.clone();
+        let stream = resumable_download_stream(initial, move |offset| {
+            offsets.lock().unwrap().push(offset);
+            async move {
+                Ok(
+                    Box::new(futures::stream::iter(vec![Ok(Bytes::from("def"))]))
+                        as FileSystemStream,
+                )
+            }
+        });
+        let downloaded: Vec<Bytes> = stream.try_collect().await?;
+        assert_eq!(downloaded.concat(), b"abcdef");
+        assert_eq!(*requested_offsets.lock().unwrap(), vec![3]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resume_attempts_exhausted_test() {
+        let failing = || -> FileSystemStream {
+            Box::new(futures::stream::iter(vec![Err(AppError::SystemError {
+                message: "connection reset".to_string(),
+            })]))
+        };
+        let stream = resumable_download_stream(failing(), move |_| async move { Ok(failing()) });
+        let result: AppResult<Vec<Bytes>> = stream.try_collect().await;
+        assert!(result.is_err());
+    }
+}
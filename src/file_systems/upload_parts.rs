@@ -0,0 +1,62 @@
+use crate::AppResult;
+use futures::{Stream, StreamExt};
+use gcloud_sdk::prost::bytes::{Bytes, BytesMut};
+
+/// Splits an upload stream into parts of fixed size (except the last one)
+/// so large files can be uploaded without buffering them in memory completely.
+pub struct UploadPartsReader<S> {
+    input: S,
+    part_size: usize,
+    buffer: BytesMut,
+    finished: bool,
+}
+
+impl<S: Stream<Item = AppResult<Bytes>> + Unpin> UploadPartsReader<S> {
+    pub fn new(input: S, part_size: usize) -> Self {
+        Self {
+            input,
+            part_size,
+            buffer: BytesMut::with_capacity(part_size),
+            finished: false,
+        }
+    }
+
+    /// Returns the next part or None when the input stream is exhausted.
+    pub async fn next_part(&mut self) -> AppResult<Option<Bytes>> {
+        while !self.finished && self.buffer.len() < self.part_size {
+            match self.input.next().await {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk?),
+                None => self.finished = true,
+            }
+        }
+        if self.buffer.is_empty() {
+            Ok(None)
+        } else {
+            let part_len = self.buffer.len().min(self.part_size);
+            Ok(Some(self.buffer.split_to(part_len).freeze()))
+        }
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn next_part_test() -> Result<(), Box<dyn std::error::Error>> {
+        let input = futures::stream::iter(vec![
+            Ok(Bytes::from("abc")),
+            Ok(Bytes::from("defgh")),
+            Ok(Bytes::from("ij")),
+        ]);
+        let mut reader = UploadPartsReader::new(input, 4);
+        assert_eq!(reader.next_part().await?, Some(Bytes::from("abcd")));
+        assert_eq!(reader.next_part().await?, Some(Bytes::from("efgh")));
+        assert_eq!(reader.next_part().await?, Some(Bytes::from("ij")));
+        assert_eq!(reader.next_part().await?, None);
+
+        let mut empty_reader = UploadPartsReader::new(futures::stream::empty(), 4);
+        assert_eq!(empty_reader.next_part().await?, None);
+        Ok(())
+    }
+}
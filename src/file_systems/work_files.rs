@@ -0,0 +1,235 @@
+use crate::errors::AppError;
+use crate::file_systems::FileSystemOptions;
+use crate::AppResult;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use tempfile::TempDir;
+
+/// Temporary files with sensitive content such as prefetched files beyond the memory budget.
+/// Files are kept in a private directory removed when dropped (including failed runs) or in memory,
+/// and optionally encrypted with a random key which exists only in the memory of the process.
+/// Encrypted files are sequences of length-prefixed chunks, so content can be appended.
+pub struct WorkFiles {
+    storage: WorkFilesStorage,
+    cipher: Option<WorkFilesCipher>,
+}
+
+enum WorkFilesStorage {
+    Dir(TempDir),
+    Memory(HashMap<String, Vec<u8>>),
+}
+
+struct WorkFilesCipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl WorkFiles {
+    pub fn new(prefix: &str, options: &FileSystemOptions) -> AppResult<Self> {
+        let storage = if options.work_in_memory {
+            WorkFilesStorage::Memory(HashMap::new())
+        } else {
+            let mut builder = tempfile::Builder::new();
+            builder.prefix(prefix);
+            #[cfg(unix)]
+            builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o700));
+            WorkFilesStorage::Dir(match options.work_dir {
+                Some(ref work_dir) => builder.tempdir_in(work_dir)?,
+                None => builder.tempdir()?,
+            })
+        };
+        let cipher = if options.encrypt_work_files {
+            Some(WorkFilesCipher::new()?)
+        } else {
+            None
+        };
+        Ok(Self { storage, cipher })
+    }
+
+    /// Location for reports, which doesn't reveal file names
+    pub fn location(&self) -> String {
+        let location = match self.storage {
+            WorkFilesStorage::Dir(ref temp_dir) => temp_dir.path().to_string_lossy().to_string(),
+            WorkFilesStorage::Memory(_) => "memory".to_string(),
+        };
+        if self.cipher.is_some() {
+            format!("{} (encrypted)", location)
+        } else {
+            location
+        }
+    }
+
+    /// Appends the content to the file, creating it if it doesn't exist yet,
+    /// so content received in chunks isn't buffered
+    pub fn append(&mut self, relative_path: &str, content: &[u8]) -> AppResult<()> {
+        let content = match self.cipher {
+            Some(ref cipher) => cipher.encrypt_chunk(content.to_vec())?,
+            None => content.to_vec(),
+        };
+        match self.storage {
+            WorkFilesStorage::Dir(ref temp_dir) => {
+                let mut open_options = std::fs::OpenOptions::new();
+                open_options.append(true).create(true);
+                #[cfg(unix)]
+                std::os::unix::fs::OpenOptionsExt::mode(&mut open_options, 0o600);
+                open_options
+                    .open(temp_dir.path().join(Self::file_name(relative_path)))?
+                    .write_all(&content)?;
+            }
+            WorkFilesStorage::Memory(ref mut files) => {
+                files
+                    .entry(relative_path.to_string())
+                    .or_default()
+                    .extend_from_slice(&content);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads and removes the file, so spilled content is kept only until it is used
+    pub fn take(&mut self, relative_path: &str) -> AppResult<Vec<u8>> {
+        let content = match self.storage {
+            WorkFilesStorage::Dir(ref temp_dir) => {
+                let file_path = temp_dir.path().join(Self::file_name(relative_path));
+                let content = std::fs::read(&file_path)?;
+                std::fs::remove_file(&file_path)?;
+                content
+            }
+            WorkFilesStorage::Memory(ref mut files) => {
+                files
+                    .remove(relative_path)
+                    .ok_or_else(|| AppError::SystemError {
+                        message: format!("Work file {} not found", relative_path),
+                    })?
+            }
+        };
+        match self.cipher {
+            Some(ref cipher) => cipher.decrypt_chunks(content),
+            None => Ok(content),
+        }
+    }
+
+    /// Files are stored by hashes of their paths, so neither paths can escape the directory
+    /// nor file names are revealed
+    fn file_name(relative_path: &str) -> String {
+        hex::encode(Sha256::digest(relative_path.as_bytes()))
+    }
+}
+
+impl WorkFilesCipher {
+    fn new() -> AppResult<Self> {
+        let rng = SystemRandom::new();
+        let mut key_bytes = [0u8; 32];
+        rng.fill(&mut key_bytes).map_err(Self::crypto_error)?;
+        let key = UnboundKey::new(&AES_256_GCM, &key_bytes).map_err(Self::crypto_error)?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng,
+        })
+    }
+
+    /// Random nonces are prepended to the encrypted content, and lengths to the chunks
+    fn encrypt_chunk(&self, mut content: Vec<u8>) -> AppResult<Vec<u8>> {
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce_bytes)
+            .map_err(Self::crypto_error)?;
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce_bytes),
+                Aad::empty(),
+                &mut content,
+            )
+            .map_err(Self::crypto_error)?;
+        let mut encrypted = Vec::with_capacity(4 + NONCE_LEN + content.len());
+        encrypted.extend_from_slice(&((NONCE_LEN + content.len()) as u32).to_le_bytes());
+        encrypted.extend_from_slice(&nonce_bytes);
+        encrypted.extend_from_slice(&content);
+        Ok(encrypted)
+    }
+
+    fn decrypt_chunks(&self, encrypted: Vec<u8>) -> AppResult<Vec<u8>> {
+        let mut content = Vec::with_capacity(encrypted.len());
+        let mut remaining = encrypted.as_slice();
+        while !remaining.is_empty() {
+            let (chunk_len, rest) = remaining
+                .split_first_chunk::<4>()
+                .ok_or_else(|| Self::crypto_error(ring::error::Unspecified))?;
+            let chunk_len = u32::from_le_bytes(*chunk_len) as usize;
+            if rest.len() < chunk_len {
+                return Err(Self::crypto_error(ring::error::Unspecified));
+            }
+            let (chunk, rest) = rest.split_at(chunk_len);
+            content.extend_from_slice(&self.decrypt(chunk.to_vec())?);
+            remaining = rest;
+        }
+        Ok(content)
+    }
+
+    fn decrypt(&self, mut encrypted: Vec<u8>) -> AppResult<Vec<u8>> {
+        if encrypted.len() < NONCE_LEN {
+            return Err(Self::crypto_error(ring::error::Unspecified));
+        }
+        let mut content = encrypted.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&encrypted).map_err(Self::crypto_error)?;
+        let content_len = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut content)
+            .map_err(Self::crypto_error)?
+            .len();
+        content.truncate(content_len);
+        Ok(content)
+    }
+
+    fn crypto_error(_: ring::error::Unspecified) -> AppError {
+        AppError::SystemError {
+            message: "Failed to encrypt or decrypt work files".to_string(),
+        }
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn work_files_test() -> AppResult<()> {
+        for (work_in_memory, encrypt_work_files) in
+            [(false, false), (false, true), (true, false), (true, true)]
+        {
+            let options = FileSystemOptions {
+                work_in_memory,
+                encrypt_work_files,
+                ..FileSystemOptions::default()
+            };
+            let mut work_files = WorkFiles::new("work_files_test", &options)?;
+            work_files.append("dir/../file.txt", b"Hello, John")?;
+            if let WorkFilesStorage::Dir(ref temp_dir) = work_files.storage {
+                let stored = std::fs::read(
+                    temp_dir
+                        .path()
+                        .join(WorkFiles::file_name("dir/../file.txt")),
+                )?;
+                assert_eq!(stored == b"Hello, John", !encrypt_work_files);
+                #[cfg(unix)]
+                assert_eq!(
+                    std::os::unix::fs::PermissionsExt::mode(
+                        &std::fs::metadata(temp_dir.path())?.permissions()
+                    ) & 0o777,
+                    0o700
+                );
+            }
+            assert_eq!(work_files.take("dir/../file.txt")?, b"Hello, John");
+            assert!(work_files.take("dir/../file.txt").is_err());
+
+            work_files.append("chunks.txt", b"Hello, ")?;
+            work_files.append("chunks.txt", b"")?;
+            work_files.append("chunks.txt", b"Jane")?;
+            assert_eq!(work_files.take("chunks.txt")?, b"Hello, Jane");
+        }
+        Ok(())
+    }
+}
@@ -1,36 +1,69 @@
 use crate::errors::AppError;
-use crate::file_systems::local::LocalFileSystem;
-use crate::file_systems::{AbsoluteFilePath, FileSystemConnection, FileSystemRef, ListFilesResult};
-use crate::file_tools::FileMatcher;
+use crate::file_systems::{
+    AbsoluteFilePath, FileSystemConnection, FileSystemOptions, FileSystemRef, ListFilesResult,
+};
+use crate::file_tools::{FileMatcher, FileMatcherResult};
 use crate::reporter::AppReporter;
 use crate::AppResult;
 use futures::{Stream, TryStreamExt};
 use gcloud_sdk::prost::bytes::Bytes;
 use rvstruct::ValueStruct;
-use std::io::Write;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use tempfile::TempDir;
 use zip::*;
 
 pub struct ZipFileSystem<'a> {
     zip_file_path: PathBuf,
-    mode: Option<ZipFileSystemMode<'a>>,
+    mode: Option<ZipFileSystemMode>,
+    /// Entries of the existing archive with --zip-append, to skip files copied by previous runs
+    appended_entries: Option<HashMap<String, FileSystemRef>>,
+    options: FileSystemOptions,
     reporter: &'a AppReporter<'a>,
 }
 
-#[allow(clippy::large_enum_variant)]
-enum ZipFileSystemMode<'a> {
+enum ZipFileSystemMode {
     Read {
-        _temp_dir: TempDir,
-        temp_file_system: LocalFileSystem<'a>,
+        archive: ZipArchive<std::fs::File>,
+        entries: Vec<ZipEntry>,
+        entries_by_path: HashMap<String, usize>,
     },
     Write {
-        zip_writer: ZipWriter<std::fs::File>,
+        zip_writer: Box<ZipWriter<std::fs::File>>,
+        appended: Option<ZipAppendedArchive>,
+        /// Entries written by this run, since entries can't be overwritten in the same archive
+        written_entries: HashMap<String, FileSystemRef>,
     },
 }
 
+/// Existing archive updated with --zip-append. Entries are written to a new archive next to it,
+/// which replaces the existing one on close with the entries that weren't replaced,
+/// so failed runs leave the existing archive intact
+struct ZipAppendedArchive {
+    archive: ZipArchive<std::fs::File>,
+    temp_path: tempfile::TempPath,
+    replaced: HashSet<String>,
+}
+
+struct ZipEntry {
+    archive_index: usize,
+    compressed_size: u64,
+    file_ref: FileSystemRef,
+}
+
 impl<'a> ZipFileSystem<'a> {
-    pub async fn new(file_path: &str, reporter: &'a AppReporter<'a>) -> AppResult<Self> {
+    pub const DEFAULT_MAX_UNCOMPRESSED_SIZE: u64 = 16 * 1024 * 1024 * 1024;
+    pub const DEFAULT_MAX_COMPRESSION_RATIO: u64 = 250;
+    /// Small entries such as repeated lines compress well without being zip bombs
+    const MIN_RATIO_CHECKED_SIZE: u64 = 1024 * 1024;
+    const UNIX_FILE_TYPE_MASK: u32 = 0o170000;
+    const UNIX_SYMLINK: u32 = 0o120000;
+
+    pub async fn new(
+        file_path: &str,
+        reporter: &'a AppReporter<'a>,
+        options: &FileSystemOptions,
+    ) -> AppResult<Self> {
         let root_path_base_str = file_path.trim_start_matches("zip://").to_string();
         let root_path_path = PathBuf::from(&root_path_base_str);
         let is_dir = file_path.ends_with('/') || root_path_path.is_dir();
@@ -42,28 +75,179 @@ impl<'a> ZipFileSystem<'a> {
         Ok(Self {
             zip_file_path: root_path_path,
             mode: None,
+            appended_entries: None,
+            options: options.clone(),
             reporter,
         })
     }
 
-    async fn extract_zip_for_read(&mut self) -> Result<(), AppError> {
+    /// Reads only the central directory of the archive. Entries are extracted one by one when downloaded,
+    /// so large archives don't need space for all extracted files up front
+    async fn open_zip_for_read(&mut self) -> Result<(), AppError> {
         if self.mode.is_none() {
             let file = std::fs::File::open(&self.zip_file_path)?;
             let mut archive = ZipArchive::new(file)?;
-            let temp_dir = tempfile::TempDir::with_prefix("redacter")?;
-            archive.extract(temp_dir.path())?;
-            let temp_dir_str = temp_dir.path().to_string_lossy();
-            self.reporter
-                .report(format!("Extracting files to temp dir: {}", temp_dir_str))?;
-            let temp_file_system =
-                LocalFileSystem::new(temp_dir_str.as_ref(), self.reporter).await?;
+            let max_uncompressed_size = self
+                .options
+                .zip_max_uncompressed_size
+                .unwrap_or(Self::DEFAULT_MAX_UNCOMPRESSED_SIZE);
+            let max_compression_ratio = self
+                .options
+                .zip_max_compression_ratio
+                .unwrap_or(Self::DEFAULT_MAX_COMPRESSION_RATIO);
+            let mut entries = Vec::with_capacity(archive.len());
+            let mut entries_by_path = HashMap::with_capacity(archive.len());
+            let mut total_uncompressed_size: u64 = 0;
+            for archive_index in 0..archive.len() {
+                let entry = archive.by_index_raw(archive_index)?;
+                if entry.is_dir() {
+                    continue;
+                }
+                // Zip-slip: entries with absolute paths or paths outside of the archive root,
+                // and symbolic links which could point outside of it
+                let maybe_entry_path = entry.enclosed_name().filter(|_| {
+                    entry.unix_mode().map_or(true, |mode| {
+                        mode & Self::UNIX_FILE_TYPE_MASK != Self::UNIX_SYMLINK
+                    })
+                });
+                let Some(entry_path) = maybe_entry_path else {
+                    self.reporter
+                        .report(format!("Skipping unsafe zip entry: {}", entry.name()))?;
+                    tracing::warn!(entry = entry.name(), "Unsafe zip entry skipped");
+                    continue;
+                };
+                if entry.size() >= Self::MIN_RATIO_CHECKED_SIZE
+                    && entry.size()
+                        > entry
+                            .compressed_size()
+                            .saturating_mul(max_compression_ratio)
+                {
+                    return Err(AppError::ZipArchiveLimitExceeded {
+                        zip_path: self.zip_file_path.to_string_lossy().to_string(),
+                        reason: format!(
+                            "entry {} is compressed more than {} times",
+                            entry.name(),
+                            max_compression_ratio
+                        ),
+                    });
+                }
+                total_uncompressed_size = total_uncompressed_size.saturating_add(entry.size());
+                if total_uncompressed_size > max_uncompressed_size {
+                    return Err(AppError::ZipArchiveLimitExceeded {
+                        zip_path: self.zip_file_path.to_string_lossy().to_string(),
+                        reason: format!(
+                            "uncompressed size is more than {} bytes",
+                            max_uncompressed_size
+                        ),
+                    });
+                }
+                let relative_path = entry_path.to_string_lossy().replace('\\', "/");
+                if entries_by_path.contains_key(&relative_path) {
+                    continue;
+                }
+                entries_by_path.insert(relative_path.clone(), entries.len());
+                entries.push(ZipEntry {
+                    archive_index,
+                    compressed_size: entry.compressed_size(),
+                    file_ref: FileSystemRef {
+                        relative_path: relative_path.as_str().into(),
+                        media_type: mime_guess::from_path(&entry_path).first(),
+                        file_size: Some(entry.size() as usize),
+                        modified_at: None,
+                        checksum: None,
+                    },
+                });
+            }
             self.mode = Some(ZipFileSystemMode::Read {
-                _temp_dir: temp_dir,
-                temp_file_system,
+                archive,
+                entries,
+                entries_by_path,
+            });
+        }
+        Ok(())
+    }
+
+    fn open_zip_for_write(&mut self) -> AppResult<()> {
+        if self.mode.is_none() {
+            let (zip_file, appended) = if !self.zip_file_path.exists() {
+                (std::fs::File::create_new(&self.zip_file_path)?, None)
+            } else if self.options.zip_append {
+                let archive = ZipArchive::new(std::fs::File::open(&self.zip_file_path)?)?;
+                let temp_dir = self
+                    .zip_file_path
+                    .parent()
+                    .filter(|parent| !parent.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."));
+                let (temp_file, temp_path) =
+                    tempfile::NamedTempFile::new_in(temp_dir)?.into_parts();
+                self.reporter.report(format!(
+                    "Appending files to the existing zip file with {} files",
+                    archive.len()
+                ))?;
+                (
+                    temp_file,
+                    Some(ZipAppendedArchive {
+                        archive,
+                        temp_path,
+                        replaced: HashSet::new(),
+                    }),
+                )
+            } else {
+                return Err(AppError::FileSystemError {
+                    scheme: "zip".to_string(),
+                    path: self.zip_file_path.to_string_lossy().to_string(),
+                    message: "Zip file already exists. Use --zip-append to add files to it".into(),
+                });
+            };
+            self.mode = Some(ZipFileSystemMode::Write {
+                zip_writer: Box::new(ZipWriter::new(zip_file)),
+                appended,
+                written_entries: HashMap::new(),
             });
         }
         Ok(())
     }
+
+    fn read_appended_entries(&mut self) -> AppResult<&HashMap<String, FileSystemRef>> {
+        if self.appended_entries.is_none() {
+            let mut appended_entries = HashMap::new();
+            if self.zip_file_path.exists() {
+                let mut archive = ZipArchive::new(std::fs::File::open(&self.zip_file_path)?)?;
+                for archive_index in 0..archive.len() {
+                    let entry = archive.by_index_raw(archive_index)?;
+                    if entry.is_dir() {
+                        continue;
+                    }
+                    // Times of entries don't have time zones and are written in UTC
+                    let modified_at = entry.last_modified().and_then(|modified_at| {
+                        chrono::NaiveDate::from_ymd_opt(
+                            modified_at.year() as i32,
+                            modified_at.month() as u32,
+                            modified_at.day() as u32,
+                        )?
+                        .and_hms_opt(
+                            modified_at.hour() as u32,
+                            modified_at.minute() as u32,
+                            modified_at.second() as u32,
+                        )
+                        .map(|modified_at| modified_at.and_utc())
+                    });
+                    appended_entries.insert(
+                        entry.name().to_string(),
+                        FileSystemRef {
+                            relative_path: entry.name().into(),
+                            media_type: mime_guess::from_path(entry.name()).first(),
+                            file_size: Some(entry.size() as usize),
+                            modified_at,
+                            checksum: None,
+                        },
+                    );
+                }
+            }
+            self.appended_entries = Some(appended_entries);
+        }
+        Ok(self.appended_entries.get_or_insert_with(HashMap::new))
+    }
 }
 
 impl<'a> FileSystemConnection<'a> for ZipFileSystem<'a> {
@@ -74,13 +258,45 @@ impl<'a> FileSystemConnection<'a> for ZipFileSystem<'a> {
         FileSystemRef,
         Box<dyn Stream<Item = AppResult<Bytes>> + Send + Sync + Unpin + 'static>,
     )> {
-        self.extract_zip_for_read().await?;
+        self.open_zip_for_read().await?;
         match self.mode {
             Some(ZipFileSystemMode::Read {
-                _temp_dir: _,
-                ref mut temp_file_system,
+                ref mut archive,
+                ref entries,
+                ref entries_by_path,
             }) => match file_ref {
-                Some(file_ref) => temp_file_system.download(Some(file_ref)).await,
+                Some(file_ref) => {
+                    let relative_path = file_ref.relative_path.value();
+                    let entry = entries_by_path
+                        .get(relative_path)
+                        .map(|idx| &entries[*idx])
+                        .ok_or_else(|| AppError::FileSystemError {
+                            scheme: "zip".to_string(),
+                            path: self.zip_file_path.to_string_lossy().to_string(),
+                            message: format!("File {} not found in the zip file", relative_path),
+                        })?;
+                    let declared_size = entry.file_ref.file_size.unwrap_or(0) as u64;
+                    // Sizes in headers can be forged, so extraction stops right after the declared size.
+                    // Declared sizes are within the archive limits checked when the archive is opened
+                    let mut content = Vec::with_capacity(declared_size as usize);
+                    archive
+                        .by_index(entry.archive_index)?
+                        .take(declared_size + 1)
+                        .read_to_end(&mut content)?;
+                    if content.len() as u64 > declared_size {
+                        return Err(AppError::ZipArchiveLimitExceeded {
+                            zip_path: self.zip_file_path.to_string_lossy().to_string(),
+                            reason: format!(
+                                "entry {} is larger than its declared size of {} bytes ({} bytes compressed)",
+                                relative_path, declared_size, entry.compressed_size
+                            ),
+                        });
+                    }
+                    Ok((
+                        entry.file_ref.clone(),
+                        Box::new(futures::stream::iter(vec![Ok(Bytes::from(content))])),
+                    ))
+                }
                 None => Err(AppError::SystemError {
                     message: "FileSystemRef is required for ZipFileSystem".into(),
                 }),
@@ -96,28 +312,46 @@ impl<'a> FileSystemConnection<'a> for ZipFileSystem<'a> {
         mut input: S,
         file_ref: Option<&FileSystemRef>,
     ) -> AppResult<()> {
-        if self.mode.is_none() {
-            let zip_file = if self.zip_file_path.exists() {
-                return Err(AppError::SystemError {
-                    message: "Zip file already exists".into(),
-                });
-            } else {
-                std::fs::File::create_new(&self.zip_file_path)?
-            };
-
-            let zip_writer = ZipWriter::new(zip_file);
-            self.mode = Some(ZipFileSystemMode::Write { zip_writer });
-        }
+        self.open_zip_for_write()?;
         match self.mode {
-            Some(ZipFileSystemMode::Write { ref mut zip_writer }) => match file_ref {
+            Some(ZipFileSystemMode::Write {
+                ref mut zip_writer,
+                ref mut appended,
+                ref mut written_entries,
+            }) => match file_ref {
                 Some(file_ref) => {
                     let file_path = Path::new(file_ref.relative_path.value());
                     let file_path_str = file_path.to_string_lossy().to_string();
+                    if written_entries.contains_key(&file_path_str) {
+                        return Err(AppError::FileSystemError {
+                            scheme: "zip".to_string(),
+                            path: self.zip_file_path.to_string_lossy().to_string(),
+                            message: format!(
+                                "File {} is already written to the zip file. Use --on-conflict to skip or rename such files",
+                                file_path_str
+                            ),
+                        });
+                    }
+                    if let Some(ref mut appended) = appended {
+                        appended.replaced.insert(file_path_str.clone());
+                    }
                     let file_options = zip::write::FullFileOptions::default();
-                    zip_writer.start_file(file_path_str, file_options)?;
+                    zip_writer.start_file(file_path_str.clone(), file_options)?;
+                    let mut written_size = 0;
                     while let Some(chunk) = input.try_next().await? {
                         zip_writer.write_all(&chunk)?;
+                        written_size += chunk.len();
                     }
+                    written_entries.insert(
+                        file_path_str.clone(),
+                        FileSystemRef {
+                            relative_path: file_path_str.into(),
+                            media_type: file_ref.media_type.clone(),
+                            file_size: Some(written_size),
+                            modified_at: Some(chrono::Utc::now()),
+                            checksum: None,
+                        },
+                    );
                     Ok(())
                 }
                 None => Err(AppError::SystemError {
@@ -135,15 +369,24 @@ impl<'a> FileSystemConnection<'a> for ZipFileSystem<'a> {
         file_matcher: Option<&FileMatcher>,
         max_files_limit: Option<usize>,
     ) -> AppResult<ListFilesResult> {
-        self.extract_zip_for_read().await?;
+        self.open_zip_for_read().await?;
         match self.mode {
-            Some(ZipFileSystemMode::Read {
-                _temp_dir: _,
-                ref mut temp_file_system,
-            }) => {
-                temp_file_system
-                    .list_files(file_matcher, max_files_limit)
-                    .await
+            Some(ZipFileSystemMode::Read { ref entries, .. }) => {
+                let mut files = Vec::new();
+                let mut skipped = 0;
+                for file_ref in entries.iter().map(|entry| &entry.file_ref) {
+                    if max_files_limit.is_some_and(|limit| files.len() >= limit) {
+                        break;
+                    }
+                    if file_matcher.iter().all(|matcher| {
+                        matches!(matcher.matches(file_ref), FileMatcherResult::Matched)
+                    }) {
+                        files.push(file_ref.clone());
+                    } else {
+                        skipped += 1;
+                    }
+                }
+                Ok(ListFilesResult { files, skipped })
             }
             _ => Err(AppError::SystemError {
                 message: "ZipFileSystem is not in read mode".into(),
@@ -151,9 +394,68 @@ impl<'a> FileSystemConnection<'a> for ZipFileSystem<'a> {
         }
     }
 
+    async fn get_file_ref(
+        &mut self,
+        file_ref: Option<&FileSystemRef>,
+    ) -> AppResult<Option<FileSystemRef>> {
+        match self.mode {
+            Some(ZipFileSystemMode::Read {
+                ref entries,
+                ref entries_by_path,
+                ..
+            }) => Ok(file_ref.and_then(|file_ref| {
+                entries_by_path
+                    .get(file_ref.relative_path.value())
+                    .map(|idx| entries[*idx].file_ref.clone())
+            })),
+            Some(ZipFileSystemMode::Write {
+                ref written_entries,
+                ..
+            }) if file_ref.is_some_and(|file_ref| {
+                written_entries.contains_key(file_ref.relative_path.value())
+            }) =>
+            {
+                Ok(file_ref.and_then(|file_ref| {
+                    written_entries.get(file_ref.relative_path.value()).cloned()
+                }))
+            }
+            // Zip files are created from scratch unless appended
+            _ if self.options.zip_append => match file_ref {
+                Some(file_ref) => Ok(self
+                    .read_appended_entries()?
+                    .get(file_ref.relative_path.value())
+                    .cloned()),
+                None => Ok(None),
+            },
+            _ => Ok(None),
+        }
+    }
+
     async fn close(mut self) -> AppResult<()> {
-        if let Some(ZipFileSystemMode::Write { zip_writer }) = self.mode {
-            zip_writer.finish()?;
+        if let Some(ZipFileSystemMode::Write {
+            mut zip_writer,
+            appended,
+            ..
+        }) = self.mode.take()
+        {
+            match appended {
+                Some(mut appended) => {
+                    for archive_index in 0..appended.archive.len() {
+                        let entry = appended.archive.by_index_raw(archive_index)?;
+                        if !appended.replaced.contains(entry.name()) {
+                            zip_writer.raw_copy_file(entry)?;
+                        }
+                    }
+                    zip_writer.finish()?;
+                    appended
+                        .temp_path
+                        .persist(&self.zip_file_path)
+                        .map_err(|err| AppError::InputOutputError(err.error))?;
+                }
+                None => {
+                    zip_writer.finish()?;
+                }
+            }
         }
         self.mode = None;
         Ok(())
@@ -203,6 +505,7 @@ mod tests {
         let mut fs = ZipFileSystem::new(
             &format!("zip://{}", zip_file_path.to_string_lossy()),
             &reporter,
+            &FileSystemOptions::default(),
         )
         .await?;
         let (file_ref, stream) = fs
@@ -210,6 +513,8 @@ mod tests {
                 relative_path: "file1.txt".into(),
                 media_type: None,
                 file_size: None,
+                modified_at: None,
+                checksum: None,
             }))
             .await?;
         let downloaded_bytes: Vec<bytes::Bytes> = stream.try_collect().await?;
@@ -236,6 +541,7 @@ mod tests {
         let mut fs = ZipFileSystem::new(
             &format!("zip://{}", zip_file_path.to_string_lossy()),
             &reporter,
+            &FileSystemOptions::default(),
         )
         .await?;
 
@@ -247,10 +553,34 @@ mod tests {
                 relative_path: "file1.txt".into(),
                 media_type: None,
                 file_size: None,
+                modified_at: None,
+                checksum: None,
             }),
         )
         .await?;
 
+        // Written entries are reported as existing, since they can't be written again
+        let written_file_ref = FileSystemRef {
+            relative_path: "file1.txt".into(),
+            media_type: None,
+            file_size: None,
+            modified_at: None,
+            checksum: None,
+        };
+        assert_eq!(
+            fs.get_file_ref(Some(&written_file_ref))
+                .await?
+                .and_then(|file_ref| file_ref.file_size),
+            Some(test_content.len())
+        );
+        assert!(fs
+            .upload(
+                futures::stream::iter(vec![Ok(bytes::Bytes::from(test_content.to_vec()))]),
+                Some(&written_file_ref),
+            )
+            .await
+            .is_err());
+
         fs.close().await?;
 
         let mut zip = ZipArchive::new(std::fs::File::open(&zip_file_path)?)?;
@@ -262,6 +592,84 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn append_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let term = console::Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let temp_dir = TempDir::with_prefix("zip_file_system_tests_append")?;
+        let zip_file_path = temp_dir.path().join("test.zip");
+        let zip_file_uri = format!("zip://{}", zip_file_path.to_string_lossy());
+        let test_file_ref = |relative_path: &str| FileSystemRef {
+            relative_path: relative_path.into(),
+            media_type: None,
+            file_size: None,
+            modified_at: None,
+            checksum: None,
+        };
+        let content_stream = |content: &'static [u8]| {
+            futures::stream::iter(vec![Ok(bytes::Bytes::from_static(content))])
+        };
+
+        let mut fs =
+            ZipFileSystem::new(&zip_file_uri, &reporter, &FileSystemOptions::default()).await?;
+        fs.upload(content_stream(b"first"), Some(&test_file_ref("file1.txt")))
+            .await?;
+        fs.upload(content_stream(b"second"), Some(&test_file_ref("file2.txt")))
+            .await?;
+        fs.close().await?;
+
+        let mut fs =
+            ZipFileSystem::new(&zip_file_uri, &reporter, &FileSystemOptions::default()).await?;
+        assert!(fs
+            .upload(content_stream(b"third"), Some(&test_file_ref("file3.txt")))
+            .await
+            .is_err());
+
+        let append_options = FileSystemOptions {
+            zip_append: true,
+            ..FileSystemOptions::default()
+        };
+        let mut fs = ZipFileSystem::new(&zip_file_uri, &reporter, &append_options).await?;
+        let existing_file_ref = fs.get_file_ref(Some(&test_file_ref("file1.txt"))).await?;
+        assert_eq!(
+            existing_file_ref.and_then(|file_ref| file_ref.file_size),
+            Some(5)
+        );
+        assert!(fs
+            .get_file_ref(Some(&test_file_ref("file3.txt")))
+            .await?
+            .is_none());
+        fs.upload(
+            content_stream(b"second updated"),
+            Some(&test_file_ref("file2.txt")),
+        )
+        .await?;
+        fs.upload(content_stream(b"third"), Some(&test_file_ref("file3.txt")))
+            .await?;
+        fs.close().await?;
+
+        let mut zip = ZipArchive::new(std::fs::File::open(&zip_file_path)?)?;
+        let mut contents = Vec::new();
+        for idx in 0..zip.len() {
+            let mut file = zip.by_index(idx)?;
+            let mut content = String::new();
+            file.read_to_string(&mut content)?;
+            contents.push((file.name().to_string(), content));
+        }
+        contents.sort();
+        assert_eq!(
+            contents,
+            vec![
+                ("file1.txt".to_string(), "first".to_string()),
+                ("file2.txt".to_string(), "second updated".to_string()),
+                ("file3.txt".to_string(), "third".to_string()),
+            ]
+        );
+        assert_eq!(std::fs::read_dir(temp_dir.path())?.count(), 1);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn list_files_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let term = console::Term::stdout();
@@ -277,6 +685,7 @@ mod tests {
         let mut fs = ZipFileSystem::new(
             &format!("zip://{}", zip_file_path.to_string_lossy()),
             &reporter,
+            &FileSystemOptions::default(),
         )
         .await?;
         let list_files_result = fs.list_files(None, None).await?;
@@ -287,4 +696,70 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn unsafe_entries_and_limits_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    {
+        let term = console::Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let temp_dir = TempDir::with_prefix("zip_file_system_tests_limits")?;
+        let zip_file_path = temp_dir.path().join("test.zip");
+        let mut zip = ZipWriter::new(std::fs::File::create(&zip_file_path)?);
+        zip.start_file("../outside.txt", zip::write::SimpleFileOptions::default())?;
+        zip.write_all(b"outside")?;
+        zip.start_file("/absolute.txt", zip::write::SimpleFileOptions::default())?;
+        zip.write_all(b"absolute")?;
+        zip.start_file("dir/inside.txt", zip::write::SimpleFileOptions::default())?;
+        zip.write_all(b"inside")?;
+        zip.start_file("zeros.bin", zip::write::SimpleFileOptions::default())?;
+        zip.write_all(&vec![0; 2 * 1024 * 1024])?;
+        zip.finish()?;
+        let zip_file_uri = format!("zip://{}", zip_file_path.to_string_lossy());
+
+        let mut fs =
+            ZipFileSystem::new(&zip_file_uri, &reporter, &FileSystemOptions::default()).await?;
+        assert!(matches!(
+            fs.list_files(None, None).await,
+            Err(AppError::ZipArchiveLimitExceeded { .. })
+        ));
+
+        let mut fs = ZipFileSystem::new(
+            &zip_file_uri,
+            &reporter,
+            &FileSystemOptions {
+                zip_max_compression_ratio: Some(10000),
+                ..FileSystemOptions::default()
+            },
+        )
+        .await?;
+        let list_files_result = fs.list_files(None, None).await?;
+        let mut listed_paths: Vec<String> = list_files_result
+            .files
+            .iter()
+            .map(|file_ref| file_ref.relative_path.value().clone())
+            .collect();
+        listed_paths.sort();
+        assert_eq!(listed_paths, vec!["dir/inside.txt", "zeros.bin"]);
+        let (_, stream) = fs.download(list_files_result.files.first()).await?;
+        let downloaded_bytes: Vec<bytes::Bytes> = stream.try_collect().await?;
+        assert_eq!(downloaded_bytes.concat(), b"inside");
+        fs.close().await?;
+
+        let mut fs = ZipFileSystem::new(
+            &zip_file_uri,
+            &reporter,
+            &FileSystemOptions {
+                zip_max_uncompressed_size: Some(1024),
+                zip_max_compression_ratio: Some(10000),
+                ..FileSystemOptions::default()
+            },
+        )
+        .await?;
+        assert!(matches!(
+            fs.list_files(None, None).await,
+            Err(AppError::ZipArchiveLimitExceeded { .. })
+        ));
+
+        Ok(())
+    }
 }
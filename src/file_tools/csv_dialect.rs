@@ -0,0 +1,290 @@
+/// CSV conventions detected from the beginning of a file,
+/// so redacted files are written back the same way as the original ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsvDialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub escape: Option<u8>,
+    pub quote_all: bool,
+    pub crlf: bool,
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            escape: None,
+            quote_all: false,
+            crlf: false,
+        }
+    }
+}
+
+impl CsvDialect {
+    /// Number of bytes from the beginning of a file used for detection
+    pub const SNIFF_SIZE: usize = 1024;
+
+    const DELIMITERS: [u8; 4] = [b',', b'\t', b';', b'|'];
+    const QUOTES: [u8; 2] = [b'"', b'\''];
+
+    /// Detects the dialect of the content. The delimiter is detected only
+    /// when it isn't specified explicitly.
+    pub fn sniff(content: &[u8], delimiter: Option<u8>) -> Self {
+        let sample = &content[..content.len().min(Self::SNIFF_SIZE)];
+        let mut lines: Vec<&[u8]> = sample
+            .split(|c| *c == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .collect();
+        if content.len() > Self::SNIFF_SIZE && lines.len() > 1 {
+            // The last line is most likely truncated
+            lines.pop();
+        }
+        lines.retain(|line| !line.is_empty());
+
+        let quote = Self::sniff_quote(&lines);
+        let delimiter = delimiter.unwrap_or_else(|| Self::sniff_delimiter(&lines, quote));
+        let quote_all = lines.first().is_some_and(|line| {
+            Self::split_fields(line, delimiter, quote)
+                .iter()
+                .all(|field| field.first() == Some(&quote))
+        });
+        let escape = sample
+            .windows(3)
+            .any(|w| {
+                w[0] == b'\\'
+                    && w[1] == quote
+                    && w[2] != delimiter
+                    && w[2] != b'\r'
+                    && w[2] != b'\n'
+            })
+            .then_some(b'\\');
+
+        Self {
+            delimiter,
+            quote,
+            escape,
+            quote_all,
+            crlf: sample.windows(2).any(|w| w == b"\r\n"),
+        }
+    }
+
+    fn sniff_quote(lines: &[&[u8]]) -> u8 {
+        Self::QUOTES
+            .iter()
+            .enumerate()
+            .map(|(position, quote)| {
+                let count: usize = lines
+                    .iter()
+                    .map(|line| Self::count_quoted_fields(line, *quote))
+                    .sum();
+                (*quote, count, position)
+            })
+            .filter(|(_, count, _)| *count > 0)
+            // Double quotes are preferred on ties
+            .max_by_key(|(_, count, position)| (*count, std::cmp::Reverse(*position)))
+            .map(|(quote, _, _)| quote)
+            .unwrap_or(b'"')
+    }
+
+    /// Counts fields starting with the quote and closed by it right before a delimiter
+    /// or the end of the line, so apostrophes in values such as `'90s` aren't quotes
+    fn count_quoted_fields(line: &[u8], quote: u8) -> usize {
+        let mut count = 0;
+        let mut idx = 0;
+        while idx < line.len() {
+            if line[idx] != quote || (idx > 0 && !Self::DELIMITERS.contains(&line[idx - 1])) {
+                idx += 1;
+                continue;
+            }
+            let mut end = idx + 1;
+            let closing = loop {
+                match line.get(end) {
+                    Some(b'\\') => end += 2,
+                    Some(c) if *c == quote && line.get(end + 1) == Some(&quote) => end += 2,
+                    Some(c) if *c == quote => break Some(end),
+                    Some(_) => end += 1,
+                    None => break None,
+                }
+            };
+            match closing {
+                Some(end)
+                    if line
+                        .get(end + 1)
+                        .map_or(true, |next| Self::DELIMITERS.contains(next)) =>
+                {
+                    count += 1;
+                    idx = end + 1;
+                }
+                _ => idx += 1,
+            }
+        }
+        count
+    }
+
+    fn sniff_delimiter(lines: &[&[u8]], quote: u8) -> u8 {
+        let counts: Vec<(u8, Vec<usize>)> = Self::DELIMITERS
+            .iter()
+            .map(|delimiter| {
+                (
+                    *delimiter,
+                    lines
+                        .iter()
+                        .map(|line| Self::split_fields(line, *delimiter, quote).len() - 1)
+                        .collect(),
+                )
+            })
+            .collect();
+        // Prefer delimiters that split every line into the same number of fields
+        let consistent = counts
+            .iter()
+            .filter(|(_, line_counts)| {
+                line_counts.first().is_some_and(|first| *first > 0)
+                    && line_counts.iter().all(|count| count == &line_counts[0])
+            })
+            .max_by_key(|(delimiter, line_counts)| {
+                (
+                    line_counts[0],
+                    std::cmp::Reverse(Self::DELIMITERS.iter().position(|d| d == delimiter)),
+                )
+            })
+            .map(|(delimiter, _)| *delimiter);
+        consistent
+            .or_else(|| {
+                counts
+                    .iter()
+                    .map(|(delimiter, line_counts)| (*delimiter, line_counts.iter().sum::<usize>()))
+                    .filter(|(_, total)| *total > 0)
+                    .max_by_key(|(delimiter, total)| {
+                        (
+                            *total,
+                            std::cmp::Reverse(Self::DELIMITERS.iter().position(|d| d == delimiter)),
+                        )
+                    })
+                    .map(|(delimiter, _)| delimiter)
+            })
+            .unwrap_or(b',')
+    }
+
+    fn split_fields(line: &[u8], delimiter: u8, quote: u8) -> Vec<&[u8]> {
+        let mut fields = Vec::new();
+        let mut in_quotes = false;
+        let mut start = 0;
+        for (idx, c) in line.iter().enumerate() {
+            if *c == quote {
+                in_quotes = !in_quotes;
+            } else if *c == delimiter && !in_quotes {
+                fields.push(&line[start..idx]);
+                start = idx + 1;
+            }
+        }
+        fields.push(&line[start..]);
+        fields
+    }
+
+    pub fn reader_builder(&self, has_headers: bool) -> csv_async::AsyncReaderBuilder {
+        let mut builder = csv_async::AsyncReaderBuilder::new();
+        builder
+            .has_headers(has_headers)
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .escape(self.escape)
+            .double_quote(self.escape.is_none());
+        builder
+    }
+
+    pub fn writer_builder(&self) -> csv_async::AsyncWriterBuilder {
+        let mut builder = csv_async::AsyncWriterBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .quote_style(if self.quote_all {
+                csv_async::QuoteStyle::Always
+            } else {
+                csv_async::QuoteStyle::Necessary
+            })
+            .terminator(if self.crlf {
+                csv_async::Terminator::CRLF
+            } else {
+                csv_async::Terminator::Any(b'\n')
+            });
+        if let Some(escape) = self.escape {
+            builder.double_quote(false).escape(escape);
+        }
+        builder
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+
+    #[test]
+    fn sniff_delimiter_test() {
+        assert_eq!(
+            CsvDialect::sniff(b"id;name;note\n1;John;\"a, b\"\n2;Jane;c\n", None),
+            CsvDialect {
+                delimiter: b';',
+                ..CsvDialect::default()
+            }
+        );
+        assert_eq!(
+            CsvDialect::sniff(b"id\tname\n1\tJohn, Jr.\n", None).delimiter,
+            b'\t'
+        );
+        assert_eq!(
+            CsvDialect::sniff(b"id,name\n1,John\n", None).delimiter,
+            b','
+        );
+        assert_eq!(CsvDialect::sniff(b"single column\n", None).delimiter, b',');
+        assert_eq!(
+            CsvDialect::sniff(b"a;b,c\n", Some(b'|')).delimiter,
+            b'|',
+            "Explicit delimiter is not overridden"
+        );
+    }
+
+    #[test]
+    fn sniff_quoting_test() {
+        let dialect = CsvDialect::sniff(b"'id','name'\r\n'1','John'\r\n", None);
+        assert_eq!(dialect.quote, b'\'');
+        assert!(dialect.quote_all);
+        assert!(dialect.crlf);
+
+        let escaped = CsvDialect::sniff(b"id,note\n1,\"say \\\"hi\\\" now\"\n", None);
+        assert_eq!(escaped.escape, Some(b'\\'));
+        assert!(!escaped.quote_all);
+    }
+
+    #[test]
+    fn sniff_apostrophes_test() {
+        assert_eq!(CsvDialect::sniff(b"1,'90s,x\n2,'80s,y\n", None).quote, b'"');
+        assert_eq!(
+            CsvDialect::sniff(b"id,note\n1,'90s,'80s\n2,\"a, b\",'c'\n", None).quote,
+            b'"'
+        );
+        let ties = CsvDialect::sniff(b"\"a\",'b'\n", None);
+        assert_eq!(ties.quote, b'"');
+        assert_eq!(ties.delimiter, b',');
+        assert_eq!(CsvDialect::sniff(b"'a''s',b\n'c',d\n", None).quote, b'\'');
+    }
+
+    #[tokio::test]
+    async fn round_trip_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let original = b"\"id\";\"name\"\r\n\"1\";\"John; Jr.\"\r\n";
+        let dialect = CsvDialect::sniff(original, None);
+        let mut reader = dialect
+            .reader_builder(false)
+            .create_reader(original.as_slice());
+        let records: Vec<csv_async::StringRecord> = reader.records().try_collect().await?;
+
+        let mut writer = dialect.writer_builder().create_writer(vec![]);
+        for record in records {
+            writer.write_record(&record).await?;
+        }
+        writer.flush().await?;
+        assert_eq!(writer.into_inner().await?, original.to_vec());
+        Ok(())
+    }
+}
@@ -1,9 +1,95 @@
+use crate::errors::AppError;
 use crate::file_systems::FileSystemRef;
+use crate::AppResult;
+use chrono::{DateTime, Utc};
 use rvstruct::ValueStruct;
+use std::path::Path;
+
+/// Filters of files: files are matched if they match any of the include globs (if specified),
+/// don't match exclude globs and ignore patterns, and match media types and modification time (if specified).
+/// Files without known media types or modification time don't match filters by them.
+#[derive(Debug, Clone, Default)]
+pub struct FileFilters {
+    pub include: Vec<globset::Glob>,
+    pub exclude: Vec<globset::Glob>,
+    pub ignore_patterns: Option<FileIgnorePatterns>,
+    /// Media types such as `application/pdf` or patterns such as `image/*`
+    pub media_types: Vec<mime::Mime>,
+    pub modified_after: Option<DateTime<Utc>>,
+    pub modified_before: Option<DateTime<Utc>>,
+}
+
+/// Exclude patterns in the gitignore format (such as `.redacterignore` files):
+/// patterns without slashes match in any directory, a leading slash anchors patterns to the root,
+/// a trailing slash matches only directories, and `!` re-includes files excluded by previous patterns.
+#[derive(Debug, Clone)]
+pub struct FileIgnorePatterns {
+    patterns: Vec<(globset::GlobMatcher, bool)>,
+}
+
+impl FileIgnorePatterns {
+    pub fn parse(content: &str) -> AppResult<Self> {
+        let mut patterns = Vec::new();
+        for line in content.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (pattern, negated) = match line.strip_prefix('!') {
+                Some(pattern) => (pattern, true),
+                None => (line.strip_prefix('\\').unwrap_or(line), false),
+            };
+            let (pattern, directory_only) = match pattern.strip_suffix('/') {
+                Some(pattern) => (pattern, true),
+                None => (pattern, false),
+            };
+            let pattern = match pattern.strip_prefix('/') {
+                Some(anchored_pattern) => anchored_pattern.to_string(),
+                None if pattern.contains('/') => pattern.to_string(),
+                None => format!("**/{}", pattern),
+            };
+            // Patterns matching directories match all files inside them
+            let mut globs = vec![format!("{}/**", pattern)];
+            if !directory_only {
+                globs.push(pattern);
+            }
+            for glob in globs {
+                let matcher = globset::GlobBuilder::new(&glob)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|e| AppError::ConfigError {
+                        message: format!("Invalid ignore pattern '{}': {}", line, e),
+                    })?
+                    .compile_matcher();
+                patterns.push((matcher, negated));
+            }
+        }
+        Ok(Self { patterns })
+    }
+
+    pub fn from_file(path: &Path) -> AppResult<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// The last matching pattern decides if the file is ignored
+    pub fn is_ignored(&self, relative_path: &str) -> bool {
+        let relative_path = relative_path.trim_start_matches('/');
+        self.patterns
+            .iter()
+            .rev()
+            .find(|(matcher, _)| matcher.is_match(relative_path))
+            .is_some_and(|(_, negated)| !negated)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct FileMatcher {
-    pub filename_matcher: Option<globset::GlobMatcher>,
+    pub include_matchers: Vec<globset::GlobMatcher>,
+    pub exclude_matchers: Vec<globset::GlobMatcher>,
+    pub ignore_patterns: Option<FileIgnorePatterns>,
+    pub media_types: Vec<mime::Mime>,
+    pub modified_after: Option<DateTime<Utc>>,
+    pub modified_before: Option<DateTime<Utc>>,
     pub max_size_limit: Option<usize>,
 }
 
@@ -12,15 +98,27 @@ pub enum FileMatcherResult {
     Matched,
     SkippedDueToSize,
     SkippedDueToName,
+    SkippedDueToMediaType,
+    SkippedDueToModificationTime,
 }
 
 impl FileMatcher {
-    pub fn new(
-        filename_matcher: Option<globset::GlobMatcher>,
-        max_size_limit: Option<usize>,
-    ) -> Self {
+    pub fn new(file_filters: FileFilters, max_size_limit: Option<usize>) -> Self {
         FileMatcher {
-            filename_matcher,
+            include_matchers: file_filters
+                .include
+                .iter()
+                .map(|glob| glob.compile_matcher())
+                .collect(),
+            exclude_matchers: file_filters
+                .exclude
+                .iter()
+                .map(|glob| glob.compile_matcher())
+                .collect(),
+            ignore_patterns: file_filters.ignore_patterns,
+            media_types: file_filters.media_types,
+            modified_after: file_filters.modified_after,
+            modified_before: file_filters.modified_before,
             max_size_limit,
         }
     }
@@ -34,14 +132,57 @@ impl FileMatcher {
             }
         }
 
-        if let Some(filename_matcher) = &self.filename_matcher {
-            if !filename_matcher.is_match(file_ref.relative_path.value().as_str()) {
-                return FileMatcherResult::SkippedDueToName;
+        let relative_path = file_ref.relative_path.value().as_str();
+        if !self.include_matchers.is_empty()
+            && !self
+                .include_matchers
+                .iter()
+                .any(|matcher| matcher.is_match(relative_path))
+        {
+            return FileMatcherResult::SkippedDueToName;
+        }
+        if self
+            .exclude_matchers
+            .iter()
+            .any(|matcher| matcher.is_match(relative_path))
+            || self
+                .ignore_patterns
+                .as_ref()
+                .is_some_and(|ignore_patterns| ignore_patterns.is_ignored(relative_path))
+        {
+            return FileMatcherResult::SkippedDueToName;
+        }
+
+        if !self.media_types.is_empty()
+            && !file_ref.media_type.as_ref().is_some_and(|media_type| {
+                self.media_types
+                    .iter()
+                    .any(|pattern| Self::media_type_matches(pattern, media_type))
+            })
+        {
+            return FileMatcherResult::SkippedDueToMediaType;
+        }
+
+        if self.modified_after.is_some() || self.modified_before.is_some() {
+            let modified_in_range = file_ref.modified_at.is_some_and(|modified_at| {
+                self.modified_after
+                    .map_or(true, |modified_after| modified_at > modified_after)
+                    && self
+                        .modified_before
+                        .map_or(true, |modified_before| modified_at < modified_before)
+            });
+            if !modified_in_range {
+                return FileMatcherResult::SkippedDueToModificationTime;
             }
         }
 
         FileMatcherResult::Matched
     }
+
+    fn media_type_matches(pattern: &mime::Mime, media_type: &mime::Mime) -> bool {
+        (pattern.type_() == mime::STAR || pattern.type_() == media_type.type_())
+            && (pattern.subtype() == mime::STAR || pattern.subtype() == media_type.subtype())
+    }
 }
 
 #[allow(unused_imports)]
@@ -54,7 +195,10 @@ mod tests {
     #[test]
     fn test_file_matcher() {
         let file_matcher = FileMatcher::new(
-            Some(globset::Glob::new("*.txt").unwrap().compile_matcher()),
+            FileFilters {
+                include: vec![globset::Glob::new("*.txt").unwrap()],
+                ..FileFilters::default()
+            },
             Some(100),
         );
 
@@ -62,6 +206,8 @@ mod tests {
             relative_path: RelativeFilePath("test.txt".to_string()),
             media_type: Some(Mime::from_str("text/plain").unwrap()),
             file_size: Some(50),
+            modified_at: None,
+            checksum: None,
         };
 
         assert_eq!(file_matcher.matches(&file_ref), FileMatcherResult::Matched);
@@ -70,6 +216,8 @@ mod tests {
             relative_path: RelativeFilePath("test.txt".to_string()),
             media_type: Some(Mime::from_str("text/plain").unwrap()),
             file_size: Some(150),
+            modified_at: None,
+            checksum: None,
         };
 
         assert_eq!(
@@ -81,6 +229,8 @@ mod tests {
             relative_path: RelativeFilePath("test.md".to_string()),
             media_type: Some(Mime::from_str("text/plain").unwrap()),
             file_size: Some(50),
+            modified_at: None,
+            checksum: None,
         };
 
         assert_eq!(
@@ -88,4 +238,107 @@ mod tests {
             FileMatcherResult::SkippedDueToName
         );
     }
+
+    #[test]
+    fn test_file_matcher_exclude() {
+        let file_matcher = FileMatcher::new(
+            FileFilters {
+                include: vec![
+                    globset::Glob::new("*.txt").unwrap(),
+                    globset::Glob::new("*.csv").unwrap(),
+                ],
+                exclude: vec![globset::Glob::new("tmp/*").unwrap()],
+                ignore_patterns: Some(FileIgnorePatterns::parse("# comment\n*.bak.csv\n").unwrap()),
+                ..FileFilters::default()
+            },
+            None,
+        );
+        let test_file_ref = |relative_path: &str| FileSystemRef {
+            relative_path: relative_path.into(),
+            media_type: None,
+            file_size: Some(50),
+            modified_at: None,
+            checksum: None,
+        };
+
+        assert_eq!(
+            file_matcher.matches(&test_file_ref("dir/test.csv")),
+            FileMatcherResult::Matched
+        );
+        assert_eq!(
+            file_matcher.matches(&test_file_ref("test.md")),
+            FileMatcherResult::SkippedDueToName
+        );
+        assert_eq!(
+            file_matcher.matches(&test_file_ref("tmp/test.txt")),
+            FileMatcherResult::SkippedDueToName
+        );
+        assert_eq!(
+            file_matcher.matches(&test_file_ref("dir/test.bak.csv")),
+            FileMatcherResult::SkippedDueToName
+        );
+    }
+
+    #[test]
+    fn test_file_matcher_media_types_and_modification_time() {
+        let file_matcher = FileMatcher::new(
+            FileFilters {
+                media_types: vec![Mime::from_str("image/*").unwrap(), mime::APPLICATION_PDF],
+                modified_after: chrono::DateTime::from_timestamp(1000, 0),
+                modified_before: chrono::DateTime::from_timestamp(2000, 0),
+                ..FileFilters::default()
+            },
+            None,
+        );
+        let test_file_ref = |media_type: &str, modified_at_secs: i64| FileSystemRef {
+            relative_path: "test".into(),
+            media_type: Some(Mime::from_str(media_type).unwrap()),
+            file_size: Some(50),
+            modified_at: chrono::DateTime::from_timestamp(modified_at_secs, 0),
+            checksum: None,
+        };
+
+        assert_eq!(
+            file_matcher.matches(&test_file_ref("image/png", 1500)),
+            FileMatcherResult::Matched
+        );
+        assert_eq!(
+            file_matcher.matches(&test_file_ref("application/pdf", 1500)),
+            FileMatcherResult::Matched
+        );
+        assert_eq!(
+            file_matcher.matches(&test_file_ref("text/plain", 1500)),
+            FileMatcherResult::SkippedDueToMediaType
+        );
+        assert_eq!(
+            file_matcher.matches(&test_file_ref("image/jpeg", 500)),
+            FileMatcherResult::SkippedDueToModificationTime
+        );
+        assert_eq!(
+            file_matcher.matches(&FileSystemRef {
+                modified_at: None,
+                ..test_file_ref("image/jpeg", 1500)
+            }),
+            FileMatcherResult::SkippedDueToModificationTime
+        );
+    }
+
+    #[test]
+    fn test_ignore_patterns() {
+        let ignore_patterns = FileIgnorePatterns::parse(
+            "\n# Temporary files\n*.log\n!important.log\n/build\nnode_modules/\ndocs/*.md\n",
+        )
+        .unwrap();
+
+        assert!(ignore_patterns.is_ignored("app.log"));
+        assert!(ignore_patterns.is_ignored("dir/app.log"));
+        assert!(!ignore_patterns.is_ignored("dir/important.log"));
+        assert!(ignore_patterns.is_ignored("build/output.txt"));
+        assert!(!ignore_patterns.is_ignored("src/build/output.txt"));
+        assert!(ignore_patterns.is_ignored("web/node_modules/lib/index.js"));
+        assert!(!ignore_patterns.is_ignored("node_modules"));
+        assert!(ignore_patterns.is_ignored("docs/readme.md"));
+        assert!(!ignore_patterns.is_ignored("docs/api/readme.md"));
+        assert!(!ignore_patterns.is_ignored("readme.md"));
+    }
 }
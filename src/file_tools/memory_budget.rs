@@ -0,0 +1,155 @@
+use crate::file_systems::{FileSystemOptions, WorkFiles};
+use crate::AppResult;
+use futures::{Stream, TryStreamExt};
+use gcloud_sdk::prost::bytes;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Memory shared by buffers of prefetched file content, so content waiting to be redacted
+/// beyond the budget is spilled to work files. It doesn't bound memory of files being redacted
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    limit: usize,
+    used: Arc<AtomicUsize>,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            used: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    fn try_reserve(&self, size: usize) -> bool {
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(size).filter(|used| *used <= self.limit)
+            })
+            .is_ok()
+    }
+
+    fn release(&self, size: usize) {
+        self.used.fetch_sub(size, Ordering::SeqCst);
+    }
+}
+
+/// Content of a file kept in memory within the memory budget and in a work file beyond it.
+/// Memory is returned to the budget when the buffer is dropped or its content is taken
+pub struct SpillBuffer {
+    budget: MemoryBudget,
+    chunks: Vec<bytes::Bytes>,
+    reserved: usize,
+    spilled: Option<WorkFiles>,
+}
+
+impl SpillBuffer {
+    const SPILLED_FILE: &'static str = "content";
+
+    pub fn new(budget: &MemoryBudget) -> Self {
+        Self {
+            budget: budget.clone(),
+            chunks: Vec::new(),
+            reserved: 0,
+            spilled: None,
+        }
+    }
+
+    pub async fn collect<S: Stream<Item = AppResult<bytes::Bytes>> + Unpin>(
+        mut input: S,
+        budget: &MemoryBudget,
+        options: &FileSystemOptions,
+    ) -> AppResult<Self> {
+        let mut buffer = Self::new(budget);
+        while let Some(chunk) = input.try_next().await? {
+            buffer.push(chunk, options)?;
+        }
+        Ok(buffer)
+    }
+
+    pub fn push(&mut self, chunk: bytes::Bytes, options: &FileSystemOptions) -> AppResult<()> {
+        if let Some(ref mut work_files) = self.spilled {
+            return work_files.append(Self::SPILLED_FILE, &chunk);
+        }
+        if self.budget.try_reserve(chunk.len()) {
+            self.reserved += chunk.len();
+            self.chunks.push(chunk);
+            return Ok(());
+        }
+        let mut work_files = WorkFiles::new("redacter-spill", options)?;
+        for buffered_chunk in self.chunks.drain(..) {
+            work_files.append(Self::SPILLED_FILE, &buffered_chunk)?;
+        }
+        work_files.append(Self::SPILLED_FILE, &chunk)?;
+        self.budget.release(self.reserved);
+        self.reserved = 0;
+        self.spilled = Some(work_files);
+        Ok(())
+    }
+
+    pub fn is_spilled(&self) -> bool {
+        self.spilled.is_some()
+    }
+
+    /// Content is read back from work files as a whole when the file is redacted,
+    /// since redacters need the whole content in memory
+    pub fn into_bytes(mut self) -> AppResult<bytes::Bytes> {
+        match self.spilled.take() {
+            Some(mut work_files) => Ok(work_files.take(Self::SPILLED_FILE)?.into()),
+            None => Ok(std::mem::take(&mut self.chunks).concat().into()),
+        }
+    }
+}
+
+impl Drop for SpillBuffer {
+    fn drop(&mut self) {
+        self.budget.release(self.reserved);
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spill_buffer_test() -> AppResult<()> {
+        let budget = MemoryBudget::new(10);
+        let options = FileSystemOptions::default();
+        let chunks = |chunks: Vec<&'static [u8]>| {
+            futures::stream::iter(
+                chunks
+                    .into_iter()
+                    .map(|chunk| Ok(bytes::Bytes::from_static(chunk))),
+            )
+        };
+
+        let in_memory =
+            SpillBuffer::collect(chunks(vec![b"Hello", b", "]), &budget, &options).await?;
+        assert!(!in_memory.is_spilled());
+        assert_eq!(budget.used(), 7);
+
+        let spilled =
+            SpillBuffer::collect(chunks(vec![b"Hello", b", John"]), &budget, &options).await?;
+        assert!(spilled.is_spilled());
+        assert_eq!(budget.used(), 7);
+        assert_eq!(
+            spilled.into_bytes()?,
+            bytes::Bytes::from_static(b"Hello, John")
+        );
+
+        assert_eq!(
+            in_memory.into_bytes()?,
+            bytes::Bytes::from_static(b"Hello, ")
+        );
+        assert_eq!(budget.used(), 0);
+        Ok(())
+    }
+}
@@ -3,3 +3,9 @@ pub use file_matcher::*;
 
 mod file_mime_override;
 pub use file_mime_override::*;
+
+mod csv_dialect;
+pub use csv_dialect::*;
+
+mod memory_budget;
+pub use memory_budget::*;
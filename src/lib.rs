@@ -0,0 +1,40 @@
+//! Copy & Redact library to securely copy and redact files removing Personal Identifiable Information (PII)
+//! across various filesystems.
+//!
+//! The same API is used by the `redacter` CLI, so it can be embedded into other services
+//! to redact files or streams without running the binary:
+//! - [`redacters::Redacters`] and [`redacters::Redacter`] to create and use DLP/LLM based redacters;
+//! - [`redacters::StreamRedacter`] to redact streams of bytes with conversions (PDF, OCR, tables);
+//! - [`file_systems::DetectFileSystem`] and [`file_systems::FileSystemConnection`] to work with
+//!   supported file systems;
+//! - [`commands`] to run the same copy/list commands as the CLI.
+
+pub mod args;
+pub mod reporter;
+
+pub mod file_systems;
+pub mod file_tools;
+
+pub mod errors;
+
+pub mod commands;
+
+pub mod redacters;
+
+pub mod metrics;
+
+pub mod notifications;
+
+pub mod logging;
+
+pub mod secrets;
+
+pub type AppResult<T> = Result<T, errors::AppError>;
+
+pub mod common_types;
+
+pub mod file_converters;
+
+pub fn config_env_var(name: &str) -> Result<String, String> {
+    std::env::var(name).map_err(|e| format!("{}: {}", name, e))
+}
@@ -0,0 +1,247 @@
+//! Structured logs of copy runs, file transfers and provider calls using `tracing`.
+//! Console output is kept as is, logs are enabled with `RUST_LOG`, `-v` or `--log-format json`.
+
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use std::io::Write;
+use std::sync::OnceLock;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    /// Default filter if `RUST_LOG` is not specified.
+    /// Text logs are disabled by default since they duplicate the console output,
+    /// and verbosity levels enable debug and trace logs for both formats.
+    fn default_filter(&self, verbosity: u8) -> &'static str {
+        match (self, verbosity) {
+            (LogFormat::Text, 0) => "off",
+            (LogFormat::Json, 0) => "redacter=info",
+            (_, 1) => "redacter=debug",
+            (_, _) => "redacter=trace",
+        }
+    }
+}
+
+/// Contents of provider responses are sensitive themselves, so they are logged only if allowed explicitly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LogRedactedContent {
+    #[default]
+    Never,
+    // Responses are logged only if they can't be processed, such as malformed LLM outputs
+    OnError,
+    Always,
+}
+
+impl LogRedactedContent {
+    pub fn global() -> LogRedactedContent {
+        LOG_REDACTED_CONTENT.get().copied().unwrap_or_default()
+    }
+
+    /// Logs the content of a processed provider response at the debug level
+    pub fn log_response(content: &str) {
+        if Self::global() == LogRedactedContent::Always {
+            tracing::debug!(content, "Provider response");
+        }
+    }
+
+    /// Logs the content of a provider response failed to be processed
+    pub fn log_failed_response(content: &str) {
+        if Self::global() != LogRedactedContent::Never {
+            tracing::warn!(content, "Failed to process provider response");
+        }
+    }
+}
+
+static LOG_REDACTED_CONTENT: OnceLock<LogRedactedContent> = OnceLock::new();
+
+pub fn init_logging(
+    log_format: LogFormat,
+    verbosity: u8,
+    log_redacted_content: LogRedactedContent,
+) {
+    let _ = LOG_REDACTED_CONTENT.set(log_redacted_content);
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(log_format.default_filter(verbosity)));
+    let registry = tracing_subscriber::registry().with(filter);
+    let result = match log_format {
+        LogFormat::Text => registry
+            .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+            .try_init(),
+        // Both formats are written to stderr, so logs aren't mixed with the console output in stdout
+        LogFormat::Json => registry.with(JsonLogLayer::new(std::io::stderr)).try_init(),
+    };
+    // Logging might be initialized already when the library is embedded
+    let _ = result;
+}
+
+#[derive(Debug, Default, Clone)]
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(
+            field.name().to_string(),
+            Value::from(format!("{:?}", value)),
+        );
+    }
+}
+
+/// Writes events as JSON lines with the fields of the current spans,
+/// so logs are aggregated by Cloud Logging, Datadog and others.
+pub struct JsonLogLayer<W> {
+    make_writer: W,
+}
+
+impl<W> JsonLogLayer<W> {
+    pub fn new(make_writer: W) -> Self {
+        Self { make_writer }
+    }
+}
+
+impl<S, W> Layer<S> for JsonLogLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + 'static,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut fields = JsonFields::default();
+            attrs.record(&mut fields);
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut extensions = span.extensions_mut();
+            if let Some(fields) = extensions.get_mut::<JsonFields>() {
+                values.record(fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut output = Map::new();
+        output.insert(
+            "timestamp".to_string(),
+            Value::from(chrono::Utc::now().to_rfc3339()),
+        );
+        let level = event.metadata().level().to_string();
+        output.insert("level".to_string(), Value::from(level.clone()));
+        // Cloud Logging uses severity to classify structured logs
+        output.insert("severity".to_string(), Value::from(level));
+        output.insert("target".to_string(), Value::from(event.metadata().target()));
+
+        let mut span_names = Vec::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                span_names.push(Value::from(span.name()));
+                if let Some(fields) = span.extensions().get::<JsonFields>() {
+                    output.extend(fields.0.clone());
+                }
+            }
+        }
+        if !span_names.is_empty() {
+            output.insert("spans".to_string(), Value::from(span_names));
+        }
+
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+        output.extend(fields.0);
+
+        if let Ok(mut line) = serde_json::to_vec(&output) {
+            line.push(b'\n');
+            let _ = self.make_writer.make_writer().write_all(&line);
+        }
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn default_filter_test() {
+        assert_eq!(LogFormat::Text.default_filter(0), "off");
+        assert_eq!(LogFormat::Json.default_filter(0), "redacter=info");
+        assert_eq!(LogFormat::Text.default_filter(1), "redacter=debug");
+        assert_eq!(LogFormat::Json.default_filter(3), "redacter=trace");
+    }
+
+    #[test]
+    fn json_log_layer_test() -> Result<(), Box<dyn std::error::Error>> {
+        #[derive(Clone, Default)]
+        struct TestWriter(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for TestWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        impl<'a> MakeWriter<'a> for TestWriter {
+            type Writer = TestWriter;
+
+            fn make_writer(&'a self) -> Self::Writer {
+                self.clone()
+            }
+        }
+
+        let writer = TestWriter::default();
+        let subscriber = tracing_subscriber::registry().with(JsonLogLayer::new(writer.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("transfer_file", file = "test.txt");
+            let _guard = span.enter();
+            tracing::info!(result = "redacted", duration_ms = 42_u64, "File processed");
+        });
+
+        let output = String::from_utf8(writer.0.lock().unwrap().clone())?;
+        let json: Value = serde_json::from_str(output.trim())?;
+        assert_eq!(json["message"], "File processed");
+        assert_eq!(json["severity"], "INFO");
+        assert_eq!(json["file"], "test.txt");
+        assert_eq!(json["result"], "redacted");
+        assert_eq!(json["duration_ms"], 42);
+        assert_eq!(json["spans"], serde_json::json!(["transfer_file"]));
+        Ok(())
+    }
+}
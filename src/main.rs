@@ -1,96 +1,172 @@
 use std::error::Error;
 
-use crate::commands::*;
-use crate::errors::AppError;
-use args::*;
 use clap::Parser;
 use console::{Style, Term};
-
-mod args;
-mod reporter;
-
-mod file_systems;
-mod file_tools;
-
-mod errors;
-
-mod commands;
-
-mod redacters;
-
-pub type AppResult<T> = Result<T, AppError>;
-
-mod common_types;
-
-mod file_converters;
-
-pub fn config_env_var(name: &str) -> Result<String, String> {
-    std::env::var(name).map_err(|e| format!("{}: {}", name, e))
-}
+use redacter::args::*;
+use redacter::commands::*;
+use redacter::errors::AppError;
+use redacter::file_tools::{FileMatcher, FileMimeOverride};
+use redacter::metrics::{AppMetrics, MetricsExportOptions};
+use redacter::notifications::{JobSummary, NotifyOptions};
+use redacter::redacters::RedacterOptions;
+use redacter::reporter::{AppReporter, AppReporterOptions};
+use redacter::AppResult;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let term = Term::stdout();
     let bold_style = Style::new().bold();
 
-    term.write_line(
-        format!(
-            "{} v{} (https://github.com/abdolence/redacter-rs)",
-            bold_style.clone().green().apply_to("Redacter"),
-            bold_style.apply_to(env!("CARGO_PKG_VERSION"))
-        )
-        .as_str(),
-    )?;
-
     let cli = CliArgs::parse();
-    match handle_args(cli, &term).await {
+    redacter::logging::init_logging(cli.log_format, cli.verbose, cli.log_redacted_content);
+    // JSON listings are written to stdout, so they aren't mixed with any other output
+    let quiet = cli.quiet
+        || matches!(
+            cli.command,
+            CliCommand::Ls {
+                output: LsOutputFormat::Json,
+                ..
+            }
+        );
+    let reporter_options = AppReporterOptions::new(&term, quiet, cli.no_progress);
+    if !reporter_options.progress {
+        console::set_colors_enabled(false);
+    }
+    let reporter = AppReporter::from(&term).with_options(&reporter_options);
+
+    reporter.report(format!(
+        "{} v{} (https://github.com/abdolence/redacter-rs)",
+        bold_style.clone().green().apply_to("Redacter"),
+        bold_style.apply_to(env!("CARGO_PKG_VERSION"))
+    ))?;
+
+    match handle_args(cli, &term, &reporter_options).await {
         Err(err) => {
-            term.write_line(
+            reporter.report_error(
                 format!(
-                    "{}: {}\nDetails: {:?}",
+                    "{} ({}): {}\nDetails: {:?}",
                     bold_style.clone().red().apply_to("Error"),
+                    err.code(),
                     &err,
                     &err.source()
                 )
                 .as_str(),
             )?;
-            std::process::exit(1);
+            std::process::exit(err.exit_code());
         }
         Ok(_) => Ok(()),
     }
 }
 
-async fn handle_args(cli: CliArgs, term: &Term) -> AppResult<()> {
+async fn handle_args(
+    cli: CliArgs,
+    term: &Term,
+    reporter_options: &AppReporterOptions,
+) -> AppResult<()> {
+    let reporter = AppReporter::from(term).with_options(reporter_options);
     match cli.command {
         CliCommand::Cp {
-            source,
+            sources,
             destination,
             max_size_limit,
             max_files_limit,
-            filename_filter,
+            max_bytes_per_run,
+            max_api_calls_per_run,
+            file_retries,
+            prefetch_files,
+            memory_budget,
+            file_filter_args,
             redacter_args,
+            file_system_args,
             mime_override,
             save_json_results,
+            report_html,
+            if_newer,
+            if_changed,
+            force,
+            quarantine,
+            on_collision,
+            on_conflict,
+            order,
+            no_dedup,
+            preserve_metadata,
+            scrub_metadata,
+            in_place,
+            backup_suffix,
+            interactive,
+            redact_file_names,
+            metrics_args,
+            notify_args,
+            exit_code_args,
         } => {
-            let options = CopyCommandOptions::new(
-                filename_filter,
-                max_size_limit,
+            let options = CopyCommandOptions {
+                file_matcher: FileMatcher::new(file_filter_args.try_into()?, max_size_limit),
+                file_mime_override: FileMimeOverride::new(mime_override),
                 max_files_limit,
-                mime_override,
-            );
+                run_limits: CopyRunLimits {
+                    max_bytes: max_bytes_per_run,
+                    max_api_calls: max_api_calls_per_run,
+                },
+                file_retries,
+                prefetch_files,
+                memory_budget,
+                if_newer,
+                if_changed,
+                force,
+                quarantine,
+                on_collision,
+                on_conflict,
+                order,
+                dedup: !no_dedup,
+                preserve_metadata,
+                scrub_metadata,
+                in_place,
+                backup_suffix,
+                interactive,
+                redact_file_names,
+                report_thumbnails: report_html.is_some(),
+                file_system_options: file_system_args.into(),
+                reporter_options: *reporter_options,
+            };
             let copy_result = command_copy(
                 term,
-                &source,
+                &sources,
                 &destination,
                 options,
-                redacter_args.map(|args| args.try_into()).transpose()?,
+                redacter_options(redacter_args).await?,
             )
-            .await?;
-            if let Some(json_path) = save_json_results {
+            .await;
+            let metrics_export_options: MetricsExportOptions = metrics_args.into();
+            let notify_options: NotifyOptions = notify_args.into();
+            let copy_result = match copy_result {
+                Ok(copy_result) => copy_result,
+                Err(err) => {
+                    // Metrics are exported for failed runs as well
+                    metrics_export_options
+                        .export_or_warn(AppMetrics::global(), &reporter)
+                        .await;
+                    notify(
+                        &reporter,
+                        &notify_options,
+                        &JobSummary::failed(&sources, &destination, &err),
+                    )
+                    .await?;
+                    return Err(err);
+                }
+            };
+            let exit_code_policy: CopyExitCodePolicy = exit_code_args.into();
+            let exit_code_check = exit_code_policy.check(&copy_result);
+            if exit_code_check.is_none() {
+                AppMetrics::global().set_last_success()?;
+            }
+            metrics_export_options
+                .export_or_warn(AppMetrics::global(), &reporter)
+                .await;
+            if let Some(ref json_path) = save_json_results {
                 let json_result = serde_json::to_string_pretty(&copy_result)?;
                 let mut file = tokio::fs::File::create(&json_path).await?;
                 tokio::io::AsyncWriteExt::write_all(&mut file, json_result.as_bytes()).await?;
-                term.write_line(
+                reporter.report(
                     format!(
                         "Results saved to JSON file: {}",
                         Style::new().bold().apply_to(json_path.display())
@@ -98,11 +174,22 @@ async fn handle_args(cli: CliArgs, term: &Term) -> AppResult<()> {
                     .as_str(),
                 )?;
             }
-            term.write_line(
+            if let Some(html_path) = report_html {
+                let html_report = copy_html_report(&sources, &destination, &copy_result);
+                tokio::fs::write(&html_path, html_report).await?;
+                reporter.report(
+                    format!(
+                        "Report saved to HTML file: {}",
+                        Style::new().bold().apply_to(html_path.display())
+                    )
+                    .as_str(),
+                )?;
+            }
+            reporter.report(
                 format!(
-                    "Finished: {} -> {}\nCopied: {}. Redacted: {}. Skipped: {}.",
-                    Style::new().bold().apply_to(source),
-                    Style::new().green().apply_to(destination),
+                    "Finished: {} -> {}\nCopied: {}. Redacted: {}. Skipped: {}. Quarantined: {}. Failed: {}.",
+                    Style::new().bold().apply_to(sources.join(", ")),
+                    Style::new().green().apply_to(&destination),
                     Style::new()
                         .bold()
                         .green()
@@ -112,19 +199,280 @@ async fn handle_args(cli: CliArgs, term: &Term) -> AppResult<()> {
                         .green()
                         .apply_to(copy_result.files_redacted),
                     Style::new().yellow().apply_to(copy_result.files_skipped),
+                    Style::new()
+                        .yellow()
+                        .apply_to(copy_result.files_quarantined),
+                    Style::new().red().apply_to(copy_result.files_failed),
                 )
                 .as_str(),
             )?;
+            for (redacter, usage) in copy_result.provider_usage.iter() {
+                reporter.report(format!(
+                    "Usage of {}: {}",
+                    Style::new().bold().apply_to(redacter),
+                    usage
+                ))?;
+            }
+            notify(
+                &reporter,
+                &notify_options,
+                &JobSummary::completed(
+                    &sources,
+                    &destination,
+                    &copy_result,
+                    save_json_results
+                        .as_ref()
+                        .map(|json_path| json_path.display().to_string()),
+                    exit_code_check.as_ref().map(|(_, reason)| reason.clone()),
+                ),
+            )
+            .await?;
+            if let Some((exit_code, reason)) = exit_code_check {
+                reporter.report_error(format!(
+                    "{}: {}",
+                    Style::new().bold().red().apply_to("Failed"),
+                    reason
+                ))?;
+                std::process::exit(exit_code);
+            }
+        }
+        CliCommand::Watch {
+            source,
+            destination,
+            max_size_limit,
+            file_filter_args,
+            redacter_args,
+            file_system_args,
+            mime_override,
+            poll_interval,
+            debounce,
+            metrics_args,
+        } => {
+            let options = WatchCommandOptions::new(
+                CopyCommandOptions {
+                    file_matcher: FileMatcher::new(file_filter_args.try_into()?, max_size_limit),
+                    file_mime_override: FileMimeOverride::new(mime_override),
+                    prefetch_files: 0,
+                    if_newer: true,
+                    file_system_options: file_system_args.into(),
+                    reporter_options: *reporter_options,
+                    ..CopyCommandOptions::default()
+                },
+                std::time::Duration::from_secs(poll_interval),
+                std::time::Duration::from_millis(debounce),
+                metrics_args.into(),
+            );
+            command_watch(
+                term,
+                &source,
+                &destination,
+                options,
+                redacter_options(redacter_args).await?,
+            )
+            .await?;
         }
         CliCommand::Ls {
+            sources,
+            max_size_limit,
+            file_filter_args,
+            short_format,
+            long_format: _,
+            sort_by,
+            output,
+            redacter_args,
+            connection_args,
+        } => {
+            let options = LsCommandOptions::new(
+                file_filter_args.try_into()?,
+                max_size_limit,
+                short_format,
+                sort_by,
+                output,
+                connection_args.into(),
+                *reporter_options,
+            );
+            command_ls(
+                term,
+                &sources,
+                options,
+                redacter_options(redacter_args).await?,
+            )
+            .await?;
+        }
+        CliCommand::Estimate {
             source,
             max_size_limit,
-            filename_filter,
+            max_files_limit,
+            file_filter_args,
+            sample_fraction,
+            sample_bytes,
+            price,
+            redacter_args,
+            mime_override,
+            connection_args,
+        } => {
+            let options = EstimateCommandOptions::new(
+                file_filter_args.try_into()?,
+                max_size_limit,
+                max_files_limit,
+                mime_override,
+                sample_fraction,
+                sample_bytes,
+                price,
+                connection_args.into(),
+                *reporter_options,
+            );
+            command_estimate(
+                term,
+                &source,
+                options,
+                redacter_options(redacter_args).await?,
+            )
+            .await?;
+        }
+        CliCommand::Diff {
+            original,
+            redacted,
+            max_size_limit,
+            file_filter_args,
+            mime_override,
+            html_report,
+            connection_args,
         } => {
-            let options = LsCommandOptions::new(filename_filter, max_size_limit);
-            command_ls(term, &source, options).await?;
+            let options = DiffCommandOptions::new(
+                file_filter_args.try_into()?,
+                max_size_limit,
+                mime_override,
+                html_report,
+                connection_args.into(),
+                *reporter_options,
+            );
+            command_diff(term, &original, &redacted, options).await?;
         }
+        CliCommand::Aggregate {
+            source,
+            destination,
+            k,
+            quasi_identifier,
+            sensitive,
+            insensitive,
+            generalize,
+            output,
+            epsilon,
+            csv_delimiter,
+            max_size_limit,
+            file_filter_args,
+            mime_override,
+            connection_args,
+        } => {
+            let options = AggregateCommandOptions::new(
+                file_filter_args.try_into()?,
+                max_size_limit,
+                mime_override,
+                AggregationOptions {
+                    k: k as usize,
+                    quasi_identifiers: quasi_identifier,
+                    sensitive,
+                    insensitive,
+                    generalizations: generalize,
+                    output: Some(output),
+                    epsilon,
+                    csv_delimiter: csv_delimiter.map(|c| c as u8),
+                },
+                connection_args.into(),
+                *reporter_options,
+            );
+            command_aggregate(term, &source, &destination, options).await?;
+        }
+        CliCommand::Check { redacter_args } => {
+            let check_result = command_check(
+                term,
+                CheckCommandOptions::new(*reporter_options),
+                redacter_options(redacter_args).await?,
+            )
+            .await?;
+            if check_result.failed() > 0 {
+                return Err(AppError::RedacterConfigError {
+                    message: format!(
+                        "{} of {} redacters failed checks",
+                        check_result.failed(),
+                        check_result.providers.len()
+                    ),
+                });
+            }
+            reporter.report(format!(
+                "All {} redacters are ready",
+                check_result.providers.len()
+            ))?;
+        }
+        CliCommand::Dlp {
+            command:
+                DlpCommand::CreateStoredInfotype {
+                    stored_info_type_id,
+                    gcp_project_id,
+                    dictionary_file,
+                    regex,
+                    display_name,
+                    description,
+                },
+        } => {
+            let source = match (dictionary_file, regex) {
+                (Some(dictionary_file), _) => DlpStoredInfoTypeSource::dictionary(&dictionary_file),
+                (None, Some(regex)) => DlpStoredInfoTypeSource::Regex(regex),
+                (None, None) => {
+                    return Err(AppError::RedacterConfigError {
+                        message: "Dictionary file or regex is required".to_string(),
+                    })
+                }
+            };
+            command_dlp_create_stored_info_type(
+                term,
+                CreateStoredInfoTypeOptions {
+                    project_id: gcp_project_id,
+                    stored_info_type_id,
+                    source,
+                    display_name,
+                    description,
+                    reporter_options: *reporter_options,
+                },
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn redacter_options(
+    redacter_args: Option<RedacterArgs>,
+) -> AppResult<Option<RedacterOptions>> {
+    match redacter_args {
+        Some(args) => Ok(Some(
+            args.apply_profile()
+                .await?
+                .resolve_secrets()
+                .await?
+                .try_into()?,
+        )),
+        None => Ok(None),
     }
+}
 
+/// Jobs aren't failed because of notifications, since files are already copied
+async fn notify(
+    reporter: &AppReporter<'_>,
+    notify_options: &NotifyOptions,
+    summary: &JobSummary,
+) -> AppResult<()> {
+    if let Err(err) = notify_options.notify(summary).await {
+        reporter.report_error(format!(
+            "{}: {}",
+            Style::new()
+                .bold()
+                .red()
+                .apply_to("Failed to send notification"),
+            err
+        ))?;
+    }
     Ok(())
 }
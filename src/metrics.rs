@@ -0,0 +1,333 @@
+//! Metrics of copy and redaction runs in Prometheus text format,
+//! so scheduled redaction jobs can be monitored as any other batch pipeline.
+
+use crate::reporter::AppReporter;
+use crate::AppResult;
+use console::Style;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+use url::Url;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AppMetric {
+    pub name: &'static str,
+    pub metric_type: &'static str,
+    pub help: &'static str,
+}
+
+impl AppMetric {
+    pub const FILES_PROCESSED: AppMetric = AppMetric {
+        name: "redacter_files_processed_total",
+        metric_type: "counter",
+        help: "Files processed by result (copied, redacted, skipped, quarantined, failed)",
+    };
+    pub const BYTES_PROCESSED: AppMetric = AppMetric {
+        name: "redacter_bytes_processed_total",
+        metric_type: "counter",
+        help: "Bytes read from sources",
+    };
+    pub const REDACTIONS_APPLIED: AppMetric = AppMetric {
+        name: "redacter_redactions_applied_total",
+        metric_type: "counter",
+        help: "Redactions applied to files",
+    };
+    pub const PROVIDER_CALLS: AppMetric = AppMetric {
+        name: "redacter_provider_calls_total",
+        metric_type: "counter",
+        help: "Redacter provider API calls",
+    };
+    pub const PROVIDER_FAILURES: AppMetric = AppMetric {
+        name: "redacter_provider_failures_total",
+        metric_type: "counter",
+        help: "Failed redacter provider API calls",
+    };
+    pub const PROVIDER_DURATION: AppMetric = AppMetric {
+        name: "redacter_provider_duration_seconds_total",
+        metric_type: "counter",
+        help: "Time spent in redacter provider API calls",
+    };
+    pub const PROVIDER_USAGE: AppMetric = AppMetric {
+        name: "redacter_provider_usage_total",
+        metric_type: "counter",
+        help: "Billable usage of redacter providers by unit (prompt_tokens, completion_tokens, bytes_inspected)",
+    };
+    pub const RETRIES: AppMetric = AppMetric {
+        name: "redacter_retries_total",
+        metric_type: "counter",
+        help: "Retries of interrupted operations such as downloads",
+    };
+    pub const FAILURES: AppMetric = AppMetric {
+        name: "redacter_failures_total",
+        metric_type: "counter",
+        help: "Files failed to be redacted",
+    };
+    pub const VERIFICATION_FAILURES: AppMetric = AppMetric {
+        name: "redacter_verification_failures_total",
+        metric_type: "counter",
+        help: "Redacted files with entities still detected by the verification",
+    };
+    pub const CACHE_HITS: AppMetric = AppMetric {
+        name: "redacter_cache_hits_total",
+        metric_type: "counter",
+        help: "Redaction results reused from the local cache",
+    };
+    pub const OCR_CACHE_HITS: AppMetric = AppMetric {
+        name: "redacter_ocr_cache_hits_total",
+        metric_type: "counter",
+        help: "OCR results of images reused instead of recognizing text again",
+    };
+    pub const DEDUPLICATED_FILES: AppMetric = AppMetric {
+        name: "redacter_deduplicated_files_total",
+        metric_type: "counter",
+        help: "Identical files redacted once within a run",
+    };
+    pub const FILES_DURATION: AppMetric = AppMetric {
+        name: "redacter_files_duration_seconds_total",
+        metric_type: "counter",
+        help: "Time spent to process files",
+    };
+    pub const RUN_DURATION: AppMetric = AppMetric {
+        name: "redacter_last_run_duration_seconds",
+        metric_type: "gauge",
+        help: "Duration of the last copy run",
+    };
+    pub const RUN_TIMESTAMP: AppMetric = AppMetric {
+        name: "redacter_last_success_timestamp_seconds",
+        metric_type: "gauge",
+        help: "Unix timestamp of the last successful copy run",
+    };
+}
+
+pub type AppMetricLabels = Vec<(&'static str, String)>;
+
+/// Process wide metrics registry, since metrics are collected across
+/// file systems, redacters and commands.
+#[derive(Debug, Default)]
+pub struct AppMetrics {
+    values: Mutex<BTreeMap<AppMetric, BTreeMap<AppMetricLabels, f64>>>,
+}
+
+impl AppMetrics {
+    pub fn global() -> &'static AppMetrics {
+        static METRICS: OnceLock<AppMetrics> = OnceLock::new();
+        METRICS.get_or_init(AppMetrics::default)
+    }
+
+    pub fn inc(&self, metric: AppMetric, labels: &[(&'static str, &str)]) {
+        self.add(metric, labels, 1.0);
+    }
+
+    pub fn add(&self, metric: AppMetric, labels: &[(&'static str, &str)], value: f64) {
+        self.update(metric, labels, |current| current + value);
+    }
+
+    pub fn add_duration(
+        &self,
+        metric: AppMetric,
+        labels: &[(&'static str, &str)],
+        value: Duration,
+    ) {
+        self.add(metric, labels, value.as_secs_f64());
+    }
+
+    pub fn set(&self, metric: AppMetric, labels: &[(&'static str, &str)], value: f64) {
+        self.update(metric, labels, |_| value);
+    }
+
+    /// Sets the timestamp of the last successful run, which must be recorded only for runs
+    /// passing the exit code policy, so alerts on stale timestamps catch failing jobs
+    pub fn set_last_success(&self) -> AppResult<()> {
+        self.set(
+            AppMetric::RUN_TIMESTAMP,
+            &[],
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)?
+                .as_secs_f64(),
+        );
+        Ok(())
+    }
+
+    /// Sum of the metric values for all labels
+    pub fn total(&self, metric: AppMetric) -> f64 {
+        self.values
+            .lock()
+            .ok()
+            .and_then(|values| values.get(&metric).map(|series| series.values().sum()))
+            .unwrap_or(0.0)
+    }
+
+    /// Values of the metric with their labels
+    pub fn series(&self, metric: AppMetric) -> Vec<(AppMetricLabels, f64)> {
+        self.values
+            .lock()
+            .ok()
+            .and_then(|values| {
+                values.get(&metric).map(|series| {
+                    series
+                        .iter()
+                        .map(|(labels, value)| (labels.clone(), *value))
+                        .collect()
+                })
+            })
+            .unwrap_or_default()
+    }
+
+    fn update<F: FnOnce(f64) -> f64>(
+        &self,
+        metric: AppMetric,
+        labels: &[(&'static str, &str)],
+        f: F,
+    ) {
+        if let Ok(mut values) = self.values.lock() {
+            let labels = labels
+                .iter()
+                .map(|(name, value)| (*name, value.to_string()))
+                .collect();
+            let current = values.entry(metric).or_default().entry(labels).or_default();
+            *current = f(*current);
+        }
+    }
+
+    /// Renders metrics in Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        if let Ok(values) = self.values.lock() {
+            for (metric, series) in values.iter() {
+                let _ = writeln!(output, "# HELP {} {}", metric.name, metric.help);
+                let _ = writeln!(output, "# TYPE {} {}", metric.name, metric.metric_type);
+                for (labels, value) in series {
+                    if labels.is_empty() {
+                        let _ = writeln!(output, "{} {}", metric.name, value);
+                    } else {
+                        let labels = labels
+                            .iter()
+                            .map(|(name, value)| {
+                                format!(
+                                    "{}=\"{}\"",
+                                    name,
+                                    value
+                                        .replace('\\', "\\\\")
+                                        .replace('"', "\\\"")
+                                        .replace('\n', "\\n")
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join(",");
+                        let _ = writeln!(output, "{}{{{}}} {}", metric.name, labels, value);
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MetricsExportOptions {
+    pub metrics_file: Option<PathBuf>,
+    pub metrics_push_gateway: Option<Url>,
+}
+
+impl MetricsExportOptions {
+    pub const PUSH_GATEWAY_JOB: &'static str = "redacter";
+
+    /// Writes the metrics to the file (such as node_exporter textfile collector directory)
+    /// and/or pushes them to Prometheus Pushgateway.
+    pub async fn export(&self, metrics: &AppMetrics) -> AppResult<()> {
+        let rendered = metrics.render();
+        if let Some(ref metrics_file) = self.metrics_file {
+            // Writing to a temporary file first, so collectors never read partial files
+            let mut temp_file = metrics_file.clone().into_os_string();
+            temp_file.push(".tmp");
+            tokio::fs::write(&temp_file, rendered.as_bytes()).await?;
+            tokio::fs::rename(&temp_file, metrics_file).await?;
+        }
+        if let Some(ref push_gateway) = self.metrics_push_gateway {
+            let push_url = format!(
+                "{}/metrics/job/{}",
+                push_gateway.as_str().trim_end_matches('/'),
+                Self::PUSH_GATEWAY_JOB
+            );
+            reqwest::Client::new()
+                .put(push_url)
+                .header(reqwest::header::CONTENT_TYPE, "text/plain; version=0.0.4")
+                .body(rendered)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(())
+    }
+
+    /// Exports the metrics reporting errors as warnings, since failed exports
+    /// must not change results and exit codes of runs
+    pub async fn export_or_warn(&self, metrics: &AppMetrics, reporter: &AppReporter<'_>) {
+        if let Err(err) = self.export(metrics).await {
+            tracing::warn!(error = %err, "Failed to export metrics");
+            let _ = reporter.report_error(format!(
+                "{}: {}",
+                Style::new()
+                    .bold()
+                    .yellow()
+                    .apply_to("Failed to export metrics"),
+                err
+            ));
+        }
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_test() {
+        let metrics = AppMetrics::default();
+        metrics.inc(AppMetric::FILES_PROCESSED, &[("result", "redacted")]);
+        metrics.inc(AppMetric::FILES_PROCESSED, &[("result", "redacted")]);
+        metrics.inc(AppMetric::FILES_PROCESSED, &[("result", "skipped")]);
+        metrics.add(AppMetric::BYTES_PROCESSED, &[], 1024.0);
+        metrics.set(AppMetric::RUN_DURATION, &[], 1.5);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains(
+            "# TYPE redacter_files_processed_total counter\nredacter_files_processed_total{result=\"redacted\"} 2\nredacter_files_processed_total{result=\"skipped\"} 1\n"
+        ));
+        assert!(rendered.contains("redacter_bytes_processed_total 1024\n"));
+        assert_eq!(metrics.total(AppMetric::FILES_PROCESSED), 3.0);
+        assert!(rendered.contains(
+            "# TYPE redacter_last_run_duration_seconds gauge\nredacter_last_run_duration_seconds 1.5\n"
+        ));
+    }
+
+    #[test]
+    fn set_last_success_test() -> AppResult<()> {
+        let metrics = AppMetrics::default();
+        assert!(metrics.series(AppMetric::RUN_TIMESTAMP).is_empty());
+        metrics.set_last_success()?;
+        assert!(metrics.total(AppMetric::RUN_TIMESTAMP) > 0.0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn export_file_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let temp_dir = tempfile::TempDir::with_prefix("metrics_tests_export")?;
+        let metrics_file = temp_dir.path().join("redacter.prom");
+        let metrics = AppMetrics::default();
+        metrics.inc(AppMetric::RETRIES, &[]);
+        MetricsExportOptions {
+            metrics_file: Some(metrics_file.clone()),
+            metrics_push_gateway: None,
+        }
+        .export(&metrics)
+        .await?;
+        assert_eq!(
+            tokio::fs::read_to_string(metrics_file).await?,
+            metrics.render()
+        );
+        Ok(())
+    }
+}
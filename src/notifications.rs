@@ -0,0 +1,222 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt::Display;
+use url::Url;
+
+use crate::commands::CopyCommandResult;
+use crate::errors::AppError;
+use crate::AppResult;
+
+/// Payloads of notifications sent to webhooks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum NotifyFormat {
+    /// Job summaries as JSON objects
+    #[default]
+    Json,
+    /// Messages for Slack incoming webhooks and compatible services (Mattermost, Rocket.Chat)
+    Slack,
+}
+
+impl Display for NotifyFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotifyFormat::Json => write!(f, "json"),
+            NotifyFormat::Slack => write!(f, "slack"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Completed,
+    /// Jobs completed with failed files or failed by exit code policies
+    CompletedWithFailures,
+    Failed,
+}
+
+impl Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobStatus::Completed => write!(f, "completed"),
+            JobStatus::CompletedWithFailures => write!(f, "completed with failures"),
+            JobStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+/// Summary of copy jobs sent when they complete or fail
+#[derive(Debug, Clone, Serialize)]
+pub struct JobSummary {
+    pub status: JobStatus,
+    pub sources: Vec<String>,
+    pub destination: String,
+    pub files_copied: usize,
+    pub files_redacted: usize,
+    pub files_skipped: usize,
+    pub files_quarantined: usize,
+    pub files_failed: usize,
+    /// Path to the JSON results of files with `--save-json-results`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+}
+
+impl JobSummary {
+    pub fn completed(
+        sources: &[String],
+        destination: &str,
+        copy_result: &CopyCommandResult,
+        results_path: Option<String>,
+        failure_reason: Option<String>,
+    ) -> Self {
+        JobSummary {
+            status: if failure_reason.is_some() || copy_result.files_failed > 0 {
+                JobStatus::CompletedWithFailures
+            } else {
+                JobStatus::Completed
+            },
+            sources: sources.to_vec(),
+            destination: destination.to_string(),
+            files_copied: copy_result.files_copied,
+            files_redacted: copy_result.files_redacted,
+            files_skipped: copy_result.files_skipped,
+            files_quarantined: copy_result.files_quarantined,
+            files_failed: copy_result.files_failed,
+            results_path,
+            error: failure_reason,
+            error_code: None,
+        }
+    }
+
+    pub fn failed(sources: &[String], destination: &str, error: &AppError) -> Self {
+        JobSummary {
+            status: JobStatus::Failed,
+            sources: sources.to_vec(),
+            destination: destination.to_string(),
+            files_copied: 0,
+            files_redacted: 0,
+            files_skipped: 0,
+            files_quarantined: 0,
+            files_failed: 0,
+            results_path: None,
+            error: Some(error.to_string()),
+            error_code: Some(error.code().to_string()),
+        }
+    }
+
+    /// Human-readable summary for chat messages
+    pub fn message(&self) -> String {
+        let mut message = format!(
+            "Redacter job {}: {} -> {}\nCopied: {}. Redacted: {}. Skipped: {}. Quarantined: {}. Failed: {}.",
+            self.status,
+            self.sources.join(", "),
+            self.destination,
+            self.files_copied,
+            self.files_redacted,
+            self.files_skipped,
+            self.files_quarantined,
+            self.files_failed
+        );
+        if let Some(ref error) = self.error {
+            message.push_str(&format!("\nReason: {}", error));
+        }
+        if let Some(ref results_path) = self.results_path {
+            message.push_str(&format!("\nResults: {}", results_path));
+        }
+        message
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NotifyOptions {
+    pub notify_webhook: Option<Url>,
+    pub notify_format: NotifyFormat,
+}
+
+impl NotifyOptions {
+    pub fn payload(&self, summary: &JobSummary) -> AppResult<serde_json::Value> {
+        Ok(match self.notify_format {
+            NotifyFormat::Json => serde_json::to_value(summary)?,
+            NotifyFormat::Slack => serde_json::json!({ "text": summary.message() }),
+        })
+    }
+
+    /// Posts job summaries to the webhook, so scheduled jobs don't fail silently.
+    /// Webhook URLs aren't included in errors, since they contain secrets such as Slack tokens.
+    pub async fn notify(&self, summary: &JobSummary) -> AppResult<()> {
+        if let Some(ref notify_webhook) = self.notify_webhook {
+            let response = reqwest::Client::new()
+                .post(notify_webhook.clone())
+                .json(&self.payload(summary)?)
+                .send()
+                .await
+                .map_err(|err| AppError::SystemError {
+                    message: format!(
+                        "Failed to send notification to webhook at {}: {}",
+                        notify_webhook.host_str().unwrap_or_default(),
+                        err.without_url()
+                    ),
+                })?;
+            if !response.status().is_success() {
+                return Err(AppError::SystemError {
+                    message: format!(
+                        "Webhook at {} responded with status {}",
+                        notify_webhook.host_str().unwrap_or_default(),
+                        response.status()
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_test() -> AppResult<()> {
+        let summary = JobSummary::completed(
+            &["gs://source/".to_string()],
+            "s3://destination/",
+            &CopyCommandResult {
+                schema_version: CopyCommandResult::SCHEMA_VERSION,
+                files_copied: 3,
+                files_redacted: 2,
+                files_skipped: 0,
+                files_quarantined: 0,
+                files_failed: 1,
+                files_remaining: 0,
+                stopped_reason: None,
+                interrupted: false,
+                files: vec![],
+                provider_usage: Default::default(),
+            },
+            Some("results.json".to_string()),
+            None,
+        );
+        assert_eq!(summary.status, JobStatus::CompletedWithFailures);
+
+        let json_payload = NotifyOptions::default().payload(&summary)?;
+        assert_eq!(json_payload["status"], "completed_with_failures");
+        assert_eq!(json_payload["files_redacted"], 2);
+        assert_eq!(json_payload["results_path"], "results.json");
+        assert!(json_payload.get("error").is_none());
+
+        let slack_payload = NotifyOptions {
+            notify_webhook: None,
+            notify_format: NotifyFormat::Slack,
+        }
+        .payload(&summary)?;
+        assert_eq!(
+            slack_payload["text"],
+            "Redacter job completed with failures: gs://source/ -> s3://destination/\nCopied: 3. Redacted: 2. Skipped: 0. Quarantined: 0. Failed: 1.\nResults: results.json"
+        );
+        Ok(())
+    }
+}
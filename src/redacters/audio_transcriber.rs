@@ -0,0 +1,286 @@
+use crate::common_types::{AudioLanguageCode, GcpProjectId, TextAudioTimestamps};
+use crate::errors::AppError;
+use crate::file_converters::audio::{mp3_sample_rate, WavAudio};
+use crate::metrics::{AppMetric, AppMetrics};
+use crate::redacters::Redacters;
+use crate::reporter::AppReporter;
+use crate::AppResult;
+use clap::ValueEnum;
+use gcloud_sdk::google::cloud::speech::v1::speech_client::SpeechClient;
+use gcloud_sdk::google::cloud::speech::v1::{
+    recognition_audio, recognition_config, LongRunningRecognizeRequest,
+    LongRunningRecognizeResponse, RecognitionAudio, RecognitionConfig,
+};
+use gcloud_sdk::google::longrunning::operations_client::OperationsClient;
+use gcloud_sdk::google::longrunning::{operation, GetOperationRequest};
+use gcloud_sdk::tonic::metadata::MetadataValue;
+use gcloud_sdk::{prost, prost_types, tonic, GoogleApi, GoogleAuthMiddleware};
+use mime::Mime;
+use rvstruct::ValueStruct;
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AudioTranscriptionProvider {
+    GcpSpeech,
+}
+
+impl Display for AudioTranscriptionProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AudioTranscriptionProvider::GcpSpeech => write!(f, "gcp-speech"),
+        }
+    }
+}
+
+/// What is written for redacted audio files: the WAV audio with redacted words
+/// silenced or beeped, or the redacted transcript as a text file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum AudioRedactionOutput {
+    #[default]
+    Silence,
+    Beep,
+    Transcript,
+}
+
+#[derive(Debug, Clone)]
+pub struct AudioTranscriberOptions {
+    pub provider: AudioTranscriptionProvider,
+    pub project_id: GcpProjectId,
+    pub language_code: AudioLanguageCode,
+    pub output: AudioRedactionOutput,
+}
+
+impl AudioTranscriberOptions {
+    pub const DEFAULT_LANGUAGE_CODE: &'static str = "en-US";
+}
+
+/// Transcribes speech in audio files with word timestamps, so the transcript can be
+/// redacted as text and redacted words can be located in the audio.
+#[derive(Clone)]
+pub struct AudioTranscriber<'a> {
+    speech_client: GoogleApi<SpeechClient<GoogleAuthMiddleware>>,
+    operations_client: GoogleApi<OperationsClient<GoogleAuthMiddleware>>,
+    pub audio_transcriber_options: AudioTranscriberOptions,
+    #[allow(dead_code)]
+    reporter: &'a AppReporter<'a>,
+}
+
+impl<'a> AudioTranscriber<'a> {
+    // Inline audio content is limited by GCP Speech-to-Text API
+    const MAX_INLINE_AUDIO_SIZE: usize = 10 * 1024 * 1024;
+    const OPERATION_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+    pub async fn new(
+        audio_transcriber_options: AudioTranscriberOptions,
+        reporter: &'a AppReporter<'a>,
+    ) -> AppResult<Self> {
+        let speech_client =
+            GoogleApi::from_function(SpeechClient::new, "https://speech.googleapis.com", None)
+                .await?;
+        let operations_client =
+            GoogleApi::from_function(OperationsClient::new, "https://speech.googleapis.com", None)
+                .await?;
+        Ok(AudioTranscriber {
+            speech_client,
+            operations_client,
+            audio_transcriber_options,
+            reporter,
+        })
+    }
+
+    /// Transcribes WAV or MP3 audio returning recognized words with their timestamps
+    pub async fn transcribe(
+        &self,
+        mime_type: &Mime,
+        data: &[u8],
+    ) -> AppResult<Vec<TextAudioTimestamps>> {
+        if data.len() > Self::MAX_INLINE_AUDIO_SIZE {
+            return Err(AppError::ConversionError {
+                message: format!(
+                    "Audio file is too large for transcription: {} bytes. Maximum is {} bytes",
+                    data.len(),
+                    Self::MAX_INLINE_AUDIO_SIZE
+                ),
+            });
+        }
+        let config = self.recognition_config(mime_type, data)?;
+        let request = LongRunningRecognizeRequest {
+            config: Some(config),
+            audio: Some(RecognitionAudio {
+                audio_source: Some(recognition_audio::AudioSource::Content(data.to_vec())),
+            }),
+            output_config: None,
+        };
+
+        let started = Instant::now();
+        let response = self.long_running_recognize(request).await;
+        let labels = [("redacter", "gcp-speech")];
+        let metrics = AppMetrics::global();
+        metrics.inc(AppMetric::PROVIDER_CALLS, &labels);
+        metrics.add_duration(AppMetric::PROVIDER_DURATION, &labels, started.elapsed());
+        if response.is_err() {
+            metrics.inc(AppMetric::PROVIDER_FAILURES, &labels);
+        }
+
+        Ok(response?
+            .results
+            .into_iter()
+            .filter_map(|result| result.alternatives.into_iter().next())
+            .flat_map(|alternative| alternative.words)
+            .map(|word| TextAudioTimestamps {
+                start_secs: word.start_time.as_ref().map_or(0.0, Self::duration_to_secs),
+                end_secs: word.end_time.as_ref().map_or(0.0, Self::duration_to_secs),
+                text: word.word,
+            })
+            .collect())
+    }
+
+    fn recognition_config(&self, mime_type: &Mime, data: &[u8]) -> AppResult<RecognitionConfig> {
+        let config = RecognitionConfig {
+            language_code: self.audio_transcriber_options.language_code.value().clone(),
+            enable_word_time_offsets: true,
+            // Punctuation would be attached to words and break matching them with redacted text
+            enable_automatic_punctuation: false,
+            ..RecognitionConfig::default()
+        };
+        if Redacters::is_mime_wav(mime_type) {
+            // Encoding and sample rate are read from the WAV header by the API
+            let wav_audio = WavAudio::parse(data)?;
+            Ok(RecognitionConfig {
+                audio_channel_count: wav_audio.channels.into(),
+                ..config
+            })
+        } else if Redacters::is_mime_mp3(mime_type) {
+            let sample_rate = mp3_sample_rate(data).ok_or_else(|| AppError::ConversionError {
+                message: "Invalid MP3 audio: no MPEG audio frames found".to_string(),
+            })?;
+            Ok(RecognitionConfig {
+                encoding: recognition_config::AudioEncoding::Mp3.into(),
+                sample_rate_hertz: sample_rate as i32,
+                ..config
+            })
+        } else {
+            Err(AppError::ConversionError {
+                message: format!(
+                    "Media type {} is not supported for transcription",
+                    mime_type
+                ),
+            })
+        }
+    }
+
+    async fn long_running_recognize(
+        &self,
+        request: LongRunningRecognizeRequest,
+    ) -> AppResult<LongRunningRecognizeResponse> {
+        let mut operation = self
+            .speech_client
+            .get()
+            .long_running_recognize(self.with_project_metadata(request)?)
+            .await?
+            .into_inner();
+        while !operation.done {
+            tokio::time::sleep(Self::OPERATION_POLL_INTERVAL).await;
+            operation = self
+                .operations_client
+                .get()
+                .get_operation(self.with_project_metadata(GetOperationRequest {
+                    name: operation.name.clone(),
+                })?)
+                .await?
+                .into_inner();
+        }
+        match operation.result {
+            Some(operation::Result::Response(response)) => {
+                prost::Message::decode(response.value.as_slice()).map_err(|e| {
+                    AppError::SystemError {
+                        message: format!("Failed to decode GCP Speech-to-Text response: {}", e),
+                    }
+                })
+            }
+            Some(operation::Result::Error(status)) => Err(AppError::ProviderError {
+                provider: "gcp-speech-to-text".to_string(),
+                retryable: matches!(
+                    gcloud_sdk::tonic::Code::from_i32(status.code),
+                    gcloud_sdk::tonic::Code::Unavailable
+                        | gcloud_sdk::tonic::Code::ResourceExhausted
+                        | gcloud_sdk::tonic::Code::DeadlineExceeded
+                ),
+                status: None,
+                message: status.message,
+            }),
+            None => Err(AppError::SystemError {
+                message: "GCP Speech-to-Text operation finished without response".to_string(),
+            }),
+        }
+    }
+
+    fn with_project_metadata<T>(&self, message: T) -> AppResult<tonic::Request<T>> {
+        let mut request = tonic::Request::new(message);
+        request.metadata_mut().insert(
+            "x-goog-user-project",
+            MetadataValue::<tonic::metadata::Ascii>::try_from(
+                self.audio_transcriber_options.project_id.value(),
+            )?,
+        );
+        Ok(request)
+    }
+
+    fn duration_to_secs(duration: &prost_types::Duration) -> f64 {
+        duration.seconds as f64 + duration.nanos as f64 / 1_000_000_000.0
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use console::Term;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ci-gcp"), ignore)]
+    async fn transcribe_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let test_gcp_project_id =
+            std::env::var("TEST_GCP_PROJECT").expect("TEST_GCP_PROJECT required");
+
+        // One second of silence
+        let sample_rate: u32 = 16000;
+        let samples = vec![0u8; sample_rate as usize * 2];
+        let mut wav_bytes = Vec::new();
+        wav_bytes.extend_from_slice(b"RIFF");
+        wav_bytes.extend_from_slice(&(36 + samples.len() as u32).to_le_bytes());
+        wav_bytes.extend_from_slice(b"WAVEfmt ");
+        wav_bytes.extend_from_slice(&16u32.to_le_bytes());
+        wav_bytes.extend_from_slice(&1u16.to_le_bytes());
+        wav_bytes.extend_from_slice(&1u16.to_le_bytes());
+        wav_bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        wav_bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        wav_bytes.extend_from_slice(&2u16.to_le_bytes());
+        wav_bytes.extend_from_slice(&16u16.to_le_bytes());
+        wav_bytes.extend_from_slice(b"data");
+        wav_bytes.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+        wav_bytes.extend_from_slice(&samples);
+
+        let transcriber = AudioTranscriber::new(
+            AudioTranscriberOptions {
+                provider: AudioTranscriptionProvider::GcpSpeech,
+                project_id: GcpProjectId::new(test_gcp_project_id),
+                language_code: AudioLanguageCode::new(
+                    AudioTranscriberOptions::DEFAULT_LANGUAGE_CODE.to_string(),
+                ),
+                output: AudioRedactionOutput::Silence,
+            },
+            &reporter,
+        )
+        .await?;
+
+        let words = transcriber
+            .transcribe(&"audio/wav".parse()?, &wav_bytes)
+            .await?;
+        assert!(words.is_empty());
+
+        Ok(())
+    }
+}
@@ -1,26 +1,39 @@
 use crate::args::RedacterType;
+use crate::common_types::RedactLanguage;
 use crate::errors::AppError;
 use crate::file_systems::FileSystemRef;
 use crate::redacters::{
+    replace_entity_spans, EntityMappings, RedactEntityType, RedactEntityTypeFilter, RedactFinding,
     RedactSupport, Redacter, RedacterDataItem, RedacterDataItemContent, Redacters,
 };
 use crate::reporter::AppReporter;
 use crate::AppResult;
 use aws_config::Region;
+use rvstruct::ValueStruct;
 
 #[derive(Debug, Clone)]
 pub struct AwsComprehendRedacterOptions {
     pub region: Option<Region>,
+    pub entity_type_filter: RedactEntityTypeFilter,
+    pub language: Option<RedactLanguage>,
+    /// Replace entities with surrogates consistent across files instead of `X` characters
+    pub entity_mappings: Option<EntityMappings>,
 }
 
 #[derive(Clone)]
 pub struct AwsComprehendRedacter<'a> {
     client: aws_sdk_comprehend::Client,
+    entity_type_filter: RedactEntityTypeFilter,
+    language_code: aws_sdk_comprehend::types::LanguageCode,
+    entity_mappings: Option<EntityMappings>,
     #[allow(dead_code)]
     reporter: &'a AppReporter<'a>,
 }
 
 impl<'a> AwsComprehendRedacter<'a> {
+    /// Languages supported by AWS Comprehend DetectPiiEntities
+    pub const SUPPORTED_LANGUAGES: [&'static str; 2] = ["en", "es"];
+
     pub async fn new(
         aws_dlp_options: AwsComprehendRedacterOptions,
         reporter: &'a AppReporter<'a>,
@@ -31,7 +44,20 @@ impl<'a> AwsComprehendRedacter<'a> {
         .or_default_provider();
         let shared_config = aws_config::from_env().region(region_provider).load().await;
         let client = aws_sdk_comprehend::Client::new(&shared_config);
-        Ok(Self { client, reporter })
+        let language_code = aws_dlp_options
+            .language
+            .as_ref()
+            .map(|language| {
+                aws_sdk_comprehend::types::LanguageCode::from(language.value().as_str())
+            })
+            .unwrap_or(aws_sdk_comprehend::types::LanguageCode::En);
+        Ok(Self {
+            client,
+            entity_type_filter: aws_dlp_options.entity_type_filter,
+            language_code,
+            entity_mappings: aws_dlp_options.entity_mappings,
+            reporter,
+        })
     }
 
     pub async fn redact_text_file(&self, input: RedacterDataItem) -> AppResult<RedacterDataItem> {
@@ -42,37 +68,82 @@ impl<'a> AwsComprehendRedacter<'a> {
             }),
         }?;
 
+        let spans = self
+            .detect_pii_entities(&text_content)
+            .await?
+            .into_iter()
+            .map(|entity| {
+                (
+                    entity.begin_offset.map(|offset| offset as usize),
+                    entity.end_offset.map(|offset| offset as usize),
+                    entity
+                        .r#type
+                        .as_ref()
+                        .map(|pii_type| {
+                            RedactEntityType::from_aws_comprehend_type(pii_type.as_str())
+                        })
+                        .unwrap_or(RedactEntityType::Other),
+                )
+            })
+            .collect();
+        let redacted_content =
+            replace_entity_spans(&text_content, spans, self.entity_mappings.as_ref());
+        Ok(RedacterDataItem {
+            file_ref: input.file_ref,
+            content: RedacterDataItemContent::Value(redacted_content),
+        })
+    }
+
+    /// PII entities found in the text, used to verify redacted content
+    pub async fn detect_text(&self, text: String) -> AppResult<Vec<RedactFinding>> {
+        Ok(self
+            .detect_pii_entities(&text)
+            .await?
+            .into_iter()
+            .filter_map(|entity| {
+                entity.r#type.map(|pii_type| {
+                    RedactFinding::new(
+                        RedacterType::AwsComprehend,
+                        pii_type.as_str().to_string(),
+                        entity.begin_offset.map(|offset| offset as usize),
+                        entity.end_offset.map(|offset| offset as usize),
+                    )
+                })
+            })
+            .collect())
+    }
+
+    async fn detect_pii_entities(
+        &self,
+        text_content: &str,
+    ) -> AppResult<Vec<aws_sdk_comprehend::types::PiiEntity>> {
         let aws_request = self
             .client
             .detect_pii_entities()
-            .language_code(aws_sdk_comprehend::types::LanguageCode::En)
-            .text(text_content.clone());
+            .language_code(self.language_code.clone())
+            .text(text_content);
 
         let result = aws_request.send().await?;
-        let redacted_content = result.entities.iter().fold(text_content, |acc, entity| {
-            entity.iter().fold(acc, |acc, entity| {
-                match (entity.begin_offset, entity.end_offset) {
-                    (Some(start), Some(end)) => [
-                        acc[..start as usize].to_string(),
-                        "X".repeat((end - start) as usize),
-                        acc[end as usize..].to_string(),
-                    ]
-                    .concat(),
-                    (Some(start), None) => {
-                        acc[..start as usize].to_string()
-                            + "X".repeat(acc.len() - start as usize).as_str()
-                    }
-                    (None, Some(end)) => {
-                        ["X".repeat(end as usize), acc[end as usize..].to_string()].concat()
-                    }
-                    _ => acc,
-                }
+        Ok(result
+            .entities
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|entity| {
+                entity.r#type.as_ref().iter().all(|pii_type| {
+                    self.entity_type_filter
+                        .accepts(RedactEntityType::from_aws_comprehend_type(
+                            pii_type.as_str(),
+                        ))
+                })
             })
-        });
-        Ok(RedacterDataItem {
-            file_ref: input.file_ref,
-            content: RedacterDataItemContent::Value(redacted_content),
-        })
+            .filter(|entity| {
+                self.entity_type_filter.accepts_span(
+                    text_content,
+                    entity.begin_offset.map(|offset| offset as usize),
+                    entity.end_offset.map(|offset| offset as usize),
+                )
+            })
+            .collect())
     }
 }
 
@@ -118,6 +189,8 @@ mod tests {
             relative_path: "temp_file.txt".into(),
             media_type: Some(mime::TEXT_PLAIN),
             file_size: Some(test_content.len()),
+            modified_at: None,
+            checksum: None,
         };
 
         let content = RedacterDataItemContent::Value(test_content.to_string());
@@ -126,6 +199,9 @@ mod tests {
         let redacter = AwsComprehendRedacter::new(
             AwsComprehendRedacterOptions {
                 region: Some(Region::new(test_aws_region)),
+                entity_type_filter: RedactEntityTypeFilter::default(),
+                language: None,
+                entity_mappings: None,
             },
             &reporter,
         )
@@ -0,0 +1,243 @@
+use std::path::PathBuf;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::args::RedacterType;
+use crate::errors::AppError;
+use crate::file_systems::FileSystemRef;
+use crate::redacters::{
+    RedactSupport, Redacter, RedacterDataItem, RedacterDataItemContent, Redacters,
+};
+use crate::reporter::AppReporter;
+use crate::AppResult;
+
+#[derive(Debug, Clone)]
+pub struct DictionaryRedacterOptions {
+    /// File with terms to redact, one per line. Empty lines and lines starting with `#` are ignored
+    pub dictionary_file: PathBuf,
+    pub ignore_case: bool,
+    pub whole_words: bool,
+}
+
+/// Local redacter replacing occurrences of user-supplied terms such as employee names,
+/// project code names or customer IDs. It doesn't call any APIs, so it can be combined with cloud providers.
+#[derive(Clone)]
+pub struct DictionaryRedacter<'a> {
+    terms_regex: Option<Regex>,
+    whole_words: bool,
+    #[allow(dead_code)]
+    reporter: &'a AppReporter<'a>,
+}
+
+impl<'a> DictionaryRedacter<'a> {
+    pub async fn new(
+        options: DictionaryRedacterOptions,
+        reporter: &'a AppReporter<'a>,
+    ) -> AppResult<Self> {
+        let dictionary = tokio::fs::read_to_string(&options.dictionary_file)
+            .await
+            .map_err(|err| AppError::RedacterConfigError {
+                message: format!(
+                    "Failed to read dictionary file {}: {}",
+                    options.dictionary_file.display(),
+                    err
+                ),
+            })?;
+        Ok(Self {
+            terms_regex: Self::terms_regex(&dictionary, options.ignore_case)?,
+            whole_words: options.whole_words,
+            reporter,
+        })
+    }
+
+    /// Terms of dictionary files, one per line. Empty lines and lines starting with # are ignored
+    pub fn dictionary_terms(dictionary: &str) -> Vec<&str> {
+        dictionary
+            .lines()
+            .map(str::trim)
+            .filter(|term| !term.is_empty() && !term.starts_with('#'))
+            .collect()
+    }
+
+    fn terms_regex(dictionary: &str, ignore_case: bool) -> AppResult<Option<Regex>> {
+        let mut terms = Self::dictionary_terms(dictionary);
+        if terms.is_empty() {
+            return Ok(None);
+        }
+        // Longer terms first, so they are preferred over their prefixes
+        terms.sort_by_key(|term| std::cmp::Reverse(term.len()));
+        terms.dedup();
+        let pattern = terms
+            .iter()
+            .map(|term| regex::escape(term))
+            .collect::<Vec<String>>()
+            .join("|");
+        let terms_regex = RegexBuilder::new(&pattern)
+            .case_insensitive(ignore_case)
+            .size_limit(1 << 30)
+            .build()
+            .map_err(|err| AppError::RedacterConfigError {
+                message: format!("Failed to compile dictionary terms: {}", err),
+            })?;
+        Ok(Some(terms_regex))
+    }
+
+    fn is_word_char(c: char) -> bool {
+        c.is_alphanumeric() || c == '_'
+    }
+
+    fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+        !text[..start]
+            .chars()
+            .next_back()
+            .is_some_and(Self::is_word_char)
+            && !text[end..].chars().next().is_some_and(Self::is_word_char)
+    }
+
+    pub fn redact_text(&self, text: &str) -> String {
+        let terms_regex = match self.terms_regex {
+            Some(ref terms_regex) => terms_regex,
+            None => return text.to_string(),
+        };
+        let mut redacted = String::with_capacity(text.len());
+        let mut position = 0;
+        let mut search_from = 0;
+        while let Some(found) = terms_regex.find_at(text, search_from) {
+            if self.whole_words && !Self::is_whole_word(text, found.start(), found.end()) {
+                // Other terms may still match as whole words starting at the next character
+                search_from = found.start()
+                    + text[found.start()..]
+                        .chars()
+                        .next()
+                        .map_or(1, char::len_utf8);
+                continue;
+            }
+            redacted.push_str(&text[position..found.start()]);
+            redacted.push_str(&"X".repeat(found.as_str().chars().count()));
+            position = found.end();
+            search_from = found.end();
+        }
+        redacted.push_str(&text[position..]);
+        redacted
+    }
+}
+
+impl<'a> Redacter for DictionaryRedacter<'a> {
+    async fn redact(&self, input: RedacterDataItem) -> AppResult<RedacterDataItem> {
+        let content = match input.content {
+            RedacterDataItemContent::Value(value) => {
+                RedacterDataItemContent::Value(self.redact_text(&value))
+            }
+            RedacterDataItemContent::Table { headers, rows } => RedacterDataItemContent::Table {
+                headers,
+                rows: rows
+                    .iter()
+                    .map(|row| row.iter().map(|cell| self.redact_text(cell)).collect())
+                    .collect(),
+            },
+            RedacterDataItemContent::Image { .. } | RedacterDataItemContent::Pdf { .. } => {
+                return Err(AppError::SystemError {
+                    message: "Attempt to redact of unsupported type".to_string(),
+                })
+            }
+        };
+        Ok(RedacterDataItem {
+            file_ref: input.file_ref,
+            content,
+        })
+    }
+
+    async fn redact_support(&self, file_ref: &FileSystemRef) -> AppResult<RedactSupport> {
+        Ok(match file_ref.media_type.as_ref() {
+            Some(media_type) if Redacters::is_mime_text(media_type) => RedactSupport::Supported,
+            Some(media_type) if Redacters::is_mime_table(media_type) => RedactSupport::Supported,
+            _ => RedactSupport::Unsupported,
+        })
+    }
+
+    fn redacter_type(&self) -> RedacterType {
+        RedacterType::Dictionary
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use console::Term;
+    use std::io::Write;
+
+    #[allow(dead_code)]
+    async fn test_redacter<'a>(
+        reporter: &'a AppReporter<'a>,
+        ignore_case: bool,
+        whole_words: bool,
+    ) -> AppResult<DictionaryRedacter<'a>> {
+        let mut dictionary_file = tempfile::NamedTempFile::new()?;
+        writeln!(
+            dictionary_file,
+            "# Employees\nJohn Doe\nJohn\n\nProject Falcon\nCUST-001"
+        )?;
+        DictionaryRedacter::new(
+            DictionaryRedacterOptions {
+                dictionary_file: dictionary_file.path().to_path_buf(),
+                ignore_case,
+                whole_words,
+            },
+            reporter,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn redact_dictionary_terms_test() -> AppResult<()> {
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+
+        let redacter = test_redacter(&reporter, false, false).await?;
+        assert_eq!(
+            redacter.redact_text("John Doe and Johnny from project falcon, CUST-001"),
+            "XXXXXXXX and XXXXny from project falcon, XXXXXXXX"
+        );
+
+        let redacter = test_redacter(&reporter, true, true).await?;
+        assert_eq!(
+            redacter.redact_text("John Doe and Johnny from project falcon, CUST-0012"),
+            "XXXXXXXX and Johnny from XXXXXXXXXXXXXX, CUST-0012"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn redact_dictionary_table_test() -> AppResult<()> {
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let redacter = test_redacter(&reporter, true, true).await?;
+
+        let redacted = redacter
+            .redact(RedacterDataItem {
+                file_ref: FileSystemRef {
+                    relative_path: "temp_file.csv".into(),
+                    media_type: Some(mime::TEXT_CSV),
+                    file_size: None,
+                    modified_at: None,
+                    checksum: None,
+                },
+                content: RedacterDataItemContent::Table {
+                    headers: vec!["customer".to_string(), "name".to_string()],
+                    rows: vec![vec!["cust-001".to_string(), "Jane Doe".to_string()]],
+                },
+            })
+            .await?;
+        match redacted.content {
+            RedacterDataItemContent::Table { headers, rows } => {
+                assert_eq!(headers, vec!["customer".to_string(), "name".to_string()]);
+                assert_eq!(
+                    rows,
+                    vec![vec!["XXXXXXXX".to_string(), "Jane Doe".to_string()]]
+                );
+            }
+            _ => panic!("Unexpected redacted content type"),
+        }
+        Ok(())
+    }
+}
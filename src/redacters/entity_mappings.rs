@@ -0,0 +1,211 @@
+use crate::errors::AppError;
+use crate::redacters::RedactEntityType;
+use crate::AppResult;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct EntityMappingsState {
+    /// Surrogates by SHA-256 hashes of entity types and normalized entity values
+    surrogates: HashMap<String, String>,
+    /// Last numbers of surrogates by their prefixes such as `PERSON_NAME`
+    counters: HashMap<String, usize>,
+}
+
+/// Surrogates of entities found by redacters, shared by all files of a run,
+/// so the same person has the same surrogate such as `PERSON_NAME_1` in every file.
+/// Mappings are persisted between runs only if the file is specified. Persisted mappings contain
+/// only hashes of entity values instead of the values themselves.
+#[derive(Clone)]
+pub struct EntityMappings {
+    state: Arc<Mutex<EntityMappingsState>>,
+    mappings_file: Option<PathBuf>,
+}
+
+impl EntityMappings {
+    pub fn new(mappings_file: Option<PathBuf>) -> AppResult<Self> {
+        let state = match mappings_file {
+            Some(ref path) if path.exists() => serde_json::from_slice(&std::fs::read(path)?)
+                .map_err(|err| AppError::RedacterConfigError {
+                    message: format!("Invalid entity mappings file {}: {}", path.display(), err),
+                })?,
+            _ => EntityMappingsState::default(),
+        };
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+            mappings_file,
+        })
+    }
+
+    pub fn surrogate(&self, entity_type: RedactEntityType, value: &str) -> String {
+        let prefix = entity_type
+            .to_possible_value()
+            .map(|value| value.get_name().replace('-', "_").to_uppercase())
+            .unwrap_or_else(|| "ENTITY".to_string());
+        let normalized_value = value
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ")
+            .to_lowercase();
+        let key = hex::encode(Sha256::digest(
+            format!("{}:{}", prefix, normalized_value).as_bytes(),
+        ));
+        let mut state = self.state.lock().unwrap();
+        if let Some(surrogate) = state.surrogates.get(&key) {
+            return surrogate.clone();
+        }
+        let counter = state.counters.entry(prefix.clone()).or_insert(0);
+        *counter += 1;
+        let surrogate = format!("{}_{}", prefix, counter);
+        state.surrogates.insert(key, surrogate.clone());
+        surrogate
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().surrogates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes mappings to the file if it is specified
+    pub async fn save(&self) -> AppResult<()> {
+        if let Some(ref path) = self.mappings_file {
+            let content = serde_json::to_vec_pretty(&*self.state.lock().unwrap())?;
+            tokio::fs::write(path, content).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Only the file is printed, since surrogates of the run are as sensitive as the original entities
+impl std::fmt::Debug for EntityMappings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EntityMappings")
+            .field("mappings_file", &self.mappings_file)
+            .finish()
+    }
+}
+
+/// Replaces entities at byte spans of the text with `X` characters, or with surrogates if mappings are specified.
+/// Overlapping spans are merged, and spans without starts or ends extend to the beginning or the end of the text.
+pub fn replace_entity_spans(
+    text: &str,
+    spans: Vec<(Option<usize>, Option<usize>, RedactEntityType)>,
+    entity_mappings: Option<&EntityMappings>,
+) -> String {
+    let mut spans: Vec<(usize, usize, RedactEntityType)> = spans
+        .into_iter()
+        .filter(|(start, end, _)| start.is_some() || end.is_some())
+        .map(|(start, end, entity_type)| {
+            let mut start = start.unwrap_or(0).min(text.len());
+            let mut end = end.unwrap_or(text.len()).min(text.len());
+            // Spans inside of multibyte characters are extended to whole characters
+            while !text.is_char_boundary(start) {
+                start -= 1;
+            }
+            while !text.is_char_boundary(end) {
+                end += 1;
+            }
+            (start, end, entity_type)
+        })
+        .filter(|(start, end, _)| start < end)
+        .collect();
+    spans.sort_by_key(|(start, end, _)| (*start, std::cmp::Reverse(*end)));
+    let mut merged: Vec<(usize, usize, RedactEntityType)> = Vec::with_capacity(spans.len());
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.0 < last.1 => last.1 = last.1.max(span.1),
+            _ => merged.push(span),
+        }
+    }
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut position = 0;
+    for (start, end, entity_type) in merged {
+        redacted.push_str(&text[position..start]);
+        match entity_mappings {
+            Some(entity_mappings) => {
+                redacted.push_str(&entity_mappings.surrogate(entity_type, &text[start..end]))
+            }
+            None => redacted.push_str(&"X".repeat(end - start)),
+        }
+        position = end;
+    }
+    redacted.push_str(&text[position..]);
+    redacted
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_entity_spans_test() -> AppResult<()> {
+        let text = "John Doe called Jane Roe, then john  doe wrote to Zoë";
+        let spans = vec![
+            (Some(0), Some(8), RedactEntityType::PersonName),
+            (Some(16), Some(24), RedactEntityType::PersonName),
+            (Some(31), Some(40), RedactEntityType::PersonName),
+            (Some(21), Some(24), RedactEntityType::PersonName),
+            (Some(50), None, RedactEntityType::PersonName),
+        ];
+        assert_eq!(
+            replace_entity_spans(text, spans.clone(), None),
+            "XXXXXXXX called XXXXXXXX, then XXXXXXXXX wrote to XXXX"
+        );
+
+        let temp_dir = tempfile::tempdir()?;
+        let mappings_file = temp_dir.path().join("mappings.json");
+        let entity_mappings = EntityMappings::new(Some(mappings_file.clone()))?;
+        assert_eq!(
+            replace_entity_spans(text, spans, Some(&entity_mappings)),
+            "PERSON_NAME_1 called PERSON_NAME_2, then PERSON_NAME_1 wrote to PERSON_NAME_3"
+        );
+        assert_eq!(
+            entity_mappings.surrogate(RedactEntityType::EmailAddress, "john@example.com"),
+            "EMAIL_ADDRESS_1"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn persisted_entity_mappings_test() -> AppResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mappings_file = temp_dir.path().join("mappings.json");
+        let entity_mappings = EntityMappings::new(Some(mappings_file.clone()))?;
+        let shared_mappings = entity_mappings.clone();
+        let handles: Vec<_> = ["Jane Roe", "John Doe", "Jane Roe"]
+            .into_iter()
+            .map(|name| {
+                let shared_mappings = shared_mappings.clone();
+                tokio::spawn(async move {
+                    shared_mappings.surrogate(RedactEntityType::PersonName, name)
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(entity_mappings.len(), 2);
+        entity_mappings.save().await?;
+
+        let saved = tokio::fs::read_to_string(&mappings_file).await?;
+        assert!(!saved.contains("Jane") && !saved.contains("john"));
+        let next_run_mappings = EntityMappings::new(Some(mappings_file))?;
+        assert_eq!(
+            next_run_mappings.surrogate(RedactEntityType::PersonName, "JANE ROE"),
+            entity_mappings.surrogate(RedactEntityType::PersonName, "Jane Roe")
+        );
+        assert_eq!(
+            next_run_mappings.surrogate(RedactEntityType::PersonName, "Max Mustermann"),
+            "PERSON_NAME_3"
+        );
+        Ok(())
+    }
+}
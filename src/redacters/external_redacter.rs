@@ -0,0 +1,377 @@
+use base64::Engine;
+use clap::ValueEnum;
+use rvstruct::ValueStruct;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+
+use crate::args::RedacterType;
+use crate::errors::AppError;
+use crate::file_systems::FileSystemRef;
+use crate::redacters::{
+    RedactEntityType, RedactEntityTypeFilter, RedactSupport, Redacter, RedacterDataItem,
+    RedacterDataItemContent, Redacters,
+};
+use crate::reporter::AppReporter;
+use crate::AppResult;
+
+#[derive(Debug, Clone)]
+pub struct ExternalRedacterOptions {
+    /// Program and its arguments
+    pub command: Vec<String>,
+    pub image_support: bool,
+    pub entity_type_filter: RedactEntityTypeFilter,
+}
+
+/// Redacter running external programs, so proprietary detection engines can be used without modifying the crate.
+/// For every item the program is started with a JSON request in stdin and responds with JSON in stdout:
+/// either redacted content of the same type or (for text) entities to redact as UTF-8 byte offsets.
+#[derive(Clone)]
+pub struct ExternalRedacter<'a> {
+    options: ExternalRedacterOptions,
+    #[allow(dead_code)]
+    reporter: &'a AppReporter<'a>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ExternalRedacterContent {
+    Text {
+        value: String,
+    },
+    Table {
+        headers: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+    Image {
+        mime_type: String,
+        /// Base64 encoded image
+        data: String,
+    },
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct ExternalRedacterRequest {
+    file_name: String,
+    media_type: Option<String>,
+    content: ExternalRedacterContent,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+struct ExternalRedacterResponse {
+    content: Option<ExternalRedacterContent>,
+    #[serde(default)]
+    entities: Vec<ExternalRedacterEntity>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct ExternalRedacterEntity {
+    start: usize,
+    end: usize,
+    /// Entity types such as `person-name` are filtered by `--redact-entity-types`, others are treated as `other`
+    entity_type: Option<String>,
+}
+
+impl<'a> ExternalRedacter<'a> {
+    pub async fn new(
+        options: ExternalRedacterOptions,
+        reporter: &'a AppReporter<'a>,
+    ) -> AppResult<Self> {
+        if options.command.is_empty() {
+            return Err(AppError::RedacterConfigError {
+                message: "External redacter command is empty".to_string(),
+            });
+        }
+        Ok(Self { options, reporter })
+    }
+
+    async fn call_command(
+        &self,
+        request: &ExternalRedacterRequest,
+    ) -> AppResult<ExternalRedacterResponse> {
+        let mut child = tokio::process::Command::new(&self.options.command[0])
+            .args(&self.options.command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| AppError::RedacterConfigError {
+                message: format!(
+                    "Failed to start external redacter '{}': {}",
+                    self.options.command[0], err
+                ),
+            })?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&serde_json::to_vec(request)?).await?;
+        }
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            return Err(AppError::provider_error(
+                RedacterType::External,
+                format!(
+                    "External redacter failed with {}: {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ));
+        }
+        serde_json::from_slice(&output.stdout).map_err(|err| {
+            AppError::provider_error(
+                RedacterType::External,
+                format!("Invalid response of external redacter: {}", err),
+            )
+        })
+    }
+
+    fn redact_entities(
+        &self,
+        text: String,
+        entities: Vec<ExternalRedacterEntity>,
+    ) -> AppResult<String> {
+        let mut entities: Vec<ExternalRedacterEntity> = entities
+            .into_iter()
+            .filter(|entity| {
+                self.options.entity_type_filter.accepts(
+                    entity
+                        .entity_type
+                        .as_ref()
+                        .and_then(|entity_type| RedactEntityType::from_str(entity_type, true).ok())
+                        .unwrap_or(RedactEntityType::Other),
+                ) && self.options.entity_type_filter.accepts_span(
+                    &text,
+                    Some(entity.start),
+                    Some(entity.end),
+                )
+            })
+            .collect();
+        entities.sort_by_key(|entity| entity.start);
+        let mut redacted = String::with_capacity(text.len());
+        let mut position = 0;
+        for entity in entities {
+            if entity.start > entity.end
+                || entity.end > text.len()
+                || !text.is_char_boundary(entity.start)
+                || !text.is_char_boundary(entity.end)
+            {
+                return Err(AppError::provider_error(
+                    RedacterType::External,
+                    format!(
+                        "External redacter returned invalid entity offsets {}..{}",
+                        entity.start, entity.end
+                    ),
+                ));
+            }
+            // Overlapping entities are redacted once
+            let start = entity.start.max(position);
+            if start < entity.end {
+                redacted.push_str(&text[position..start]);
+                redacted.push_str(&"X".repeat(text[start..entity.end].chars().count()));
+                position = entity.end;
+            }
+        }
+        redacted.push_str(&text[position..]);
+        Ok(redacted)
+    }
+
+    fn to_request_content(content: RedacterDataItemContent) -> AppResult<ExternalRedacterContent> {
+        match content {
+            RedacterDataItemContent::Value(value) => Ok(ExternalRedacterContent::Text { value }),
+            RedacterDataItemContent::Table { headers, rows } => {
+                Ok(ExternalRedacterContent::Table { headers, rows })
+            }
+            RedacterDataItemContent::Image { mime_type, data } => {
+                Ok(ExternalRedacterContent::Image {
+                    mime_type: mime_type.to_string(),
+                    data: base64::engine::general_purpose::STANDARD.encode(&data),
+                })
+            }
+            RedacterDataItemContent::Pdf { .. } => Err(AppError::SystemError {
+                message: "Attempt to redact of unsupported type".to_string(),
+            }),
+        }
+    }
+
+    fn from_response_content(
+        content: ExternalRedacterContent,
+    ) -> AppResult<RedacterDataItemContent> {
+        match content {
+            ExternalRedacterContent::Text { value } => Ok(RedacterDataItemContent::Value(value)),
+            ExternalRedacterContent::Table { headers, rows } => {
+                Ok(RedacterDataItemContent::Table { headers, rows })
+            }
+            ExternalRedacterContent::Image { mime_type, data } => {
+                Ok(RedacterDataItemContent::Image {
+                    mime_type: mime_type.parse().map_err(|err| AppError::SystemError {
+                        message: format!(
+                            "External redacter returned invalid media type '{}': {}",
+                            mime_type, err
+                        ),
+                    })?,
+                    data: base64::engine::general_purpose::STANDARD
+                        .decode(data)
+                        .map_err(|err| AppError::SystemError {
+                            message: format!(
+                                "External redacter returned image not encoded in base64: {}",
+                                err
+                            ),
+                        })?
+                        .into(),
+                })
+            }
+        }
+    }
+}
+
+impl<'a> Redacter for ExternalRedacter<'a> {
+    async fn redact(&self, input: RedacterDataItem) -> AppResult<RedacterDataItem> {
+        let request = ExternalRedacterRequest {
+            file_name: input.file_ref.relative_path.value().clone(),
+            media_type: input
+                .file_ref
+                .media_type
+                .as_ref()
+                .map(|media_type| media_type.to_string()),
+            content: Self::to_request_content(input.content)?,
+        };
+        let response = self.call_command(&request).await?;
+        let content = match (response.content, request.content) {
+            (Some(content), _) => Self::from_response_content(content)?,
+            (None, ExternalRedacterContent::Text { value }) => {
+                RedacterDataItemContent::Value(self.redact_entities(value, response.entities)?)
+            }
+            (None, _) => {
+                return Err(AppError::provider_error(
+                    RedacterType::External,
+                    "External redacter returned no redacted content".to_string(),
+                ))
+            }
+        };
+        Ok(RedacterDataItem {
+            file_ref: input.file_ref,
+            content,
+        })
+    }
+
+    async fn redact_support(&self, file_ref: &FileSystemRef) -> AppResult<RedactSupport> {
+        Ok(match file_ref.media_type.as_ref() {
+            Some(media_type) if Redacters::is_mime_text(media_type) => RedactSupport::Supported,
+            Some(media_type) if Redacters::is_mime_table(media_type) => RedactSupport::Supported,
+            Some(media_type)
+                if self.options.image_support && Redacters::is_mime_image(media_type) =>
+            {
+                RedactSupport::Supported
+            }
+            _ => RedactSupport::Unsupported,
+        })
+    }
+
+    fn redacter_type(&self) -> RedacterType {
+        RedacterType::External
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use console::Term;
+
+    #[allow(dead_code)]
+    fn test_item(content: RedacterDataItemContent) -> RedacterDataItem {
+        RedacterDataItem {
+            file_ref: FileSystemRef {
+                relative_path: "temp_file.txt".into(),
+                media_type: Some(mime::TEXT_PLAIN),
+                file_size: None,
+                modified_at: None,
+                checksum: None,
+            },
+            content,
+        }
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(unix), ignore)]
+    async fn redact_text_with_entities_test() -> AppResult<()> {
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let redacter = ExternalRedacter::new(
+            ExternalRedacterOptions {
+                command: vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    r#"cat > /dev/null; echo '{"entities": [{"start": 7, "end": 15, "entity_type": "person-name"}, {"start": 19, "end": 27, "entity_type": "location"}]}'"#.to_string(),
+                ],
+                image_support: false,
+                entity_type_filter: RedactEntityTypeFilter {
+                    redact_entity_types: None,
+                    ignore_entity_types: vec![RedactEntityType::Location],
+                    keep_terms: vec![],
+                },
+            },
+            &reporter,
+        )
+        .await?;
+
+        let redacted = redacter
+            .redact(test_item(RedacterDataItemContent::Value(
+                "Hello, John Doe in München".to_string(),
+            )))
+            .await?;
+        match redacted.content {
+            RedacterDataItemContent::Value(value) => {
+                assert_eq!(value, "Hello, XXXXXXXX in München")
+            }
+            _ => panic!("Unexpected redacted content type"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(unix), ignore)]
+    async fn redact_table_with_content_and_errors_test() -> AppResult<()> {
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let redacter = |script: &str| {
+            ExternalRedacter::new(
+                ExternalRedacterOptions {
+                    command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+                    image_support: false,
+                    entity_type_filter: RedactEntityTypeFilter::default(),
+                },
+                &reporter,
+            )
+        };
+        let table = || RedacterDataItemContent::Table {
+            headers: vec!["name".to_string()],
+            rows: vec![vec!["John".to_string()]],
+        };
+
+        let redacted = redacter(
+            r#"cat > /dev/null; echo '{"content": {"type": "table", "headers": ["name"], "rows": [["[NAME]"]]}}'"#,
+        )
+        .await?
+        .redact(test_item(table()))
+        .await?;
+        match redacted.content {
+            RedacterDataItemContent::Table { rows, .. } => {
+                assert_eq!(rows, vec![vec!["[NAME]".to_string()]])
+            }
+            _ => panic!("Unexpected redacted content type"),
+        }
+
+        assert!(
+            redacter("cat > /dev/null; echo 'engine unavailable' >&2; exit 1")
+                .await?
+                .redact(test_item(table()))
+                .await
+                .is_err()
+        );
+        assert!(redacter(r#"cat > /dev/null; echo '{"entities": []}'"#)
+            .await?
+            .redact(test_item(table()))
+            .await
+            .is_err());
+        Ok(())
+    }
+}
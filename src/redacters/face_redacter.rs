@@ -0,0 +1,222 @@
+use crate::common_types::{GcpProjectId, TextImageCoords};
+use crate::errors::AppError;
+use crate::metrics::{AppMetric, AppMetrics};
+use crate::redacters::{
+    redact_image_at_coords, ImageRedactionOptions, RedacterDataItem, RedacterDataItemContent,
+};
+use crate::reporter::AppReporter;
+use crate::AppResult;
+use gcloud_sdk::google::cloud::vision::v1::image_annotator_client::ImageAnnotatorClient;
+use gcloud_sdk::google::cloud::vision::v1::{
+    feature, AnnotateImageRequest, BatchAnnotateImagesRequest, BoundingPoly, Feature, Image,
+};
+use gcloud_sdk::tonic::metadata::MetadataValue;
+use gcloud_sdk::{tonic, GoogleApi, GoogleAuthMiddleware};
+use rvstruct::ValueStruct;
+use std::time::Instant;
+
+#[derive(Debug, Clone)]
+pub struct FaceRedacterOptions {
+    pub project_id: GcpProjectId,
+}
+
+/// Detects faces in images using GCP Vision API and redacts them,
+/// since photos of people are sensitive even if they don't contain any text.
+#[derive(Clone)]
+pub struct FaceRedacter<'a> {
+    client: GoogleApi<ImageAnnotatorClient<GoogleAuthMiddleware>>,
+    face_redacter_options: FaceRedacterOptions,
+    #[allow(dead_code)]
+    reporter: &'a AppReporter<'a>,
+}
+
+impl<'a> FaceRedacter<'a> {
+    pub const PROVIDER_NAME: &'static str = "gcp-vision";
+
+    pub async fn new(
+        face_redacter_options: FaceRedacterOptions,
+        reporter: &'a AppReporter<'a>,
+    ) -> AppResult<Self> {
+        let client = GoogleApi::from_function(
+            ImageAnnotatorClient::new,
+            "https://vision.googleapis.com",
+            None,
+        )
+        .await?;
+        Ok(FaceRedacter {
+            client,
+            face_redacter_options,
+            reporter,
+        })
+    }
+
+    pub async fn detect_faces(&self, data: &[u8]) -> AppResult<Vec<TextImageCoords>> {
+        let mut request = tonic::Request::new(BatchAnnotateImagesRequest {
+            requests: vec![AnnotateImageRequest {
+                image: Some(Image {
+                    content: data.to_vec(),
+                    source: None,
+                }),
+                features: vec![Feature {
+                    r#type: feature::Type::FaceDetection.into(),
+                    ..Feature::default()
+                }],
+                image_context: None,
+            }],
+            parent: format!(
+                "projects/{}/locations/global",
+                self.face_redacter_options.project_id.value()
+            ),
+            ..BatchAnnotateImagesRequest::default()
+        });
+        request.metadata_mut().insert(
+            "x-goog-user-project",
+            MetadataValue::<tonic::metadata::Ascii>::try_from(
+                self.face_redacter_options.project_id.value(),
+            )?,
+        );
+        let started = Instant::now();
+        let response = self.client.get().batch_annotate_images(request).await;
+        let labels = [("redacter", Self::PROVIDER_NAME)];
+        let metrics = AppMetrics::global();
+        metrics.inc(AppMetric::PROVIDER_CALLS, &labels);
+        metrics.add_duration(AppMetric::PROVIDER_DURATION, &labels, started.elapsed());
+        if response.is_err() {
+            metrics.inc(AppMetric::PROVIDER_FAILURES, &labels);
+        }
+        let response = response?;
+        let mut coords = vec![];
+        for image_response in response.into_inner().responses {
+            if let Some(error) = image_response.error {
+                return Err(AppError::SystemError {
+                    message: format!("GCP Vision face detection error: {}", error.message),
+                });
+            }
+            coords.extend(
+                image_response
+                    .face_annotations
+                    .iter()
+                    .flat_map(|face| face.bounding_poly.as_ref())
+                    .flat_map(Self::bounding_poly_to_coords),
+            );
+        }
+        Ok(coords)
+    }
+
+    /// Redacts detected faces in the image, returning coordinates of the faces found
+    pub async fn redact_faces(
+        &self,
+        input: RedacterDataItem,
+        image_redaction: &ImageRedactionOptions,
+    ) -> AppResult<(RedacterDataItem, Vec<TextImageCoords>)> {
+        match input.content {
+            RedacterDataItemContent::Image { mime_type, data } => {
+                let faces_coords = self.detect_faces(&data).await?;
+                let redacted_data = if !faces_coords.is_empty() {
+                    redact_image_at_coords(
+                        mime_type.clone(),
+                        data,
+                        faces_coords.clone(),
+                        &image_redaction.without_padding(),
+                    )?
+                } else {
+                    data
+                };
+                Ok((
+                    RedacterDataItem {
+                        file_ref: input.file_ref,
+                        content: RedacterDataItemContent::Image {
+                            mime_type,
+                            data: redacted_data,
+                        },
+                    },
+                    faces_coords,
+                ))
+            }
+            _ => Err(AppError::SystemError {
+                message: "Attempt to redact faces in non-image content".to_string(),
+            }),
+        }
+    }
+
+    fn bounding_poly_to_coords(bounding_poly: &BoundingPoly) -> Option<TextImageCoords> {
+        // Missing coordinates of partially visible faces are reported as zeros
+        let xs = bounding_poly.vertices.iter().map(|vertex| vertex.x.max(0));
+        let ys = bounding_poly.vertices.iter().map(|vertex| vertex.y.max(0));
+        Some(TextImageCoords {
+            x1: xs.clone().min()? as f32,
+            y1: ys.clone().min()? as f32,
+            x2: xs.max()? as f32,
+            y2: ys.max()? as f32,
+            text: None,
+        })
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::file_systems::FileSystemRef;
+    use console::Term;
+    use gcloud_sdk::google::cloud::vision::v1::Vertex;
+
+    #[test]
+    fn bounding_poly_to_coords_test() {
+        let bounding_poly = BoundingPoly {
+            vertices: vec![
+                Vertex { x: 10, y: 20 },
+                Vertex { x: 110, y: 20 },
+                Vertex { x: 110, y: 140 },
+                Vertex { x: 10, y: 140 },
+            ],
+            normalized_vertices: vec![],
+        };
+        let coords = FaceRedacter::bounding_poly_to_coords(&bounding_poly).unwrap();
+        assert_eq!(
+            (coords.x1, coords.y1, coords.x2, coords.y2),
+            (10.0, 20.0, 110.0, 140.0)
+        );
+        assert!(FaceRedacter::bounding_poly_to_coords(&BoundingPoly::default()).is_none());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ci-gcp"), ignore)]
+    async fn redact_faces_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let test_gcp_project_id =
+            std::env::var("TEST_GCP_PROJECT").expect("TEST_GCP_PROJECT required");
+        let test_content = tokio::fs::read("test-fixtures/media/form-example.png").await?;
+
+        let file_ref = FileSystemRef {
+            relative_path: "form-example.png".into(),
+            media_type: Some(mime::IMAGE_PNG),
+            file_size: Some(test_content.len()),
+            modified_at: None,
+            checksum: None,
+        };
+
+        let input = RedacterDataItem {
+            file_ref,
+            content: RedacterDataItemContent::Image {
+                mime_type: mime::IMAGE_PNG,
+                data: test_content.into(),
+            },
+        };
+
+        let redacter = FaceRedacter::new(
+            FaceRedacterOptions {
+                project_id: GcpProjectId::new(test_gcp_project_id),
+            },
+            &reporter,
+        )
+        .await?;
+
+        let (_, faces_coords) = redacter
+            .redact_faces(input, &ImageRedactionOptions::default())
+            .await?;
+        assert!(faces_coords.is_empty());
+
+        Ok(())
+    }
+}
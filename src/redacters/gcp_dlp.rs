@@ -1,18 +1,22 @@
 use crate::args::RedacterType;
-use crate::common_types::GcpProjectId;
+use crate::common_types::{GcpProjectId, TextImageCoords};
 use crate::errors::AppError;
 use crate::file_systems::FileSystemRef;
 use crate::redacters::{
-    RedactSupport, Redacter, RedacterDataItem, RedacterDataItemContent, Redacters,
+    redact_image_at_coords, ImageRedactionOptions, ImageRedactionStyle, RedactEntityType,
+    RedactEntityTypeFilter, RedactFinding, RedactSupport, Redacter, RedacterDataItem,
+    RedacterDataItemContent, Redacters,
 };
 use crate::reporter::AppReporter;
 use crate::AppResult;
+use clap::ValueEnum;
 use gcloud_sdk::google::privacy::dlp::v2::dlp_service_client::DlpServiceClient;
 use gcloud_sdk::tonic::metadata::MetadataValue;
 use gcloud_sdk::{tonic, GoogleApi, GoogleAuthMiddleware};
+use image::ImageFormat;
 use mime::Mime;
 use rvstruct::ValueStruct;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use tokio_util::bytes;
 
 #[derive(Clone)]
@@ -28,6 +32,29 @@ pub struct GcpDlpRedacterOptions {
     pub project_id: GcpProjectId,
     pub user_defined_built_in_info_types: Vec<String>,
     pub user_defined_stored_info_types: Vec<String>,
+    pub entity_type_filter: RedactEntityTypeFilter,
+    pub transformation: GcpDlpTransformation,
+    pub crypto_key: Option<GcpDlpKmsWrappedKey>,
+    pub surrogate_info_type: Option<String>,
+    pub image_redaction: ImageRedactionOptions,
+}
+
+/// How the values found by GCP DLP are transformed in text and tables:
+/// replaced with [REDACTED], encrypted with format-preserving encryption (FFX)
+/// or encrypted deterministically (AES-SIV) producing base64 encoded tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum GcpDlpTransformation {
+    #[default]
+    Replace,
+    Fpe,
+    Deterministic,
+}
+
+/// Data encryption key wrapped (encrypted) by Cloud KMS key
+#[derive(Debug, Clone)]
+pub struct GcpDlpKmsWrappedKey {
+    pub crypto_key_name: String,
+    pub wrapped_key: Vec<u8>,
 }
 
 impl<'a> GcpDlpRedacter<'a> {
@@ -53,10 +80,26 @@ impl<'a> GcpDlpRedacter<'a> {
         "GCP_API_KEY",
         "ENCRYPTION_KEY",
     ];
+    const FPE_PHONE_NUMBER_ALPHABET: &'static str = "0123456789 +-().";
+    const FPE_CARD_NUMBER_ALPHABET: &'static str = "0123456789 -";
+    const FPE_EMAIL_ADDRESS_ALPHABET: &'static str =
+        "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz@.-_+";
+    /// All printable ASCII characters, the largest alphabet supported by DLP (radix 95)
+    const FPE_TEXT_ALPHABET: &'static str = " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
     pub async fn new(
         gcp_dlp_options: GcpDlpRedacterOptions,
         reporter: &'a AppReporter<'a>,
     ) -> AppResult<Self> {
+        // DLP uses its default detectors if no info types are specified, so filters excluding
+        // all info types would redact everything in images and nothing in texts otherwise
+        if Self::built_in_info_types(&gcp_dlp_options).is_empty()
+            && gcp_dlp_options.user_defined_stored_info_types.is_empty()
+        {
+            return Err(AppError::RedacterConfigError {
+                message: "No GCP DLP info types are left after applying entity type filters"
+                    .to_string(),
+            });
+        }
         let client =
             GoogleApi::from_function(DlpServiceClient::new, "https://dlp.googleapis.com", None)
                 .await?;
@@ -95,12 +138,56 @@ impl<'a> GcpDlpRedacter<'a> {
                 content,
             })
         } else {
-            Err(AppError::SystemError {
-                message: "No content item in the response".to_string(),
-            })
+            Err(AppError::provider_error(
+                RedacterType::GcpDlp,
+                "No content item in the response".to_string(),
+            ))
         }
     }
 
+    /// Findings in the text, used to verify redacted content
+    pub async fn detect_text(&self, text: String) -> AppResult<Vec<RedactFinding>> {
+        let mut request = tonic::Request::new(
+            gcloud_sdk::google::privacy::dlp::v2::InspectContentRequest {
+                parent: format!(
+                    "projects/{}/locations/global",
+                    self.gcp_dlp_options.project_id.value()
+                ),
+                inspect_config: Some(self.create_inspect_config()),
+                item: Some(RedacterDataItemContent::Value(text).try_into()?),
+                ..gcloud_sdk::google::privacy::dlp::v2::InspectContentRequest::default()
+            },
+        );
+        request.metadata_mut().insert(
+            "x-goog-user-project",
+            MetadataValue::<tonic::metadata::Ascii>::try_from(
+                self.gcp_dlp_options.project_id.value(),
+            )?,
+        );
+        let response = self.client.get().inspect_content(request).await?;
+        Ok(response
+            .into_inner()
+            .result
+            .map(|result| {
+                result
+                    .findings
+                    .into_iter()
+                    .filter_map(|finding| {
+                        let byte_range = finding.location.and_then(|location| location.byte_range);
+                        finding.info_type.map(|info_type| {
+                            RedactFinding::new(
+                                RedacterType::GcpDlp,
+                                info_type.name,
+                                byte_range.map(|range| range.start as usize),
+                                byte_range.map(|range| range.end as usize),
+                            )
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
     async fn redact_image_content(
         &self,
         input_bytes_content: gcloud_sdk::google::privacy::dlp::v2::ByteContentItem,
@@ -112,6 +199,7 @@ impl<'a> GcpDlpRedacter<'a> {
                     self.gcp_dlp_options.project_id.value()
                 ),
                 inspect_config: Some(self.create_inspect_config()),
+                image_redaction_configs: self.create_image_redaction_configs(),
                 byte_item: Some(input_bytes_content),
                 ..gcloud_sdk::google::privacy::dlp::v2::RedactImageRequest::default()
             });
@@ -125,14 +213,80 @@ impl<'a> GcpDlpRedacter<'a> {
         Ok(response.into_inner().redacted_image.into())
     }
 
+    /// Bounding boxes of findings in the image, so images are redacted
+    /// with styles not supported by DLP (such as blur)
+    async fn inspect_image_content(
+        &self,
+        input_bytes_content: gcloud_sdk::google::privacy::dlp::v2::ByteContentItem,
+    ) -> AppResult<Vec<TextImageCoords>> {
+        let mut request = tonic::Request::new(
+            gcloud_sdk::google::privacy::dlp::v2::InspectContentRequest {
+                parent: format!(
+                    "projects/{}/locations/global",
+                    self.gcp_dlp_options.project_id.value()
+                ),
+                inspect_config: Some(self.create_inspect_config()),
+                item: Some(gcloud_sdk::google::privacy::dlp::v2::ContentItem {
+                    data_item: Some(
+                        gcloud_sdk::google::privacy::dlp::v2::content_item::DataItem::ByteItem(
+                            input_bytes_content,
+                        ),
+                    ),
+                }),
+                ..gcloud_sdk::google::privacy::dlp::v2::InspectContentRequest::default()
+            },
+        );
+        request.metadata_mut().insert(
+            "x-goog-user-project",
+            MetadataValue::<tonic::metadata::Ascii>::try_from(
+                self.gcp_dlp_options.project_id.value(),
+            )?,
+        );
+        let response = self.client.get().inspect_content(request).await?;
+        Ok(response
+            .into_inner()
+            .result
+            .map(|result| result.findings)
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|finding| finding.location.map(|location| location.content_locations))
+            .flatten()
+            .flat_map(|content_location| match content_location.location {
+                Some(
+                    gcloud_sdk::google::privacy::dlp::v2::content_location::Location::ImageLocation(
+                        image_location,
+                    ),
+                ) => image_location.bounding_boxes,
+                _ => vec![],
+            })
+            .map(|bounding_box| TextImageCoords {
+                x1: bounding_box.left as f32,
+                y1: bounding_box.top as f32,
+                x2: (bounding_box.left + bounding_box.width) as f32,
+                y2: (bounding_box.top + bounding_box.height) as f32,
+                text: None,
+            })
+            .collect())
+    }
+
     pub async fn redact_image_file(&self, input: RedacterDataItem) -> AppResult<RedacterDataItem> {
         match &input.content {
-            RedacterDataItemContent::Image { mime_type, data: _ } => {
+            RedacterDataItemContent::Image { mime_type, data } => {
                 let output_mime = mime_type.clone();
+                let image_redaction = &self.gcp_dlp_options.image_redaction;
 
+                let redacted_data = if image_redaction.style == ImageRedactionStyle::Solid {
+                    self.redact_image_content(input.content.try_into()?).await?
+                } else {
+                    let data = data.clone();
+                    let pii_coords = self
+                        .inspect_image_content(input.content.try_into()?)
+                        .await?;
+                    redact_image_at_coords(output_mime.clone(), data, pii_coords, image_redaction)?
+                };
                 let content = RedacterDataItemContent::Image {
                     mime_type: output_mime,
-                    data: self.redact_image_content(input.content.try_into()?).await?,
+                    data: redacted_data,
                 };
                 Ok(RedacterDataItem {
                     file_ref: input.file_ref,
@@ -145,22 +299,81 @@ impl<'a> GcpDlpRedacter<'a> {
         }
     }
 
+    /// Converts images to PNG for DLP, and redacted images back to their original format
+    pub async fn redact_converted_image_file(
+        &self,
+        input: RedacterDataItem,
+    ) -> AppResult<RedacterDataItem> {
+        match input.content {
+            RedacterDataItemContent::Image { mime_type, data } => {
+                let image_format = ImageFormat::from_mime_type(&mime_type).ok_or_else(|| {
+                    AppError::SystemError {
+                        message: format!("Unsupported image format: {}", mime_type),
+                    }
+                })?;
+                let redacted_png = self
+                    .redact_image_file(RedacterDataItem {
+                        file_ref: input.file_ref.clone(),
+                        content: RedacterDataItemContent::Image {
+                            mime_type: mime::IMAGE_PNG,
+                            data: Self::convert_image_format(
+                                &data,
+                                image_format,
+                                ImageFormat::Png,
+                            )?,
+                        },
+                    })
+                    .await?;
+                match redacted_png.content {
+                    RedacterDataItemContent::Image { data, .. } => Ok(RedacterDataItem {
+                        file_ref: input.file_ref,
+                        content: RedacterDataItemContent::Image {
+                            data: Self::convert_image_format(
+                                &data,
+                                ImageFormat::Png,
+                                image_format,
+                            )?,
+                            mime_type,
+                        },
+                    }),
+                    _ => Err(AppError::SystemError {
+                        message: "Redacted image is not returned as image".to_string(),
+                    }),
+                }
+            }
+            _ => Err(AppError::SystemError {
+                message: "Attempt to redact of unsupported image type".to_string(),
+            }),
+        }
+    }
+
+    fn convert_image_format(
+        data: &[u8],
+        from: ImageFormat,
+        to: ImageFormat,
+    ) -> AppResult<bytes::Bytes> {
+        let image = image::load_from_memory_with_format(data, from)?;
+        let mut output = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut output, to)?;
+        Ok(output.into_inner().into())
+    }
+
     fn create_inspect_config(&self) -> gcloud_sdk::google::privacy::dlp::v2::InspectConfig {
-        gcloud_sdk::google::privacy::dlp::v2::InspectConfig {
-            info_types: self
-                .create_built_in_info_types()
-                .iter()
-                .map(|v| gcloud_sdk::google::privacy::dlp::v2::InfoType {
-                    name: v.to_string(),
-                    ..gcloud_sdk::google::privacy::dlp::v2::InfoType::default()
-                })
-                .collect(),
-            custom_info_types: self
-                .gcp_dlp_options
-                .user_defined_stored_info_types
-                .iter()
-                .map(
-                    |stored_info_type_name| {
+        let mut inspect_config =
+            gcloud_sdk::google::privacy::dlp::v2::InspectConfig {
+                info_types: self
+                    .create_built_in_info_types()
+                    .iter()
+                    .map(|v| gcloud_sdk::google::privacy::dlp::v2::InfoType {
+                        name: v.to_string(),
+                        ..gcloud_sdk::google::privacy::dlp::v2::InfoType::default()
+                    })
+                    .collect(),
+                custom_info_types: self
+                    .gcp_dlp_options
+                    .user_defined_stored_info_types
+                    .iter()
+                    .map(|stored_info_type_name| {
                         gcloud_sdk::google::privacy::dlp::v2::CustomInfoType {
                 info_type: Some(gcloud_sdk::google::privacy::dlp::v2::InfoType {
                     name: stored_info_type_name.clone(),
@@ -180,11 +393,85 @@ impl<'a> GcpDlpRedacter<'a> {
                 ),
                 ..gcloud_sdk::google::privacy::dlp::v2::CustomInfoType::default()
             }
-                    },
-                )
-                .collect(),
-            ..gcloud_sdk::google::privacy::dlp::v2::InspectConfig::default()
+                    })
+                    .collect(),
+                ..gcloud_sdk::google::privacy::dlp::v2::InspectConfig::default()
+            };
+        let keep_terms = &self.gcp_dlp_options.entity_type_filter.keep_terms;
+        if !keep_terms.is_empty() {
+            // Findings matching keep terms are excluded for all info types
+            inspect_config
+                .rule_set
+                .push(gcloud_sdk::google::privacy::dlp::v2::InspectionRuleSet {
+                    info_types: inspect_config
+                        .info_types
+                        .iter()
+                        .cloned()
+                        .chain(
+                            inspect_config
+                                .custom_info_types
+                                .iter()
+                                .filter_map(|custom_info_type| custom_info_type.info_type.clone()),
+                        )
+                        .collect(),
+                    rules: vec![gcloud_sdk::google::privacy::dlp::v2::InspectionRule {
+                        r#type: Some(
+                            gcloud_sdk::google::privacy::dlp::v2::inspection_rule::Type::ExclusionRule(
+                                gcloud_sdk::google::privacy::dlp::v2::ExclusionRule {
+                                    matching_type: gcloud_sdk::google::privacy::dlp::v2::MatchingType::FullMatch
+                                        as i32,
+                                    r#type: Some(
+                                        gcloud_sdk::google::privacy::dlp::v2::exclusion_rule::Type::Dictionary(
+                                            gcloud_sdk::google::privacy::dlp::v2::custom_info_type::Dictionary {
+                                                source: Some(
+                                                    gcloud_sdk::google::privacy::dlp::v2::custom_info_type::dictionary::Source::WordList(
+                                                        gcloud_sdk::google::privacy::dlp::v2::custom_info_type::dictionary::WordList {
+                                                            words: keep_terms.clone(),
+                                                        },
+                                                    ),
+                                                ),
+                                            },
+                                        ),
+                                    ),
+                                },
+                            ),
+                        ),
+                    }],
+                });
         }
+        inspect_config
+    }
+
+    /// Solid regions are filled with the configured color for all info types
+    fn create_image_redaction_configs(
+        &self,
+    ) -> Vec<gcloud_sdk::google::privacy::dlp::v2::redact_image_request::ImageRedactionConfig> {
+        let color = self.gcp_dlp_options.image_redaction.color;
+        let inspect_config = self.create_inspect_config();
+        inspect_config
+            .info_types
+            .into_iter()
+            .chain(
+                inspect_config
+                    .custom_info_types
+                    .into_iter()
+                    .filter_map(|custom_info_type| custom_info_type.info_type),
+            )
+            .map(|info_type| {
+                gcloud_sdk::google::privacy::dlp::v2::redact_image_request::ImageRedactionConfig {
+                    redaction_color: Some(gcloud_sdk::google::privacy::dlp::v2::Color {
+                        red: color.red as f32 / 255.0,
+                        green: color.green as f32 / 255.0,
+                        blue: color.blue as f32 / 255.0,
+                    }),
+                    target: Some(
+                        gcloud_sdk::google::privacy::dlp::v2::redact_image_request::image_redaction_config::Target::InfoType(
+                            info_type,
+                        ),
+                    ),
+                }
+            })
+            .collect()
     }
 
     fn create_deidentify_config(&self) -> gcloud_sdk::google::privacy::dlp::v2::DeidentifyConfig {
@@ -194,39 +481,133 @@ impl<'a> GcpDlpRedacter<'a> {
             .iter()
             .map(|s| s.as_str())
             .collect();
+        let info_types: Vec<&str> = self
+            .create_built_in_info_types()
+            .union(&user_stored_info_types_set)
+            .copied()
+            .collect();
         gcloud_sdk::google::privacy::dlp::v2::DeidentifyConfig {
             transformation: Some(gcloud_sdk::google::privacy::dlp::v2::deidentify_config::Transformation::InfoTypeTransformations(
                 gcloud_sdk::google::privacy::dlp::v2::InfoTypeTransformations {
-                    transformations: vec![
-                        gcloud_sdk::google::privacy::dlp::v2::info_type_transformations::InfoTypeTransformation {
-                            info_types: self.create_built_in_info_types().union(
-                                &user_stored_info_types_set
-                            ).collect::<Vec<_>>().iter().map(|v| gcloud_sdk::google::privacy::dlp::v2::InfoType {
-                                name: v.to_string(),
-                                ..gcloud_sdk::google::privacy::dlp::v2::InfoType::default()
-                            }).collect(),
-                            primitive_transformation: Some(gcloud_sdk::google::privacy::dlp::v2::PrimitiveTransformation {
-                                transformation: Some(
-                                    gcloud_sdk::google::privacy::dlp::v2::primitive_transformation::Transformation::ReplaceConfig(gcloud_sdk::google::privacy::dlp::v2::ReplaceValueConfig {
-                                        new_value: Some(gcloud_sdk::google::privacy::dlp::v2::Value {
-                                            r#type: Some(gcloud_sdk::google::privacy::dlp::v2::value::Type::StringValue(
-                                                "[REDACTED]".to_string()
-                                            ))
-                                        })
-                                    })
-                                )
-                            }),
-                        }
-                    ]
+                    transformations: Self::create_info_type_transformations(&self.gcp_dlp_options, &info_types),
                 })),
             ..gcloud_sdk::google::privacy::dlp::v2::DeidentifyConfig::default()
         }
     }
 
+    /// Format-preserving encryption fails on characters outside of its alphabet,
+    /// so info types are encrypted with alphabets of their values, such as `@` in emails
+    /// and `+` and parentheses in phone numbers
+    fn create_info_type_transformations(
+        options: &GcpDlpRedacterOptions,
+        info_types: &[&str],
+    ) -> Vec<gcloud_sdk::google::privacy::dlp::v2::info_type_transformations::InfoTypeTransformation>
+    {
+        let mut info_types_by_alphabet: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for info_type in info_types {
+            info_types_by_alphabet
+                .entry(Self::fpe_alphabet(info_type))
+                .or_default()
+                .push(info_type);
+        }
+        let info_types_by_alphabet: Vec<(&str, Vec<&str>)> = match options.transformation {
+            GcpDlpTransformation::Fpe => info_types_by_alphabet.into_iter().collect(),
+            GcpDlpTransformation::Replace | GcpDlpTransformation::Deterministic => {
+                vec![(Self::FPE_TEXT_ALPHABET, info_types.to_vec())]
+            }
+        };
+        info_types_by_alphabet
+            .into_iter()
+            .map(|(alphabet, mut info_types)| {
+                info_types.sort();
+                gcloud_sdk::google::privacy::dlp::v2::info_type_transformations::InfoTypeTransformation {
+                    info_types: info_types
+                        .iter()
+                        .map(|info_type| gcloud_sdk::google::privacy::dlp::v2::InfoType {
+                            name: info_type.to_string(),
+                            ..gcloud_sdk::google::privacy::dlp::v2::InfoType::default()
+                        })
+                        .collect(),
+                    primitive_transformation: Some(Self::create_primitive_transformation(
+                        options, alphabet,
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    fn fpe_alphabet(info_type: &str) -> &'static str {
+        match info_type {
+            "PHONE_NUMBER" => Self::FPE_PHONE_NUMBER_ALPHABET,
+            "CREDIT_CARD_NUMBER" => Self::FPE_CARD_NUMBER_ALPHABET,
+            "EMAIL_ADDRESS" => Self::FPE_EMAIL_ADDRESS_ALPHABET,
+            _ => Self::FPE_TEXT_ALPHABET,
+        }
+    }
+
+    fn create_primitive_transformation(
+        options: &GcpDlpRedacterOptions,
+        fpe_alphabet: &str,
+    ) -> gcloud_sdk::google::privacy::dlp::v2::PrimitiveTransformation {
+        let crypto_key = options.crypto_key.as_ref().map(|key| {
+            gcloud_sdk::google::privacy::dlp::v2::CryptoKey {
+                source: Some(
+                    gcloud_sdk::google::privacy::dlp::v2::crypto_key::Source::KmsWrapped(
+                        gcloud_sdk::google::privacy::dlp::v2::KmsWrappedCryptoKey {
+                            wrapped_key: key.wrapped_key.clone(),
+                            crypto_key_name: key.crypto_key_name.clone(),
+                        },
+                    ),
+                ),
+            }
+        });
+        let surrogate_info_type = options.surrogate_info_type.as_ref().map(|name| {
+            gcloud_sdk::google::privacy::dlp::v2::InfoType {
+                name: name.clone(),
+                ..gcloud_sdk::google::privacy::dlp::v2::InfoType::default()
+            }
+        });
+        let transformation = match options.transformation {
+            GcpDlpTransformation::Replace => {
+                gcloud_sdk::google::privacy::dlp::v2::primitive_transformation::Transformation::ReplaceConfig(gcloud_sdk::google::privacy::dlp::v2::ReplaceValueConfig {
+                    new_value: Some(gcloud_sdk::google::privacy::dlp::v2::Value {
+                        r#type: Some(gcloud_sdk::google::privacy::dlp::v2::value::Type::StringValue(
+                            "[REDACTED]".to_string()
+                        ))
+                    })
+                })
+            }
+            GcpDlpTransformation::Fpe => {
+                gcloud_sdk::google::privacy::dlp::v2::primitive_transformation::Transformation::CryptoReplaceFfxFpeConfig(gcloud_sdk::google::privacy::dlp::v2::CryptoReplaceFfxFpeConfig {
+                    crypto_key,
+                    surrogate_info_type,
+                    alphabet: Some(gcloud_sdk::google::privacy::dlp::v2::crypto_replace_ffx_fpe_config::Alphabet::CustomAlphabet(
+                        fpe_alphabet.to_string()
+                    )),
+                    ..gcloud_sdk::google::privacy::dlp::v2::CryptoReplaceFfxFpeConfig::default()
+                })
+            }
+            GcpDlpTransformation::Deterministic => {
+                gcloud_sdk::google::privacy::dlp::v2::primitive_transformation::Transformation::CryptoDeterministicConfig(gcloud_sdk::google::privacy::dlp::v2::CryptoDeterministicConfig {
+                    crypto_key,
+                    surrogate_info_type,
+                    ..gcloud_sdk::google::privacy::dlp::v2::CryptoDeterministicConfig::default()
+                })
+            }
+        };
+        gcloud_sdk::google::privacy::dlp::v2::PrimitiveTransformation {
+            transformation: Some(transformation),
+        }
+    }
+
     fn create_built_in_info_types(&self) -> HashSet<&str> {
+        Self::built_in_info_types(&self.gcp_dlp_options)
+    }
+
+    fn built_in_info_types(gcp_dlp_options: &GcpDlpRedacterOptions) -> HashSet<&str> {
         [
             Self::INFO_TYPES.to_vec(),
-            self.gcp_dlp_options
+            gcp_dlp_options
                 .user_defined_built_in_info_types
                 .iter()
                 .map(|v| v.as_str())
@@ -234,6 +615,11 @@ impl<'a> GcpDlpRedacter<'a> {
         ]
         .concat()
         .into_iter()
+        .filter(|info_type| {
+            gcp_dlp_options
+                .entity_type_filter
+                .accepts(RedactEntityType::from_gcp_dlp_info_type(info_type))
+        })
         .collect()
     }
 
@@ -246,6 +632,13 @@ impl<'a> GcpDlpRedacter<'a> {
                 || mime_type.subtype() == "gif"
                 || mime_type.subtype() == "bmp")
     }
+
+    /// Other image formats such as WebP are supported if they can be decoded and encoded back locally
+    fn check_convertible_image_type(mime_type: &Mime) -> bool {
+        Redacters::is_mime_image(mime_type)
+            && ImageFormat::from_mime_type(mime_type)
+                .is_some_and(|format| format.reading_enabled() && format.writing_enabled())
+    }
 }
 
 impl<'a> Redacter for GcpDlpRedacter<'a> {
@@ -259,6 +652,11 @@ impl<'a> Redacter for GcpDlpRedacter<'a> {
             {
                 self.redact_image_file(input).await
             }
+            RedacterDataItemContent::Image { mime_type, .. }
+                if Self::check_convertible_image_type(mime_type) =>
+            {
+                self.redact_converted_image_file(input).await
+            }
             RedacterDataItemContent::Image { .. } | RedacterDataItemContent::Pdf { .. } => {
                 Err(AppError::SystemError {
                     message: "Attempt to redact of unsupported type".to_string(),
@@ -271,7 +669,10 @@ impl<'a> Redacter for GcpDlpRedacter<'a> {
         Ok(match file_ref.media_type.as_ref() {
             Some(media_type) if Redacters::is_mime_text(media_type) => RedactSupport::Supported,
             Some(media_type) if Redacters::is_mime_table(media_type) => RedactSupport::Supported,
-            Some(media_type) if Self::check_supported_image_type(media_type) => {
+            Some(media_type)
+                if Self::check_supported_image_type(media_type)
+                    || Self::check_convertible_image_type(media_type) =>
+            {
                 RedactSupport::Supported
             }
             _ => RedactSupport::Unsupported,
@@ -446,6 +847,8 @@ mod tests {
             relative_path: "temp_file.txt".into(),
             media_type: Some(mime::TEXT_PLAIN),
             file_size: Some(test_content.len()),
+            modified_at: None,
+            checksum: None,
         };
 
         let content = RedacterDataItemContent::Value(test_content.to_string());
@@ -456,6 +859,11 @@ mod tests {
                 project_id: GcpProjectId::new(test_gcp_project_id),
                 user_defined_built_in_info_types: vec![],
                 user_defined_stored_info_types: vec![],
+                entity_type_filter: RedactEntityTypeFilter::default(),
+                transformation: GcpDlpTransformation::Replace,
+                crypto_key: None,
+                surrogate_info_type: None,
+                image_redaction: ImageRedactionOptions::default(),
             },
             &reporter,
         )
@@ -471,4 +879,170 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn filtered_out_info_types_test() {
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let result = GcpDlpRedacter::new(
+            GcpDlpRedacterOptions {
+                project_id: GcpProjectId::new("test-project".to_string()),
+                user_defined_built_in_info_types: vec![],
+                user_defined_stored_info_types: vec![],
+                entity_type_filter: RedactEntityTypeFilter {
+                    redact_entity_types: Some(vec![]),
+                    ..RedactEntityTypeFilter::default()
+                },
+                transformation: GcpDlpTransformation::Replace,
+                crypto_key: None,
+                surrogate_info_type: None,
+                image_redaction: ImageRedactionOptions::default(),
+            },
+            &reporter,
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::RedacterConfigError { .. })));
+    }
+
+    #[test]
+    fn check_image_types_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let webp: Mime = "image/webp".parse()?;
+        assert!(GcpDlpRedacter::check_supported_image_type(&mime::IMAGE_PNG));
+        assert!(!GcpDlpRedacter::check_supported_image_type(&webp));
+        assert!(GcpDlpRedacter::check_convertible_image_type(&webp));
+        assert!(!GcpDlpRedacter::check_convertible_image_type(
+            &"image/x-unknown".parse()?
+        ));
+
+        let image = image::DynamicImage::ImageRgb8(image::RgbImage::new(4, 4));
+        let mut webp_data = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut webp_data, ImageFormat::WebP)?;
+        let png_data = GcpDlpRedacter::convert_image_format(
+            &webp_data.into_inner(),
+            ImageFormat::WebP,
+            ImageFormat::Png,
+        )?;
+        assert_eq!(image::guess_format(&png_data)?, ImageFormat::Png);
+        Ok(())
+    }
+
+    #[test]
+    fn create_primitive_transformation_test() {
+        let options = GcpDlpRedacterOptions {
+            project_id: GcpProjectId::new("test-project".to_string()),
+            user_defined_built_in_info_types: vec![],
+            user_defined_stored_info_types: vec![],
+            entity_type_filter: RedactEntityTypeFilter::default(),
+            transformation: GcpDlpTransformation::Deterministic,
+            crypto_key: Some(GcpDlpKmsWrappedKey {
+                crypto_key_name: "projects/p/locations/global/keyRings/r/cryptoKeys/k".to_string(),
+                wrapped_key: vec![1, 2, 3],
+            }),
+            surrogate_info_type: Some("REDACTED_TOKEN".to_string()),
+            image_redaction: ImageRedactionOptions::default(),
+        };
+
+        match GcpDlpRedacter::create_primitive_transformation(&options, GcpDlpRedacter::FPE_TEXT_ALPHABET).transformation {
+            Some(gcloud_sdk::google::privacy::dlp::v2::primitive_transformation::Transformation::CryptoDeterministicConfig(config)) => {
+                match config.crypto_key.and_then(|key| key.source) {
+                    Some(gcloud_sdk::google::privacy::dlp::v2::crypto_key::Source::KmsWrapped(key)) => {
+                        assert_eq!(key.wrapped_key, vec![1, 2, 3]);
+                        assert_eq!(key.crypto_key_name, "projects/p/locations/global/keyRings/r/cryptoKeys/k");
+                    }
+                    _ => panic!("Unexpected crypto key source"),
+                }
+                assert_eq!(
+                    config.surrogate_info_type.map(|info_type| info_type.name),
+                    Some("REDACTED_TOKEN".to_string())
+                );
+            }
+            _ => panic!("Unexpected primitive transformation"),
+        }
+
+        let replace_options = GcpDlpRedacterOptions {
+            transformation: GcpDlpTransformation::Replace,
+            crypto_key: None,
+            surrogate_info_type: None,
+            ..options
+        };
+        assert!(matches!(
+            GcpDlpRedacter::create_primitive_transformation(&replace_options, GcpDlpRedacter::FPE_TEXT_ALPHABET).transformation,
+            Some(gcloud_sdk::google::privacy::dlp::v2::primitive_transformation::Transformation::ReplaceConfig(_))
+        ));
+    }
+
+    #[test]
+    fn fpe_alphabets_test() {
+        let options = GcpDlpRedacterOptions {
+            project_id: GcpProjectId::new("test-project".to_string()),
+            user_defined_built_in_info_types: vec![],
+            user_defined_stored_info_types: vec![],
+            entity_type_filter: RedactEntityTypeFilter::default(),
+            transformation: GcpDlpTransformation::Fpe,
+            crypto_key: None,
+            surrogate_info_type: Some("REDACTED_TOKEN".to_string()),
+            image_redaction: ImageRedactionOptions::default(),
+        };
+        let transformations = GcpDlpRedacter::create_info_type_transformations(
+            &options,
+            &["EMAIL_ADDRESS", "PHONE_NUMBER", "PERSON_NAME", "LOCATION"],
+        );
+        assert_eq!(transformations.len(), 3);
+        let alphabet_of = |info_type: &str| -> String {
+            let transformation = transformations
+                .iter()
+                .find(|transformation| {
+                    transformation
+                        .info_types
+                        .iter()
+                        .any(|v| v.name == info_type)
+                })
+                .expect("Info type transformation");
+            match transformation
+                .primitive_transformation
+                .as_ref()
+                .and_then(|v| v.transformation.as_ref())
+            {
+                Some(gcloud_sdk::google::privacy::dlp::v2::primitive_transformation::Transformation::CryptoReplaceFfxFpeConfig(config)) => {
+                    match config.alphabet.as_ref() {
+                        Some(gcloud_sdk::google::privacy::dlp::v2::crypto_replace_ffx_fpe_config::Alphabet::CustomAlphabet(alphabet)) => alphabet.clone(),
+                        _ => panic!("Unexpected FPE alphabet"),
+                    }
+                }
+                _ => panic!("Unexpected primitive transformation"),
+            }
+        };
+        for (info_type, value) in [
+            ("EMAIL_ADDRESS", "john.doe+test@example.com"),
+            ("PHONE_NUMBER", "+1 (555) 123-4567"),
+            ("PERSON_NAME", "John O'Doe, Jr."),
+            (
+                "LOCATION",
+                "1600 Amphitheatre Pkwy, Mountain View, CA 94043",
+            ),
+        ] {
+            let alphabet = alphabet_of(info_type);
+            assert!((2..=95).contains(&alphabet.len()));
+            assert_eq!(
+                alphabet.chars().collect::<HashSet<char>>().len(),
+                alphabet.len()
+            );
+            assert!(
+                value.chars().all(|c| alphabet.contains(c)),
+                "{} is not in the alphabet of {}",
+                value,
+                info_type
+            );
+        }
+
+        let deterministic = GcpDlpRedacter::create_info_type_transformations(
+            &GcpDlpRedacterOptions {
+                transformation: GcpDlpTransformation::Deterministic,
+                ..options
+            },
+            &["EMAIL_ADDRESS", "PHONE_NUMBER"],
+        );
+        assert_eq!(deterministic.len(), 1);
+        assert_eq!(deterministic[0].info_types.len(), 2);
+    }
 }
@@ -0,0 +1,369 @@
+use crate::reporter::AppReporter;
+use crate::AppResult;
+use std::future::Future;
+
+/// Splits large texts into chunks fitting output limits of LLM models, so redacted texts
+/// aren't truncated by models. Chunks are split at line boundaries when possible
+/// and start with a few lines of the previous chunk as context for entities spanning chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LlmTextChunker {
+    max_chunk_tokens: usize,
+    overlap_tokens: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LlmTextChunk {
+    pub text: String,
+    /// Number of leading lines repeated from the previous chunk, which are dropped from the redacted chunk
+    /// once they are aligned with the previous redacted chunk
+    pub overlap_lines: usize,
+}
+
+impl LlmTextChunker {
+    const DEFAULT_MAX_OUTPUT_TOKENS: usize = 4096;
+    /// Output limits of model families by their name fragments, checked in order
+    const MODEL_MAX_OUTPUT_TOKENS: [(&'static str, usize); 6] = [
+        ("gpt-4o", 16384),
+        ("gpt-4.1", 32768),
+        ("gemini-1.0", 2048),
+        ("gemini", 8192),
+        ("claude", 8192),
+        ("gpt-3.5", 4096),
+    ];
+    const ASCII_CHARS_PER_TOKEN: usize = 4;
+
+    pub fn new(max_chunk_tokens: usize, overlap_tokens: usize) -> Self {
+        Self {
+            max_chunk_tokens: max_chunk_tokens.max(1),
+            overlap_tokens,
+        }
+    }
+
+    /// Redacted texts are about as long as the original ones, so chunks are limited by output limits of models
+    /// with a margin for estimation errors
+    pub fn for_model(model_name: &str) -> Self {
+        let max_output_tokens = Self::MODEL_MAX_OUTPUT_TOKENS
+            .iter()
+            .find(|(model_fragment, _)| model_name.contains(model_fragment))
+            .map(|(_, max_output_tokens)| *max_output_tokens)
+            .unwrap_or(Self::DEFAULT_MAX_OUTPUT_TOKENS);
+        let max_chunk_tokens = max_output_tokens * 3 / 4;
+        Self::new(max_chunk_tokens, max_chunk_tokens / 20)
+    }
+
+    /// Estimates tokens without model tokenizers: ASCII text is about 4 characters per token,
+    /// and other characters such as CJK ones are mostly tokens on their own
+    pub fn estimate_tokens(text: &str) -> usize {
+        let (ascii_chars, other_chars) =
+            text.chars().fold((0_usize, 0_usize), |(ascii, other), c| {
+                if c.is_ascii() {
+                    (ascii + 1, other)
+                } else {
+                    (ascii, other + 1)
+                }
+            });
+        ascii_chars.div_ceil(Self::ASCII_CHARS_PER_TOKEN) + other_chars
+    }
+
+    /// Estimates the number of chunks of a text by its size, such as for cost estimates
+    /// without reading texts
+    pub fn estimate_chunks(&self, text_bytes: usize) -> usize {
+        let tokens = text_bytes.div_ceil(Self::ASCII_CHARS_PER_TOKEN);
+        if tokens <= self.max_chunk_tokens {
+            1
+        } else {
+            // Every chunk after the first one repeats the overlap of the previous chunk
+            let chunk_tokens = self
+                .max_chunk_tokens
+                .saturating_sub(self.overlap_tokens)
+                .max(1);
+            1 + (tokens - self.max_chunk_tokens).div_ceil(chunk_tokens)
+        }
+    }
+
+    pub fn split(&self, text: &str) -> Vec<LlmTextChunk> {
+        if Self::estimate_tokens(text) <= self.max_chunk_tokens {
+            return vec![LlmTextChunk {
+                text: text.to_string(),
+                overlap_lines: 0,
+            }];
+        }
+        let segments = self.segments(text);
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        while start < segments.len() {
+            // Only whole lines are repeated, so they can be dropped from redacted chunks by counting lines
+            let mut overlap_start = start;
+            let mut chunk_tokens = 0;
+            while overlap_start > 0 && segments[overlap_start - 1].ends_with('\n') {
+                let segment_tokens = Self::estimate_tokens(segments[overlap_start - 1]);
+                if chunk_tokens + segment_tokens > self.overlap_tokens {
+                    break;
+                }
+                chunk_tokens += segment_tokens;
+                overlap_start -= 1;
+            }
+            let mut end = start;
+            while end < segments.len() {
+                let segment_tokens = Self::estimate_tokens(segments[end]);
+                if end > start && chunk_tokens + segment_tokens > self.max_chunk_tokens {
+                    break;
+                }
+                chunk_tokens += segment_tokens;
+                end += 1;
+            }
+            chunks.push(LlmTextChunk {
+                text: segments[overlap_start..end].concat(),
+                overlap_lines: start - overlap_start,
+            });
+            start = end;
+        }
+        chunks
+    }
+
+    /// Lines, with lines longer than chunks split at whitespaces or, if there are none, at characters
+    fn segments<'t>(&self, text: &'t str) -> Vec<&'t str> {
+        let mut segments = Vec::new();
+        for line in text.split_inclusive('\n') {
+            let mut rest = line;
+            while Self::estimate_tokens(rest) > self.max_chunk_tokens {
+                let max_end = rest
+                    .char_indices()
+                    .scan(
+                        (0_usize, 0_usize),
+                        |(ascii_chars, other_chars), (idx, c)| {
+                            if c.is_ascii() {
+                                *ascii_chars += 1;
+                            } else {
+                                *other_chars += 1;
+                            }
+                            let tokens =
+                                ascii_chars.div_ceil(Self::ASCII_CHARS_PER_TOKEN) + *other_chars;
+                            Some((idx + c.len_utf8(), tokens))
+                        },
+                    )
+                    .take_while(|(_, tokens)| *tokens <= self.max_chunk_tokens)
+                    .map(|(idx, _)| idx)
+                    .last()
+                    .unwrap_or_else(|| rest.chars().next().map_or(rest.len(), char::len_utf8));
+                let split_at = rest[..max_end]
+                    .rfind(char::is_whitespace)
+                    .map(|idx| idx + rest[idx..].chars().next().map_or(1, char::len_utf8))
+                    .unwrap_or(max_end);
+                segments.push(&rest[..split_at]);
+                rest = &rest[split_at..];
+            }
+            if !rest.is_empty() {
+                segments.push(rest);
+            }
+        }
+        segments
+    }
+
+    /// Redacts the text at once if it fits a chunk, or chunk by chunk reporting that it had to be chunked
+    pub async fn redact_in_chunks<F, Fut>(
+        &self,
+        text: String,
+        reporter: &AppReporter<'_>,
+        redact_chunk: F,
+    ) -> AppResult<String>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = AppResult<String>>,
+    {
+        let chunks = self.split(&text);
+        if chunks.len() == 1 {
+            return redact_chunk(text).await;
+        }
+        reporter.report(format!(
+            "↳ Text exceeds the output limit of the model, redacting it in {} chunks",
+            chunks.len()
+        ))?;
+        tracing::warn!(chunks = chunks.len(), "Text redacted in chunks");
+        let mut redacted_chunks = Vec::with_capacity(chunks.len());
+        for chunk in &chunks {
+            redacted_chunks.push(redact_chunk(chunk.text.clone()).await?);
+        }
+        Ok(Self::merge(&chunks, redacted_chunks))
+    }
+
+    /// Joins redacted chunks dropping lines repeated from previous chunks.
+    /// Trailing line breaks of redacted chunks are aligned with the original chunks, since models tend to change them
+    pub fn merge(chunks: &[LlmTextChunk], redacted_chunks: Vec<String>) -> String {
+        let mut merged = String::new();
+        for (chunk, redacted_chunk) in chunks.iter().zip(redacted_chunks) {
+            let redacted_chunk = if chunk.overlap_lines > 0 {
+                let skipped_lines = Self::redacted_overlap_lines(chunk, &redacted_chunk, &merged);
+                redacted_chunk
+                    .split_inclusive('\n')
+                    .skip(skipped_lines)
+                    .collect::<String>()
+            } else {
+                redacted_chunk
+            };
+            let redacted_chunk = redacted_chunk.trim_end_matches(['\n', '\r']);
+            merged.push_str(redacted_chunk);
+            let original_line_break =
+                &chunk.text[chunk.text.trim_end_matches(['\n', '\r']).len()..];
+            merged.push_str(original_line_break);
+        }
+        merged
+    }
+
+    /// Number of leading lines of the redacted chunk repeating the previous chunk.
+    /// Models may join or split lines, so overlapping lines are dropped by their count only
+    /// if the redacted chunk has as many lines as the original one. Otherwise, they're aligned
+    /// by content with the end of the previous redacted chunk. If neither matches, nothing is dropped,
+    /// since repeated lines are better than lost ones.
+    fn redacted_overlap_lines(chunk: &LlmTextChunk, redacted_chunk: &str, merged: &str) -> usize {
+        let lines = |text: &str| -> Vec<String> {
+            text.trim_end_matches(['\n', '\r'])
+                .split('\n')
+                .map(|line| line.trim_end().to_string())
+                .collect()
+        };
+        let redacted_lines = lines(redacted_chunk);
+        if redacted_lines.len() == lines(&chunk.text).len() {
+            return chunk.overlap_lines;
+        }
+        let merged_lines = lines(merged);
+        let max_overlap = redacted_lines.len().min(merged_lines.len());
+        let aligned_lines = (1..=max_overlap).rev().find(|overlap| {
+            let repeated_lines = &redacted_lines[..*overlap];
+            repeated_lines == &merged_lines[merged_lines.len() - overlap..]
+                && repeated_lines.iter().any(|line| !line.trim().is_empty())
+        });
+        aligned_lines.unwrap_or_else(|| {
+            tracing::warn!(
+                overlap_lines = chunk.overlap_lines,
+                "Redacted chunk doesn't match the previous chunk, overlapping lines are kept"
+            );
+            0
+        })
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_tokens_test() {
+        assert_eq!(LlmTextChunker::estimate_tokens(""), 0);
+        assert_eq!(LlmTextChunker::estimate_tokens("Hello, John"), 3);
+        assert_eq!(LlmTextChunker::estimate_tokens("こんにちは"), 5);
+    }
+
+    #[test]
+    fn estimate_chunks_test() {
+        let chunker = LlmTextChunker::new(10, 2);
+        assert_eq!(chunker.estimate_chunks(0), 1);
+        assert_eq!(chunker.estimate_chunks(40), 1);
+        assert_eq!(chunker.estimate_chunks(44), 2);
+        assert_eq!(chunker.estimate_chunks(72), 2);
+        assert_eq!(chunker.estimate_chunks(76), 3);
+    }
+
+    #[test]
+    fn split_and_merge_chunks_test() {
+        let chunker = LlmTextChunker::new(10, 4);
+        let short_text = "Hello, John\n";
+        assert_eq!(
+            chunker.split(short_text),
+            vec![LlmTextChunk {
+                text: short_text.to_string(),
+                overlap_lines: 0,
+            }]
+        );
+
+        let text = "First line here\nJohn Doe\nSecond line of text\nThird line\n";
+        let chunks = chunker.split(text);
+        assert!(chunks.len() > 1);
+        assert!(chunks
+            .iter()
+            .all(|chunk| LlmTextChunker::estimate_tokens(&chunk.text) <= 10));
+        assert!(chunks.iter().skip(1).any(|chunk| chunk.overlap_lines > 0));
+        let redacted_chunks = chunks
+            .iter()
+            .map(|chunk| {
+                chunk
+                    .text
+                    .replace("John Doe", "[REDACTED]")
+                    .trim()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            LlmTextChunker::merge(&chunks, redacted_chunks),
+            "First line here\n[REDACTED]\nSecond line of text\nThird line\n"
+        );
+
+        let long_line = "word ".repeat(30);
+        let chunks = chunker.split(&long_line);
+        assert!(chunks.len() > 1);
+        assert_eq!(
+            LlmTextChunker::merge(
+                &chunks,
+                chunks.iter().map(|chunk| chunk.text.clone()).collect()
+            ),
+            long_line
+        );
+    }
+
+    #[test]
+    fn merge_chunks_with_changed_lines_test() {
+        let chunker = LlmTextChunker::new(10, 4);
+        let text = "First line here\nJohn Doe\nSecond line of text\nThird line\n";
+        let chunks = chunker.split(text);
+        assert_eq!(chunks[1].overlap_lines, 1);
+        assert!(chunks[1].text.starts_with("John Doe\n"));
+
+        let redact = |chunks: &[LlmTextChunk], redact_second: &dyn Fn(&str) -> String| {
+            chunks
+                .iter()
+                .enumerate()
+                .map(|(index, chunk)| {
+                    if index == 1 {
+                        redact_second(&chunk.text)
+                    } else {
+                        chunk.text.replace("John Doe", "[REDACTED]")
+                    }
+                })
+                .collect::<Vec<String>>()
+        };
+
+        // The model splits a new line into two, so repeated lines are aligned by content
+        let redacted_chunks = redact(&chunks, &|text| {
+            text.replace("John Doe", "[REDACTED]")
+                .replace("Second line of text", "Second line\nof text")
+        });
+        assert_eq!(
+            LlmTextChunker::merge(&chunks, redacted_chunks),
+            "First line here\n[REDACTED]\nSecond line\nof text\nThird line\n"
+        );
+
+        // The model joins the repeated line with a new one, so dropping lines by count would lose the new line.
+        // Chunks not matching the previous ones keep the repeated lines instead.
+        let redacted_chunks = redact(&chunks, &|text| text.replace("John Doe\n", "[REDACTED] "));
+        assert_eq!(
+            LlmTextChunker::merge(&chunks, redacted_chunks),
+            "First line here\n[REDACTED]\n[REDACTED] Second line of text\nThird line\n"
+        );
+    }
+
+    #[test]
+    fn chunker_for_model_test() {
+        assert_eq!(
+            LlmTextChunker::for_model("gpt-4o-mini"),
+            LlmTextChunker::new(12288, 614)
+        );
+        assert_eq!(
+            LlmTextChunker::for_model("models/gemini-1.5-flash"),
+            LlmTextChunker::new(6144, 307)
+        );
+        assert_eq!(
+            LlmTextChunker::for_model("unknown"),
+            LlmTextChunker::new(3072, 153)
+        );
+    }
+}
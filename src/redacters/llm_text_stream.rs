@@ -0,0 +1,71 @@
+use crate::redacters::LlmTextChunker;
+use crate::reporter::AppReporter;
+use crate::AppResult;
+
+/// Collects redacted texts streamed by LLM models. Redacted texts are about as long as the original ones,
+/// so progress of long responses is reported by comparing their estimated tokens.
+pub struct LlmTextStreamCollector<'a> {
+    reporter: &'a AppReporter<'a>,
+    expected_tokens: usize,
+    received_tokens: usize,
+    next_report_percent: usize,
+    text: String,
+}
+
+impl<'a> LlmTextStreamCollector<'a> {
+    /// Shorter responses finish fast enough without progress reports
+    const MIN_REPORTED_TOKENS: usize = 2048;
+    const REPORT_PERCENT_STEP: usize = 25;
+
+    pub fn new(reporter: &'a AppReporter<'a>, input_text: &str) -> Self {
+        Self {
+            reporter,
+            expected_tokens: LlmTextChunker::estimate_tokens(input_text),
+            received_tokens: 0,
+            next_report_percent: Self::REPORT_PERCENT_STEP,
+            text: String::new(),
+        }
+    }
+
+    pub fn push(&mut self, text: &str) -> AppResult<()> {
+        self.text.push_str(text);
+        self.received_tokens += LlmTextChunker::estimate_tokens(text);
+        if self.expected_tokens < Self::MIN_REPORTED_TOKENS {
+            return Ok(());
+        }
+        let received_percent = self.received_tokens * 100 / self.expected_tokens;
+        if received_percent >= self.next_report_percent && received_percent < 100 {
+            self.reporter.report(format!(
+                "↳ Received about {}% of the redacted text",
+                received_percent
+            ))?;
+            self.next_report_percent =
+                (received_percent / Self::REPORT_PERCENT_STEP + 1) * Self::REPORT_PERCENT_STEP;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use console::Term;
+
+    #[test]
+    fn collect_streamed_text_test() -> AppResult<()> {
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let input_text = "word ".repeat(4000);
+        let mut collector = LlmTextStreamCollector::new(&reporter, &input_text);
+        for _ in 0..4 {
+            collector.push(&"word ".repeat(1000))?;
+        }
+        assert_eq!(collector.next_report_percent, 100);
+        assert_eq!(collector.finish(), input_text);
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+use crate::errors::AppError;
+use crate::redacters::{NetworkIdAnonymizer, NetworkIdOptions};
+use crate::AppResult;
+use clap::ValueEnum;
+use regex::Regex;
+use std::fmt::Display;
+use std::ops::Range;
+
+/// Formats of log files redacted by fields with `--redact-log-format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LogFileFormat {
+    /// Format detected for every line from the formats below
+    #[default]
+    Auto,
+    /// Syslog messages (RFC 3164 and RFC 5424)
+    Syslog,
+    /// Apache/Nginx access logs in the common or combined format
+    Access,
+    /// JSON objects per line
+    Json,
+}
+
+impl Display for LogFileFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogFileFormat::Auto => write!(f, "auto"),
+            LogFileFormat::Syslog => write!(f, "syslog"),
+            LogFileFormat::Access => write!(f, "access"),
+            LogFileFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogRedactionOptions {
+    pub format: LogFileFormat,
+    /// IP and MAC addresses in logs are anonymized as in other files with `--network-*` options
+    pub network: NetworkIdOptions,
+}
+
+/// Log lines with IP addresses, emails, users and user agents redacted locally,
+/// so only free text such as messages is sent to redacters. This is much cheaper for large logs
+/// than redacting whole lines, and timestamps and other fields are never corrupted by LLMs.
+#[derive(Debug, Clone)]
+pub struct LogFieldsProjection {
+    lines: Vec<Vec<LogSegment>>,
+    remnants: Vec<String>,
+    local_redactions: usize,
+}
+
+#[derive(Debug, Clone)]
+enum LogSegment {
+    Text(String),
+    /// Free text sent to redacters. Messages of JSON logs are kept escaped, so they stay on single lines
+    Remnant {
+        index: usize,
+        json: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogField {
+    Message,
+    JsonMessage,
+    User,
+    UserAgent,
+}
+
+struct LogFieldsParser {
+    options: LogRedactionOptions,
+    syslog_rfc5424: Regex,
+    syslog_rfc3164: Regex,
+    access: Regex,
+    json_string_field: Regex,
+    email: Regex,
+    network_ids: NetworkIdAnonymizer,
+}
+
+impl LogFieldsProjection {
+    pub fn new(content: &str, options: &LogRedactionOptions) -> AppResult<Self> {
+        let parser = LogFieldsParser::new(options.clone())?;
+        let mut projection = Self {
+            lines: Vec::new(),
+            remnants: Vec::new(),
+            local_redactions: 0,
+        };
+        for line in content.split('\n') {
+            let (line, line_ending) = match line.strip_suffix('\r') {
+                Some(line) => (line, "\r"),
+                None => (line, ""),
+            };
+            let mut segments = Vec::new();
+            let fields = match parser.parse_line(line) {
+                Some(fields) => fields,
+                None if line.is_empty() => Vec::new(),
+                None => {
+                    // Lines of unknown formats are free text as a whole
+                    segments.push(LogSegment::Remnant {
+                        index: projection.remnants.len(),
+                        json: false,
+                    });
+                    projection
+                        .remnants
+                        .push(parser.mask(line, &mut projection.local_redactions));
+                    segments.push(LogSegment::Text(line_ending.to_string()));
+                    projection.lines.push(segments);
+                    continue;
+                }
+            };
+            let mut position = 0;
+            for (span, field) in fields {
+                segments.push(LogSegment::Text(parser.mask(
+                    &line[position..span.start],
+                    &mut projection.local_redactions,
+                )));
+                match field {
+                    LogField::Message | LogField::JsonMessage => {
+                        segments.push(LogSegment::Remnant {
+                            index: projection.remnants.len(),
+                            json: field == LogField::JsonMessage,
+                        });
+                        projection.remnants.push(
+                            parser.mask(&line[span.clone()], &mut projection.local_redactions),
+                        );
+                    }
+                    LogField::User => {
+                        segments.push(LogSegment::Text("[USER]".to_string()));
+                        projection.local_redactions += 1;
+                    }
+                    LogField::UserAgent => {
+                        segments.push(LogSegment::Text("[USER_AGENT]".to_string()));
+                        projection.local_redactions += 1;
+                    }
+                }
+                position = span.end;
+            }
+            segments.push(LogSegment::Text(
+                parser.mask(&line[position..], &mut projection.local_redactions),
+            ));
+            segments.push(LogSegment::Text(line_ending.to_string()));
+            projection.lines.push(segments);
+        }
+        Ok(projection)
+    }
+
+    /// Number of fields redacted locally
+    pub fn local_redactions(&self) -> usize {
+        self.local_redactions
+    }
+
+    pub fn has_remnants(&self) -> bool {
+        self.remnants
+            .iter()
+            .any(|remnant| !remnant.trim().is_empty())
+    }
+
+    /// Free text of all lines separated by new lines
+    pub fn projected(&self) -> String {
+        self.remnants.join("\n")
+    }
+
+    /// Puts redacted free text back to the lines
+    pub fn merge(self, redacted: &str) -> AppResult<String> {
+        let redacted_remnants: Vec<&str> = if self.remnants.is_empty() {
+            Vec::new()
+        } else {
+            redacted.split('\n').collect()
+        };
+        if redacted_remnants.len() != self.remnants.len() {
+            return Err(AppError::ConversionError {
+                message: format!(
+                    "Redacted text has {} lines instead of {} messages of the log",
+                    redacted_remnants.len(),
+                    self.remnants.len()
+                ),
+            });
+        }
+        Ok(self
+            .lines
+            .into_iter()
+            .map(|segments| {
+                segments
+                    .into_iter()
+                    .map(|segment| match segment {
+                        LogSegment::Text(text) => text,
+                        LogSegment::Remnant { index, json: false } => {
+                            redacted_remnants[index].to_string()
+                        }
+                        LogSegment::Remnant { index, json: true } => {
+                            Self::json_string_content(redacted_remnants[index])
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n"))
+    }
+
+    /// Redacters may add quotes to messages, which are escaped to keep JSON lines valid
+    fn json_string_content(content: &str) -> String {
+        if serde_json::from_str::<String>(&format!("\"{}\"", content)).is_ok() {
+            content.to_string()
+        } else {
+            let escaped = serde_json::Value::String(content.to_string()).to_string();
+            escaped[1..escaped.len() - 1].to_string()
+        }
+    }
+}
+
+impl LogFieldsParser {
+    const JSON_MESSAGE_KEYS: [&'static str; 7] = [
+        "message",
+        "msg",
+        "error",
+        "err",
+        "exception",
+        "stacktrace",
+        "log",
+    ];
+    const JSON_USER_KEYS: [&'static str; 3] = ["user", "username", "login"];
+    const JSON_USER_AGENT_KEYS: [&'static str; 3] = ["useragent", "httpuseragent", "ua"];
+
+    fn new(options: LogRedactionOptions) -> AppResult<Self> {
+        let compile = |pattern: &str| {
+            Regex::new(pattern).map_err(|err| AppError::SystemError {
+                message: format!("Failed to compile log pattern: {}", err),
+            })
+        };
+        Ok(Self {
+            network_ids: NetworkIdAnonymizer::new(options.network.clone())?,
+            options,
+            syslog_rfc5424: compile(
+                r"^<\d{1,3}>\d{1,2} \S+ \S+ \S+ \S+ \S+ (?:-|(?:\[(?:[^\]\\]|\\.)*\])+)(?: (?P<message>.*))?$",
+            )?,
+            syslog_rfc3164: compile(
+                r"^(?:<\d{1,3}>)?(?:[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}|\d{4}-\d{2}-\d{2}T\S+) \S+ [^\s:\[]+(?:\[\d+\])?: ?(?P<message>.*)$",
+            )?,
+            access: compile(
+                r#"^\S+ \S+ (?P<user>\S+) \[[^\]]+\] "(?:[^"\\]|\\.)*" \d{3} \S+(?: "(?:[^"\\]|\\.)*" "(?P<user_agent>(?:[^"\\]|\\.)*)")?"#,
+            )?,
+            json_string_field: compile(
+                r#""(?P<key>[A-Za-z0-9_@.-]+)"\s*:\s*"(?P<value>(?:[^"\\]|\\.)*)""#,
+            )?,
+            email: compile(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}")?,
+        })
+    }
+
+    /// Spans of fields of the line in the order of the line, if the line has a known format
+    fn parse_line(&self, line: &str) -> Option<Vec<(Range<usize>, LogField)>> {
+        let formats = match self.options.format {
+            LogFileFormat::Auto => vec![
+                LogFileFormat::Syslog,
+                LogFileFormat::Access,
+                LogFileFormat::Json,
+            ],
+            format => vec![format],
+        };
+        formats
+            .into_iter()
+            .find_map(|format| self.parse_line_as(line, format))
+    }
+
+    fn parse_line_as(
+        &self,
+        line: &str,
+        format: LogFileFormat,
+    ) -> Option<Vec<(Range<usize>, LogField)>> {
+        match format {
+            LogFileFormat::Syslog => {
+                let captures = self
+                    .syslog_rfc5424
+                    .captures(line)
+                    .or_else(|| self.syslog_rfc3164.captures(line))?;
+                Some(
+                    captures
+                        .name("message")
+                        .map(|message| vec![(message.range(), LogField::Message)])
+                        .unwrap_or_default(),
+                )
+            }
+            LogFileFormat::Access => {
+                let captures = self.access.captures(line)?;
+                let mut fields = Vec::new();
+                if let Some(user) = captures.name("user").filter(|user| user.as_str() != "-") {
+                    fields.push((user.range(), LogField::User));
+                }
+                if let Some(user_agent) = captures.name("user_agent").filter(|user_agent| {
+                    !user_agent.as_str().is_empty() && user_agent.as_str() != "-"
+                }) {
+                    fields.push((user_agent.range(), LogField::UserAgent));
+                }
+                Some(fields)
+            }
+            LogFileFormat::Json => {
+                if !line.trim_start().starts_with('{')
+                    || serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(line)
+                        .is_err()
+                {
+                    return None;
+                }
+                Some(
+                    self.json_string_field
+                        .captures_iter(line)
+                        .filter_map(|captures| {
+                            let key: String = captures["key"]
+                                .chars()
+                                .filter(|ch| ch.is_ascii_alphanumeric())
+                                .collect::<String>()
+                                .to_lowercase();
+                            let value = captures.name("value")?;
+                            let field = if Self::JSON_MESSAGE_KEYS.contains(&key.as_str()) {
+                                LogField::JsonMessage
+                            } else if Self::JSON_USER_KEYS.contains(&key.as_str()) {
+                                LogField::User
+                            } else if Self::JSON_USER_AGENT_KEYS.contains(&key.as_str()) {
+                                LogField::UserAgent
+                            } else {
+                                return None;
+                            };
+                            Some((value.range(), field)).filter(|_| !value.as_str().is_empty())
+                        })
+                        .collect(),
+                )
+            }
+            LogFileFormat::Auto => None,
+        }
+    }
+
+    /// Masks emails and anonymizes network identifiers in the text, counting masked values
+    fn mask(&self, text: &str, local_redactions: &mut usize) -> String {
+        let masked = self.email.replace_all(text, |_: &regex::Captures| {
+            *local_redactions += 1;
+            "[EMAIL]"
+        });
+        self.network_ids.anonymize(&masked, local_redactions)
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::redacters::IpAnonymization;
+
+    #[test]
+    fn log_fields_projection_test() -> AppResult<()> {
+        let content = [
+            r#"<34>Oct 11 22:14:15 mymachine su[123]: 'su root' failed for John Doe from 10.0.0.5"#,
+            r#"192.168.1.20 - frank [10/Oct/2000:13:55:36 -0700] "GET /a?email=frank@example.com HTTP/1.0" 200 2326 "-" "Mozilla/5.0 (X11)""#,
+            r#"{"ts":"2024-01-01T00:00:00Z","level":"info","msg":"Login by John Doe","client_ip":"2001:db8:85a3::8a2e:370:7334","user":"jdoe"}"#,
+            "Free text about John Doe at std::fmt v1.2.3.4.5\r",
+            "",
+        ]
+        .join("\n");
+        let projection = LogFieldsProjection::new(
+            &content,
+            &LogRedactionOptions {
+                format: LogFileFormat::Auto,
+                network: NetworkIdOptions {
+                    ip: Some(IpAnonymization::Truncate),
+                    ..NetworkIdOptions::default()
+                },
+            },
+        )?;
+        assert!(projection.has_remnants());
+        assert_eq!(projection.local_redactions(), 7);
+        assert_eq!(
+            projection.projected(),
+            [
+                "'su root' failed for John Doe from 10.0.0.0/24",
+                "Login by John Doe",
+                "Free text about John Doe at std::fmt v1.2.3.4.5",
+            ]
+            .join("\n")
+        );
+
+        let merged = projection.merge(
+            &[
+                "'su root' failed for [PERSON] from 10.0.0.0/24",
+                "Login by \"[PERSON]\"",
+                "Free text about [PERSON] at std::fmt v1.2.3.4.5",
+            ]
+            .join("\n"),
+        )?;
+        assert_eq!(
+            merged,
+            [
+                r#"<34>Oct 11 22:14:15 mymachine su[123]: 'su root' failed for [PERSON] from 10.0.0.0/24"#,
+                r#"192.168.1.0/24 - [USER] [10/Oct/2000:13:55:36 -0700] "GET /a?email=[EMAIL] HTTP/1.0" 200 2326 "-" "[USER_AGENT]""#,
+                r#"{"ts":"2024-01-01T00:00:00Z","level":"info","msg":"Login by \"[PERSON]\"","client_ip":"2001:db8:85a3::/48","user":"[USER]"}"#,
+                "Free text about [PERSON] at std::fmt v1.2.3.4.5\r",
+                "",
+            ]
+            .join("\n")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn log_fields_access_only_test() -> AppResult<()> {
+        let projection = LogFieldsProjection::new(
+            r#"10.1.2.3 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0" 200 2326"#,
+            &LogRedactionOptions {
+                format: LogFileFormat::Access,
+                network: NetworkIdOptions::default(),
+            },
+        )?;
+        assert!(!projection.has_remnants());
+        assert_eq!(
+            projection.merge("")?,
+            r#"[IP] - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0" 200 2326"#
+        );
+        Ok(())
+    }
+}
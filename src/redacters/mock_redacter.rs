@@ -0,0 +1,190 @@
+use regex::Regex;
+
+use crate::args::RedacterType;
+use crate::errors::AppError;
+use crate::file_systems::FileSystemRef;
+use crate::redacters::{
+    RedactSupport, Redacter, RedacterDataItem, RedacterDataItemContent, Redacters,
+};
+use crate::reporter::AppReporter;
+use crate::AppResult;
+
+#[derive(Debug, Clone)]
+pub struct MockRedacterOptions {
+    /// Regular expressions of texts to replace
+    pub patterns: Vec<String>,
+    /// Replace every Nth word of texts in addition to patterns
+    pub every_nth_word: Option<usize>,
+}
+
+impl MockRedacterOptions {
+    /// Used when neither patterns nor every Nth word are specified
+    pub const DEFAULT_EVERY_NTH_WORD: usize = 5;
+}
+
+/// Deterministic local redacter for integration tests and dry-runs of file systems and converters
+/// without any external dependencies. The same input is always redacted the same way.
+#[derive(Clone)]
+pub struct MockRedacter<'a> {
+    patterns: Vec<Regex>,
+    every_nth_word: Option<usize>,
+    words: Regex,
+    #[allow(dead_code)]
+    reporter: &'a AppReporter<'a>,
+}
+
+impl<'a> MockRedacter<'a> {
+    pub async fn new(
+        options: MockRedacterOptions,
+        reporter: &'a AppReporter<'a>,
+    ) -> AppResult<Self> {
+        let patterns = options
+            .patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|err| AppError::RedacterConfigError {
+                    message: format!("Invalid mock redacter pattern {}: {}", pattern, err),
+                })
+            })
+            .collect::<AppResult<Vec<Regex>>>()?;
+        let every_nth_word = match options.every_nth_word {
+            Some(0) => {
+                return Err(AppError::RedacterConfigError {
+                    message: "Mock redacter word interval must be greater than 0".to_string(),
+                })
+            }
+            Some(n) => Some(n),
+            None if patterns.is_empty() => Some(MockRedacterOptions::DEFAULT_EVERY_NTH_WORD),
+            None => None,
+        };
+        Ok(Self {
+            patterns,
+            every_nth_word,
+            words: Regex::new(r"\S+").map_err(|err| AppError::SystemError {
+                message: format!("Failed to compile word pattern: {}", err),
+            })?,
+            reporter,
+        })
+    }
+
+    fn mask(text: &str) -> String {
+        text.chars()
+            .map(|c| if c.is_whitespace() { c } else { 'X' })
+            .collect()
+    }
+
+    pub fn redact_text(&self, text: &str) -> String {
+        let mut redacted = self
+            .patterns
+            .iter()
+            .fold(text.to_string(), |text, pattern| {
+                pattern
+                    .replace_all(&text, |caps: &regex::Captures| Self::mask(&caps[0]))
+                    .into_owned()
+            });
+        if let Some(every_nth_word) = self.every_nth_word {
+            let mut words_redacted = String::with_capacity(redacted.len());
+            let mut position = 0;
+            for word in self
+                .words
+                .find_iter(&redacted)
+                .skip(every_nth_word - 1)
+                .step_by(every_nth_word)
+            {
+                words_redacted.push_str(&redacted[position..word.start()]);
+                words_redacted.push_str(&Self::mask(word.as_str()));
+                position = word.end();
+            }
+            words_redacted.push_str(&redacted[position..]);
+            redacted = words_redacted;
+        }
+        redacted
+    }
+}
+
+impl<'a> Redacter for MockRedacter<'a> {
+    async fn redact(&self, input: RedacterDataItem) -> AppResult<RedacterDataItem> {
+        let content = match input.content {
+            RedacterDataItemContent::Value(value) => {
+                RedacterDataItemContent::Value(self.redact_text(&value))
+            }
+            RedacterDataItemContent::Table { headers, rows } => RedacterDataItemContent::Table {
+                headers,
+                rows: rows
+                    .iter()
+                    .map(|row| row.iter().map(|cell| self.redact_text(cell)).collect())
+                    .collect(),
+            },
+            RedacterDataItemContent::Image { .. } | RedacterDataItemContent::Pdf { .. } => {
+                return Err(AppError::SystemError {
+                    message: "Attempt to redact of unsupported type".to_string(),
+                })
+            }
+        };
+        Ok(RedacterDataItem {
+            file_ref: input.file_ref,
+            content,
+        })
+    }
+
+    async fn redact_support(&self, file_ref: &FileSystemRef) -> AppResult<RedactSupport> {
+        Ok(match file_ref.media_type.as_ref() {
+            Some(media_type) if Redacters::is_mime_text(media_type) => RedactSupport::Supported,
+            Some(media_type) if Redacters::is_mime_table(media_type) => RedactSupport::Supported,
+            _ => RedactSupport::Unsupported,
+        })
+    }
+
+    fn redacter_type(&self) -> RedacterType {
+        RedacterType::Mock
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use console::Term;
+
+    #[tokio::test]
+    async fn redact_mock_test() -> AppResult<()> {
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+
+        let redacter = MockRedacter::new(
+            MockRedacterOptions {
+                patterns: vec![r"\d{3}-\d{4}".to_string(), "John Doe".to_string()],
+                every_nth_word: None,
+            },
+            &reporter,
+        )
+        .await?;
+        assert_eq!(
+            redacter.redact_text("Call John Doe at 555-1234."),
+            "Call XXXX XXX at XXXXXXXX."
+        );
+
+        let redacter = MockRedacter::new(
+            MockRedacterOptions {
+                patterns: vec![],
+                every_nth_word: Some(2),
+            },
+            &reporter,
+        )
+        .await?;
+        assert_eq!(
+            redacter.redact_text("one two  three\nfour five"),
+            "one XXX  three\nXXXX five"
+        );
+
+        assert!(MockRedacter::new(
+            MockRedacterOptions {
+                patterns: vec![],
+                every_nth_word: Some(0),
+            },
+            &reporter,
+        )
+        .await
+        .is_err());
+        Ok(())
+    }
+}
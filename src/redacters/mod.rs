@@ -1,9 +1,17 @@
+use crate::errors::AppError;
+use crate::file_converters::ocr::OcrOptions;
 use crate::file_systems::FileSystemRef;
+use crate::logging::LogRedactedContent;
+use crate::metrics::{AppMetric, AppMetrics};
 use crate::reporter::AppReporter;
 use crate::AppResult;
 use gcloud_sdk::prost::bytes;
 use mime::Mime;
+use rvstruct::ValueStruct;
 use std::fmt::Display;
+use std::future::Future;
+use std::time::Instant;
+use tracing::Instrument;
 
 mod gcp_dlp;
 pub use gcp_dlp::*;
@@ -23,6 +31,24 @@ pub use gemini_llm::*;
 mod open_ai_llm;
 pub use open_ai_llm::*;
 
+mod llm_text_chunker;
+pub use llm_text_chunker::*;
+
+mod llm_text_stream;
+pub use llm_text_stream::*;
+
+mod external_redacter;
+pub use external_redacter::*;
+
+mod dictionary_redacter;
+pub use dictionary_redacter::*;
+
+mod secrets_redacter;
+pub use secrets_redacter::*;
+
+mod mock_redacter;
+pub use mock_redacter::*;
+
 mod simple_image_redacter;
 pub use simple_image_redacter::*;
 mod stream_redacter;
@@ -31,8 +57,51 @@ pub use stream_redacter::*;
 mod redacter_throttler;
 pub use redacter_throttler::*;
 
+mod table_columns;
+pub use table_columns::*;
+
+mod log_fields;
+pub use log_fields::*;
+
+mod network_identifiers;
+pub use network_identifiers::*;
+
+mod text_sampling;
+pub use text_sampling::*;
+
+mod redacted_words;
+pub use redacted_words::*;
+
+mod redaction_cache;
+pub use redaction_cache::*;
+
+mod redaction_profiles;
+pub use redaction_profiles::*;
+
+mod entity_mappings;
+pub use entity_mappings::*;
+
+mod provider_io;
+pub use provider_io::*;
+
+mod redaction_deduplicator;
+pub use redaction_deduplicator::*;
+
+mod redacter_usage;
+pub use redacter_usage::*;
+
+mod redacter_verifier;
+pub use redacter_verifier::*;
+
+mod face_redacter;
+pub use face_redacter::*;
+
+mod audio_transcriber;
+pub use audio_transcriber::*;
+
 use crate::args::RedacterType;
-use crate::common_types::DlpRequestLimit;
+use crate::common_types::{DlpRequestLimit, DlpVolumeLimit};
+use clap::ValueEnum;
 
 #[derive(Debug, Clone)]
 pub struct RedacterDataItem {
@@ -64,12 +133,206 @@ pub enum Redacters<'a> {
     GeminiLlm(GeminiLlmRedacter<'a>),
     OpenAiLlm(OpenAiLlmRedacter<'a>),
     GcpVertexAi(GcpVertexAiRedacter<'a>),
+    External(ExternalRedacter<'a>),
+    Dictionary(DictionaryRedacter<'a>),
+    Secrets(SecretsRedacter<'a>),
+    Mock(MockRedacter<'a>),
+}
+
+/// Common entity categories that provider specific types (DLP info types,
+/// AWS Comprehend PII types, MS Presidio entities) are normalized to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum RedactEntityType {
+    PersonName,
+    EmailAddress,
+    PhoneNumber,
+    CreditCardNumber,
+    FinancialAccount,
+    Location,
+    Age,
+    DateOfBirth,
+    DateTime,
+    Gender,
+    IpAddress,
+    MacAddress,
+    Url,
+    Passport,
+    DriverLicense,
+    NationalId,
+    VatNumber,
+    Credentials,
+    Other,
+}
+
+impl RedactEntityType {
+    pub fn from_gcp_dlp_info_type(info_type: &str) -> Self {
+        match info_type {
+            "PERSON_NAME" | "FIRST_NAME" | "LAST_NAME" => Self::PersonName,
+            "EMAIL_ADDRESS" => Self::EmailAddress,
+            "PHONE_NUMBER" => Self::PhoneNumber,
+            "CREDIT_CARD_NUMBER" | "CREDIT_CARD_TRACK_NUMBER" => Self::CreditCardNumber,
+            "FINANCIAL_ACCOUNT_NUMBER" | "IBAN_CODE" | "SWIFT_CODE" => Self::FinancialAccount,
+            "LOCATION" | "STREET_ADDRESS" => Self::Location,
+            "AGE" => Self::Age,
+            "DATE_OF_BIRTH" => Self::DateOfBirth,
+            "DATE" | "TIME" => Self::DateTime,
+            "GENDER" => Self::Gender,
+            "IP_ADDRESS" => Self::IpAddress,
+            "MAC_ADDRESS" | "MAC_ADDRESS_LOCAL" => Self::MacAddress,
+            "URL" | "DOMAIN_NAME" => Self::Url,
+            "PASSPORT" => Self::Passport,
+            "VAT_NUMBER" => Self::VatNumber,
+            "AUTH_TOKEN"
+            | "AWS_CREDENTIALS"
+            | "BASIC_AUTH_HEADER"
+            | "PASSWORD"
+            | "OAUTH_CLIENT_SECRET"
+            | "GCP_API_KEY"
+            | "ENCRYPTION_KEY"
+            | "JSON_WEB_TOKEN" => Self::Credentials,
+            other if other.ends_with("_DRIVERS_LICENSE_NUMBER") => Self::DriverLicense,
+            other if other.ends_with("_PASSPORT") => Self::Passport,
+            other
+                if other.ends_with("_SOCIAL_SECURITY_NUMBER")
+                    || other.ends_with("_NATIONAL_ID") =>
+            {
+                Self::NationalId
+            }
+            _ => Self::Other,
+        }
+    }
+
+    pub fn from_aws_comprehend_type(pii_type: &str) -> Self {
+        match pii_type {
+            "NAME" | "USERNAME" => Self::PersonName,
+            "EMAIL" => Self::EmailAddress,
+            "PHONE" => Self::PhoneNumber,
+            "CREDIT_DEBIT_NUMBER" | "CREDIT_DEBIT_CVV" | "CREDIT_DEBIT_EXPIRY" => {
+                Self::CreditCardNumber
+            }
+            "BANK_ACCOUNT_NUMBER"
+            | "BANK_ROUTING"
+            | "INTERNATIONAL_BANK_ACCOUNT_NUMBER"
+            | "SWIFT_CODE" => Self::FinancialAccount,
+            "ADDRESS" => Self::Location,
+            "AGE" => Self::Age,
+            "DATE_TIME" => Self::DateTime,
+            "IP_ADDRESS" => Self::IpAddress,
+            "MAC_ADDRESS" => Self::MacAddress,
+            "URL" => Self::Url,
+            "PASSPORT_NUMBER" => Self::Passport,
+            "DRIVER_ID" => Self::DriverLicense,
+            "SSN"
+            | "CA_SOCIAL_INSURANCE_NUMBER"
+            | "IN_AADHAAR"
+            | "UK_NATIONAL_INSURANCE_NUMBER" => Self::NationalId,
+            "PASSWORD" | "PIN" | "AWS_ACCESS_KEY" | "AWS_SECRET_KEY" => Self::Credentials,
+            _ => Self::Other,
+        }
+    }
+
+    pub fn from_ms_presidio_entity(entity_type: &str) -> Self {
+        match entity_type {
+            "PERSON" => Self::PersonName,
+            "EMAIL_ADDRESS" => Self::EmailAddress,
+            "PHONE_NUMBER" => Self::PhoneNumber,
+            "CREDIT_CARD" => Self::CreditCardNumber,
+            "IBAN_CODE" | "US_BANK_NUMBER" | "CRYPTO" => Self::FinancialAccount,
+            "LOCATION" => Self::Location,
+            "DATE_TIME" => Self::DateTime,
+            "IP_ADDRESS" => Self::IpAddress,
+            "URL" => Self::Url,
+            "US_PASSPORT" => Self::Passport,
+            "US_DRIVER_LICENSE" => Self::DriverLicense,
+            "US_SSN" | "US_ITIN" | "UK_NHS" => Self::NationalId,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Which entity types should be redacted. When `redact_entity_types` is specified
+/// only those are redacted, `ignore_entity_types` are never redacted.
+/// Entities matching `keep_terms` (such as product or company names) are never redacted whatever their types are.
+#[derive(Debug, Clone, Default)]
+pub struct RedactEntityTypeFilter {
+    pub redact_entity_types: Option<Vec<RedactEntityType>>,
+    pub ignore_entity_types: Vec<RedactEntityType>,
+    pub keep_terms: Vec<String>,
+}
+
+impl RedactEntityTypeFilter {
+    pub fn accepts(&self, entity_type: RedactEntityType) -> bool {
+        !self.ignore_entity_types.contains(&entity_type)
+            && self
+                .redact_entity_types
+                .as_ref()
+                .map_or(true, |types| types.contains(&entity_type))
+    }
+
+    /// Whether the text of a detected entity is a keep term, matched case-insensitively
+    pub fn keeps(&self, text: &str) -> bool {
+        let text = text.trim().to_lowercase();
+        self.keep_terms
+            .iter()
+            .any(|term| term.to_lowercase() == text)
+    }
+
+    /// Whether the entity at the span of the text should be redacted. Entities with invalid spans are redacted
+    pub fn accepts_span(&self, text: &str, start: Option<usize>, end: Option<usize>) -> bool {
+        start
+            .zip(end)
+            .and_then(|(start, end)| text.get(start..end))
+            .map_or(true, |span| !self.keeps(span))
+    }
+
+    /// Additional instructions for LLM based redacters that don't report entity types
+    pub fn llm_prompt_instructions(&self) -> String {
+        fn to_names(types: &[RedactEntityType]) -> String {
+            types
+                .iter()
+                .filter_map(|t| {
+                    t.to_possible_value()
+                        .map(|v| v.get_name().replace('-', " "))
+                })
+                .collect::<Vec<String>>()
+                .join(", ")
+        }
+        let mut instructions = String::new();
+        if let Some(ref redact_entity_types) = self.redact_entity_types {
+            instructions.push_str(&format!(
+                " Only consider the following categories of personal information: {}.",
+                to_names(redact_entity_types)
+            ));
+        }
+        if !self.ignore_entity_types.is_empty() {
+            instructions.push_str(&format!(
+                " Never replace the following categories of information: {}.",
+                to_names(&self.ignore_entity_types)
+            ));
+        }
+        if !self.keep_terms.is_empty() {
+            instructions.push_str(&format!(
+                " Never replace the following terms even if they look like personal information: {}.",
+                self.keep_terms
+                    .iter()
+                    .map(|term| format!("\"{}\"", term))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ));
+        }
+        instructions
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct RedacterOptions {
     pub provider_options: Vec<RedacterProviderOptions>,
     pub base_options: RedacterBaseOptions,
+    pub verify_options: Option<RedacterVerifyOptions>,
+    pub face_redacter_options: Option<FaceRedacterOptions>,
+    pub audio_transcriber_options: Option<AudioTranscriberOptions>,
+    pub ocr_options: OcrOptions,
+    pub cache_options: Option<RedactionCacheOptions>,
 }
 
 #[derive(Debug, Clone)]
@@ -77,8 +340,57 @@ pub struct RedacterBaseOptions {
     pub allow_unsupported_copies: bool,
     pub csv_headers_disable: bool,
     pub csv_delimiter: Option<u8>,
+    pub csv_redact_columns: Option<Vec<String>>,
+    pub csv_skip_columns: Vec<String>,
     pub sampling_size: Option<usize>,
+    pub sampling_strategy: SamplingStrategy,
+    /// Redact whole files only if sensitive data is found in their samples, copying other files as is
+    pub sample_then_full: bool,
+    /// Files are redacted in memory, so larger files are skipped instead of exhausting memory
+    pub max_item_size: Option<usize>,
     pub limit_dlp_requests: Option<DlpRequestLimit>,
+    /// Limit of bytes or estimated LLM tokens sent to redacters per interval
+    pub limit_dlp_volume: Option<DlpVolumeLimit>,
+    /// Number of pages of PDF and TIFF files redacted concurrently
+    pub page_concurrency: usize,
+    pub image_redaction: ImageRedactionOptions,
+    pub redact_policy: RedactPolicy,
+    /// Hash the original text of image regions redacted through OCR for audit of redacted regions
+    pub hash_redacted_text: bool,
+    /// Rebuild redacted PDFs in PDF/A-2b conformance for archives
+    pub pdf_a: bool,
+    /// Redact images embedded in OpenDocument files, which are kept as is otherwise
+    pub redact_embedded_images: bool,
+    /// Redact fields of log files locally and send only free text of logs to redacters
+    pub log_redaction: Option<LogRedactionOptions>,
+    /// Anonymize IP and MAC addresses locally by masking, truncating or hashing them
+    pub network_anonymization: Option<NetworkIdOptions>,
+    /// Surrogates of entities shared by redacters replacing entities at spans, saved at the end of runs
+    pub entity_mappings: Option<EntityMappings>,
+}
+
+impl RedacterBaseOptions {
+    pub const DEFAULT_PAGE_CONCURRENCY: usize = 4;
+}
+
+/// How multiple redacters supporting the same file are applied: all of them one after another,
+/// only the first one (in the specified order), or the next one only if the previous one fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum RedactPolicy {
+    #[default]
+    All,
+    FirstSupported,
+    FallbackOnError,
+}
+
+impl Display for RedactPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RedactPolicy::All => write!(f, "all"),
+            RedactPolicy::FirstSupported => write!(f, "first-supported"),
+            RedactPolicy::FallbackOnError => write!(f, "fallback-on-error"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -89,24 +401,62 @@ pub enum RedacterProviderOptions {
     GeminiLlm(GeminiLlmRedacterOptions),
     OpenAiLlm(OpenAiLlmRedacterOptions),
     GcpVertexAi(GcpVertexAiRedacterOptions),
+    External(ExternalRedacterOptions),
+    Dictionary(DictionaryRedacterOptions),
+    Secrets(SecretsRedacterOptions),
+    Mock(MockRedacterOptions),
+}
+
+impl RedacterProviderOptions {
+    pub fn redacter_type(&self) -> RedacterType {
+        match self {
+            RedacterProviderOptions::GcpDlp(_) => RedacterType::GcpDlp,
+            RedacterProviderOptions::AwsComprehend(_) => RedacterType::AwsComprehend,
+            RedacterProviderOptions::MsPresidio(_) => RedacterType::MsPresidio,
+            RedacterProviderOptions::GeminiLlm(_) => RedacterType::GeminiLlm,
+            RedacterProviderOptions::OpenAiLlm(_) => RedacterType::OpenAiLlm,
+            RedacterProviderOptions::GcpVertexAi(_) => RedacterType::GcpVertexAi,
+            RedacterProviderOptions::External(_) => RedacterType::External,
+            RedacterProviderOptions::Dictionary(_) => RedacterType::Dictionary,
+            RedacterProviderOptions::Secrets(_) => RedacterType::Secrets,
+            RedacterProviderOptions::Mock(_) => RedacterType::Mock,
+        }
+    }
 }
 
 impl Display for RedacterOptions {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let to_display = self
+        fn to_display(options: &RedacterProviderOptions) -> String {
+            options.redacter_type().to_string()
+        }
+        let providers = self
             .provider_options
             .iter()
-            .map(|o| match o {
-                RedacterProviderOptions::GcpDlp(_) => "gcp-dlp".to_string(),
-                RedacterProviderOptions::AwsComprehend(_) => "aws-comprehend".to_string(),
-                RedacterProviderOptions::MsPresidio(_) => "ms-presidio".to_string(),
-                RedacterProviderOptions::GeminiLlm(_) => "gemini-llm".to_string(),
-                RedacterProviderOptions::OpenAiLlm(_) => "openai-llm".to_string(),
-                RedacterProviderOptions::GcpVertexAi(_) => "gcp-vertex-ai".to_string(),
-            })
+            .map(to_display)
             .collect::<Vec<String>>()
             .join(", ");
-        write!(f, "{}", to_display)
+        write!(f, "{}", providers)?;
+        if self.base_options.redact_policy != RedactPolicy::All {
+            write!(f, " ({})", self.base_options.redact_policy)?;
+        }
+        if let Some(ref verify_options) = self.verify_options {
+            write!(
+                f,
+                ", verified by {}",
+                to_display(&verify_options.provider_options)
+            )?;
+        }
+        if self.face_redacter_options.is_some() {
+            write!(f, ", faces redacted by {}", FaceRedacter::PROVIDER_NAME)?;
+        }
+        if let Some(ref audio_transcriber_options) = self.audio_transcriber_options {
+            write!(
+                f,
+                ", audio transcribed by {}",
+                audio_transcriber_options.provider
+            )?;
+        }
+        Ok(())
     }
 }
 
@@ -134,6 +484,61 @@ impl<'a> Redacters<'a> {
             RedacterProviderOptions::GcpVertexAi(options) => Ok(Redacters::GcpVertexAi(
                 GcpVertexAiRedacter::new(options, reporter).await?,
             )),
+            RedacterProviderOptions::External(options) => Ok(Redacters::External(
+                ExternalRedacter::new(options, reporter).await?,
+            )),
+            RedacterProviderOptions::Dictionary(options) => Ok(Redacters::Dictionary(
+                DictionaryRedacter::new(options, reporter).await?,
+            )),
+            RedacterProviderOptions::Secrets(options) => Ok(Redacters::Secrets(
+                SecretsRedacter::new(options, reporter).await?,
+            )),
+            RedacterProviderOptions::Mock(options) => {
+                Ok(Redacters::Mock(MockRedacter::new(options, reporter).await?))
+            }
+        }
+    }
+
+    /// Detects entity types in the text without redacting it.
+    /// Only redacters reporting detected entities support it.
+    pub async fn detect_text(&self, text: String) -> AppResult<Vec<RedactFinding>> {
+        match self {
+            Redacters::GcpDlp(redacter) => redacter.detect_text(text).await,
+            Redacters::AwsComprehend(redacter) => redacter.detect_text(text).await,
+            Redacters::MsPresidio(redacter) => redacter.detect_text(text).await,
+            _ => Err(AppError::RedacterConfigError {
+                message: format!(
+                    "Redacter {} doesn't support detection",
+                    self.redacter_type()
+                ),
+            }),
+        }
+    }
+
+    /// Redacters analyzing text files in advance in batches instead of requests per file
+    pub fn is_batch_enabled(&self) -> bool {
+        match self {
+            Redacters::GcpVertexAi(redacter) => redacter.is_batch_enabled(),
+            Redacters::MsPresidio(redacter) => redacter.is_batch_enabled(),
+            _ => false,
+        }
+    }
+
+    /// LLM redacters split large texts into chunks redacted with separate requests
+    pub fn llm_text_chunker(&self) -> Option<LlmTextChunker> {
+        match self {
+            Redacters::GeminiLlm(redacter) => Some(redacter.text_chunker()),
+            Redacters::OpenAiLlm(redacter) => Some(redacter.text_chunker()),
+            Redacters::GcpVertexAi(redacter) => Some(redacter.text_chunker()),
+            _ => None,
+        }
+    }
+
+    pub async fn redact_batch(&mut self, contents: Vec<String>) -> AppResult<()> {
+        match self {
+            Redacters::GcpVertexAi(redacter) => redacter.redact_batch(contents).await,
+            Redacters::MsPresidio(redacter) => redacter.redact_batch(contents).await,
+            _ => Ok(()),
         }
     }
 
@@ -156,13 +561,41 @@ impl<'a> Redacters<'a> {
     }
 
     pub fn is_mime_table(mime: &Mime) -> bool {
-        mime.type_() == mime::TEXT && mime.subtype() == mime::CSV
+        mime.type_() == mime::TEXT && (mime.subtype() == mime::CSV || Self::is_mime_tsv(mime))
+    }
+
+    pub fn is_mime_tsv(mime: &Mime) -> bool {
+        mime.type_() == mime::TEXT && mime.subtype() == "tab-separated-values"
     }
 
     pub fn is_mime_image(mime: &Mime) -> bool {
         mime.type_() == mime::IMAGE
     }
 
+    pub fn is_mime_tiff(mime: &Mime) -> bool {
+        mime.type_() == mime::IMAGE && mime.subtype() == "tiff"
+    }
+
+    pub fn is_mime_heif(mime: &Mime) -> bool {
+        mime.type_() == mime::IMAGE && matches!(mime.subtype().as_str(), "heic" | "heif")
+    }
+
+    pub fn is_mime_audio(mime: &Mime) -> bool {
+        Self::is_mime_wav(mime) || Self::is_mime_mp3(mime)
+    }
+
+    pub fn is_mime_wav(mime: &Mime) -> bool {
+        mime.type_() == "audio"
+            && matches!(
+                mime.subtype().as_str(),
+                "wav" | "x-wav" | "wave" | "vnd.wave"
+            )
+    }
+
+    pub fn is_mime_mp3(mime: &Mime) -> bool {
+        mime.type_() == "audio" && matches!(mime.subtype().as_str(), "mpeg" | "mp3")
+    }
+
     pub fn is_mime_pdf(mime: &Mime) -> bool {
         *mime == mime::APPLICATION_PDF
     }
@@ -175,23 +608,89 @@ pub enum RedactSupport {
 }
 
 pub trait Redacter {
-    async fn redact(&self, input: RedacterDataItem) -> AppResult<RedacterDataItem>;
+    fn redact(
+        &self,
+        input: RedacterDataItem,
+    ) -> impl Future<Output = AppResult<RedacterDataItem>> + Send;
 
-    async fn redact_support(&self, file_ref: &FileSystemRef) -> AppResult<RedactSupport>;
+    fn redact_support(
+        &self,
+        file_ref: &FileSystemRef,
+    ) -> impl Future<Output = AppResult<RedactSupport>> + Send;
 
     fn redacter_type(&self) -> RedacterType;
 }
 
 impl<'a> Redacter for Redacters<'a> {
     async fn redact(&self, input: RedacterDataItem) -> AppResult<RedacterDataItem> {
-        match self {
-            Redacters::GcpDlp(redacter) => redacter.redact(input).await,
-            Redacters::AwsComprehend(redacter) => redacter.redact(input).await,
-            Redacters::MsPresidio(redacter) => redacter.redact(input).await,
-            Redacters::GeminiLlm(redacter) => redacter.redact(input).await,
-            Redacters::OpenAiLlm(redacter) => redacter.redact(input).await,
-            Redacters::GcpVertexAi(redacter) => redacter.redact(input).await,
+        let redacter_type = self.redacter_type().to_string();
+        let span = tracing::info_span!(
+            "provider_call",
+            provider = redacter_type.as_str(),
+            file = input.file_ref.relative_path.value().as_str()
+        );
+        // Providers billed by inspected content don't report usage in responses
+        if matches!(self, Redacters::GcpDlp(_) | Redacters::AwsComprehend(_)) {
+            RedacterProviderUsage::record_bytes_inspected(self.redacter_type(), &input.content);
         }
+        span.in_scope(|| {
+            tracing::debug!(
+                media_type = input
+                    .file_ref
+                    .media_type
+                    .as_ref()
+                    .map(|media_type| media_type.to_string()),
+                "Provider call started"
+            )
+        });
+        let started = Instant::now();
+        let result = match self {
+            Redacters::GcpDlp(redacter) => redacter.redact(input).instrument(span.clone()).await,
+            Redacters::AwsComprehend(redacter) => {
+                redacter.redact(input).instrument(span.clone()).await
+            }
+            Redacters::MsPresidio(redacter) => {
+                redacter.redact(input).instrument(span.clone()).await
+            }
+            Redacters::GeminiLlm(redacter) => redacter.redact(input).instrument(span.clone()).await,
+            Redacters::OpenAiLlm(redacter) => redacter.redact(input).instrument(span.clone()).await,
+            Redacters::GcpVertexAi(redacter) => {
+                redacter.redact(input).instrument(span.clone()).await
+            }
+            Redacters::External(redacter) => redacter.redact(input).instrument(span.clone()).await,
+            Redacters::Dictionary(redacter) => {
+                redacter.redact(input).instrument(span.clone()).await
+            }
+            Redacters::Secrets(redacter) => redacter.redact(input).instrument(span.clone()).await,
+            Redacters::Mock(redacter) => redacter.redact(input).instrument(span.clone()).await,
+        };
+        let duration = started.elapsed();
+        let labels = [("redacter", redacter_type.as_str())];
+        let metrics = AppMetrics::global();
+        metrics.inc(AppMetric::PROVIDER_CALLS, &labels);
+        metrics.add_duration(AppMetric::PROVIDER_DURATION, &labels, duration);
+        span.in_scope(|| match result {
+            Ok(ref item) => {
+                tracing::info!(
+                    duration_ms = duration.as_millis() as u64,
+                    result = "ok",
+                    "Provider call finished"
+                );
+                if let RedacterDataItemContent::Value(ref content) = item.content {
+                    LogRedactedContent::log_response(content);
+                }
+            }
+            Err(ref err) => {
+                metrics.inc(AppMetric::PROVIDER_FAILURES, &labels);
+                tracing::warn!(
+                    duration_ms = duration.as_millis() as u64,
+                    result = "error",
+                    error = %err,
+                    "Provider call failed"
+                )
+            }
+        });
+        result
     }
 
     async fn redact_support(&self, file_ref: &FileSystemRef) -> AppResult<RedactSupport> {
@@ -202,6 +701,10 @@ impl<'a> Redacter for Redacters<'a> {
             Redacters::GeminiLlm(redacter) => redacter.redact_support(file_ref).await,
             Redacters::OpenAiLlm(redacter) => redacter.redact_support(file_ref).await,
             Redacters::GcpVertexAi(redacter) => redacter.redact_support(file_ref).await,
+            Redacters::External(redacter) => redacter.redact_support(file_ref).await,
+            Redacters::Dictionary(redacter) => redacter.redact_support(file_ref).await,
+            Redacters::Secrets(redacter) => redacter.redact_support(file_ref).await,
+            Redacters::Mock(redacter) => redacter.redact_support(file_ref).await,
         }
     }
 
@@ -213,6 +716,48 @@ impl<'a> Redacter for Redacters<'a> {
             Redacters::GeminiLlm(_) => RedacterType::GeminiLlm,
             Redacters::OpenAiLlm(_) => RedacterType::OpenAiLlm,
             Redacters::GcpVertexAi(_) => RedacterType::GcpVertexAi,
+            Redacters::External(_) => RedacterType::External,
+            Redacters::Dictionary(_) => RedacterType::Dictionary,
+            Redacters::Secrets(_) => RedacterType::Secrets,
+            Redacters::Mock(_) => RedacterType::Mock,
         }
     }
 }
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entity_type_filter_test() {
+        let filter = RedactEntityTypeFilter {
+            redact_entity_types: Some(vec![
+                RedactEntityType::PersonName,
+                RedactEntityType::EmailAddress,
+            ]),
+            ignore_entity_types: vec![RedactEntityType::EmailAddress],
+            keep_terms: vec![],
+        };
+        assert!(filter.accepts(RedactEntityType::from_gcp_dlp_info_type("PERSON_NAME")));
+        assert!(filter.accepts(RedactEntityType::from_aws_comprehend_type("NAME")));
+        assert!(filter.accepts(RedactEntityType::from_ms_presidio_entity("PERSON")));
+        assert!(!filter.accepts(RedactEntityType::from_gcp_dlp_info_type("EMAIL_ADDRESS")));
+        assert!(!filter.accepts(RedactEntityType::from_ms_presidio_entity("LOCATION")));
+        assert!(RedactEntityTypeFilter::default().accepts(RedactEntityType::Other));
+    }
+
+    #[test]
+    fn keep_terms_test() {
+        let filter = RedactEntityTypeFilter {
+            keep_terms: vec!["Acme Corp".to_string()],
+            ..RedactEntityTypeFilter::default()
+        };
+        let text = "John Doe works at ACME corp";
+        assert!(filter.accepts_span(text, Some(0), Some(8)));
+        assert!(!filter.accepts_span(text, Some(18), Some(27)));
+        assert!(filter.accepts_span(text, Some(18), Some(100)));
+        assert!(filter
+            .llm_prompt_instructions()
+            .contains("Never replace the following terms even if they look like personal information: \"Acme Corp\"."));
+    }
+}
@@ -1,12 +1,17 @@
 use rvstruct::ValueStruct;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use url::Url;
 
 use crate::args::RedacterType;
+use crate::common_types::{ImageRedactionColor, RedactLanguage};
 use crate::errors::AppError;
 use crate::file_systems::FileSystemRef;
 use crate::redacters::{
-    RedactSupport, Redacter, RedacterDataItem, RedacterDataItemContent, Redacters,
+    redact_image_as_filled, replace_entity_spans, EntityMappings, ImageRedactionOptions,
+    ImageRedactionStyle, RedactEntityType, RedactEntityTypeFilter, RedactFinding, RedactSupport,
+    Redacter, RedacterDataItem, RedacterDataItemContent, Redacters,
 };
 use crate::reporter::AppReporter;
 use crate::AppResult;
@@ -15,23 +20,40 @@ use crate::AppResult;
 pub struct MsPresidioRedacterOptions {
     pub text_analyze_url: Option<Url>,
     pub image_redact_url: Option<Url>,
+    pub entity_type_filter: RedactEntityTypeFilter,
+    pub language: Option<RedactLanguage>,
+    pub image_redaction: ImageRedactionOptions,
+    /// Minimum confidence score of entities found by Presidio
+    pub score_threshold: Option<f64>,
+    /// Presidio entity types to find such as PERSON. All types except the ones with
+    /// a lot of false positives are found if not specified
+    pub entities: Vec<String>,
+    /// Maximum size of combined texts analyzed in a single request when batching small text files
+    pub batch_size: Option<usize>,
+    /// Replace entities in texts with surrogates consistent across files instead of `X` characters
+    pub entity_mappings: Option<EntityMappings>,
 }
 
 #[derive(Clone)]
 pub struct MsPresidioRedacter<'a> {
     client: reqwest::Client,
     ms_presidio_options: MsPresidioRedacterOptions,
-    #[allow(dead_code)]
     reporter: &'a AppReporter<'a>,
+    /// Entities found in batches by SHA-256 of analyzed texts
+    batch_results: HashMap<String, Vec<MsPresidioAnalyzedItem>>,
 }
 
 #[derive(Serialize, Clone, Debug)]
 struct MsPresidioAnalyzeRequest {
     text: String,
     language: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score_threshold: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    entities: Vec<String>,
 }
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Clone, Debug, PartialEq)]
 struct MsPresidioAnalyzedItem {
     entity_type: String,
     start: Option<usize>,
@@ -43,6 +65,24 @@ impl<'a> MsPresidioRedacter<'a> {
     /// since they produce a lot of false positives
     const DISALLOW_ENTITY_TYPES: [&'static str; 1] = ["US_DRIVER_LICENSE"];
 
+    const DEFAULT_LANGUAGE: &'static str = "en";
+
+    /// Separates texts analyzed in a batch, so entities don't span several texts
+    const BATCH_TEXT_SEPARATOR: &'static str = "\n\n\n";
+
+    /// Color marking regions to blur or pixelate, unlikely to be found in images
+    const IMAGE_FILL_MARKER_COLOR: ImageRedactionColor = ImageRedactionColor {
+        red: 255,
+        green: 0,
+        blue: 255,
+    };
+
+    /// Presidio languages depend on the NLP engine configuration of the deployment,
+    /// so only the ISO 639-1 code format is checked
+    pub fn is_supported_language(language: &RedactLanguage) -> bool {
+        language.value().len() == 2 && language.value().chars().all(|c| c.is_ascii_lowercase())
+    }
+
     pub async fn new(
         ms_presidio_options: MsPresidioRedacterOptions,
         reporter: &'a AppReporter<'a>,
@@ -52,9 +92,105 @@ impl<'a> MsPresidioRedacter<'a> {
             client,
             ms_presidio_options,
             reporter,
+            batch_results: HashMap::new(),
         })
     }
 
+    pub fn is_batch_enabled(&self) -> bool {
+        self.ms_presidio_options.batch_size.is_some()
+    }
+
+    /// Analyzes small texts combined into requests up to the batch size, so directories of tiny files
+    /// don't need a request per file. Larger texts are analyzed later with requests per file.
+    pub async fn redact_batch(&mut self, contents: Vec<String>) -> AppResult<()> {
+        let Some(batch_size) = self.ms_presidio_options.batch_size else {
+            return Ok(());
+        };
+        let batches = Self::split_batches(
+            contents
+                .into_iter()
+                .filter(|content| !content.is_empty() && content.len() <= batch_size)
+                .collect(),
+            batch_size,
+        );
+        let total_contents: usize = batches.iter().map(Vec::len).sum();
+        if total_contents == 0 {
+            return Ok(());
+        }
+        self.reporter.report(format!(
+            "Analyzing {} texts in {} MsPresidio batch requests",
+            total_contents,
+            batches.len()
+        ))?;
+        for batch in batches {
+            let combined_text = batch.join(Self::BATCH_TEXT_SEPARATOR);
+            let items = self.request_analyze(&combined_text).await?;
+            for (content, content_items) in Self::split_batch_items(&batch, items) {
+                self.batch_results
+                    .insert(Self::batch_content_key(&content), content_items);
+            }
+        }
+        Ok(())
+    }
+
+    /// Groups texts, so combined texts with separators don't exceed the batch size
+    fn split_batches(contents: Vec<String>, batch_size: usize) -> Vec<Vec<String>> {
+        let mut batches: Vec<Vec<String>> = Vec::new();
+        let mut batch_len = 0;
+        for content in contents {
+            match batches.last_mut() {
+                Some(batch)
+                    if batch_len + Self::BATCH_TEXT_SEPARATOR.len() + content.len()
+                        <= batch_size =>
+                {
+                    batch_len += Self::BATCH_TEXT_SEPARATOR.len() + content.len();
+                    batch.push(content);
+                }
+                _ => {
+                    batch_len = content.len();
+                    batches.push(vec![content]);
+                }
+            }
+        }
+        batches
+    }
+
+    /// Maps entities found in combined texts back to the texts with their own offsets
+    fn split_batch_items(
+        batch: &[String],
+        items: Vec<MsPresidioAnalyzedItem>,
+    ) -> Vec<(String, Vec<MsPresidioAnalyzedItem>)> {
+        let mut offset = 0;
+        batch
+            .iter()
+            .map(|content| {
+                let content_start = offset;
+                let content_end = offset + content.len();
+                offset = content_end + Self::BATCH_TEXT_SEPARATOR.len();
+                let content_items = items
+                    .iter()
+                    .filter_map(|item| match (item.start, item.end) {
+                        (Some(start), Some(end))
+                            if start >= content_start && end <= content_end =>
+                        {
+                            Some(MsPresidioAnalyzedItem {
+                                entity_type: item.entity_type.clone(),
+                                start: Some(start - content_start),
+                                end: Some(end - content_start),
+                            })
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                (content.clone(), content_items)
+            })
+            .collect()
+    }
+
+    fn batch_content_key(content: &str) -> String {
+        hex::encode(Sha256::digest(content.as_bytes()))
+    }
+
     pub async fn redact_text_file(&self, input: RedacterDataItem) -> AppResult<RedacterDataItem> {
         let text_content = match input.content {
             RedacterDataItemContent::Value(content) => Ok(content),
@@ -63,14 +199,92 @@ impl<'a> MsPresidioRedacter<'a> {
             }),
         }?;
 
+        let spans = self
+            .analyze_text(&text_content)
+            .await?
+            .into_iter()
+            .map(|entity| {
+                (
+                    entity.start,
+                    entity.end,
+                    RedactEntityType::from_ms_presidio_entity(&entity.entity_type),
+                )
+            })
+            .collect();
+        let redacted_text_content = replace_entity_spans(
+            &text_content,
+            spans,
+            self.ms_presidio_options.entity_mappings.as_ref(),
+        );
+        Ok(RedacterDataItem {
+            file_ref: input.file_ref,
+            content: RedacterDataItemContent::Value(redacted_text_content),
+        })
+    }
+
+    /// Entities found in the text, used to verify redacted content
+    pub async fn detect_text(&self, text: String) -> AppResult<Vec<RedactFinding>> {
+        Ok(self
+            .analyze_text(&text)
+            .await?
+            .into_iter()
+            .map(|item| {
+                RedactFinding::new(
+                    RedacterType::MsPresidio,
+                    item.entity_type,
+                    item.start,
+                    item.end,
+                )
+            })
+            .collect())
+    }
+
+    async fn analyze_text(&self, text_content: &str) -> AppResult<Vec<MsPresidioAnalyzedItem>> {
+        let response_items = match self
+            .batch_results
+            .get(&Self::batch_content_key(text_content))
+        {
+            Some(batch_items) => batch_items.clone(),
+            None => self.request_analyze(text_content).await?,
+        };
+        Ok(response_items
+            .into_iter()
+            .filter(|item| {
+                // Explicitly requested entities are redacted even if they produce false positives
+                !self.ms_presidio_options.entities.is_empty()
+                    || !Self::DISALLOW_ENTITY_TYPES.contains(&item.entity_type.as_str())
+            })
+            .filter(|item| {
+                self.ms_presidio_options
+                    .entity_type_filter
+                    .accepts(RedactEntityType::from_ms_presidio_entity(&item.entity_type))
+            })
+            .filter(|item| {
+                self.ms_presidio_options.entity_type_filter.accepts_span(
+                    text_content,
+                    item.start,
+                    item.end,
+                )
+            })
+            .collect())
+    }
+
+    async fn request_analyze(&self, text_content: &str) -> AppResult<Vec<MsPresidioAnalyzedItem>> {
         let analyze_url = self.ms_presidio_options.text_analyze_url.as_ref().ok_or(
             AppError::RedacterConfigError {
                 message: "Text analyze URL is not configured".to_string(),
             },
         )?;
         let analyze_request = MsPresidioAnalyzeRequest {
-            text: text_content.clone(),
-            language: "en".to_string(),
+            text: text_content.to_string(),
+            language: self
+                .ms_presidio_options
+                .language
+                .as_ref()
+                .map(|language| language.value().clone())
+                .unwrap_or_else(|| Self::DEFAULT_LANGUAGE.to_string()),
+            score_threshold: self.ms_presidio_options.score_threshold,
+            entities: self.ms_presidio_options.entities.clone(),
         };
         let response = self
             .client
@@ -87,36 +301,13 @@ impl<'a> MsPresidioRedacter<'a> {
         {
             let response_status = response.status();
             let response_text = response.text().await.unwrap_or_default();
-            return Err(AppError::SystemError {
-                message: format!(
-                    "Failed to analyze text: {}. HTTP status: {}.",
-                    response_text, response_status
-                ),
-            });
+            return Err(AppError::provider_http_error(
+                RedacterType::MsPresidio,
+                response_status,
+                format!("Failed to analyze text: {}", response_text),
+            ));
         }
-        let response_items: Vec<MsPresidioAnalyzedItem> = response.json().await?;
-        let redacted_text_content = response_items
-            .iter()
-            .filter(|item| !Self::DISALLOW_ENTITY_TYPES.contains(&item.entity_type.as_str()))
-            .fold(text_content, |acc, entity| {
-                match (entity.start, entity.end) {
-                    (Some(start), Some(end)) => [
-                        acc[..start].to_string(),
-                        "X".repeat(end - start),
-                        acc[end..].to_string(),
-                    ]
-                    .concat(),
-                    (Some(start), None) => {
-                        acc[..start].to_string() + "X".repeat(acc.len() - start).as_str()
-                    }
-                    (None, Some(end)) => ["X".repeat(end), acc[end..].to_string()].concat(),
-                    _ => acc,
-                }
-            });
-        Ok(RedacterDataItem {
-            file_ref: input.file_ref,
-            content: RedacterDataItemContent::Value(redacted_text_content),
-        })
+        Ok(response.json().await?)
     }
 
     pub async fn redact_image_file(&self, input: RedacterDataItem) -> AppResult<RedacterDataItem> {
@@ -133,11 +324,29 @@ impl<'a> MsPresidioRedacter<'a> {
                     input.file_ref.relative_path.value(),
                     input.file_ref.media_type
                 ))?;
+                let image_redaction = &self.ms_presidio_options.image_redaction;
+                // Presidio only fills regions, so other styles are applied to the filled pixels
+                let fill_color = if image_redaction.style == ImageRedactionStyle::Solid {
+                    image_redaction.color
+                } else {
+                    Self::IMAGE_FILL_MARKER_COLOR
+                };
                 let file_part = reqwest::multipart::Part::bytes(data.to_vec())
                     .file_name(input.file_ref.relative_path.filename())
                     .mime_str(mime_type.as_ref())
                     .unwrap();
-                let form = reqwest::multipart::Form::new().part("image", file_part);
+                let form = reqwest::multipart::Form::new()
+                    .part("image", file_part)
+                    .text(
+                        "data",
+                        serde_json::json!({
+                            "color_fill": format!(
+                                "{},{},{}",
+                                fill_color.red, fill_color.green, fill_color.blue
+                            )
+                        })
+                        .to_string(),
+                    );
                 let response = self
                     .client
                     .post(redact_url.clone())
@@ -147,14 +356,24 @@ impl<'a> MsPresidioRedacter<'a> {
                 if !response.status().is_success() {
                     let response_status = response.status();
                     let response_text = response.text().await.unwrap_or_default();
-                    return Err(AppError::SystemError {
-                        message: format!(
-                            "Failed to redact image: {}. HTTP status: {}.",
-                            response_text, response_status
-                        ),
-                    });
+                    return Err(AppError::provider_http_error(
+                        RedacterType::MsPresidio,
+                        response_status,
+                        format!("Failed to redact image: {}", response_text),
+                    ));
                 }
-                let redacted_image_bytes = response.bytes().await?;
+                let filled_image_bytes = response.bytes().await?;
+                let redacted_image_bytes = if image_redaction.style == ImageRedactionStyle::Solid {
+                    filled_image_bytes
+                } else {
+                    redact_image_as_filled(
+                        mime_type.clone(),
+                        data,
+                        filled_image_bytes,
+                        fill_color,
+                        image_redaction,
+                    )?
+                };
                 Ok(RedacterDataItem {
                     file_ref: input.file_ref,
                     content: RedacterDataItemContent::Image {
@@ -230,6 +449,8 @@ mod tests {
             relative_path: "temp_file.txt".into(),
             media_type: Some(mime::TEXT_PLAIN),
             file_size: Some(test_content.len()),
+            modified_at: None,
+            checksum: None,
         };
 
         let content = RedacterDataItemContent::Value(test_content.to_string());
@@ -239,6 +460,13 @@ mod tests {
             MsPresidioRedacterOptions {
                 text_analyze_url: Some(test_analyze_url),
                 image_redact_url: None,
+                entity_type_filter: RedactEntityTypeFilter::default(),
+                language: None,
+                image_redaction: ImageRedactionOptions::default(),
+                score_threshold: None,
+                entities: vec![],
+                batch_size: None,
+                entity_mappings: None,
             },
             &reporter,
         )
@@ -254,4 +482,40 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn split_batch_items_test() {
+        let contents = vec![
+            "Hello, John".to_string(),
+            "No entities".to_string(),
+            "Call Jane".to_string(),
+        ];
+        let batches = MsPresidioRedacter::split_batches(contents.clone(), 30);
+        assert_eq!(
+            batches,
+            vec![contents[..2].to_vec(), contents[2..].to_vec()]
+        );
+
+        let combined_text = batches[0].join(MsPresidioRedacter::BATCH_TEXT_SEPARATOR);
+        let john_start = combined_text.find("John").unwrap();
+        let items = vec![MsPresidioAnalyzedItem {
+            entity_type: "PERSON".to_string(),
+            start: Some(john_start),
+            end: Some(john_start + 4),
+        }];
+        assert_eq!(
+            MsPresidioRedacter::split_batch_items(&batches[0], items),
+            vec![
+                (
+                    "Hello, John".to_string(),
+                    vec![MsPresidioAnalyzedItem {
+                        entity_type: "PERSON".to_string(),
+                        start: Some(7),
+                        end: Some(11),
+                    }]
+                ),
+                ("No entities".to_string(), vec![]),
+            ]
+        );
+    }
 }
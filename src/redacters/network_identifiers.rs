@@ -0,0 +1,297 @@
+use crate::errors::AppError;
+use crate::AppResult;
+use clap::ValueEnum;
+use regex::Regex;
+use sha2::Digest;
+use std::fmt::Display;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// How IP addresses are anonymized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum IpAnonymization {
+    /// Whole addresses are replaced with `[IP]`
+    #[default]
+    Mask,
+    /// Host bits are zeroed keeping networks such as `192.168.1.0/24`
+    Truncate,
+    /// Addresses are replaced with keyed hashes such as `ip-3f2a9c1b7d4e`, so the same address has the same pseudonym
+    Hash,
+}
+
+impl Display for IpAnonymization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpAnonymization::Mask => write!(f, "mask"),
+            IpAnonymization::Truncate => write!(f, "truncate"),
+            IpAnonymization::Hash => write!(f, "hash"),
+        }
+    }
+}
+
+/// How MAC addresses are anonymized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum MacAnonymization {
+    /// Whole addresses are replaced with `[MAC]`
+    #[default]
+    Mask,
+    /// Only the vendor prefix (OUI) is kept such as `00:1a:2b:00:00:00`
+    Oui,
+    /// Addresses are replaced with keyed hashes such as `mac-3f2a9c1b7d4e`, so the same address has the same pseudonym
+    Hash,
+}
+
+impl Display for MacAnonymization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MacAnonymization::Mask => write!(f, "mask"),
+            MacAnonymization::Oui => write!(f, "oui"),
+            MacAnonymization::Hash => write!(f, "hash"),
+        }
+    }
+}
+
+/// Key of pseudonyms of network identifiers, which is never printed
+#[derive(Clone, Default)]
+pub struct NetworkIdHashKey(Vec<u8>);
+
+impl NetworkIdHashKey {
+    pub fn new(key: Vec<u8>) -> Self {
+        Self(key)
+    }
+
+    /// Random key, so pseudonyms are consistent only within a run
+    pub fn random() -> AppResult<Self> {
+        let mut key = vec![0u8; 32];
+        ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut key).map_err(
+            |_| AppError::SystemError {
+                message: "Failed to generate a key of network identifiers".to_string(),
+            },
+        )?;
+        Ok(Self(key))
+    }
+
+    /// SHA-256 hash of the key, so keys can be compared without revealing them
+    pub fn key_hash(&self) -> String {
+        hex::encode(sha2::Sha256::digest(&self.0))
+    }
+}
+
+impl std::fmt::Debug for NetworkIdHashKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "NetworkIdHashKey(***)")
+    }
+}
+
+/// Addresses without anonymization modes are left as is
+#[derive(Debug, Clone)]
+pub struct NetworkIdOptions {
+    pub ip: Option<IpAnonymization>,
+    pub ipv4_prefix: u8,
+    pub ipv6_prefix: u8,
+    pub mac: Option<MacAnonymization>,
+    pub hash_key: NetworkIdHashKey,
+}
+
+impl NetworkIdOptions {
+    pub const DEFAULT_IPV4_PREFIX: u8 = 24;
+    pub const DEFAULT_IPV6_PREFIX: u8 = 48;
+}
+
+/// All addresses are masked by default
+impl Default for NetworkIdOptions {
+    fn default() -> Self {
+        Self {
+            ip: Some(IpAnonymization::default()),
+            ipv4_prefix: Self::DEFAULT_IPV4_PREFIX,
+            ipv6_prefix: Self::DEFAULT_IPV6_PREFIX,
+            mac: Some(MacAnonymization::default()),
+            hash_key: NetworkIdHashKey::default(),
+        }
+    }
+}
+
+/// Local anonymization of IP and MAC addresses. Unlike replacing them with `[REDACTED]` by redacters,
+/// truncated networks, vendor prefixes and pseudonyms keep text useful for analytics.
+pub struct NetworkIdAnonymizer {
+    options: NetworkIdOptions,
+    hash_key: ring::hmac::Key,
+    ipv4: Regex,
+    ipv6: Regex,
+    mac: Regex,
+}
+
+impl NetworkIdAnonymizer {
+    pub fn new(options: NetworkIdOptions) -> AppResult<Self> {
+        let compile = |pattern: &str| {
+            Regex::new(pattern).map_err(|err| AppError::SystemError {
+                message: format!("Failed to compile network identifier pattern: {}", err),
+            })
+        };
+        Ok(Self {
+            hash_key: ring::hmac::Key::new(ring::hmac::HMAC_SHA256, &options.hash_key.0),
+            options,
+            ipv4: compile(r"\b\d{1,3}(?:\.\d{1,3}){3}\b")?,
+            ipv6: compile(r"[0-9A-Fa-f]{0,4}(?::[0-9A-Fa-f]{0,4}){2,7}")?,
+            mac: compile(r"\b[0-9A-Fa-f]{2}(?:[:-][0-9A-Fa-f]{2}){5}\b")?,
+        })
+    }
+
+    /// Anonymizes MAC and IP addresses in the text, counting anonymized addresses
+    pub fn anonymize(&self, text: &str, anonymized: &mut usize) -> String {
+        let mut text = text.to_string();
+        if let Some(mac_anonymization) = self.options.mac {
+            text = self.replace_standalone(&text, &self.mac, anonymized, |mac| {
+                self.anonymize_mac(mac, mac_anonymization)
+            });
+        }
+        if let Some(ip_anonymization) = self.options.ip {
+            text = self.replace_standalone(&text, &self.ipv4, anonymized, |candidate| {
+                candidate
+                    .parse::<Ipv4Addr>()
+                    .ok()
+                    .map(|ip| self.anonymize_ipv4(ip, ip_anonymization))
+            });
+            text = self.replace_standalone(&text, &self.ipv6, anonymized, |candidate| {
+                // Candidates such as `::` are valid addresses without digits, but rather separators
+                candidate
+                    .parse::<Ipv6Addr>()
+                    .ok()
+                    .filter(|_| candidate.chars().any(|ch| ch.is_ascii_digit()))
+                    .map(|ip| self.anonymize_ipv6(ip, ip_anonymization))
+            });
+        }
+        text
+    }
+
+    /// Candidates are replaced only if they are valid addresses not adjacent to other words or numbers
+    /// (such as versions `1.2.3.4.5` or paths `std::fmt`)
+    fn replace_standalone<F: Fn(&str) -> Option<String>>(
+        &self,
+        text: &str,
+        pattern: &Regex,
+        anonymized: &mut usize,
+        replacement: F,
+    ) -> String {
+        let is_adjacent = |ch: char| ch.is_alphanumeric() || ch == '.' || ch == '_';
+        let mut result = String::with_capacity(text.len());
+        let mut position = 0;
+        for candidate in pattern.find_iter(text) {
+            let is_standalone = !text[..candidate.start()]
+                .chars()
+                .next_back()
+                .is_some_and(is_adjacent)
+                && !text[candidate.end()..]
+                    .chars()
+                    .next()
+                    .is_some_and(is_adjacent);
+            if let Some(value) = replacement(candidate.as_str()).filter(|_| is_standalone) {
+                result.push_str(&text[position..candidate.start()]);
+                result.push_str(&value);
+                position = candidate.end();
+                *anonymized += 1;
+            }
+        }
+        result.push_str(&text[position..]);
+        result
+    }
+
+    fn anonymize_ipv4(&self, ip: Ipv4Addr, anonymization: IpAnonymization) -> String {
+        match anonymization {
+            IpAnonymization::Mask => "[IP]".to_string(),
+            IpAnonymization::Truncate => {
+                let prefix = self.options.ipv4_prefix.min(32);
+                let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+                format!("{}/{}", Ipv4Addr::from(u32::from(ip) & mask), prefix)
+            }
+            IpAnonymization::Hash => format!("ip-{}", self.hash(&ip.octets())),
+        }
+    }
+
+    fn anonymize_ipv6(&self, ip: Ipv6Addr, anonymization: IpAnonymization) -> String {
+        match anonymization {
+            IpAnonymization::Mask => "[IP]".to_string(),
+            IpAnonymization::Truncate => {
+                let prefix = self.options.ipv6_prefix.min(128);
+                let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+                format!("{}/{}", Ipv6Addr::from(u128::from(ip) & mask), prefix)
+            }
+            IpAnonymization::Hash => format!("ip-{}", self.hash(&ip.octets())),
+        }
+    }
+
+    fn anonymize_mac(&self, mac: &str, anonymization: MacAnonymization) -> Option<String> {
+        let octets: Vec<u8> = mac
+            .split([':', '-'])
+            .map(|octet| u8::from_str_radix(octet, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        Some(match anonymization {
+            MacAnonymization::Mask => "[MAC]".to_string(),
+            MacAnonymization::Oui => format!(
+                "{:02x}:{:02x}:{:02x}:00:00:00",
+                octets[0], octets[1], octets[2]
+            ),
+            MacAnonymization::Hash => format!("mac-{}", self.hash(&octets)),
+        })
+    }
+
+    /// Keyed hashes of binary addresses, so different notations of the same address have the same pseudonym
+    fn hash(&self, value: &[u8]) -> String {
+        hex::encode(&ring::hmac::sign(&self.hash_key, value).as_ref()[..6])
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anonymize_network_ids_test() -> AppResult<()> {
+        let text = "from 192.168.10.20 and 2001:db8:85a3::8a2e:370:7334 via 00:1A:2B:3C:4D:5E at 12:30:45 v1.2.3.4.5 std::fmt";
+        let mut anonymized = 0;
+        let masked =
+            NetworkIdAnonymizer::new(NetworkIdOptions::default())?.anonymize(text, &mut anonymized);
+        assert_eq!(
+            masked,
+            "from [IP] and [IP] via [MAC] at 12:30:45 v1.2.3.4.5 std::fmt"
+        );
+        assert_eq!(anonymized, 3);
+
+        let truncated = NetworkIdAnonymizer::new(NetworkIdOptions {
+            ip: Some(IpAnonymization::Truncate),
+            ipv4_prefix: 16,
+            mac: Some(MacAnonymization::Oui),
+            ..NetworkIdOptions::default()
+        })?
+        .anonymize(text, &mut anonymized);
+        assert_eq!(
+            truncated,
+            "from 192.168.0.0/16 and 2001:db8:85a3::/48 via 00:1a:2b:00:00:00 at 12:30:45 v1.2.3.4.5 std::fmt"
+        );
+
+        let hashing = NetworkIdAnonymizer::new(NetworkIdOptions {
+            ip: Some(IpAnonymization::Hash),
+            mac: Some(MacAnonymization::Hash),
+            hash_key: NetworkIdHashKey::new(b"key".to_vec()),
+            ..NetworkIdOptions::default()
+        })?;
+        let hashed = hashing.anonymize("10.0.0.1 00-1a-2b-3c-4d-5e 10.0.0.2", &mut anonymized);
+        let pseudonyms: Vec<&str> = hashed.split(' ').collect();
+        assert!(pseudonyms[0].starts_with("ip-") && pseudonyms[0].len() == 15);
+        assert!(pseudonyms[1].starts_with("mac-"));
+        assert_ne!(pseudonyms[0], pseudonyms[2]);
+        assert_eq!(
+            hashing.anonymize("00:1A:2B:3C:4D:5E", &mut anonymized),
+            pseudonyms[1]
+        );
+
+        let mac_only = NetworkIdAnonymizer::new(NetworkIdOptions {
+            ip: None,
+            ..NetworkIdOptions::default()
+        })?;
+        assert_eq!(
+            mac_only.anonymize("10.0.0.1 00:1a:2b:3c:4d:5e", &mut anonymized),
+            "10.0.0.1 [MAC]"
+        );
+        Ok(())
+    }
+}
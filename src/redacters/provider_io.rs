@@ -0,0 +1,172 @@
+use crate::args::RedacterType;
+use crate::errors::AppError;
+use crate::AppResult;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderIoMode {
+    /// Requests are sent to providers and both requests and responses are stored
+    Record,
+    /// Stored responses are used instead of calling providers, and missing responses fail redaction
+    Replay,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProviderIoEntry {
+    redacter: String,
+    operation: String,
+    model: String,
+    request: String,
+    responses: Vec<String>,
+    recorded_at: DateTime<Utc>,
+}
+
+/// Provider requests and responses recorded in a directory, so prompts and processing of responses
+/// of LLM redacters can be tuned offline by replaying the same responses without calling providers again.
+/// Responses are keyed by providers, models, operations and input content, but not prompts,
+/// since prompts are what is usually changed between replays. Recorded requests and responses contain
+/// the original content, so the directory and files are readable only by the owner.
+#[derive(Debug, Clone)]
+pub struct ProviderIo {
+    pub mode: ProviderIoMode,
+    pub dir: PathBuf,
+}
+
+impl ProviderIo {
+    pub fn new(mode: ProviderIoMode, dir: PathBuf) -> AppResult<Self> {
+        match mode {
+            ProviderIoMode::Record => {
+                let mut builder = std::fs::DirBuilder::new();
+                builder.recursive(true);
+                #[cfg(unix)]
+                std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+                builder.create(&dir)?;
+            }
+            ProviderIoMode::Replay if !dir.is_dir() => {
+                return Err(AppError::RedacterConfigError {
+                    message: format!(
+                        "Directory of recorded provider responses {} doesn't exist",
+                        dir.display()
+                    ),
+                })
+            }
+            ProviderIoMode::Replay => {}
+        }
+        Ok(Self { mode, dir })
+    }
+
+    pub fn key(redacter_type: RedacterType, operation: &str, model: &str, input: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{}:{}:{}:", redacter_type, operation, model).as_bytes());
+        hasher.update(input);
+        format!("{}-{}", redacter_type, hex::encode(hasher.finalize()))
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.mode == ProviderIoMode::Replay
+    }
+
+    /// Recorded responses in the replay mode, or `None` if providers should be called
+    pub async fn recorded_responses(&self, key: &str) -> AppResult<Option<Vec<String>>> {
+        if !self.is_replay() {
+            return Ok(None);
+        }
+        let entry_path = self.dir.join(format!("{}.json", key));
+        if !tokio::fs::try_exists(&entry_path).await? {
+            return Err(AppError::SystemError {
+                message: format!(
+                    "No recorded provider response {} in {}. Record responses with --record-provider-io first",
+                    key,
+                    self.dir.display()
+                ),
+            });
+        }
+        let entry: ProviderIoEntry = serde_json::from_slice(&tokio::fs::read(entry_path).await?)?;
+        Ok(Some(entry.responses))
+    }
+
+    /// Stores the request and responses in the record mode
+    pub async fn record(
+        &self,
+        key: &str,
+        operation: &str,
+        model: &str,
+        request: String,
+        responses: Vec<String>,
+    ) -> AppResult<()> {
+        if self.mode != ProviderIoMode::Record {
+            return Ok(());
+        }
+        let (redacter, _) = key.rsplit_once('-').unwrap_or((key, ""));
+        let entry = ProviderIoEntry {
+            redacter: redacter.to_string(),
+            operation: operation.to_string(),
+            model: model.to_string(),
+            request,
+            responses,
+            recorded_at: Utc::now(),
+        };
+        let content = serde_json::to_vec_pretty(&entry)?;
+        let entry_path = self.dir.join(format!("{}.json", key));
+        tokio::task::spawn_blocking(move || -> AppResult<()> {
+            let mut open_options = std::fs::OpenOptions::new();
+            open_options.write(true).create(true).truncate(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut open_options, 0o600);
+            open_options.open(entry_path)?.write_all(&content)?;
+            Ok(())
+        })
+        .await
+        .map_err(|err| AppError::SystemError {
+            message: format!("Failed to record provider response: {}", err),
+        })?
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn record_and_replay_test() -> AppResult<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path().join("provider-io");
+        let recorder = ProviderIo::new(ProviderIoMode::Record, dir.clone())?;
+        let key = ProviderIo::key(RedacterType::OpenAiLlm, "text", "gpt-4o-mini", b"John Doe");
+        assert_ne!(
+            key,
+            ProviderIo::key(RedacterType::OpenAiLlm, "text", "gpt-4o", b"John Doe")
+        );
+        assert!(recorder.recorded_responses(&key).await?.is_none());
+        recorder
+            .record(
+                &key,
+                "text",
+                "gpt-4o-mini",
+                "{\"model\":\"gpt-4o-mini\"}".to_string(),
+                vec!["data: [REDACTED]\n\n".to_string()],
+            )
+            .await?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let entry_metadata = std::fs::metadata(dir.join(format!("{}.json", key)))?;
+            assert_eq!(entry_metadata.permissions().mode() & 0o777, 0o600);
+            assert_eq!(std::fs::metadata(&dir)?.permissions().mode() & 0o777, 0o700);
+        }
+
+        let replayer = ProviderIo::new(ProviderIoMode::Replay, dir)?;
+        assert_eq!(
+            replayer.recorded_responses(&key).await?,
+            Some(vec!["data: [REDACTED]\n\n".to_string()])
+        );
+        let missing_key = ProviderIo::key(RedacterType::OpenAiLlm, "text", "gpt-4o-mini", b"Jane");
+        assert!(replayer.recorded_responses(&missing_key).await.is_err());
+        assert!(ProviderIo::new(ProviderIoMode::Replay, temp_dir.path().join("missing")).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+
+/// Finds which words of the original text were redacted, aligning them with the words of the redacted text.
+/// Redacters may normalize whitespace and punctuation or replace only parts of words, so words are
+/// compared normalized and fuzzy, and the longest common subsequence of words is considered unchanged.
+/// Everything else, such as multi-word entities replaced with a single placeholder, is redacted.
+pub fn find_redacted_words<S: AsRef<str>>(original_words: &[S], redacted_text: &str) -> Vec<bool> {
+    let original: Vec<String> = original_words
+        .iter()
+        .map(|word| normalize_word(word.as_ref()))
+        .collect();
+    let redacted: Vec<String> = redacted_text
+        .split_whitespace()
+        .map(normalize_word)
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    if original.len().saturating_mul(redacted.len()) > MAX_ALIGNMENT_CELLS {
+        // Too long to align, so only words completely missing from the redacted text are redacted
+        let redacted_set: HashSet<&str> = redacted.iter().map(|word| word.as_str()).collect();
+        return original
+            .iter()
+            .map(|word| !word.is_empty() && !redacted_set.contains(word.as_str()))
+            .collect();
+    }
+
+    let columns = redacted.len() + 1;
+    let mut lcs_lengths = vec![0u16; (original.len() + 1) * columns];
+    for (i, original_word) in original.iter().enumerate() {
+        for (j, redacted_word) in redacted.iter().enumerate() {
+            lcs_lengths[(i + 1) * columns + j + 1] = if words_match(original_word, redacted_word) {
+                lcs_lengths[i * columns + j] + 1
+            } else {
+                lcs_lengths[i * columns + j + 1].max(lcs_lengths[(i + 1) * columns + j])
+            };
+        }
+    }
+
+    // Words without letters and digits are never sensitive, so they aren't redacted
+    let mut redacted_words: Vec<bool> = original.iter().map(|word| !word.is_empty()).collect();
+    let (mut i, mut j) = (original.len(), redacted.len());
+    while i > 0 && j > 0 {
+        if words_match(&original[i - 1], &redacted[j - 1])
+            && lcs_lengths[i * columns + j] == lcs_lengths[(i - 1) * columns + j - 1] + 1
+        {
+            redacted_words[i - 1] = false;
+            i -= 1;
+            j -= 1;
+        } else if lcs_lengths[(i - 1) * columns + j] >= lcs_lengths[i * columns + j - 1] {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+    redacted_words
+}
+
+// Limits the memory used for alignment to 64MB
+const MAX_ALIGNMENT_CELLS: usize = 32 * 1024 * 1024;
+
+fn normalize_word(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Words match if they are the same or slightly different (up to 20% of characters) for longer words,
+/// since OCR and redacters may treat some characters differently
+fn words_match(original_word: &str, redacted_word: &str) -> bool {
+    if original_word.is_empty() || original_word == redacted_word {
+        return !original_word.is_empty();
+    }
+    let original_chars: Vec<char> = original_word.chars().collect();
+    let redacted_chars: Vec<char> = redacted_word.chars().collect();
+    let max_distance = original_chars.len().min(redacted_chars.len()) / 5;
+    max_distance > 0
+        && original_chars.len().abs_diff(redacted_chars.len()) <= max_distance
+        && levenshtein_distance(&original_chars, &redacted_chars) <= max_distance
+}
+
+fn levenshtein_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous_row: Vec<usize> = (0..=b.len()).collect();
+    let mut current_row = vec![0; b.len() + 1];
+    for (i, a_char) in a.iter().enumerate() {
+        current_row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution_cost = if a_char == b_char { 0 } else { 1 };
+            current_row[j + 1] = (previous_row[j] + substitution_cost)
+                .min(previous_row[j + 1] + 1)
+                .min(current_row[j] + 1);
+        }
+        std::mem::swap(&mut previous_row, &mut current_row);
+    }
+    previous_row[b.len()]
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_word_entities_test() {
+        let words = [
+            "Contact", "John", "Smith", "or", "John's", "manager", "at", "+1", "555", "0100.",
+        ];
+        assert_eq!(
+            find_redacted_words(
+                &words,
+                "Contact [PERSON_NAME] or John's manager at [PHONE_NUMBER]."
+            ),
+            vec![false, true, true, false, false, false, false, true, true, true]
+        );
+    }
+
+    #[test]
+    fn normalized_whitespace_and_punctuation_test() {
+        let words = ["Name:", "Jane", "Doe,", "e-mail:", "jane@example.com", "-"];
+        assert_eq!(
+            find_redacted_words(&words, "Name  [REDACTED] [REDACTED]\nemail: [REDACTED]"),
+            vec![false, true, true, false, true, false]
+        );
+    }
+
+    #[test]
+    fn partial_words_and_fuzzy_match_test() {
+        let words = ["Customer", "ID:12345", "Adress", "unchanged"];
+        assert_eq!(
+            find_redacted_words(&words, "Customer ID:[REDACTED] Address unchanged"),
+            vec![false, true, false, false]
+        );
+    }
+
+    #[test]
+    fn repeated_words_test() {
+        let words = ["John", "called", "John"];
+        assert_eq!(
+            find_redacted_words(&words, "John called [NAME]"),
+            vec![false, false, true]
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::common_types::DlpVolumeLimit;
 use std::ops::Add;
 use std::time::{Duration, Instant};
 
@@ -62,11 +63,9 @@ impl RedacterThrottler {
             let delay_penalty = (self.rate_limit_in_millis as f64 * self.capacity.abs() as f64
                 / self.max_capacity as f64) as u64;
 
-            let delay_in_millis = if base_delay_in_millis < self.rate_limit_in_millis {
-                self.rate_limit_in_millis - base_delay_in_millis
-            } else {
-                0
-            };
+            let delay_in_millis = self
+                .rate_limit_in_millis
+                .saturating_sub(base_delay_in_millis);
             let delay_with_penalty =
                 Duration::from_millis(delay_in_millis.saturating_add(delay_penalty));
 
@@ -85,10 +84,59 @@ impl RedacterThrottler {
     }
 }
 
+/// Throttles redaction by the volume of content instead of the number of requests.
+/// The volume available for redaction is restored continuously up to the limit, and files larger than
+/// the available volume are delayed until it is restored, so the limit is kept on average.
+#[derive(Clone, Debug)]
+pub struct RedacterVolumeThrottler {
+    limit: DlpVolumeLimit,
+    available: f64,
+    last_updated: Instant,
+    delay: Duration,
+}
+
+impl RedacterVolumeThrottler {
+    pub fn new(limit: DlpVolumeLimit) -> Self {
+        Self {
+            available: limit.value as f64,
+            limit,
+            last_updated: Instant::now(),
+            delay: Duration::from_millis(0),
+        }
+    }
+
+    /// Takes the volume of content with the size in bytes
+    pub fn update(&self, now: Instant, size: usize) -> Self {
+        let volume_per_milli = self.limit.value as f64 / self.limit.per.as_millis() as f64;
+        let time_elapsed_millis = now
+            .checked_duration_since(self.last_updated)
+            .unwrap_or_else(|| Duration::from_millis(0))
+            .as_millis() as f64;
+        let available = (self.available + time_elapsed_millis * volume_per_milli)
+            .min(self.limit.value as f64)
+            - self.limit.volume_of(size) as f64;
+        let delay = if available < 0.0 {
+            Duration::from_millis((-available / volume_per_milli).ceil() as u64)
+        } else {
+            Duration::from_millis(0)
+        };
+        Self {
+            available,
+            last_updated: now,
+            delay,
+            ..self.clone()
+        }
+    }
+
+    pub fn delay(&self) -> &Duration {
+        &self.delay
+    }
+}
+
 #[allow(unused_imports)]
 mod tests {
     use super::*;
-    use crate::common_types::DlpRequestLimit;
+    use crate::common_types::{DlpRequestLimit, DlpVolumeUnit};
     use crate::redacters::RedacterProviderOptions;
     use console::Term;
 
@@ -135,4 +183,24 @@ mod tests {
             Duration::from_millis(counter.rate_limit_in_millis)
         );
     }
+
+    #[test]
+    fn check_volume_delay() {
+        let limit: DlpVolumeLimit = "1000bps".parse().unwrap();
+        let throttler = limit.to_throttling_counter();
+        let now = throttler.last_updated;
+
+        let updated = throttler.update(now, 600);
+        assert_eq!(updated.delay, Duration::from_millis(0));
+        let updated = updated.update(now, 600);
+        assert_eq!(updated.delay, Duration::from_millis(200));
+        let updated = updated.update(now.add(Duration::from_millis(1200)), 500);
+        assert_eq!(updated.delay, Duration::from_millis(0));
+
+        let tokens = DlpVolumeLimit::new(100, Duration::from_secs(60), DlpVolumeUnit::Tokens)
+            .to_throttling_counter();
+        let updated = tokens.update(tokens.last_updated, 800);
+        assert_eq!(updated.delay, Duration::from_secs(60));
+        assert!("10rpm".parse::<DlpVolumeLimit>().is_err());
+    }
 }
@@ -0,0 +1,198 @@
+use crate::args::RedacterType;
+use crate::metrics::{AppMetric, AppMetrics};
+use crate::redacters::RedacterDataItemContent;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+/// Billable usage of a redacter provider reported in copy results,
+/// so costs can be attributed to runs without provider billing consoles.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RedacterProviderUsage {
+    pub calls: u64,
+    /// Input tokens reported by LLM providers
+    #[serde(skip_serializing_if = "is_zero")]
+    pub prompt_tokens: u64,
+    /// Output tokens reported by LLM providers
+    #[serde(skip_serializing_if = "is_zero")]
+    pub completion_tokens: u64,
+    /// Content sent to providers billed by the inspected content, such as GCP DLP and AWS Comprehend
+    #[serde(skip_serializing_if = "is_zero")]
+    pub bytes_inspected: u64,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+impl RedacterProviderUsage {
+    const PROMPT_TOKENS_UNIT: &'static str = "prompt_tokens";
+    const COMPLETION_TOKENS_UNIT: &'static str = "completion_tokens";
+    const BYTES_INSPECTED_UNIT: &'static str = "bytes_inspected";
+
+    pub fn record_tokens(redacter_type: RedacterType, prompt_tokens: u64, completion_tokens: u64) {
+        let redacter_type = redacter_type.to_string();
+        let metrics = AppMetrics::global();
+        for (unit, value) in [
+            (Self::PROMPT_TOKENS_UNIT, prompt_tokens),
+            (Self::COMPLETION_TOKENS_UNIT, completion_tokens),
+        ] {
+            metrics.add(
+                AppMetric::PROVIDER_USAGE,
+                &[("redacter", redacter_type.as_str()), ("unit", unit)],
+                value as f64,
+            );
+        }
+    }
+
+    pub fn record_bytes_inspected(redacter_type: RedacterType, content: &RedacterDataItemContent) {
+        let bytes_inspected = match content {
+            RedacterDataItemContent::Value(value) => value.len(),
+            RedacterDataItemContent::Table { headers, rows } => headers
+                .iter()
+                .chain(rows.iter().flatten())
+                .map(String::len)
+                .sum(),
+            RedacterDataItemContent::Image { data, .. } | RedacterDataItemContent::Pdf { data } => {
+                data.len()
+            }
+        };
+        AppMetrics::global().add(
+            AppMetric::PROVIDER_USAGE,
+            &[
+                ("redacter", redacter_type.to_string().as_str()),
+                ("unit", Self::BYTES_INSPECTED_UNIT),
+            ],
+            bytes_inspected as f64,
+        );
+    }
+
+    /// Usage by redacter types collected in the metrics
+    pub fn collect(metrics: &AppMetrics) -> BTreeMap<String, RedacterProviderUsage> {
+        let mut usage: BTreeMap<String, RedacterProviderUsage> = BTreeMap::new();
+        let label = |labels: &[(&'static str, String)], name: &str| {
+            labels
+                .iter()
+                .find(|(label_name, _)| *label_name == name)
+                .map(|(_, value)| value.clone())
+        };
+        for (labels, value) in metrics.series(AppMetric::PROVIDER_CALLS) {
+            if let Some(redacter) = label(&labels, "redacter") {
+                usage.entry(redacter).or_default().calls += value as u64;
+            }
+        }
+        for (labels, value) in metrics.series(AppMetric::PROVIDER_USAGE) {
+            if let (Some(redacter), Some(unit)) =
+                (label(&labels, "redacter"), label(&labels, "unit"))
+            {
+                let provider_usage = usage.entry(redacter).or_default();
+                match unit.as_str() {
+                    Self::PROMPT_TOKENS_UNIT => provider_usage.prompt_tokens += value as u64,
+                    Self::COMPLETION_TOKENS_UNIT => {
+                        provider_usage.completion_tokens += value as u64
+                    }
+                    Self::BYTES_INSPECTED_UNIT => provider_usage.bytes_inspected += value as u64,
+                    _ => {}
+                }
+            }
+        }
+        usage
+    }
+
+    /// Usage collected since the previous usage, since metrics are process wide
+    /// and commands such as watch copy files several times
+    pub fn collect_since(
+        metrics: &AppMetrics,
+        previous: &BTreeMap<String, RedacterProviderUsage>,
+    ) -> BTreeMap<String, RedacterProviderUsage> {
+        Self::collect(metrics)
+            .into_iter()
+            .map(|(redacter, usage)| {
+                let previous_usage = previous.get(&redacter).cloned().unwrap_or_default();
+                (
+                    redacter,
+                    RedacterProviderUsage {
+                        calls: usage.calls.saturating_sub(previous_usage.calls),
+                        prompt_tokens: usage
+                            .prompt_tokens
+                            .saturating_sub(previous_usage.prompt_tokens),
+                        completion_tokens: usage
+                            .completion_tokens
+                            .saturating_sub(previous_usage.completion_tokens),
+                        bytes_inspected: usage
+                            .bytes_inspected
+                            .saturating_sub(previous_usage.bytes_inspected),
+                    },
+                )
+            })
+            .filter(|(_, usage)| *usage != RedacterProviderUsage::default())
+            .collect()
+    }
+}
+
+impl Display for RedacterProviderUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} calls", self.calls)?;
+        if self.prompt_tokens > 0 || self.completion_tokens > 0 {
+            write!(
+                f,
+                ", {} prompt and {} completion tokens",
+                self.prompt_tokens, self.completion_tokens
+            )?;
+        }
+        if self.bytes_inspected > 0 {
+            write!(f, ", {} bytes inspected", self.bytes_inspected)?;
+        }
+        Ok(())
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_usage_test() {
+        let metrics = AppMetrics::default();
+        metrics.inc(AppMetric::PROVIDER_CALLS, &[("redacter", "gcp-dlp")]);
+        metrics.add(
+            AppMetric::PROVIDER_USAGE,
+            &[("redacter", "gcp-dlp"), ("unit", "bytes_inspected")],
+            1024.0,
+        );
+        metrics.inc(AppMetric::PROVIDER_CALLS, &[("redacter", "openai-llm")]);
+        metrics.add(
+            AppMetric::PROVIDER_USAGE,
+            &[("redacter", "openai-llm"), ("unit", "prompt_tokens")],
+            100.0,
+        );
+        metrics.add(
+            AppMetric::PROVIDER_USAGE,
+            &[("redacter", "openai-llm"), ("unit", "completion_tokens")],
+            50.0,
+        );
+        let previous = RedacterProviderUsage::collect(&metrics);
+        assert_eq!(
+            previous.get("openai-llm"),
+            Some(&RedacterProviderUsage {
+                calls: 1,
+                prompt_tokens: 100,
+                completion_tokens: 50,
+                bytes_inspected: 0,
+            })
+        );
+
+        metrics.inc(AppMetric::PROVIDER_CALLS, &[("redacter", "openai-llm")]);
+        metrics.add(
+            AppMetric::PROVIDER_USAGE,
+            &[("redacter", "openai-llm"), ("unit", "prompt_tokens")],
+            10.0,
+        );
+        let usage = RedacterProviderUsage::collect_since(&metrics, &previous);
+        assert_eq!(usage.len(), 1);
+        assert_eq!(
+            usage.get("openai-llm").map(|usage| usage.to_string()),
+            Some("1 calls, 10 prompt and 0 completion tokens".to_string())
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use crate::args::RedacterType;
+use crate::redacters::{
+    LlmTextChunker, Redacter, RedacterDataItem, RedacterDataItemContent, RedacterProviderOptions,
+    Redacters,
+};
+use crate::reporter::AppReporter;
+use crate::AppResult;
+use clap::ValueEnum;
+use rvstruct::ValueStruct;
+use serde::{Deserialize, Serialize};
+use tracing::Instrument;
+
+/// What happens with redacted files if the verification still finds sensitive data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum RedactVerifyAction {
+    #[default]
+    Fail,
+    Report,
+}
+
+#[derive(Debug, Clone)]
+pub struct RedacterVerifyOptions {
+    pub provider_options: RedacterProviderOptions,
+    pub action: RedactVerifyAction,
+}
+
+/// Entity detected in text by a provider, with context for reviewers to triage findings without opening files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedactFinding {
+    /// Type of the detecting redacter such as `gcp-dlp`
+    pub provider: String,
+    /// Provider specific entity type such as `EMAIL_ADDRESS`
+    pub info_type: String,
+    /// Byte offsets of the entity in the verified text, where tables are lines of comma separated cells
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<usize>,
+    /// Surrounding text with the entity replaced by its type, so reports don't contain detected entities
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+impl RedactFinding {
+    const CONTEXT_CHARS: usize = 24;
+
+    pub fn new(
+        provider: RedacterType,
+        info_type: String,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Self {
+        Self {
+            provider: provider.to_string(),
+            info_type,
+            start,
+            end,
+            context: None,
+        }
+    }
+
+    /// Adds a few characters around the entity from the text, if offsets of the entity are valid
+    pub fn with_context(self, text: &str) -> Self {
+        let context = self
+            .start
+            .zip(self.end)
+            .filter(|(start, end)| {
+                start <= end && text.is_char_boundary(*start) && text.is_char_boundary(*end)
+            })
+            .map(|(start, end)| {
+                let before: String = text[..start]
+                    .chars()
+                    .rev()
+                    .take(Self::CONTEXT_CHARS)
+                    .collect::<Vec<char>>()
+                    .into_iter()
+                    .rev()
+                    .collect();
+                let after: String = text
+                    .get(end..)
+                    .unwrap_or_default()
+                    .chars()
+                    .take(Self::CONTEXT_CHARS)
+                    .collect();
+                format!("{}[{}]{}", before, self.info_type, after).replace(['\n', '\r', '\t'], " ")
+            });
+        Self { context, ..self }
+    }
+}
+
+/// Runs a detection capable provider against the redacted content,
+/// catching entities missed by redacters (such as LLM based ones).
+#[derive(Clone)]
+pub struct RedacterVerifier<'a> {
+    redacter: Redacters<'a>,
+    pub action: RedactVerifyAction,
+}
+
+impl<'a> RedacterVerifier<'a> {
+    pub async fn new(
+        verify_options: RedacterVerifyOptions,
+        reporter: &'a AppReporter<'a>,
+    ) -> AppResult<Self> {
+        let redacter = Redacters::new_redacter(verify_options.provider_options, reporter).await?;
+        Ok(Self {
+            redacter,
+            action: verify_options.action,
+        })
+    }
+
+    pub fn redacter_type(&self) -> RedacterType {
+        self.redacter.redacter_type()
+    }
+
+    pub fn llm_text_chunker(&self) -> Option<LlmTextChunker> {
+        self.redacter.llm_text_chunker()
+    }
+
+    /// Returns entities still found in the redacted content with their context,
+    /// or `None` if the content can't be verified (such as images and PDFs).
+    pub async fn verify(
+        &self,
+        redacted: &RedacterDataItem,
+    ) -> AppResult<Option<Vec<RedactFinding>>> {
+        let text = match &redacted.content {
+            RedacterDataItemContent::Value(value) => value.clone(),
+            RedacterDataItemContent::Table { headers, rows } => Self::table_to_text(headers, rows),
+            RedacterDataItemContent::Image { .. } | RedacterDataItemContent::Pdf { .. } => {
+                return Ok(None)
+            }
+        };
+        if text.trim().is_empty() {
+            return Ok(Some(vec![]));
+        }
+        let span = tracing::info_span!(
+            "provider_verify",
+            provider = self.redacter.redacter_type().to_string(),
+            file = redacted.file_ref.relative_path.value().as_str()
+        );
+        let findings: Vec<RedactFinding> = self
+            .redacter
+            .detect_text(text.clone())
+            .instrument(span.clone())
+            .await?
+            .into_iter()
+            .map(|finding| finding.with_context(&text))
+            .collect();
+        span.in_scope(|| tracing::info!(findings = findings.len(), "Verification finished"));
+        Ok(Some(findings))
+    }
+
+    fn table_to_text(headers: &[String], rows: &[Vec<String>]) -> String {
+        std::iter::once(headers)
+            .filter(|headers| !headers.is_empty())
+            .chain(rows.iter().map(|row| row.as_slice()))
+            .map(|row| row.join(", "))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Unique entity types in the order they were found, for reporting
+    pub fn summarize_findings(findings: &[RedactFinding]) -> String {
+        let mut unique: Vec<&str> = Vec::new();
+        for finding in findings {
+            if !unique.contains(&finding.info_type.as_str()) {
+                unique.push(finding.info_type.as_str());
+            }
+        }
+        unique.join(", ")
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_to_text_test() {
+        assert_eq!(
+            RedacterVerifier::table_to_text(
+                &["name", "email"].map(String::from),
+                &[
+                    ["[REDACTED]", "john@example.com"]
+                        .map(String::from)
+                        .to_vec(),
+                    ["[REDACTED]", "[REDACTED]"].map(String::from).to_vec()
+                ]
+            ),
+            "name, email\n[REDACTED], john@example.com\n[REDACTED], [REDACTED]"
+        );
+        assert_eq!(
+            RedacterVerifier::table_to_text(&[], &[["1", "2"].map(String::from).to_vec()]),
+            "1, 2"
+        );
+    }
+
+    #[test]
+    fn summarize_findings_test() {
+        assert_eq!(
+            RedacterVerifier::summarize_findings(
+                &["EMAIL_ADDRESS", "PERSON_NAME", "EMAIL_ADDRESS"].map(|info_type| {
+                    RedactFinding::new(RedacterType::GcpDlp, info_type.to_string(), None, None)
+                })
+            ),
+            "EMAIL_ADDRESS, PERSON_NAME"
+        );
+    }
+
+    #[test]
+    fn finding_context_test() {
+        let text = "Contact [REDACTED] at john@example.com\nor by phone";
+        let finding = RedactFinding::new(
+            RedacterType::MsPresidio,
+            "EMAIL_ADDRESS".to_string(),
+            Some(22),
+            Some(38),
+        )
+        .with_context(text);
+        assert_eq!(
+            finding.context.as_deref(),
+            Some("Contact [REDACTED] at [EMAIL_ADDRESS] or by phone")
+        );
+        assert_eq!(
+            serde_json::to_string(&finding).unwrap(),
+            r#"{"provider":"ms-presidio","info_type":"EMAIL_ADDRESS","start":22,"end":38,"context":"Contact [REDACTED] at [EMAIL_ADDRESS] or by phone"}"#
+        );
+        assert_eq!(
+            RedactFinding::new(
+                RedacterType::AwsComprehend,
+                "NAME".to_string(),
+                Some(3),
+                Some(100)
+            )
+            .with_context(text)
+            .context,
+            None
+        );
+    }
+}
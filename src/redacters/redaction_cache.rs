@@ -0,0 +1,661 @@
+use crate::file_systems::FileSystemRef;
+use crate::metrics::{AppMetric, AppMetrics};
+use crate::redacters::{
+    NetworkIdOptions, RedactFinding, RedactStreamResult, RedactedImageRegion, RedacterOptions,
+    RedacterProviderOptions, StreamRedactPlan, StreamRedacter,
+};
+use crate::reporter::AppReporter;
+use crate::AppResult;
+use chrono::{DateTime, Utc};
+use futures::{Stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct RedactionCacheOptions {
+    pub cache_dir: PathBuf,
+    pub ttl: Duration,
+    pub max_size: Option<u64>,
+    /// Keep OCR results between runs, which unlike redacted content contain the original text of images
+    pub ocr: bool,
+}
+
+impl RedactionCacheOptions {
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+}
+
+type RedactionCacheFields = BTreeMap<&'static str, String>;
+
+/// Options affecting redaction results, which are a part of keys of cached redaction results.
+/// Options are listed explicitly, so secrets such as API keys and options of throttling,
+/// concurrency and caching don't change keys, while keys of pseudonyms are included as hashes.
+#[derive(Debug, Clone, Serialize)]
+pub struct RedactionCacheFingerprint {
+    version: u32,
+    providers: Vec<RedactionCacheFields>,
+    verify_provider: Option<RedactionCacheFields>,
+    base: RedactionCacheFields,
+    ocr: RedactionCacheFields,
+    face_redaction: bool,
+    audio_transcription: Option<RedactionCacheFields>,
+}
+
+impl RedactionCacheFingerprint {
+    /// Increased when redaction results of the same options change between versions
+    const VERSION: u32 = 1;
+
+    pub fn new(options: &RedacterOptions) -> AppResult<Self> {
+        let base_options = &options.base_options;
+        let mut base = RedactionCacheFields::new();
+        base.insert(
+            "allow_unsupported_copies",
+            base_options.allow_unsupported_copies.to_string(),
+        );
+        base.insert(
+            "csv_headers_disable",
+            base_options.csv_headers_disable.to_string(),
+        );
+        base.insert("csv_delimiter", debug_value(&base_options.csv_delimiter));
+        base.insert(
+            "csv_redact_columns",
+            debug_value(&base_options.csv_redact_columns),
+        );
+        base.insert(
+            "csv_skip_columns",
+            debug_value(&base_options.csv_skip_columns),
+        );
+        base.insert("sampling_size", debug_value(&base_options.sampling_size));
+        base.insert(
+            "sampling_strategy",
+            debug_value(&base_options.sampling_strategy),
+        );
+        base.insert(
+            "sample_then_full",
+            base_options.sample_then_full.to_string(),
+        );
+        base.insert(
+            "image_redaction",
+            debug_value(&base_options.image_redaction),
+        );
+        base.insert("redact_policy", base_options.redact_policy.to_string());
+        base.insert(
+            "hash_redacted_text",
+            base_options.hash_redacted_text.to_string(),
+        );
+        base.insert("pdf_a", base_options.pdf_a.to_string());
+        base.insert(
+            "redact_embedded_images",
+            base_options.redact_embedded_images.to_string(),
+        );
+        if let Some(ref log_redaction) = base_options.log_redaction {
+            base.insert("log_format", debug_value(&log_redaction.format));
+            base.insert("log_network", Self::network_value(&log_redaction.network));
+        }
+        if let Some(ref network_anonymization) = base_options.network_anonymization {
+            base.insert("network", Self::network_value(network_anonymization));
+        }
+
+        let mut ocr = RedactionCacheFields::new();
+        ocr.insert("engine", debug_value(&options.ocr_options.engine));
+        ocr.insert("languages", debug_value(&options.ocr_options.languages));
+
+        Ok(Self {
+            version: Self::VERSION,
+            providers: options
+                .provider_options
+                .iter()
+                .map(Self::provider_fields)
+                .collect::<AppResult<Vec<_>>>()?,
+            verify_provider: options
+                .verify_options
+                .as_ref()
+                .map(|verify_options| {
+                    let mut fields = Self::provider_fields(&verify_options.provider_options)?;
+                    fields.insert("action", debug_value(&verify_options.action));
+                    Ok::<_, crate::errors::AppError>(fields)
+                })
+                .transpose()?,
+            base,
+            ocr,
+            face_redaction: options.face_redacter_options.is_some(),
+            audio_transcription: options.audio_transcriber_options.as_ref().map(
+                |audio_transcriber_options| {
+                    let mut fields = RedactionCacheFields::new();
+                    fields.insert("provider", audio_transcriber_options.provider.to_string());
+                    fields.insert(
+                        "language_code",
+                        debug_value(&audio_transcriber_options.language_code),
+                    );
+                    fields.insert("output", debug_value(&audio_transcriber_options.output));
+                    fields
+                },
+            ),
+        })
+    }
+
+    pub fn to_json(&self) -> AppResult<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Network identifiers are pseudonymized with the key, which is hashed as it is
+    fn network_value(options: &NetworkIdOptions) -> String {
+        format!(
+            "ip={:?};prefixes={}/{};mac={:?};hash_key={}",
+            options.ip,
+            options.ipv4_prefix,
+            options.ipv6_prefix,
+            options.mac,
+            options.hash_key.key_hash()
+        )
+    }
+
+    fn provider_fields(options: &RedacterProviderOptions) -> AppResult<RedactionCacheFields> {
+        let mut fields = RedactionCacheFields::new();
+        fields.insert("provider", options.redacter_type().to_string());
+        match options {
+            RedacterProviderOptions::GcpDlp(options) => {
+                fields.insert(
+                    "built_in_info_types",
+                    debug_value(&options.user_defined_built_in_info_types),
+                );
+                fields.insert(
+                    "stored_info_types",
+                    debug_value(&options.user_defined_stored_info_types),
+                );
+                fields.insert(
+                    "entity_type_filter",
+                    debug_value(&options.entity_type_filter),
+                );
+                fields.insert("transformation", debug_value(&options.transformation));
+                // Format-preserving encryption depends on the key, which is hashed as it is
+                fields.insert(
+                    "crypto_key",
+                    options
+                        .crypto_key
+                        .as_ref()
+                        .map(|crypto_key| {
+                            format!(
+                                "{}:{}",
+                                crypto_key.crypto_key_name,
+                                hex::encode(Sha256::digest(&crypto_key.wrapped_key))
+                            )
+                        })
+                        .unwrap_or_default(),
+                );
+                fields.insert(
+                    "surrogate_info_type",
+                    debug_value(&options.surrogate_info_type),
+                );
+                fields.insert("image_redaction", debug_value(&options.image_redaction));
+            }
+            RedacterProviderOptions::AwsComprehend(options) => {
+                fields.insert(
+                    "entity_type_filter",
+                    debug_value(&options.entity_type_filter),
+                );
+                fields.insert("language", debug_value(&options.language));
+            }
+            RedacterProviderOptions::MsPresidio(options) => {
+                fields.insert("text_analyze_url", debug_value(&options.text_analyze_url));
+                fields.insert("image_redact_url", debug_value(&options.image_redact_url));
+                fields.insert(
+                    "entity_type_filter",
+                    debug_value(&options.entity_type_filter),
+                );
+                fields.insert("language", debug_value(&options.language));
+                fields.insert("image_redaction", debug_value(&options.image_redaction));
+                fields.insert("score_threshold", debug_value(&options.score_threshold));
+                fields.insert("entities", debug_value(&options.entities));
+            }
+            RedacterProviderOptions::GeminiLlm(options) => {
+                fields.insert("model", debug_value(&options.gemini_model));
+                fields.insert(
+                    "entity_type_filter",
+                    debug_value(&options.entity_type_filter),
+                );
+                fields.insert("image_redaction", debug_value(&options.image_redaction));
+                fields.insert("provider_io", debug_value(&options.provider_io));
+            }
+            RedacterProviderOptions::OpenAiLlm(options) => {
+                fields.insert("model", debug_value(&options.model));
+                fields.insert("api_url", debug_value(&options.api_url));
+                fields.insert(
+                    "entity_type_filter",
+                    debug_value(&options.entity_type_filter),
+                );
+                fields.insert("image_redaction", debug_value(&options.image_redaction));
+                fields.insert("provider_io", debug_value(&options.provider_io));
+            }
+            RedacterProviderOptions::GcpVertexAi(options) => {
+                fields.insert(
+                    "native_image_support",
+                    options.native_image_support.to_string(),
+                );
+                fields.insert("text_model", debug_value(&options.text_model));
+                fields.insert("image_model", debug_value(&options.image_model));
+                fields.insert("block_none_harmful", options.block_none_harmful.to_string());
+                fields.insert(
+                    "entity_type_filter",
+                    debug_value(&options.entity_type_filter),
+                );
+                fields.insert("image_redaction", debug_value(&options.image_redaction));
+            }
+            RedacterProviderOptions::External(options) => {
+                fields.insert("command", debug_value(&options.command));
+                fields.insert("image_support", options.image_support.to_string());
+                fields.insert(
+                    "entity_type_filter",
+                    debug_value(&options.entity_type_filter),
+                );
+            }
+            RedacterProviderOptions::Dictionary(options) => {
+                // Terms of dictionaries change without changing their files
+                let dictionary = std::fs::read(&options.dictionary_file)?;
+                fields.insert("dictionary", hex::encode(Sha256::digest(&dictionary)));
+                fields.insert("ignore_case", options.ignore_case.to_string());
+                fields.insert("whole_words", options.whole_words.to_string());
+            }
+            RedacterProviderOptions::Secrets(options) => {
+                fields.insert("min_entropy", options.min_entropy.to_string());
+            }
+            RedacterProviderOptions::Mock(options) => {
+                fields.insert("patterns", debug_value(&options.patterns));
+                fields.insert("every_nth_word", debug_value(&options.every_nth_word));
+            }
+        }
+        Ok(fields)
+    }
+}
+
+fn debug_value<T: Debug>(value: &T) -> String {
+    format!("{:?}", value)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RedactionCacheEntry {
+    number_of_redactions: usize,
+    #[serde(default)]
+    applied_redacters: Vec<String>,
+    residual_findings: Vec<RedactFinding>,
+    #[serde(default)]
+    redacted_regions: Vec<RedactedImageRegion>,
+    /// Content without anything to redact isn't stored, the input is used as is instead
+    #[serde(default)]
+    unchanged: bool,
+    created_at: DateTime<Utc>,
+}
+
+/// Local cache of redacted content keyed by hashes of the content and redacter options,
+/// so re-running jobs over mostly unchanged datasets doesn't call (and bill) providers again.
+/// Each entry is stored as redacted content in `<key>.bin` and `<key>.json` with the redaction results.
+/// Files without anything to redact are cached as well, with `<key>.json` only.
+pub struct RedactionCache<'a> {
+    options: RedactionCacheOptions,
+    options_fingerprint: String,
+    reporter: &'a AppReporter<'a>,
+}
+
+impl<'a> RedactionCache<'a> {
+    /// The fingerprint should describe all options affecting redaction results, such as providers and their options
+    pub async fn new(
+        options: RedactionCacheOptions,
+        options_fingerprint: &str,
+        reporter: &'a AppReporter<'a>,
+    ) -> AppResult<Self> {
+        tokio::fs::create_dir_all(&options.cache_dir).await?;
+        let cache = Self {
+            options,
+            options_fingerprint: hex::encode(Sha256::digest(options_fingerprint.as_bytes())),
+            reporter,
+        };
+        cache.cleanup().await?;
+        Ok(cache)
+    }
+
+    pub async fn redact_stream<
+        'r,
+        S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static,
+    >(
+        &self,
+        stream_redacter: &'r StreamRedacter<'r>,
+        input: S,
+        redact_plan: StreamRedactPlan<'r>,
+        file_ref: &FileSystemRef,
+    ) -> AppResult<RedactStreamResult> {
+        let all_chunks: Vec<bytes::Bytes> = input.try_collect().await?;
+        let data = bytes::Bytes::from(all_chunks.concat());
+        let key = self.key(file_ref, &data);
+
+        match self.get(&key, &data).await {
+            Ok(Some(cached_result)) => {
+                self.reporter.report("↳ Using cached redaction result")?;
+                AppMetrics::global().inc(AppMetric::CACHE_HITS, &[]);
+                return Ok(cached_result);
+            }
+            Ok(None) => {}
+            Err(error) => {
+                tracing::warn!(error = %error, "Failed to read cached redaction result");
+            }
+        }
+
+        let redacted_result = stream_redacter
+            .redact_stream(
+                futures::stream::iter(vec![Ok(data.clone())]),
+                redact_plan,
+                file_ref,
+            )
+            .await?;
+        let redacted_chunks: Vec<bytes::Bytes> = redacted_result.stream.try_collect().await?;
+        let redacted_data = bytes::Bytes::from(redacted_chunks.concat());
+        let entry = RedactionCacheEntry {
+            number_of_redactions: redacted_result.number_of_redactions,
+            applied_redacters: redacted_result.applied_redacters,
+            residual_findings: redacted_result.residual_findings,
+            redacted_regions: redacted_result.redacted_regions,
+            unchanged: redacted_data == data,
+            created_at: Utc::now(),
+        };
+        if let Err(error) = self.put(&key, &entry, &redacted_data).await {
+            tracing::warn!(error = %error, "Failed to cache redaction result");
+        }
+        Ok(RedactStreamResult {
+            number_of_redactions: entry.number_of_redactions,
+            applied_redacters: entry.applied_redacters,
+            residual_findings: entry.residual_findings,
+            redacted_regions: entry.redacted_regions,
+            stream: Box::new(futures::stream::iter(vec![Ok(redacted_data)])),
+        })
+    }
+
+    fn key(&self, file_ref: &FileSystemRef, data: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.options_fingerprint.as_bytes());
+        hasher.update(
+            file_ref
+                .media_type
+                .as_ref()
+                .map(|media_type| media_type.to_string())
+                .unwrap_or_default()
+                .as_bytes(),
+        );
+        hasher.update(data);
+        hex::encode(hasher.finalize())
+    }
+
+    async fn get(&self, key: &str, input: &bytes::Bytes) -> AppResult<Option<RedactStreamResult>> {
+        let entry_path = self.options.cache_dir.join(format!("{}.json", key));
+        if !tokio::fs::try_exists(&entry_path).await? {
+            return Ok(None);
+        }
+        // Entries written by older versions in other formats are redacted again
+        let entry: RedactionCacheEntry =
+            match serde_json::from_slice(&tokio::fs::read(&entry_path).await?) {
+                Ok(entry) => entry,
+                Err(_) => return Ok(None),
+            };
+        if self.is_expired(&entry) {
+            self.remove(key).await?;
+            return Ok(None);
+        }
+        let data = if entry.unchanged {
+            input.clone()
+        } else {
+            tokio::fs::read(self.options.cache_dir.join(format!("{}.bin", key)))
+                .await?
+                .into()
+        };
+        Ok(Some(RedactStreamResult {
+            number_of_redactions: entry.number_of_redactions,
+            applied_redacters: entry.applied_redacters,
+            residual_findings: entry.residual_findings,
+            redacted_regions: entry.redacted_regions,
+            stream: Box::new(futures::stream::iter(vec![Ok(data)])),
+        }))
+    }
+
+    async fn put(&self, key: &str, entry: &RedactionCacheEntry, data: &[u8]) -> AppResult<()> {
+        // The entry is written last, so partially written content is never used
+        if !entry.unchanged {
+            tokio::fs::write(self.options.cache_dir.join(format!("{}.bin", key)), data).await?;
+        }
+        tokio::fs::write(
+            self.options.cache_dir.join(format!("{}.json", key)),
+            serde_json::to_vec(entry)?,
+        )
+        .await?;
+        if self.options.max_size.is_some() {
+            self.cleanup().await?;
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> AppResult<()> {
+        for extension in ["json", "bin"] {
+            let path = self
+                .options
+                .cache_dir
+                .join(format!("{}.{}", key, extension));
+            if tokio::fs::try_exists(&path).await? {
+                tokio::fs::remove_file(path).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_expired(&self, entry: &RedactionCacheEntry) -> bool {
+        Utc::now()
+            .signed_duration_since(entry.created_at)
+            .to_std()
+            .is_ok_and(|age| age > self.options.ttl)
+    }
+
+    /// Removes expired entries, and the oldest entries exceeding the maximum size of the cache
+    async fn cleanup(&self) -> AppResult<()> {
+        let mut entries = Vec::new();
+        let mut dir = tokio::fs::read_dir(&self.options.cache_dir).await?;
+        while let Some(dir_entry) = dir.next_entry().await? {
+            let path = dir_entry.path();
+            let key = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(key) if path.extension().is_some_and(|ext| ext == "json") => key.to_string(),
+                _ => continue,
+            };
+            let entry: Option<RedactionCacheEntry> = tokio::fs::read(&path)
+                .await
+                .ok()
+                .and_then(|content| serde_json::from_slice(&content).ok());
+            match entry {
+                Some(entry) if !self.is_expired(&entry) => {
+                    let data_size =
+                        tokio::fs::metadata(self.options.cache_dir.join(format!("{}.bin", key)))
+                            .await
+                            .map(|metadata| metadata.len())
+                            .unwrap_or(0);
+                    entries.push((
+                        key,
+                        entry.created_at,
+                        data_size + dir_entry.metadata().await?.len(),
+                    ));
+                }
+                _ => self.remove(&key).await?,
+            }
+        }
+        if let Some(max_size) = self.options.max_size {
+            entries.sort_by_key(|(_, created_at, _)| std::cmp::Reverse(*created_at));
+            let mut total_size = 0;
+            for (key, _, size) in entries {
+                total_size += size;
+                if total_size > max_size {
+                    self.remove(&key).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use crate::file_converters::FileConverters;
+    use crate::redacters::Redacters;
+    use console::Term;
+
+    #[test]
+    fn fingerprint_test() -> AppResult<()> {
+        #[derive(clap::Parser)]
+        struct TestArgs {
+            #[command(flatten)]
+            redacter_args: crate::args::RedacterArgs,
+        }
+        let fingerprint = |args: &[&str]| -> AppResult<String> {
+            let test_args = <TestArgs as clap::Parser>::try_parse_from(
+                ["redacter", "-d", "open-ai-llm"].iter().chain(args),
+            )
+            .map_err(|err| crate::errors::AppError::SystemError {
+                message: err.to_string(),
+            })?;
+            let options: RedacterOptions = test_args.redacter_args.try_into()?;
+            RedactionCacheFingerprint::new(&options)?.to_json()
+        };
+        let default_model = fingerprint(&["--open-ai-api-key", "key1"])?;
+        assert_eq!(default_model, fingerprint(&["--open-ai-api-key", "key2"])?);
+        assert!(!default_model.contains("key1"));
+        assert_ne!(
+            default_model,
+            fingerprint(&["--open-ai-api-key", "key1", "--open-ai-model", "gpt-4o"])?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cleanup_expired_and_oversized_entries_test() -> AppResult<()> {
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let temp_dir = tempfile::tempdir()?;
+        let cache = RedactionCache::new(
+            RedactionCacheOptions {
+                cache_dir: temp_dir.path().to_path_buf(),
+                ttl: Duration::from_secs(60),
+                max_size: Some(1024),
+                ocr: false,
+            },
+            "gcp-dlp",
+            &reporter,
+        )
+        .await?;
+        let entry = |created_at: DateTime<Utc>| RedactionCacheEntry {
+            number_of_redactions: 1,
+            applied_redacters: vec!["gcp-dlp".to_string()],
+            residual_findings: vec![],
+            redacted_regions: vec![],
+            unchanged: false,
+            created_at,
+        };
+        cache
+            .put(
+                "expired",
+                &entry(Utc::now() - chrono::Duration::hours(1)),
+                b"a",
+            )
+            .await?;
+        cache
+            .put(
+                "old",
+                &entry(Utc::now() - chrono::Duration::seconds(10)),
+                &[0; 600],
+            )
+            .await?;
+        cache.put("new", &entry(Utc::now()), &[0; 600]).await?;
+
+        let input = bytes::Bytes::new();
+        assert!(cache.get("expired", &input).await?.is_none());
+        assert!(cache.get("old", &input).await?.is_none());
+        let cached = cache.get("new", &input).await?.expect("cached entry");
+        assert_eq!(cached.number_of_redactions, 1);
+        assert_eq!(cached.applied_redacters, vec!["gcp-dlp".to_string()]);
+        let cached_data: Vec<bytes::Bytes> = cached.stream.try_collect().await?;
+        assert_eq!(cached_data.concat().len(), 600);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unchanged_content_test() -> AppResult<()> {
+        #[derive(clap::Parser)]
+        struct TestArgs {
+            #[command(flatten)]
+            redacter_args: crate::args::RedacterArgs,
+        }
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let temp_dir = tempfile::tempdir()?;
+        let test_args = <TestArgs as clap::Parser>::try_parse_from([
+            "redacter",
+            "-d",
+            "mock",
+            "--mock-pattern",
+            "John",
+        ])
+        .map_err(|err| crate::errors::AppError::SystemError {
+            message: err.to_string(),
+        })?;
+        let options: RedacterOptions = test_args.redacter_args.try_into()?;
+        let mut redacters = Vec::new();
+        for provider_options in options.provider_options {
+            redacters.push(Redacters::new_redacter(provider_options, &reporter).await?);
+        }
+        let file_converters = FileConverters::new();
+        let stream_redacter = StreamRedacter::new(
+            &options.base_options,
+            &file_converters,
+            &reporter,
+            None,
+            None,
+            None,
+        );
+        let cache = RedactionCache::new(
+            RedactionCacheOptions {
+                cache_dir: temp_dir.path().to_path_buf(),
+                ttl: Duration::from_secs(60),
+                max_size: None,
+                ocr: false,
+            },
+            "mock",
+            &reporter,
+        )
+        .await?;
+        let file_ref = FileSystemRef {
+            relative_path: "clean.txt".into(),
+            media_type: Some(mime::TEXT_PLAIN),
+            file_size: Some(11),
+            modified_at: None,
+            checksum: None,
+        };
+        let clean = bytes::Bytes::from_static(b"Hello, Jane");
+        let cache_hits = AppMetrics::global().total(AppMetric::CACHE_HITS);
+        for _ in 0..2 {
+            let redact_plan = stream_redacter
+                .create_redact_plan(&redacters, &file_ref)
+                .await?;
+            let result = cache
+                .redact_stream(
+                    &stream_redacter,
+                    futures::stream::iter(vec![Ok(clean.clone())]),
+                    redact_plan,
+                    &file_ref,
+                )
+                .await?;
+            let redacted: Vec<bytes::Bytes> = result.stream.try_collect().await?;
+            assert_eq!(redacted.concat(), clean);
+        }
+
+        assert!(AppMetrics::global().total(AppMetric::CACHE_HITS) >= cache_hits + 1.0);
+        let key = cache.key(&file_ref, &clean);
+        assert!(cache.get(&key, &clean).await?.is_some());
+        assert!(!temp_dir.path().join(format!("{}.bin", key)).exists());
+        Ok(())
+    }
+}
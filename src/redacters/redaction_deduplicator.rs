@@ -0,0 +1,216 @@
+use crate::file_systems::{FileChecksum, FileSystemRef};
+use crate::metrics::{AppMetric, AppMetrics};
+use crate::redacters::{
+    RedactFinding, RedactStreamResult, RedactedImageRegion, RedactionCache, StreamRedactPlan,
+    StreamRedacter,
+};
+use crate::reporter::AppReporter;
+use crate::AppResult;
+use console::Style;
+use futures::{Stream, TryStreamExt};
+use rvstruct::ValueStruct;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Listed files with the same size and checksum (if reported by the file system) might be identical
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct DuplicateCandidateKey {
+    file_size: usize,
+    checksum: Option<FileChecksum>,
+}
+
+struct DeduplicatedResult {
+    candidate_key: DuplicateCandidateKey,
+    relative_path: String,
+    number_of_redactions: usize,
+    applied_redacters: Vec<String>,
+    residual_findings: Vec<RedactFinding>,
+    redacted_regions: Vec<RedactedImageRegion>,
+    data: bytes::Bytes,
+}
+
+/// Redacts identical files within a run only once, reusing the redacted content for their duplicates.
+/// Duplicate candidates are detected by sizes and checksums of listed files and confirmed by content hashes,
+/// so only redacted content of candidates is kept in memory, until all candidates of the same size are processed.
+pub struct RedactionDeduplicator<'a> {
+    remaining_candidates: HashMap<DuplicateCandidateKey, usize>,
+    redacted_results: HashMap<String, DeduplicatedResult>,
+    reporter: &'a AppReporter<'a>,
+}
+
+impl<'a> RedactionDeduplicator<'a> {
+    pub fn new(files: &[FileSystemRef], reporter: &'a AppReporter<'a>) -> Self {
+        let mut remaining_candidates: HashMap<DuplicateCandidateKey, usize> = HashMap::new();
+        for file_ref in files {
+            if let Some(candidate_key) = Self::candidate_key(file_ref) {
+                *remaining_candidates.entry(candidate_key).or_default() += 1;
+            }
+        }
+        remaining_candidates.retain(|_, count| *count > 1);
+        Self {
+            remaining_candidates,
+            redacted_results: HashMap::new(),
+            reporter,
+        }
+    }
+
+    pub fn number_of_candidates(&self) -> usize {
+        self.remaining_candidates.values().sum()
+    }
+
+    pub fn is_duplicate_candidate(&self, file_ref: &FileSystemRef) -> bool {
+        Self::candidate_key(file_ref)
+            .is_some_and(|candidate_key| self.remaining_candidates.contains_key(&candidate_key))
+    }
+
+    /// Releases redacted content once all candidates with the same size and checksum are processed
+    pub fn file_processed(&mut self, file_ref: &FileSystemRef) {
+        let Some(candidate_key) = Self::candidate_key(file_ref) else {
+            return;
+        };
+        if let Some(count) = self.remaining_candidates.get_mut(&candidate_key) {
+            *count -= 1;
+            if *count == 0 {
+                self.remaining_candidates.remove(&candidate_key);
+                self.redacted_results
+                    .retain(|_, result| result.candidate_key != candidate_key);
+            }
+        }
+    }
+
+    /// Redacts the content of the listed source file, unless an identical file was already redacted
+    pub async fn redact_stream<
+        'r,
+        S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static,
+    >(
+        &mut self,
+        source_file_ref: &FileSystemRef,
+        stream_redacter: &'r StreamRedacter<'r>,
+        redaction_cache: Option<&RedactionCache<'_>>,
+        input: S,
+        redact_plan: StreamRedactPlan<'r>,
+        file_ref: &FileSystemRef,
+    ) -> AppResult<RedactStreamResult> {
+        let all_chunks: Vec<bytes::Bytes> = input.try_collect().await?;
+        let data = bytes::Bytes::from(all_chunks.concat());
+        let key = Self::content_key(file_ref, &data);
+
+        if let Some(result) = self.redacted_results.get(&key) {
+            self.reporter.report(format!(
+                "↳ Reusing redaction result of identical file {}",
+                Style::new().bold().apply_to(&result.relative_path)
+            ))?;
+            AppMetrics::global().inc(AppMetric::DEDUPLICATED_FILES, &[]);
+            return Ok(RedactStreamResult {
+                number_of_redactions: result.number_of_redactions,
+                applied_redacters: result.applied_redacters.clone(),
+                residual_findings: result.residual_findings.clone(),
+                redacted_regions: result.redacted_regions.clone(),
+                stream: Box::new(futures::stream::iter(vec![Ok(result.data.clone())])),
+            });
+        }
+
+        let input = futures::stream::iter(vec![Ok(data)]);
+        let redacted_result = match redaction_cache {
+            Some(redaction_cache) => {
+                redaction_cache
+                    .redact_stream(stream_redacter, input, redact_plan, file_ref)
+                    .await?
+            }
+            None => {
+                stream_redacter
+                    .redact_stream(input, redact_plan, file_ref)
+                    .await?
+            }
+        };
+        let Some(candidate_key) = Self::candidate_key(source_file_ref) else {
+            return Ok(redacted_result);
+        };
+        let redacted_chunks: Vec<bytes::Bytes> = redacted_result.stream.try_collect().await?;
+        let redacted_data = bytes::Bytes::from(redacted_chunks.concat());
+        self.redacted_results.insert(
+            key,
+            DeduplicatedResult {
+                candidate_key,
+                relative_path: source_file_ref.relative_path.value().clone(),
+                number_of_redactions: redacted_result.number_of_redactions,
+                applied_redacters: redacted_result.applied_redacters.clone(),
+                residual_findings: redacted_result.residual_findings.clone(),
+                redacted_regions: redacted_result.redacted_regions.clone(),
+                data: redacted_data.clone(),
+            },
+        );
+        Ok(RedactStreamResult {
+            number_of_redactions: redacted_result.number_of_redactions,
+            applied_redacters: redacted_result.applied_redacters,
+            residual_findings: redacted_result.residual_findings,
+            redacted_regions: redacted_result.redacted_regions,
+            stream: Box::new(futures::stream::iter(vec![Ok(redacted_data)])),
+        })
+    }
+
+    fn candidate_key(file_ref: &FileSystemRef) -> Option<DuplicateCandidateKey> {
+        file_ref.file_size.map(|file_size| DuplicateCandidateKey {
+            file_size,
+            checksum: file_ref.checksum.clone(),
+        })
+    }
+
+    fn content_key(file_ref: &FileSystemRef, data: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(
+            file_ref
+                .media_type
+                .as_ref()
+                .map(|media_type| media_type.to_string())
+                .unwrap_or_default()
+                .as_bytes(),
+        );
+        hasher.update(data);
+        hex::encode(hasher.finalize())
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use console::Term;
+
+    #[test]
+    fn duplicate_candidates_test() {
+        let term = Term::stdout();
+        let reporter: AppReporter = AppReporter::from(&term);
+        let test_file_ref = |path: &str, file_size: usize, checksum: Option<&str>| FileSystemRef {
+            relative_path: path.into(),
+            media_type: Some(mime::TEXT_PLAIN),
+            file_size: Some(file_size),
+            modified_at: None,
+            checksum: checksum.map(|checksum| FileChecksum::Md5(checksum.to_string())),
+        };
+        let first = test_file_ref("a.txt", 10, Some("aa"));
+        let duplicate = test_file_ref("b.txt", 10, Some("aa"));
+        let same_size = test_file_ref("c.txt", 10, Some("bb"));
+        let unique = test_file_ref("d.txt", 20, None);
+        let mut deduplicator = RedactionDeduplicator::new(
+            &[
+                first.clone(),
+                duplicate.clone(),
+                same_size.clone(),
+                unique.clone(),
+            ],
+            &reporter,
+        );
+
+        assert_eq!(deduplicator.number_of_candidates(), 2);
+        assert!(deduplicator.is_duplicate_candidate(&first));
+        assert!(deduplicator.is_duplicate_candidate(&duplicate));
+        assert!(!deduplicator.is_duplicate_candidate(&same_size));
+        assert!(!deduplicator.is_duplicate_candidate(&unique));
+
+        deduplicator.file_processed(&first);
+        assert!(deduplicator.is_duplicate_candidate(&duplicate));
+        deduplicator.file_processed(&duplicate);
+        assert!(!deduplicator.is_duplicate_candidate(&duplicate));
+        assert_eq!(deduplicator.number_of_candidates(), 0);
+    }
+}
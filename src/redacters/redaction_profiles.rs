@@ -0,0 +1,222 @@
+use crate::args::RedacterType;
+use crate::errors::AppError;
+use crate::redacters::{
+    GcpDlpTransformation, ImageRedactionStyle, IpAnonymization, MacAnonymization, RedactEntityType,
+};
+use crate::AppResult;
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Named set of redaction options, so users select a profile such as `hipaa` instead of assembling
+/// many options for every run. Options specified explicitly take precedence over profiles.
+/// Values use the same names as the command line options, such as `person-name` or `gcp-dlp`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RedactionProfile {
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_value_enums")]
+    pub redact: Option<Vec<RedacterType>>,
+    #[serde(default, deserialize_with = "deserialize_value_enums")]
+    pub redact_entity_types: Option<Vec<RedactEntityType>>,
+    #[serde(default, deserialize_with = "deserialize_value_enums")]
+    pub ignore_entity_types: Option<Vec<RedactEntityType>>,
+    pub ms_presidio_score_threshold: Option<f64>,
+    pub secrets_min_entropy: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_value_enum")]
+    pub gcp_dlp_transformation: Option<GcpDlpTransformation>,
+    #[serde(default, deserialize_with = "deserialize_value_enum")]
+    pub image_redaction_style: Option<ImageRedactionStyle>,
+    #[serde(default, deserialize_with = "deserialize_value_enum")]
+    pub network_ip_anonymization: Option<IpAnonymization>,
+    #[serde(default, deserialize_with = "deserialize_value_enum")]
+    pub network_mac_anonymization: Option<MacAnonymization>,
+    pub redact_faces: Option<bool>,
+}
+
+impl RedactionProfile {
+    pub const BUILT_IN_NAMES: [&'static str; 3] = ["hipaa", "pci", "gdpr-basic"];
+
+    /// Profiles shipped with the tool. They use GCP DLP unless other redacters are specified.
+    pub fn built_in(name: &str) -> Option<Self> {
+        match name {
+            "hipaa" => Some(Self {
+                description: Some("HIPAA Safe Harbor identifiers of patients".to_string()),
+                redact: Some(vec![RedacterType::GcpDlp]),
+                redact_entity_types: Some(vec![
+                    RedactEntityType::PersonName,
+                    RedactEntityType::EmailAddress,
+                    RedactEntityType::PhoneNumber,
+                    RedactEntityType::Location,
+                    RedactEntityType::Age,
+                    RedactEntityType::DateOfBirth,
+                    RedactEntityType::DateTime,
+                    RedactEntityType::IpAddress,
+                    RedactEntityType::MacAddress,
+                    RedactEntityType::Url,
+                    RedactEntityType::FinancialAccount,
+                    RedactEntityType::Passport,
+                    RedactEntityType::DriverLicense,
+                    RedactEntityType::NationalId,
+                ]),
+                // Lower thresholds, since missed identifiers are worse than redacted extra text
+                ms_presidio_score_threshold: Some(0.3),
+                image_redaction_style: Some(ImageRedactionStyle::Solid),
+                network_ip_anonymization: Some(IpAnonymization::Mask),
+                network_mac_anonymization: Some(MacAnonymization::Mask),
+                redact_faces: Some(true),
+                ..Self::default()
+            }),
+            "pci" => Some(Self {
+                description: Some("PCI DSS cardholder data and credentials".to_string()),
+                redact: Some(vec![RedacterType::GcpDlp, RedacterType::Secrets]),
+                redact_entity_types: Some(vec![
+                    RedactEntityType::CreditCardNumber,
+                    RedactEntityType::FinancialAccount,
+                    RedactEntityType::PersonName,
+                    RedactEntityType::Credentials,
+                ]),
+                ms_presidio_score_threshold: Some(0.5),
+                image_redaction_style: Some(ImageRedactionStyle::Solid),
+                ..Self::default()
+            }),
+            "gdpr-basic" => Some(Self {
+                description: Some(
+                    "Basic GDPR personal data with pseudonymized network identifiers".to_string(),
+                ),
+                redact: Some(vec![RedacterType::GcpDlp]),
+                redact_entity_types: Some(vec![
+                    RedactEntityType::PersonName,
+                    RedactEntityType::EmailAddress,
+                    RedactEntityType::PhoneNumber,
+                    RedactEntityType::Location,
+                    RedactEntityType::DateOfBirth,
+                    RedactEntityType::IpAddress,
+                    RedactEntityType::MacAddress,
+                    RedactEntityType::Passport,
+                    RedactEntityType::DriverLicense,
+                    RedactEntityType::NationalId,
+                ]),
+                ms_presidio_score_threshold: Some(0.5),
+                image_redaction_style: Some(ImageRedactionStyle::Blur),
+                network_ip_anonymization: Some(IpAnonymization::Truncate),
+                network_mac_anonymization: Some(MacAnonymization::Oui),
+                ..Self::default()
+            }),
+            _ => None,
+        }
+    }
+
+    /// Finds profiles in the JSON file of profiles by names first, so the built-in profiles can be overridden
+    pub async fn find(name: &str, profiles_file: Option<&Path>) -> AppResult<Self> {
+        if let Some(profiles_file) = profiles_file {
+            let mut profiles: HashMap<String, RedactionProfile> =
+                serde_json::from_slice(&tokio::fs::read(profiles_file).await?).map_err(|err| {
+                    AppError::ConfigError {
+                        message: format!(
+                            "Invalid profiles file {}: {}",
+                            profiles_file.display(),
+                            err
+                        ),
+                    }
+                })?;
+            if let Some(profile) = profiles.remove(name) {
+                return Ok(profile);
+            }
+        }
+        Self::built_in(name).ok_or_else(|| AppError::ConfigError {
+            message: format!(
+                "Unknown redaction profile '{}'. Built-in profiles: {}",
+                name,
+                Self::BUILT_IN_NAMES.join(", ")
+            ),
+        })
+    }
+}
+
+fn parse_value_enum<T: ValueEnum, E: serde::de::Error>(value: &str) -> Result<T, E> {
+    T::from_str(value, false).map_err(|_| {
+        E::custom(format!(
+            "unknown value '{}', possible values: {}",
+            value,
+            T::value_variants()
+                .iter()
+                .filter_map(|variant| variant.to_possible_value())
+                .map(|possible_value| possible_value.get_name().to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ))
+    })
+}
+
+fn deserialize_value_enum<'de, D: Deserializer<'de>, T: ValueEnum>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| parse_value_enum(&value))
+        .transpose()
+}
+
+fn deserialize_value_enums<'de, D: Deserializer<'de>, T: ValueEnum>(
+    deserializer: D,
+) -> Result<Option<Vec<T>>, D::Error> {
+    Option::<Vec<String>>::deserialize(deserializer)?
+        .map(|values| values.iter().map(|value| parse_value_enum(value)).collect())
+        .transpose()
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn find_redaction_profiles_test() -> AppResult<()> {
+        for name in RedactionProfile::BUILT_IN_NAMES {
+            assert!(RedactionProfile::find(name, None).await?.redact.is_some());
+        }
+        assert!(RedactionProfile::find("sox", None).await.is_err());
+
+        let temp_dir = tempfile::tempdir()?;
+        let profiles_file = temp_dir.path().join("profiles.json");
+        tokio::fs::write(
+            &profiles_file,
+            r#"{
+                "hipaa": { "redact": ["ms-presidio"], "ms-presidio-score-threshold": 0.2 },
+                "hr": {
+                    "redact": ["dictionary", "secrets"],
+                    "redact-entity-types": ["person-name", "national-id"],
+                    "network-ip-anonymization": "hash"
+                }
+            }"#,
+        )
+        .await?;
+        let hipaa = RedactionProfile::find("hipaa", Some(&profiles_file)).await?;
+        assert_eq!(hipaa.redact, Some(vec![RedacterType::MsPresidio]));
+        assert_eq!(hipaa.ms_presidio_score_threshold, Some(0.2));
+        assert_eq!(hipaa.redact_entity_types, None);
+        let hr = RedactionProfile::find("hr", Some(&profiles_file)).await?;
+        assert_eq!(
+            hr.redact_entity_types,
+            Some(vec![
+                RedactEntityType::PersonName,
+                RedactEntityType::NationalId
+            ])
+        );
+        assert_eq!(hr.network_ip_anonymization, Some(IpAnonymization::Hash));
+        assert!(RedactionProfile::find("pci", Some(&profiles_file))
+            .await?
+            .redact_entity_types
+            .is_some());
+
+        tokio::fs::write(
+            &profiles_file,
+            r#"{ "hr": { "redact-entity-types": ["salary"] } }"#,
+        )
+        .await?;
+        assert!(RedactionProfile::find("hr", Some(&profiles_file))
+            .await
+            .is_err());
+        Ok(())
+    }
+}
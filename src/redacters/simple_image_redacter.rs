@@ -1,43 +1,481 @@
-use crate::common_types::TextImageCoords;
+use crate::common_types::{ImageRedactionColor, TextImageCoords};
 use crate::errors::AppError;
 use crate::AppResult;
 use bytes::Bytes;
-use image::{ImageFormat, RgbImage};
+use clap::ValueEnum;
+use image::{DynamicImage, ImageFormat, RgbImage};
 use mime::Mime;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ImageRedactionStyle {
+    #[default]
+    Solid,
+    Blur,
+    Pixelate,
+}
+
+/// How regions with sensitive data are obfuscated in images,
+/// applied the same way by all redacters and OCR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageRedactionOptions {
+    pub style: ImageRedactionStyle,
+    /// Color of solid regions
+    pub color: ImageRedactionColor,
+    /// Padding added to every side of text regions as a percentage of their heights,
+    /// since heights of text regions approximate font sizes
+    pub padding_percent: u8,
+}
+
+impl ImageRedactionOptions {
+    pub const DEFAULT_PADDING_PERCENT: u8 = 20;
+    const BLUR_SIGMA: f32 = 16.0;
+    const PIXELATE_BLOCK_SIZE: u32 = 16;
+
+    /// Regions such as faces aren't text, so they are redacted as detected
+    pub fn without_padding(&self) -> Self {
+        Self {
+            padding_percent: 0,
+            ..*self
+        }
+    }
+}
+
+impl Default for ImageRedactionOptions {
+    fn default() -> Self {
+        Self {
+            style: ImageRedactionStyle::default(),
+            color: ImageRedactionColor::default(),
+            padding_percent: Self::DEFAULT_PADDING_PERCENT,
+        }
+    }
+}
+
+/// Copy of an image resized for analysis by providers limiting sizes of images such as LLMs.
+/// Coordinates of regions found in the copy are mapped back to the original image, so the original
+/// image is redacted at its original resolution instead of being replaced by the resized copy.
+pub struct ResizedImage {
+    pub data: Vec<u8>,
+    width: u32,
+    height: u32,
+    original_width: u32,
+    original_height: u32,
+}
+
+impl ResizedImage {
+    pub const LLM_MAX_SIZE: u32 = 1024;
+
+    /// Resizes the image to fit the max size keeping the aspect ratio
+    pub fn new(image: &DynamicImage, image_format: ImageFormat, max_size: u32) -> AppResult<Self> {
+        let resized_image = image.resize(max_size, max_size, image::imageops::FilterType::Gaussian);
+        let mut resized_image_bytes = std::io::Cursor::new(Vec::new());
+        resized_image.write_to(&mut resized_image_bytes, image_format)?;
+        Ok(Self {
+            data: resized_image_bytes.into_inner(),
+            width: resized_image.width(),
+            height: resized_image.height(),
+            original_width: image.width(),
+            original_height: image.height(),
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Maps coordinates of regions in the resized image to the original image
+    pub fn to_original_coords(&self, coords: Vec<TextImageCoords>) -> Vec<TextImageCoords> {
+        if self.width == 0 || self.height == 0 {
+            return coords;
+        }
+        let scale_x = self.original_width as f32 / self.width as f32;
+        let scale_y = self.original_height as f32 / self.height as f32;
+        coords
+            .into_iter()
+            .map(|coords| TextImageCoords {
+                x1: coords.x1 * scale_x,
+                y1: coords.y1 * scale_y,
+                x2: coords.x2 * scale_x,
+                y2: coords.y2 * scale_y,
+                text: coords.text,
+            })
+            .collect()
+    }
+
+    /// Images redacted natively by providers are returned in the resized resolution,
+    /// so they are resized back to the dimensions of the original image
+    pub fn to_original_size(
+        &self,
+        redacted_data: &[u8],
+        image_format: ImageFormat,
+    ) -> AppResult<Bytes> {
+        let redacted_image = image::load_from_memory(redacted_data)?;
+        let redacted_image = if redacted_image.width() == self.original_width
+            && redacted_image.height() == self.original_height
+        {
+            redacted_image
+        } else {
+            redacted_image.resize_exact(
+                self.original_width,
+                self.original_height,
+                image::imageops::FilterType::Gaussian,
+            )
+        };
+        let mut output = std::io::Cursor::new(Vec::new());
+        redacted_image.write_to(&mut output, image_format)?;
+        Ok(output.into_inner().into())
+    }
+}
+
+/// Regions (x1, y1, x2, y2) with exclusive ends, limited by the image size
+type ImageRegion = (u32, u32, u32, u32);
+
 pub fn redact_image_at_coords(
     mime: Mime,
     data: Bytes,
     pii_coords: Vec<TextImageCoords>,
-    approximation_factor: f32,
+    image_redaction_options: &ImageRedactionOptions,
 ) -> AppResult<Bytes> {
     let image_format = ImageFormat::from_mime_type(&mime).ok_or_else(|| AppError::SystemError {
         message: format!("Unsupported image mime type: {}", mime),
     })?;
     let image = image::load_from_memory_with_format(&data, image_format)?;
     let mut image = image.to_rgb8();
-    redact_rgba_image_at_coords(&mut image, &pii_coords, approximation_factor);
+    redact_rgba_image_at_coords(&mut image, &pii_coords, image_redaction_options);
     let mut output = std::io::Cursor::new(Vec::new());
     image.write_to(&mut output, image_format)?;
     Ok(output.into_inner().into())
 }
 
+/// Redacts regions at coordinates with padding proportional to their heights,
+/// so small text isn't over-masked and large headlines aren't under-masked
 pub fn redact_rgba_image_at_coords(
     image: &mut RgbImage,
-    pii_coords: &Vec<TextImageCoords>,
-    approximation_factor: f32,
+    pii_coords: &[TextImageCoords],
+    image_redaction_options: &ImageRedactionOptions,
 ) {
-    for TextImageCoords { x1, y1, x2, y2, .. } in pii_coords {
-        for x in
-            ((x1 - x1 * approximation_factor) as u32)..((x2 + x2 * approximation_factor) as u32)
-        {
-            for y in
-                ((y1 - y1 * approximation_factor) as u32)..((y2 + y2 * approximation_factor) as u32)
-            {
-                let safe_x = x.min(image.width() - 1);
-                let safe_y = y.min(image.height() - 1);
-                image.put_pixel(safe_x, safe_y, image::Rgb([0, 0, 0]));
+    if image.width() == 0 || image.height() == 0 {
+        return;
+    }
+    let padding_ratio = image_redaction_options.padding_percent as f32 / 100.0;
+    let regions: Vec<ImageRegion> = pii_coords
+        .iter()
+        .map(|TextImageCoords { x1, y1, x2, y2, .. }| {
+            let padding = ((y2 - y1).abs() * padding_ratio).ceil();
+            (
+                ((x1 - padding).max(0.0) as u32).min(image.width() - 1),
+                ((y1 - padding).max(0.0) as u32).min(image.height() - 1),
+                ((x2 + padding).max(0.0).ceil() as u32).min(image.width()),
+                ((y2 + padding).max(0.0).ceil() as u32).min(image.height()),
+            )
+        })
+        .filter(|(x1, y1, x2, y2)| x1 < x2 && y1 < y2)
+        .collect();
+    redact_image_regions(image, &regions, image_redaction_options);
+}
+
+/// Redacts the pixels changed by an external redacter filling regions with the specified color,
+/// since such redacters (such as MsPresidio) don't report the coordinates of regions.
+pub fn redact_image_as_filled(
+    mime: Mime,
+    original_data: Bytes,
+    filled_data: Bytes,
+    fill_color: ImageRedactionColor,
+    image_redaction_options: &ImageRedactionOptions,
+) -> AppResult<Bytes> {
+    // Lossy formats don't preserve the fill color precisely
+    const COLOR_TOLERANCE: i32 = 48;
+    let image_format = ImageFormat::from_mime_type(&mime).ok_or_else(|| AppError::SystemError {
+        message: format!("Unsupported image mime type: {}", mime),
+    })?;
+    let mut image = image::load_from_memory_with_format(&original_data, image_format)?.to_rgb8();
+    let filled_image = image::load_from_memory_with_format(&filled_data, image_format)?.to_rgb8();
+    if image.dimensions() != filled_image.dimensions() {
+        return Err(AppError::SystemError {
+            message: "Redacted image dimensions are different from the original image".to_string(),
+        });
+    }
+    let is_fill_color = |pixel: &image::Rgb<u8>| {
+        pixel
+            .0
+            .iter()
+            .zip(fill_color.to_rgb().0.iter())
+            .all(|(a, b)| (*a as i32 - *b as i32).abs() <= COLOR_TOLERANCE)
+    };
+    // Every filled row segment is a region of one pixel height
+    let mut regions: Vec<ImageRegion> = Vec::new();
+    for y in 0..image.height() {
+        let mut segment_start: Option<u32> = None;
+        for x in 0..=image.width() {
+            let filled = x < image.width()
+                && is_fill_color(filled_image.get_pixel(x, y))
+                && !is_fill_color(image.get_pixel(x, y));
+            match (filled, segment_start) {
+                (true, None) => segment_start = Some(x),
+                (false, Some(start)) => {
+                    regions.push((start, y, x, y + 1));
+                    segment_start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    redact_image_regions(&mut image, &regions, image_redaction_options);
+    let mut output = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut output, image_format)?;
+    Ok(output.into_inner().into())
+}
+
+fn redact_image_regions(
+    image: &mut RgbImage,
+    regions: &[ImageRegion],
+    image_redaction_options: &ImageRedactionOptions,
+) {
+    if regions.is_empty() {
+        return;
+    }
+    match image_redaction_options.style {
+        ImageRedactionStyle::Solid => {
+            let color = image_redaction_options.color.to_rgb();
+            for (x1, y1, x2, y2) in regions {
+                for x in *x1..*x2 {
+                    for y in *y1..*y2 {
+                        image.put_pixel(x, y, color);
+                    }
+                }
+            }
+        }
+        ImageRedactionStyle::Blur => {
+            // Blurring only the area around regions, since blurring large images is slow
+            let margin = (ImageRedactionOptions::BLUR_SIGMA * 3.0) as u32;
+            let area_x1 = regions.iter().map(|r| r.0).min().unwrap_or(0);
+            let area_y1 = regions.iter().map(|r| r.1).min().unwrap_or(0);
+            let area_x2 = regions.iter().map(|r| r.2).max().unwrap_or(0);
+            let area_y2 = regions.iter().map(|r| r.3).max().unwrap_or(0);
+            let area_x1 = area_x1.saturating_sub(margin);
+            let area_y1 = area_y1.saturating_sub(margin);
+            let area_x2 = (area_x2 + margin).min(image.width());
+            let area_y2 = (area_y2 + margin).min(image.height());
+            let area = image::imageops::crop_imm(
+                image,
+                area_x1,
+                area_y1,
+                area_x2 - area_x1,
+                area_y2 - area_y1,
+            )
+            .to_image();
+            let blurred = image::imageops::fast_blur(&area, ImageRedactionOptions::BLUR_SIGMA);
+            for (x1, y1, x2, y2) in regions {
+                for x in *x1..*x2 {
+                    for y in *y1..*y2 {
+                        image.put_pixel(x, y, *blurred.get_pixel(x - area_x1, y - area_y1));
+                    }
+                }
+            }
+        }
+        ImageRedactionStyle::Pixelate => {
+            let block_size = ImageRedactionOptions::PIXELATE_BLOCK_SIZE;
+            let original = image.clone();
+            let block_color = |block_x: u32, block_y: u32| {
+                let x2 = (block_x + block_size).min(original.width());
+                let y2 = (block_y + block_size).min(original.height());
+                let mut sum = [0u64; 3];
+                for x in block_x..x2 {
+                    for y in block_y..y2 {
+                        let pixel = original.get_pixel(x, y);
+                        for (channel, value) in sum.iter_mut().zip(pixel.0.iter()) {
+                            *channel += *value as u64;
+                        }
+                    }
+                }
+                let count = ((x2 - block_x) * (y2 - block_y)).max(1) as u64;
+                image::Rgb(sum.map(|channel| (channel / count) as u8))
+            };
+            // Blocks are aligned to the image grid, so adjacent regions are pixelated the same way
+            let mut block_colors = std::collections::HashMap::new();
+            for (x1, y1, x2, y2) in regions {
+                for x in *x1..*x2 {
+                    for y in *y1..*y2 {
+                        let block = (x - x % block_size, y - y % block_size);
+                        let color = *block_colors
+                            .entry(block)
+                            .or_insert_with(|| block_color(block.0, block.1));
+                        image.put_pixel(x, y, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_image_styles_test() {
+        let striped_image = || {
+            RgbImage::from_fn(64, 64, |x, _| {
+                if x % 2 == 0 {
+                    image::Rgb([255, 255, 255])
+                } else {
+                    image::Rgb([0, 0, 0])
+                }
+            })
+        };
+        let coords = vec![TextImageCoords {
+            x1: 16.0,
+            y1: 16.0,
+            x2: 48.0,
+            y2: 48.0,
+            text: None,
+        }];
+
+        let mut solid = striped_image();
+        redact_rgba_image_at_coords(
+            &mut solid,
+            &coords,
+            &ImageRedactionOptions {
+                style: ImageRedactionStyle::Solid,
+                color: "#ff0000".parse().unwrap(),
+                padding_percent: 0,
+            },
+        );
+        assert_eq!(solid.get_pixel(20, 20), &image::Rgb([255, 0, 0]));
+        assert_eq!(solid.get_pixel(0, 0), &image::Rgb([255, 255, 255]));
+
+        let mut pixelated = striped_image();
+        redact_rgba_image_at_coords(
+            &mut pixelated,
+            &coords,
+            &ImageRedactionOptions {
+                style: ImageRedactionStyle::Pixelate,
+                ..ImageRedactionOptions::default()
+            },
+        );
+        assert_eq!(pixelated.get_pixel(20, 20), &image::Rgb([127, 127, 127]));
+        assert_eq!(pixelated.get_pixel(21, 20), &image::Rgb([127, 127, 127]));
+        assert_eq!(pixelated.get_pixel(1, 1), &image::Rgb([0, 0, 0]));
+
+        let mut blurred = striped_image();
+        redact_rgba_image_at_coords(
+            &mut blurred,
+            &coords,
+            &ImageRedactionOptions {
+                style: ImageRedactionStyle::Blur,
+                ..ImageRedactionOptions::default()
+            },
+        );
+        let blurred_pixel = blurred.get_pixel(32, 32).0[0];
+        assert!(
+            blurred_pixel > 64 && blurred_pixel < 192,
+            "Stripes are blurred: {}",
+            blurred_pixel
+        );
+        assert_eq!(blurred.get_pixel(63, 63), &image::Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn redact_image_padding_test() {
+        let text_coords = |x1: f32, y1: f32, x2: f32, y2: f32| TextImageCoords {
+            x1,
+            y1,
+            x2,
+            y2,
+            text: None,
+        };
+        let options = ImageRedactionOptions {
+            color: "#ff0000".parse().unwrap(),
+            padding_percent: 25,
+            ..ImageRedactionOptions::default()
+        };
+        let red = image::Rgb([255, 0, 0]);
+        let mut image = RgbImage::from_pixel(400, 400, image::Rgb([255, 255, 255]));
+        redact_rgba_image_at_coords(
+            &mut image,
+            &[
+                // Small text far from the origin is padded by its own height only
+                text_coords(300.0, 300.0, 340.0, 308.0),
+                // Headline of 80 pixels is padded by 20 pixels
+                text_coords(40.0, 40.0, 200.0, 120.0),
+            ],
+            &options,
+        );
+        assert_eq!(image.get_pixel(298, 298), &red);
+        assert_ne!(image.get_pixel(297, 297), &red);
+        assert_eq!(image.get_pixel(341, 309), &red);
+        assert_ne!(image.get_pixel(342, 310), &red);
+        assert_eq!(image.get_pixel(20, 20), &red);
+        assert_eq!(image.get_pixel(219, 139), &red);
+        assert_ne!(image.get_pixel(19, 19), &red);
+        assert_ne!(image.get_pixel(220, 140), &red);
+
+        let mut unpadded = RgbImage::from_pixel(400, 400, image::Rgb([255, 255, 255]));
+        redact_rgba_image_at_coords(
+            &mut unpadded,
+            &[text_coords(40.0, 40.0, 200.0, 120.0)],
+            &options.without_padding(),
+        );
+        assert_eq!(unpadded.get_pixel(40, 40), &red);
+        assert_ne!(unpadded.get_pixel(39, 39), &red);
+    }
+
+    #[test]
+    fn resized_image_coords_test() -> AppResult<()> {
+        let image =
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(200, 150, image::Rgb([255, 255, 255])));
+        let resized_image = ResizedImage::new(&image, ImageFormat::Png, 100)?;
+        assert_eq!((resized_image.width(), resized_image.height()), (100, 75));
+
+        let original_coords = resized_image.to_original_coords(vec![TextImageCoords {
+            x1: 10.0,
+            y1: 5.0,
+            x2: 30.0,
+            y2: 6.0,
+            text: Some("John".to_string()),
+        }]);
+        assert_eq!(
+            original_coords
+                .iter()
+                .map(|coords| (coords.x1, coords.y1, coords.x2, coords.y2))
+                .collect::<Vec<_>>(),
+            vec![(20.0, 10.0, 60.0, 12.0)]
+        );
+        assert_eq!(original_coords[0].text.as_deref(), Some("John"));
+
+        let redacted = resized_image.to_original_size(&resized_image.data, ImageFormat::Png)?;
+        let redacted = image::load_from_memory(&redacted)?;
+        assert_eq!((redacted.width(), redacted.height()), (200, 150));
+        Ok(())
+    }
+
+    #[test]
+    fn redact_image_as_filled_test() -> Result<(), Box<dyn std::error::Error>> {
+        let original = RgbImage::from_pixel(32, 32, image::Rgb([200, 200, 200]));
+        let mut filled = original.clone();
+        for x in 8..16 {
+            for y in 8..16 {
+                filled.put_pixel(x, y, image::Rgb([255, 0, 255]));
             }
         }
+        let to_png = |image: &RgbImage| -> Result<Bytes, Box<dyn std::error::Error>> {
+            let mut output = std::io::Cursor::new(Vec::new());
+            image.write_to(&mut output, ImageFormat::Png)?;
+            Ok(output.into_inner().into())
+        };
+        let redacted = redact_image_as_filled(
+            mime::IMAGE_PNG,
+            to_png(&original)?,
+            to_png(&filled)?,
+            "#ff00ff".parse()?,
+            &ImageRedactionOptions::default(),
+        )?;
+        let redacted = image::load_from_memory(&redacted)?.to_rgb8();
+        assert_eq!(redacted.get_pixel(10, 10), &image::Rgb([0, 0, 0]));
+        assert_eq!(redacted.get_pixel(20, 20), &image::Rgb([200, 200, 200]));
+        Ok(())
     }
 }
@@ -1,46 +1,174 @@
+use crate::commands::diff_image_regions;
+use crate::common_types::TextImageCoords;
 use crate::errors::AppError;
+use crate::file_converters::audio::{WavAudio, WavAudioMask};
+use crate::file_converters::docx::DocxDocument;
 use crate::file_converters::ocr::Ocr;
+use crate::file_converters::odf::{OdfDocument, OdfDocumentType};
 use crate::file_converters::pdf::{PdfInfo, PdfPageInfo, PdfToImage};
+use crate::file_converters::pdf_a::{pdf_to_pdf_a, validate_pdf_a};
+use crate::file_converters::pptx::PptxDocument;
+use crate::file_converters::tiff_pages::{images_to_tiff, tiff_to_images};
+use crate::file_converters::xml_package::PackageImage;
 use crate::file_converters::FileConverters;
 use crate::file_systems::FileSystemRef;
+use crate::file_tools::CsvDialect;
 use crate::redacters::{
-    redact_rgba_image_at_coords, RedactSupport, Redacter, RedacterBaseOptions, RedacterDataItem,
-    RedacterDataItemContent, Redacters,
+    find_redacted_words, redact_rgba_image_at_coords, AudioRedactionOutput, AudioTranscriber,
+    FaceRedacter, LogFieldsProjection, NetworkIdAnonymizer, RedactFinding, RedactPolicy,
+    RedactSupport, Redacter, RedacterBaseOptions, RedacterDataItem, RedacterDataItemContent,
+    RedacterThrottler, RedacterVerifier, Redacters, TableColumnsProjection,
 };
+use crate::reporter::AppReporter;
 use crate::AppResult;
-use futures::{Stream, TryStreamExt};
-use image::ImageFormat;
-use indicatif::ProgressBar;
-use std::collections::HashSet;
+use futures::{Stream, StreamExt, TryStreamExt};
+use image::{DynamicImage, ImageFormat};
+use rvstruct::ValueStruct;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
 
 pub struct RedactStreamResult {
     pub number_of_redactions: usize,
+    /// Types of redacters applied to the content such as `gcp-dlp`
+    pub applied_redacters: Vec<String>,
+    /// Entity types still detected by the verification after redaction
+    pub residual_findings: Vec<RedactFinding>,
+    /// Redacted regions of images, and of pages of PDF and TIFF files
+    pub redacted_regions: Vec<RedactedImageRegion>,
     pub stream: Box<dyn Stream<Item = AppResult<bytes::Bytes>> + Send + Sync + Unpin + 'static>,
 }
 
+/// Bounding box of a redacted region in pixels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedactedImageRegion {
+    /// Zero-based index of the page of PDF and TIFF files, always 0 for other images
+    pub page: usize,
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+    /// SHA-256 hash of the original text found by OCR in the region, if text hashes are enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_hash: Option<String>,
+}
+
+impl RedactedImageRegion {
+    pub fn from_coords(page: usize, coords: &TextImageCoords, hash_text: bool) -> Self {
+        Self {
+            page,
+            x1: coords.x1,
+            y1: coords.y1,
+            x2: coords.x2,
+            y2: coords.y2,
+            text_hash: coords
+                .text
+                .as_ref()
+                .filter(|_| hash_text)
+                .map(|text| hex::encode(Sha256::digest(text.as_bytes()))),
+        }
+    }
+
+    /// Regions redacted by providers are found by comparing pages before and after redaction
+    pub fn changed_regions(
+        page: usize,
+        original: &DynamicImage,
+        redacted: &DynamicImage,
+    ) -> Vec<Self> {
+        let (regions, _) = diff_image_regions(original, redacted);
+        regions
+            .into_iter()
+            .map(|region| Self {
+                page,
+                x1: region.x as f32,
+                y1: region.y as f32,
+                x2: (region.x + region.width) as f32,
+                y2: (region.y + region.height) as f32,
+                text_hash: None,
+            })
+            .collect()
+    }
+}
+
 pub struct StreamRedacter<'a> {
     redacter_base_options: &'a RedacterBaseOptions,
     file_converters: &'a FileConverters<'a>,
-    bar: &'a ProgressBar,
+    reporter: &'a AppReporter<'a>,
+    verifier: Option<&'a RedacterVerifier<'a>>,
+    face_redacter: Option<&'a FaceRedacter<'a>>,
+    audio_transcriber: Option<&'a AudioTranscriber<'a>>,
+    request_throttler: Option<&'a Mutex<Option<RedacterThrottler>>>,
 }
 
+#[derive(Default)]
 pub struct StreamRedactPlan<'a> {
     pub apply_pdf_image_converter: bool,
+    pub apply_tiff_pages_converter: bool,
     pub apply_ocr: bool,
     pub leave_data_table_as_text: bool,
+    pub apply_face_redaction: bool,
+    pub apply_audio_transcription: bool,
+    pub apply_odf_converter: bool,
+    pub apply_pptx_converter: bool,
+    pub apply_docx_converter: bool,
     pub supported_redacters: Vec<&'a Redacters<'a>>,
 }
 
+impl StreamRedactPlan<'_> {
+    pub fn has_redactions(&self) -> bool {
+        !self.supported_redacters.is_empty() || self.apply_face_redaction
+    }
+
+    /// Conversions of files applied for the supported redacters such as `pdf-image` and `ocr`,
+    /// empty if files are supported natively
+    pub fn conversions(&self) -> Vec<&'static str> {
+        [
+            (self.apply_pdf_image_converter, "pdf-image"),
+            (self.apply_tiff_pages_converter, "tiff-pages"),
+            (self.apply_ocr, "ocr"),
+            (self.leave_data_table_as_text, "table-as-text"),
+            (self.apply_audio_transcription, "transcription"),
+            (self.apply_odf_converter, "odf"),
+            (self.apply_pptx_converter, "pptx"),
+            (self.apply_docx_converter, "docx"),
+        ]
+        .into_iter()
+        .filter_map(|(applied, conversion)| applied.then_some(conversion))
+        .collect()
+    }
+}
+
 impl<'a> StreamRedacter<'a> {
     pub fn new(
         redacter_base_options: &'a RedacterBaseOptions,
         file_converters: &'a FileConverters<'a>,
-        bar: &'a ProgressBar,
+        reporter: &'a AppReporter<'a>,
+        verifier: Option<&'a RedacterVerifier<'a>>,
+        face_redacter: Option<&'a FaceRedacter<'a>>,
+        audio_transcriber: Option<&'a AudioTranscriber<'a>>,
     ) -> Self {
         Self {
             redacter_base_options,
             file_converters,
-            bar,
+            reporter,
+            verifier,
+            face_redacter,
+            audio_transcriber,
+            request_throttler: None,
+        }
+    }
+
+    /// Pages of PDF and TIFF files are redacted with separate requests, so they are throttled
+    /// with the same throttler as files
+    pub fn with_request_throttler(
+        self,
+        request_throttler: &'a Mutex<Option<RedacterThrottler>>,
+    ) -> Self {
+        Self {
+            request_throttler: Some(request_throttler),
+            ..self
         }
     }
 
@@ -51,15 +179,27 @@ impl<'a> StreamRedacter<'a> {
     ) -> AppResult<StreamRedactPlan<'a>> {
         let mut stream_redact_plan = StreamRedactPlan {
             apply_pdf_image_converter: false,
+            apply_tiff_pages_converter: false,
             apply_ocr: false,
             leave_data_table_as_text: false,
+            apply_face_redaction: false,
+            apply_audio_transcription: false,
+            apply_odf_converter: false,
+            apply_pptx_converter: false,
+            apply_docx_converter: false,
             supported_redacters: vec![],
         };
-        // Supports natively
-        for redacter in redacters {
-            let supported_options = redacter.redact_support(file_ref).await?;
-            if supported_options == RedactSupport::Supported {
-                stream_redact_plan.supported_redacters.push(redacter);
+        // Supports natively, except multi-page TIFFs that are always redacted page by page
+        if !file_ref
+            .media_type
+            .as_ref()
+            .is_some_and(Redacters::is_mime_tiff)
+        {
+            for redacter in redacters {
+                let supported_options = redacter.redact_support(file_ref).await?;
+                if supported_options == RedactSupport::Supported {
+                    stream_redact_plan.supported_redacters.push(redacter);
+                }
             }
         }
 
@@ -81,6 +221,144 @@ impl<'a> StreamRedacter<'a> {
                     if !stream_redact_plan.supported_redacters.is_empty() {
                         stream_redact_plan.leave_data_table_as_text = true;
                     }
+                } else if let Some(document_type) = OdfDocumentType::from_mime(file_ref_media) {
+                    // Spreadsheets are redacted as tables if redacters support them, and as text otherwise
+                    if document_type == OdfDocumentType::Spreadsheet {
+                        for redacter in redacters {
+                            let supported_options = redacter
+                                .redact_support(&FileSystemRef {
+                                    media_type: Some(mime::TEXT_CSV),
+                                    ..file_ref.clone()
+                                })
+                                .await?;
+                            if supported_options == RedactSupport::Supported {
+                                stream_redact_plan.supported_redacters.push(redacter);
+                            }
+                        }
+                    }
+                    if stream_redact_plan.supported_redacters.is_empty() {
+                        for redacter in redacters {
+                            let supported_options = redacter
+                                .redact_support(&FileSystemRef {
+                                    media_type: Some(mime::TEXT_PLAIN),
+                                    ..file_ref.clone()
+                                })
+                                .await?;
+                            if supported_options == RedactSupport::Supported {
+                                stream_redact_plan.supported_redacters.push(redacter);
+                            }
+                        }
+                        stream_redact_plan.leave_data_table_as_text = document_type
+                            == OdfDocumentType::Spreadsheet
+                            && !stream_redact_plan.supported_redacters.is_empty();
+                    }
+                    if self.redacter_base_options.redact_embedded_images {
+                        // Embedded images are redacted natively or using OCR with text redacters
+                        for redacter in redacters {
+                            let supported_options = redacter
+                                .redact_support(&FileSystemRef {
+                                    media_type: Some(mime::IMAGE_PNG),
+                                    ..file_ref.clone()
+                                })
+                                .await?;
+                            if supported_options == RedactSupport::Supported
+                                && !stream_redact_plan
+                                    .supported_redacters
+                                    .iter()
+                                    .any(|supported| std::ptr::eq(*supported, redacter))
+                            {
+                                stream_redact_plan.supported_redacters.push(redacter);
+                            }
+                        }
+                    }
+                    stream_redact_plan.apply_odf_converter =
+                        !stream_redact_plan.supported_redacters.is_empty()
+                            || (self.redacter_base_options.redact_embedded_images
+                                && self.face_redacter.is_some());
+                } else if PptxDocument::is_mime_pptx(file_ref_media) {
+                    // Text of slides is redacted as text, and images of slides natively or using OCR
+                    for redacter in redacters {
+                        let supports_text = redacter
+                            .redact_support(&FileSystemRef {
+                                media_type: Some(mime::TEXT_PLAIN),
+                                ..file_ref.clone()
+                            })
+                            .await?
+                            == RedactSupport::Supported;
+                        let supports_images = redacter
+                            .redact_support(&FileSystemRef {
+                                media_type: Some(mime::IMAGE_PNG),
+                                ..file_ref.clone()
+                            })
+                            .await?
+                            == RedactSupport::Supported;
+                        if supports_text || supports_images {
+                            stream_redact_plan.supported_redacters.push(redacter);
+                        }
+                    }
+                    stream_redact_plan.apply_pptx_converter =
+                        !stream_redact_plan.supported_redacters.is_empty()
+                            || self.face_redacter.is_some();
+                } else if DocxDocument::is_mime_docx(file_ref_media) {
+                    // Text of Word documents is redacted as text, and embedded images natively
+                    // or using OCR with `--redact-embedded-images`
+                    for redacter in redacters {
+                        let supports_text = redacter
+                            .redact_support(&FileSystemRef {
+                                media_type: Some(mime::TEXT_PLAIN),
+                                ..file_ref.clone()
+                            })
+                            .await?
+                            == RedactSupport::Supported;
+                        let supports_images = self.redacter_base_options.redact_embedded_images
+                            && redacter
+                                .redact_support(&FileSystemRef {
+                                    media_type: Some(mime::IMAGE_PNG),
+                                    ..file_ref.clone()
+                                })
+                                .await?
+                                == RedactSupport::Supported;
+                        if supports_text || supports_images {
+                            stream_redact_plan.supported_redacters.push(redacter);
+                        }
+                    }
+                    stream_redact_plan.apply_docx_converter =
+                        !stream_redact_plan.supported_redacters.is_empty()
+                            || (self.redacter_base_options.redact_embedded_images
+                                && self.face_redacter.is_some());
+                } else if Redacters::is_mime_tiff(file_ref_media) {
+                    for redacter in redacters {
+                        let supported_options = redacter
+                            .redact_support(&FileSystemRef {
+                                media_type: Some(mime::IMAGE_PNG),
+                                ..file_ref.clone()
+                            })
+                            .await?;
+                        if supported_options == RedactSupport::Supported {
+                            stream_redact_plan.supported_redacters.push(redacter);
+                        }
+                    }
+
+                    if stream_redact_plan.supported_redacters.is_empty()
+                        && self.file_converters.ocr.is_some()
+                    {
+                        for redacter in redacters {
+                            let supported_options = redacter
+                                .redact_support(&FileSystemRef {
+                                    media_type: Some(mime::TEXT_PLAIN),
+                                    ..file_ref.clone()
+                                })
+                                .await?;
+                            if supported_options == RedactSupport::Supported {
+                                stream_redact_plan.supported_redacters.push(redacter);
+                            }
+                        }
+                        stream_redact_plan.apply_ocr =
+                            !stream_redact_plan.supported_redacters.is_empty();
+                    }
+
+                    stream_redact_plan.apply_tiff_pages_converter =
+                        !stream_redact_plan.supported_redacters.is_empty();
                 } else if self.file_converters.pdf_image_converter.is_some()
                     && Redacters::is_mime_pdf(file_ref_media)
                 {
@@ -136,14 +414,943 @@ impl<'a> StreamRedacter<'a> {
                     if !stream_redact_plan.supported_redacters.is_empty() {
                         stream_redact_plan.apply_ocr = true;
                     }
+                } else if self.is_audio_transcription_supported(file_ref_media) {
+                    for redacter in redacters {
+                        let supported_options = redacter
+                            .redact_support(&FileSystemRef {
+                                media_type: Some(mime::TEXT_PLAIN),
+                                ..file_ref.clone()
+                            })
+                            .await?;
+                        if supported_options == RedactSupport::Supported {
+                            stream_redact_plan.supported_redacters.push(redacter);
+                        }
+                    }
+                    stream_redact_plan.apply_audio_transcription =
+                        !stream_redact_plan.supported_redacters.is_empty();
+                }
+            }
+        }
+
+        if self.redacter_base_options.redact_policy == RedactPolicy::FirstSupported {
+            stream_redact_plan.supported_redacters.truncate(1);
+        }
+
+        if self.face_redacter.is_some() {
+            stream_redact_plan.apply_face_redaction =
+                file_ref.media_type.as_ref().is_some_and(|file_ref_media| {
+                    Redacters::is_mime_image(file_ref_media)
+                        || PptxDocument::is_mime_pptx(file_ref_media)
+                        || (self.redacter_base_options.redact_embedded_images
+                            && (OdfDocumentType::from_mime(file_ref_media).is_some()
+                                || DocxDocument::is_mime_docx(file_ref_media)))
+                        || (self.file_converters.pdf_image_converter.is_some()
+                            && Redacters::is_mime_pdf(file_ref_media))
+                });
+        }
+
+        Ok(stream_redact_plan)
+    }
+
+    /// Redacted words can be silenced or beeped only in WAV audio,
+    /// other audio formats are supported only with the transcript output.
+    fn is_audio_transcription_supported(&self, media_type: &mime::Mime) -> bool {
+        self.audio_transcriber.is_some_and(|audio_transcriber| {
+            Redacters::is_mime_wav(media_type)
+                || (Redacters::is_mime_audio(media_type)
+                    && audio_transcriber.audio_transcriber_options.output
+                        == AudioRedactionOutput::Transcript)
+        })
+    }
+
+    /// Redacted transcripts of audio files are written as text files with `.txt` appended to their file names.
+    pub fn audio_transcript_file_ref(
+        &self,
+        redact_plan: &StreamRedactPlan<'a>,
+        file_ref: &FileSystemRef,
+    ) -> Option<FileSystemRef> {
+        self.audio_transcriber.filter(|audio_transcriber| {
+            redact_plan.apply_audio_transcription
+                && audio_transcriber.audio_transcriber_options.output
+                    == AudioRedactionOutput::Transcript
+        })?;
+        Some(FileSystemRef {
+            relative_path: format!("{}.txt", file_ref.relative_path.value()).into(),
+            media_type: Some(mime::TEXT_PLAIN),
+            file_size: None,
+            ..file_ref.clone()
+        })
+    }
+
+    /// HEIF images (such as phone photos) aren't supported by redacters,
+    /// so they are redacted as JPEG images written with `.jpg` appended to their file names.
+    pub fn heif_converted_file_ref(&self, file_ref: &FileSystemRef) -> Option<FileSystemRef> {
+        self.file_converters.heif_image_converter.as_ref()?;
+        file_ref
+            .media_type
+            .as_ref()
+            .filter(|media_type| Redacters::is_mime_heif(media_type))?;
+        Some(FileSystemRef {
+            relative_path: format!("{}.jpg", file_ref.relative_path.value()).into(),
+            media_type: Some(mime::IMAGE_JPEG),
+            file_size: None,
+            ..file_ref.clone()
+        })
+    }
+
+    pub async fn convert_heif_to_jpeg<
+        S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static,
+    >(
+        &'a self,
+        input: S,
+    ) -> AppResult<bytes::Bytes> {
+        const JPEG_QUALITY: u8 = 90;
+        match self.file_converters.heif_image_converter {
+            Some(ref heif_image_converter) => {
+                let all_chunks: Vec<bytes::Bytes> = input.try_collect().await?;
+                let image = heif_image_converter.convert_to_image(&all_chunks.concat())?;
+                let mut output = std::io::Cursor::new(Vec::new());
+                image.to_rgb8().write_with_encoder(
+                    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY),
+                )?;
+                Ok(output.into_inner().into())
+            }
+            None => Err(AppError::SystemError {
+                message: "HEIF to image converter is not available".to_string(),
+            }),
+        }
+    }
+
+    pub async fn redact_stream<
+        S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static,
+    >(
+        &'a self,
+        input: S,
+        redact_plan: StreamRedactPlan<'a>,
+        file_ref: &FileSystemRef,
+    ) -> AppResult<RedactStreamResult> {
+        if redact_plan.apply_audio_transcription {
+            return self.redact_audio_stream(input, redact_plan, file_ref).await;
+        }
+        if redact_plan.apply_odf_converter {
+            return self.redact_odf_stream(input, redact_plan, file_ref).await;
+        }
+        if redact_plan.apply_pptx_converter {
+            return self.redact_pptx_stream(input, redact_plan, file_ref).await;
+        }
+        if redact_plan.apply_docx_converter {
+            return self.redact_docx_stream(input, redact_plan, file_ref).await;
+        }
+        let (redacted, csv_dialect, table_projection) = if self
+            .has_table_columns_selection(file_ref)
+        {
+            let (table_item, csv_dialect) = self
+                .stream_to_table_redact_item(self.redacter_base_options, input, file_ref)
+                .await?;
+            let (projected_item, table_projection) =
+                self.project_table_columns(table_item, &redact_plan).await?;
+            (projected_item, Some(csv_dialect), Some(table_projection))
+        } else {
+            let (item, csv_dialect) = self
+                .stream_to_redact_item(self.redacter_base_options, input, file_ref, &redact_plan)
+                .await?;
+            (item, csv_dialect, None)
+        };
+        let (redacted, log_projection) = self.project_log_fields(redacted)?;
+        // Addresses in logs are already anonymized with log fields
+        let (redacted, anonymized_network_ids) = match log_projection {
+            Some(_) => (redacted, 0),
+            None => self.anonymize_network_ids(redacted)?,
+        };
+        let supported_redacters = match (&table_projection, &log_projection) {
+            (Some(projection), _) if !projection.has_redact_columns() => {
+                self.reporter.report(
+                    "↲ Skipping redaction because no CSV columns are selected for redaction",
+                )?;
+                vec![]
+            }
+            (_, Some(projection)) if !projection.has_remnants() => {
+                self.reporter
+                    .report("↲ Skipping redaction by redacters because the log has no free text")?;
+                vec![]
+            }
+            _ => redact_plan.supported_redacters.clone(),
+        };
+
+        // Samples of projected logs would be copied instead of the logs
+        if log_projection.is_none() {
+            if let Some(sampled_result) = self
+                .redact_sample_then_full(&redacted, &supported_redacters, &redact_plan, file_ref)
+                .await?
+            {
+                return Ok(sampled_result);
+            }
+        }
+
+        let (redacted_item, mut number_of_redactions, applied_redacters, mut redacted_regions) =
+            self.redact_with_policy(redacted, &supported_redacters, &redact_plan, file_ref)
+                .await?;
+        let mut redacted = redacted_item;
+        if log_projection
+            .as_ref()
+            .is_some_and(|projection| projection.local_redactions() > 0)
+        {
+            number_of_redactions += 1;
+        }
+        if anonymized_network_ids > 0 {
+            number_of_redactions += 1;
+        }
+
+        if redact_plan.apply_face_redaction {
+            if let Some(face_redacter) = self.face_redacter {
+                let (redacted_item, face_regions) =
+                    self.redact_faces(redacted, face_redacter).await?;
+                redacted = redacted_item;
+                redacted_regions.extend(face_regions);
+                number_of_redactions += 1;
+            }
+        }
+
+        let residual_findings = self
+            .verify_redacted(&redacted, number_of_redactions)
+            .await?;
+
+        if let Some(projection) = table_projection {
+            redacted = self.merge_table_columns(redacted, projection).await?;
+        }
+        if let Some(projection) = log_projection {
+            redacted = Self::merge_log_fields(redacted, projection)?;
+        }
+
+        let output_stream = match redacted.content {
+            RedacterDataItemContent::Value(content) => {
+                let bytes = bytes::Bytes::from(content.into_bytes());
+                Box::new(futures::stream::iter(vec![Ok(bytes)]))
+            }
+            RedacterDataItemContent::Image { data, .. } => {
+                Box::new(futures::stream::iter(vec![Ok(data)]))
+            }
+            RedacterDataItemContent::Pdf { data } => {
+                Box::new(futures::stream::iter(vec![Ok(data)]))
+            }
+            RedacterDataItemContent::Table { headers, rows } => {
+                let mut writer = csv_dialect
+                    .unwrap_or_default()
+                    .writer_builder()
+                    .create_writer(vec![]);
+                if !headers.is_empty() {
+                    writer.write_record(headers).await?;
+                }
+                for row in rows {
+                    writer.write_record(row).await?;
+                }
+                writer.flush().await?;
+                let bytes = bytes::Bytes::from(writer.into_inner().await?);
+                Box::new(futures::stream::iter(vec![Ok(bytes)]))
+            }
+        };
+
+        Ok(RedactStreamResult {
+            number_of_redactions,
+            applied_redacters,
+            residual_findings,
+            redacted_regions,
+            stream: output_stream,
+        })
+    }
+
+    /// Redacts the item with supported redacters according to the redact policy, returning
+    /// the number of applied redacters, their types and redacted image regions
+    async fn redact_with_policy(
+        &'a self,
+        mut redacted: RedacterDataItem,
+        supported_redacters: &[&'a Redacters<'a>],
+        redact_plan: &StreamRedactPlan<'a>,
+        file_ref: &FileSystemRef,
+    ) -> AppResult<(
+        RedacterDataItem,
+        usize,
+        Vec<String>,
+        Vec<RedactedImageRegion>,
+    )> {
+        let mut number_of_redactions = 0;
+        let mut applied_redacters = Vec::new();
+        let mut redacted_regions = Vec::new();
+        match self.redacter_base_options.redact_policy {
+            RedactPolicy::FallbackOnError => {
+                let mut last_error = None;
+                for (index, redacter) in supported_redacters.iter().copied().enumerate() {
+                    let width = " ".repeat(index);
+                    match self
+                        .redact_with_redacter(
+                            redacted.clone(),
+                            redacter,
+                            redact_plan,
+                            file_ref,
+                            &width,
+                        )
+                        .await
+                    {
+                        Ok((redacted_item, applied, regions)) => {
+                            redacted = redacted_item;
+                            number_of_redactions += usize::from(applied);
+                            redacted_regions.extend(regions);
+                            if applied {
+                                applied_redacters.push(redacter.redacter_type().to_string());
+                            }
+                            last_error = None;
+                            break;
+                        }
+                        Err(err) => {
+                            self.reporter.report(format!(
+                                "{width}↲ Redacting using {} redacter failed, falling back to the next redacter: {}",
+                                redacter.redacter_type(),
+                                err
+                            ))?;
+                            last_error = Some(err);
+                        }
+                    }
+                }
+                if let Some(err) = last_error {
+                    return Err(err);
+                }
+            }
+            RedactPolicy::All | RedactPolicy::FirstSupported => {
+                for (index, redacter) in supported_redacters.iter().copied().enumerate() {
+                    let width = " ".repeat(index);
+                    let (redacted_item, applied, regions) = self
+                        .redact_with_redacter(redacted, redacter, redact_plan, file_ref, &width)
+                        .await?;
+                    redacted = redacted_item;
+                    number_of_redactions += usize::from(applied);
+                    redacted_regions.extend(regions);
+                    if applied {
+                        applied_redacters.push(redacter.redacter_type().to_string());
+                    }
+                }
+            }
+        }
+
+        Ok((
+            redacted,
+            number_of_redactions,
+            applied_redacters,
+            redacted_regions,
+        ))
+    }
+
+    /// Redacts a sample of the text with `--sample-then-full`, so whole texts are redacted only
+    /// if the sample has sensitive data. Returns the text as is if the sample is clean
+    async fn redact_sample_then_full(
+        &'a self,
+        item: &RedacterDataItem,
+        supported_redacters: &[&'a Redacters<'a>],
+        redact_plan: &StreamRedactPlan<'a>,
+        file_ref: &FileSystemRef,
+    ) -> AppResult<Option<RedactStreamResult>> {
+        let (sampling_size, content) =
+            match (self.redacter_base_options.sampling_size, &item.content) {
+                (Some(sampling_size), RedacterDataItemContent::Value(content))
+                    if self.redacter_base_options.sample_then_full
+                        && !supported_redacters.is_empty() =>
+                {
+                    (sampling_size, content)
+                }
+                _ => return Ok(None),
+            };
+        let sample = self
+            .redacter_base_options
+            .sampling_strategy
+            .sample(content, sampling_size);
+        if sample.len() == content.len() {
+            return Ok(None);
+        }
+        self.reporter.report(format!(
+            "↳ Redacting a sample of {} characters ({})",
+            sample.chars().count(),
+            self.redacter_base_options.sampling_strategy
+        ))?;
+        let sample_item = RedacterDataItem {
+            content: RedacterDataItemContent::Value(sample.clone()),
+            file_ref: file_ref.clone(),
+        };
+        let (redacted_sample, number_of_redactions, applied_redacters, _) = self
+            .redact_with_policy(sample_item, supported_redacters, redact_plan, file_ref)
+            .await?;
+        match redacted_sample.content {
+            RedacterDataItemContent::Value(redacted_sample) if redacted_sample == sample => {
+                self.reporter.report(
+                    "↲ Skipping redaction of the whole file because the sample has no sensitive data",
+                )?;
+                Ok(Some(RedactStreamResult {
+                    number_of_redactions,
+                    applied_redacters,
+                    residual_findings: vec![],
+                    redacted_regions: vec![],
+                    stream: Box::new(futures::stream::iter(vec![Ok(bytes::Bytes::from(
+                        content.clone().into_bytes(),
+                    ))])),
+                }))
+            }
+            _ => {
+                self.reporter
+                    .report("↳ Redacting the whole file because the sample has sensitive data")?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Redacts the item with the redacter using converters of the plan, returning redacted image regions.
+    /// Returns the item as is if required converters are not available
+    async fn redact_with_redacter(
+        &'a self,
+        redacted: RedacterDataItem,
+        redacter: &'a Redacters<'a>,
+        redact_plan: &StreamRedactPlan<'a>,
+        file_ref: &FileSystemRef,
+        width: &str,
+    ) -> AppResult<(RedacterDataItem, bool, Vec<RedactedImageRegion>)> {
+        if redact_plan.apply_pdf_image_converter {
+            match (
+                &self.file_converters.pdf_image_converter,
+                &self.file_converters.ocr,
+            ) {
+                (Some(ref pdf_to_image), _) if !redact_plan.apply_ocr => {
+                    let (redacted, regions) = self
+                        .redact_pdf_with_images_converter(
+                            file_ref,
+                            redacted,
+                            redacter,
+                            width,
+                            pdf_to_image.as_ref(),
+                            None,
+                        )
+                        .await?;
+                    return Ok((redacted, true, regions));
+                }
+                (Some(ref pdf_to_image), Some(ref ocr)) => {
+                    let (redacted, regions) = self
+                        .redact_pdf_with_images_converter(
+                            file_ref,
+                            redacted,
+                            redacter,
+                            width,
+                            pdf_to_image.as_ref(),
+                            Some(ocr.as_ref()),
+                        )
+                        .await?;
+                    return Ok((redacted, true, regions));
+                }
+                (None, Some(_)) => {
+                    self.reporter.report(format!(
+                        "{width}↲ Skipping redaction because PDF to image converter is not available",
+                    ))?;
+                }
+                (Some(_), None) => {
+                    self.reporter.report(format!(
+                        "{width}↲ Skipping redaction because OCR is not available",
+                    ))?;
+                }
+                (None, None) => {
+                    self.reporter.report(format!(
+                        "{width}↲ Skipping redaction because PDF/OCR are not available",
+                    ))?;
+                }
+            }
+        } else if redact_plan.apply_tiff_pages_converter {
+            let ocr = if redact_plan.apply_ocr {
+                self.file_converters.ocr.as_deref()
+            } else {
+                None
+            };
+            let (redacted, regions) = self
+                .redact_tiff_with_pages_converter(file_ref, redacted, redacter, width, ocr)
+                .await?;
+            return Ok((redacted, true, regions));
+        } else if redact_plan.apply_ocr {
+            match self.file_converters.ocr {
+                Some(ref ocr) => {
+                    let (redacted, redacted_coords) = self
+                        .redact_with_ocr_converter(
+                            file_ref,
+                            redacted,
+                            redacter,
+                            width,
+                            ocr.as_ref(),
+                        )
+                        .await?;
+                    return Ok((redacted, true, self.coords_to_regions(0, &redacted_coords)));
+                }
+                None => {
+                    self.reporter.report(format!(
+                        "{width}↲ Skipping redaction because OCR is not available",
+                    ))?;
+                }
+            }
+        } else {
+            self.reporter.report(format!(
+                "{width}↳ Redacting using {} redacter",
+                redacter.redacter_type()
+            ))?;
+            let original = redacted.clone();
+            let redacted = redacter.redact(redacted).await?;
+            let regions = Self::changed_item_regions(&original, &redacted);
+            return Ok((redacted, true, regions));
+        }
+        Ok((redacted, false, Vec::new()))
+    }
+
+    fn coords_to_regions(
+        &self,
+        page: usize,
+        coords: &[TextImageCoords],
+    ) -> Vec<RedactedImageRegion> {
+        coords
+            .iter()
+            .map(|coords| {
+                RedactedImageRegion::from_coords(
+                    page,
+                    coords,
+                    self.redacter_base_options.hash_redacted_text,
+                )
+            })
+            .collect()
+    }
+
+    /// Images which can't be decoded locally are redacted without reporting regions
+    fn changed_item_regions(
+        original: &RedacterDataItem,
+        redacted: &RedacterDataItem,
+    ) -> Vec<RedactedImageRegion> {
+        match (&original.content, &redacted.content) {
+            (
+                RedacterDataItemContent::Image {
+                    data: original_data,
+                    ..
+                },
+                RedacterDataItemContent::Image {
+                    data: redacted_data,
+                    ..
+                },
+            ) => match (
+                image::load_from_memory(original_data),
+                image::load_from_memory(redacted_data),
+            ) {
+                (Ok(original_image), Ok(redacted_image)) => {
+                    RedactedImageRegion::changed_regions(0, &original_image, &redacted_image)
+                }
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    async fn verify_redacted(
+        &'a self,
+        redacted: &RedacterDataItem,
+        number_of_redactions: usize,
+    ) -> AppResult<Vec<RedactFinding>> {
+        match self.verifier {
+            Some(verifier) if number_of_redactions > 0 => {
+                self.reporter.report(format!(
+                    "↳ Verifying using {} redacter",
+                    verifier.redacter_type()
+                ))?;
+                match verifier.verify(redacted).await? {
+                    Some(findings) => Ok(findings),
+                    None => {
+                        self.reporter.report(
+                            "↲ Skipping verification because only text and tables can be verified",
+                        )?;
+                        Ok(vec![])
+                    }
+                }
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Redacts text of OpenDocument files (such as Google Docs and Sheets exports) as text,
+    /// or spreadsheet cells as tables, then writes the redacted text back to the documents.
+    /// Embedded images are redacted as any other images with `--redact-embedded-images`.
+    async fn redact_odf_stream<
+        S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static,
+    >(
+        &'a self,
+        input: S,
+        redact_plan: StreamRedactPlan<'a>,
+        file_ref: &FileSystemRef,
+    ) -> AppResult<RedactStreamResult> {
+        let document_type = file_ref
+            .media_type
+            .as_ref()
+            .and_then(OdfDocumentType::from_mime)
+            .ok_or_else(|| AppError::SystemError {
+                message: "OpenDocument media type is required".to_string(),
+            })?;
+        let all_chunks: Vec<bytes::Bytes> = input.try_collect().await?;
+        let document = OdfDocument::open(all_chunks.concat().into())?;
+
+        let item = if document_type == OdfDocumentType::Spreadsheet
+            && !redact_plan.leave_data_table_as_text
+        {
+            let (headers, rows) = document.table();
+            RedacterDataItem {
+                content: RedacterDataItemContent::Table { headers, rows },
+                file_ref: FileSystemRef {
+                    media_type: Some(mime::TEXT_CSV),
+                    ..file_ref.clone()
+                },
+            }
+        } else {
+            RedacterDataItem {
+                content: RedacterDataItemContent::Value(document.text()),
+                file_ref: FileSystemRef {
+                    media_type: Some(mime::TEXT_PLAIN),
+                    ..file_ref.clone()
+                },
+            }
+        };
+        let mut text_redacters = Vec::new();
+        for redacter in redact_plan.supported_redacters.iter().copied() {
+            if redacter.redact_support(&item.file_ref).await? == RedactSupport::Supported {
+                text_redacters.push(redacter);
+            }
+        }
+
+        let mut number_of_redactions = 0;
+        let mut applied_redacters: Vec<String> = Vec::new();
+        let mut residual_findings = Vec::new();
+        let mut redacted_content = None;
+        if document.number_of_paragraphs() == 0 {
+            self.reporter
+                .report("↲ Skipping redaction of text because the document has no text")?;
+        } else if !text_redacters.is_empty() {
+            self.reporter.report(format!(
+                "↳ Redacting {} paragraphs of the OpenDocument file",
+                document.number_of_paragraphs()
+            ))?;
+            let (redacted, text_redactions, text_applied_redacters, _) = self
+                .redact_with_policy(item, &text_redacters, &redact_plan, file_ref)
+                .await?;
+            residual_findings = self.verify_redacted(&redacted, text_redactions).await?;
+            number_of_redactions += text_redactions;
+            applied_redacters.extend(text_applied_redacters);
+            redacted_content = Some(redacted.content);
+        }
+
+        let mut redacted_images = Vec::new();
+        if self.redacter_base_options.redact_embedded_images && !document.images().is_empty() {
+            let mut ocr_redacters = Vec::new();
+            for redacter in redact_plan.supported_redacters.iter().copied() {
+                if redacter
+                    .redact_support(&FileSystemRef {
+                        media_type: Some(mime::TEXT_PLAIN),
+                        ..file_ref.clone()
+                    })
+                    .await?
+                    == RedactSupport::Supported
+                {
+                    ocr_redacters.push(redacter);
+                }
+            }
+            let (images, image_redactions, image_applied_redacters) = self
+                .redact_package_images(document.images(), &ocr_redacters, &redact_plan, file_ref)
+                .await?;
+            redacted_images = images;
+            number_of_redactions += image_redactions;
+            for redacter_type in image_applied_redacters {
+                if !applied_redacters.contains(&redacter_type) {
+                    applied_redacters.push(redacter_type);
+                }
+            }
+        }
+
+        let output_bytes = match redacted_content {
+            None => document.repack_text(&document.text(), redacted_images)?,
+            Some(RedacterDataItemContent::Value(content)) => {
+                document.repack_text(&content, redacted_images)?
+            }
+            Some(RedacterDataItemContent::Table { rows, .. }) => {
+                document.repack_table(&rows, redacted_images)?
+            }
+            Some(_) => {
+                return Err(AppError::SystemError {
+                    message: "Redacted OpenDocument text is not returned as text or table"
+                        .to_string(),
+                })
+            }
+        };
+
+        Ok(RedactStreamResult {
+            number_of_redactions,
+            applied_redacters,
+            residual_findings,
+            redacted_regions: Vec::new(),
+            stream: Box::new(futures::stream::iter(vec![Ok(output_bytes)])),
+        })
+    }
+
+    /// Redacts text of slides of PowerPoint presentations as text and embedded images as any other images
+    /// (natively or using OCR), then writes the redacted text and images back to the presentations.
+    async fn redact_pptx_stream<
+        S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static,
+    >(
+        &'a self,
+        input: S,
+        redact_plan: StreamRedactPlan<'a>,
+        file_ref: &FileSystemRef,
+    ) -> AppResult<RedactStreamResult> {
+        let all_chunks: Vec<bytes::Bytes> = input.try_collect().await?;
+        let document = PptxDocument::open(all_chunks.concat().into())?;
+        let mut text_redacters = Vec::new();
+        for redacter in redact_plan.supported_redacters.iter().copied() {
+            if redacter
+                .redact_support(&FileSystemRef {
+                    media_type: Some(mime::TEXT_PLAIN),
+                    ..file_ref.clone()
+                })
+                .await?
+                == RedactSupport::Supported
+            {
+                text_redacters.push(redacter);
+            }
+        }
+        let mut number_of_redactions = 0;
+        let mut applied_redacters: Vec<String> = Vec::new();
+
+        let mut redacted_text = document.text();
+        let mut residual_findings = Vec::new();
+        if document.number_of_paragraphs() > 0 && !text_redacters.is_empty() {
+            self.reporter.report(format!(
+                "↳ Redacting {} paragraphs of {} slides",
+                document.number_of_paragraphs(),
+                document.number_of_slides()
+            ))?;
+            let (redacted, text_redactions, text_applied_redacters, _) = self
+                .redact_with_policy(
+                    RedacterDataItem {
+                        content: RedacterDataItemContent::Value(redacted_text),
+                        file_ref: FileSystemRef {
+                            media_type: Some(mime::TEXT_PLAIN),
+                            ..file_ref.clone()
+                        },
+                    },
+                    &text_redacters,
+                    &redact_plan,
+                    file_ref,
+                )
+                .await?;
+            residual_findings = self.verify_redacted(&redacted, text_redactions).await?;
+            redacted_text = match redacted.content {
+                RedacterDataItemContent::Value(content) => content,
+                _ => {
+                    return Err(AppError::SystemError {
+                        message: "Redacted text of slides is not returned as text".to_string(),
+                    })
+                }
+            };
+            number_of_redactions += text_redactions;
+            applied_redacters.extend(text_applied_redacters);
+        }
+
+        let (redacted_images, image_redactions, image_applied_redacters) = self
+            .redact_package_images(document.images(), &text_redacters, &redact_plan, file_ref)
+            .await?;
+        number_of_redactions += image_redactions;
+        for redacter_type in image_applied_redacters {
+            if !applied_redacters.contains(&redacter_type) {
+                applied_redacters.push(redacter_type);
+            }
+        }
+
+        Ok(RedactStreamResult {
+            number_of_redactions,
+            applied_redacters,
+            residual_findings,
+            redacted_regions: Vec::new(),
+            stream: Box::new(futures::stream::iter(vec![Ok(
+                document.repack(&redacted_text, redacted_images)?
+            )])),
+        })
+    }
+
+    /// Redacts text of Word documents as text, then writes the redacted text back to the documents.
+    /// Embedded images are redacted as any other images with `--redact-embedded-images`.
+    async fn redact_docx_stream<
+        S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static,
+    >(
+        &'a self,
+        input: S,
+        redact_plan: StreamRedactPlan<'a>,
+        file_ref: &FileSystemRef,
+    ) -> AppResult<RedactStreamResult> {
+        let all_chunks: Vec<bytes::Bytes> = input.try_collect().await?;
+        let document = DocxDocument::open(all_chunks.concat().into())?;
+        let mut text_redacters = Vec::new();
+        for redacter in redact_plan.supported_redacters.iter().copied() {
+            if redacter
+                .redact_support(&FileSystemRef {
+                    media_type: Some(mime::TEXT_PLAIN),
+                    ..file_ref.clone()
+                })
+                .await?
+                == RedactSupport::Supported
+            {
+                text_redacters.push(redacter);
+            }
+        }
+        let mut number_of_redactions = 0;
+        let mut applied_redacters: Vec<String> = Vec::new();
+
+        let mut redacted_text = document.text();
+        let mut residual_findings = Vec::new();
+        if document.number_of_paragraphs() == 0 {
+            self.reporter
+                .report("↲ Skipping redaction of text because the document has no text")?;
+        } else if !text_redacters.is_empty() {
+            self.reporter.report(format!(
+                "↳ Redacting {} paragraphs of the Word document",
+                document.number_of_paragraphs()
+            ))?;
+            let (redacted, text_redactions, text_applied_redacters, _) = self
+                .redact_with_policy(
+                    RedacterDataItem {
+                        content: RedacterDataItemContent::Value(redacted_text),
+                        file_ref: FileSystemRef {
+                            media_type: Some(mime::TEXT_PLAIN),
+                            ..file_ref.clone()
+                        },
+                    },
+                    &text_redacters,
+                    &redact_plan,
+                    file_ref,
+                )
+                .await?;
+            residual_findings = self.verify_redacted(&redacted, text_redactions).await?;
+            redacted_text = match redacted.content {
+                RedacterDataItemContent::Value(content) => content,
+                _ => {
+                    return Err(AppError::SystemError {
+                        message: "Redacted text of the Word document is not returned as text"
+                            .to_string(),
+                    })
+                }
+            };
+            number_of_redactions += text_redactions;
+            applied_redacters.extend(text_applied_redacters);
+        }
+
+        let mut redacted_images = Vec::new();
+        if self.redacter_base_options.redact_embedded_images && !document.images().is_empty() {
+            let (images, image_redactions, image_applied_redacters) = self
+                .redact_package_images(document.images(), &text_redacters, &redact_plan, file_ref)
+                .await?;
+            redacted_images = images;
+            number_of_redactions += image_redactions;
+            for redacter_type in image_applied_redacters {
+                if !applied_redacters.contains(&redacter_type) {
+                    applied_redacters.push(redacter_type);
+                }
+            }
+        }
+
+        Ok(RedactStreamResult {
+            number_of_redactions,
+            applied_redacters,
+            residual_findings,
+            redacted_regions: Vec::new(),
+            stream: Box::new(futures::stream::iter(vec![Ok(
+                document.repack(&redacted_text, redacted_images)?
+            )])),
+        })
+    }
+
+    /// Redacts images embedded in documents and presentations as any other images
+    /// (natively or using OCR with text redacters), returning the redacted images with
+    /// the number of redactions and applied redacters
+    async fn redact_package_images(
+        &'a self,
+        images: &[PackageImage],
+        text_redacters: &[&'a Redacters<'a>],
+        redact_plan: &StreamRedactPlan<'a>,
+        file_ref: &FileSystemRef,
+    ) -> AppResult<(Vec<PackageImage>, usize, Vec<String>)> {
+        let mut redacted_images = Vec::new();
+        let mut number_of_redactions = 0;
+        let mut applied_redacters: Vec<String> = Vec::new();
+        for image in images {
+            let image_file_ref = FileSystemRef {
+                relative_path: format!("{}/{}", file_ref.relative_path.value(), image.name).into(),
+                media_type: Some(image.mime_type.clone()),
+                file_size: Some(image.data.len()),
+                ..file_ref.clone()
+            };
+            let mut native_redacters = Vec::new();
+            for redacter in redact_plan.supported_redacters.iter().copied() {
+                if redacter.redact_support(&image_file_ref).await? == RedactSupport::Supported {
+                    native_redacters.push(redacter);
+                }
+            }
+            let mut image_redact_plan = StreamRedactPlan {
+                apply_ocr: native_redacters.is_empty(),
+                supported_redacters: if native_redacters.is_empty() {
+                    text_redacters.to_vec()
+                } else {
+                    native_redacters
+                },
+                ..StreamRedactPlan::default()
+            };
+            if image_redact_plan.apply_ocr && self.file_converters.ocr.is_none() {
+                image_redact_plan.supported_redacters.clear();
+            }
+            if image_redact_plan.supported_redacters.is_empty() && !redact_plan.apply_face_redaction
+            {
+                continue;
+            }
+            self.reporter
+                .report(format!("↳ Redacting image {}", image.name))?;
+            let (mut redacted, image_redactions, image_applied_redacters, _) = self
+                .redact_with_policy(
+                    RedacterDataItem {
+                        content: RedacterDataItemContent::Image {
+                            mime_type: image.mime_type.clone(),
+                            data: image.data.clone(),
+                        },
+                        file_ref: image_file_ref.clone(),
+                    },
+                    &image_redact_plan.supported_redacters,
+                    &image_redact_plan,
+                    &image_file_ref,
+                )
+                .await?;
+            number_of_redactions += image_redactions;
+            for redacter_type in image_applied_redacters {
+                if !applied_redacters.contains(&redacter_type) {
+                    applied_redacters.push(redacter_type);
+                }
+            }
+            if redact_plan.apply_face_redaction {
+                if let Some(face_redacter) = self.face_redacter {
+                    let (redacted_item, _) = self.redact_faces(redacted, face_redacter).await?;
+                    redacted = redacted_item;
+                    number_of_redactions += 1;
                 }
             }
+            if let RedacterDataItemContent::Image { mime_type, data } = redacted.content {
+                redacted_images.push(PackageImage {
+                    name: image.name.clone(),
+                    mime_type,
+                    data,
+                });
+            }
         }
-
-        Ok(stream_redact_plan)
+        Ok((redacted_images, number_of_redactions, applied_redacters))
     }
 
-    pub async fn redact_stream<
+    /// Transcribes audio and redacts the transcript as text, then either writes the transcript
+    /// or silences/beeps the words removed by redacters in the audio.
+    async fn redact_audio_stream<
         S: Stream<Item = AppResult<bytes::Bytes>> + Send + Unpin + Sync + 'static,
     >(
         &'a self,
@@ -151,117 +1358,278 @@ impl<'a> StreamRedacter<'a> {
         redact_plan: StreamRedactPlan<'a>,
         file_ref: &FileSystemRef,
     ) -> AppResult<RedactStreamResult> {
-        let mut redacted = self
-            .stream_to_redact_item(self.redacter_base_options, input, file_ref, &redact_plan)
+        let (audio_transcriber, mime_type) = match (self.audio_transcriber, &file_ref.media_type) {
+            (Some(audio_transcriber), Some(mime_type)) => (audio_transcriber, mime_type),
+            _ => {
+                return Err(AppError::SystemError {
+                    message: "Audio transcriber is not available".to_string(),
+                })
+            }
+        };
+        let output = audio_transcriber.audio_transcriber_options.output;
+        let all_chunks: Vec<bytes::Bytes> = input.try_collect().await?;
+        let audio_data = all_chunks.concat();
+
+        self.reporter.report(format!(
+            "↳ Transcribing audio using {} provider",
+            audio_transcriber.audio_transcriber_options.provider
+        ))?;
+        let words = audio_transcriber.transcribe(mime_type, &audio_data).await?;
+        self.reporter
+            .report(format!(" ↳ Transcribed {} words", words.len()))?;
+        let text = words
+            .iter()
+            .map(|word| word.text.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        self.reporter.report("↳ Redacting the transcript")?;
+        let (redacted, number_of_redactions, applied_redacters, _) = self
+            .redact_with_policy(
+                RedacterDataItem {
+                    content: RedacterDataItemContent::Value(text),
+                    file_ref: FileSystemRef {
+                        media_type: Some(mime::TEXT_PLAIN),
+                        ..file_ref.clone()
+                    },
+                },
+                &redact_plan.supported_redacters,
+                &redact_plan,
+                file_ref,
+            )
+            .await?;
+        let residual_findings = self
+            .verify_redacted(&redacted, number_of_redactions)
             .await?;
-        let mut number_of_redactions = 0;
 
-        for (index, redacter) in redact_plan.supported_redacters.iter().enumerate() {
-            let width = " ".repeat(index);
-            if redact_plan.apply_pdf_image_converter {
-                match (
-                    &self.file_converters.pdf_image_converter,
-                    &self.file_converters.ocr,
-                ) {
-                    (Some(ref pdf_to_image), _) if !redact_plan.apply_ocr => {
-                        redacted = self
-                            .redact_pdf_with_images_converter(
-                                file_ref,
-                                redacted,
-                                *redacter,
-                                &width,
-                                pdf_to_image.as_ref(),
-                                None,
-                            )
-                            .await?;
-                        number_of_redactions += 1;
-                    }
-                    (Some(ref pdf_to_image), Some(ref ocr)) => {
-                        redacted = self
-                            .redact_pdf_with_images_converter(
-                                file_ref,
-                                redacted,
-                                *redacter,
-                                &width,
-                                pdf_to_image.as_ref(),
-                                Some(ocr.as_ref()),
-                            )
-                            .await?;
-                        number_of_redactions += 1;
-                    }
-                    (None, Some(_)) => {
-                        self.bar.println(format!(
-                            "{width}↲ Skipping redaction because PDF to image converter is not available",
-                        ));
-                    }
-                    (Some(_), None) => {
-                        self.bar.println(format!(
-                            "{width}↲ Skipping redaction because OCR is not available",
-                        ));
-                    }
-                    (None, None) => {
-                        self.bar.println(format!(
-                            "{width}↲ Skipping redaction because PDF/OCR are not available",
-                        ));
-                    }
-                }
-            } else if redact_plan.apply_ocr {
-                match self.file_converters.ocr {
-                    Some(ref ocr) => {
-                        redacted = self
-                            .redact_with_ocr_converter(
-                                file_ref,
-                                redacted,
-                                *redacter,
-                                &width,
-                                ocr.as_ref(),
-                            )
-                            .await?;
-                        number_of_redactions += 1;
+        let redacted_text = match redacted.content {
+            RedacterDataItemContent::Value(content) => content,
+            _ => {
+                return Err(AppError::SystemError {
+                    message: "Redacted transcript is not returned as text".to_string(),
+                })
+            }
+        };
+        let output_bytes = match output {
+            AudioRedactionOutput::Transcript => bytes::Bytes::from(redacted_text.into_bytes()),
+            AudioRedactionOutput::Silence | AudioRedactionOutput::Beep => {
+                let redacted_words = find_redacted_words(
+                    &words
+                        .iter()
+                        .map(|word| word.text.as_str())
+                        .collect::<Vec<&str>>(),
+                    &redacted_text,
+                );
+                let redacted_time_ranges: Vec<(f64, f64)> = words
+                    .iter()
+                    .zip(redacted_words)
+                    .filter(|(_, redacted)| *redacted)
+                    .map(|(word, _)| (word.start_secs, word.end_secs))
+                    .collect();
+                self.reporter.report(format!(
+                    " ↳ Redacting {} words in audio",
+                    redacted_time_ranges.len()
+                ))?;
+                let mut wav_audio = WavAudio::parse(&audio_data)?;
+                wav_audio.mask_time_ranges(
+                    &redacted_time_ranges,
+                    if output == AudioRedactionOutput::Beep {
+                        WavAudioMask::Beep
+                    } else {
+                        WavAudioMask::Silence
+                    },
+                );
+                wav_audio.into_bytes()
+            }
+        };
+
+        Ok(RedactStreamResult {
+            number_of_redactions,
+            applied_redacters,
+            residual_findings,
+            redacted_regions: Vec::new(),
+            stream: Box::new(futures::stream::iter(vec![Ok(output_bytes)])),
+        })
+    }
+
+    fn has_table_columns_selection(&self, file_ref: &FileSystemRef) -> bool {
+        (self.redacter_base_options.csv_redact_columns.is_some()
+            || !self.redacter_base_options.csv_skip_columns.is_empty())
+            && file_ref
+                .media_type
+                .as_ref()
+                .is_some_and(Redacters::is_mime_table)
+    }
+
+    async fn project_table_columns(
+        &'a self,
+        table_item: RedacterDataItem,
+        redact_plan: &StreamRedactPlan<'a>,
+    ) -> AppResult<(RedacterDataItem, TableColumnsProjection)> {
+        match table_item.content {
+            RedacterDataItemContent::Table { headers, rows } => {
+                let projection = TableColumnsProjection::new(
+                    headers,
+                    rows,
+                    self.redacter_base_options.csv_redact_columns.as_ref(),
+                    &self.redacter_base_options.csv_skip_columns,
+                )?;
+                let (headers, rows) = projection.projected();
+                let content = if redact_plan.leave_data_table_as_text {
+                    let mut writer = csv_async::AsyncWriter::from_writer(vec![]);
+                    if !headers.is_empty() {
+                        writer.write_record(headers).await?;
                     }
-                    None => {
-                        self.bar.println(format!(
-                            "{width}↲ Skipping redaction because OCR is not available",
-                        ));
+                    for row in rows {
+                        writer.write_record(row).await?;
                     }
-                }
-            } else {
-                self.bar.println(format!(
-                    "{width}↳ Redacting using {} redacter",
-                    redacter.redacter_type()
-                ));
-                redacted = redacter.redact(redacted).await?;
-                number_of_redactions += 1;
+                    writer.flush().await?;
+                    RedacterDataItemContent::Value(
+                        String::from_utf8(writer.into_inner().await?).map_err(|e| {
+                            AppError::SystemError {
+                                message: format!("Failed to convert CSV to string: {}", e),
+                            }
+                        })?,
+                    )
+                } else {
+                    RedacterDataItemContent::Table { headers, rows }
+                };
+                Ok((
+                    RedacterDataItem {
+                        content,
+                        file_ref: table_item.file_ref,
+                    },
+                    projection,
+                ))
             }
+            _ => Err(AppError::SystemError {
+                message: "Attempt to select columns of non-table content".to_string(),
+            }),
         }
+    }
 
-        let output_stream = match redacted.content {
+    async fn merge_table_columns(
+        &'a self,
+        redacted: RedacterDataItem,
+        projection: TableColumnsProjection,
+    ) -> AppResult<RedacterDataItem> {
+        let redacted_rows = match redacted.content {
+            RedacterDataItemContent::Table { rows, .. } => rows,
             RedacterDataItemContent::Value(content) => {
-                let bytes = bytes::Bytes::from(content.into_bytes());
-                Box::new(futures::stream::iter(vec![Ok(bytes)]))
+                let mut reader = csv_async::AsyncReaderBuilder::default()
+                    .has_headers(!self.redacter_base_options.csv_headers_disable)
+                    .create_reader(content.as_bytes());
+                let records: Vec<csv_async::StringRecord> = reader.records().try_collect().await?;
+                records
+                    .iter()
+                    .map(|r| r.iter().map(|c| c.to_string()).collect())
+                    .collect()
             }
-            RedacterDataItemContent::Image { data, .. } => {
-                Box::new(futures::stream::iter(vec![Ok(data)]))
+            _ => {
+                return Err(AppError::SystemError {
+                    message: "Redacted table is not returned as table or text".to_string(),
+                })
             }
-            RedacterDataItemContent::Pdf { data } => {
-                Box::new(futures::stream::iter(vec![Ok(data)]))
+        };
+        let (headers, rows) = projection.merge(redacted_rows)?;
+        Ok(RedacterDataItem {
+            content: RedacterDataItemContent::Table { headers, rows },
+            file_ref: redacted.file_ref,
+        })
+    }
+
+    /// Text files are redacted as logs with `--redact-log-format`, leaving only free text for redacters
+    fn project_log_fields(
+        &'a self,
+        item: RedacterDataItem,
+    ) -> AppResult<(RedacterDataItem, Option<LogFieldsProjection>)> {
+        let is_text_plain = item
+            .file_ref
+            .media_type
+            .as_ref()
+            .is_some_and(|mime| mime.type_() == mime::TEXT && mime.subtype() == mime::PLAIN);
+        match (&self.redacter_base_options.log_redaction, item.content) {
+            (Some(log_redaction), RedacterDataItemContent::Value(content)) if is_text_plain => {
+                let projection = LogFieldsProjection::new(&content, log_redaction)?;
+                self.reporter.report(format!(
+                    "↳ Redacted {} fields of the log ({}) locally",
+                    projection.local_redactions(),
+                    log_redaction.format
+                ))?;
+                Ok((
+                    RedacterDataItem {
+                        content: RedacterDataItemContent::Value(projection.projected()),
+                        file_ref: item.file_ref,
+                    },
+                    Some(projection),
+                ))
             }
-            RedacterDataItemContent::Table { headers, rows } => {
-                let mut writer = csv_async::AsyncWriter::from_writer(vec![]);
-                writer.write_record(headers).await?;
-                for row in rows {
-                    writer.write_record(row).await?;
-                }
-                writer.flush().await?;
-                let bytes = bytes::Bytes::from(writer.into_inner().await?);
-                Box::new(futures::stream::iter(vec![Ok(bytes)]))
+            (_, content) => Ok((
+                RedacterDataItem {
+                    content,
+                    file_ref: item.file_ref,
+                },
+                None,
+            )),
+        }
+    }
+
+    /// IP and MAC addresses of texts and tables are anonymized locally with `--network-*` options,
+    /// so original addresses are never sent to redacters
+    fn anonymize_network_ids(
+        &'a self,
+        item: RedacterDataItem,
+    ) -> AppResult<(RedacterDataItem, usize)> {
+        let anonymizer = match &self.redacter_base_options.network_anonymization {
+            Some(options) => NetworkIdAnonymizer::new(options.clone())?,
+            None => return Ok((item, 0)),
+        };
+        let mut anonymized = 0;
+        let content = match item.content {
+            RedacterDataItemContent::Value(content) => {
+                RedacterDataItemContent::Value(anonymizer.anonymize(&content, &mut anonymized))
             }
+            RedacterDataItemContent::Table { headers, rows } => RedacterDataItemContent::Table {
+                headers,
+                rows: rows
+                    .into_iter()
+                    .map(|row| {
+                        row.into_iter()
+                            .map(|cell| anonymizer.anonymize(&cell, &mut anonymized))
+                            .collect()
+                    })
+                    .collect(),
+            },
+            content => content,
         };
+        if anonymized > 0 {
+            self.reporter.report(format!(
+                "↳ Anonymized {} network identifiers locally",
+                anonymized
+            ))?;
+        }
+        Ok((
+            RedacterDataItem {
+                content,
+                file_ref: item.file_ref,
+            },
+            anonymized,
+        ))
+    }
 
-        Ok(RedactStreamResult {
-            number_of_redactions,
-            stream: output_stream,
-        })
+    fn merge_log_fields(
+        redacted: RedacterDataItem,
+        projection: LogFieldsProjection,
+    ) -> AppResult<RedacterDataItem> {
+        match redacted.content {
+            RedacterDataItemContent::Value(content) => Ok(RedacterDataItem {
+                content: RedacterDataItemContent::Value(projection.merge(&content)?),
+                file_ref: redacted.file_ref,
+            }),
+            _ => Err(AppError::SystemError {
+                message: "Redacted log is not returned as text".to_string(),
+            }),
+        }
     }
 
     async fn stream_to_redact_item<
@@ -272,24 +1640,30 @@ impl<'a> StreamRedacter<'a> {
         input: S,
         file_ref: &FileSystemRef,
         redact_plan: &StreamRedactPlan<'a>,
-    ) -> AppResult<RedacterDataItem> {
+    ) -> AppResult<(RedacterDataItem, Option<CsvDialect>)> {
         match file_ref.media_type {
             Some(ref mime)
                 if Redacters::is_mime_text(mime)
                     || (Redacters::is_mime_table(mime) && redact_plan.leave_data_table_as_text) =>
             {
-                self.stream_to_text_redact_item(input, file_ref).await
+                Ok((
+                    self.stream_to_text_redact_item(input, file_ref).await?,
+                    None,
+                ))
             }
-            Some(ref mime) if Redacters::is_mime_image(mime) => {
+            Some(ref mime) if Redacters::is_mime_image(mime) => Ok((
                 self.stream_to_image_redact_item(input, file_ref, mime.clone())
-                    .await
-            }
+                    .await?,
+                None,
+            )),
             Some(ref mime) if Redacters::is_mime_table(mime) => {
-                self.stream_to_table_redact_item(redacter_base_options, input, file_ref)
-                    .await
+                let (item, csv_dialect) = self
+                    .stream_to_table_redact_item(redacter_base_options, input, file_ref)
+                    .await?;
+                Ok((item, Some(csv_dialect)))
             }
             Some(ref mime) if Redacters::is_mime_pdf(mime) => {
-                self.stream_to_pdf_redact_item(input, file_ref).await
+                Ok((self.stream_to_pdf_redact_item(input, file_ref).await?, None))
             }
             Some(ref mime) => Err(AppError::SystemError {
                 message: format!("Media type {} is not supported for redaction", mime),
@@ -312,14 +1686,13 @@ impl<'a> StreamRedacter<'a> {
         let whole_content = String::from_utf8(all_bytes).map_err(|e| AppError::SystemError {
             message: format!("Failed to convert bytes to string: {}", e),
         })?;
-        let content = if let Some(sampling_size) = self.redacter_base_options.sampling_size {
-            let sampling_size = std::cmp::min(sampling_size, whole_content.len());
-            whole_content
-                .chars()
-                .take(sampling_size)
-                .collect::<String>()
-        } else {
-            whole_content
+        // Whole texts are kept with --sample-then-full, since samples are taken before redacting them
+        let content = match self.redacter_base_options.sampling_size {
+            Some(sampling_size) if !self.redacter_base_options.sample_then_full => self
+                .redacter_base_options
+                .sampling_strategy
+                .sample(&whole_content, sampling_size),
+            _ => whole_content,
         };
         Ok(RedacterDataItem {
             content: RedacterDataItemContent::Value(content),
@@ -334,20 +1707,22 @@ impl<'a> StreamRedacter<'a> {
         redacter_base_options: &RedacterBaseOptions,
         input: S,
         file_ref: &FileSystemRef,
-    ) -> AppResult<RedacterDataItem> {
-        let reader = tokio_util::io::StreamReader::new(
-            input.map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err)),
-        );
-        let mut reader = csv_async::AsyncReaderBuilder::default()
-            .has_headers(!redacter_base_options.csv_headers_disable)
-            .delimiter(
-                redacter_base_options
-                    .csv_delimiter
+    ) -> AppResult<(RedacterDataItem, CsvDialect)> {
+        let all_chunks: Vec<bytes::Bytes> = input.try_collect().await?;
+        let all_bytes = all_chunks.concat();
+        let csv_dialect = CsvDialect::sniff(
+            &all_bytes,
+            redacter_base_options.csv_delimiter.or_else(|| {
+                file_ref
+                    .media_type
                     .as_ref()
-                    .cloned()
-                    .unwrap_or(b','),
-            )
-            .create_reader(reader);
+                    .filter(|mime| Redacters::is_mime_tsv(mime))
+                    .map(|_| b'\t')
+            }),
+        );
+        let mut reader = csv_dialect
+            .reader_builder(!redacter_base_options.csv_headers_disable)
+            .create_reader(all_bytes.as_slice());
         let headers = if !redacter_base_options.csv_headers_disable {
             reader
                 .headers()
@@ -359,16 +1734,19 @@ impl<'a> StreamRedacter<'a> {
             vec![]
         };
         let records: Vec<csv_async::StringRecord> = reader.records().try_collect().await?;
-        Ok(RedacterDataItem {
-            content: RedacterDataItemContent::Table {
-                headers,
-                rows: records
-                    .iter()
-                    .map(|r| r.iter().map(|c| c.to_string()).collect())
-                    .collect(),
+        Ok((
+            RedacterDataItem {
+                content: RedacterDataItemContent::Table {
+                    headers,
+                    rows: records
+                        .iter()
+                        .map(|r| r.iter().map(|c| c.to_string()).collect())
+                        .collect(),
+                },
+                file_ref: file_ref.clone(),
             },
-            file_ref: file_ref.clone(),
-        })
+            csv_dialect,
+        ))
     }
 
     async fn stream_to_image_redact_item<
@@ -412,88 +1790,403 @@ impl<'a> StreamRedacter<'a> {
         file_ref: &FileSystemRef,
         redacted: RedacterDataItem,
         redacter: &impl Redacter,
-        width: &String,
+        width: &str,
         converter: &dyn PdfToImage,
         ocr: Option<&dyn Ocr>,
-    ) -> Result<RedacterDataItem, AppError> {
+    ) -> AppResult<(RedacterDataItem, Vec<RedactedImageRegion>)> {
         match redacted.content {
             RedacterDataItemContent::Pdf { data } => {
-                self.bar.println(format!(
+                self.reporter.report(format!(
                     "{width}↳ Redacting using {} redacter and converting the PDF to images",
                     redacter.redacter_type()
-                ));
+                ))?;
                 let pdf_info = converter.convert_to_images(data)?;
-                self.bar.println(format!(
+                self.reporter.report(format!(
                     "{width} ↳ Converting {pdf_info_pages} images",
                     pdf_info_pages = pdf_info.pages.len()
-                ));
-                let mut redacted_pages = Vec::with_capacity(pdf_info.pages.len());
-                for page in pdf_info.pages {
-                    let mut png_image_bytes = std::io::Cursor::new(Vec::new());
-                    page.page_as_images
-                        .write_to(&mut png_image_bytes, ImageFormat::Png)?;
-                    let image_to_redact = RedacterDataItem {
+                ))?;
+                let page_images: Vec<&DynamicImage> = pdf_info
+                    .pages
+                    .iter()
+                    .map(|page| &page.page_as_images)
+                    .collect();
+                let (redacted_images, redacted_regions) = self
+                    .redact_page_images(file_ref, &page_images, redacter, width, ocr)
+                    .await?;
+                let redacted_pdf_info = PdfInfo {
+                    pages: Self::keep_original_pdf_pages(
+                        pdf_info.pages.into_iter().zip(redacted_images).collect(),
+                        &redacted_regions,
+                    ),
+                    ..pdf_info
+                };
+                let redact_pdf_as_images = self.images_to_pdf(converter, redacted_pdf_info)?;
+                Ok((
+                    RedacterDataItem {
+                        content: RedacterDataItemContent::Pdf {
+                            data: redact_pdf_as_images,
+                        },
+                        file_ref: file_ref.clone(),
+                    },
+                    redacted_regions,
+                ))
+            }
+            _ => Ok((redacted, Vec::new())),
+        }
+    }
+
+    async fn redact_tiff_with_pages_converter(
+        &'a self,
+        file_ref: &FileSystemRef,
+        redacted: RedacterDataItem,
+        redacter: &impl Redacter,
+        width: &str,
+        ocr: Option<&dyn Ocr>,
+    ) -> AppResult<(RedacterDataItem, Vec<RedactedImageRegion>)> {
+        match redacted.content {
+            RedacterDataItemContent::Image { mime_type, data } => {
+                self.reporter.report(format!(
+                    "{width}↳ Redacting using {} redacter and converting the TIFF pages to images",
+                    redacter.redacter_type()
+                ))?;
+                let pages = tiff_to_images(&data)?;
+                self.reporter.report(format!(
+                    "{width} ↳ Converting {pages_len} images",
+                    pages_len = pages.len()
+                ))?;
+                let (redacted_pages, redacted_regions) = self
+                    .redact_page_images(
+                        file_ref,
+                        &pages.iter().collect::<Vec<&DynamicImage>>(),
+                        redacter,
+                        width,
+                        ocr,
+                    )
+                    .await?;
+                Ok((
+                    RedacterDataItem {
                         content: RedacterDataItemContent::Image {
-                            mime_type: mime::IMAGE_PNG,
-                            data: png_image_bytes.into_inner().into(),
+                            mime_type,
+                            data: images_to_tiff(&redacted_pages)?,
                         },
                         file_ref: file_ref.clone(),
-                    };
-                    let redacted_image = if let Some(ocr_engine) = ocr {
-                        self.redact_with_ocr_converter(
-                            file_ref,
-                            image_to_redact,
-                            redacter,
-                            &format!("  {}", width),
-                            ocr_engine,
-                        )
-                        .await?
-                    } else {
-                        redacter.redact(image_to_redact).await?
-                    };
-                    if let RedacterDataItemContent::Image { data, .. } = redacted_image.content {
-                        redacted_pages.push(PdfPageInfo {
-                            page_as_images: image::load_from_memory_with_format(
-                                &data,
-                                ImageFormat::Png,
-                            )?,
-                            ..page
-                        });
+                    },
+                    redacted_regions,
+                ))
+            }
+            content => Ok((
+                RedacterDataItem {
+                    content,
+                    file_ref: redacted.file_ref,
+                },
+                Vec::new(),
+            )),
+        }
+    }
+
+    /// Redacts pages of PDFs and multi-page TIFFs with up to `page_concurrency` pages at a time,
+    /// keeping the order of pages
+    async fn redact_page_images(
+        &'a self,
+        file_ref: &FileSystemRef,
+        pages: &[&DynamicImage],
+        redacter: &impl Redacter,
+        width: &str,
+        ocr: Option<&dyn Ocr>,
+    ) -> AppResult<(Vec<DynamicImage>, Vec<RedactedImageRegion>)> {
+        let redacted_pages_counter = AtomicUsize::new(0);
+        let redacted: Vec<(DynamicImage, Vec<RedactedImageRegion>)> =
+            futures::stream::iter(pages.iter().enumerate())
+                .map(|(page_index, page)| {
+                    let redacted_pages_counter = &redacted_pages_counter;
+                    async move {
+                        self.throttle_request().await;
+                        let redacted_page = self
+                            .redact_page_image(file_ref, page, page_index, redacter, width, ocr)
+                            .await?;
+                        let redacted_pages =
+                            redacted_pages_counter.fetch_add(1, Ordering::Relaxed) + 1;
+                        self.reporter.report_progress(format!(
+                            "Redacted {} / {} pages",
+                            redacted_pages,
+                            pages.len()
+                        ));
+                        Ok::<_, AppError>(redacted_page)
                     }
-                }
-                let redacted_pdf_info = PdfInfo {
-                    pages: redacted_pages,
+                })
+                .buffered(self.redacter_base_options.page_concurrency.max(1))
+                .try_collect()
+                .await?;
+        self.reporter.report_progress("");
+        Ok(redacted.into_iter().fold(
+            (Vec::with_capacity(pages.len()), Vec::new()),
+            |(mut redacted_pages, mut redacted_regions), (redacted_page, page_regions)| {
+                redacted_pages.push(redacted_page);
+                redacted_regions.extend(page_regions);
+                (redacted_pages, redacted_regions)
+            },
+        ))
+    }
+
+    async fn throttle_request(&self) {
+        let delay = self
+            .request_throttler
+            .and_then(|request_throttler| request_throttler.lock().ok())
+            .and_then(|mut request_throttler| {
+                request_throttler.as_mut().map(|throttler| {
+                    *throttler = throttler.update(Instant::now());
+                    *throttler.delay()
+                })
+            });
+        if let Some(delay) = delay.filter(|delay| delay.as_millis() > 0) {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Redacts a page of PDFs and multi-page TIFFs as a PNG image
+    async fn redact_page_image(
+        &'a self,
+        file_ref: &FileSystemRef,
+        page: &DynamicImage,
+        page_index: usize,
+        redacter: &impl Redacter,
+        width: &str,
+        ocr: Option<&dyn Ocr>,
+    ) -> AppResult<(DynamicImage, Vec<RedactedImageRegion>)> {
+        let mut png_image_bytes = std::io::Cursor::new(Vec::new());
+        page.write_to(&mut png_image_bytes, ImageFormat::Png)?;
+        let image_to_redact = RedacterDataItem {
+            content: RedacterDataItemContent::Image {
+                mime_type: mime::IMAGE_PNG,
+                data: png_image_bytes.into_inner().into(),
+            },
+            file_ref: file_ref.clone(),
+        };
+        let (redacted_image, redacted_coords) = if let Some(ocr_engine) = ocr {
+            let (redacted_image, redacted_coords) = self
+                .redact_with_ocr_converter(
+                    file_ref,
+                    image_to_redact,
+                    redacter,
+                    &format!("  {}", width),
+                    ocr_engine,
+                )
+                .await?;
+            (redacted_image, Some(redacted_coords))
+        } else {
+            (redacter.redact(image_to_redact).await?, None)
+        };
+        match redacted_image.content {
+            RedacterDataItemContent::Image { data, .. } => {
+                let redacted_page = image::load_from_memory_with_format(&data, ImageFormat::Png)?;
+                let regions = match redacted_coords {
+                    Some(redacted_coords) => self.coords_to_regions(page_index, &redacted_coords),
+                    None => RedactedImageRegion::changed_regions(page_index, page, &redacted_page),
                 };
-                let redact_pdf_as_images = converter.images_to_pdf(redacted_pdf_info)?;
-                Ok(RedacterDataItem {
-                    content: RedacterDataItemContent::Pdf {
-                        data: redact_pdf_as_images,
+                Ok((redacted_page, regions))
+            }
+            _ => Err(AppError::SystemError {
+                message: "Redacted page is not returned as image".to_string(),
+            }),
+        }
+    }
+
+    /// Rebuilds PDFs from page images, converting them to PDF/A if required
+    fn images_to_pdf(
+        &self,
+        converter: &dyn PdfToImage,
+        pdf_info: PdfInfo,
+    ) -> AppResult<bytes::Bytes> {
+        if self.redacter_base_options.pdf_a {
+            // Original pages may have fonts not embedded and other content not allowed in PDF/A
+            let pdf = converter.images_to_pdf(PdfInfo {
+                pages: pdf_info
+                    .pages
+                    .into_iter()
+                    .map(|page| PdfPageInfo {
+                        keep_original: false,
+                        ..page
+                    })
+                    .collect(),
+                ..pdf_info
+            })?;
+            let pdf_a = pdf_to_pdf_a(&pdf)?;
+            validate_pdf_a(&pdf_a)?;
+            Ok(pdf_a)
+        } else {
+            converter.images_to_pdf(pdf_info)
+        }
+    }
+
+    /// Pages without redacted regions keep the original PDF pages instead of rasterized images
+    fn keep_original_pdf_pages(
+        pages: Vec<(PdfPageInfo, DynamicImage)>,
+        redacted_regions: &[RedactedImageRegion],
+    ) -> Vec<PdfPageInfo> {
+        pages
+            .into_iter()
+            .enumerate()
+            .map(|(page_index, (page, page_as_images))| PdfPageInfo {
+                page_as_images,
+                keep_original: !redacted_regions
+                    .iter()
+                    .any(|region| region.page == page_index),
+                ..page
+            })
+            .collect()
+    }
+
+    /// Redacts faces, returning regions of the faces found
+    async fn redact_faces(
+        &'a self,
+        redacted: RedacterDataItem,
+        face_redacter: &FaceRedacter<'a>,
+    ) -> AppResult<(RedacterDataItem, Vec<RedactedImageRegion>)> {
+        match &redacted.content {
+            RedacterDataItemContent::Image { mime_type, data }
+                if Redacters::is_mime_tiff(mime_type) =>
+            {
+                self.reporter.report(format!(
+                    "↳ Redacting faces using {} detector and converting the TIFF pages to images",
+                    FaceRedacter::PROVIDER_NAME
+                ))?;
+                let mut face_regions = Vec::new();
+                let mut redacted_pages = Vec::new();
+                for (page_index, page) in tiff_to_images(data)?.iter().enumerate() {
+                    let (redacted_page, page_faces_coords) = self
+                        .redact_faces_in_page(&redacted.file_ref, page, face_redacter)
+                        .await?;
+                    face_regions.extend(self.coords_to_regions(page_index, &page_faces_coords));
+                    redacted_pages.push(redacted_page);
+                }
+                self.reporter
+                    .report(format!(" ↳ Redacted {} faces", face_regions.len()))?;
+                Ok((
+                    RedacterDataItem {
+                        content: RedacterDataItemContent::Image {
+                            mime_type: mime_type.clone(),
+                            data: images_to_tiff(&redacted_pages)?,
+                        },
+                        file_ref: redacted.file_ref,
                     },
-                    file_ref: file_ref.clone(),
-                })
+                    face_regions,
+                ))
+            }
+            RedacterDataItemContent::Image { .. } => {
+                self.reporter.report(format!(
+                    "↳ Redacting faces using {} detector",
+                    FaceRedacter::PROVIDER_NAME
+                ))?;
+                let (redacted, faces_coords) = face_redacter
+                    .redact_faces(redacted, &self.redacter_base_options.image_redaction)
+                    .await?;
+                self.reporter
+                    .report(format!(" ↳ Redacted {} faces", faces_coords.len()))?;
+                Ok((redacted, self.coords_to_regions(0, &faces_coords)))
             }
-            _ => Ok(redacted),
+            RedacterDataItemContent::Pdf { data } => match self.file_converters.pdf_image_converter
+            {
+                Some(ref converter) => {
+                    self.reporter.report(format!(
+                        "↳ Redacting faces using {} detector and converting the PDF to images",
+                        FaceRedacter::PROVIDER_NAME
+                    ))?;
+                    let pdf_info = converter.convert_to_images(data.clone())?;
+                    let mut face_regions = Vec::new();
+                    let mut redacted_pages = Vec::with_capacity(pdf_info.pages.len());
+                    for (page_index, page) in pdf_info.pages.into_iter().enumerate() {
+                        let (redacted_page, page_faces_coords) = self
+                            .redact_faces_in_page(
+                                &redacted.file_ref,
+                                &page.page_as_images,
+                                face_redacter,
+                            )
+                            .await?;
+                        face_regions.extend(self.coords_to_regions(page_index, &page_faces_coords));
+                        redacted_pages.push((page, redacted_page));
+                    }
+                    self.reporter
+                        .report(format!(" ↳ Redacted {} faces", face_regions.len()))?;
+                    Ok((
+                        RedacterDataItem {
+                            content: RedacterDataItemContent::Pdf {
+                                data: self.images_to_pdf(
+                                    converter.as_ref(),
+                                    PdfInfo {
+                                        pages: Self::keep_original_pdf_pages(
+                                            redacted_pages,
+                                            &face_regions,
+                                        ),
+                                        ..pdf_info
+                                    },
+                                )?,
+                            },
+                            file_ref: redacted.file_ref,
+                        },
+                        face_regions,
+                    ))
+                }
+                None => {
+                    self.reporter.report(
+                        "↲ Skipping face redaction because PDF to image converter is not available",
+                    )?;
+                    Ok((redacted, Vec::new()))
+                }
+            },
+            _ => Ok((redacted, Vec::new())),
+        }
+    }
+
+    async fn redact_faces_in_page(
+        &'a self,
+        file_ref: &FileSystemRef,
+        page: &DynamicImage,
+        face_redacter: &FaceRedacter<'a>,
+    ) -> AppResult<(DynamicImage, Vec<TextImageCoords>)> {
+        let mut png_image_bytes = std::io::Cursor::new(Vec::new());
+        page.write_to(&mut png_image_bytes, ImageFormat::Png)?;
+        let (redacted_image, faces_coords) = face_redacter
+            .redact_faces(
+                RedacterDataItem {
+                    content: RedacterDataItemContent::Image {
+                        mime_type: mime::IMAGE_PNG,
+                        data: png_image_bytes.into_inner().into(),
+                    },
+                    file_ref: file_ref.clone(),
+                },
+                &self.redacter_base_options.image_redaction,
+            )
+            .await?;
+        match redacted_image.content {
+            RedacterDataItemContent::Image { data, .. } => Ok((
+                image::load_from_memory_with_format(&data, ImageFormat::Png)?,
+                faces_coords,
+            )),
+            _ => Err(AppError::SystemError {
+                message: "Redacted page is not returned as image".to_string(),
+            }),
         }
     }
 
+    /// Redacts text found by OCR in the image, returning coordinates with the original text of redacted words
     async fn redact_with_ocr_converter(
         &'a self,
         file_ref: &FileSystemRef,
         redacted: RedacterDataItem,
         redacter: &impl Redacter,
-        width: &String,
+        width: &str,
         ocr: &dyn Ocr,
-    ) -> Result<RedacterDataItem, AppError> {
+    ) -> AppResult<(RedacterDataItem, Vec<TextImageCoords>)> {
         match &redacted.content {
             RedacterDataItemContent::Image { data, mime_type } => {
                 match ImageFormat::from_mime_type(mime_type) {
                     Some(image_format) => {
-                        self.bar.println(format!(
+                        self.reporter.report(format!(
                             "{width}↳ Redacting using {} redacter and converting the image to text using OCR engine",
                             redacter.redacter_type()
-                        ));
+                        ))?;
                         let image = image::load_from_memory_with_format(data, image_format)?;
-                        let text_coords = ocr.image_to_text(image.clone())?;
+                        let text_coords = ocr.image_to_text(image.clone()).await?;
                         let text = text_coords
                             .iter()
                             .map(|coord| coord.text.clone())
@@ -512,29 +2205,39 @@ impl<'a> StreamRedacter<'a> {
 
                         match redacted_text.content {
                             RedacterDataItemContent::Value(content) => {
-                                let words_set: HashSet<&str> =
-                                    HashSet::from_iter(content.split(" ").collect::<Vec<_>>());
+                                let text_coords: Vec<TextImageCoords> = text_coords
+                                    .into_iter()
+                                    .filter(|text_coord| text_coord.text.is_some())
+                                    .collect();
+                                let words: Vec<&str> = text_coords
+                                    .iter()
+                                    .flat_map(|text_coord| text_coord.text.as_deref())
+                                    .collect();
+                                let redacted_words = find_redacted_words(&words, &content);
                                 let mut redacted_image = image.to_rgb8();
-                                for text_coord in text_coords {
-                                    if let Some(text) = &text_coord.text {
-                                        if !words_set.contains(text.as_str()) {
-                                            redact_rgba_image_at_coords(
-                                                &mut redacted_image,
-                                                &vec![text_coord],
-                                                0.10,
-                                            );
-                                        }
-                                    }
-                                }
+                                let redacted_coords: Vec<TextImageCoords> = text_coords
+                                    .iter()
+                                    .zip(redacted_words)
+                                    .filter(|(_, redacted)| *redacted)
+                                    .map(|(text_coord, _)| text_coord.clone())
+                                    .collect();
+                                redact_rgba_image_at_coords(
+                                    &mut redacted_image,
+                                    &redacted_coords,
+                                    &self.redacter_base_options.image_redaction,
+                                );
                                 let mut output = std::io::Cursor::new(Vec::new());
                                 redacted_image.write_to(&mut output, image_format)?;
-                                Ok(RedacterDataItem {
-                                    file_ref: file_ref.clone(),
-                                    content: RedacterDataItemContent::Image {
-                                        mime_type: mime_type.clone(),
-                                        data: output.into_inner().into(),
+                                Ok((
+                                    RedacterDataItem {
+                                        file_ref: file_ref.clone(),
+                                        content: RedacterDataItemContent::Image {
+                                            mime_type: mime_type.clone(),
+                                            data: output.into_inner().into(),
+                                        },
                                     },
-                                })
+                                    redacted_coords,
+                                ))
                             }
                             _ => Err(AppError::SystemError {
                                 message: "Redacted text is not returned as text".to_string(),
@@ -542,14 +2245,89 @@ impl<'a> StreamRedacter<'a> {
                         }
                     }
                     None => {
-                        self.bar.println(format!(
+                        self.reporter.report(format!(
                             "{width}↲ Skipping redaction through OCR because image format is not supported",
-                        ));
-                        Ok(redacted)
+                        ))?;
+                        Ok((redacted, Vec::new()))
                     }
                 }
             }
-            _ => Ok(redacted),
+            _ => Ok((redacted, Vec::new())),
+        }
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn redacted_image_regions_test() {
+        let coords = TextImageCoords {
+            x1: 10.0,
+            y1: 20.0,
+            x2: 50.0,
+            y2: 30.0,
+            text: Some("John".to_string()),
+        };
+        let region = RedactedImageRegion::from_coords(1, &coords, true);
+        assert_eq!(region.page, 1);
+        assert_eq!(
+            (region.x1, region.y1, region.x2, region.y2),
+            (10.0, 20.0, 50.0, 30.0)
+        );
+        assert_eq!(
+            region.text_hash,
+            Some(hex::encode(Sha256::digest("John".as_bytes())))
+        );
+        assert_eq!(
+            RedactedImageRegion::from_coords(1, &coords, false).text_hash,
+            None
+        );
+
+        let original = DynamicImage::ImageRgb8(RgbImage::from_pixel(64, 64, Rgb([255, 255, 255])));
+        let mut redacted_image = RgbImage::from_pixel(64, 64, Rgb([255, 255, 255]));
+        for x in 16..32 {
+            for y in 8..16 {
+                redacted_image.put_pixel(x, y, Rgb([0, 0, 0]));
+            }
         }
+        let regions = RedactedImageRegion::changed_regions(
+            0,
+            &original,
+            &DynamicImage::ImageRgb8(redacted_image),
+        );
+        assert_eq!(
+            regions,
+            vec![RedactedImageRegion {
+                page: 0,
+                x1: 16.0,
+                y1: 8.0,
+                x2: 32.0,
+                y2: 16.0,
+                text_hash: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn redact_plan_conversions_test() {
+        let mut redact_plan = StreamRedactPlan {
+            apply_pdf_image_converter: false,
+            apply_tiff_pages_converter: false,
+            apply_ocr: false,
+            leave_data_table_as_text: false,
+            apply_face_redaction: true,
+            apply_audio_transcription: false,
+            apply_odf_converter: false,
+            apply_pptx_converter: false,
+            apply_docx_converter: false,
+            supported_redacters: vec![],
+        };
+        assert!(redact_plan.conversions().is_empty());
+        redact_plan.apply_pdf_image_converter = true;
+        redact_plan.apply_ocr = true;
+        assert_eq!(redact_plan.conversions(), vec!["pdf-image", "ocr"]);
     }
 }
@@ -0,0 +1,238 @@
+use crate::errors::AppError;
+use crate::AppResult;
+
+/// Original table with the list of columns selected for redaction,
+/// so only those columns are sent to redacters and the rest is kept as is.
+#[derive(Debug, Clone)]
+pub struct TableColumnsProjection {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    redact_columns: Vec<usize>,
+}
+
+impl TableColumnsProjection {
+    /// Columns are selected by header names or by zero-based indexes (for tables without headers).
+    /// Without `redact_columns` all columns except `skip_columns` are selected.
+    /// Unknown columns to redact are errors, since a typo would copy the columns unredacted.
+    pub fn new(
+        headers: Vec<String>,
+        rows: Vec<Vec<String>>,
+        redact_columns: Option<&Vec<String>>,
+        skip_columns: &[String],
+    ) -> AppResult<Self> {
+        let columns_count = if headers.is_empty() {
+            rows.first().map(|row| row.len()).unwrap_or(0)
+        } else {
+            headers.len()
+        };
+        if let Some(columns) = redact_columns.filter(|_| columns_count > 0) {
+            let unknown_columns: Vec<&str> = columns
+                .iter()
+                .filter(|column| {
+                    !(0..columns_count).any(|index| {
+                        Self::column_matches(
+                            std::slice::from_ref(*column),
+                            headers.get(index).map(|h| h.as_str()),
+                            index,
+                        )
+                    })
+                })
+                .map(|column| column.as_str())
+                .collect();
+            if !unknown_columns.is_empty() {
+                return Err(AppError::RedacterConfigError {
+                    message: format!(
+                        "CSV columns to redact are not found in the table: {}. Available columns: {}",
+                        unknown_columns.join(", "),
+                        if headers.is_empty() {
+                            format!("0..{}", columns_count - 1)
+                        } else {
+                            headers.join(", ")
+                        }
+                    ),
+                });
+            }
+        }
+        let redact_columns = (0..columns_count)
+            .filter(|index| {
+                let header = headers.get(*index).map(|h| h.as_str());
+                redact_columns
+                    .map(|columns| Self::column_matches(columns, header, *index))
+                    .unwrap_or(true)
+                    && !Self::column_matches(skip_columns, header, *index)
+            })
+            .collect();
+        Ok(Self {
+            headers,
+            rows,
+            redact_columns,
+        })
+    }
+
+    fn column_matches(columns: &[String], header: Option<&str>, index: usize) -> bool {
+        columns.iter().any(|column| {
+            header.is_some_and(|header| header == column) || *column == index.to_string()
+        })
+    }
+
+    pub fn has_redact_columns(&self) -> bool {
+        !self.redact_columns.is_empty()
+    }
+
+    /// Headers and rows containing only the columns selected for redaction
+    pub fn projected(&self) -> (Vec<String>, Vec<Vec<String>>) {
+        let headers = if self.headers.is_empty() {
+            vec![]
+        } else {
+            self.redact_columns
+                .iter()
+                .map(|index| self.headers[*index].clone())
+                .collect()
+        };
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                self.redact_columns
+                    .iter()
+                    .map(|index| row.get(*index).cloned().unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+        (headers, rows)
+    }
+
+    /// Puts redacted columns back to the original table
+    pub fn merge(
+        self,
+        redacted_rows: Vec<Vec<String>>,
+    ) -> AppResult<(Vec<String>, Vec<Vec<String>>)> {
+        if redacted_rows.len() != self.rows.len()
+            || redacted_rows
+                .iter()
+                .any(|row| row.len() != self.redact_columns.len())
+        {
+            return Err(AppError::ConversionError {
+                message: format!(
+                    "Redacted table doesn't match the selected columns: expected {} rows with {} columns",
+                    self.rows.len(),
+                    self.redact_columns.len()
+                ),
+            });
+        }
+        let rows = self
+            .rows
+            .into_iter()
+            .zip(redacted_rows)
+            .map(|(mut row, redacted_row)| {
+                for (index, value) in self.redact_columns.iter().zip(redacted_row) {
+                    if let Some(cell) = row.get_mut(*index) {
+                        *cell = value;
+                    }
+                }
+                row
+            })
+            .collect();
+        Ok((self.headers, rows))
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_and_merge_test() -> AppResult<()> {
+        let projection = TableColumnsProjection::new(
+            ["id", "name", "email", "created_at"]
+                .map(String::from)
+                .to_vec(),
+            vec![
+                ["1", "John", "john@example.com", "2024-01-01"]
+                    .map(String::from)
+                    .to_vec(),
+                ["2", "Jane", "jane@example.com", "2024-01-02"]
+                    .map(String::from)
+                    .to_vec(),
+            ],
+            None,
+            &["id", "created_at"].map(String::from),
+        )?;
+        let (headers, rows) = projection.projected();
+        assert_eq!(headers, ["name", "email"].map(String::from).to_vec());
+        assert_eq!(
+            rows[1],
+            ["Jane", "jane@example.com"].map(String::from).to_vec()
+        );
+
+        let (headers, rows) = projection.merge(vec![
+            ["[REDACTED]", "[REDACTED]"].map(String::from).to_vec(),
+            ["[REDACTED]", "[REDACTED]"].map(String::from).to_vec(),
+        ])?;
+        assert_eq!(
+            headers,
+            ["id", "name", "email", "created_at"]
+                .map(String::from)
+                .to_vec()
+        );
+        assert_eq!(
+            rows[0],
+            ["1", "[REDACTED]", "[REDACTED]", "2024-01-01"]
+                .map(String::from)
+                .to_vec()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn select_columns_without_headers_test() -> AppResult<()> {
+        let projection = TableColumnsProjection::new(
+            vec![],
+            vec![["1", "John", "john@example.com"].map(String::from).to_vec()],
+            Some(&["2"].map(String::from).to_vec()),
+            &[],
+        )?;
+        let (headers, rows) = projection.projected();
+        assert!(headers.is_empty());
+        assert_eq!(rows, vec![["john@example.com"].map(String::from).to_vec()]);
+
+        assert!(projection.clone().merge(vec![]).is_err());
+
+        let nothing_selected = TableColumnsProjection::new(
+            ["id", "email"].map(String::from).to_vec(),
+            vec![["1", "john@example.com"].map(String::from).to_vec()],
+            Some(&["email"].map(String::from).to_vec()),
+            &["email"].map(String::from),
+        )?;
+        assert!(!nothing_selected.has_redact_columns());
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_redact_columns_test() {
+        let misspelled = TableColumnsProjection::new(
+            ["id", "email"].map(String::from).to_vec(),
+            vec![["1", "john@example.com"].map(String::from).to_vec()],
+            Some(&["email", "emial"].map(String::from).to_vec()),
+            &[],
+        );
+        match misspelled {
+            Err(AppError::RedacterConfigError { message }) => {
+                assert!(message.contains("emial"), "{}", message);
+                assert!(!message.contains("email,"), "{}", message);
+            }
+            _ => panic!("Misspelled column must be a config error"),
+        }
+
+        let out_of_range = TableColumnsProjection::new(
+            vec![],
+            vec![["1", "John"].map(String::from).to_vec()],
+            Some(&["2"].map(String::from).to_vec()),
+            &[],
+        );
+        assert!(matches!(
+            out_of_range,
+            Err(AppError::RedacterConfigError { .. })
+        ));
+    }
+}
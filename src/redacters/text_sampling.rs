@@ -0,0 +1,120 @@
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt::Display;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// How samples of texts are taken with `--sampling-size`. Samples from the start of files miss
+/// sensitive data later in files, so samples can be spread across the whole text instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum SamplingStrategy {
+    /// Characters from the start of the text
+    #[default]
+    Head,
+    /// Chunks at random positions in equal parts of the text. Positions are seeded by the text,
+    /// so the same text is always sampled the same way for redaction caches and batches
+    RandomChunks,
+    /// Whole lines evenly spread across the text
+    Lines,
+}
+
+impl SamplingStrategy {
+    const RANDOM_CHUNKS: usize = 8;
+
+    /// Sample of the text with at most the sampling size characters (excluding separators of chunks)
+    pub fn sample(&self, content: &str, sampling_size: usize) -> String {
+        if content.chars().count() <= sampling_size {
+            return content.to_string();
+        }
+        match self {
+            SamplingStrategy::Head => content.chars().take(sampling_size).collect(),
+            SamplingStrategy::RandomChunks => Self::sample_random_chunks(content, sampling_size),
+            SamplingStrategy::Lines => Self::sample_lines(content, sampling_size),
+        }
+    }
+
+    fn sample_random_chunks(content: &str, sampling_size: usize) -> String {
+        let chars: Vec<char> = content.chars().collect();
+        let chunks = Self::RANDOM_CHUNKS.min(sampling_size).max(1);
+        let chunk_size = sampling_size / chunks;
+        let part_size = chars.len() / chunks;
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        let mut rng = StdRng::seed_from_u64(hasher.finish());
+        (0..chunks)
+            .map(|chunk_idx| {
+                let offset = rng.gen_range(0..=part_size - chunk_size);
+                let start = chunk_idx * part_size + offset;
+                chars[start..start + chunk_size].iter().collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn sample_lines(content: &str, sampling_size: usize) -> String {
+        let lines: Vec<&str> = content.lines().collect();
+        let sampled_lines =
+            (lines.len() * sampling_size / content.chars().count()).clamp(1, lines.len());
+        let mut remaining = sampling_size;
+        let mut sample = Vec::new();
+        for line in lines.iter().step_by(lines.len() / sampled_lines) {
+            let line_size = line.chars().count();
+            if line_size > remaining {
+                // Long lines are truncated only if nothing else fits, so samples are never empty
+                if sample.is_empty() {
+                    sample.push(line.chars().take(remaining).collect());
+                }
+                continue;
+            }
+            remaining -= line_size;
+            sample.push(line.to_string());
+        }
+        sample.join("\n")
+    }
+}
+
+impl Display for SamplingStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SamplingStrategy::Head => write!(f, "head"),
+            SamplingStrategy::RandomChunks => write!(f, "random-chunks"),
+            SamplingStrategy::Lines => write!(f, "lines"),
+        }
+    }
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_text_test() {
+        let content = (0..100)
+            .map(|idx| format!("line {:02}", idx))
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        assert_eq!(SamplingStrategy::Lines.sample("short", 10), "short");
+        assert_eq!(
+            SamplingStrategy::Head.sample(&content, 15),
+            "line 00\nline 01"
+        );
+
+        let lines_sample = SamplingStrategy::Lines.sample(&content, 80);
+        let sampled_lines: Vec<&str> = lines_sample.lines().collect();
+        assert_eq!(sampled_lines.len(), 10);
+        assert_eq!(sampled_lines.first(), Some(&"line 00"));
+        assert_eq!(sampled_lines.last(), Some(&"line 90"));
+
+        let parts: String = ('a'..='h')
+            .flat_map(|part| std::iter::repeat(part).take(125))
+            .collect();
+        assert_eq!(
+            SamplingStrategy::RandomChunks.sample(&parts, 80),
+            ('a'..='h')
+                .map(|part| part.to_string().repeat(10))
+                .collect::<Vec<String>>()
+                .join("\n")
+        );
+    }
+}
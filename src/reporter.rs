@@ -2,20 +2,87 @@ use crate::AppResult;
 use console::Term;
 use indicatif::ProgressBar;
 
+/// Console output options, so output is suitable for CI pipelines and logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppReporterOptions {
+    /// Only errors are reported
+    pub quiet: bool,
+    /// Progress bars and colored output are enabled
+    pub progress: bool,
+}
+
+impl Default for AppReporterOptions {
+    fn default() -> Self {
+        Self {
+            quiet: false,
+            progress: true,
+        }
+    }
+}
+
+impl AppReporterOptions {
+    /// Progress bars are disabled automatically if the output isn't a terminal
+    pub fn new(term: &Term, quiet: bool, no_progress: bool) -> Self {
+        Self {
+            quiet,
+            progress: !quiet && !no_progress && term.is_term(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AppReporter<'a> {
     inner: AppReporterInner<'a>,
+    quiet: bool,
 }
 
 impl<'a> AppReporter<'a> {
+    pub fn with_options(self, options: &AppReporterOptions) -> Self {
+        Self {
+            quiet: options.quiet,
+            ..self
+        }
+    }
+
     pub fn report<S>(&'a self, message: S) -> AppResult<()>
     where
         S: AsRef<str>,
     {
+        if self.quiet {
+            return Ok(());
+        }
+        self.write_line(message.as_ref())
+    }
+
+    /// Errors are reported even in quiet mode
+    pub fn report_error<S>(&'a self, message: S) -> AppResult<()>
+    where
+        S: AsRef<str>,
+    {
+        self.write_line(message.as_ref())
+    }
+
+    /// Progress within the current file such as redacted pages, shown only on progress bars
+    pub fn report_progress<S>(&self, message: S)
+    where
+        S: Into<String>,
+    {
+        if let AppReporterInner::ProgressBar(progress_bar) = &self.inner {
+            if !self.quiet {
+                progress_bar.set_message(message.into());
+            }
+        }
+    }
+
+    fn write_line(&self, message: &str) -> AppResult<()> {
         match &self.inner {
-            AppReporterInner::Term(term) => Ok(term.write_line(message.as_ref())?),
+            AppReporterInner::Term(term) => Ok(term.write_line(message)?),
+            // Hidden progress bars don't print anything
+            AppReporterInner::ProgressBar(progress_bar) if progress_bar.is_hidden() => {
+                Ok(Term::stdout().write_line(message)?)
+            }
             AppReporterInner::ProgressBar(progress_bar) => {
-                progress_bar.println(message.as_ref());
+                progress_bar.println(message);
                 Ok(())
             }
         }
@@ -32,6 +99,7 @@ impl<'a> From<&'a Term> for AppReporter<'a> {
     fn from(term: &'a Term) -> Self {
         AppReporter {
             inner: AppReporterInner::Term(term),
+            quiet: false,
         }
     }
 }
@@ -40,6 +108,7 @@ impl<'a> From<&'a ProgressBar> for AppReporter<'a> {
     fn from(progress_bar: &'a ProgressBar) -> Self {
         AppReporter {
             inner: AppReporterInner::ProgressBar(progress_bar),
+            quiet: false,
         }
     }
 }
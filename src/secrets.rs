@@ -0,0 +1,166 @@
+//! Credentials for providers can be specified as references to secrets instead of plain values,
+//! so they don't leak into shell history and process listings:
+//! - `gcp-secret-manager://projects/<project>/secrets/<secret>/versions/<version>` for GCP Secret Manager;
+//! - `aws-secrets-manager://<secret name or ARN>` for AWS Secrets Manager.
+
+use crate::errors::AppError;
+use crate::AppResult;
+use aws_sdk_s3::config::ProvideCredentials;
+use gcloud_sdk::google::cloud::secretmanager::v1::secret_manager_service_client::SecretManagerServiceClient;
+use gcloud_sdk::GoogleApi;
+use std::path::Path;
+
+const GCP_SECRET_MANAGER_PREFIX: &str = "gcp-secret-manager://";
+const AWS_SECRETS_MANAGER_PREFIX: &str = "aws-secrets-manager://";
+
+pub fn is_secret_ref(value: &str) -> bool {
+    value.starts_with(GCP_SECRET_MANAGER_PREFIX) || value.starts_with(AWS_SECRETS_MANAGER_PREFIX)
+}
+
+/// Returns values of secret references from secret managers, and other values as is
+pub async fn resolve_secret(value: &str) -> AppResult<String> {
+    if let Some(secret_version_name) = value.strip_prefix(GCP_SECRET_MANAGER_PREFIX) {
+        read_gcp_secret(secret_version_name).await
+    } else if let Some(secret_id) = value.strip_prefix(AWS_SECRETS_MANAGER_PREFIX) {
+        read_aws_secret(secret_id).await
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// Secret files such as mounted Kubernetes secrets usually end with a new line, which isn't a part of secrets
+pub async fn read_secret_file(path: &Path) -> AppResult<String> {
+    let content = tokio::fs::read_to_string(path).await?;
+    Ok(content.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn read_gcp_secret(secret_version_name: &str) -> AppResult<String> {
+    let client: GoogleApi<SecretManagerServiceClient<gcloud_sdk::GoogleAuthMiddleware>> =
+        GoogleApi::from_function(
+            SecretManagerServiceClient::new,
+            "https://secretmanager.googleapis.com",
+            None,
+        )
+        .await?;
+    let response = client
+        .get()
+        .access_secret_version(
+            gcloud_sdk::google::cloud::secretmanager::v1::AccessSecretVersionRequest {
+                name: secret_version_name.to_string(),
+            },
+        )
+        .await?
+        .into_inner();
+    let payload = response.payload.ok_or_else(|| AppError::ConfigError {
+        message: format!("No payload in GCP secret: {}", secret_version_name),
+    })?;
+    String::from_utf8(payload.data.ref_sensitive_value().clone()).map_err(|_| {
+        AppError::ConfigError {
+            message: format!(
+                "GCP secret is not a valid UTF-8 string: {}",
+                secret_version_name
+            ),
+        }
+    })
+}
+
+/// Reads secrets with the AWS Secrets Manager JSON API signed with credentials of the default AWS profile.
+/// Regions are taken from secret ARNs or the AWS profile.
+async fn read_aws_secret(secret_id: &str) -> AppResult<String> {
+    let aws_config = aws_config::from_env().load().await;
+    let region = aws_secret_region(secret_id)
+        .or_else(|| aws_config.region().map(|region| region.to_string()))
+        .ok_or_else(|| AppError::ConfigError {
+            message: format!("No AWS region found for secret: {}", secret_id),
+        })?;
+    let credentials = aws_config
+        .credentials_provider()
+        .ok_or_else(|| AppError::ConfigError {
+            message: "No AWS credentials found to read secrets".to_string(),
+        })?
+        .provide_credentials()
+        .await
+        .map_err(|err| AppError::AwsSdkError(Box::new(err)))?;
+
+    let url = format!("https://secretsmanager.{}.amazonaws.com/", region);
+    let body = serde_json::to_vec(&serde_json::json!({ "SecretId": secret_id }))?;
+    let headers = [
+        ("content-type", "application/x-amz-json-1.1"),
+        ("x-amz-target", "secretsmanager.GetSecretValue"),
+    ];
+    let identity = credentials.into();
+    let signing_params = aws_sigv4::sign::v4::SigningParams::builder()
+        .identity(&identity)
+        .region(&region)
+        .name("secretsmanager")
+        .time(std::time::SystemTime::now())
+        .settings(aws_sigv4::http_request::SigningSettings::default())
+        .build()
+        .map_err(|err| AppError::AwsSdkError(Box::new(err)))?
+        .into();
+    let signable_request = aws_sigv4::http_request::SignableRequest::new(
+        "POST",
+        url.as_str(),
+        headers.iter().copied(),
+        aws_sigv4::http_request::SignableBody::Bytes(&body),
+    )
+    .map_err(|err| AppError::AwsSdkError(Box::new(err)))?;
+    let (signing_instructions, _) =
+        aws_sigv4::http_request::sign(signable_request, &signing_params)
+            .map_err(|err| AppError::AwsSdkError(Box::new(err)))?
+            .into_parts();
+
+    let mut request = reqwest::Client::new().post(url.as_str()).body(body.clone());
+    for (name, value) in headers
+        .iter()
+        .copied()
+        .chain(signing_instructions.headers())
+    {
+        request = request.header(name, value);
+    }
+    let response = request.send().await?.error_for_status()?;
+    let secret_value: serde_json::Value = serde_json::from_slice(&response.bytes().await?)?;
+    secret_value
+        .get("SecretString")
+        .and_then(|secret_string| secret_string.as_str())
+        .map(|secret_string| secret_string.to_string())
+        .ok_or_else(|| AppError::ConfigError {
+            message: format!("AWS secret has no string value: {}", secret_id),
+        })
+}
+
+/// ARNs are in the format of `arn:aws:secretsmanager:<region>:<account>:secret:<name>`
+fn aws_secret_region(secret_id: &str) -> Option<String> {
+    secret_id
+        .strip_prefix("arn:")
+        .and_then(|arn| arn.split(':').nth(2))
+        .filter(|region| !region.is_empty())
+        .map(|region| region.to_string())
+}
+
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn resolve_plain_values_and_files_test() -> AppResult<()> {
+        assert_eq!(resolve_secret("sk-test").await?, "sk-test");
+        assert!(is_secret_ref("aws-secrets-manager://my-secret"));
+        assert!(!is_secret_ref("http://localhost:5002/analyze"));
+
+        let temp_dir = tempfile::tempdir()?;
+        let secret_file = temp_dir.path().join("api-key");
+        tokio::fs::write(&secret_file, "sk-test\n").await?;
+        assert_eq!(read_secret_file(&secret_file).await?, "sk-test");
+        Ok(())
+    }
+
+    #[test]
+    fn aws_secret_region_test() {
+        assert_eq!(
+            aws_secret_region("arn:aws:secretsmanager:eu-west-1:111122223333:secret:my-secret"),
+            Some("eu-west-1".to_string())
+        );
+        assert_eq!(aws_secret_region("my-secret"), None);
+    }
+}
@@ -0,0 +1,251 @@
+//! Shared harness of integration tests running `command_copy` against local mock HTTP servers
+//! of providers and storage emulators, so they don't require cloud credentials.
+
+#![allow(dead_code)]
+
+use clap::Parser;
+use console::Term;
+use redacter::args::RedacterArgs;
+use redacter::commands::{command_copy, CopyCommandOptions, CopyCommandResult};
+use redacter::file_systems::FileSystemOptions;
+use redacter::redacters::RedacterOptions;
+use redacter::reporter::AppReporterOptions;
+use redacter::AppResult;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Requests received by mock servers
+#[derive(Debug, Clone)]
+pub struct MockHttpRequest {
+    pub method: String,
+    pub path: String,
+    pub body: Vec<u8>,
+}
+
+impl MockHttpRequest {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockHttpResponse {
+    pub status: u16,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+impl MockHttpResponse {
+    pub fn json(value: serde_json::Value) -> Self {
+        Self {
+            status: 200,
+            content_type: mime::APPLICATION_JSON.to_string(),
+            body: value.to_string().into_bytes(),
+        }
+    }
+
+    pub fn event_stream(events: Vec<serde_json::Value>) -> Self {
+        let mut body = String::new();
+        for event in events {
+            body.push_str(&format!("data: {}\n\n", event));
+        }
+        body.push_str("data: [DONE]\n\n");
+        Self {
+            status: 200,
+            content_type: mime::TEXT_EVENT_STREAM.to_string(),
+            body: body.into_bytes(),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self {
+            status: 404,
+            content_type: mime::TEXT_PLAIN.to_string(),
+            body: b"Not found".to_vec(),
+        }
+    }
+}
+
+type MockHttpHandler = dyn Fn(&MockHttpRequest) -> MockHttpResponse + Send + Sync;
+
+/// Minimal HTTP/1.1 server answering with responses of handlers, used instead of real providers
+/// such as MsPresidio and OpenAI. Every connection is closed after a response.
+pub struct MockHttpServer {
+    url: String,
+    requests: Arc<Mutex<Vec<MockHttpRequest>>>,
+    server_task: tokio::task::JoinHandle<()>,
+}
+
+impl MockHttpServer {
+    pub async fn start<F>(handler: F) -> AppResult<Self>
+    where
+        F: Fn(&MockHttpRequest) -> MockHttpResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<MockHttpHandler> = Arc::new(handler);
+        let server_requests = requests.clone();
+        let server_task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let requests = server_requests.clone();
+                tokio::spawn(async move {
+                    if let Some(request) = Self::read_request(stream, handler.as_ref()).await {
+                        requests.lock().unwrap().push(request);
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            url,
+            requests,
+            server_task,
+        })
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.url, path)
+    }
+
+    pub fn requests(&self) -> Vec<MockHttpRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    async fn read_request(
+        mut stream: TcpStream,
+        handler: &MockHttpHandler,
+    ) -> Option<MockHttpRequest> {
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 8192];
+        let headers_end = loop {
+            let read = stream.read(&mut chunk).await.ok()?;
+            if read == 0 {
+                return None;
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+            if let Some(position) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                break position + 4;
+            }
+        };
+        let headers = String::from_utf8_lossy(&buffer[..headers_end]).to_string();
+        let mut request_line = headers.lines().next()?.split_whitespace();
+        let method = request_line.next()?.to_string();
+        let path = request_line.next()?.to_string();
+        let content_length = headers
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        while buffer.len() < headers_end + content_length {
+            let read = stream.read(&mut chunk).await.ok()?;
+            if read == 0 {
+                break;
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+        }
+        let request = MockHttpRequest {
+            method,
+            path,
+            body: buffer[headers_end..].to_vec(),
+        };
+        let response = handler(&request);
+        let response_head = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.status,
+            response.content_type,
+            response.body.len()
+        );
+        stream.write_all(response_head.as_bytes()).await.ok()?;
+        stream.write_all(&response.body).await.ok()?;
+        stream.shutdown().await.ok()?;
+        Some(request)
+    }
+}
+
+impl Drop for MockHttpServer {
+    fn drop(&mut self) {
+        self.server_task.abort();
+    }
+}
+
+#[derive(Parser, Debug)]
+struct TestRedacterArgs {
+    #[command(flatten)]
+    redacter_args: RedacterArgs,
+}
+
+/// Redacter options parsed from the same arguments as the CLI such as `["-d", "ms-presidio"]`
+pub fn redacter_options(args: &[&str]) -> AppResult<RedacterOptions> {
+    let test_args =
+        TestRedacterArgs::try_parse_from(std::iter::once("redacter").chain(args.iter().copied()))
+            .expect("Invalid redacter arguments");
+    test_args.redacter_args.try_into()
+}
+
+pub fn copy_options(file_system_options: FileSystemOptions) -> CopyCommandOptions {
+    CopyCommandOptions {
+        prefetch_files: 0,
+        file_system_options,
+        reporter_options: AppReporterOptions {
+            quiet: true,
+            progress: false,
+        },
+        ..CopyCommandOptions::default()
+    }
+}
+
+pub async fn copy(
+    sources: &[String],
+    destination: &str,
+    file_system_options: FileSystemOptions,
+    redacter_options: Option<RedacterOptions>,
+) -> AppResult<CopyCommandResult> {
+    copy_with_options(
+        sources,
+        destination,
+        copy_options(file_system_options),
+        redacter_options,
+    )
+    .await
+}
+
+pub async fn copy_with_options(
+    sources: &[String],
+    destination: &str,
+    options: CopyCommandOptions,
+    redacter_options: Option<RedacterOptions>,
+) -> AppResult<CopyCommandResult> {
+    let term = Term::stdout();
+    command_copy(&term, sources, destination, options, redacter_options).await
+}
+
+/// Directory with sample files of different content types to copy
+pub fn sample_files_dir() -> AppResult<tempfile::TempDir> {
+    let samples_dir = tempfile::tempdir()?;
+    std::fs::write(
+        samples_dir.path().join("note.txt"),
+        "Hello, my name is John Doe and my phone is 555-1234.",
+    )?;
+    std::fs::write(
+        samples_dir.path().join("customers.csv"),
+        "id,name,city\n1,John Doe,London\n2,Jane Roe,Paris\n",
+    )?;
+    std::fs::write(
+        samples_dir.path().join("config.json"),
+        "{\"owner\": \"John Doe\", \"retries\": 3}",
+    )?;
+    std::fs::copy(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-fixtures/media/form-example.png"),
+        samples_dir.path().join("form-example.png"),
+    )?;
+    Ok(samples_dir)
+}
+
+/// Directory path with a trailing slash, so it's copied as a directory
+pub fn dir_path(dir: &std::path::Path) -> String {
+    format!("{}/", dir.display())
+}
@@ -0,0 +1,368 @@
+mod common;
+
+use common::*;
+use redacter::commands::{CopyCollisionMode, CopyCommandOptions, CopyCommandResult};
+use redacter::file_systems::FileSystemOptions;
+use redacter::AppResult;
+
+/// Presidio analyzer finding only the `John Doe` person
+fn ms_presidio_analyze(request: &MockHttpRequest) -> MockHttpResponse {
+    let text = request.json()["text"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let entities: Vec<serde_json::Value> = text
+        .match_indices("John Doe")
+        .map(|(start, name)| {
+            serde_json::json!({
+                "entity_type": "PERSON",
+                "start": start,
+                "end": start + name.len(),
+                "score": 0.85
+            })
+        })
+        .collect();
+    MockHttpResponse::json(serde_json::Value::Array(entities))
+}
+
+/// OpenAI chat completions streaming user texts with `John Doe` replaced
+fn open_ai_chat_completions(request: &MockHttpRequest) -> MockHttpResponse {
+    let request_json = request.json();
+    let user_text = request_json["messages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|message| message["role"] == "user")
+        .and_then(|message| message["content"][0]["text"].as_str())
+        .unwrap_or_default()
+        .replace("John Doe", "[REDACTED]");
+    let (first_half, second_half) = user_text.split_at(user_text.len() / 2);
+    MockHttpResponse::event_stream(vec![
+        serde_json::json!({ "choices": [{ "delta": { "content": first_half } }] }),
+        serde_json::json!({ "choices": [{ "delta": { "content": second_half } }] }),
+        serde_json::json!({
+            "choices": [],
+            "usage": { "prompt_tokens": 100, "completion_tokens": 20 }
+        }),
+    ])
+}
+
+#[tokio::test]
+async fn copy_with_mocked_ms_presidio_test() -> AppResult<()> {
+    let presidio = MockHttpServer::start(|request| match request.path.as_str() {
+        "/analyze" => ms_presidio_analyze(request),
+        // Images are returned as they are, as if nothing was found
+        "/redact" => MockHttpResponse {
+            status: 200,
+            content_type: "image/png".to_string(),
+            body: std::fs::read(
+                std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("test-fixtures/media/form-example.png"),
+            )
+            .unwrap_or_default(),
+        },
+        _ => MockHttpResponse::not_found(),
+    })
+    .await?;
+    let samples_dir = sample_files_dir()?;
+    let destination_dir = tempfile::tempdir()?;
+
+    let result = copy(
+        &[dir_path(samples_dir.path())],
+        &dir_path(destination_dir.path()),
+        FileSystemOptions::default(),
+        Some(redacter_options(&[
+            "-d",
+            "ms-presidio",
+            "--ms-presidio-text-analyze-url",
+            &presidio.url("/analyze"),
+            "--ms-presidio-image-redact-url",
+            &presidio.url("/redact"),
+        ])?),
+    )
+    .await?;
+
+    assert_eq!(result.files_copied, 4);
+    assert_eq!(result.files_redacted, 4);
+    assert_eq!(result.files_failed, 0);
+    assert_eq!(
+        std::fs::read_to_string(destination_dir.path().join("note.txt"))?,
+        "Hello, my name is XXXXXXXX and my phone is 555-1234."
+    );
+    assert_eq!(
+        std::fs::read_to_string(destination_dir.path().join("customers.csv"))?,
+        "id,name,city\n1,XXXXXXXX,London\n2,Jane Roe,Paris\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(destination_dir.path().join("config.json"))?,
+        "{\"owner\": \"XXXXXXXX\", \"retries\": 3}"
+    );
+    assert!(destination_dir.path().join("form-example.png").exists());
+    let note_result = result
+        .files
+        .iter()
+        .find(|file| file.source.ends_with("note.txt"))
+        .expect("Result of note.txt");
+    assert_eq!(note_result.redactions, 1);
+    assert_eq!(note_result.redacters, vec!["ms-presidio".to_string()]);
+    assert_eq!(note_result.destination_size, Some(52));
+    let json_result = serde_json::to_value(&result)?;
+    assert_eq!(
+        json_result["schema_version"],
+        CopyCommandResult::SCHEMA_VERSION
+    );
+    assert!(json_result["files"][0]["duration_ms"].is_u64());
+    let analyze_requests: Vec<MockHttpRequest> = presidio
+        .requests()
+        .into_iter()
+        .filter(|request| request.path == "/analyze")
+        .collect();
+    assert_eq!(analyze_requests.len(), 3);
+    assert!(analyze_requests
+        .iter()
+        .all(|request| request.method == "POST" && request.json()["language"] == "en"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_with_mocked_open_ai_test() -> AppResult<()> {
+    let open_ai = MockHttpServer::start(|request| match request.path.as_str() {
+        "/v1/chat/completions" => open_ai_chat_completions(request),
+        _ => MockHttpResponse::not_found(),
+    })
+    .await?;
+    let samples_dir = sample_files_dir()?;
+    let destination_dir = tempfile::tempdir()?;
+
+    let result = copy(
+        &[format!("{}", samples_dir.path().join("note.txt").display())],
+        &dir_path(destination_dir.path()),
+        FileSystemOptions::default(),
+        Some(redacter_options(&[
+            "-d",
+            "open-ai-llm",
+            "--open-ai-api-key",
+            "test-key",
+            "--open-ai-api-url",
+            &open_ai.url("/v1"),
+        ])?),
+    )
+    .await?;
+
+    assert_eq!(result.files_redacted, 1);
+    assert_eq!(
+        std::fs::read_to_string(destination_dir.path().join("note.txt"))?,
+        "Hello, my name is [REDACTED] and my phone is 555-1234."
+    );
+    let requests = open_ai.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].json()["model"], "gpt-4o-mini");
+    assert_eq!(requests[0].json()["stream"], true);
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_with_failing_provider_test() -> AppResult<()> {
+    let presidio = MockHttpServer::start(|_| MockHttpResponse {
+        status: 503,
+        content_type: mime::TEXT_PLAIN.to_string(),
+        body: b"Service unavailable".to_vec(),
+    })
+    .await?;
+    let samples_dir = sample_files_dir()?;
+    let destination_dir = tempfile::tempdir()?;
+
+    let result = copy(
+        &[format!("{}", samples_dir.path().join("note.txt").display())],
+        &dir_path(destination_dir.path()),
+        FileSystemOptions::default(),
+        Some(redacter_options(&[
+            "-d",
+            "ms-presidio",
+            "--ms-presidio-text-analyze-url",
+            &presidio.url("/analyze"),
+        ])?),
+    )
+    .await?;
+
+    assert_eq!(result.files_failed, 1);
+    assert_eq!(result.files_copied, 0);
+    assert!(!destination_dir.path().join("note.txt").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_with_mock_redacter_test() -> AppResult<()> {
+    let samples_dir = sample_files_dir()?;
+    let destination_dir = tempfile::tempdir()?;
+
+    let result = copy(
+        &[dir_path(samples_dir.path())],
+        &dir_path(destination_dir.path()),
+        FileSystemOptions::default(),
+        Some(redacter_options(&[
+            "-d",
+            "mock",
+            "--mock-pattern",
+            r"\d{3}-\d{4}",
+        ])?),
+    )
+    .await?;
+
+    assert_eq!(result.files_redacted, 3);
+    // Images aren't supported without OCR, so they're skipped
+    assert_eq!(result.files_skipped, 1);
+    assert_eq!(
+        std::fs::read_to_string(destination_dir.path().join("note.txt"))?,
+        "Hello, my name is John Doe and my phone is XXXXXXXX."
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_on_conflict_test() -> AppResult<()> {
+    let samples_dir = sample_files_dir()?;
+    let destination_dir = tempfile::tempdir()?;
+    std::fs::write(destination_dir.path().join("note.txt"), "Existing note")?;
+    let sources = [format!("{}", samples_dir.path().join("note.txt").display())];
+    let destination = dir_path(destination_dir.path());
+    let copy_on_conflict = |on_conflict: CopyCollisionMode| {
+        copy_with_options(
+            &sources,
+            &destination,
+            CopyCommandOptions {
+                on_conflict,
+                ..copy_options(FileSystemOptions::default())
+            },
+            None,
+        )
+    };
+
+    let result = copy_on_conflict(CopyCollisionMode::Skip).await?;
+    assert_eq!(result.files_skipped, 1);
+    assert_eq!(
+        std::fs::read_to_string(destination_dir.path().join("note.txt"))?,
+        "Existing note"
+    );
+
+    let result = copy_on_conflict(CopyCollisionMode::Fail).await?;
+    assert_eq!(result.files_failed, 1);
+    assert_eq!(
+        result.files[0].error_code.as_deref(),
+        Some("file_system_error")
+    );
+
+    copy_on_conflict(CopyCollisionMode::Rename).await?;
+    copy_on_conflict(CopyCollisionMode::Rename).await?;
+    assert_eq!(
+        std::fs::read_to_string(destination_dir.path().join("note.txt"))?,
+        "Existing note"
+    );
+    assert!(destination_dir.path().join("note-1.txt").exists());
+    assert!(destination_dir.path().join("note-2.txt").exists());
+
+    let result = copy_on_conflict(CopyCollisionMode::Overwrite).await?;
+    assert_eq!(result.files_copied, 1);
+    assert_eq!(
+        std::fs::read_to_string(destination_dir.path().join("note.txt"))?,
+        "Hello, my name is John Doe and my phone is 555-1234."
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_upload_error_without_retries_test() -> AppResult<()> {
+    let samples_dir = sample_files_dir()?;
+    let destination_dir = tempfile::tempdir()?;
+    // A directory in place of the destination file fails the upload
+    std::fs::create_dir(destination_dir.path().join("note.txt"))?;
+
+    let result = copy_with_options(
+        &[dir_path(samples_dir.path())],
+        &dir_path(destination_dir.path()),
+        CopyCommandOptions {
+            file_retries: 0,
+            on_conflict: CopyCollisionMode::Overwrite,
+            ..copy_options(FileSystemOptions::default())
+        },
+        None,
+    )
+    .await?;
+
+    // Other files are copied, and the failed file is reported instead of aborting the run
+    assert_eq!(result.files_failed, 1);
+    assert!(result.files_copied > 0);
+    assert!(result
+        .files
+        .iter()
+        .any(|file| file.error_code.as_deref() == Some("io_error")));
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_already_redacted_test() -> AppResult<()> {
+    let samples_dir = sample_files_dir()?;
+    std::fs::write(samples_dir.path().join("empty.txt"), "")?;
+    let redacted_dir = tempfile::tempdir()?;
+    let result = copy(
+        &[dir_path(samples_dir.path())],
+        &dir_path(redacted_dir.path()),
+        FileSystemOptions::default(),
+        Some(redacter_options(&[
+            "-d",
+            "mock",
+            "--mock-pattern",
+            "John Doe",
+        ])?),
+    )
+    .await?;
+    assert_eq!(result.files_redacted, 3);
+    let empty_result = result
+        .files
+        .iter()
+        .find(|file| file.source.ends_with("empty.txt"))
+        .expect("Result of empty.txt");
+    assert_eq!(empty_result.result.to_string(), "copied");
+    assert_eq!(
+        empty_result.reason.as_deref(),
+        Some("Empty file has nothing to redact")
+    );
+
+    // Files redacted by the previous run aren't redacted again unless forced
+    let destination_dir = tempfile::tempdir()?;
+    let sources = [format!(
+        "{}",
+        redacted_dir.path().join("note.txt").display()
+    )];
+    let destination = dir_path(destination_dir.path());
+    let copy_redacted = |force: bool| {
+        copy_with_options(
+            &sources,
+            &destination,
+            CopyCommandOptions {
+                force,
+                ..copy_options(FileSystemOptions::default())
+            },
+            Some(
+                redacter_options(&["-d", "mock", "--mock-pattern", "Hello"])
+                    .expect("Invalid redacter options"),
+            ),
+        )
+    };
+    let result = copy_redacted(false).await?;
+    assert_eq!(result.files_copied, 1);
+    assert_eq!(result.files_redacted, 0);
+    assert_eq!(result.files[0].reason.as_deref(), Some("Already redacted"));
+    assert_eq!(
+        std::fs::read_to_string(destination_dir.path().join("note.txt"))?,
+        "Hello, my name is XXXX XXX and my phone is 555-1234."
+    );
+
+    let result = copy_redacted(true).await?;
+    assert_eq!(result.files_redacted, 1);
+    assert_eq!(
+        std::fs::read_to_string(destination_dir.path().join("note.txt"))?,
+        "XXXXX, my name is XXXX XXX and my phone is 555-1234."
+    );
+    Ok(())
+}
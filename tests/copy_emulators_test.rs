@@ -0,0 +1,111 @@
+//! Copies between local directories and storage emulators: fake-gcs-server for GCS and MinIO for S3.
+//! Emulators are expected to be running, see `ci-emulators` job in `.github/workflows/tests.yml`:
+//! - `STORAGE_EMULATOR_HOST` such as `http://localhost:4443` for fake-gcs-server;
+//! - `TEST_S3_ENDPOINT_URL` such as `http://localhost:9000` for MinIO with
+//!   `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_REGION` of MinIO.
+
+#![cfg(feature = "ci-emulators")]
+
+mod common;
+
+use common::*;
+use redacter::file_systems::FileSystemOptions;
+use redacter::{config_env_var, AppResult};
+
+const TEST_BUCKET_NAME: &str = "redacter-test";
+
+/// Copies sample files to the destination with the mock redacter, then back to a local directory
+async fn copy_roundtrip(remote_dir: &str, file_system_options: FileSystemOptions) -> AppResult<()> {
+    let samples_dir = sample_files_dir()?;
+    let result = copy(
+        &[dir_path(samples_dir.path())],
+        remote_dir,
+        file_system_options.clone(),
+        Some(redacter_options(&[
+            "-d",
+            "mock",
+            "--mock-pattern",
+            "John Doe",
+        ])?),
+    )
+    .await?;
+    assert_eq!(result.files_redacted, 3);
+    assert_eq!(result.files_failed, 0);
+
+    let downloaded_dir = tempfile::tempdir()?;
+    let result = copy(
+        &[remote_dir.to_string()],
+        &dir_path(downloaded_dir.path()),
+        file_system_options,
+        None,
+    )
+    .await?;
+    assert_eq!(result.files_copied, 3);
+    assert_eq!(
+        std::fs::read_to_string(downloaded_dir.path().join("note.txt"))?,
+        "Hello, my name is XXXX XXX and my phone is 555-1234."
+    );
+    assert_eq!(
+        std::fs::read_to_string(downloaded_dir.path().join("customers.csv"))?,
+        "id,name,city\n1,XXXX XXX,London\n2,Jane Roe,Paris\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_gcs_emulator_test() -> AppResult<()> {
+    let emulator_host = config_env_var("STORAGE_EMULATOR_HOST").expect("Emulator host is required");
+    let emulator_url = if emulator_host.starts_with("http") {
+        emulator_host
+    } else {
+        format!("http://{}", emulator_host)
+    };
+    // Buckets that already exist are reported as conflicts
+    let response = reqwest::Client::new()
+        .post(format!("{}/storage/v1/b?project=test", emulator_url))
+        .json(&serde_json::json!({ "name": TEST_BUCKET_NAME }))
+        .send()
+        .await?;
+    assert!(response.status().is_success() || response.status() == reqwest::StatusCode::CONFLICT);
+
+    copy_roundtrip(
+        &format!("gs://{}/{}/", TEST_BUCKET_NAME, rand::random::<u64>()),
+        FileSystemOptions::default(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn copy_s3_emulator_test() -> AppResult<()> {
+    let endpoint_url: url::Url = config_env_var("TEST_S3_ENDPOINT_URL")
+        .expect("S3 endpoint URL is required")
+        .parse()
+        .expect("Invalid TEST_S3_ENDPOINT_URL");
+    let shared_config = aws_config::from_env().load().await;
+    let s3_client = aws_sdk_s3::Client::from_conf(
+        aws_sdk_s3::config::Builder::from(&shared_config)
+            .endpoint_url(endpoint_url.as_str().trim_end_matches('/'))
+            .force_path_style(true)
+            .build(),
+    );
+    if let Err(err) = s3_client
+        .create_bucket()
+        .bucket(TEST_BUCKET_NAME)
+        .send()
+        .await
+    {
+        assert!(err
+            .as_service_error()
+            .is_some_and(|err| err.is_bucket_already_owned_by_you()));
+    }
+
+    copy_roundtrip(
+        &format!("s3://{}/{}/", TEST_BUCKET_NAME, rand::random::<u64>()),
+        FileSystemOptions {
+            s3_endpoint_url: Some(endpoint_url),
+            s3_force_path_style: true,
+            ..FileSystemOptions::default()
+        },
+    )
+    .await
+}